@@ -0,0 +1,69 @@
+// ============================================================
+// Velocity arrows (A, --arrows): one arrow per point of a sparse lattice,
+// pointing along the flow there and growing with its speed. Each instance
+// is nine vertices, a shaft quad and a head triangle, placed entirely in
+// the vertex shader from the velocity texture.
+// ============================================================
+struct ArrowParams {
+    grid_size: u32,
+    // Arrows per side of the grid
+    per_side: u32,
+    // Physical cell size, see SimParams in fluid.wgsl
+    cell_size: vec2<f32>,
+    // Part of the domain drawn, (x, y, w, h) in texture coordinates
+    viewport: vec4<f32>,
+    // Speed (x-cells/s) drawn at full length
+    full_speed: f32,
+    _pad0: f32,
+    _pad1: f32,
+    _pad2: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: ArrowParams;
+@group(0) @binding(1) var velocity: texture_2d<f32>;
+@group(0) @binding(2) var obstacles: texture_2d<f32>;
+
+// Corners of the arrow along x from its tail (0) to its tip (1), with
+// y across it: z picks the shaft's half width (0) or the head's (1), w
+// whether x is measured back from the tip by the head's length
+fn corner(vid: u32) -> vec4<f32> {
+    var corners = array<vec4<f32>, 9>(
+        vec4<f32>(0.0, -1.0, 0.0, 0.0), vec4<f32>(1.0, -1.0, 0.0, 1.0), vec4<f32>(1.0, 1.0, 0.0, 1.0),
+        vec4<f32>(0.0, -1.0, 0.0, 0.0), vec4<f32>(1.0, 1.0, 0.0, 1.0), vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(1.0, -1.0, 1.0, 1.0), vec4<f32>(1.0, 0.0, 1.0, 0.0), vec4<f32>(1.0, 1.0, 1.0, 1.0),
+    );
+    return corners[vid];
+}
+
+@vertex
+fn vs_arrow(@builtin(vertex_index) vid: u32, @builtin(instance_index) iid: u32) -> @builtin(position) vec4<f32> {
+    // Lattice point in grid cells
+    let spacing = f32(params.grid_size) / f32(params.per_side);
+    let center = (vec2<f32>(f32(iid % params.per_side), f32(iid / params.per_side)) + 0.5) * spacing;
+    let cell = vec2<i32>(center);
+    let v = textureLoad(velocity, cell, 0).xy;
+    let speed = length(v);
+    if (speed < 1e-3 || textureLoad(obstacles, cell, 0).x > 0.5) {
+        // Nothing to point along: a degenerate triangle draws nothing
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    // Lengths in x-cells, in which the screen is isotropic. The square
+    // root keeps slow flow visible next to fast.
+    let length_max = 0.9 * spacing * min(params.cell_size.x, params.cell_size.y);
+    let len = length_max * sqrt(min(speed / params.full_speed, 1.0));
+    let shaft = 0.05 * length_max;
+    let head = min(0.4 * len, 0.3 * length_max);
+    let c = corner(vid);
+    let along = c.x * len - c.w * head - 0.5 * len;
+    let across = c.y * select(shaft, 3.0 * shaft, c.z > 0.5);
+    let dir = v / speed;
+    let offset = dir * along + vec2<f32>(-dir.y, dir.x) * across;
+    let p = center + offset / params.cell_size;
+    let uv = (p / f32(params.grid_size) - params.viewport.xy) / params.viewport.zw;
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_arrow() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 1.0, 1.0, 0.9);
+}
@@ -0,0 +1,25 @@
+// ============================================================
+// CFL check: the largest |vx| and |vy| on the grid, for the
+// timestep limit in src/cfl.rs
+// ============================================================
+@group(0) @binding(0) var velocity: texture_2d<f32>;
+// Bits of the two maxima. Non-negative floats order the same as their bit
+// patterns, so atomicMax on the bits is a float max.
+@group(0) @binding(1) var<storage, read_write> result: array<atomic<u32>, 2>;
+
+var<workgroup> local_max: array<atomic<u32>, 2>;
+
+@compute @workgroup_size(8, 8)
+fn max_velocity(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(local_invocation_index) index: u32) {
+    if (all(gid.xy < textureDimensions(velocity))) {
+        let v = abs(textureLoad(velocity, vec2<i32>(gid.xy), 0).xy);
+        atomicMax(&local_max[0], bitcast<u32>(v.x));
+        atomicMax(&local_max[1], bitcast<u32>(v.y));
+    }
+    // One global atomic per workgroup instead of one per cell
+    workgroupBarrier();
+    if (index == 0u) {
+        atomicMax(&result[0], atomicLoad(&local_max[0]));
+        atomicMax(&result[1], atomicLoad(&local_max[1]));
+    }
+}
@@ -0,0 +1,306 @@
+// ============================================================
+// Preconditioned conjugate gradient for the pressure Poisson equation
+// ============================================================
+// Solves A x = b with A = -lap (the same clamped/Neumann, wrapping or open
+// Laplacian as fluid.wgsl and multigrid.wgsl) and b = -divergence. Vectors live in f32
+// storage buffers so the solve isn't limited by the f16 textures; only the
+// final pressure is written back to `pressure`.
+//
+// A is singular (constants are in its null space) and b generally has a
+// nonzero mean, so the solver works on the mean-free part of b, and
+// rounding drift in the mean of r is projected out again each iteration.
+//
+// Dot products are reduced in two steps: each 8x8 workgroup writes a partial
+// sum, then a single 256-thread workgroup adds the partials into `state`.
+// Every kernel checks `state.done`, so once the tolerance is met the rest of
+// the recorded iterations cost a dispatch each and do no work.
+struct CgParams {
+    size: u32,
+    // Workgroups per row in the 8x8 kernels, for indexing `partials`
+    groups: u32,
+    // Stop once |r| <= tolerance * |b|
+    tolerance: f32,
+    // SimParams::boundary: 0 clamps at the edges, 1 wraps, 2 holds 0 outside
+    boundary: u32,
+    // (1/dx², 1/dy²)
+    inv_h2: vec2<f32>,
+}
+
+// `done` must stay first: multigrid.wgsl reads it as its gate.
+struct CgState {
+    done: u32,
+    iterations: u32,
+    // Mean of r, projected out before preconditioning
+    mean: f32,
+    bb: f32,
+    rz: f32,
+    alpha: f32,
+    beta: f32,
+    rr: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: CgParams;
+@group(0) @binding(1) var pressure: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(2) var divergence_tex: texture_storage_2d<rgba16float, read_write>;
+// Preconditioner input / output: level 0 rhs and pressure of the V-cycle
+@group(0) @binding(3) var precond_rhs: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(4) var precond_out: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(5) var<storage, read_write> x: array<f32>;
+@group(0) @binding(6) var<storage, read_write> r: array<f32>;
+@group(0) @binding(7) var<storage, read_write> d: array<f32>;
+@group(0) @binding(8) var<storage, read_write> ad: array<f32>;
+@group(0) @binding(9) var<storage, read_write> partials: array<vec2<f32>>;
+@group(0) @binding(10) var<storage, read_write> state: CgState;
+
+var<workgroup> sums: array<vec2<f32>, 256>;
+
+// ============================================================
+// Helpers
+// ============================================================
+fn in_grid(gid: vec3<u32>) -> bool {
+    return gid.x < params.size && gid.y < params.size;
+}
+
+fn index(c: vec2<i32>) -> u32 {
+    return u32(c.y) * params.size + u32(c.x);
+}
+
+// Sum `v` over an 8x8 workgroup into its slot in `partials`. Must be
+// reached by every invocation.
+fn write_partial(lid: u32, wid: vec3<u32>, v: vec2<f32>) {
+    sums[lid] = v;
+    workgroupBarrier();
+    for (var s = 32u; s > 0u; s = s >> 1u) {
+        if (lid < s) { sums[lid] += sums[lid + s]; }
+        workgroupBarrier();
+    }
+    if (lid == 0u) { partials[wid.y * params.groups + wid.x] = sums[0]; }
+}
+
+// Total of all partials, for a single 256-thread workgroup. The result is
+// valid in every invocation.
+fn sum_partials(lid: u32) -> vec2<f32> {
+    var acc = vec2<f32>(0.0);
+    for (var i = lid; i < arrayLength(&partials); i += 256u) {
+        acc += partials[i];
+    }
+    sums[lid] = acc;
+    workgroupBarrier();
+    for (var s = 128u; s > 0u; s = s >> 1u) {
+        if (lid < s) { sums[lid] += sums[lid + s]; }
+        workgroupBarrier();
+    }
+    return sums[0];
+}
+
+fn cell_count() -> f32 {
+    return f32(params.size * params.size);
+}
+
+// Mean of a field summing to `total`, as projected out of r. Open edges pin
+// the pressure outside to 0, which takes constants out of A's null space,
+// so nothing is projected out there.
+fn mean_of(total: f32) -> f32 {
+    if (params.boundary == 2u) { return 0.0; }
+    return total / cell_count();
+}
+
+// d at neighbour n: off the grid, clamped back onto the edge (where it
+// matches the center and drops out of A), wrapped to the other side, or 0
+// past an open edge.
+fn load_d(n: vec2<i32>) -> f32 {
+    let size = vec2<i32>(i32(params.size));
+    if (params.boundary == 1u) { return d[index((n % size + size) % size)]; }
+    if (params.boundary == 2u && (any(n < vec2<i32>(0)) || any(n >= size))) { return 0.0; }
+    return d[index(clamp(n, vec2<i32>(0), size - 1))];
+}
+
+// A v at cell c.
+fn apply_a(c: vec2<i32>) -> f32 {
+    let v = d[index(c)];
+    let sx = 2.0 * v - load_d(c + vec2<i32>(-1, 0)) - load_d(c + vec2<i32>(1, 0));
+    let sy = 2.0 * v - load_d(c + vec2<i32>(0, -1)) - load_d(c + vec2<i32>(0, 1));
+    return sx * params.inv_h2.x + sy * params.inv_h2.y;
+}
+
+// ============================================================
+// Compute: setup
+// ============================================================
+// x = 0, r = b. Partials: (sum b, 0).
+@compute @workgroup_size(8, 8)
+fn cg_init(@builtin(global_invocation_id) gid: vec3<u32>,
+           @builtin(local_invocation_index) lid: u32,
+           @builtin(workgroup_id) wid: vec3<u32>) {
+    var v = vec2<f32>(0.0);
+    if (in_grid(gid)) {
+        let c = vec2<i32>(gid.xy);
+        let i = index(c);
+        let b = -textureLoad(divergence_tex, c).x;
+        x[i] = 0.0;
+        r[i] = b;
+        d[i] = 0.0;
+        ad[i] = 0.0;
+        v = vec2<f32>(b, 0.0);
+    }
+    write_partial(lid, wid, v);
+}
+
+@compute @workgroup_size(256)
+fn cg_reduce_mean(@builtin(local_invocation_index) lid: u32) {
+    let total = sum_partials(lid);
+    if (lid == 0u) {
+        state.mean = mean_of(total.x);
+    }
+}
+
+// r = b - mean(b), so r stays (nearly) mean-free: A d always sums to zero.
+// Partials: (sum r, r·r).
+@compute @workgroup_size(8, 8)
+fn cg_init_residual(@builtin(global_invocation_id) gid: vec3<u32>,
+                    @builtin(local_invocation_index) lid: u32,
+                    @builtin(workgroup_id) wid: vec3<u32>) {
+    var v = vec2<f32>(0.0);
+    if (in_grid(gid)) {
+        let i = index(vec2<i32>(gid.xy));
+        let res = r[i] - state.mean;
+        r[i] = res;
+        v = vec2<f32>(res, res * res);
+    }
+    write_partial(lid, wid, v);
+}
+
+@compute @workgroup_size(256)
+fn cg_reduce_init(@builtin(local_invocation_index) lid: u32) {
+    let total = sum_partials(lid);
+    if (lid == 0u) {
+        state.mean = mean_of(total.x);
+        state.bb = total.y;
+        state.rr = total.y;
+        state.rz = 0.0;
+        state.beta = 0.0;
+        state.iterations = 0u;
+        state.done = select(0u, 1u, total.y <= 1e-12);
+    }
+}
+
+// ============================================================
+// Compute: preconditioner z = M⁻¹ r (one multigrid V-cycle)
+// ============================================================
+// The V-cycle solves lap(z) = rhs from z = 0, so rhs = -(r - mean).
+@compute @workgroup_size(8, 8)
+fn cg_precond_in(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_grid(gid) || state.done != 0u) { return; }
+    let c = vec2<i32>(gid.xy);
+    textureStore(precond_rhs, c, vec4<f32>(state.mean - r[index(c)], 0.0, 0.0, 0.0));
+    textureStore(precond_out, c, vec4<f32>(0.0));
+}
+
+// Partials: (r·z, z·Ad) for the Polak-Ribière beta, which tolerates the
+// V-cycle being only approximately symmetric.
+@compute @workgroup_size(8, 8)
+fn cg_precond_out(@builtin(global_invocation_id) gid: vec3<u32>,
+                  @builtin(local_invocation_index) lid: u32,
+                  @builtin(workgroup_id) wid: vec3<u32>) {
+    var v = vec2<f32>(0.0);
+    if (in_grid(gid) && state.done == 0u) {
+        let c = vec2<i32>(gid.xy);
+        let i = index(c);
+        let z = textureLoad(precond_out, c).x;
+        v = vec2<f32>((r[i] - state.mean) * z, z * ad[i]);
+    }
+    write_partial(lid, wid, v);
+}
+
+// beta = z·(r_new - r_old) / rz_old, with r_new - r_old = -alpha Ad.
+@compute @workgroup_size(256)
+fn cg_reduce_beta(@builtin(local_invocation_index) lid: u32) {
+    let total = sum_partials(lid);
+    if (lid == 0u && state.done == 0u) {
+        if (state.iterations == 0u) {
+            state.beta = 0.0;
+        } else {
+            state.beta = max(-state.alpha * total.y / state.rz, 0.0);
+        }
+        state.rz = total.x;
+    }
+}
+
+// d = z + beta d
+@compute @workgroup_size(8, 8)
+fn cg_update_d(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_grid(gid) || state.done != 0u) { return; }
+    let c = vec2<i32>(gid.xy);
+    let i = index(c);
+    d[i] = textureLoad(precond_out, c).x + state.beta * d[i];
+}
+
+// ============================================================
+// Compute: step along d
+// ============================================================
+// Ad = A d. Partials: (d·Ad, 0).
+@compute @workgroup_size(8, 8)
+fn cg_apply(@builtin(global_invocation_id) gid: vec3<u32>,
+            @builtin(local_invocation_index) lid: u32,
+            @builtin(workgroup_id) wid: vec3<u32>) {
+    var v = vec2<f32>(0.0);
+    if (in_grid(gid) && state.done == 0u) {
+        let c = vec2<i32>(gid.xy);
+        let i = index(c);
+        let a = apply_a(c);
+        ad[i] = a;
+        v = vec2<f32>(d[i] * a, 0.0);
+    }
+    write_partial(lid, wid, v);
+}
+
+@compute @workgroup_size(256)
+fn cg_reduce_alpha(@builtin(local_invocation_index) lid: u32) {
+    let total = sum_partials(lid);
+    if (lid == 0u && state.done == 0u) {
+        if (total.x > 0.0) {
+            state.alpha = state.rz / total.x;
+        } else {
+            // d is in the null space: nothing left to reduce
+            state.done = 1u;
+        }
+    }
+}
+
+// x += alpha d, r -= alpha Ad. Partials: (sum r, r·r).
+@compute @workgroup_size(8, 8)
+fn cg_update_x(@builtin(global_invocation_id) gid: vec3<u32>,
+               @builtin(local_invocation_index) lid: u32,
+               @builtin(workgroup_id) wid: vec3<u32>) {
+    var v = vec2<f32>(0.0);
+    if (in_grid(gid) && state.done == 0u) {
+        let i = index(vec2<i32>(gid.xy));
+        x[i] += state.alpha * d[i];
+        let res = r[i] - state.alpha * ad[i];
+        r[i] = res;
+        v = vec2<f32>(res, res * res);
+    }
+    write_partial(lid, wid, v);
+}
+
+@compute @workgroup_size(256)
+fn cg_reduce_residual(@builtin(local_invocation_index) lid: u32) {
+    let total = sum_partials(lid);
+    if (lid == 0u && state.done == 0u) {
+        let mean = mean_of(total.x);
+        state.mean = mean;
+        state.rr = max(total.y - mean * total.x, 0.0);
+        state.iterations += 1u;
+        let tol = params.tolerance;
+        if (state.rr <= tol * tol * state.bb) { state.done = 1u; }
+    }
+}
+
+// ============================================================
+// Compute: hand the result to the gradient pass
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn cg_write_pressure(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_grid(gid)) { return; }
+    let c = vec2<i32>(gid.xy);
+    textureStore(pressure, c, vec4<f32>(x[index(c)], 0.0, 0.0, 0.0));
+}
@@ -0,0 +1,128 @@
+// ============================================================
+// Render-time detail (--detail): fine noise at screen resolution, carried
+// along by the velocity field and multiplied into the dye by render.wgsl.
+// Advected noise stretches and smears, so there are two layers, each
+// regenerated once per LAYER_PERIOD half a period apart; the renderer fades
+// each layer out before its reseed and back in after.
+// ============================================================
+struct DetailParams {
+    grid_size: u32,
+    // Detail texture side, in texels
+    size: u32,
+    dt: f32,
+    // SimParams::time after this step
+    time: f32,
+    // Physical cell size, see SimParams in fluid.wgsl
+    cell_size: vec2<f32>,
+    _pad: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: DetailParams;
+@group(0) @binding(1) var velocity: texture_2d<f32>;
+// x = layer A, y = layer B, both noise in [0, 1]
+@group(0) @binding(2) var detail: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(3) var detail_tmp: texture_storage_2d<rgba16float, read_write>;
+
+// Seconds between reseeds of one layer; must match render.wgsl
+const LAYER_PERIOD: f32 = 2.0;
+// Noise feature size in detail texels
+const FEATURE: f32 = 3.0;
+
+fn hash3(c: vec3<i32>) -> f32 {
+    var h = (u32(c.x) * 0x8da6b343u) ^ (u32(c.y) * 0xd8163841u) ^ (u32(c.z) * 0xcb1ab31fu);
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    h = h ^ (h >> 16u);
+    return f32(h) / 4294967295.0;
+}
+
+// Smooth value noise in [0, 1], as in fluid.wgsl
+fn value_noise(q: vec3<f32>) -> f32 {
+    let i = vec3<i32>(floor(q));
+    let f = fract(q);
+    let u = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(hash3(i), hash3(i + vec3<i32>(1, 0, 0)), u.x);
+    let x10 = mix(hash3(i + vec3<i32>(0, 1, 0)), hash3(i + vec3<i32>(1, 1, 0)), u.x);
+    let x01 = mix(hash3(i + vec3<i32>(0, 0, 1)), hash3(i + vec3<i32>(1, 0, 1)), u.x);
+    let x11 = mix(hash3(i + vec3<i32>(0, 1, 1)), hash3(i + vec3<i32>(1, 1, 1)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+// Fresh noise for texel `p`, different for every `seed`
+fn fresh_noise(p: vec2<i32>, seed: f32) -> f32 {
+    let q = (vec2<f32>(p) + 0.5) / FEATURE;
+    return 0.65 * value_noise(vec3<f32>(q, seed)) + 0.35 * value_noise(vec3<f32>(q * 2.3, seed + 4096.0));
+}
+
+// Reseed cycle of each layer at time `t`; B runs half a period behind A
+fn cycles(t: f32) -> vec2<f32> {
+    return floor(t / LAYER_PERIOD + vec2<f32>(0.0, 0.5));
+}
+
+fn safe_load_detail(p: vec2<i32>) -> vec2<f32> {
+    let cp = clamp(p, vec2<i32>(0), vec2<i32>(i32(params.size) - 1));
+    return textureLoad(detail, cp).xy;
+}
+
+// Bilinear velocity lookup at a position in grid cells, as in particles.wgsl
+fn sample_vel(pos: vec2<f32>) -> vec2<f32> {
+    let size = i32(params.grid_size);
+    let pp = clamp(pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(f32(size) - 1.001));
+    let i = vec2<i32>(floor(pp));
+    let f = fract(pp);
+    let hi = vec2<i32>(size - 1);
+    let v00 = textureLoad(velocity, clamp(i, vec2<i32>(0), hi), 0).xy;
+    let v10 = textureLoad(velocity, clamp(i + vec2<i32>(1, 0), vec2<i32>(0), hi), 0).xy;
+    let v01 = textureLoad(velocity, clamp(i + vec2<i32>(0, 1), vec2<i32>(0), hi), 0).xy;
+    let v11 = textureLoad(velocity, clamp(i + vec2<i32>(1, 1), vec2<i32>(0), hi), 0).xy;
+    return mix(mix(v00, v10, f.x), mix(v01, v11, f.x), f.y);
+}
+
+// ============================================================
+// Compute: seed both layers (at startup)
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn seed_detail(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let p = vec2<i32>(gid.xy);
+    let c = cycles(params.time);
+    textureStore(detail, p, vec4<f32>(fresh_noise(p, 2.0 * c.x), fresh_noise(p, 2.0 * c.y + 1.0), 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: advect both layers, reseeding one whose period just ran out
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn advect_detail(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+
+    let scale = f32(params.size) / f32(params.grid_size);
+    let p = vec2<i32>(gid.xy);
+    let pos = vec2<f32>(p) + 0.5;
+    let vel = sample_vel(pos / scale) * scale;
+    let prev_pos = pos - vel * params.dt / params.cell_size;
+
+    let size = f32(params.size);
+    let pp = clamp(prev_pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(size - 1.001));
+    let i = vec2<i32>(floor(pp));
+    let f = fract(pp);
+    let d00 = safe_load_detail(i);
+    let d10 = safe_load_detail(i + vec2<i32>(1, 0));
+    let d01 = safe_load_detail(i + vec2<i32>(0, 1));
+    let d11 = safe_load_detail(i + vec2<i32>(1, 1));
+    var d = mix(mix(d00, d10, f.x), mix(d01, d11, f.x), f.y);
+
+    // A layer reseeds while the renderer has faded it out entirely
+    let now = cycles(params.time);
+    let reseed = now != cycles(params.time - params.dt);
+    if (reseed.x) { d.x = fresh_noise(p, 2.0 * now.x); }
+    if (reseed.y) { d.y = fresh_noise(p, 2.0 * now.y + 1.0); }
+    textureStore(detail_tmp, p, vec4<f32>(d, 0.0, 0.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn copy_detail(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let p = vec2<i32>(gid.xy);
+    textureStore(detail, p, textureLoad(detail_tmp, p));
+}
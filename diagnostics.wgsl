@@ -0,0 +1,137 @@
+// ============================================================
+// Flow diagnostics (E, --diagnostics): kinetic energy, enstrophy and dye
+// mass summed over the grid in two passes. partial_sums reduces each
+// 16×16 tile in workgroup memory; total sums the tiles in one workgroup.
+// ============================================================
+struct DiagParams {
+    grid_size: u32,
+    // Tiles written by partial_sums
+    tiles: u32,
+    // Physical cell size, see SimParams in fluid.wgsl
+    cell_size: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: DiagParams;
+@group(0) @binding(1) var velocity: texture_2d<f32>;
+// xzw = red, green and blue dye, y = temperature
+@group(0) @binding(2) var density: texture_2d<f32>;
+// Per tile, then the grid total: x = energy (½|v|²), y = enstrophy (½ω²),
+// z = dye mass (all three colors)
+@group(0) @binding(3) var<storage, read_write> partials: array<vec4<f32>>;
+@group(0) @binding(4) var<storage, read_write> result: vec4<f32>;
+
+const TILE: u32 = 256u;
+
+var<workgroup> sums: array<vec4<f32>, 256>;
+
+fn load_vel(c: vec2<i32>) -> vec2<f32> {
+    let last = i32(params.grid_size) - 1;
+    return textureLoad(velocity, clamp(c, vec2<i32>(0), vec2<i32>(last)), 0).xy;
+}
+
+// Tree sum of `sums`, left in sums[0]. Every invocation of the workgroup
+// must call it.
+fn reduce(index: u32) {
+    for (var stride = TILE / 2u; stride > 0u; stride /= 2u) {
+        workgroupBarrier();
+        if (index < stride) {
+            sums[index] += sums[index + stride];
+        }
+    }
+    workgroupBarrier();
+}
+
+@compute @workgroup_size(16, 16)
+fn partial_sums(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    var cell = vec4<f32>(0.0);
+    if (all(gid.xy < vec2<u32>(params.grid_size))) {
+        let c = vec2<i32>(gid.xy);
+        let v = load_vel(c);
+        // Same central difference as compute_curl, clamped at the edges
+        let h = params.cell_size;
+        let dvy_dx = load_vel(c + vec2<i32>(1, 0)).y - load_vel(c - vec2<i32>(1, 0)).y;
+        let dvx_dy = load_vel(c + vec2<i32>(0, 1)).x - load_vel(c - vec2<i32>(0, 1)).x;
+        let curl = 0.5 * (dvy_dx / h.x - dvx_dy / h.y);
+        let dye = textureLoad(density, c, 0);
+        cell = vec4<f32>(0.5 * dot(v, v), 0.5 * curl * curl, dye.x + dye.z + dye.w, 0.0);
+    }
+    sums[index] = cell;
+    reduce(index);
+    if (index == 0u) {
+        let per_row = (params.grid_size + 15u) / 16u;
+        partials[wid.y * per_row + wid.x] = sums[0];
+    }
+}
+
+@compute @workgroup_size(256)
+fn total(@builtin(local_invocation_index) index: u32) {
+    var acc = vec4<f32>(0.0);
+    for (var i = index; i < params.tiles; i += TILE) {
+        acc += partials[i];
+    }
+    sums[index] = acc;
+    reduce(index);
+    if (index == 0u) {
+        result = sums[0];
+    }
+}
+
+// ============================================================
+// Plot: the recent history as three lines over a dark panel, drawn into a
+// viewport in the window's corner
+// ============================================================
+// Numbered after the compute bindings, so both fit one module
+struct Plot {
+    // Oldest first, each series scaled to 0..1 by its peak
+    samples: array<vec4<f32>, 256>,
+    count: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(5) var<uniform> plot: Plot;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = uvs[vid];
+    return out;
+}
+
+@fragment
+fn fs_plot(in: VSOut) -> @location(0) vec4<f32> {
+    var color = vec3<f32>(0.03, 0.035, 0.05);
+    let n = max(plot.count, 2u);
+    let x = clamp(in.uv.x, 0.0, 1.0) * f32(n - 1u);
+    let i = min(u32(floor(x)), n - 2u);
+    let v = mix(plot.samples[i], plot.samples[i + 1u], x - f32(i));
+    // Values run bottom to top with a small margin
+    let y = 1.0 - in.uv.y;
+    let pixel = fwidth(y);
+    // Energy orange, enstrophy cyan, dye mass white
+    var series = array<vec3<f32>, 3>(
+        vec3<f32>(1.0, 0.55, 0.15), vec3<f32>(0.3, 0.85, 1.0), vec3<f32>(0.9, 0.9, 0.9)
+    );
+    for (var s = 0; s < 3; s++) {
+        let d = abs(0.05 + 0.9 * v[s] - y) / pixel;
+        color = mix(color, series[s], 1.0 - smoothstep(0.75, 1.75, d));
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,96 @@
+// ============================================================
+// Image distortion (--image): a picture in place of the dye, smeared by
+// the flow. Each cell remembers where in the picture its fluid came from,
+// carried along by the velocity like dye, and the renderer looks the
+// picture up there.
+// ============================================================
+struct DistortParams {
+    grid_size: u32,
+    dt: f32,
+    // Physical cell size, see SimParams in fluid.wgsl
+    cell_size: vec2<f32>,
+    // Part of the domain drawn, (x, y, w, h) in texture coordinates
+    viewport: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: DistortParams;
+
+// Compute bindings. xy = picture coordinates the cell's fluid came from,
+// minus the cell's own: 0 until the flow moves it, so half floats keep
+// still regions exact
+@group(0) @binding(1) var velocity: texture_2d<f32>;
+@group(0) @binding(2) var offsets: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(3) var offsets_tmp: texture_storage_2d<rgba16float, read_write>;
+
+// Render bindings
+@group(0) @binding(4) var offset_tex: texture_2d<f32>;
+@group(0) @binding(5) var picture: texture_2d<f32>;
+@group(0) @binding(6) var picture_sampler: sampler;
+@group(0) @binding(7) var obstacles: texture_2d<f32>;
+
+fn load_offset(p: vec2<i32>) -> vec2<f32> {
+    let cp = clamp(p, vec2<i32>(0), vec2<i32>(i32(params.grid_size) - 1));
+    return textureLoad(offsets, cp).xy;
+}
+
+// ============================================================
+// Compute: semi-Lagrangian step of the offsets, as advect_detail
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn advect_offsets(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let p = vec2<i32>(gid.xy);
+    let n = f32(params.grid_size);
+    let pos = vec2<f32>(p) + 0.5;
+    let prev_pos = pos - textureLoad(velocity, p, 0).xy * params.dt / params.cell_size;
+
+    let pp = clamp(prev_pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(n - 1.001));
+    let i = vec2<i32>(floor(pp));
+    let f = fract(pp);
+    let o00 = load_offset(i);
+    let o10 = load_offset(i + vec2<i32>(1, 0));
+    let o01 = load_offset(i + vec2<i32>(0, 1));
+    let o11 = load_offset(i + vec2<i32>(1, 1));
+    let o = mix(mix(o00, o10, f.x), mix(o01, o11, f.x), f.y);
+    // The fluid now here came from prev_pos, so it shows what was there.
+    // From past the edge it shows the picture's edge, stretched in.
+    textureStore(offsets_tmp, p, vec4<f32>(o + (prev_pos - pos) / n, 0.0, 0.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn copy_offsets(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let p = vec2<i32>(gid.xy);
+    textureStore(offsets, p, textureLoad(offsets_tmp, p));
+}
+
+// ============================================================
+// Render: the picture at each point's offset, obstacles over it
+// ============================================================
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_distort(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = params.viewport.xy + uvs[vid] * params.viewport.zw;
+    return out;
+}
+
+@fragment
+fn fs_distort(in: VSOut) -> @location(0) vec4<f32> {
+    let offset = textureSampleLevel(offset_tex, picture_sampler, in.uv, 0.0).xy;
+    let color = textureSampleLevel(picture, picture_sampler, in.uv + offset, 0.0).rgb;
+    // Solid obstacles as flat slate, as in fs_draw
+    let solid = textureSampleLevel(obstacles, picture_sampler, in.uv, 0.0).x;
+    return vec4<f32>(mix(color, vec3<f32>(0.16, 0.17, 0.2), clamp(solid, 0.0, 1.0)), 1.0);
+}
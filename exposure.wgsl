@@ -0,0 +1,31 @@
+// ============================================================
+// Long exposure (L): draws the finished exposure, the running mean of
+// every frame rendered while it was open, over the whole window
+// ============================================================
+@group(0) @binding(0) var still_tex: texture_2d<f32>;
+@group(0) @binding(1) var still_sampler: sampler;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = uvs[vid];
+    return out;
+}
+
+@fragment
+fn fs_still(in: VSOut) -> @location(0) vec4<f32> {
+    let c = textureSampleLevel(still_tex, still_sampler, in.uv, 0.0).rgb;
+    return vec4<f32>(c, 1.0);
+}
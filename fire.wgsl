@@ -0,0 +1,192 @@
+// ============================================================
+// Fire (--fire): fuel advected with the flow burns wherever the gas is hot
+// enough, turning into heat and soot. Temperature and soot live in the
+// fluid's dye texture, packed as fluid.wgsl's `scalars` (temperature in y,
+// soot as gray dye), so its buoyancy lifts the hot gas, its advection
+// carries both and its decay cools the flame; the fuel has a texture of
+// its own.
+// ============================================================
+struct FireParams {
+    grid_size: u32,
+    dt: f32,
+    // Physical cell size, as in SimParams
+    cell_size: vec2<f32>,
+    // Brush center and its movement this frame, in grid cells
+    mouse_pos: vec2<f32>,
+    mouse_delta: vec2<f32>,
+    radius: f32,
+    // 1 while the left button sprays fuel, 2 while the right one is a torch
+    mouse_mode: u32,
+    // Nonzero while the burner along the floor feeds fuel
+    burner: u32,
+    time: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: FireParams;
+// The fluid's velocity and dye textures
+@group(0) @binding(1) var velocity: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(2) var density: texture_storage_2d<rgba16float, read_write>;
+// x = fuel. advect_fuel traces it into fuel_tmp; burn writes it back.
+@group(0) @binding(3) var fuel: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(4) var fuel_tmp: texture_storage_2d<rgba16float, read_write>;
+
+// Temperature fuel starts to burn at
+const IGNITION: f32 = 0.6;
+// Share of the fuel in a hot cell that burns per second
+const BURN_RATE: f32 = 6.0;
+// Temperature and soot one unit of burnt fuel leaves behind
+const HEAT_RELEASE: f32 = 3.0;
+const SOOT_YIELD: f32 = 0.25;
+// The flame doesn't get hotter than this, however rich the mixture
+const MAX_TEMPERATURE: f32 = 6.0;
+// The burner: fuel per second per cell, the speed it leaves at (cells/s,
+// up the grid), its pilot temperature and its size as a share of the grid
+const BURNER_RATE: f32 = 3.0;
+const BURNER_SPEED: f32 = 40.0;
+const PILOT: f32 = 1.0;
+const BURNER_WIDTH: f32 = 0.2;
+const BURNER_HEIGHT: f32 = 3.0;
+// Fuel the spray adds per second at its center, and the torch's heat
+const SPRAY_RATE: f32 = 8.0;
+const TORCH: f32 = 2.0;
+// Velocity a mouse stroke adds per cell of movement, as in fluid.wgsl
+const MOUSE_IMPULSE: f32 = 50.0;
+
+const MOUSE_SPRAY: u32 = 1u;
+const MOUSE_TORCH: u32 = 2u;
+
+fn clamp_cell(c: vec2<i32>) -> vec2<i32> {
+    return clamp(c, vec2<i32>(0), vec2<i32>(i32(params.grid_size) - 1));
+}
+
+// Bilinear sample of the fuel at `p` (in cells, centers at +0.5), clamped
+// to the grid
+fn sample_fuel(p: vec2<f32>) -> f32 {
+    let n = f32(params.grid_size);
+    let q = clamp(p, vec2<f32>(0.5), vec2<f32>(n - 0.5)) - 0.5;
+    let c = vec2<i32>(floor(q));
+    let f = q - floor(q);
+    let a = textureLoad(fuel, clamp_cell(c)).x;
+    let b = textureLoad(fuel, clamp_cell(c + vec2<i32>(1, 0))).x;
+    let d = textureLoad(fuel, clamp_cell(c + vec2<i32>(0, 1))).x;
+    let e = textureLoad(fuel, clamp_cell(c + vec2<i32>(1, 1))).x;
+    return mix(mix(a, b, f.x), mix(d, e, f.x), f.y);
+}
+
+// Cheap per-cell flicker in [0, 1), so the burner doesn't feed a perfectly
+// even sheet of fuel
+fn flicker(c: vec2<i32>) -> f32 {
+    let k = dot(vec2<f32>(c), vec2<f32>(12.9898, 78.233)) + floor(params.time * 30.0) * 3.7;
+    return fract(sin(k) * 43758.547);
+}
+
+// ============================================================
+// Fuel advection: fuel -> fuel_tmp
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn advect_fuel(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let back = vec2<f32>(c) + 0.5 - params.dt * textureLoad(velocity, c).xy / params.cell_size;
+    textureStore(fuel_tmp, c, vec4<f32>(sample_fuel(back), 0.0, 0.0, 0.0));
+}
+
+// ============================================================
+// Sources and combustion: fuel_tmp -> fuel, and the dye's heat and soot
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn burn(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let center = vec2<f32>(c) + 0.5;
+    let n = f32(params.grid_size);
+    var f = textureLoad(fuel_tmp, c).x;
+    let dye = textureLoad(density, c);
+    var temperature = dye.y;
+    var soot = max(dye.x, max(dye.z, dye.w));
+    var v = textureLoad(velocity, c).xy;
+
+    if (params.burner != 0u && abs(center.x - 0.5 * n) < 0.5 * BURNER_WIDTH * n && center.y > n - BURNER_HEIGHT) {
+        // Fuel jets up off the floor, over a pilot flame that lights it
+        f += BURNER_RATE * (0.5 + flicker(c)) * params.dt;
+        temperature = max(temperature, PILOT);
+        v = vec2<f32>(v.x, -BURNER_SPEED);
+    }
+
+    let d = (center - params.mouse_pos) * params.cell_size;
+    let falloff = exp(-dot(d, d) / (params.radius * params.radius + 0.001));
+    if (params.mouse_mode == MOUSE_SPRAY) {
+        f += SPRAY_RATE * falloff * params.dt;
+        v += params.mouse_delta * params.cell_size * falloff * MOUSE_IMPULSE;
+    } else if (params.mouse_mode == MOUSE_TORCH) {
+        temperature = max(temperature, TORCH * falloff);
+    }
+
+    if (temperature > IGNITION && f > 0.0) {
+        let burnt = f * (1.0 - exp(-BURN_RATE * params.dt));
+        f -= burnt;
+        temperature = min(temperature + HEAT_RELEASE * burnt, MAX_TEMPERATURE);
+        soot += SOOT_YIELD * burnt;
+    }
+
+    textureStore(fuel, c, vec4<f32>(f, 0.0, 0.0, 0.0));
+    textureStore(density, c, vec4<f32>(soot, temperature, soot, soot));
+    textureStore(velocity, c, vec4<f32>(v, 0.0, 0.0));
+}
+
+// ============================================================
+// Render: blackbody glow over soot
+// ============================================================
+// Numbered after the compute bindings, so both fit one module
+@group(0) @binding(5) var density_tex: texture_2d<f32>;
+@group(0) @binding(6) var fuel_tex: texture_2d<f32>;
+@group(0) @binding(7) var field_sampler: sampler;
+
+// Temperature at which the glow reaches white
+const WHITE_HOT: f32 = 4.0;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = uvs[vid];
+    return out;
+}
+
+// Glow of gas at temperature t, roughly as a black body's: dull red first,
+// then orange and yellow, white at WHITE_HOT. Faint below ignition.
+fn blackbody(t: f32) -> vec3<f32> {
+    let k = clamp(t / WHITE_HOT, 0.0, 1.0);
+    let color = vec3<f32>(
+        clamp(3.0 * k, 0.0, 1.0),
+        clamp(3.0 * k - 1.0, 0.0, 1.0),
+        clamp(3.0 * k - 2.0, 0.0, 1.0),
+    );
+    return color * smoothstep(0.2 * IGNITION, 1.5 * IGNITION, t) * (1.0 + k);
+}
+
+@fragment
+fn fs_fire(in: VSOut) -> @location(0) vec4<f32> {
+    let c = textureSampleLevel(density_tex, field_sampler, in.uv, 0.0);
+    let f = textureSampleLevel(fuel_tex, field_sampler, in.uv, 0.0).x;
+    let soot = max(c.x, max(c.z, c.w));
+    let background = mix(vec3<f32>(0.01, 0.01, 0.02), vec3<f32>(0.04, 0.03, 0.03), in.uv.y);
+    // Unburnt fuel as a faint blue haze, soot as gray smoke over it, and the
+    // glow on top, dimmed where the soot is thick
+    var color = background + vec3<f32>(0.03, 0.06, 0.15) * clamp(f, 0.0, 1.0);
+    let smoke = 1.0 - exp(-2.0 * soot);
+    color = mix(color, vec3<f32>(0.16, 0.15, 0.14), smoke);
+    color += blackbody(c.y) * (1.0 - 0.5 * smoke);
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,225 @@
+// ============================================================
+// FLIP/PIC liquid (--flip): particles carry the velocity, the grid only
+// enforces incompressibility. Each step splats particle velocities onto the
+// grid, fluid.wgsl projects it (with a free surface: cells no particle
+// reaches are air at zero pressure), and the particles take back the change.
+// ============================================================
+struct FlipParams {
+    grid_size: u32,
+    count: u32,
+    dt: f32,
+    // 1 = pure FLIP (lively, noisy), 0 = pure PIC (smooth, viscous)
+    flip_ratio: f32,
+    // Physical cell size, as in SimParams
+    cell_size: vec2<f32>,
+    // Body force in cells/s², +y down
+    gravity: vec2<f32>,
+    // Brush center and its movement this frame, in grid cells
+    mouse_pos: vec2<f32>,
+    mouse_delta: vec2<f32>,
+    radius: f32,
+    mouse_down: u32,
+    // Window width over height, for round sprites
+    aspect: f32,
+    // Sprite radius in cells
+    sprite: f32,
+}
+
+struct Particle {
+    // Grid cells, (0, 0) the top-left corner
+    pos: vec2<f32>,
+    // Physical cells/s, like the fluid's velocity texture
+    vel: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: FlipParams;
+@group(0) @binding(1) var<storage, read_write> particles: array<Particle>;
+// Per cell: weighted x and y velocity sums and the weight sum, in FIXED units
+@group(0) @binding(2) var<storage, read_write> accum: array<atomic<i32>>;
+// The fluid's velocity and dye textures; the liquid fraction goes in the dye
+@group(0) @binding(3) var velocity: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(4) var density: texture_storage_2d<rgba16float, read_write>;
+// Grid velocity before projection, for the FLIP update
+@group(0) @binding(5) var<storage, read_write> before: array<vec2<f32>>;
+
+// Fixed-point scale for the atomic sums
+const FIXED: f32 = 4096.0;
+// Particles seeded per full cell; must match `PARTICLES_PER_CELL` in src/flip.rs
+const PARTICLES_PER_CELL: f32 = 4.0;
+
+fn cell_index(c: vec2<i32>) -> u32 {
+    return u32(c.y) * params.grid_size + u32(c.x);
+}
+
+fn in_grid(c: vec2<i32>) -> bool {
+    return all(c >= vec2<i32>(0)) && all(c < vec2<i32>(i32(params.grid_size)));
+}
+
+// The four cell centers around `pos` and their bilinear weights
+fn corners(pos: vec2<f32>) -> array<vec2<i32>, 4> {
+    let base = vec2<i32>(floor(pos - 0.5));
+    return array<vec2<i32>, 4>(base, base + vec2<i32>(1, 0), base + vec2<i32>(0, 1), base + vec2<i32>(1, 1));
+}
+
+fn weights(pos: vec2<f32>) -> vec4<f32> {
+    let f = fract(pos - 0.5);
+    return vec4<f32>((1.0 - f.x) * (1.0 - f.y), f.x * (1.0 - f.y), (1.0 - f.x) * f.y, f.x * f.y);
+}
+
+// ============================================================
+// Particles to grid
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn clear_grid(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let i = cell_index(vec2<i32>(gid.xy)) * 3u;
+    atomicStore(&accum[i], 0);
+    atomicStore(&accum[i + 1u], 0);
+    atomicStore(&accum[i + 2u], 0);
+}
+
+@compute @workgroup_size(64)
+fn particle_to_grid(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= params.count) { return; }
+    let p = particles[gid.x];
+    var cs = corners(p.pos);
+    let ws = weights(p.pos);
+    for (var k = 0; k < 4; k++) {
+        if (!in_grid(cs[k])) { continue; }
+        let i = cell_index(cs[k]) * 3u;
+        atomicAdd(&accum[i], i32(round(p.vel.x * ws[k] * FIXED)));
+        atomicAdd(&accum[i + 1u], i32(round(p.vel.y * ws[k] * FIXED)));
+        atomicAdd(&accum[i + 2u], i32(round(ws[k] * FIXED)));
+    }
+}
+
+// Weighted velocity and weight of a cell's splats (zero outside the grid)
+fn splatted(c: vec2<i32>) -> vec3<f32> {
+    if (!in_grid(c)) { return vec3<f32>(0.0); }
+    let i = cell_index(c) * 3u;
+    return vec3<f32>(f32(atomicLoad(&accum[i])), f32(atomicLoad(&accum[i + 1u])), f32(atomicLoad(&accum[i + 2u]))) / FIXED;
+}
+
+// Average the splats into the velocity texture. Cells without particles
+// take the mean of their neighbours that have some, so particles near the
+// surface interpolate toward the liquid's velocity rather than toward zero.
+// Forces act here, on the grid, so the projection sees them.
+@compute @workgroup_size(8, 8)
+fn grid_normalize(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let own = splatted(c);
+    var v = vec2<f32>(0.0);
+    if (own.z > 1e-4) {
+        v = own.xy / own.z;
+        v += params.gravity * params.dt;
+    } else {
+        var sum = vec2<f32>(0.0);
+        var n = 0.0;
+        for (var dy = -1; dy <= 1; dy++) {
+            for (var dx = -1; dx <= 1; dx++) {
+                let s = splatted(c + vec2<i32>(dx, dy));
+                if (s.z > 1e-4) {
+                    sum += s.xy / s.z;
+                    n += 1.0;
+                }
+            }
+        }
+        if (n > 0.0) { v = sum / n; }
+    }
+
+    if (params.mouse_down != 0u) {
+        // Dragged along at the stroke's speed, like the stirring rod
+        let d = (vec2<f32>(c) + 0.5 - params.mouse_pos) * params.cell_size;
+        let w = exp(-dot(d, d) / (params.radius * params.radius));
+        v = mix(v, params.mouse_delta * params.cell_size / params.dt, w);
+    }
+
+    before[cell_index(c)] = v;
+    textureStore(velocity, c, vec4<f32>(v, 0.0, 0.0));
+    let fraction = own.z / PARTICLES_PER_CELL;
+    textureStore(density, c, vec4<f32>(fraction, 0.0, fraction, fraction));
+}
+
+// ============================================================
+// Grid to particles
+// ============================================================
+fn sample_velocity(pos: vec2<f32>) -> vec2<f32> {
+    var cs = corners(pos);
+    let ws = weights(pos);
+    let last = vec2<i32>(i32(params.grid_size) - 1);
+    var v = vec2<f32>(0.0);
+    for (var k = 0; k < 4; k++) {
+        v += textureLoad(velocity, clamp(cs[k], vec2<i32>(0), last)).xy * ws[k];
+    }
+    return v;
+}
+
+fn sample_before(pos: vec2<f32>) -> vec2<f32> {
+    var cs = corners(pos);
+    let ws = weights(pos);
+    let last = vec2<i32>(i32(params.grid_size) - 1);
+    var v = vec2<f32>(0.0);
+    for (var k = 0; k < 4; k++) {
+        v += before[cell_index(clamp(cs[k], vec2<i32>(0), last))] * ws[k];
+    }
+    return v;
+}
+
+// Blend the grid's new velocity (PIC) with the particle's own plus the
+// grid's change (FLIP), then move the particle through the new field.
+@compute @workgroup_size(64)
+fn grid_to_particle(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= params.count) { return; }
+    var p = particles[gid.x];
+    let pic = sample_velocity(p.pos);
+    let flip = p.vel + pic - sample_before(p.pos);
+    p.vel = mix(pic, flip, params.flip_ratio);
+
+    // Midpoint step through the projected field
+    let mid = p.pos + 0.5 * params.dt * pic / params.cell_size;
+    p.pos += params.dt * sample_velocity(mid) / params.cell_size;
+
+    // The walls stop the particle and what it carries toward them
+    let lo = vec2<f32>(0.5);
+    let hi = vec2<f32>(f32(params.grid_size) - 0.5);
+    p.vel = select(p.vel, vec2<f32>(0.0), (p.pos < lo & p.vel < vec2<f32>(0.0)) | (p.pos > hi & p.vel > vec2<f32>(0.0)));
+    p.pos = clamp(p.pos, lo, hi);
+    particles[gid.x] = p;
+}
+
+// ============================================================
+// Point sprites: a round, speed-tinted disc per particle
+// ============================================================
+struct SpriteOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) speed: f32,
+}
+
+@vertex
+fn vs_sprite(@builtin(vertex_index) vid: u32, @location(0) pos: vec2<f32>, @location(1) vel: vec2<f32>) -> SpriteOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0)
+    );
+    let n = f32(params.grid_size);
+    let corner = corners[vid];
+    let center = vec2<f32>(pos.x / n * 2.0 - 1.0, 1.0 - pos.y / n * 2.0);
+    let size = vec2<f32>(1.0, params.aspect) * params.sprite / n * 2.0;
+    var out: SpriteOut;
+    out.pos = vec4<f32>(center + corner * size, 0.0, 1.0);
+    out.corner = corner;
+    out.speed = length(vel);
+    return out;
+}
+
+@fragment
+fn fs_sprite(in: SpriteOut) -> @location(0) vec4<f32> {
+    let r2 = dot(in.corner, in.corner);
+    if (r2 > 1.0) { discard; }
+    // Deep blue at rest to foamy white when fast
+    let t = clamp(in.speed / 150.0, 0.0, 1.0);
+    let color = mix(vec3<f32>(0.05, 0.25, 0.7), vec3<f32>(0.85, 0.95, 1.0), t);
+    return vec4<f32>(color * (1.0 - 0.35 * r2), 1.0);
+}
@@ -6,12 +6,88 @@ struct SimParams {
     mouse_down: u32,
     dt: f32,
     viscosity: f32,
-    dissipation: f32,
+    // Per-step decay multipliers (1 = no decay)
+    dye_decay: f32,
     add_strength: f32,
     mouse_pos: vec2<f32>,
     mouse_delta: vec2<f32>,
     radius: f32,
-    _pad: vec3<f32>,
+    emitter_count: u32,
+    vorticity: f32,
+    velocity_decay: f32,
+    // Physical cell size (dx, dy). dx is 1, so velocities are in x-cells per
+    // second; dy follows the window aspect so the domain isn't stretched.
+    cell_size: vec2<f32>,
+    // Values whose magnitude decays below these are cleared (0 = never)
+    dye_threshold: f32,
+    velocity_threshold: f32,
+    // Nonzero while the cost heat map is shown: kernels count their work
+    // into `cost`
+    instrument: u32,
+    // Dye diffusivity in cells²/s (viscosity is the velocity equivalent)
+    diffusion: f32,
+    // Buoyancy, in cells/s² per unit of dye / temperature: dye sinks,
+    // heat rises
+    smoke_weight: f32,
+    heat_lift: f32,
+    // Per-step temperature multiplier (cooling toward ambient 0)
+    temperature_decay: f32,
+    // Temperature the mouse adds per frame, like add_strength for dye
+    heat_strength: f32,
+    // Body force in cells/s², scaled by the local dye when gravity_by_dye
+    // is nonzero
+    gravity: vec2<f32>,
+    gravity_by_dye: u32,
+    // Seconds since the scene was applied
+    time: f32,
+    // Ambient wind in cells/s, gusting by wind_gust (a fraction) every
+    // wind_gust_period seconds
+    wind: vec2<f32>,
+    wind_gust: f32,
+    wind_gust_period: f32,
+    // Dye color the mouse injects, scaled by add_strength
+    dye_color: vec3<f32>,
+    // Grid edges: one of the BOUNDARY_ codes (src/scene.rs `Boundary`)
+    boundary: u32,
+    // Nonzero while the stirring rod stands in for the mouse
+    rod: u32,
+    // Peak pull toward the domain center, in cells/s² per unit of dye
+    well: f32,
+    _pad0: u32,
+    _pad1: u32,
+    // Extra downward acceleration per unit of red, green and blue dye;
+    // negative rises
+    dye_density: vec3<f32>,
+    // Curl noise stirred into sheared flow behind obstacles, in cells/s²
+    // (0 = off)
+    wake: f32,
+    // Nonzero in the FLIP liquid mode (--flip): cells the particles leave
+    // empty are air, held at zero pressure so the liquid has a free surface
+    liquid: u32,
+    // Sub-grid turbulence produced per unit of |curl| per second (0 = off)
+    subgrid: f32,
+    // Variable density: how much heavier than the clear fluid's 1 a unit of
+    // dye makes a cell, and how much lighter a unit of temperature. Both 0
+    // keeps the density uniform (Boussinesq, buoyancy only)
+    dye_mass: f32,
+    heat_expansion: f32,
+}
+
+const BOUNDARY_WALLS: u32 = 0u;
+const BOUNDARY_WRAP: u32 = 1u;
+const BOUNDARY_OUTFLOW: u32 = 2u;
+
+// Continuous scene source: steers velocity toward `velocity` and adds
+// `color` dye at `density` and heat at `temperature` per second, with a
+// Gaussian falloff of `radius` cells.
+struct Emitter {
+    pos: vec2<f32>,
+    velocity: vec2<f32>,
+    color: vec3<f32>,
+    radius: f32,
+    density: f32,
+    temperature: f32,
+    _pad: vec2<f32>,
 }
 
 // ============================================================
@@ -20,11 +96,22 @@ struct SimParams {
 @group(0) @binding(0) var<uniform> params: SimParams;
 @group(0) @binding(1) var velocity: texture_storage_2d<rgba16float, read_write>;
 @group(0) @binding(2) var velocity_tmp: texture_storage_2d<rgba16float, read_write>;
+// Scalars: xzw = red, green and blue dye, y = temperature. Every storage
+// texture slot is taken, so temperature shares the dye textures instead of
+// getting its own. See `scalars` / `dye_rgb`.
 @group(0) @binding(3) var density: texture_storage_2d<rgba16float, read_write>;
 @group(0) @binding(4) var density_tmp: texture_storage_2d<rgba16float, read_write>;
 @group(0) @binding(5) var pressure: texture_storage_2d<rgba16float, read_write>;
 @group(0) @binding(6) var pressure_tmp: texture_storage_2d<rgba16float, read_write>;
 @group(0) @binding(7) var divergence_tex: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(8) var<storage, read> emitters: array<Emitter>;
+// r = solid mask, g = signed distance, ba = solid velocity in grid cells/s
+@group(0) @binding(9) var obstacles: texture_2d<f32>;
+// x = curl, y = sub-grid turbulence, z = last step's sub-grid turbulence
+@group(0) @binding(10) var curl_tex: texture_storage_2d<rgba16float, read_write>;
+// Per-cell work counters (grid_size² entries) plus the frame's maximum in
+// the last slot. Cleared each frame while instrumenting.
+@group(0) @binding(11) var<storage, read_write> cost: array<atomic<u32>>;
 
 // ============================================================
 // Render bindings — group 1 (used only by vertex/fragment)
@@ -39,97 +126,578 @@ fn in_bounds(gid: vec3<u32>) -> bool {
     return gid.x < params.grid_size && gid.y < params.grid_size;
 }
 
-fn safe_load_vel(p: vec2<i32>) -> vec2<f32> {
+fn wraps() -> bool {
+    return params.boundary == BOUNDARY_WRAP;
+}
+
+// The cell a tap at p reads. Off the grid, taps clamp onto the edge (walls,
+// and velocity at open edges) or come from the opposite side (wrap).
+fn edge_cell(p: vec2<i32>) -> vec2<i32> {
+    let size = vec2<i32>(i32(params.grid_size));
+    if (wraps()) { return (p % size + size) % size; }
+    return clamp(p, vec2<i32>(0), size - 1);
+}
+
+// Past an open edge lies still, clear ambient fluid: dye, temperature and
+// pressure read 0 there, so what flows out is gone. Velocity still clamps,
+// which lets flow leave at whatever speed it reaches the edge.
+fn outside_open_edge(p: vec2<i32>) -> bool {
     let size = i32(params.grid_size);
-    let cp = clamp(p, vec2<i32>(0), vec2<i32>(size - 1));
+    return params.boundary == BOUNDARY_OUTFLOW && (any(p < vec2<i32>(0)) || any(p >= vec2<i32>(size)));
+}
+
+// Cells off the grid count as fluid; the window edge has its own clamped
+// boundary. With wrapping edges they are the cells on the other side.
+fn is_solid(p: vec2<i32>) -> bool {
+    let size = i32(params.grid_size);
+    if (!wraps() && (any(p < vec2<i32>(0)) || any(p >= vec2<i32>(size)))) { return false; }
+    return textureLoad(obstacles, edge_cell(p), 0).x > 0.5;
+}
+
+// Liquid fraction (dye amount, written by flip.wgsl) below which a cell is air
+const LIQUID_MIN: f32 = 0.25;
+
+fn is_air(p: vec2<i32>) -> bool {
+    if (params.liquid == 0u) { return false; }
+    return dye_amount(textureLoad(density, edge_cell(p))) < LIQUID_MIN;
+}
+
+// Lightest a cell can get, so heat can't thin the fluid to nothing
+const DENSITY_MIN: f32 = 0.1;
+
+fn varies_density() -> bool {
+    return params.dye_mass != 0.0 || params.heat_expansion != 0.0;
+}
+
+// Density of the fluid at p, relative to clear fluid at ambient
+// temperature. Past an open edge lies clear, ambient fluid
+fn density_at(p: vec2<i32>) -> f32 {
+    let c = safe_load_scalars(p);
+    return max(1.0 + params.dye_mass * dye_amount(c) - params.heat_expansion * c.y, DENSITY_MIN);
+}
+
+// 1/ρ on the face between p, of density `rho`, and its neighbour n
+fn face_beta(n: vec2<i32>, rho: f32) -> f32 {
+    return 2.0 / (rho + density_at(n));
+}
+
+// Neighbour pressure seen from a fluid cell whose own pressure is `center`.
+// Walls are impermeable, so the pressure gradient into a solid neighbour is
+// zero (Neumann) and the neighbour mirrors `center`.
+fn wall_press(n: vec2<i32>, neighbour: f32, center: f32) -> f32 {
+    return select(neighbour, center, is_solid(n));
+}
+
+// Velocity of the solid at p. The obstacle texture holds it in grid cells
+// per second; scale to the fluid's units.
+fn solid_vel(p: vec2<i32>) -> vec2<f32> {
+    let cp = edge_cell(p);
+    return textureLoad(obstacles, cp, 0).zw * params.cell_size;
+}
+
+// Velocity of neighbour n as the divergence sees it: solids move with their
+// prescribed velocity (zero for fixed obstacles).
+fn wall_vel(n: vec2<i32>) -> vec2<f32> {
+    return select(safe_load_vel(n), solid_vel(n), is_solid(n));
+}
+
+// Charge `units` of work to cell p (debug heat map). Each kernel charges one
+// unit for its base work plus extra for the slow paths it takes.
+fn charge(p: vec2<i32>, units: u32) {
+    if (params.instrument != 0u) {
+        atomicAdd(&cost[u32(p.y) * params.grid_size + u32(p.x)], units);
+    }
+}
+
+// How many of p's four neighbour taps fall outside the grid and get clamped.
+// Wrapped taps cost the same as any other.
+fn clamped_taps(p: vec2<i32>) -> u32 {
+    if (wraps()) { return 0u; }
+    let last = i32(params.grid_size) - 1;
+    return u32(p.x == 0) + u32(p.x == last) + u32(p.y == 0) + u32(p.y == last);
+}
+
+fn safe_load_vel(p: vec2<i32>) -> vec2<f32> {
+    let cp = edge_cell(p);
     return textureLoad(velocity, cp).xy;
 }
 
 fn safe_load_vel_tmp(p: vec2<i32>) -> vec2<f32> {
-    let size = i32(params.grid_size);
-    let cp = clamp(p, vec2<i32>(0), vec2<i32>(size - 1));
+    let cp = edge_cell(p);
     return textureLoad(velocity_tmp, cp).xy;
 }
 
-fn safe_load_dens(p: vec2<i32>) -> f32 {
-    let size = i32(params.grid_size);
-    let cp = clamp(p, vec2<i32>(0), vec2<i32>(size - 1));
-    return textureLoad(density, cp).x;
+// Dye colors (rgb)
+fn safe_load_dens(p: vec2<i32>) -> vec3<f32> {
+    if (outside_open_edge(p)) { return vec3<f32>(0.0); }
+    let cp = edge_cell(p);
+    return dye_rgb(textureLoad(density, cp));
+}
+
+// Dye and temperature
+fn safe_load_scalars(p: vec2<i32>) -> vec4<f32> {
+    if (outside_open_edge(p)) { return vec4<f32>(0.0); }
+    let cp = edge_cell(p);
+    return textureLoad(density, cp);
+}
+
+// Packing of the dye/temperature texels
+fn scalars(dye: vec3<f32>, temperature: f32) -> vec4<f32> {
+    return vec4<f32>(dye.r, temperature, dye.g, dye.b);
+}
+
+fn dye_rgb(c: vec4<f32>) -> vec3<f32> {
+    return c.xzw;
+}
+
+// How much dye a texel holds, whatever its color: what buoyancy and
+// dye-weighted gravity act on. White dye of strength d is d.
+fn dye_amount(c: vec4<f32>) -> f32 {
+    return max(c.x, max(c.z, c.w));
 }
 
 fn safe_load_press(p: vec2<i32>) -> f32 {
-    let size = i32(params.grid_size);
-    let cp = clamp(p, vec2<i32>(0), vec2<i32>(size - 1));
+    if (outside_open_edge(p)) { return 0.0; }
+    let cp = edge_cell(p);
     return textureLoad(pressure, cp).x;
 }
 
 fn safe_load_press_tmp(p: vec2<i32>) -> f32 {
-    let size = i32(params.grid_size);
-    let cp = clamp(p, vec2<i32>(0), vec2<i32>(size - 1));
+    if (outside_open_edge(p)) { return 0.0; }
+    let cp = edge_cell(p);
     return textureLoad(pressure_tmp, cp).x;
 }
 
 fn safe_load_div(p: vec2<i32>) -> f32 {
-    let size = i32(params.grid_size);
-    let cp = clamp(p, vec2<i32>(0), vec2<i32>(size - 1));
+    let cp = edge_cell(p);
     return textureLoad(divergence_tex, cp).x;
 }
 
 // ============================================================
-// Compute: add mouse forces + dye
+// Driver workarounds
+// ============================================================
+// Flipped to `true` in the source before compiling when the adapter needs
+// it (src/workarounds.rs). Constants, so the unused path folds away.
+const WA_SPLIT_READ_WRITE: bool = false;
+
+// The velocity / dye + temperature at p that an in-place kernel is about to
+// overwrite. With WA_SPLIT_READ_WRITE, step() has stashed the field into its
+// tmp texture first, so no invocation loads and stores the same texel.
+fn load_vel_for_update(p: vec2<i32>) -> vec2<f32> {
+    if (WA_SPLIT_READ_WRITE) { return textureLoad(velocity_tmp, p).xy; }
+    return textureLoad(velocity, p).xy;
+}
+
+fn load_scalars_for_update(p: vec2<i32>) -> vec4<f32> {
+    if (WA_SPLIT_READ_WRITE) { return textureLoad(density_tmp, p); }
+    return textureLoad(density, p);
+}
+
+@compute @workgroup_size(8, 8)
+fn stash_vel(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    textureStore(velocity_tmp, p, textureLoad(velocity, p));
+}
+
+@compute @workgroup_size(8, 8)
+fn stash_dens(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    textureStore(density_tmp, p, textureLoad(density, p));
+}
+
+// ============================================================
+// Compute: add mouse forces + dye, scene emitters, wind
 // ============================================================
+// How quickly the fluid picks up the ambient wind, per second. Slow enough
+// that stirring still shows, then drifts off downwind.
+const WIND_RATE: f32 = 0.5;
+// Velocity a mouse stroke adds per cell of movement; must match
+// `MOUSE_IMPULSE` in src/cfl.rs
+const MOUSE_IMPULSE: f32 = 50.0;
+
+fn current_wind() -> vec2<f32> {
+    let gust = params.wind_gust * sin(6.2831853 * params.time / params.wind_gust_period);
+    return params.wind * (1.0 + gust);
+}
+
+// Offset d between two positions in cells, taken the short way round when
+// the edges wrap so splats near one edge continue on the other.
+fn shortest_offset(d: vec2<f32>) -> vec2<f32> {
+    if (!wraps()) { return d; }
+    let size = f32(params.grid_size);
+    return d - size * round(d / size);
+}
+
 @compute @workgroup_size(8, 8)
 fn add_source(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
-    if (params.mouse_down == 0u) { return; }
+    let windy = any(params.wind != vec2<f32>(0.0));
+    if (params.mouse_down == 0u && params.emitter_count == 0u && !windy) { return; }
 
     let pos = vec2<f32>(f32(gid.x), f32(gid.y));
-    let d = distance(pos, params.mouse_pos);
-    let falloff = exp(-(d * d) / (params.radius * params.radius + 0.001));
-
     let p = vec2<i32>(gid.xy);
-    let v = textureLoad(velocity, p).xy;
-    let add_vel = params.mouse_delta * falloff * 50.0;
-    textureStore(velocity, p, vec4<f32>(v + add_vel, 0.0, 0.0));
+    charge(p, 1u + params.mouse_down + params.emitter_count);
+    var v = load_vel_for_update(p);
+    // Dye colors and temperature, packed as by `scalars`
+    var c = load_scalars_for_update(p);
+
+    if (windy) {
+        v = mix(v, current_wind(), 1.0 - exp(-WIND_RATE * params.dt));
+    }
+
+    if (params.mouse_down != 0u) {
+        let d = length(shortest_offset(pos - params.mouse_pos) * params.cell_size);
+        let falloff = exp(-(d * d) / (params.radius * params.radius + 0.001));
+        if (params.rod != 0u) {
+            // The rod drags the fluid it covers along at its own velocity
+            v = mix(v, params.mouse_delta * params.cell_size / params.dt, falloff);
+        } else {
+            v = v + params.mouse_delta * params.cell_size * falloff * MOUSE_IMPULSE;
+        }
+        c = c + scalars(params.dye_color * params.add_strength, params.heat_strength) * falloff;
+    }
 
-    let c = textureLoad(density, p).x;
-    let add_dye = params.add_strength * falloff;
-    textureStore(density, p, vec4<f32>(c + add_dye, 0.0, 0.0, 0.0));
+    for (var e = 0u; e < params.emitter_count; e = e + 1u) {
+        let em = emitters[e];
+        let d = length(shortest_offset(pos - em.pos) * params.cell_size);
+        let falloff = exp(-(d * d) / (em.radius * em.radius + 0.001));
+        v = mix(v, em.velocity, falloff);
+        c = c + scalars(em.color * em.density, em.temperature) * params.dt * falloff;
+    }
+
+    textureStore(velocity, p, vec4<f32>(v, 0.0, 0.0));
+    textureStore(density, p, c);
 }
 
 // ============================================================
-// Compute: advect velocity
+// Compute: vorticity confinement
 // ============================================================
 @compute @workgroup_size(8, 8)
-fn advect_vel(@builtin(global_invocation_id) gid: vec3<u32>) {
+fn compute_curl(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
 
     let p = vec2<i32>(gid.xy);
+    charge(p, 1u + clamped_taps(p));
+    let dvy_dx = safe_load_vel(p + vec2<i32>(1, 0)).y - safe_load_vel(p - vec2<i32>(1, 0)).y;
+    let dvx_dy = safe_load_vel(p + vec2<i32>(0, 1)).x - safe_load_vel(p - vec2<i32>(0, 1)).x;
+    let h = params.cell_size;
+    // Stash the sub-grid turbulence for subgrid_energy to advect
+    let k = select(0.0, textureLoad(curl_tex, p).y, params.subgrid > 0.0);
+    textureStore(curl_tex, p, vec4<f32>(0.5 * (dvy_dx / h.x - dvx_dy / h.y), k, k, 0.0));
+}
+
+fn safe_load_curl(p: vec2<i32>) -> f32 {
+    let cp = edge_cell(p);
+    return textureLoad(curl_tex, cp).x;
+}
+
+// Push velocity around vortex centers (toward higher |curl|) to put back the
+// small-scale swirl that advection and the coarse grid smear out.
+@compute @workgroup_size(8, 8)
+fn vorticity_confinement(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    if (params.vorticity <= 0.0) { return; }
+
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    if (is_solid(p)) { return; }
+    charge(p, clamped_taps(p));
+
+    let grad = 0.5 * vec2<f32>(
+        abs(safe_load_curl(p + vec2<i32>(1, 0))) - abs(safe_load_curl(p - vec2<i32>(1, 0))),
+        abs(safe_load_curl(p + vec2<i32>(0, 1))) - abs(safe_load_curl(p - vec2<i32>(0, 1))),
+    ) / params.cell_size;
+    let n = grad / (length(grad) + 1e-5);
+    let w = safe_load_curl(p);
+    let force = params.vorticity * vec2<f32>(n.y * w, -n.x * w);
+
+    let v = load_vel_for_update(p) + force * params.dt;
+    textureStore(velocity, p, vec4<f32>(v, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: sub-grid turbulence energy
+// ============================================================
+// A k-style scalar for the eddies too small for the grid: shear produces
+// it, it rides along with the flow and dies away over SUBGRID_HALF_LIFE.
+// It doesn't push the flow; the render pass breaks the dye up with noise
+// where it's high. The advection reads last step's copy in curl_tex.z,
+// which no invocation changes, and writes curl_tex.y.
+const SUBGRID_HALF_LIFE: f32 = 0.8;
+
+fn safe_load_subgrid(p: vec2<i32>) -> f32 {
+    return textureLoad(curl_tex, edge_cell(p)).z;
+}
+
+@compute @workgroup_size(8, 8)
+fn subgrid_energy(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    let own = textureLoad(curl_tex, p);
+    if (is_solid(p)) {
+        textureStore(curl_tex, p, vec4<f32>(own.x, 0.0, own.z, 0.0));
+        return;
+    }
+    charge(p, 4u);
+
     let pos = vec2<f32>(f32(gid.x) + 0.5, f32(gid.y) + 0.5);
     let vel = textureLoad(velocity, p).xy;
-    let prev_pos = pos - vel * params.dt;
+    let pp = backtrace_cell(pos - vel * params.dt / params.cell_size);
+    let i = vec2<i32>(floor(pp));
+    let f = fract(pp);
+    let k0 = mix(safe_load_subgrid(i), safe_load_subgrid(i + vec2<i32>(1, 0)), f.x);
+    let k1 = mix(safe_load_subgrid(i + vec2<i32>(0, 1)), safe_load_subgrid(i + vec2<i32>(1, 1)), f.x);
+    let produced = params.subgrid * abs(own.x) * params.dt;
+    let k = (mix(k0, k1, f.y) + produced) * exp2(-params.dt / SUBGRID_HALF_LIFE);
+    textureStore(curl_tex, p, vec4<f32>(own.x, k, own.z, 0.0));
+}
+
+// ============================================================
+// Compute: wake turbulence
+// ============================================================
+// A coarse grid resolves the shear layers that peel off an obstacle but not
+// the small eddies they break into, so a low-resolution wake looks like
+// smooth ribbons. Where the flow is strongly sheared and an obstacle lies
+// not far upstream, stir in small-scale curl noise as a stand-in for them.
 
+// Shear (|curl|, 1/s) at which a wake cell gets the full noise
+const WAKE_SHEAR: f32 = 6.0;
+// How far downstream of an obstacle, in cells, the noise reaches
+const WAKE_REACH: f32 = 48.0;
+const WAKE_MARCH_STEPS: i32 = 12;
+// Noise feature size in cells, and how fast it changes (features per second)
+const WAKE_SCALE: f32 = 5.0;
+const WAKE_RATE: f32 = 3.0;
+
+fn hash3(c: vec3<i32>) -> f32 {
+    var h = (u32(c.x) * 0x8da6b343u) ^ (u32(c.y) * 0xd8163841u) ^ (u32(c.z) * 0xcb1ab31fu);
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    h = h ^ (h >> 16u);
+    return f32(h) / 4294967295.0;
+}
+
+// Smooth value noise in [0, 1] over (x, y, time)
+fn value_noise(q: vec3<f32>) -> f32 {
+    let i = vec3<i32>(floor(q));
+    let f = fract(q);
+    let u = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(hash3(i), hash3(i + vec3<i32>(1, 0, 0)), u.x);
+    let x10 = mix(hash3(i + vec3<i32>(0, 1, 0)), hash3(i + vec3<i32>(1, 1, 0)), u.x);
+    let x01 = mix(hash3(i + vec3<i32>(0, 0, 1)), hash3(i + vec3<i32>(1, 0, 1)), u.x);
+    let x11 = mix(hash3(i + vec3<i32>(0, 1, 1)), hash3(i + vec3<i32>(1, 1, 1)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+// Curl of a noise stream function: divergence-free, magnitude around 1
+fn curl_noise(p: vec2<f32>) -> vec2<f32> {
+    let t = params.time * WAKE_RATE;
+    let e = 0.5;
+    let n_x0 = value_noise(vec3<f32>((p - vec2<f32>(e, 0.0)) / WAKE_SCALE, t));
+    let n_x1 = value_noise(vec3<f32>((p + vec2<f32>(e, 0.0)) / WAKE_SCALE, t));
+    let n_y0 = value_noise(vec3<f32>((p - vec2<f32>(0.0, e)) / WAKE_SCALE, t));
+    let n_y1 = value_noise(vec3<f32>((p + vec2<f32>(0.0, e)) / WAKE_SCALE, t));
+    return vec2<f32>(n_y1 - n_y0, n_x0 - n_x1) * WAKE_SCALE / (2.0 * e);
+}
+
+// 1 right behind an obstacle, fading to 0 at WAKE_REACH cells downstream
+// (looking back along the local flow direction `dir`); 0 if none is upstream
+fn downstream_of_obstacle(p: vec2<i32>, dir: vec2<f32>) -> f32 {
+    let stride = WAKE_REACH / f32(WAKE_MARCH_STEPS);
+    for (var i = 1; i <= WAKE_MARCH_STEPS; i++) {
+        let back = f32(i) * stride;
+        let q = vec2<i32>(floor(vec2<f32>(p) + 0.5 - dir * back));
+        if (is_solid(q)) { return 1.0 - back / WAKE_REACH; }
+    }
+    return 0.0;
+}
+
+@compute @workgroup_size(8, 8)
+fn wake_turbulence(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    if (is_solid(p)) { return; }
+
+    let v = load_vel_for_update(p);
+    let shear = clamp(abs(textureLoad(curl_tex, p).x) / WAKE_SHEAR, 0.0, 1.0);
+    let speed = length(v);
+    var amount = 0.0;
+    if (shear > 0.0 && speed > 1.0) {
+        charge(p, u32(WAKE_MARCH_STEPS));
+        amount = shear * downstream_of_obstacle(p, v / speed);
+    }
+    if (amount <= 0.0) { return; }
+    let stir = curl_noise(vec2<f32>(p) + 0.5) * params.wake * amount;
+    textureStore(velocity, p, vec4<f32>(v + stir * params.dt, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: buoyancy
+// ============================================================
+// Dye is heavier than the surrounding fluid and heat is lighter (ambient
+// temperature is 0). Gravity points down the grid (+y), so the vertical
+// acceleration is smoke_weight * dye - heat_lift * temperature, plus each
+// dye color's own density, which can be negative: some colors sink while
+// others rise.
+@compute @workgroup_size(8, 8)
+fn apply_buoyancy(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    if (is_solid(p)) { return; }
+    let c = textureLoad(density, p);
+    let v = load_vel_for_update(p);
+    let accel = params.smoke_weight * dye_amount(c) + dot(params.dye_density, dye_rgb(c)) - params.heat_lift * c.y;
+    textureStore(velocity, p, vec4<f32>(v.x, v.y + accel * params.dt, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: gravity
+// ============================================================
+// Uniform gravity is a pure gradient, so in a closed box the projection
+// cancels it (the fluid just carries hydrostatic pressure). Weighted by dye
+// it isn't: ink falls through the clear fluid around it.
+// The gravity well's pull toward the center, per unit of dye. It peaks at
+// `well` a tenth of the domain out, fades to zero at the center instead of
+// blowing up, and falls off as 1/r beyond, like a softened point mass.
+fn well_pull(p: vec2<i32>) -> vec2<f32> {
     let size = f32(params.grid_size);
-    let pp = clamp(prev_pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(size - 1.001));
+    let d = (size * 0.5 - (vec2<f32>(p) + 0.5)) * params.cell_size;
+    let r = length(d);
+    let s = size * 0.1;
+    return d * (params.well * 2.0 * s / (r * r + s * s));
+}
+
+@compute @workgroup_size(8, 8)
+fn apply_gravity(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    if (is_solid(p)) { return; }
+    let amount = dye_amount(textureLoad(density, p));
+    var g = params.gravity;
+    if (params.gravity_by_dye != 0u) {
+        g = g * amount;
+    }
+    // Always dye-weighted: a uniform pull would be all gradient too
+    g += well_pull(p) * amount;
+    let v = load_vel_for_update(p) + g * params.dt;
+    textureStore(velocity, p, vec4<f32>(v, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: advect velocity
+// ============================================================
+// Where a backtrace from cell center `pos` samples, as the lower-left cell
+// of its bilinear footprint. Between walls it stops at the edge; with
+// wrapping or open edges it may run off the grid, and the taps wrap or
+// bring in clear fluid.
+fn backtrace_cell(pos: vec2<f32>) -> vec2<f32> {
+    if (params.boundary != BOUNDARY_WALLS) { return pos - vec2<f32>(0.5); }
+    let size = f32(params.grid_size);
+    return clamp(pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(size - 1.001));
+}
+
+// Semi-Lagrangian: the velocity that the flow in `velocity` brings to cell
+// p over `dt`. With `reflect` the taps read the reflected field
+// 2·velocity − velocity_tmp instead (advection-reflection, below).
+fn advected_vel(p: vec2<i32>, dt: f32, reflect: bool) -> vec2<f32> {
+    if (is_solid(p)) {
+        charge(p, 1u);
+        return solid_vel(p);
+    }
+    let pos = vec2<f32>(p) + 0.5;
+    let vel = textureLoad(velocity, p).xy;
+    let prev_pos = pos - vel * dt / params.cell_size;
+
+    let pp = backtrace_cell(prev_pos);
+    // Backtraces that leave the grid get clamped onto the edge
+    charge(p, 1u + 4u * u32(any(pp != prev_pos - vec2<f32>(0.5))));
     let i = vec2<i32>(floor(pp));
     let f = fract(pp);
 
-    let v00 = safe_load_vel(i);
-    let v10 = safe_load_vel(i + vec2<i32>(1, 0));
-    let v01 = safe_load_vel(i + vec2<i32>(0, 1));
-    let v11 = safe_load_vel(i + vec2<i32>(1, 1));
+    // Solid taps hold the solid's velocity, which drags flow next to
+    // obstacles along with them (no-slip)
+    var v00 = safe_load_vel(i);
+    var v10 = safe_load_vel(i + vec2<i32>(1, 0));
+    var v01 = safe_load_vel(i + vec2<i32>(0, 1));
+    var v11 = safe_load_vel(i + vec2<i32>(1, 1));
+    if (reflect) {
+        v00 = 2.0 * v00 - safe_load_vel_tmp(i);
+        v10 = 2.0 * v10 - safe_load_vel_tmp(i + vec2<i32>(1, 0));
+        v01 = 2.0 * v01 - safe_load_vel_tmp(i + vec2<i32>(0, 1));
+        v11 = 2.0 * v11 - safe_load_vel_tmp(i + vec2<i32>(1, 1));
+    }
 
     let v0 = mix(v00, v10, f.x);
     let v1 = mix(v01, v11, f.x);
-    let sampled = mix(v0, v1, f.y) * params.dissipation;
+    return mix(v0, v1, f.y);
+}
 
-    textureStore(velocity_tmp, p, vec4<f32>(sampled, 0.0, 0.0));
+fn decayed_vel(v: vec2<f32>) -> vec2<f32> {
+    let decayed = v * params.velocity_decay;
+    return select(decayed, vec2<f32>(0.0), length(decayed) < params.velocity_threshold);
+}
+
+@compute @workgroup_size(8, 8)
+fn advect_vel(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    let v = select(decayed_vel(advected_vel(p, params.dt, false)), solid_vel(p), is_solid(p));
+    textureStore(velocity_tmp, p, vec4<f32>(v, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: advection-reflection (--advection reflection)
+// ============================================================
+// Zehnder et al. 2018. The step advects velocity for half of dt and
+// projects that to u½, keeping the unprojected ũ½ in velocity_tmp. The
+// projection took away ũ½ − u½; reflecting through u½ gives 2u½ − ũ½,
+// which has the same kinetic energy as ũ½ but carries the lost part with
+// the opposite sign. Advecting that with u½ for the second half and
+// projecting again loses only what the two halves disagree on, so far less
+// swirl is dissipated than by projecting one full step.
+
+// First half: velocity_tmp = velocity moved by itself for dt/2, undecayed
+@compute @workgroup_size(8, 8)
+fn advect_vel_half(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    textureStore(velocity_tmp, p, vec4<f32>(advected_vel(p, 0.5 * params.dt, false), 0.0, 0.0));
+}
+
+// Second half, after the projection: the reflected field moved by u½ for
+// dt/2, into pressure_tmp, which is free until compute_divergence clears it
+@compute @workgroup_size(8, 8)
+fn advect_vel_reflected(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    let v = select(decayed_vel(advected_vel(p, 0.5 * params.dt, true)), solid_vel(p), is_solid(p));
+    textureStore(pressure_tmp, p, vec4<f32>(v, 0.0, 0.0));
+}
+
+// Back into velocity, and into velocity_tmp for the viscosity solve
+@compute @workgroup_size(8, 8)
+fn copy_reflected(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
+    let v = vec4<f32>(textureLoad(pressure_tmp, p).xy, 0.0, 0.0);
+    textureStore(velocity, p, v);
+    textureStore(velocity_tmp, p, v);
 }
 
 @compute @workgroup_size(8, 8)
 fn copy_vel(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
     let p = vec2<i32>(gid.xy);
+    charge(p, 1u);
     let v = textureLoad(velocity_tmp, p).xy;
     textureStore(velocity, p, vec4<f32>(v, 0.0, 0.0));
 }
@@ -144,31 +712,140 @@ fn advect_dens(@builtin(global_invocation_id) gid: vec3<u32>) {
     let p = vec2<i32>(gid.xy);
     let pos = vec2<f32>(f32(gid.x) + 0.5, f32(gid.y) + 0.5);
     let vel = textureLoad(velocity, p).xy;
-    let prev_pos = pos - vel * params.dt;
+    let prev_pos = pos - vel * params.dt / params.cell_size;
 
-    let size = f32(params.grid_size);
-    let pp = clamp(prev_pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(size - 1.001));
+    let pp = backtrace_cell(prev_pos);
+    // Backtraces that leave the grid get clamped onto the edge
+    charge(p, 1u + 4u * u32(any(pp != prev_pos - vec2<f32>(0.5))));
     let i = vec2<i32>(floor(pp));
     let f = fract(pp);
 
-    let d00 = safe_load_dens(i);
-    let d10 = safe_load_dens(i + vec2<i32>(1, 0));
-    let d01 = safe_load_dens(i + vec2<i32>(0, 1));
-    let d11 = safe_load_dens(i + vec2<i32>(1, 1));
+    // Dye and temperature travel together. Solid taps are left out and the
+    // rest reweighted, so dye doesn't fade into the empty obstacle cells.
+    var sum = vec4<f32>(0.0);
+    var weight = 0.0;
+    for (var k = 0; k < 4; k = k + 1) {
+        let o = vec2<i32>(k & 1, k >> 1u);
+        let w = mix(1.0 - f.x, f.x, f32(o.x)) * mix(1.0 - f.y, f.y, f32(o.y));
+        if (!is_solid(i + o)) {
+            sum = sum + safe_load_scalars(i + o) * w;
+            weight = weight + w;
+        }
+    }
+    let sampled = select(vec4<f32>(0.0), sum / weight, weight > 1e-4 && !is_solid(p));
+    var dye = dye_rgb(sampled) * params.dye_decay;
+    // The threshold goes by the strongest channel, so faint tints fade with
+    // the dye around them rather than leaving a color cast
+    if (max(abs(dye.r), max(abs(dye.g), abs(dye.b))) < params.dye_threshold) { dye = vec3<f32>(0.0); }
 
-    let d0 = mix(d00, d10, f.x);
-    let d1 = mix(d01, d11, f.x);
-    let sampled = mix(d0, d1, f.y) * params.dissipation;
-
-    textureStore(density_tmp, p, vec4<f32>(sampled, 0.0, 0.0, 0.0));
+    textureStore(density_tmp, p, scalars(dye, sampled.y * params.temperature_decay));
 }
 
 @compute @workgroup_size(8, 8)
 fn copy_dens(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
     let p = vec2<i32>(gid.xy);
-    let d = textureLoad(density_tmp, p).x;
-    textureStore(density, p, vec4<f32>(d, 0.0, 0.0, 0.0));
+    charge(p, 1u);
+    textureStore(density, p, textureLoad(density_tmp, p));
+}
+
+// ============================================================
+// Compute: implicit diffusion (viscosity, dye diffusion)
+// ============================================================
+// Backward Euler, (1 - k·dt·lap) q = q0, solved with Jacobi sweeps so any
+// viscosity is stable. q0 is the advected field still held in velocity_tmp /
+// density_tmp. The iterate ping-pongs between velocity/density and the
+// pressure textures, which are free until compute_divergence clears them.
+// Solid cells are held at the solid's velocity, which makes obstacles no-slip.
+fn diffusion_rates(k: f32) -> vec2<f32> {
+    return k * params.dt / (params.cell_size * params.cell_size);
+}
+
+fn diffuse_vel_from(p: vec2<i32>, sum_x: vec2<f32>, sum_y: vec2<f32>) -> vec2<f32> {
+    let a = diffusion_rates(params.viscosity);
+    return (textureLoad(velocity_tmp, p).xy + sum_x * a.x + sum_y * a.y) / (1.0 + 2.0 * (a.x + a.y));
+}
+
+fn diffuse_dens_from(p: vec2<i32>, sum_x: vec3<f32>, sum_y: vec3<f32>) -> vec3<f32> {
+    let a = diffusion_rates(params.diffusion);
+    return (dye_rgb(textureLoad(density_tmp, p)) + sum_x * a.x + sum_y * a.y) / (1.0 + 2.0 * (a.x + a.y));
+}
+
+fn safe_load_scratch(tex: texture_storage_2d<rgba16float, read_write>, p: vec2<i32>) -> vec4<f32> {
+    return textureLoad(tex, edge_cell(p));
+}
+
+// The dye iterate in `pressure`, clear past an open edge like the dye itself
+fn scratch_dye(p: vec2<i32>) -> vec3<f32> {
+    if (outside_open_edge(p)) { return vec3<f32>(0.0); }
+    return safe_load_scratch(pressure, p).xyz;
+}
+
+// velocity -> pressure_tmp.xy, dye -> pressure.xyz
+@compute @workgroup_size(8, 8)
+fn diffuse_a(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u + clamped_taps(p));
+    let solid = is_solid(p);
+    let l = p + vec2<i32>(-1, 0);
+    let r = p + vec2<i32>(1, 0);
+    let b = p + vec2<i32>(0, -1);
+    let t = p + vec2<i32>(0, 1);
+
+    if (params.viscosity > 0.0) {
+        var v = solid_vel(p);
+        if (!solid) {
+            v = diffuse_vel_from(p, safe_load_vel(l) + safe_load_vel(r), safe_load_vel(b) + safe_load_vel(t));
+        }
+        textureStore(pressure_tmp, p, vec4<f32>(v, 0.0, 0.0));
+    }
+    if (params.diffusion > 0.0) {
+        var d = vec3<f32>(0.0);
+        if (!solid) {
+            d = diffuse_dens_from(p, safe_load_dens(l) + safe_load_dens(r), safe_load_dens(b) + safe_load_dens(t));
+        }
+        textureStore(pressure, p, vec4<f32>(d, 0.0));
+    }
+}
+
+// pressure_tmp.xy -> velocity, pressure.xyz -> dye
+@compute @workgroup_size(8, 8)
+fn diffuse_b(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    charge(p, 1u + clamped_taps(p));
+    let solid = is_solid(p);
+    let l = p + vec2<i32>(-1, 0);
+    let r = p + vec2<i32>(1, 0);
+    let b = p + vec2<i32>(0, -1);
+    let t = p + vec2<i32>(0, 1);
+
+    if (params.viscosity > 0.0) {
+        var v = solid_vel(p);
+        if (!solid) {
+            v = diffuse_vel_from(p,
+                safe_load_scratch(pressure_tmp, l).xy + safe_load_scratch(pressure_tmp, r).xy,
+                safe_load_scratch(pressure_tmp, b).xy + safe_load_scratch(pressure_tmp, t).xy);
+        }
+        textureStore(velocity, p, vec4<f32>(v, 0.0, 0.0));
+    }
+    if (params.diffusion > 0.0) {
+        var d = vec3<f32>(0.0);
+        if (!solid) {
+            d = diffuse_dens_from(p, scratch_dye(l) + scratch_dye(r), scratch_dye(b) + scratch_dye(t));
+        }
+        // Only dye diffuses; keep the temperature. density_tmp still holds
+        // the same advected temperature, and reading it there keeps this
+        // kernel split under WA_SPLIT_READ_WRITE without a stash
+        var temperature: f32;
+        if (WA_SPLIT_READ_WRITE) {
+            temperature = textureLoad(density_tmp, p).y;
+        } else {
+            temperature = textureLoad(density, p).y;
+        }
+        textureStore(density, p, scalars(d, temperature));
+    }
 }
 
 // ============================================================
@@ -178,62 +855,144 @@ fn copy_dens(@builtin(global_invocation_id) gid: vec3<u32>) {
 fn compute_divergence(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
     let p = vec2<i32>(gid.xy);
+    charge(p, 1u + clamped_taps(p));
 
-    let vL = safe_load_vel(p + vec2<i32>(-1, 0)).x;
-    let vR = safe_load_vel(p + vec2<i32>(1, 0)).x;
-    let vB = safe_load_vel(p + vec2<i32>(0, -1)).y;
-    let vT = safe_load_vel(p + vec2<i32>(0, 1)).y;
+    let vL = wall_vel(p + vec2<i32>(-1, 0)).x;
+    let vR = wall_vel(p + vec2<i32>(1, 0)).x;
+    let vB = wall_vel(p + vec2<i32>(0, -1)).y;
+    let vT = wall_vel(p + vec2<i32>(0, 1)).y;
 
-    let div = 0.5 * (vR - vL + vT - vB);
+    let h = params.cell_size;
+    // Solid cells carry no flow, so nothing to correct there
+    let div = select(0.5 * ((vR - vL) / h.x + (vT - vB) / h.y), 0.0, is_solid(p));
     textureStore(divergence_tex, p, vec4<f32>(div, 0.0, 0.0, 0.0));
 
     textureStore(pressure, p, vec4<f32>(0.0));
     textureStore(pressure_tmp, p, vec4<f32>(0.0));
 }
 
+// One Jacobi update of (pL + pR - 2p)/dx² + (pB + pT - 2p)/dy² = div
+fn jacobi_step(pL: f32, pR: f32, pB: f32, pT: f32, div: f32) -> f32 {
+    let inv_h2 = 1.0 / (params.cell_size * params.cell_size);
+    return ((pL + pR) * inv_h2.x + (pB + pT) * inv_h2.y - div) / (2.0 * (inv_h2.x + inv_h2.y));
+}
+
+// The same for ∇·(β∇p) = div, with β = 1/ρ on each face: denser cells
+// accelerate less under the same pressure difference
+fn jacobi_step_weighted(p: vec2<i32>, pL: f32, pR: f32, pB: f32, pT: f32, div: f32) -> f32 {
+    let inv_h2 = 1.0 / (params.cell_size * params.cell_size);
+    let rho = density_at(p);
+    let bL = face_beta(p + vec2<i32>(-1, 0), rho);
+    let bR = face_beta(p + vec2<i32>(1, 0), rho);
+    let bB = face_beta(p + vec2<i32>(0, -1), rho);
+    let bT = face_beta(p + vec2<i32>(0, 1), rho);
+    let sum = (bL * pL + bR * pR) * inv_h2.x + (bB * pB + bT * pT) * inv_h2.y;
+    return (sum - div) / ((bL + bR) * inv_h2.x + (bB + bT) * inv_h2.y);
+}
+
+// A Jacobi update, weighted by density when it varies
+fn pressure_update(p: vec2<i32>, pL: f32, pR: f32, pB: f32, pT: f32, div: f32) -> f32 {
+    if (varies_density()) {
+        return jacobi_step_weighted(p, pL, pR, pB, pT, div);
+    }
+    return jacobi_step(pL, pR, pB, pT, div);
+}
+
 @compute @workgroup_size(8, 8)
 fn pressure_jacobi_a(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
     let p = vec2<i32>(gid.xy);
+    charge(p, 1u + clamped_taps(p));
 
-    let pL = safe_load_press(p + vec2<i32>(-1, 0));
-    let pR = safe_load_press(p + vec2<i32>(1, 0));
-    let pB = safe_load_press(p + vec2<i32>(0, -1));
-    let pT = safe_load_press(p + vec2<i32>(0, 1));
+    if (is_solid(p) || is_air(p)) {
+        textureStore(pressure_tmp, p, vec4<f32>(0.0));
+        return;
+    }
+    let pC = safe_load_press(p);
+    let pL = wall_press(p + vec2<i32>(-1, 0), safe_load_press(p + vec2<i32>(-1, 0)), pC);
+    let pR = wall_press(p + vec2<i32>(1, 0), safe_load_press(p + vec2<i32>(1, 0)), pC);
+    let pB = wall_press(p + vec2<i32>(0, -1), safe_load_press(p + vec2<i32>(0, -1)), pC);
+    let pT = wall_press(p + vec2<i32>(0, 1), safe_load_press(p + vec2<i32>(0, 1)), pC);
     let div = safe_load_div(p);
 
-    let new_p = (pL + pR + pB + pT - div) * 0.25;
-    textureStore(pressure_tmp, p, vec4<f32>(new_p, 0.0, 0.0, 0.0));
+    textureStore(pressure_tmp, p, vec4<f32>(pressure_update(p, pL, pR, pB, pT, div), 0.0, 0.0, 0.0));
 }
 
 @compute @workgroup_size(8, 8)
 fn pressure_jacobi_b(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
     let p = vec2<i32>(gid.xy);
+    charge(p, 1u + clamped_taps(p));
 
-    let pL = safe_load_press_tmp(p + vec2<i32>(-1, 0));
-    let pR = safe_load_press_tmp(p + vec2<i32>(1, 0));
-    let pB = safe_load_press_tmp(p + vec2<i32>(0, -1));
-    let pT = safe_load_press_tmp(p + vec2<i32>(0, 1));
+    if (is_solid(p) || is_air(p)) {
+        textureStore(pressure, p, vec4<f32>(0.0));
+        return;
+    }
+    let pC = safe_load_press_tmp(p);
+    let pL = wall_press(p + vec2<i32>(-1, 0), safe_load_press_tmp(p + vec2<i32>(-1, 0)), pC);
+    let pR = wall_press(p + vec2<i32>(1, 0), safe_load_press_tmp(p + vec2<i32>(1, 0)), pC);
+    let pB = wall_press(p + vec2<i32>(0, -1), safe_load_press_tmp(p + vec2<i32>(0, -1)), pC);
+    let pT = wall_press(p + vec2<i32>(0, 1), safe_load_press_tmp(p + vec2<i32>(0, 1)), pC);
     let div = safe_load_div(p);
 
-    let new_p = (pL + pR + pB + pT - div) * 0.25;
-    textureStore(pressure, p, vec4<f32>(new_p, 0.0, 0.0, 0.0));
+    textureStore(pressure, p, vec4<f32>(pressure_update(p, pL, pR, pB, pT, div), 0.0, 0.0, 0.0));
 }
 
 @compute @workgroup_size(8, 8)
 fn subtract_gradient(@builtin(global_invocation_id) gid: vec3<u32>) {
     if (!in_bounds(gid)) { return; }
     let p = vec2<i32>(gid.xy);
+    charge(p, 1u + clamped_taps(p));
 
-    let pL = safe_load_press(p + vec2<i32>(-1, 0));
-    let pR = safe_load_press(p + vec2<i32>(1, 0));
-    let pB = safe_load_press(p + vec2<i32>(0, -1));
-    let pT = safe_load_press(p + vec2<i32>(0, 1));
+    if (is_solid(p)) { return; }
+    let pC = safe_load_press(p);
+    let pL = wall_press(p + vec2<i32>(-1, 0), safe_load_press(p + vec2<i32>(-1, 0)), pC);
+    let pR = wall_press(p + vec2<i32>(1, 0), safe_load_press(p + vec2<i32>(1, 0)), pC);
+    let pB = wall_press(p + vec2<i32>(0, -1), safe_load_press(p + vec2<i32>(0, -1)), pC);
+    let pT = wall_press(p + vec2<i32>(0, 1), safe_load_press(p + vec2<i32>(0, 1)), pC);
 
-    let grad = vec2<f32>(pR - pL, pT - pB) * 0.5;
-    let vel = textureLoad(velocity, p).xy;
-    textureStore(velocity, p, vec4<f32>(vel - grad, 0.0, 0.0));
+    var grad = vec2<f32>(pR - pL, pT - pB) * 0.5 / params.cell_size;
+    if (varies_density()) {
+        // Each face's difference times its 1/ρ, averaged to the center
+        let rho = density_at(p);
+        let gx = face_beta(p + vec2<i32>(1, 0), rho) * (pR - pC) + face_beta(p + vec2<i32>(-1, 0), rho) * (pC - pL);
+        let gy = face_beta(p + vec2<i32>(0, 1), rho) * (pT - pC) + face_beta(p + vec2<i32>(0, -1), rho) * (pC - pB);
+        grad = vec2<f32>(gx, gy) * 0.5 / params.cell_size;
+    }
+    var vel = load_vel_for_update(p) - grad;
+    // No flow through walls: relative to a solid neighbour, drop any
+    // velocity component pointing into it
+    let l = p + vec2<i32>(-1, 0);
+    let r = p + vec2<i32>(1, 0);
+    let b = p + vec2<i32>(0, -1);
+    let t = p + vec2<i32>(0, 1);
+    if (is_solid(l) && vel.x < solid_vel(l).x) { vel.x = solid_vel(l).x; }
+    if (is_solid(r) && vel.x > solid_vel(r).x) { vel.x = solid_vel(r).x; }
+    if (is_solid(b) && vel.y < solid_vel(b).y) { vel.y = solid_vel(b).y; }
+    if (is_solid(t) && vel.y > solid_vel(t).y) { vel.y = solid_vel(t).y; }
+    textureStore(velocity, p, vec4<f32>(vel, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: solid obstacles
+// ============================================================
+// Solid cells hold no fluid: after projection their velocity is set to the
+// solid's own and their dye cleared.
+// This is the last fluid kernel each frame, so it also records the frame's
+// maximum cost for the heat map.
+@compute @workgroup_size(8, 8)
+fn enforce_obstacles(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (!in_bounds(gid)) { return; }
+    let p = vec2<i32>(gid.xy);
+    let solid = is_solid(p);
+    charge(p, 1u + u32(solid));
+    if (params.instrument != 0u) {
+        let n = params.grid_size * params.grid_size;
+        atomicMax(&cost[n], atomicLoad(&cost[u32(p.y) * params.grid_size + u32(p.x)]));
+    }
+    if (!solid) { return; }
+    textureStore(velocity, p, vec4<f32>(solid_vel(p), 0.0, 0.0));
+    textureStore(density, p, vec4<f32>(0.0));
 }
 
 // ============================================================
@@ -264,7 +1023,7 @@ fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
 
 @fragment
 fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
-    let c = textureSampleLevel(render_density_tex, render_sampler, in.uv, 0.0).x;
+    let c = dye_amount(textureSampleLevel(render_density_tex, render_sampler, in.uv, 0.0));
     let intensity = clamp(c, 0.0, 1.0);
     let color = vec3<f32>(0.1, 0.4, 0.9) * intensity + vec3<f32>(0.0, 0.7, 1.0) * intensity * intensity;
     return vec4<f32>(color, 1.0);
@@ -0,0 +1,17 @@
+// ============================================================
+// HUD (F1, --hud): the panel behind the text, which src/hud.rs draws
+// with glyphon. The pass's viewport is the panel.
+// ============================================================
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    return vec4<f32>(positions[vid], 0.0, 1.0);
+}
+
+@fragment
+fn fs_hud() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.03, 0.035, 0.05, 1.0);
+}
@@ -0,0 +1,67 @@
+// ============================================================
+// Field inspector (Shift + I): outlines the cell under the cursor, whose
+// values the window title shows, and optionally draws the grid's cell
+// boundaries over the view
+// ============================================================
+struct InspectParams {
+    // Part of the domain drawn, (x, y, w, h) in texture coordinates
+    viewport: vec4<f32>,
+    // Cell under the cursor, or -1 when it's outside the window
+    cell: vec2<i32>,
+    grid_size: u32,
+    // 1 to draw the grid lines too
+    show_grid: u32,
+}
+
+@group(0) @binding(0) var<uniform> inspect: InspectParams;
+
+// Cells narrower than this many pixels get no grid lines, which would
+// only tint the view; they fade in over the next as many
+const GRID_MIN_PIXELS: f32 = 4.0;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_inspect(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = inspect.viewport.xy + uvs[vid] * inspect.viewport.zw;
+    return out;
+}
+
+@fragment
+fn fs_inspect(in: VSOut) -> @location(0) vec4<f32> {
+    let p = in.uv * f32(inspect.grid_size);
+    // Cells per pixel along each axis
+    let px = max(fwidth(p), vec2<f32>(1e-6));
+
+    var color = vec4<f32>(0.0);
+    if (inspect.show_grid != 0u) {
+        // Pixels to the nearest cell boundary, drawn a pixel wide
+        let d = abs(fract(p + 0.5) - 0.5) / px;
+        let line = 1.0 - clamp(min(d.x, d.y) - 0.5, 0.0, 1.0);
+        let fade = clamp((1.0 / max(px.x, px.y) - GRID_MIN_PIXELS) / GRID_MIN_PIXELS, 0.0, 1.0);
+        color = vec4<f32>(0.8, 0.85, 0.9, line * fade * 0.3);
+    }
+    if (inspect.cell.x >= 0) {
+        // Amber outline straddling the inspected cell's edge, so it still
+        // shows where a cell is smaller than a pixel
+        let q = (p - vec2<f32>(inspect.cell)) / px;
+        let size = 1.0 / px;
+        let outside = max(max(-q.x, q.x - size.x), max(-q.y, q.y - size.y));
+        let inside = min(min(q.x, size.x - q.x), min(q.y, size.y - q.y));
+        if (outside < 2.0 && (outside > 0.0 || inside < 1.5)) {
+            color = vec4<f32>(1.0, 0.75, 0.2, 0.9);
+        }
+    }
+    return color;
+}
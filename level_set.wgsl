@@ -0,0 +1,258 @@
+// ============================================================
+// Level set liquid (--level-set): the liquid is where a signed distance
+// field φ is negative. φ is advected with the velocity and redistanced so it
+// stays a distance; its sign tells fluid.wgsl's projection which cells are
+// air (held at zero pressure, a free surface). The fluid's own velocity
+// texture carries the flow, extrapolated a few cells past the surface so
+// the surface has a velocity to move with.
+// ============================================================
+struct LevelSetParams {
+    grid_size: u32,
+    dt: f32,
+    // Physical cell size, as in SimParams
+    cell_size: vec2<f32>,
+    // Body force in cells/s², +y down
+    gravity: vec2<f32>,
+    // Brush center and its movement this frame, in grid cells
+    mouse_pos: vec2<f32>,
+    mouse_delta: vec2<f32>,
+    radius: f32,
+    // 1 while the left button drags the liquid, 2 while the right one pours
+    mouse_mode: u32,
+    // Surface tension coefficient in cells³/s², 0 for none
+    surface_tension: f32,
+    _pad0: f32,
+    _pad1: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: LevelSetParams;
+// The fluid's velocity and dye textures; the liquid fraction goes in the dye
+@group(0) @binding(1) var velocity: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(2) var density: texture_storage_2d<rgba16float, read_write>;
+// x = φ in cells, negative in the liquid. The redistancing sweeps swap the
+// two between bind groups.
+@group(0) @binding(3) var phi_src: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(4) var phi_dst: texture_storage_2d<rgba16float, read_write>;
+// The velocity extrapolated past the surface, which advect traces back in
+@group(0) @binding(5) var extended: texture_storage_2d<rgba16float, read_write>;
+
+// φ is only kept as a distance this close to the surface; farther out it
+// is clamped, which the renderer and the extrapolation don't look past
+const BAND: f32 = 6.0;
+// Cells past the surface the velocity is extrapolated to
+const EXTEND: i32 = 3;
+// Pseudo-time step of a redistancing sweep, in cells
+const REDISTANCE_STEP: f32 = 0.5;
+// Half-width in cells of the band surface tension acts in
+const TENSION_BAND: f32 = 1.5;
+// σ·dt² stays below this (in cells³), under the capillary wave limit of an
+// explicit surface tension step
+const MAX_CAPILLARY: f32 = 0.1;
+
+const MOUSE_DRAG: u32 = 1u;
+const MOUSE_POUR: u32 = 2u;
+
+fn clamp_cell(c: vec2<i32>) -> vec2<i32> {
+    return clamp(c, vec2<i32>(0), vec2<i32>(i32(params.grid_size) - 1));
+}
+
+// Bilinear sample of the extended velocity and of φ at `p` (in cells,
+// centers at +0.5), clamped to the grid
+fn sample_extended(p: vec2<f32>) -> vec2<f32> {
+    let n = f32(params.grid_size);
+    let q = clamp(p, vec2<f32>(0.5), vec2<f32>(n - 0.5)) - 0.5;
+    let c = vec2<i32>(floor(q));
+    let f = q - floor(q);
+    let a = textureLoad(extended, clamp_cell(c)).xy;
+    let b = textureLoad(extended, clamp_cell(c + vec2<i32>(1, 0))).xy;
+    let d = textureLoad(extended, clamp_cell(c + vec2<i32>(0, 1))).xy;
+    let e = textureLoad(extended, clamp_cell(c + vec2<i32>(1, 1))).xy;
+    return mix(mix(a, b, f.x), mix(d, e, f.x), f.y);
+}
+
+fn sample_phi(p: vec2<f32>) -> f32 {
+    let n = f32(params.grid_size);
+    let q = clamp(p, vec2<f32>(0.5), vec2<f32>(n - 0.5)) - 0.5;
+    let c = vec2<i32>(floor(q));
+    let f = q - floor(q);
+    let a = textureLoad(phi_src, clamp_cell(c)).x;
+    let b = textureLoad(phi_src, clamp_cell(c + vec2<i32>(1, 0))).x;
+    let d = textureLoad(phi_src, clamp_cell(c + vec2<i32>(0, 1))).x;
+    let e = textureLoad(phi_src, clamp_cell(c + vec2<i32>(1, 1))).x;
+    return mix(mix(a, b, f.x), mix(d, e, f.x), f.y);
+}
+
+// Share of a cell that's liquid, from φ at its center
+fn liquid_fraction(phi: f32) -> f32 {
+    return clamp(0.5 - phi, 0.0, 1.0);
+}
+
+// Unit normal pointing out of the liquid
+fn phi_normal(c: vec2<i32>) -> vec2<f32> {
+    let c0 = clamp_cell(c);
+    let g = 0.5 * vec2<f32>(
+        textureLoad(phi_src, clamp_cell(c0 + vec2<i32>(1, 0))).x - textureLoad(phi_src, clamp_cell(c0 - vec2<i32>(1, 0))).x,
+        textureLoad(phi_src, clamp_cell(c0 + vec2<i32>(0, 1))).x - textureLoad(phi_src, clamp_cell(c0 - vec2<i32>(0, 1))).x,
+    );
+    let len = length(g);
+    return select(vec2<f32>(0.0), g / len, len > 1e-3);
+}
+
+// Surface tension as a continuum force: -σκn spread over the cells within
+// TENSION_BAND of the surface by a smoothed delta of φ, κ being the
+// divergence of the normal. It pulls convex bumps in and pushes dents out,
+// so a blob rounds off into a drop.
+fn surface_tension_force(c: vec2<i32>, phi: f32) -> vec2<f32> {
+    if (params.surface_tension <= 0.0 || abs(phi) >= TENSION_BAND) { return vec2<f32>(0.0); }
+    let kappa = 0.5 * (phi_normal(c + vec2<i32>(1, 0)).x - phi_normal(c - vec2<i32>(1, 0)).x
+        + phi_normal(c + vec2<i32>(0, 1)).y - phi_normal(c - vec2<i32>(0, 1)).y);
+    let delta = (1.0 + cos(3.14159265 * phi / TENSION_BAND)) / (2.0 * TENSION_BAND);
+    let sigma = min(params.surface_tension, MAX_CAPILLARY / (params.dt * params.dt));
+    // Tighter than a cell's radius is grid noise, not curvature
+    return -sigma * clamp(kappa, -1.0, 1.0) * delta * phi_normal(c);
+}
+
+// ============================================================
+// Velocity extrapolation: velocity -> extended
+// ============================================================
+// Liquid cells keep their velocity. Air cells near the surface take the
+// mean of the liquid cells within EXTEND, weighted toward the nearest, so
+// a surface cell's backtrace lands on the liquid's motion and not on the
+// stale velocity the projection left in the air.
+@compute @workgroup_size(8, 8)
+fn extrapolate(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let phi = textureLoad(phi_src, c).x;
+    if (phi <= 0.0) {
+        textureStore(extended, c, textureLoad(velocity, c));
+        return;
+    }
+    var sum = vec2<f32>(0.0);
+    var weight = 0.0;
+    if (phi < f32(EXTEND) + 1.0) {
+        for (var dy = -EXTEND; dy <= EXTEND; dy++) {
+            for (var dx = -EXTEND; dx <= EXTEND; dx++) {
+                let n = c + vec2<i32>(dx, dy);
+                if (any(n != clamp_cell(n)) || textureLoad(phi_src, n).x > 0.0) { continue; }
+                let w = 1.0 / f32(dx * dx + dy * dy);
+                sum += w * textureLoad(velocity, n).xy;
+                weight += w;
+            }
+        }
+    }
+    let v = select(vec2<f32>(0.0), sum / weight, weight > 0.0);
+    textureStore(extended, c, vec4<f32>(v, 0.0, 0.0));
+}
+
+// ============================================================
+// Advection and forces: extended, phi_src -> velocity, phi_dst
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn advect(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let center = vec2<f32>(c) + 0.5;
+    let back = center - params.dt * textureLoad(extended, c).xy / params.cell_size;
+    var v = sample_extended(back) + params.dt * (params.gravity + surface_tension_force(c, textureLoad(phi_src, c).x));
+    var phi = sample_phi(back);
+
+    let d = (center - params.mouse_pos) * params.cell_size;
+    if (params.mouse_mode == MOUSE_DRAG) {
+        // Dragged along at the stroke's speed, as in FLIP mode
+        let w = exp(-dot(d, d) / (params.radius * params.radius));
+        v = mix(v, params.mouse_delta * params.cell_size / params.dt, w);
+    } else if (params.mouse_mode == MOUSE_POUR) {
+        // A disc of new liquid, moving with the stroke
+        let disc = length(center - params.mouse_pos) - params.radius * 0.5;
+        if (disc < phi) {
+            phi = disc;
+            if (disc < 0.0) { v = params.mouse_delta * params.cell_size / params.dt; }
+        }
+    }
+
+    // Free slip at the walls: nothing flows out through them
+    let last = i32(params.grid_size) - 1;
+    if ((c.x == 0 && v.x < 0.0) || (c.x == last && v.x > 0.0)) { v.x = 0.0; }
+    if ((c.y == 0 && v.y < 0.0) || (c.y == last && v.y > 0.0)) { v.y = 0.0; }
+    textureStore(velocity, c, vec4<f32>(v, 0.0, 0.0));
+    textureStore(phi_dst, c, vec4<f32>(clamp(phi, -BAND, BAND), 0.0, 0.0, 0.0));
+}
+
+// ============================================================
+// Redistancing: phi_src -> phi_dst, and the liquid fraction
+// ============================================================
+// One sweep of φτ + sign(φ)(|∇φ| - 1) = 0 with Godunov's upwind gradient.
+// Advection bends φ away from a distance; this pulls it back while
+// leaving the zero crossing about where it is. Each sweep also writes the
+// liquid fraction into the dye, where fluid.wgsl's is_air reads it.
+@compute @workgroup_size(8, 8)
+fn redistance(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let phi = textureLoad(phi_src, c).x;
+    let back_x = phi - textureLoad(phi_src, clamp_cell(c - vec2<i32>(1, 0))).x;
+    let ahead_x = textureLoad(phi_src, clamp_cell(c + vec2<i32>(1, 0))).x - phi;
+    let back_y = phi - textureLoad(phi_src, clamp_cell(c - vec2<i32>(0, 1))).x;
+    let ahead_y = textureLoad(phi_src, clamp_cell(c + vec2<i32>(0, 1))).x - phi;
+    // Take the differences from the side the distance grows away from
+    var gx: f32;
+    var gy: f32;
+    if (phi > 0.0) {
+        gx = max(max(back_x, 0.0), -min(ahead_x, 0.0));
+        gy = max(max(back_y, 0.0), -min(ahead_y, 0.0));
+    } else {
+        gx = max(-min(back_x, 0.0), max(ahead_x, 0.0));
+        gy = max(-min(back_y, 0.0), max(ahead_y, 0.0));
+    }
+    let smooth_sign = phi / sqrt(phi * phi + 1.0);
+    let next = clamp(phi - REDISTANCE_STEP * smooth_sign * (sqrt(gx * gx + gy * gy) - 1.0), -BAND, BAND);
+    textureStore(phi_dst, c, vec4<f32>(next, 0.0, 0.0, 0.0));
+    let fraction = liquid_fraction(next);
+    textureStore(density, c, vec4<f32>(fraction, 0.0, fraction, fraction));
+}
+
+// ============================================================
+// Render: the liquid shaded by depth below its surface
+// ============================================================
+// Numbered after the compute bindings, so both fit one module
+@group(0) @binding(6) var phi_tex: texture_2d<f32>;
+@group(0) @binding(7) var velocity_tex: texture_2d<f32>;
+@group(0) @binding(8) var field_sampler: sampler;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = uvs[vid];
+    return out;
+}
+
+@fragment
+fn fs_level_set(in: VSOut) -> @location(0) vec4<f32> {
+    let phi = textureSampleLevel(phi_tex, field_sampler, in.uv, 0.0).x;
+    let speed = length(textureSampleLevel(velocity_tex, field_sampler, in.uv, 0.0).xy);
+    let air = mix(vec3<f32>(0.03, 0.03, 0.05), vec3<f32>(0.08, 0.09, 0.12), in.uv.y);
+    // Lighter near the surface, deep blue a band's width under it, and
+    // foamy where it's fast
+    let depth = clamp(-phi / BAND, 0.0, 1.0);
+    var liquid = mix(vec3<f32>(0.2, 0.55, 0.85), vec3<f32>(0.03, 0.15, 0.45), depth);
+    liquid = mix(liquid, vec3<f32>(0.85, 0.95, 1.0), clamp(speed / 250.0, 0.0, 0.6));
+    // A one-pixel-soft edge and a bright rim along the surface itself
+    let px = max(fwidth(phi), 1e-3);
+    let inside = clamp(0.5 - phi / px, 0.0, 1.0);
+    let rim = 1.0 - smoothstep(0.0, 1.0, abs(phi));
+    let color = mix(air, liquid, inside) + vec3<f32>(0.35, 0.45, 0.5) * rim * inside;
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,67 @@
+// ============================================================
+// Motion blur (Shift + B, --motion-blur): every frame is blended into a
+// running accumulation, which fades the earlier frames by the decay each
+// frame, and the accumulation is what's shown
+// ============================================================
+struct BlurParams {
+    // Dithering mode of the target the accumulation is shown in, see
+    // `dither`: 0 when that's the tonemapper's HDR one
+    dither: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+// The frame being blended in, or the accumulation being shown
+@group(0) @binding(0) var blur_src: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> blur: BlurParams;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    return vec4<f32>(positions[vid], 0.0, 1.0);
+}
+
+// ============================================================
+// Dithering before the window quantizes, as in render.wgsl: `mode` 0 off,
+// 1 an 8-bit target, 2 an sRGB one
+// ============================================================
+// Jimenez's interleaved gradient noise: uniform in 0..1 and spread evenly
+// over neighboring pixels, with little of the clumping white noise has
+fn gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.982919 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+fn srgb_encode(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn dither(color: vec3<f32>, pixel: vec2<f32>, mode: u32) -> vec3<f32> {
+    if (mode == 0u) { return color; }
+    let c = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    // Two samples make the noise triangular, ±1 step: its error no longer
+    // depends on the color, which a single uniform sample's does
+    let p = floor(pixel);
+    let n = (gradient_noise(p) + gradient_noise(p + vec2<f32>(113.0, 71.0)) - 1.0) / 255.0;
+    if (mode == 2u) {
+        return srgb_decode(clamp(srgb_encode(c) + n, vec3<f32>(0.0), vec3<f32>(1.0)));
+    }
+    return clamp(c + n, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// The blend constant does the mixing: src · (1 - decay) + dst · decay
+@fragment
+fn fs_accumulate(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureLoad(blur_src, vec2<i32>(pos.xy), 0).rgb, 1.0);
+}
+
+@fragment
+fn fs_present(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(dither(textureLoad(blur_src, vec2<i32>(pos.xy), 0).rgb, pos.xy, blur.dither), 1.0);
+}
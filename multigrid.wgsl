@@ -0,0 +1,143 @@
+// ============================================================
+// Geometric multigrid for the pressure Poisson equation
+// ============================================================
+// Solves lap(p) = b on a cell-centered grid with the same boundaries as the
+// Jacobi kernels in fluid.wgsl: clamped (Neumann) walls, wrapping edges, or
+// open edges with the pressure held at 0 outside (Dirichlet). Level 0 is the simulation
+// grid (`pressure` / `divergence_tex`); each coarser level halves the
+// resolution and doubles the cell size (dx, dy).
+//
+// One bind group per level: the level's own pressure/rhs/residual plus the
+// next coarser level's pressure/rhs, so restriction and prolongation need
+// no extra bindings.
+struct LevelParams {
+    size: u32,
+    coarse_size: u32,
+    // (1/dx², 1/dy²) at this level
+    inv_h2: vec2<f32>,
+    // SimParams::boundary: 0 clamps at the edges, 1 wraps, 2 holds 0 outside
+    boundary: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform> level: LevelParams;
+@group(0) @binding(1) var p: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(2) var b: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(3) var r: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(4) var p_coarse: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(5) var b_coarse: texture_storage_2d<rgba16float, read_write>;
+// Nonzero skips every kernel. Set by the CG solver once it has converged
+// (the V-cycle is its preconditioner); always 0 when multigrid runs alone.
+@group(0) @binding(6) var<storage, read> gate: u32;
+
+// ============================================================
+// Helpers
+// ============================================================
+// Where a tap at c reads on a level of `size` cells: clamped onto the edge,
+// or from the other side when the edges wrap.
+fn edge_cell(c: vec2<i32>, size: u32) -> vec2<i32> {
+    let n = vec2<i32>(i32(size));
+    if (level.boundary == 1u) { return (c % n + n) % n; }
+    return clamp(c, vec2<i32>(0), n - 1);
+}
+
+// A tap of `tex` on a level of `size` cells; 0 past an open edge.
+fn load_level(tex: texture_storage_2d<rgba16float, read_write>, c: vec2<i32>, size: u32) -> f32 {
+    let outside = any(c < vec2<i32>(0)) || any(c >= vec2<i32>(i32(size)));
+    if (outside && level.boundary == 2u) { return 0.0; }
+    return textureLoad(tex, edge_cell(c, size)).x;
+}
+
+fn load_p(c: vec2<i32>) -> f32 {
+    return load_level(p, c, level.size);
+}
+
+fn load_r(c: vec2<i32>) -> f32 {
+    return load_level(r, c, level.size);
+}
+
+fn load_p_coarse(c: vec2<i32>) -> f32 {
+    return load_level(p_coarse, c, level.coarse_size);
+}
+
+// Neighbour sums along x and y, kept apart for non-square cells.
+fn neighbor_sums(c: vec2<i32>) -> vec2<f32> {
+    return vec2<f32>(
+        load_p(c + vec2<i32>(-1, 0)) + load_p(c + vec2<i32>(1, 0)),
+        load_p(c + vec2<i32>(0, -1)) + load_p(c + vec2<i32>(0, 1)),
+    );
+}
+
+// Red-black Gauss-Seidel: each pass updates one checkerboard color in place,
+// reading only cells of the other color.
+fn gauss_seidel(gid: vec3<u32>, color: u32) {
+    if (gate != 0u || gid.x >= level.size || gid.y >= level.size) { return; }
+    if (((gid.x + gid.y) & 1u) != color) { return; }
+    let c = vec2<i32>(gid.xy);
+    let rhs = textureLoad(b, c).x;
+    let new_p = (dot(neighbor_sums(c), level.inv_h2) - rhs) / (2.0 * (level.inv_h2.x + level.inv_h2.y));
+    textureStore(p, c, vec4<f32>(new_p, 0.0, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: smoothing
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn smooth_red(@builtin(global_invocation_id) gid: vec3<u32>) {
+    gauss_seidel(gid, 0u);
+}
+
+@compute @workgroup_size(8, 8)
+fn smooth_black(@builtin(global_invocation_id) gid: vec3<u32>) {
+    gauss_seidel(gid, 1u);
+}
+
+// ============================================================
+// Compute: residual r = b - lap(p)
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn residual(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gate != 0u || gid.x >= level.size || gid.y >= level.size) { return; }
+    let c = vec2<i32>(gid.xy);
+    let lap = dot(neighbor_sums(c) - 2.0 * load_p(c), level.inv_h2);
+    let res = textureLoad(b, c).x - lap;
+    textureStore(r, c, vec4<f32>(res, 0.0, 0.0, 0.0));
+}
+
+// ============================================================
+// Compute: restrict residual to the coarse rhs, clear coarse p
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn restrict_residual(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gate != 0u || gid.x >= level.coarse_size || gid.y >= level.coarse_size) { return; }
+    let cc = vec2<i32>(gid.xy);
+    let f = cc * 2;
+    // 2x2 average; on odd sizes the last coarse cell's outer children clamp
+    // back onto the edge (or wrap, which makes the coarse level only
+    // approximately periodic; the fine smoothing absorbs the difference).
+    let avg = 0.25 * (load_r(f) + load_r(f + vec2<i32>(1, 0))
+                    + load_r(f + vec2<i32>(0, 1)) + load_r(f + vec2<i32>(1, 1)));
+    textureStore(b_coarse, cc, vec4<f32>(avg, 0.0, 0.0, 0.0));
+    textureStore(p_coarse, cc, vec4<f32>(0.0));
+}
+
+// ============================================================
+// Compute: prolongate the coarse correction and add it to p
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn prolongate(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gate != 0u || gid.x >= level.size || gid.y >= level.size) { return; }
+    let c = vec2<i32>(gid.xy);
+
+    // Bilinear between coarse cell centers
+    let pos = (vec2<f32>(gid.xy) + 0.5) * 0.5 - 0.5;
+    let i = vec2<i32>(floor(pos));
+    let t = pos - floor(pos);
+    let e00 = load_p_coarse(i);
+    let e10 = load_p_coarse(i + vec2<i32>(1, 0));
+    let e01 = load_p_coarse(i + vec2<i32>(0, 1));
+    let e11 = load_p_coarse(i + vec2<i32>(1, 1));
+    let e = mix(mix(e00, e10, t.x), mix(e01, e11, t.x), t.y);
+
+    textureStore(p, c, vec4<f32>(textureLoad(p, c).x + e, 0.0, 0.0, 0.0));
+}
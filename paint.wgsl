@@ -0,0 +1,45 @@
+// ============================================================
+// Obstacle painting: stamp or erase a capsule in the obstacle mask
+// ============================================================
+struct PaintParams {
+    // Stroke segment from the previous cursor position, in grid cells
+    start: vec2<f32>,
+    end: vec2<f32>,
+    radius: f32,
+    // 1 = paint solid, 0 = erase
+    solid: u32,
+    grid_size: u32,
+    _pad: f32,
+}
+
+@group(0) @binding(0) var<uniform> paint: PaintParams;
+// r = solid mask, g = signed distance to the nearest obstacle surface in cells
+@group(0) @binding(1) var obstacles: texture_storage_2d<rgba16float, read_write>;
+
+// Distance from q to the segment a-b.
+fn segment_distance(q: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> f32 {
+    let ab = b - a;
+    let t = clamp(dot(q - a, ab) / max(dot(ab, ab), 1e-6), 0.0, 1.0);
+    return length(q - a - ab * t);
+}
+
+// Painting unions the brush into the mask, erasing subtracts it. The signed
+// distance is updated the same way (min for a union, max for a subtraction),
+// which is exact outside the solids and close enough inside for particles.
+@compute @workgroup_size(8, 8)
+fn paint_obstacles(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= paint.grid_size || gid.y >= paint.grid_size) { return; }
+    let p = vec2<i32>(gid.xy);
+    let d = segment_distance(vec2<f32>(gid.xy) + vec2<f32>(0.5), paint.start, paint.end) - paint.radius;
+    let o = textureLoad(obstacles, p).xy;
+    var mask = o.x;
+    var sdf: f32;
+    if (paint.solid != 0u) {
+        if (d < 0.0) { mask = 1.0; }
+        sdf = min(o.y, d);
+    } else {
+        if (d < 0.0) { mask = 0.0; }
+        sdf = max(o.y, -d);
+    }
+    textureStore(obstacles, p, vec4<f32>(mask, sdf, 0.0, 0.0));
+}
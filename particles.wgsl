@@ -0,0 +1,397 @@
+// ============================================================
+// Particle params — shared by the tracer/trail kernels
+// ============================================================
+struct ParticleParams {
+    grid_size: u32,
+    trail_size: u32,
+    count: u32,
+    frame: u32,
+    dt: f32,
+    fade: f32,
+    max_life: f32,
+    color_mode: u32,
+    emitter_pos: vec2<f32>,
+    emitter_active: u32,
+    emitter_radius: f32,
+    emitter_color: vec4<f32>,
+    reseed_mode: u32,
+    spawn_budget: u32,
+    // Physical cell size, see SimParams in fluid.wgsl
+    cell_size: vec2<f32>,
+}
+
+// color_mode values
+const COLOR_WHITE: u32 = 0u;
+const COLOR_ORIGIN: u32 = 1u;
+const COLOR_AGE: u32 = 2u;
+
+// reseed_mode values
+const RESEED_UNIFORM: u32 = 0u;
+const RESEED_DENSITY: u32 = 1u;
+const RESEED_INFLOW: u32 = 2u;
+const RESEED_CURSOR: u32 = 3u;
+
+// Candidate positions tried per respawn before giving up until next frame
+const RESEED_TRIES: u32 = 8u;
+
+// Particles are kept at least this far (in cells) from obstacle surfaces
+const OBSTACLE_SKIN: f32 = 0.5;
+
+struct Particle {
+    pos: vec2<f32>,
+    prev: vec2<f32>,
+    life: f32,
+    age: f32,
+    _pad: vec2<f32>,
+    color: vec4<f32>,
+}
+
+struct Counters {
+    dead_count: atomic<u32>,
+}
+
+// ============================================================
+// Bindings — group 0
+// ============================================================
+@group(0) @binding(0) var<uniform> pparams: ParticleParams;
+@group(0) @binding(1) var velocity: texture_2d<f32>;
+@group(0) @binding(2) var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(3) var trail: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(4) var trail_tmp: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(5) var density: texture_2d<f32>;
+@group(0) @binding(6) var<storage, read_write> dead_list: array<u32>;
+@group(0) @binding(7) var<storage, read_write> counters: Counters;
+// r = solid mask, g = signed distance to the nearest obstacle surface in cells
+@group(0) @binding(8) var obstacles: texture_2d<f32>;
+
+// ============================================================
+// Helpers
+// ============================================================
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn rand01(seed: u32) -> f32 {
+    return f32(pcg(seed)) / 4294967295.0;
+}
+
+// Smooth cosine rainbow, h in 0..1
+fn palette(h: f32) -> vec3<f32> {
+    return 0.5 + 0.5 * cos(6.2832 * (h + vec3<f32>(0.0, 0.33, 0.67)));
+}
+
+fn particle_color(p: Particle) -> vec3<f32> {
+    switch pparams.color_mode {
+        case COLOR_ORIGIN: { return p.color.rgb; }
+        case COLOR_AGE: {
+            // young = hot white-yellow, old = deep violet
+            let t = clamp(p.age / pparams.max_life, 0.0, 1.0);
+            return mix(vec3<f32>(1.0, 0.95, 0.7), vec3<f32>(0.35, 0.1, 0.8), sqrt(t));
+        }
+        default: { return vec3<f32>(0.75, 0.85, 1.0); }
+    }
+}
+
+// Bilinear lookup of a grid-sized field at a position in grid cells.
+fn sample_grid(tex: texture_2d<f32>, pos: vec2<f32>) -> vec4<f32> {
+    let size = i32(pparams.grid_size);
+    let pp = clamp(pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(f32(size) - 1.001));
+    let i = vec2<i32>(floor(pp));
+    let f = fract(pp);
+    let hi = vec2<i32>(size - 1);
+
+    let v00 = textureLoad(tex, clamp(i, vec2<i32>(0), hi), 0);
+    let v10 = textureLoad(tex, clamp(i + vec2<i32>(1, 0), vec2<i32>(0), hi), 0);
+    let v01 = textureLoad(tex, clamp(i + vec2<i32>(0, 1), vec2<i32>(0), hi), 0);
+    let v11 = textureLoad(tex, clamp(i + vec2<i32>(1, 1), vec2<i32>(0), hi), 0);
+
+    return mix(mix(v00, v10, f.x), mix(v01, v11, f.x), f.y);
+}
+
+fn sample_vel(pos: vec2<f32>) -> vec2<f32> {
+    return sample_grid(velocity, pos).xy;
+}
+
+fn sample_sdf(pos: vec2<f32>) -> f32 {
+    return sample_grid(obstacles, pos).y;
+}
+
+// Direction away from the nearest obstacle surface.
+fn sdf_normal(pos: vec2<f32>) -> vec2<f32> {
+    let gx = sample_sdf(pos + vec2<f32>(1.0, 0.0)) - sample_sdf(pos - vec2<f32>(1.0, 0.0));
+    let gy = sample_sdf(pos + vec2<f32>(0.0, 1.0)) - sample_sdf(pos - vec2<f32>(0.0, 1.0));
+    let g = vec2<f32>(gx, gy);
+    let len = length(g);
+    if (len < 1e-5) { return vec2<f32>(0.0); }
+    return g / len;
+}
+
+// Resolve a step from `prev` to `pos` against the obstacles. The part of the
+// step heading into the surface is dropped so the particle slides along it,
+// and anything still closer than OBSTACLE_SKIN is pushed back out. The
+// normal is taken at `prev`, which is outside, so a step that overshoots the
+// middle of a thin wall still gets pushed back to the side it came from.
+fn collide(prev: vec2<f32>, pos: vec2<f32>) -> vec2<f32> {
+    if (sample_sdf(pos) >= OBSTACLE_SKIN) { return pos; }
+
+    let n = sdf_normal(prev);
+    var step = pos - prev;
+    step = step - n * min(dot(step, n), 0.0);
+    var q = prev + step;
+    let d = sample_sdf(q);
+    if (d < OBSTACLE_SKIN) { q = q + n * (OBSTACLE_SKIN - d); }
+    return q;
+}
+
+fn safe_load_trail(p: vec2<i32>) -> vec3<f32> {
+    let size = i32(pparams.trail_size);
+    let cp = clamp(p, vec2<i32>(0), vec2<i32>(size - 1));
+    return textureLoad(trail, cp).rgb;
+}
+
+// ============================================================
+// Compute: move live particles along the flow
+// ============================================================
+@compute @workgroup_size(64)
+fn advect_particles(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid.x;
+    if (idx >= pparams.count) { return; }
+
+    var p = particles[idx];
+    if (p.life <= 0.0) { return; }
+
+    let size = f32(pparams.grid_size);
+    p.prev = p.pos;
+    p.pos = collide(p.prev, p.pos + sample_vel(p.pos) * pparams.dt / pparams.cell_size);
+    p.life = p.life - pparams.dt;
+    p.age = p.age + pparams.dt;
+
+    let outside = p.pos.x < 0.0 || p.pos.y < 0.0 || p.pos.x >= size || p.pos.y >= size;
+    if (outside) { p.life = 0.0; }
+
+    particles[idx] = p;
+}
+
+// ============================================================
+// Compute: gather dead particles into a compact list
+// ============================================================
+@compute @workgroup_size(1)
+fn reset_counters() {
+    atomicStore(&counters.dead_count, 0u);
+}
+
+@compute @workgroup_size(64)
+fn compact_dead(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid.x;
+    if (idx >= pparams.count) { return; }
+    if (particles[idx].life > 0.0) { return; }
+
+    let slot = atomicAdd(&counters.dead_count, 1u);
+    dead_list[slot] = idx;
+}
+
+// ============================================================
+// Compute: respawn up to spawn_budget dead particles
+// ============================================================
+fn cursor_spawn(seed: u32) -> vec2<f32> {
+    let a = rand01(seed) * 6.2832;
+    let r = sqrt(rand01(seed + 1u)) * pparams.emitter_radius;
+    return pparams.emitter_pos + vec2<f32>(cos(a), sin(a)) * r / pparams.cell_size;
+}
+
+// Rejection-sample a position, weighted by the dye density there.
+fn density_spawn(seed: u32) -> vec2<f32> {
+    let size = f32(pparams.grid_size);
+    var pos = vec2<f32>(0.0);
+    for (var t = 0u; t < RESEED_TRIES; t = t + 1u) {
+        let s = seed + t * 3u;
+        pos = vec2<f32>(rand01(s), rand01(s + 1u)) * size;
+        // Dye is rgb in xzw (y is temperature); take the strongest channel
+        let c = textureLoad(density, vec2<i32>(pos), 0);
+        let d = max(c.x, max(c.z, c.w));
+        if (rand01(s + 2u) < clamp(d, 0.0, 1.0)) { break; }
+    }
+    return pos;
+}
+
+// Pick a point on the domain edge where the flow is entering. Returns a
+// negative position if none of the candidates had inflow.
+fn inflow_spawn(seed: u32) -> vec2<f32> {
+    let size = f32(pparams.grid_size);
+    for (var t = 0u; t < RESEED_TRIES; t = t + 1u) {
+        let s = seed + t * 3u;
+        let side = pcg(s) % 4u;
+        let u = rand01(s + 1u) * size;
+        var pos: vec2<f32>;
+        var inward: vec2<f32>;
+        switch side {
+            case 0u: { pos = vec2<f32>(0.5, u); inward = vec2<f32>(1.0, 0.0); }
+            case 1u: { pos = vec2<f32>(size - 0.5, u); inward = vec2<f32>(-1.0, 0.0); }
+            case 2u: { pos = vec2<f32>(u, 0.5); inward = vec2<f32>(0.0, 1.0); }
+            default: { pos = vec2<f32>(u, size - 0.5); inward = vec2<f32>(0.0, -1.0); }
+        }
+        if (dot(sample_vel(pos), inward) > 0.5) { return pos; }
+    }
+    return vec2<f32>(-1.0);
+}
+
+@compute @workgroup_size(64)
+fn reseed_particles(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let n = min(atomicLoad(&counters.dead_count), pparams.spawn_budget);
+    if (gid.x >= n) { return; }
+
+    let idx = dead_list[gid.x];
+    let seed = idx * 7u + pparams.frame * 9781u;
+    let size = f32(pparams.grid_size);
+    var p = particles[idx];
+
+    // While the cursor is down, half of the respawns come from it and carry
+    // the stroke's color; the rest follow the reseed mode and are colored by
+    // where in the domain they were born.
+    let from_cursor = pparams.reseed_mode == RESEED_CURSOR
+        || (pparams.emitter_active != 0u && (idx & 1u) == 0u);
+
+    var pos: vec2<f32>;
+    if (from_cursor) {
+        pos = cursor_spawn(seed);
+    } else {
+        switch pparams.reseed_mode {
+            case RESEED_DENSITY: { pos = density_spawn(seed); }
+            case RESEED_INFLOW: { pos = inflow_spawn(seed); }
+            default: { pos = vec2<f32>(rand01(seed), rand01(seed + 1u)) * size; }
+        }
+    }
+    // No inflow anywhere, or the spot is inside an obstacle: stay dead and
+    // try again next frame.
+    if (pos.x < 0.0 || sample_sdf(pos) < OBSTACLE_SKIN) { return; }
+
+    p.pos = clamp(pos, vec2<f32>(0.0), vec2<f32>(size - 0.001));
+    if (from_cursor) {
+        p.color = pparams.emitter_color;
+    } else {
+        let d = p.pos / size - vec2<f32>(0.5);
+        p.color = vec4<f32>(palette(atan2(d.y, d.x) / 6.2832 + 0.5), 1.0);
+    }
+    p.prev = p.pos;
+    p.life = pparams.max_life * (0.5 + 0.5 * rand01(seed + 5u));
+    p.age = 0.0;
+    particles[idx] = p;
+}
+
+// ============================================================
+// Compute: stamp each particle's last step into the trail
+// ============================================================
+@compute @workgroup_size(64)
+fn splat_particles(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid.x;
+    if (idx >= pparams.count) { return; }
+
+    let p = particles[idx];
+    if (p.life <= 0.0) { return; }
+
+    let scale = f32(pparams.trail_size) / f32(pparams.grid_size);
+    let a = p.prev * scale;
+    let b = p.pos * scale;
+    let steps = clamp(i32(ceil(distance(a, b))), 1, 16);
+
+    // Fade in over the first moments of life so respawns don't pop.
+    let strength = 0.35 * clamp(p.age * 2.0, 0.0, 1.0);
+    let add = particle_color(p) * strength;
+    let limit = i32(pparams.trail_size) - 1;
+
+    for (var s = 0; s <= steps; s = s + 1) {
+        let q = vec2<i32>(floor(mix(a, b, f32(s) / f32(steps))));
+        if (q.x < 0 || q.y < 0 || q.x > limit || q.y > limit) { continue; }
+        let t = textureLoad(trail, q).rgb;
+        textureStore(trail, q, vec4<f32>(min(t + add, vec3<f32>(1.5)), 0.0));
+    }
+}
+
+// ============================================================
+// Compute: advect + fade the trail accumulation texture
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn advect_trail(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= pparams.trail_size || gid.y >= pparams.trail_size) { return; }
+
+    let scale = f32(pparams.trail_size) / f32(pparams.grid_size);
+    let p = vec2<i32>(gid.xy);
+    let pos = vec2<f32>(f32(gid.x) + 0.5, f32(gid.y) + 0.5);
+    let vel = sample_vel(pos / scale) * scale;
+    let prev_pos = pos - vel * pparams.dt / pparams.cell_size;
+
+    let size = f32(pparams.trail_size);
+    let pp = clamp(prev_pos - vec2<f32>(0.5), vec2<f32>(0.0), vec2<f32>(size - 1.001));
+    let i = vec2<i32>(floor(pp));
+    let f = fract(pp);
+
+    let t00 = safe_load_trail(i);
+    let t10 = safe_load_trail(i + vec2<i32>(1, 0));
+    let t01 = safe_load_trail(i + vec2<i32>(0, 1));
+    let t11 = safe_load_trail(i + vec2<i32>(1, 1));
+
+    let sampled = mix(mix(t00, t10, f.x), mix(t01, t11, f.x), f.y) * pparams.fade;
+    textureStore(trail_tmp, p, vec4<f32>(sampled, 0.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn copy_trail(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= pparams.trail_size || gid.y >= pparams.trail_size) { return; }
+    let p = vec2<i32>(gid.xy);
+    let t = textureLoad(trail_tmp, p).rgb;
+    textureStore(trail, p, vec4<f32>(t, 0.0));
+}
+
+// ============================================================
+// Render: each live particle as a soft round sprite (Shift + T), added
+// over the view. One instance per particle, read straight from the
+// particle buffer; only `pparams` is bound.
+// ============================================================
+// Sprite radius in x-cells
+const SPRITE_RADIUS: f32 = 1.25;
+
+struct SpriteOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) offset: vec2<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+@vertex
+fn vs_sprite(
+    @builtin(vertex_index) vid: u32,
+    @location(0) pos: vec2<f32>,
+    @location(1) life_age: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> SpriteOut {
+    var corners = array<vec2<f32>, 4>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(-1.0, 1.0), vec2<f32>(1.0, 1.0)
+    );
+    var out: SpriteOut;
+    let corner = corners[vid];
+    let p = Particle(pos, pos, life_age.x, life_age.y, vec2<f32>(0.0), color);
+    // Fade in over the first half second as the trail does, and out over
+    // the last one
+    let fade = clamp(p.age * 2.0, 0.0, 1.0) * clamp(p.life, 0.0, 1.0);
+    out.color = particle_color(p) * fade;
+    out.offset = corner;
+    // Round on screen: the radius is in x-cells, so y is scaled by the
+    // cell's aspect
+    let cell = pos + corner * SPRITE_RADIUS / pparams.cell_size;
+    let uv = cell / f32(pparams.grid_size);
+    out.pos = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    if (p.life <= 0.0) {
+        // Dead: a degenerate strip draws nothing
+        out.pos = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return out;
+}
+
+@fragment
+fn fs_sprite(in: SpriteOut) -> @location(0) vec4<f32> {
+    let r2 = dot(in.offset, in.offset);
+    if (r2 > 1.0) { discard; }
+    let glow = exp(-4.0 * r2);
+    return vec4<f32>(in.color * glow * 0.6, 1.0);
+}
@@ -0,0 +1,136 @@
+// ============================================================
+// Gray–Scott reaction-diffusion (--gray-scott): two chemicals, u fed in
+// and v eating it (u + 2v -> 3v), diffusing at different rates. Depending
+// on the feed and kill rates v settles into spots, stripes or branching
+// coral. The chemicals can ride the fluid's velocity between reaction
+// steps, so stirring smears the pattern and it regrows along the swirls.
+// ============================================================
+struct ReactionParams {
+    grid_size: u32,
+    // The fluid's step, which the advection covers
+    dt: f32,
+    cell_size: vec2<f32>,
+    // Rate u is fed in at and rate v is removed at, per reaction step
+    feed: f32,
+    kill: f32,
+    // Nonzero to carry the chemicals with the flow
+    advect: u32,
+    // 1 while the right button seeds v
+    mouse_mode: u32,
+    // Brush center in grid cells, and its radius
+    mouse_pos: vec2<f32>,
+    radius: f32,
+    _pad: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: ReactionParams;
+// The fluid's velocity texture
+@group(0) @binding(1) var velocity: texture_storage_2d<rgba16float, read_write>;
+// x = u, y = v. Each pass reads one and writes the other; the bind groups
+// swap them.
+@group(0) @binding(2) var chem_src: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(3) var chem_dst: texture_storage_2d<rgba16float, read_write>;
+
+// Diffusion rates of u and v in cells² per reaction step. v at half u's
+// rate is what lets patterns form at all.
+const DIFFUSE_U: f32 = 1.0;
+const DIFFUSE_V: f32 = 0.5;
+
+const MOUSE_SEED: u32 = 1u;
+
+fn clamp_cell(c: vec2<i32>) -> vec2<i32> {
+    return clamp(c, vec2<i32>(0), vec2<i32>(i32(params.grid_size) - 1));
+}
+
+// Clamped to the grid, so nothing diffuses or flows through the edges
+fn load_chem(c: vec2<i32>) -> vec2<f32> {
+    return textureLoad(chem_src, clamp_cell(c)).xy;
+}
+
+fn sample_chem(p: vec2<f32>) -> vec2<f32> {
+    let n = f32(params.grid_size);
+    let q = clamp(p, vec2<f32>(0.5), vec2<f32>(n - 0.5)) - 0.5;
+    let c = vec2<i32>(floor(q));
+    let f = q - floor(q);
+    let a = load_chem(c);
+    let b = load_chem(c + vec2<i32>(1, 0));
+    let d = load_chem(c + vec2<i32>(0, 1));
+    let e = load_chem(c + vec2<i32>(1, 1));
+    return mix(mix(a, b, f.x), mix(d, e, f.x), f.y);
+}
+
+// ============================================================
+// Advection and seeding: chem_src -> chem_dst, once per frame
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn advect(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let center = vec2<f32>(c) + 0.5;
+    var chem = load_chem(c);
+    if (params.advect != 0u) {
+        chem = sample_chem(center - params.dt * textureLoad(velocity, c).xy / params.cell_size);
+    }
+    if (params.mouse_mode == MOUSE_SEED && length(center - params.mouse_pos) < params.radius) {
+        chem = vec2<f32>(0.5, 0.25);
+    }
+    textureStore(chem_dst, c, vec4<f32>(chem, 0.0, 0.0));
+}
+
+// ============================================================
+// One reaction-diffusion step: chem_src -> chem_dst
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn react(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let chem = load_chem(c);
+    // Nine-point Laplacian: the sides weigh 0.2, the corners 0.05
+    let sides = load_chem(c + vec2<i32>(1, 0)) + load_chem(c - vec2<i32>(1, 0))
+        + load_chem(c + vec2<i32>(0, 1)) + load_chem(c - vec2<i32>(0, 1));
+    let corners = load_chem(c + vec2<i32>(1, 1)) + load_chem(c + vec2<i32>(-1, 1))
+        + load_chem(c + vec2<i32>(1, -1)) + load_chem(c + vec2<i32>(-1, -1));
+    let lap = 0.2 * sides + 0.05 * corners - chem;
+    let uvv = chem.x * chem.y * chem.y;
+    let u = chem.x + DIFFUSE_U * lap.x - uvv + params.feed * (1.0 - chem.x);
+    let v = chem.y + DIFFUSE_V * lap.y + uvv - (params.feed + params.kill) * chem.y;
+    textureStore(chem_dst, c, vec4<f32>(clamp(u, 0.0, 1.0), clamp(v, 0.0, 1.0), 0.0, 0.0));
+}
+
+// ============================================================
+// Render: v through a coral ramp, embossed along its gradient
+// ============================================================
+// Numbered after the compute bindings, so both fit one module
+@group(0) @binding(4) var chem_tex: texture_2d<f32>;
+@group(0) @binding(5) var field_sampler: sampler;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = uvs[vid];
+    return out;
+}
+
+@fragment
+fn fs_reaction(in: VSOut) -> @location(0) vec4<f32> {
+    let v = textureSampleLevel(chem_tex, field_sampler, in.uv, 0.0).y;
+    let t = smoothstep(0.05, 0.35, v);
+    var color = mix(vec3<f32>(0.02, 0.04, 0.1), vec3<f32>(0.95, 0.42, 0.32), smoothstep(0.0, 0.6, t));
+    color = mix(color, vec3<f32>(1.0, 0.93, 0.82), smoothstep(0.6, 1.0, t));
+    // Lit from the top left, as if v were raised
+    let slope = vec2<f32>(dpdx(t), dpdy(t));
+    color *= clamp(1.0 + 4.0 * dot(slope, vec2<f32>(-0.7, -0.7)), 0.6, 1.4);
+    return vec4<f32>(color, 1.0);
+}
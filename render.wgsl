@@ -0,0 +1,589 @@
+// ============================================================
+// Render: fullscreen triangle, samples the sim fields
+// ============================================================
+@group(0) @binding(0) var render_density_tex: texture_2d<f32>;
+@group(0) @binding(1) var render_sampler: sampler;
+@group(0) @binding(2) var render_velocity_tex: texture_2d<f32>;
+@group(0) @binding(3) var render_trail_tex: texture_2d<f32>;
+@group(0) @binding(4) var render_obstacle_tex: texture_2d<f32>;
+// Per-cell work counters from fluid.wgsl, frame maximum in the last slot
+@group(0) @binding(5) var<storage, read> render_cost: array<u32>;
+// The state the difference view diffs against: the other copy while
+// comparing solvers, the stored reference otherwise
+@group(0) @binding(6) var render_other_density_tex: texture_2d<f32>;
+@group(0) @binding(7) var render_other_velocity_tex: texture_2d<f32>;
+
+struct ViewParams {
+    // Part of the domain drawn, (x, y, w, h) in texture coordinates
+    viewport: vec4<f32>,
+    // 0 dye, 1 temperature, 2 velocity x, 3 velocity y, 4 speed
+    diff_field: u32,
+    // Difference drawn at full color
+    diff_scale: f32,
+    // Simulated seconds, animating the sub-grid detail and fading the
+    // advected detail layers
+    time: f32,
+    // Strength of the advected detail layer, 0 = off
+    detail: f32,
+    // Wall shear rate (1/s) outlined thickest and hottest, 0 = no outline
+    wall_shear: f32,
+    // Field view (Q): 0 dye, 1 velocity, 2 pressure, 3 divergence,
+    // 4 vorticity, 5 line integral convolution, 6 schlieren
+    field: u32,
+    // Field value drawn at full color
+    field_scale: f32,
+    // 1 when drawing into the HDR target for tonemapping (--tonemap):
+    // dense dye glows past 1 instead of saturating
+    hdr: u32,
+    // Colormap (Shift + Q): 0 colors by flow direction, n reads row n - 1
+    // of the LUT
+    colormap: u32,
+    // Dithering of the window's colors, see `dither`
+    dither: u32,
+    // Dye shading (Shift + V): 0 plain, 1 tinted by the vorticity's sign,
+    // 2 darkened by its magnitude, 3 lit as a relief
+    shading: u32,
+    // Where the relief's light comes from (Shift + L), radians
+    // counterclockwise from the right of the screen
+    light_angle: f32,
+}
+@group(0) @binding(8) var<uniform> view: ViewParams;
+// x = curl, y = sub-grid turbulence (fluid.wgsl `subgrid_energy`)
+@group(0) @binding(9) var render_subgrid_tex: texture_2d<f32>;
+// Two layers of advected noise (detail.wgsl)
+@group(0) @binding(10) var render_detail_tex: texture_2d<f32>;
+// The last pressure solve and the divergence it removed, for the field views
+@group(0) @binding(11) var render_pressure_tex: texture_2d<f32>;
+@group(0) @binding(12) var render_divergence_tex: texture_2d<f32>;
+// Colormap LUTs (src/colormap.rs), one 256-texel row each
+@group(0) @binding(13) var render_lut_tex: texture_2d<f32>;
+// What glass shading (Shift + W) refracts: pool tiles or --background
+@group(0) @binding(14) var render_background_tex: texture_2d<f32>;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = view.viewport.xy + uvs[vid] * view.viewport.zw;
+    return out;
+}
+
+// ============================================================
+// Dithering (Shift + D): noise of about one 8-bit step added to the
+// color before the window quantizes it, so smooth dye gradients come out
+// as fine grain instead of bands. `mode`: 0 off (an HDR target, or
+// --no-dither), 1 an 8-bit target stored as is, 2 an sRGB one, where the
+// steps are even in the encoded value rather than the linear one
+// ============================================================
+// Jimenez's interleaved gradient noise: uniform in 0..1 and spread evenly
+// over neighboring pixels, with little of the clumping white noise has
+fn gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.982919 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+fn srgb_encode(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn dither(color: vec3<f32>, pixel: vec2<f32>, mode: u32) -> vec3<f32> {
+    if (mode == 0u) { return color; }
+    let c = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    // Two samples make the noise triangular, ±1 step: its error no longer
+    // depends on the color, which a single uniform sample's does
+    let p = floor(pixel);
+    let n = (gradient_noise(p) + gradient_noise(p + vec2<f32>(113.0, 71.0)) - 1.0) / 255.0;
+    if (mode == 2u) {
+        return srgb_decode(clamp(srgb_encode(c) + n, vec3<f32>(0.0), vec3<f32>(1.0)));
+    }
+    return clamp(c + n, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// HSV to RGB conversion
+fn hsv2rgb(h: f32, s: f32, v: f32) -> vec3<f32> {
+    let c = v * s;
+    let hp = h * 6.0;
+    let x = c * (1.0 - abs(hp % 2.0 - 1.0));
+    let m = v - c;
+    var rgb: vec3<f32>;
+    if (hp < 1.0) { rgb = vec3<f32>(c, x, 0.0); }
+    else if (hp < 2.0) { rgb = vec3<f32>(x, c, 0.0); }
+    else if (hp < 3.0) { rgb = vec3<f32>(0.0, c, x); }
+    else if (hp < 4.0) { rgb = vec3<f32>(0.0, x, c); }
+    else if (hp < 5.0) { rgb = vec3<f32>(x, 0.0, c); }
+    else { rgb = vec3<f32>(c, 0.0, x); }
+    return rgb + vec3<f32>(m, m, m);
+}
+
+// Dye texels hold red, green and blue dye in xzw (y is temperature). The
+// amount is the strongest channel, as in fluid.wgsl.
+fn dye_rgb(c: vec4<f32>) -> vec3<f32> {
+    return c.xzw;
+}
+
+fn dye_amount(c: vec4<f32>) -> f32 {
+    return max(c.x, max(c.z, c.w));
+}
+
+// ============================================================
+// Sub-grid detail: where the sub-grid turbulence is high, the dye is broken
+// up by animated noise finer than a cell, as the eddies it stands for would
+// ============================================================
+// Noise features per cell, for the two octaves
+const DETAIL_SCALE: f32 = 2.0;
+// How fast the noise changes, in features per second
+const DETAIL_RATE: f32 = 1.5;
+
+fn hash3(c: vec3<i32>) -> f32 {
+    var h = (u32(c.x) * 0x8da6b343u) ^ (u32(c.y) * 0xd8163841u) ^ (u32(c.z) * 0xcb1ab31fu);
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    h = h ^ (h >> 16u);
+    return f32(h) / 4294967295.0;
+}
+
+// Smooth value noise in [0, 1] over (x, y, time), as in fluid.wgsl
+fn value_noise(q: vec3<f32>) -> f32 {
+    let i = vec3<i32>(floor(q));
+    let f = fract(q);
+    let u = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(hash3(i), hash3(i + vec3<i32>(1, 0, 0)), u.x);
+    let x10 = mix(hash3(i + vec3<i32>(0, 1, 0)), hash3(i + vec3<i32>(1, 1, 0)), u.x);
+    let x01 = mix(hash3(i + vec3<i32>(0, 0, 1)), hash3(i + vec3<i32>(1, 0, 1)), u.x);
+    let x11 = mix(hash3(i + vec3<i32>(0, 1, 1)), hash3(i + vec3<i32>(1, 1, 1)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+// Multiplier for the dye at `uv`: 1 without turbulence, noise averaging 1
+// with it
+fn subgrid_breakup(uv: vec2<f32>) -> f32 {
+    let k = clamp(textureSampleLevel(render_subgrid_tex, render_sampler, uv, 0.0).y, 0.0, 1.0);
+    if (k <= 0.0) { return 1.0; }
+    let q = uv * f32(textureDimensions(render_density_tex).x) * DETAIL_SCALE;
+    let t = view.time * DETAIL_RATE;
+    let n = 0.65 * value_noise(vec3<f32>(q, t)) + 0.35 * value_noise(vec3<f32>(q * 2.3, t * 1.7));
+    return mix(1.0, 2.0 * n, k);
+}
+
+// ============================================================
+// Advected detail: screen-resolution noise the flow carries along
+// (detail.wgsl). Each layer is faded out just before it's reseeded and back
+// in after, half a period apart, so the blend never pops.
+// ============================================================
+// Seconds between reseeds of one layer; must match detail.wgsl
+const LAYER_PERIOD: f32 = 2.0;
+
+// Multiplier for the dye at `uv`, averaging 1
+fn advected_detail(uv: vec2<f32>) -> f32 {
+    if (view.detail <= 0.0) { return 1.0; }
+    let layers = textureSampleLevel(render_detail_tex, render_sampler, uv, 0.0).xy;
+    let phase = fract(view.time / LAYER_PERIOD + vec2<f32>(0.0, 0.5));
+    let w = 1.0 - abs(2.0 * phase - 1.0);
+    // Blending two noises flattens them; rescale to keep the contrast
+    let n = (dot(layers - 0.5, w) / max(length(w), 1e-3)) + 0.5;
+    return mix(1.0, 2.0 * clamp(n, 0.0, 1.0), view.detail);
+}
+
+// The selected colormap at t in 0..1, sampled down the middle of its row
+fn colormap(t: f32) -> vec3<f32> {
+    let rows = f32(textureDimensions(render_lut_tex).y);
+    let v = (f32(view.colormap) - 0.5) / rows;
+    return textureSampleLevel(render_lut_tex, render_sampler, vec2<f32>(clamp(t, 0.0, 1.0), v), 0.0).rgb;
+}
+
+// Dye amount the glow and bloom keep growing to in the HDR target
+const HDR_LIMIT: f32 = 4.0;
+
+// Vorticity (1/s) at which dye shading is at full strength, as the
+// vorticity field view's default scale
+const SHADING_VORTICITY: f32 = 10.0;
+// How dark the shear shading gets the dye at full strength
+const SHEAR_DARKEN: f32 = 0.85;
+
+// The dye's light, shaded by the local vorticity (Shift + V). The square
+// root brings out the weaker swirls of thin shear layers next to the
+// strong ones of vortex cores.
+fn vorticity_shading(light: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+    let w = textureSampleLevel(render_subgrid_tex, render_sampler, uv, 0.0).x / SHADING_VORTICITY;
+    let s = sqrt(clamp(abs(w), 0.0, 1.0));
+    if (view.shading == 2u) {
+        return light * (1.0 - SHEAR_DARKEN * s);
+    }
+    // Same hues as the vorticity field view, at the dye's own luminance
+    let luma = vec3<f32>(0.2126, 0.7152, 0.0722);
+    let hue = select(vec3<f32>(0.15, 0.45, 1.0), vec3<f32>(1.0, 0.25, 0.1), w > 0.0);
+    return mix(light, hue * dot(light, luma) / dot(hue, luma), s);
+}
+
+// Slope exaggeration of the lit relief
+const LIT_BUMP: f32 = 6.0;
+// Height of the light over the screen, against 1 across it
+const LIT_ELEVATION: f32 = 0.7;
+// Light facing slopes in full shadow still get
+const LIT_AMBIENT: f32 = 0.25;
+const LIT_SPECULAR: f32 = 0.6;
+const LIT_SHININESS: f32 = 40.0;
+
+// The dye's light shaded as a relief (Shift + V): the same height field as
+// glass shading, lit from `view.light_angle`. Flat dye, thick or thin,
+// keeps its brightness, slopes toward the light brighten and catch a
+// highlight, and slopes away fall toward the ambient.
+fn lit_shading(light: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+    let h = 1.0 / f32(textureDimensions(render_density_tex).x);
+    let slope = 0.5 * vec2<f32>(
+        glass_height(uv + vec2<f32>(h, 0.0)) - glass_height(uv - vec2<f32>(h, 0.0)),
+        glass_height(uv + vec2<f32>(0.0, h)) - glass_height(uv - vec2<f32>(0.0, h)),
+    );
+    let normal = normalize(vec3<f32>(-slope * LIT_BUMP, 1.0));
+    // y runs down the grid, so up on screen is -y
+    let to_light = normalize(vec3<f32>(cos(view.light_angle), -sin(view.light_angle), LIT_ELEVATION));
+    let diffuse = max(dot(normal, to_light), 0.0) / to_light.z;
+    // Highlight relative to a flat surface's, so level dye doesn't shine
+    let half_dir = normalize(to_light + vec3<f32>(0.0, 0.0, 1.0));
+    let flat_specular = pow(half_dir.z, LIT_SHININESS);
+    let specular = max(pow(max(dot(normal, half_dir), 0.0), LIT_SHININESS) - flat_specular, 0.0);
+    return light * (LIT_AMBIENT + (1.0 - LIT_AMBIENT) * diffuse) + vec3<f32>(specular * LIT_SPECULAR);
+}
+
+@fragment
+fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
+    let scalars = textureSampleLevel(render_density_tex, render_sampler, in.uv, 0.0);
+    let dens = dye_amount(scalars);
+    let vel = textureSampleLevel(render_velocity_tex, render_sampler, in.uv, 0.0).xy;
+    let trail = textureSampleLevel(render_trail_tex, render_sampler, in.uv, 0.0).rgb;
+
+    // Velocity magnitude and direction
+    let speed = length(vel);
+    let angle = atan2(vel.y, vel.x); // -pi to pi
+
+    // Map velocity direction to hue (0..1), speed controls saturation
+    let hue = fract(angle / 6.2832 + 0.5);  // normalize -pi..pi to 0..1
+    let sat = clamp(speed * 3.0, 0.2, 1.0);  // more speed = more saturated
+    let limit = select(1.0, HDR_LIMIT, view.hdr != 0u);
+    let intensity = clamp(dens * subgrid_breakup(in.uv) * advected_detail(in.uv), 0.0, limit);
+
+    // Base color from velocity direction. Colored dye shows its own color
+    // instead, as much as it's saturated; white dye keeps the flow tint
+    let flow_color = hsv2rgb(hue, sat, 1.0);
+    let dye = max(dye_rgb(scalars), vec3<f32>(0.0));
+    let dye_max = max(dens, 1e-4);
+    let dye_sat = clamp((dye_max - min(dye.r, min(dye.g, dye.b))) / dye_max, 0.0, 1.0);
+    let base_color = mix(flow_color, dye / dye_max, dye_sat);
+
+    // Glow: boost bright areas with a power curve
+    let glow = pow(intensity, 0.6);        // softer falloff for thin wisps
+    let bloom = pow(intensity, 3.0) * 0.8; // hot-white core on dense areas
+
+    // Subtle dark background gradient (not pure black)
+    let bg = vec3<f32>(0.01, 0.01, 0.03);
+
+    // Composite: colored fluid + white bloom on top
+    var fluid = base_color * glow;
+    var white_bloom = vec3<f32>(bloom, bloom, bloom);
+    if (view.colormap != 0u) {
+        // The glow through the colormap instead, which has its own dark
+        // end and bright core. Past 1 (HDR only) it keeps brightening.
+        fluid = colormap(glow) * max(glow, 1.0);
+        white_bloom = vec3<f32>(0.0);
+    }
+    var light = fluid + white_bloom;
+    switch view.shading {
+        case 1u, 2u: { light = vorticity_shading(light, in.uv); }
+        case 3u: { light = lit_shading(light, in.uv); }
+        default: {}
+    }
+    // Particle streaks: thin filaments over the dye, already tinted per particle
+    let streak = clamp(trail, vec3<f32>(0.0), vec3<f32>(1.0)) * 0.8;
+    let color = bg * max(1.0 - intensity, 0.0) + light + streak;
+
+    // Solid obstacles drawn as flat slate, linearly filtered for soft edges
+    let solid = textureSampleLevel(render_obstacle_tex, render_sampler, in.uv, 0.0).x;
+    var shaded = mix(color, vec3<f32>(0.16, 0.17, 0.2), clamp(solid, 0.0, 1.0));
+    if (view.wall_shear > 0.0) {
+        let outline = wall_shear_outline(in.uv);
+        shaded = mix(shaded, outline.rgb, outline.a);
+    }
+
+    if (view.hdr != 0u) {
+        return vec4<f32>(max(shaded, vec3<f32>(0.0)), 1.0);
+    }
+    return vec4<f32>(dither(clamp(shaded, vec3<f32>(0.0), vec3<f32>(1.0)), in.pos.xy, view.dither), 1.0);
+}
+
+// ============================================================
+// Wall shear outline (S): obstacle edges outlined thicker and hotter where
+// the flow shears past them faster
+// ============================================================
+// Outline thickness in cells at zero shear and at view.wall_shear
+const OUTLINE_MIN: f32 = 0.3;
+const OUTLINE_MAX: f32 = 2.5;
+
+// Color and coverage of the outline at `uv`. The obstacle texture's signed
+// distance says how far the nearest wall is and, by its gradient, which
+// way. The shear rate there is the flow's speed along the wall, relative
+// to the wall's own, over the half cell between the first cell center and
+// the wall face.
+fn wall_shear_outline(uv: vec2<f32>) -> vec4<f32> {
+    let n = f32(textureDimensions(render_obstacle_tex).x);
+    let px = fwidth(uv.x) * n;
+    let d = textureSampleLevel(render_obstacle_tex, render_sampler, uv, 0.0).y;
+    if (d < 0.0 || d > OUTLINE_MAX + px) { return vec4<f32>(0.0); }
+    let h = 0.5 / n;
+    let grad = vec2<f32>(
+        textureSampleLevel(render_obstacle_tex, render_sampler, uv + vec2<f32>(h, 0.0), 0.0).y
+            - textureSampleLevel(render_obstacle_tex, render_sampler, uv - vec2<f32>(h, 0.0), 0.0).y,
+        textureSampleLevel(render_obstacle_tex, render_sampler, uv + vec2<f32>(0.0, h), 0.0).y
+            - textureSampleLevel(render_obstacle_tex, render_sampler, uv - vec2<f32>(0.0, h), 0.0).y,
+    );
+    if (dot(grad, grad) < 1e-8) { return vec4<f32>(0.0); }
+    let normal = normalize(grad);
+    // The wall point nearest `uv`, the first cell center off it and the
+    // solid just behind it
+    let wall = uv - normal * d / n;
+    let flow = textureSampleLevel(render_velocity_tex, render_sampler, wall + normal * 0.5 / n, 0.0).xy
+        - textureSampleLevel(render_obstacle_tex, render_sampler, wall - normal * 0.5 / n, 0.0).zw;
+    let tangential = flow - dot(flow, normal) * normal;
+    let s = clamp(length(tangential) / 0.5 / view.wall_shear, 0.0, 1.0);
+    let thickness = mix(OUTLINE_MIN, OUTLINE_MAX, s);
+    let coverage = 1.0 - smoothstep(thickness - px, thickness, d);
+    return vec4<f32>(heat_ramp(mix(0.3, 1.0, s)), coverage);
+}
+
+// ============================================================
+// Glass shading (Shift + W): the dye as the height of a clear surface,
+// like water over a pool floor, refracting the background under it
+// ============================================================
+// Surface slope per unit of height change across a cell
+const GLASS_BUMP: f32 = 8.0;
+// Background shift, in texture coordinates, per unit of refracted slope
+const GLASS_DEPTH: f32 = 0.03;
+// Water's index of refraction
+const GLASS_IOR: f32 = 1.33;
+// Light from the upper left, y running down the grid
+const GLASS_LIGHT: vec3<f32> = vec3<f32>(-0.45, -0.6, 0.66);
+
+// Surface height at `uv`, rising with the dye and leveling off where it's dense
+fn glass_height(uv: vec2<f32>) -> f32 {
+    return 1.0 - exp(-dye_amount(textureSampleLevel(render_density_tex, render_sampler, uv, 0.0)));
+}
+
+@fragment
+fn fs_glass(in: VSOut) -> @location(0) vec4<f32> {
+    let h = 1.0 / f32(textureDimensions(render_density_tex).x);
+    let slope = 0.5 * vec2<f32>(
+        glass_height(in.uv + vec2<f32>(h, 0.0)) - glass_height(in.uv - vec2<f32>(h, 0.0)),
+        glass_height(in.uv + vec2<f32>(0.0, h)) - glass_height(in.uv - vec2<f32>(0.0, h)),
+    );
+    let normal = normalize(vec3<f32>(-slope * GLASS_BUMP, 1.0));
+
+    // Looking straight down through the surface onto the background
+    let ray = refract(vec3<f32>(0.0, 0.0, -1.0), normal, 1.0 / GLASS_IOR);
+    let floor_uv = in.uv + ray.xy / max(-ray.z, 0.1) * GLASS_DEPTH;
+    var color = textureSampleLevel(render_background_tex, render_sampler, floor_uv, 0.0).rgb;
+
+    // Thicker fluid absorbs more, toward the dye's own color; white dye
+    // gives clear water a faint aqua cast
+    let scalars = textureSampleLevel(render_density_tex, render_sampler, in.uv, 0.0);
+    let dens = dye_amount(scalars);
+    let tint = mix(vec3<f32>(0.75, 0.92, 0.95), max(dye_rgb(scalars), vec3<f32>(0.0)) / max(dens, 1e-4), 0.5);
+    color *= mix(vec3<f32>(1.0), tint, clamp(dens, 0.0, 1.0));
+
+    // Sky reflected at grazing slopes (Schlick) and a highlight from the light
+    let facing = normal.z;
+    let fresnel = 0.02 + 0.98 * pow(1.0 - facing, 5.0);
+    color = mix(color, vec3<f32>(0.8, 0.88, 1.0), fresnel);
+    let half_dir = normalize(normalize(GLASS_LIGHT) + vec3<f32>(0.0, 0.0, 1.0));
+    color += vec3<f32>(pow(max(dot(normal, half_dir), 0.0), 80.0) * 0.9);
+
+    let solid = textureSampleLevel(render_obstacle_tex, render_sampler, in.uv, 0.0).x;
+    let shaded = mix(color, vec3<f32>(0.16, 0.17, 0.2), clamp(solid, 0.0, 1.0));
+    if (view.hdr != 0u) {
+        return vec4<f32>(shaded, 1.0);
+    }
+    return vec4<f32>(dither(clamp(shaded, vec3<f32>(0.0), vec3<f32>(1.0)), in.pos.xy, view.dither), 1.0);
+}
+
+// ============================================================
+// Debug: per-cell compute cost heat map (H)
+// ============================================================
+// black -> blue -> red -> yellow -> white as a cell's cost approaches the
+// frame's most expensive cell.
+fn heat_ramp(t: f32) -> vec3<f32> {
+    var stops = array<vec3<f32>, 5>(
+        vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(0.1, 0.2, 0.8), vec3<f32>(0.9, 0.1, 0.1),
+        vec3<f32>(1.0, 0.85, 0.1), vec3<f32>(1.0, 1.0, 1.0)
+    );
+    let x = clamp(t, 0.0, 1.0) * 4.0;
+    let i = min(u32(x), 3u);
+    return mix(stops[i], stops[i + 1u], x - f32(i));
+}
+
+@fragment
+fn fs_heat(in: VSOut) -> @location(0) vec4<f32> {
+    let n = textureDimensions(render_density_tex).x;
+    let cell = min(vec2<u32>(clamp(in.uv, vec2<f32>(0.0), vec2<f32>(1.0)) * f32(n)), vec2<u32>(n - 1u));
+    let max_cost = max(render_cost[n * n], 1u);
+    let heat = heat_ramp(f32(render_cost[cell.y * n + cell.x]) / f32(max_cost));
+    // Faint obstacle outlines for orientation
+    let solid = textureSampleLevel(render_obstacle_tex, render_sampler, in.uv, 0.0).x;
+    return vec4<f32>(dither(mix(heat, vec3<f32>(0.35), clamp(solid, 0.0, 1.0) * 0.3), in.pos.xy, view.dither), 1.0);
+}
+
+// ============================================================
+// Difference view: live - reference (D), or copy - copy (K, then V)
+// ============================================================
+// Symmetric diverging map: black where the states agree, blue where this one
+// is lower, red where it is higher, saturating to white at +-diff_scale.
+fn diverging(t: f32) -> vec3<f32> {
+    let m = abs(clamp(t, -1.0, 1.0));
+    let hue = select(vec3<f32>(0.15, 0.45, 1.0), vec3<f32>(1.0, 0.25, 0.1), t > 0.0);
+    return mix(hue * sqrt(m), vec3<f32>(1.0), m * m * 0.6);
+}
+
+@fragment
+fn fs_diff(in: VSOut) -> @location(0) vec4<f32> {
+    let da = textureSampleLevel(render_density_tex, render_sampler, in.uv, 0.0);
+    let db = textureSampleLevel(render_other_density_tex, render_sampler, in.uv, 0.0);
+    let va = textureSampleLevel(render_velocity_tex, render_sampler, in.uv, 0.0).xy;
+    let vb = textureSampleLevel(render_other_velocity_tex, render_sampler, in.uv, 0.0).xy;
+    var d: f32;
+    switch view.diff_field {
+        case 0u: { d = dye_amount(da) - dye_amount(db); }
+        case 1u: { d = da.y - db.y; }
+        case 2u: { d = va.x - vb.x; }
+        case 3u: { d = va.y - vb.y; }
+        default: { d = length(va) - length(vb); }
+    }
+    let color = diverging(d / max(view.diff_scale, 1e-6));
+    // Faint obstacle outlines for orientation
+    let solid = textureSampleLevel(render_obstacle_tex, render_sampler, in.uv, 0.0).x;
+    return vec4<f32>(dither(mix(color, vec3<f32>(0.35), clamp(solid, 0.0, 1.0) * 0.3), in.pos.xy, view.dither), 1.0);
+}
+
+// ============================================================
+// Field views (Q): the solver's own fields through a colormap
+// ============================================================
+// Line integral convolution: pixels the kernel reaches each way along the
+// flow, and the contrast its average is stretched by
+const LIC_REACH: i32 = 20;
+const LIC_CONTRAST: f32 = 5.0;
+
+// White noise on the window's pixel lattice, bilinearly interpolated so
+// sub-pixel steps along a streamline don't alias
+fn pixel_noise(p: vec2<f32>) -> f32 {
+    let i = vec2<i32>(floor(p));
+    let f = fract(p);
+    let a = mix(hash3(vec3<i32>(i, 0)), hash3(vec3<i32>(i + vec2<i32>(1, 0), 0)), f.x);
+    let b = mix(hash3(vec3<i32>(i + vec2<i32>(0, 1), 0)), hash3(vec3<i32>(i + vec2<i32>(1, 1), 0)), f.x);
+    return mix(a, b, f.y);
+}
+
+// Noise averaged along the streamline through `uv`, a pixel per step and
+// Hann-weighted toward the ends, so it smears into streaks along the flow
+// and stays grainy across it. `pixel` is one pixel's size in uv.
+fn lic(uv: vec2<f32>, pixel: vec2<f32>) -> f32 {
+    var sum = pixel_noise(uv / pixel);
+    var weight = 1.0;
+    for (var dir = -1.0; dir <= 1.0; dir += 2.0) {
+        var p = uv;
+        for (var k = 1; k <= LIC_REACH; k++) {
+            // Cells are square on screen, so the flow's direction in
+            // pixels is the velocity's own
+            let v = textureSampleLevel(render_velocity_tex, render_sampler, p, 0.0).xy;
+            let speed = length(v);
+            if (speed < 1e-3) { break; }
+            p += dir * (v / speed) * pixel;
+            if (textureSampleLevel(render_obstacle_tex, render_sampler, p, 0.0).x > 0.5) { break; }
+            let w = 0.5 + 0.5 * cos(3.14159 * f32(k) / f32(LIC_REACH + 1));
+            sum += w * pixel_noise(p / pixel);
+            weight += w;
+        }
+    }
+    return clamp((sum / weight - 0.5) * LIC_CONTRAST + 0.5, 0.0, 1.0);
+}
+
+// ============================================================
+// Synthetic schlieren: a wind-tunnel photograph of the dye. The light
+// bends where the dye's amount changes, so flat regions show the plain
+// backdrop, and a knife edge in the focal plane cuts off light bent one
+// way and lets more through bent the other: gradients toward the knife
+// direction come out bright, those away dark, and strong ones of any
+// direction scatter to shadow.
+// ============================================================
+// Brightness where the dye is even
+const SCHLIEREN_BACKDROP: f32 = 0.55;
+// Gradients pointing this way (toward the top right on screen) brighten
+const SCHLIEREN_KNIFE: vec2<f32> = vec2<f32>(0.5, -0.866);
+// Brightness change across the knife at full scale
+const SCHLIEREN_CONTRAST: f32 = 0.45;
+
+fn schlieren(uv: vec2<f32>) -> f32 {
+    let h = 1.0 / f32(textureDimensions(render_density_tex).x);
+    let dx = dye_amount(textureSampleLevel(render_density_tex, render_sampler, uv + vec2<f32>(h, 0.0), 0.0))
+        - dye_amount(textureSampleLevel(render_density_tex, render_sampler, uv - vec2<f32>(h, 0.0), 0.0));
+    let dy = dye_amount(textureSampleLevel(render_density_tex, render_sampler, uv + vec2<f32>(0.0, h), 0.0))
+        - dye_amount(textureSampleLevel(render_density_tex, render_sampler, uv - vec2<f32>(0.0, h), 0.0));
+    // Dye per cell, at full shade at the field scale
+    let g = 0.5 * vec2<f32>(dx, dy) / max(view.field_scale, 1e-6);
+    let knife = clamp(dot(g, SCHLIEREN_KNIFE), -1.0, 1.0);
+    let gray = SCHLIEREN_BACKDROP * exp(-0.6 * length(g)) + SCHLIEREN_CONTRAST * knife;
+    return clamp(gray, 0.0, 1.0);
+}
+
+@fragment
+fn fs_field(in: VSOut) -> @location(0) vec4<f32> {
+    let scale = max(view.field_scale, 1e-6);
+    var color: vec3<f32>;
+    switch view.field {
+        case 1u: {
+            // Direction as hue, as in fs_draw, speed as brightness, or the
+            // speed alone through the colormap
+            let vel = textureSampleLevel(render_velocity_tex, render_sampler, in.uv, 0.0).xy;
+            let hue = fract(atan2(vel.y, vel.x) / 6.2832 + 0.5);
+            let level = sqrt(clamp(length(vel) / scale, 0.0, 1.0));
+            color = hsv2rgb(hue, 0.85, level);
+            if (view.colormap != 0u) {
+                color = colormap(level);
+            }
+        }
+        case 2u: {
+            color = diverging(textureSampleLevel(render_pressure_tex, render_sampler, in.uv, 0.0).x / scale);
+        }
+        case 3u: {
+            color = diverging(textureSampleLevel(render_divergence_tex, render_sampler, in.uv, 0.0).x / scale);
+        }
+        case 5u: {
+            // Streaks tinted from slate in still fluid to amber at full speed
+            let pixel = abs(vec2<f32>(dpdx(in.uv).x, dpdy(in.uv).y));
+            let speed = length(textureSampleLevel(render_velocity_tex, render_sampler, in.uv, 0.0).xy);
+            let tint = mix(vec3<f32>(0.45, 0.55, 0.75), vec3<f32>(1.0, 0.75, 0.35), sqrt(clamp(speed / scale, 0.0, 1.0)));
+            color = tint * lic(in.uv, pixel);
+        }
+        case 6u: {
+            // A gray ramp with the faint warmth of a photographic print
+            color = vec3<f32>(schlieren(in.uv)) * vec3<f32>(1.0, 0.98, 0.94);
+        }
+        default: {
+            // y runs down the grid, so red turns clockwise on screen and
+            // blue counterclockwise
+            color = diverging(textureSampleLevel(render_subgrid_tex, render_sampler, in.uv, 0.0).x / scale);
+        }
+    }
+    // Faint obstacle outlines for orientation
+    let solid = textureSampleLevel(render_obstacle_tex, render_sampler, in.uv, 0.0).x;
+    return vec4<f32>(dither(mix(color, vec3<f32>(0.35), clamp(solid, 0.0, 1.0) * 0.3), in.pos.xy, view.dither), 1.0);
+}
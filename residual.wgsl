@@ -0,0 +1,93 @@
+// ============================================================
+// Jacobi residual (--solver jacobi:auto). A Jacobi sweep moves each cell
+// by its residual over the stencil's diagonal, so how far the last sweep
+// moved the pressure measures how far the solve is from done, with the
+// same walls, boundaries and density weights as the sweeps themselves.
+// From a zero start the first sweep moves it by the right-hand side's
+// share, which makes the ratio of the two a relative residual.
+// ============================================================
+struct ResidualParams {
+    grid_size: u32,
+    // Tiles written by the norm kernels
+    tiles: u32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: ResidualParams;
+@group(0) @binding(1) var pressure: texture_2d<f32>;
+@group(0) @binding(2) var pressure_tmp: texture_2d<f32>;
+// Per tile: x = Σ first sweep², y = Σ last sweep²
+@group(0) @binding(3) var<storage, read_write> partials: array<vec2<f32>>;
+@group(0) @binding(4) var<storage, read_write> result: vec2<f32>;
+
+const TILE: u32 = 256u;
+
+var<workgroup> sums: array<vec2<f32>, 256>;
+
+// Tree sum of `sums`, left in sums[0]. Every invocation of the workgroup
+// must call it.
+fn reduce(index: u32) {
+    for (var stride = TILE / 2u; stride > 0u; stride /= 2u) {
+        workgroupBarrier();
+        if (index < stride) {
+            sums[index] += sums[index + stride];
+        }
+    }
+    workgroupBarrier();
+}
+
+fn tile_index(wid: vec3<u32>) -> u32 {
+    return wid.y * ((params.grid_size + 15u) / 16u) + wid.x;
+}
+
+// After the first half sweep: pressure_tmp holds it, from pressure = 0
+@compute @workgroup_size(16, 16)
+fn initial_norm(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    var moved = 0.0;
+    if (all(gid.xy < vec2<u32>(params.grid_size))) {
+        moved = textureLoad(pressure_tmp, vec2<i32>(gid.xy), 0).x;
+    }
+    sums[index] = vec2<f32>(moved * moved, 0.0);
+    reduce(index);
+    if (index == 0u) {
+        partials[tile_index(wid)].x = sums[0].x;
+    }
+}
+
+// After the last full iteration: pressure holds the newest half sweep,
+// pressure_tmp the one before it
+@compute @workgroup_size(16, 16)
+fn final_norm(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    var moved = 0.0;
+    if (all(gid.xy < vec2<u32>(params.grid_size))) {
+        let c = vec2<i32>(gid.xy);
+        moved = textureLoad(pressure, c, 0).x - textureLoad(pressure_tmp, c, 0).x;
+    }
+    sums[index] = vec2<f32>(0.0, moved * moved);
+    reduce(index);
+    if (index == 0u) {
+        partials[tile_index(wid)].y = sums[0].y;
+    }
+}
+
+@compute @workgroup_size(256)
+fn total(@builtin(local_invocation_index) index: u32) {
+    var acc = vec2<f32>(0.0);
+    for (var i = index; i < params.tiles; i += TILE) {
+        acc += partials[i];
+    }
+    sums[index] = acc;
+    reduce(index);
+    if (index == 0u) {
+        result = sums[0];
+    }
+}
@@ -0,0 +1,169 @@
+// ============================================================
+// Shallow water (--shallow): a height field over a flat bed. Each cell
+// holds the surface elevation above rest and the two depth-integrated
+// momenta; a finite-volume step exchanges Rusanov fluxes across every cell
+// face, and the renderer lights the surface from its slopes.
+// ============================================================
+struct WaterParams {
+    size: u32,
+    dt: f32,
+    // Cells/s²
+    gravity: f32,
+    // Depth of the still pond, in cells
+    rest_depth: f32,
+    // Finger center in cells
+    mouse_pos: vec2<f32>,
+    radius: f32,
+    mouse_down: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: WaterParams;
+// x = elevation above rest_depth, y = depth * x velocity, z = depth * y velocity.
+// Elevation rather than depth, so half floats keep small ripples exact.
+@group(0) @binding(1) var water_src: texture_storage_2d<rgba16float, read_write>;
+@group(0) @binding(2) var water_dst: texture_storage_2d<rgba16float, read_write>;
+
+// How fast the finger pushes the surface down, in cells/s at its center
+const PUSH_RATE: f32 = 40.0;
+// Depths below this count as dry, with no velocity
+const DRY: f32 = 1e-3;
+// Bed friction, fraction of the momentum lost per second
+const FRICTION: f32 = 0.05;
+
+// (depth, x momentum, y momentum) of cell `c`. Past a wall the neighbour
+// is the cell's mirror image: same depth, normal momentum reversed.
+fn load_state(c: vec2<i32>) -> vec3<f32> {
+    let last = i32(params.size) - 1;
+    let inside = clamp(c, vec2<i32>(0), vec2<i32>(last));
+    let w = textureLoad(water_src, inside).xyz;
+    var u = vec3<f32>(max(params.rest_depth + w.x, 0.0), w.y, w.z);
+    if (c.x != inside.x) { u.y = -u.y; }
+    if (c.y != inside.y) { u.z = -u.z; }
+    return u;
+}
+
+fn velocity(u: vec3<f32>) -> vec2<f32> {
+    return select(vec2<f32>(0.0), u.yz / u.x, u.x > DRY);
+}
+
+// Physical flux of (h, hu, hv) along x
+fn flux_x(u: vec3<f32>) -> vec3<f32> {
+    let v = velocity(u);
+    return vec3<f32>(u.y, u.y * v.x + 0.5 * params.gravity * u.x * u.x, u.y * v.y);
+}
+
+fn flux_y(u: vec3<f32>) -> vec3<f32> {
+    let v = velocity(u);
+    return vec3<f32>(u.z, u.z * v.x, u.z * v.y + 0.5 * params.gravity * u.x * u.x);
+}
+
+// Rusanov (local Lax-Friedrichs) flux through the face between `l` and `r`,
+// the mean of their fluxes plus dissipation at the faster wave speed
+fn rusanov_x(l: vec3<f32>, r: vec3<f32>) -> vec3<f32> {
+    let a = max(abs(velocity(l).x) + sqrt(params.gravity * l.x), abs(velocity(r).x) + sqrt(params.gravity * r.x));
+    return 0.5 * (flux_x(l) + flux_x(r)) - 0.5 * a * (r - l);
+}
+
+fn rusanov_y(l: vec3<f32>, r: vec3<f32>) -> vec3<f32> {
+    let a = max(abs(velocity(l).y) + sqrt(params.gravity * l.x), abs(velocity(r).y) + sqrt(params.gravity * r.x));
+    return 0.5 * (flux_y(l) + flux_y(r)) - 0.5 * a * (r - l);
+}
+
+// ============================================================
+// Compute: one finite-volume step, water_src to water_dst
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn step_water(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let u = load_state(c);
+    let west = rusanov_x(load_state(c - vec2<i32>(1, 0)), u);
+    let east = rusanov_x(u, load_state(c + vec2<i32>(1, 0)));
+    let north = rusanov_y(load_state(c - vec2<i32>(0, 1)), u);
+    let south = rusanov_y(u, load_state(c + vec2<i32>(0, 1)));
+    var next = u - params.dt * (east - west + south - north);
+    next = vec3<f32>(max(next.x, 0.0), next.yz * (1.0 - FRICTION * params.dt));
+
+    if (params.mouse_down != 0u) {
+        // A finger pressed into the surface, making a dent that spreads as rings
+        let d = vec2<f32>(c) + 0.5 - params.mouse_pos;
+        let w = exp(-dot(d, d) / (params.radius * params.radius));
+        next.x = max(next.x - PUSH_RATE * w * params.dt, 0.0);
+    }
+
+    if (next.x <= DRY) { next = vec3<f32>(next.x, 0.0, 0.0); }
+    textureStore(water_dst, c, vec4<f32>(next.x - params.rest_depth, next.yz, 0.0));
+}
+
+// ============================================================
+// Render: a lit water surface over a tiled pool floor
+// ============================================================
+struct ViewParams {
+    size: f32,
+    rest_depth: f32,
+    _pad: vec2<f32>,
+}
+
+// Numbered after the compute bindings, so both fit one module
+@group(0) @binding(3) var<uniform> view: ViewParams;
+@group(0) @binding(4) var water_tex: texture_2d<f32>;
+@group(0) @binding(5) var water_sampler: sampler;
+
+// Slope exaggeration for the lighting; real ripples are very flat
+const RELIEF: f32 = 4.0;
+// How far, in cells per cell of depth, the floor is displaced by refraction
+const REFRACTION: f32 = 0.3;
+// Floor tiles per side of the pool
+const TILES: f32 = 16.0;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = uvs[vid];
+    return out;
+}
+
+fn elevation(uv: vec2<f32>) -> f32 {
+    return textureSampleLevel(water_tex, water_sampler, uv, 0.0).x;
+}
+
+@fragment
+fn fs_water(in: VSOut) -> @location(0) vec4<f32> {
+    let texel = 1.0 / view.size;
+    let eta = elevation(in.uv);
+    let depth = max(view.rest_depth + eta, 0.0);
+    // Central differences, per cell
+    let gx = 0.5 * (elevation(in.uv + vec2<f32>(texel, 0.0)) - elevation(in.uv - vec2<f32>(texel, 0.0)));
+    let gy = 0.5 * (elevation(in.uv + vec2<f32>(0.0, texel)) - elevation(in.uv - vec2<f32>(0.0, texel)));
+    let n = normalize(vec3<f32>(-gx * RELIEF, -gy * RELIEF, 1.0));
+
+    // The floor seen through the surface, shifted by its slope
+    let floor_uv = in.uv - n.xy * depth * REFRACTION * texel;
+    let tile = floor(floor_uv * TILES);
+    let checker = select(0.75, 0.9, (i32(tile.x) + i32(tile.y)) % 2 == 0);
+    let floor_color = vec3<f32>(0.55, 0.7, 0.75) * checker;
+
+    // Deeper water absorbs more of the floor's light
+    let absorb = exp(-depth / (view.rest_depth * 2.0));
+    let water = mix(vec3<f32>(0.02, 0.12, 0.22), floor_color, absorb);
+
+    let light = normalize(vec3<f32>(-0.4, -0.5, 0.75));
+    let diffuse = max(dot(n, light), 0.0);
+    let spec = pow(max(dot(reflect(-light, n), vec3<f32>(0.0, 0.0, 1.0)), 0.0), 80.0);
+    let fresnel = 0.02 + 0.98 * pow(1.0 - n.z, 5.0);
+    let sky = vec3<f32>(0.6, 0.75, 0.9);
+    let color = water * (0.5 + 0.5 * diffuse) + sky * fresnel + vec3<f32>(spec);
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,238 @@
+// ============================================================
+// Weakly compressible SPH (--sph): the particles are the fluid. Each
+// substep bins them into a neighbor grid, sums each one's density from the
+// neighbors within SMOOTHING, turns density above rest into pressure with a
+// stiff equation of state, and moves every particle by the pressure,
+// viscosity, gravity and mouse forces on it.
+// ============================================================
+struct SphParams {
+    count: u32,
+    // Domain side in cells
+    domain: f32,
+    // Neighbor bins per side, each SMOOTHING cells across
+    bins: u32,
+    dt: f32,
+    // Density of particles at rest spacing
+    rest_density: f32,
+    // Pressure per unit of density above rest (sound speed squared)
+    stiffness: f32,
+    viscosity: f32,
+    // Window width over height, for round sprites
+    aspect: f32,
+    // Body force in cells/s², +y down
+    gravity: vec2<f32>,
+    // Brush center in cells, and the cursor's velocity in cells/s
+    mouse_pos: vec2<f32>,
+    mouse_vel: vec2<f32>,
+    radius: f32,
+    mouse_down: u32,
+    // Sprite radius in cells
+    sprite: f32,
+    _pad: f32,
+}
+
+struct Particle {
+    pos: vec2<f32>,
+    vel: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: SphParams;
+@group(0) @binding(1) var<storage, read_write> particles: array<Particle>;
+// Per particle: density and pressure
+@group(0) @binding(2) var<storage, read_write> fluid: array<vec2<f32>>;
+@group(0) @binding(3) var<storage, read_write> accel: array<vec2<f32>>;
+// Particles per bin and their indices, BIN_CAPACITY slots per bin
+@group(0) @binding(4) var<storage, read_write> bin_counts: array<atomic<u32>>;
+@group(0) @binding(5) var<storage, read_write> bin_slots: array<u32>;
+
+// Kernel radius in cells; must match `SMOOTHING` in src/sph.rs
+const SMOOTHING: f32 = 2.0;
+// Slots per bin; particles past it go unseen by their neighbors this substep
+const BIN_CAPACITY: u32 = 16u;
+const PI: f32 = 3.14159265;
+// Share of its speed a particle keeps when it bounces off a wall
+const WALL_BOUNCE: f32 = 0.3;
+
+// ============================================================
+// 2D smoothing kernels (Müller et al. 2003)
+// ============================================================
+fn poly6(r2: f32) -> f32 {
+    let h2 = SMOOTHING * SMOOTHING;
+    let d = h2 - r2;
+    return 4.0 / (PI * pow(SMOOTHING, 8.0)) * d * d * d;
+}
+
+// Magnitude of the spiky kernel's gradient, pointing away from the neighbor
+fn spiky_grad(r: f32) -> f32 {
+    let d = SMOOTHING - r;
+    return 30.0 / (PI * pow(SMOOTHING, 5.0)) * d * d;
+}
+
+fn viscosity_laplacian(r: f32) -> f32 {
+    return 40.0 / (PI * pow(SMOOTHING, 5.0)) * (SMOOTHING - r);
+}
+
+fn bin_of(pos: vec2<f32>) -> vec2<i32> {
+    return clamp(vec2<i32>(floor(pos / SMOOTHING)), vec2<i32>(0), vec2<i32>(i32(params.bins) - 1));
+}
+
+fn bin_index(b: vec2<i32>) -> u32 {
+    return u32(b.y) * params.bins + u32(b.x);
+}
+
+fn in_bins(b: vec2<i32>) -> bool {
+    return all(b >= vec2<i32>(0)) && all(b < vec2<i32>(i32(params.bins)));
+}
+
+// Particles filed in bin `b` this substep
+fn bin_len(b: u32) -> u32 {
+    return min(atomicLoad(&bin_counts[b]), BIN_CAPACITY);
+}
+
+// ============================================================
+// Neighbor grid
+// ============================================================
+@compute @workgroup_size(64)
+fn clear_bins(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= params.bins * params.bins) { return; }
+    atomicStore(&bin_counts[gid.x], 0u);
+}
+
+@compute @workgroup_size(64)
+fn bin_particles(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= params.count) { return; }
+    let b = bin_index(bin_of(particles[gid.x].pos));
+    let slot = atomicAdd(&bin_counts[b], 1u);
+    if (slot < BIN_CAPACITY) {
+        bin_slots[b * BIN_CAPACITY + slot] = gid.x;
+    }
+}
+
+// ============================================================
+// Density and pressure
+// ============================================================
+@compute @workgroup_size(64)
+fn compute_density(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= params.count) { return; }
+    let pos = particles[i].pos;
+    let home = bin_of(pos);
+    var density = 0.0;
+    for (var dy = -1; dy <= 1; dy++) {
+        for (var dx = -1; dx <= 1; dx++) {
+            let b = home + vec2<i32>(dx, dy);
+            if (!in_bins(b)) { continue; }
+            let bi = bin_index(b);
+            let n = bin_len(bi);
+            for (var k = 0u; k < n; k++) {
+                let d = pos - particles[bin_slots[bi * BIN_CAPACITY + k]].pos;
+                let r2 = dot(d, d);
+                if (r2 < SMOOTHING * SMOOTHING) { density += poly6(r2); }
+            }
+        }
+    }
+    // Only compression pushes back: a pull toward rest density would clump
+    // the particles at the surface
+    let pressure = max(params.stiffness * (density - params.rest_density), 0.0);
+    fluid[i] = vec2<f32>(density, pressure);
+}
+
+// ============================================================
+// Forces
+// ============================================================
+@compute @workgroup_size(64)
+fn compute_forces(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= params.count) { return; }
+    let me = particles[i];
+    let mine = fluid[i];
+    let home = bin_of(me.pos);
+    var a = params.gravity;
+    for (var dy = -1; dy <= 1; dy++) {
+        for (var dx = -1; dx <= 1; dx++) {
+            let b = home + vec2<i32>(dx, dy);
+            if (!in_bins(b)) { continue; }
+            let bi = bin_index(b);
+            let n = bin_len(bi);
+            for (var k = 0u; k < n; k++) {
+                let j = bin_slots[bi * BIN_CAPACITY + k];
+                if (j == i) { continue; }
+                let d = me.pos - particles[j].pos;
+                let r = length(d);
+                if (r >= SMOOTHING) { continue; }
+                let theirs = fluid[j];
+                // Coincident particles get pushed apart in an arbitrary but
+                // fixed direction
+                let dir = select(vec2<f32>(0.7071, 0.7071), d / r, r > 1e-5);
+                let p = mine.y / (mine.x * mine.x) + theirs.y / (theirs.x * theirs.x);
+                a += p * spiky_grad(r) * dir;
+                a += params.viscosity * (particles[j].vel - me.vel) / theirs.x * viscosity_laplacian(r) / mine.x;
+            }
+        }
+    }
+    accel[i] = a;
+}
+
+// ============================================================
+// Integration
+// ============================================================
+@compute @workgroup_size(64)
+fn integrate(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= params.count) { return; }
+    var p = particles[i];
+    p.vel += accel[i] * params.dt;
+
+    if (params.mouse_down != 0u) {
+        // Dragged along at the cursor's speed, as in FLIP mode
+        let d = p.pos - params.mouse_pos;
+        let w = exp(-dot(d, d) / (params.radius * params.radius));
+        p.vel = mix(p.vel, params.mouse_vel, w);
+    }
+
+    p.pos += p.vel * params.dt;
+
+    // Walls: pushed back inside, losing most of the speed into them
+    let lo = vec2<f32>(0.5);
+    let hi = vec2<f32>(params.domain - 0.5);
+    let out = (p.pos < lo & p.vel < vec2<f32>(0.0)) | (p.pos > hi & p.vel > vec2<f32>(0.0));
+    p.vel = select(p.vel, -p.vel * WALL_BOUNCE, out);
+    p.pos = clamp(p.pos, lo, hi);
+    particles[i] = p;
+}
+
+// ============================================================
+// Point sprites: a round, speed-tinted disc per particle
+// ============================================================
+struct SpriteOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) speed: f32,
+}
+
+@vertex
+fn vs_sprite(@builtin(vertex_index) vid: u32, @location(0) pos: vec2<f32>, @location(1) vel: vec2<f32>) -> SpriteOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0)
+    );
+    let n = params.domain;
+    let corner = corners[vid];
+    let center = vec2<f32>(pos.x / n * 2.0 - 1.0, 1.0 - pos.y / n * 2.0);
+    let size = vec2<f32>(1.0, params.aspect) * params.sprite / n * 2.0;
+    var out: SpriteOut;
+    out.pos = vec4<f32>(center + corner * size, 0.0, 1.0);
+    out.corner = corner;
+    out.speed = length(vel);
+    return out;
+}
+
+@fragment
+fn fs_sprite(in: SpriteOut) -> @location(0) vec4<f32> {
+    let r2 = dot(in.corner, in.corner);
+    if (r2 > 1.0) { discard; }
+    // Teal at rest to foamy white when fast
+    let t = clamp(in.speed / 150.0, 0.0, 1.0);
+    let color = mix(vec3<f32>(0.05, 0.45, 0.55), vec3<f32>(0.85, 0.95, 1.0), t);
+    return vec4<f32>(color * (1.0 - 0.35 * r2), 1.0);
+}
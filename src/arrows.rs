@@ -0,0 +1,135 @@
+//! Velocity arrows (A, `--arrows <N>`): an N×N lattice of arrows over the
+//! fluid, each along the flow at its point and longer the faster it goes.
+//! `vs_arrow` in `arrows.wgsl` builds every arrow from the velocity
+//! texture, so there is nothing to compute or upload per frame.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+/// Arrows per side when `--arrows` doesn't say.
+pub const DEFAULT_PER_SIDE: u32 = 32;
+/// Speed in cells per second drawn at full length.
+const FULL_SPEED: f32 = 100.0;
+/// A shaft quad and a head triangle.
+const VERTICES: u32 = 9;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ArrowParams {
+    grid_size: u32,
+    per_side: u32,
+    cell_size: [f32; 2],
+    viewport: [f32; 4],
+    full_speed: f32,
+    _pad: [f32; 3],
+}
+
+pub struct Arrows {
+    /// The arrows are drawn (A).
+    pub showing: bool,
+    params: ArrowParams,
+    param_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Arrows {
+    /// `per_side` arrows each way; `viewport` is the part of the domain the
+    /// window shows, as in `ViewParams`.
+    pub fn new(
+        device: &wgpu::Device, velocity_view: &wgpu::TextureView, obstacle_view: &wgpu::TextureView,
+        grid_size: u32, format: wgpu::TextureFormat, per_side: u32, viewport: [f32; 4],
+    ) -> Self {
+        let params = ArrowParams {
+            grid_size, per_side, cell_size: [1.0; 2], viewport, full_speed: FULL_SPEED, _pad: [0.0; 3],
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("arrow_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("arrow_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../arrows.wgsl").into()),
+        });
+
+        let sampled_tex = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+        };
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("arrow_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::VERTEX, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::VERTEX, ty: sampled_tex, count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("arrow_bg"), layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(obstacle_view) },
+            ],
+        });
+        let pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        // One instance per arrow, no vertex buffers, blended over the fluid
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fs_arrow"), layout: Some(&pl),
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_arrow", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader, entry_point: "fs_arrow",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format, blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self { showing: false, params, param_buffer, bind_group, pipeline }
+    }
+
+    /// Show or hide the arrows.
+    pub fn toggle(&mut self) {
+        self.showing = !self.showing;
+        eprintln!("Velocity arrows: {}", if self.showing {
+            format!("on ({}×{}, full length at {} cells/s)", self.params.per_side, self.params.per_side, FULL_SPEED)
+        } else {
+            "off".to_string()
+        });
+    }
+
+    /// Keep the arrows' shape true to the window's aspect ratio.
+    pub fn set_cell_size(&mut self, queue: &wgpu::Queue, cell_size: [f32; 2]) {
+        self.params.cell_size = cell_size;
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// Draw the arrows over whatever the pass drew before.
+    pub fn draw<'a>(&'a self, r: &mut wgpu::RenderPass<'a>) {
+        if !self.showing {
+            return;
+        }
+        r.set_pipeline(&self.pipeline);
+        r.set_bind_group(0, &self.bind_group, &[]);
+        r.draw(0..VERTICES, 0..self.params.per_side * self.params.per_side);
+    }
+}
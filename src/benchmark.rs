@@ -0,0 +1,121 @@
+//! Canonical initial flows for checking solver changes against known
+//! behavior: a `[benchmark]` table in a scene writes one of them straight
+//! into the velocity and dye fields.
+//!
+//! All three are laid out for a periodic domain (`boundary = "wrap"`) and
+//! are divergence-free as written, so the first projection leaves them
+//! alone and whatever they do next is the solver's doing.
+
+use std::f32::consts::TAU;
+
+/// Dye of the two fluids the shear flows start with.
+const RED: [f32; 3] = [1.0, 0.3, 0.1];
+const BLUE: [f32; 3] = [0.2, 0.6, 1.0];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    /// A band of fluid moving against the rest, with a small sinusoidal
+    /// kick across both interfaces (McNally, Lyra & Passy 2012). Each
+    /// interface rolls up into two billows.
+    KelvinHelmholtz,
+    /// A grid of counter-rotating vortices. Without viscosity it is a
+    /// steady solution, so any drift or decay is the solver's. With
+    /// viscosity ν (cells²/s) the energy falls as exp(−16π²·m²·ν·t / N²)
+    /// on an N-cell grid with m vortex pairs across.
+    TaylorGreen,
+    /// Two very thin shear layers and a weak transverse wave (Bell, Colella
+    /// & Glaz 1989). The layers roll up into two vortices joined by braids
+    /// that thin until the grid can no longer hold them.
+    DoubleShear,
+}
+
+impl Flow {
+    const ALL: [Flow; 3] = [Flow::KelvinHelmholtz, Flow::TaylorGreen, Flow::DoubleShear];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Flow::KelvinHelmholtz => "kelvin_helmholtz",
+            Flow::TaylorGreen => "taylor_green",
+            Flow::DoubleShear => "double_shear",
+        }
+    }
+}
+
+impl std::str::FromStr for Flow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("benchmark flow", s, &Flow::ALL.map(|f| (f.name(), f)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchmarkSpec {
+    pub flow: Flow,
+    /// Peak speed in grid cells per second: each stream's speed in the
+    /// shear flows, the vortices' in Taylor–Green.
+    pub speed: f32,
+    /// Taylor–Green: vortex pairs across the domain each way.
+    pub modes: u32,
+}
+
+/// Per-cell (dye, velocity) in grid cells per second, row-major, for
+/// `Scene::initial_fields` to add its blobs to.
+pub fn fields(spec: &BenchmarkSpec, grid: u32) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+    let n = grid as usize;
+    let mut density = vec![[0.0f32; 3]; n * n];
+    let mut velocity = vec![[0.0f32; 2]; n * n];
+    let u = spec.speed;
+    for y in 0..n {
+        // Cell centers in domain units
+        let fy = (y as f32 + 0.5) / n as f32;
+        for x in 0..n {
+            let fx = (x as f32 + 0.5) / n as f32;
+            let i = y * n + x;
+            match spec.flow {
+                Flow::KelvinHelmholtz => {
+                    // 0 outside the band, 1 inside, smoothed over 0.025
+                    let inside = kh_profile(fy);
+                    velocity[i] = [u * (1.0 - 2.0 * inside), 0.02 * u * (2.0 * TAU * fx).sin()];
+                    density[i] = mix(BLUE, RED, inside);
+                }
+                Flow::TaylorGreen => {
+                    let k = TAU * spec.modes as f32;
+                    let (sx, cx) = (k * fx).sin_cos();
+                    let (sy, cy) = (k * fy).sin_cos();
+                    velocity[i] = [u * sx * cy, -u * cx * sy];
+                    // Red spins one way, blue the other, brightest at the
+                    // vortex centers
+                    let spin = sx * sy;
+                    density[i] = if spin > 0.0 { RED } else { BLUE }.map(|c| c * spin.abs());
+                }
+                Flow::DoubleShear => {
+                    // Layer thickness 1/80 of the domain, a 5% wave across
+                    let layer = if fy <= 0.5 { (80.0 * (fy - 0.25)).tanh() } else { (80.0 * (0.75 - fy)).tanh() };
+                    velocity[i] = [u * layer, 0.05 * u * (TAU * (fx + 0.25)).sin()];
+                    density[i] = mix(BLUE, RED, 0.5 + 0.5 * layer);
+                }
+            }
+        }
+    }
+    (density, velocity)
+}
+
+/// How far `y` is inside the middle band (¼..¾) of the Kelvin–Helmholtz
+/// setup, 0 to 1, with McNally et al.'s exponential smoothing.
+fn kh_profile(y: f32) -> f32 {
+    const L: f32 = 0.025;
+    if y < 0.25 {
+        0.5 * ((y - 0.25) / L).exp()
+    } else if y < 0.5 {
+        1.0 - 0.5 * ((0.25 - y) / L).exp()
+    } else if y < 0.75 {
+        1.0 - 0.5 * ((y - 0.75) / L).exp()
+    } else {
+        0.5 * ((0.75 - y) / L).exp()
+    }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
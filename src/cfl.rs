@@ -0,0 +1,153 @@
+//! Timestep limit (`--cfl`). Semi-Lagrangian advection is stable at any
+//! step, but a backtrace across dozens of cells smears the flow and lets a
+//! hard mouse fling blow the velocity up. Each step measures the largest
+//! velocity on the GPU (`cfl.wgsl`), and the next step's `dt` shrinks so
+//! no backtrace crosses more than `limit` cells. The flow then runs in slow
+//! motion until it calms down.
+
+use crate::readback::Readback;
+use crate::SimParams;
+
+/// Velocity a mouse stroke adds per cell of movement. Must match
+/// `MOUSE_IMPULSE` in `fluid.wgsl`.
+const MOUSE_IMPULSE: f32 = 50.0;
+/// However fast the flow, `dt` stays above this fraction of the scene's.
+const MIN_FRACTION: f32 = 0.125;
+
+pub struct Cfl {
+    /// Most grid cells a backtrace may cross in one step.
+    pub limit: f32,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    grid_size: u32,
+    result: wgpu::Buffer,
+    readback: Readback,
+    /// Largest |vx| and |vy| after the last step, in x-cells per second.
+    max_velocity: [f32; 2],
+}
+
+impl Cfl {
+    pub fn new(device: &wgpu::Device, velocity_view: &wgpu::TextureView, grid_size: u32, limit: f32) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("cfl_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../cfl.wgsl").into()),
+        });
+        let result = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cfl_result"),
+            size: 8,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cfl_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("cfl_bg"), layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: result.as_entire_binding() },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("max_velocity"), layout: Some(&layout), module: &shader, entry_point: "max_velocity",
+        });
+
+        Self {
+            limit, pipeline, bind_group, grid_size, result,
+            readback: Readback::new(device, "cfl_staging", 8), max_velocity: [0.0; 2],
+        }
+    }
+
+    /// `params` for this step: unchanged while the flow is slow enough,
+    /// otherwise with `dt` cut to the limit. Accounts for the last measured
+    /// velocity plus whatever the mouse is about to add.
+    pub fn apply(&mut self, device: &wgpu::Device, params: &SimParams) -> SimParams {
+        self.collect(device);
+        let stroking = params.mouse_down != 0 && params.rod == 0;
+        let stroke = |axis: usize| if stroking { params.mouse_delta[axis].abs() * MOUSE_IMPULSE } else { 0.0 };
+        let cells_per_sec = (0..2)
+            .map(|axis| self.max_velocity[axis] / params.cell_size[axis] + stroke(axis))
+            .fold(0.0, f32::max);
+        let crossed = cells_per_sec * params.dt;
+        if crossed <= self.limit {
+            return *params;
+        }
+        with_dt(params, (params.dt * self.limit / crossed).max(params.dt * MIN_FRACTION))
+    }
+
+    /// Measure the velocity the step just recorded leaves behind. Call
+    /// `submitted` once the encoder is submitted.
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.readback.is_free() {
+            // Still mapped for the last readback
+            return;
+        }
+        encoder.clear_buffer(&self.result, 0, None);
+        {
+            let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("cfl"), timestamp_writes: None,
+            });
+            let wg = self.grid_size.div_ceil(8);
+            c.set_pipeline(&self.pipeline);
+            c.set_bind_group(0, &self.bind_group, &[]);
+            c.dispatch_workgroups(wg, wg, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.result, 0, self.readback.buffer(), 0, 8);
+        self.readback.recorded(());
+    }
+
+    pub fn submitted(&mut self) {
+        self.readback.submitted();
+    }
+
+    /// Read the last measurement. Natively this waits for it if the GPU
+    /// isn't done yet, so each step's `dt` always follows from the step
+    /// before it and runs stay reproducible. On the web a wait can't block;
+    /// the old measurement is used until the new one arrives.
+    /// A lost device keeps the old measurement.
+    fn collect(&mut self, device: &wgpu::Device) {
+        if let Some(bits) = self.readback.collect(device, true, |(), bytes| bytemuck::pod_read_unaligned::<[u32; 2]>(bytes)) {
+            self.max_velocity = bits.map(f32::from_bits);
+        }
+    }
+}
+
+/// `params` stepped by `dt` instead: the per-step decay factors are
+/// rescaled to match, and so is the rod's motion, which is a velocity
+/// times `dt`. A mouse stroke is an impulse and stays as it is.
+pub fn with_dt(params: &SimParams, dt: f32) -> SimParams {
+    let k = dt / params.dt;
+    let mut p = SimParams {
+        dt,
+        dye_decay: params.dye_decay.powf(k),
+        velocity_decay: params.velocity_decay.powf(k),
+        temperature_decay: params.temperature_decay.powf(k),
+        ..*params
+    };
+    if p.rod != 0 {
+        p.mouse_delta = p.mouse_delta.map(|d| d * k);
+    }
+    p
+}
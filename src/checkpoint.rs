@@ -0,0 +1,144 @@
+//! Full simulation checkpoints (F5 saves, F6 restores, `--checkpoint` starts
+//! from one). Unlike a snapshot, which keeps one dye channel and quantizes
+//! for size, a checkpoint holds everything needed to carry on as if nothing
+//! happened: the velocity, dye (with its color and temperature) and
+//! pressure textures texel for texel, the `SimParams` and the scene whose
+//! emitters and obstacles they ran with.
+//!
+//! Layout: `b"WFC1"`, grid size, `SimParams` size, scene name length and
+//! raw payload length (u32 LE each), the `SimParams` bytes, the scene name,
+//! then the payload, LZ-compressed like a snapshot's. The payload is the
+//! three fields' raw half floats, each split into low and high byte planes.
+
+use crate::simulation::Simulation;
+use crate::snapshot::{lz_compress, lz_decompress};
+use crate::SimParams;
+
+const MAGIC: &[u8; 4] = b"WFC1";
+/// Velocity, dye and pressure.
+const FIELDS: usize = 3;
+
+pub struct Checkpoint {
+    pub grid: u32,
+    /// Builtin name or path, as it was loaded.
+    pub scene: String,
+    pub params: SimParams,
+    /// `Rgba16Float` texels of velocity, dye and pressure.
+    fields: [Vec<[u16; 4]>; FIELDS],
+}
+
+impl Checkpoint {
+    /// Read the simulation back. Blocks until the GPU has caught up.
+    pub fn capture(device: &wgpu::Device, queue: &wgpu::Queue, sim: &Simulation, grid: u32, scene: &str, params: &SimParams) -> Self {
+        let fields = [&sim.velocity, &sim.density, &sim.pressure].map(|tex| crate::read_texels(device, queue, tex, grid));
+        Self { grid, scene: scene.to_string(), params: *params, fields }
+    }
+
+    /// Write the fields into the simulation, which must be on the same grid.
+    pub fn restore(&self, queue: &wgpu::Queue, sim: &Simulation) {
+        for (tex, texels) in [&sim.velocity, &sim.density, &sim.pressure].into_iter().zip(&self.fields) {
+            crate::upload_texels(queue, tex, self.grid, texels);
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.fields.iter().map(|f| f.len() * 8).sum());
+        for field in &self.fields {
+            let halves: &[u16] = bytemuck::cast_slice(field);
+            raw.extend(halves.iter().map(|h| *h as u8));
+            raw.extend(halves.iter().map(|h| (h >> 8) as u8));
+        }
+
+        let params = bytemuck::bytes_of(&self.params);
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        for n in [self.grid, params.len() as u32, self.scene.len() as u32, raw.len() as u32] {
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        out.extend_from_slice(params);
+        out.extend_from_slice(self.scene.as_bytes());
+        lz_compress(&raw, &mut out);
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Checkpoint, String> {
+        if bytes.get(..3) != Some(&MAGIC[..3]) {
+            return Err("not a checkpoint file".to_string());
+        }
+        // The last byte of the magic is the format's version
+        if bytes.get(3) != Some(&MAGIC[3]) {
+            return Err("saved by a different version of wgpu-fluid".to_string());
+        }
+        if bytes.len() < 20 {
+            return Err("checkpoint file is truncated".to_string());
+        }
+        let header: [u32; 4] = std::array::from_fn(|i| u32::from_le_bytes(bytes[4 + i * 4..8 + i * 4].try_into().unwrap()));
+        let [grid, params_len, scene_len, raw_len] = header.map(|n| n as usize);
+        if params_len != std::mem::size_of::<SimParams>() {
+            return Err("saved by a different version of wgpu-fluid".to_string());
+        }
+        let cells = grid * grid;
+        if raw_len != cells * 8 * FIELDS {
+            return Err(format!("payload size {} doesn't match a {}² grid", raw_len, grid));
+        }
+        let params_end = 20 + params_len;
+        let scene_end = params_end + scene_len;
+        if bytes.len() < scene_end {
+            return Err("checkpoint file is truncated".to_string());
+        }
+        let params = bytemuck::pod_read_unaligned(&bytes[20..params_end]);
+        let scene = String::from_utf8(bytes[params_end..scene_end].to_vec()).map_err(|_| "checkpoint data is corrupt".to_string())?;
+        let raw = lz_decompress(&bytes[scene_end..], raw_len)?;
+
+        let mut planes = raw.chunks(cells * 4);
+        let fields = std::array::from_fn(|_| {
+            let (lo, hi) = (planes.next().unwrap(), planes.next().unwrap());
+            let halves: Vec<u16> = lo.iter().zip(hi).map(|(&l, &h)| l as u16 | (h as u16) << 8).collect();
+            halves.chunks_exact(4).map(|t| [t[0], t[1], t[2], t[3]]).collect()
+        });
+        Ok(Checkpoint { grid: grid as u32, scene, params, fields })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8² checkpoint whose texels are all different.
+    fn checkpoint() -> Checkpoint {
+        let cells = 8 * 8;
+        let field = |k: u16| (0..cells as u16).map(|i| [i, i ^ k, i.wrapping_mul(k), 0x3c00 + k]).collect();
+        Checkpoint {
+            grid: 8,
+            scene: "karman".to_string(),
+            params: crate::base_params(&crate::config::Config::default(), [1.0, 0.75]),
+            fields: [field(1), field(0x1234), field(0xbeef)],
+        }
+    }
+
+    #[test]
+    fn round_trips_exactly() {
+        let before = checkpoint();
+        let after = Checkpoint::decode(&before.encode()).unwrap();
+        assert_eq!(after.grid, before.grid);
+        assert_eq!(after.scene, before.scene);
+        assert_eq!(bytemuck::bytes_of(&after.params), bytemuck::bytes_of(&before.params));
+        assert_eq!(after.fields, before.fields);
+    }
+
+    #[test]
+    fn rejects_truncated_and_other_files() {
+        let file = checkpoint().encode();
+        for len in 0..file.len() {
+            assert!(Checkpoint::decode(&file[..len]).is_err(), "{} of {} bytes decoded", len, file.len());
+        }
+        let mut newer = file.clone();
+        newer[3] = b'2';
+        assert_eq!(Checkpoint::decode(&newer).err().unwrap(), "saved by a different version of wgpu-fluid");
+        // `SimParams` grew
+        let mut other_params = file.clone();
+        other_params[8] = other_params[8].wrapping_add(4);
+        assert_eq!(Checkpoint::decode(&other_params).err().unwrap(), "saved by a different version of wgpu-fluid");
+        assert_eq!(Checkpoint::decode(b"WFS1 a snapshot file").err().unwrap(), "not a checkpoint file");
+    }
+}
@@ -0,0 +1,204 @@
+//! Colormaps (Shift + Q, `--colormap`): lookup tables the dye view maps the
+//! dye amount through, and the velocity field view the speed, in place of
+//! the flow-direction hue. Every table is one row of a single LUT texture,
+//! bound to `render.wgsl` at binding 13. The last row is the custom table,
+//! loaded from a horizontal gradient PNG (`--colormap FILE.png`, or dropped
+//! on the window).
+
+/// Samples per table.
+pub const WIDTH: usize = 256;
+
+/// Values are `ViewParams::colormap`: 0 keeps the hue, n uses row n − 1 of
+/// the LUT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    /// No table: hue from the flow's direction, as without a colormap.
+    Flow = 0,
+    Inferno = 1,
+    Viridis = 2,
+    /// Navy through teal to white.
+    Ocean = 3,
+    /// Black through red, orange and yellow to white.
+    Fire = 4,
+    Grayscale = 5,
+    /// Loaded from a PNG, once there is one.
+    Custom = 6,
+}
+
+impl Colormap {
+    /// The tables that are built in, plus flow.
+    const BUILTIN: [Colormap; 6] = [
+        Colormap::Flow, Colormap::Inferno, Colormap::Viridis, Colormap::Ocean, Colormap::Fire, Colormap::Grayscale,
+    ];
+
+    /// The next colormap, through the custom one if it's been loaded.
+    pub fn next(self, custom: bool) -> Self {
+        match self {
+            Colormap::Grayscale if custom => Colormap::Custom,
+            Colormap::Custom => Colormap::Flow,
+            c => Self::BUILTIN[(c as usize + 1) % Self::BUILTIN.len()],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Flow => "flow",
+            Colormap::Inferno => "inferno",
+            Colormap::Viridis => "viridis",
+            Colormap::Ocean => "ocean",
+            Colormap::Fire => "fire",
+            Colormap::Grayscale => "grayscale",
+            Colormap::Custom => "custom",
+        }
+    }
+
+    /// The table's color at `t` in 0..1, sRGB.
+    fn sample(self, t: f32) -> [f32; 3] {
+        match self {
+            // Polynomial fits of matplotlib's maps (Matt Zucker)
+            Colormap::Inferno => polynomial(&[
+                [0.000_22, 0.001_65, -0.019_48],
+                [0.106_51, 0.563_96, 3.932_71],
+                [11.602_49, -3.972_85, -15.942_39],
+                [-41.704, 17.436_4, 44.354_15],
+                [77.162_94, -33.402_36, -81.807_31],
+                [-71.319_43, 32.626_06, 73.209_52],
+                [25.131_13, -12.242_67, -23.070_33],
+            ], t),
+            Colormap::Viridis => polynomial(&[
+                [0.277_73, 0.005_41, 0.334_1],
+                [0.105_09, 1.404_61, 1.384_59],
+                [-0.330_86, 0.214_85, 0.095_09],
+                [-4.634_23, -5.799_1, -19.332_44],
+                [6.228_27, 14.179_93, 56.690_55],
+                [4.776_39, -13.745_15, -65.353_03],
+                [-5.435_46, 4.645_85, 26.312_44],
+            ], t),
+            Colormap::Ocean => ramp(&[
+                [0.0, 0.02, 0.08], [0.0, 0.15, 0.35], [0.0, 0.45, 0.6], [0.35, 0.8, 0.85], [0.95, 1.0, 1.0],
+            ], t),
+            Colormap::Fire => ramp(&[
+                [0.0, 0.0, 0.0], [0.5, 0.02, 0.0], [0.9, 0.3, 0.0], [1.0, 0.75, 0.1], [1.0, 1.0, 0.85],
+            ], t),
+            Colormap::Flow | Colormap::Grayscale | Colormap::Custom => [t; 3],
+        }
+    }
+}
+
+impl std::str::FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Self::BUILTIN.into_iter().find(|c| c.name() == s).ok_or_else(|| {
+            let names = Self::BUILTIN.map(|c| c.name());
+            format!("unknown colormap `{}` (expected {})", s, crate::config::one_of(names.into_iter().chain(["a .png file"])))
+        })
+    }
+}
+
+/// Σ coefficients[i]·tⁱ per channel.
+fn polynomial(coefficients: &[[f32; 3]], t: f32) -> [f32; 3] {
+    let mut c = [0.0; 3];
+    for k in coefficients.iter().rev() {
+        for (c, k) in c.iter_mut().zip(k) {
+            *c = *c * t + k;
+        }
+    }
+    c
+}
+
+/// Linear interpolation between evenly spaced stops.
+fn ramp(stops: &[[f32; 3]], t: f32) -> [f32; 3] {
+    let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (x as usize).min(stops.len() - 2);
+    let f = x - i as f32;
+    [0, 1, 2].map(|c| stops[i][c] + (stops[i + 1][c] - stops[i][c]) * f)
+}
+
+/// The LUT texture: one `WIDTH`-wide row per table, sRGB so the shader
+/// reads linear colors.
+pub struct Lut {
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl Lut {
+    /// The built-in tables, with the custom row gray until one is loaded.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let tables = [&Colormap::BUILTIN[1..], &[Colormap::Custom]].concat();
+        let size = wgpu::Extent3d { width: WIDTH as u32, height: tables.len() as u32, depth_or_array_layers: 1 };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("colormap_lut"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let mut texels = Vec::with_capacity(WIDTH * 4 * tables.len());
+        for map in tables {
+            for i in 0..WIDTH {
+                let [r, g, b] = map.sample(i as f32 / (WIDTH - 1) as f32);
+                texels.extend([r, g, b].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+                texels.push(255);
+            }
+        }
+        queue.write_texture(
+            texture.as_image_copy(),
+            &texels,
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(WIDTH as u32 * 4), rows_per_image: None },
+            size,
+        );
+        let view = texture.create_view(&Default::default());
+        Self { texture, view }
+    }
+
+    /// Load the custom table from a PNG file. The image's middle row is
+    /// stretched or squeezed to `WIDTH` texels; alpha is ignored.
+    pub fn load(&self, queue: &wgpu::Queue, path: &str) -> Result<(), String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let image = crate::png::decode(&bytes)?;
+        let width = image.width as usize;
+        let row = &image.rgba[image.height as usize / 2 * width * 4..][..width * 4];
+        let mut texels = Vec::with_capacity(WIDTH * 4);
+        for i in 0..WIDTH {
+            let x = i as f32 / (WIDTH - 1) as f32 * (width - 1) as f32;
+            let (a, f) = (x as usize, x.fract());
+            let b = (a + 1).min(width - 1);
+            for c in 0..3 {
+                texels.push((row[a * 4 + c] as f32 * (1.0 - f) + row[b * 4 + c] as f32 * f).round() as u8);
+            }
+            texels.push(255);
+        }
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: Colormap::Custom as u32 - 1, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &texels,
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(WIDTH as u32 * 4), rows_per_image: None },
+            wgpu::Extent3d { width: WIDTH as u32, height: 1, depth_or_array_layers: 1 },
+        );
+        Ok(())
+    }
+}
+
+/// The colormap to start on and whether the custom table is loaded: with
+/// `--colormap FILE.png`, that file in `lut`, or flow if it can't be read.
+pub fn startup(lut: &Lut, queue: &wgpu::Queue, colormap: Colormap, file: Option<&str>) -> (Colormap, bool) {
+    let Some(path) = file else { return (colormap, false) };
+    match lut.load(queue, path) {
+        Ok(()) => {
+            eprintln!("Colormap: {}", path);
+            (Colormap::Custom, true)
+        }
+        Err(e) => {
+            eprintln!("Failed to load colormap {}: {}", path, e);
+            (Colormap::Flow, false)
+        }
+    }
+}
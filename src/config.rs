@@ -0,0 +1,1182 @@
+use clap::{CommandFactory, Parser};
+use winit::keyboard::KeyCode;
+
+use crate::decay::Decay;
+use crate::dye::DyeColor;
+use crate::field_export;
+use crate::image_seed;
+use crate::mouse_force::MouseForce;
+use crate::noise::NoiseSpec;
+use crate::pressure::SolverKind;
+use crate::reaction::GrayScottPattern;
+use crate::rng::Seed;
+use crate::rod::Rod;
+use crate::scene::{Boundary, GravityMode};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::session::Session;
+use crate::simulation::Advection;
+use crate::supersample::MAX_FACTOR;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::tune::TuneSpec;
+use crate::two_phase::{Phase, TwoPhaseScene};
+use crate::volume::{Stereo, MAX_EYE_SEPARATION, MAX_STEPS, MIN_STEPS};
+use crate::wall::WallTile;
+use crate::workarounds::Workarounds;
+
+// The command line as clap reads it (the doc comments are the --help).
+// Values are left `None` when not given, so the checks between flags can
+// tell a flag given its default value from one left out, and
+// `Config::default` stays the one place the defaults live.
+/// Real-time 2D fluid simulation on the GPU
+#[derive(Parser, Debug)]
+#[command(name = "wgpu-fluid", args_override_self = true)]
+struct Args {
+    /// Simulation resolution in cells per side [default: 256]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (16..=4096).contains(n), "between 16 and 4096"))]
+    grid: Option<u32>,
+    /// Simulate an N³ volume instead, ray-marched from an orbiting camera
+    /// (16 to 256; 64–128 run well on most GPUs)
+    #[arg(long = "3d", value_name = "N", value_parser = checked(|n: &u32| (16..=256).contains(n), "between 16 and 256"))]
+    volume: Option<u32>,
+    /// 3D mode stereo output: off, anaglyph (red-cyan glasses) or sbs (side by
+    /// side for 3D displays); V cycles it [default: off]
+    #[arg(long, value_name = "MODE")]
+    stereo: Option<Stereo>,
+    /// Distance between the stereo eyes in cube widths; [ / ] change it
+    /// (0 to 0.3) [default: 0.06]
+    #[arg(long, value_name = "F", value_parser = checked(|f: &f32| (0.0..=MAX_EYE_SEPARATION).contains(f), format!("between 0 and {}", MAX_EYE_SEPARATION)))]
+    eye_separation: Option<f32>,
+    /// 3D mode ray march samples per cube width; - / = halve and double it
+    /// (8 to 1024) [default: one per cell]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (MIN_STEPS..=MAX_STEPS).contains(n), format!("between {} and {}", MIN_STEPS, MAX_STEPS)))]
+    volume_steps: Option<u32>,
+    /// Simulate a splashing liquid of N particles instead (FLIP/PIC), projected
+    /// on the --grid with Jacobi (1024 to 1048576; try 32768 with --grid 128)
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (1024..=1 << 20).contains(n), "between 1024 and 1048576"))]
+    flip: Option<u32>,
+    /// Simulate a liquid of N particles with weakly compressible SPH instead, in
+    /// a --grid sized box (1024 to 262144; try 16384 with the default grid)
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (1024..=1 << 18).contains(n), "between 1024 and 262144"))]
+    sph: Option<u32>,
+    /// Simulate an interactive pond of --grid² cells with the shallow water
+    /// equations instead
+    #[arg(long)]
+    shallow: bool,
+    /// Simulate two immiscible fluids of different density instead, on the --grid:
+    /// rayleigh-taylor (heavy over light) or oil-water (an oil blob rising
+    /// through water); 1 / 2 switch scenes
+    #[arg(long, value_name = "SCENE")]
+    two_phase: Option<TwoPhaseScene>,
+    /// Simulate a free-surface liquid on the --grid alone instead, its surface a
+    /// level set; left drag pushes it, right drag pours more
+    #[arg(long)]
+    level_set: bool,
+    /// Simulate flames on the --grid instead: fuel from a burner along the floor
+    /// burns into heat and soot; left drag sprays fuel, right drag is a torch
+    #[arg(long)]
+    fire: bool,
+    /// Simulate Gray–Scott reaction-diffusion on the --grid instead, carried by the
+    /// flow: coral, fingerprint, spots or mitosis; left drag stirs, right drag seeds
+    #[arg(long, value_name = "PATTERN")]
+    gray_scott: Option<GrayScottPattern>,
+    /// Level set and two-phase modes: surface tension in cells³/s², so drops bead
+    /// up and merge (try 200; capped where it would go unstable) [default: 0]
+    #[arg(long, value_name = "S", value_parser = checked(|s: &f32| *s >= 0.0 && s.is_finite(), "0 or more"))]
+    surface_tension: Option<f32>,
+    /// Two-phase mode: density and viscosity in cells²/s of the first and second
+    /// fluid [default: the scene's]
+    #[arg(long, value_name = "D1[:V1],D2[:V2]", value_parser = Phase::parse_pair)]
+    fluids: Option<[Phase; 2]>,
+    /// Pressure solver: jacobi[:iterations], jacobi:auto[:target[:max_iterations]]
+    /// (iterations follow the measured residual), multigrid[:cycles] or
+    /// cg[:tolerance[:max_iterations]]; @half after jacobi or multigrid solves
+    /// at half resolution, faster but blurrier [default: jacobi:20]
+    #[arg(long, value_name = "NAME[:N]")]
+    solver: Option<SolverKind>,
+    /// Solver for the comparison clone (K), same syntax as --solver
+    /// [default: the same at full resolution for @half, multigrid:1 for
+    /// jacobi, jacobi:20 otherwise]
+    #[arg(long, value_name = "NAME[:N]")]
+    compare: Option<SolverKind>,
+    /// How velocity moves itself: semi-lagrangian, or reflection (experimental:
+    /// advection-reflection, which keeps more of the swirl's energy for a
+    /// second pressure solve per step) [default: semi-lagrangian]
+    #[arg(long, value_name = "SCHEME")]
+    advection: Option<Advection>,
+    /// Advection for the comparison clone (K); without --compare the clone then
+    /// keeps the solver, so only the advection differs [default: --advection]
+    #[arg(long, value_name = "SCHEME")]
+    compare_advection: Option<Advection>,
+    /// Initial window size in logical pixels [default: 800x800]
+    #[arg(long, value_name = "WxH", value_parser = size)]
+    window: Option<(u32, u32)>,
+    /// on, off, fifo, mailbox or immediate [default: on]
+    #[arg(long, value_name = "MODE", value_parser = |s: &str| parse_choice("vsync mode", s, &VSYNC_MODES))]
+    vsync: Option<wgpu::PresentMode>,
+    /// all, primary, vulkan, metal, dx12, gl or webgpu [default: all]
+    #[arg(long, value_name = "NAME", value_parser = |s: &str| parse_choice("backend", s, &BACKENDS))]
+    backend: Option<wgpu::Backends>,
+    /// high, low or none [default: high]
+    #[arg(long, value_name = "PREF", value_parser = |s: &str| parse_choice("power preference", s, &POWER_PREFERENCES))]
+    power: Option<wgpu::PowerPreference>,
+    /// Initial viscosity in cells²/s, 0 to disable [default: 0]
+    #[arg(long, value_name = "F")]
+    viscosity: Option<f32>,
+    /// Initial dye diffusivity in cells²/s, 0 to disable [default: 0]
+    #[arg(long, value_name = "F")]
+    diffusion: Option<f32>,
+    /// Color the mouse injects: white, red, yellow, green, cyan, blue, magenta,
+    /// rainbow (hue cycles over time) or direction (hue follows the stroke);
+    /// X cycles it [default: white]
+    #[arg(long, value_name = "NAME")]
+    dye_color: Option<DyeColor>,
+    /// Seconds per hue cycle for the rainbow dye color [default: 6]
+    #[arg(long, value_name = "SECS", value_parser = checked(|s: &f32| *s > 0.0, "positive"))]
+    dye_cycle: Option<f32>,
+    /// How mouse movement maps to injected momentum: linear, squared (gentle
+    /// when slow, matching linear at CAP cells) or capped (at most CAP cells);
+    /// M cycles the curve, , / . halve / double the sensitivity [default: linear:1:8]
+    #[arg(long, value_name = "CURVE[:SENSITIVITY[:CAP]]")]
+    mouse_force: Option<MouseForce>,
+    /// Simulation steps per frame, each a 1/N of dt; N / Shift + N change it
+    /// (1 to 16) [default: 1]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (1..=crate::MAX_SUBSTEPS).contains(n), format!("between 1 and {}", crate::MAX_SUBSTEPS)))]
+    substeps: Option<u32>,
+    /// Most cells a step's advection may trace back; dt shrinks (slow motion)
+    /// while the flow is faster, e.g. after a hard fling [default: 4]
+    #[arg(long, value_name = "CELLS|off", value_parser = cfl)]
+    cfl: Option<OrNone<f32>>,
+    /// Start with the stirring rod (I) instead of plain mouse strokes: the cursor
+    /// pulls a rod with this mass and drag through the fluid [default: 1:6]
+    #[arg(long, value_name = "MASS[:DRAG]")]
+    rod: Option<Rod>,
+    /// How dye fades: none, half-life:<secs> or threshold:<secs>:<min> [default: half-life:5.5]
+    #[arg(long, value_name = "MODEL")]
+    dye_decay: Option<Decay>,
+    /// How velocity fades, same models as --dye-decay [default: half-life:5.5]
+    #[arg(long, value_name = "MODEL")]
+    velocity_decay: Option<Decay>,
+    /// How strongly heat rises, in cells/s² per unit of temperature [default: 20]
+    #[arg(long, value_name = "F", allow_hyphen_values = true)]
+    buoyancy: Option<f32>,
+    /// How heat cools off, same models as --dye-decay [default: half-life:3]
+    #[arg(long, value_name = "MODEL")]
+    temperature_decay: Option<Decay>,
+    /// Extra downward accel in cells/s² per unit of red, green and blue dye;
+    /// negative rises, e.g. 30,0,-30 sinks red and floats blue [default: 0,0,0]
+    #[arg(long, value_name = "R,G,B", allow_hyphen_values = true, value_parser = rgb)]
+    dye_density: Option<[f32; 3]>,
+    /// Body force in cells/s², +y down; G toggles it [default: 0,0]
+    #[arg(long, value_name = "X,Y", allow_hyphen_values = true, value_parser = xy)]
+    gravity: Option<[f32; 2]>,
+    /// dye (force scales with dye, ink falls) or uniform [default: dye]
+    #[arg(long, value_name = "MODE")]
+    gravity_mode: Option<GravityMode>,
+    /// Gravity well at the center pulling on dye, peak cells/s² per unit of dye
+    /// (see the galaxy scene) [default: 0]
+    #[arg(long, value_name = "F", allow_hyphen_values = true)]
+    well: Option<f32>,
+    /// Small eddies stirred into sheared flow behind obstacles, in cells/s²,
+    /// faking turbulence the grid is too coarse to resolve [default: 0]
+    #[arg(long, value_name = "F")]
+    wake: Option<f32>,
+    /// Sub-grid turbulence produced per unit of shear; the renderer breaks dye
+    /// up with fine noise where it builds up [default: 0]
+    #[arg(long, value_name = "F")]
+    subgrid: Option<f32>,
+    /// Density a unit of dye adds to the clear fluid's 1; the Jacobi projection
+    /// weights by 1/density, so with --gravity-mode uniform heavy dye sinks
+    /// [default: 0]
+    #[arg(long, value_name = "F", allow_hyphen_values = true)]
+    dye_mass: Option<f32>,
+    /// Density a unit of temperature takes away, so hot fluid rises the same
+    /// way [default: 0]
+    #[arg(long, value_name = "F", allow_hyphen_values = true)]
+    heat_expansion: Option<f32>,
+    /// Strength (0-1) of the render-time detail layer: fine noise at screen
+    /// resolution carried by the flow and multiplied into the dye; J toggles
+    /// it [default: 0]
+    #[arg(long, value_name = "F", value_parser = checked(|f: &f32| (0.0..=1.0).contains(f), "between 0 and 1"))]
+    detail: Option<f32>,
+    /// Vortex particles (up to 16384) seeded where the flow swirls; they carry
+    /// its curl and splat it back where the grid has smeared it away
+    /// [default: 0, off]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| *n <= crate::vortex::MAX_COUNT, format!("at most {}", crate::vortex::MAX_COUNT)))]
+    vortex_particles: Option<u32>,
+    /// Longest a vortex particle lives [default: 4]
+    #[arg(long, value_name = "SECONDS", value_parser = checked(|s: &f32| *s > 0.0 && s.is_finite(), "positive"))]
+    vortex_life: Option<f32>,
+    /// Start with the wall shear outline (S) on, outlining obstacles thickest and
+    /// hottest where the shear rate reaches RATE per second [default: 100]
+    #[arg(long, value_name = "RATE", value_parser = checked(|r: &f32| *r > 0.0 && r.is_finite(), "positive"))]
+    wall_shear: Option<f32>,
+    /// Start with the streamline overlay (Z) on, tracing lines from a
+    /// SEEDS×SEEDS grid, 2 to 128 [default: 32]
+    #[arg(long, value_name = "SEEDS", value_parser = checked(|n: &u32| (2..=128).contains(n), "2 to 128"))]
+    streamlines: Option<u32>,
+    /// Start with the velocity arrows (A) on, N×N of them, 2 to 128 [default: 32]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (2..=128).contains(n), "2 to 128"))]
+    arrows: Option<u32>,
+    /// Draw in HDR and map it to the window through clamp, reinhard or aces,
+    /// after an exposure of STOPS (U cycles the curve, Y / Shift + Y step the
+    /// exposure) [default: off; stops default: 0]
+    #[arg(long, value_name = "CURVE[:STOPS]")]
+    tonemap: Option<crate::tonemap::TonemapSpec>,
+    /// Draw the scene at N times the window's size on each side, 1 to 4, and
+    /// average it down, smoothing the edges of the upsampled grid [default: 1]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (1..=MAX_FACTOR).contains(n), format!("1 to {}", MAX_FACTOR)))]
+    supersample: Option<u32>,
+    /// Shade the dye: plain, spin (tinted red / blue by the direction the fluid
+    /// turns), shear (darkened where it turns fastest) or lit (a relief under a
+    /// light); Shift + V cycles it [default: plain]
+    #[arg(long, value_name = "MODE")]
+    shading: Option<crate::shading::Shading>,
+    /// Where lit shading's light comes from, counterclockwise from the right of
+    /// the screen; Shift + L turns it 45° [default: 135, upper left]
+    #[arg(long, value_name = "DEGREES", allow_hyphen_values = true, value_parser = checked(|d: &f32| d.is_finite(), "an angle in degrees"))]
+    light: Option<f32>,
+    /// Start with motion blur (Shift + B) on, each frame keeping DECAY (0 to 1) of
+    /// the trail of the ones before [default: off; Shift + B uses 0.8]
+    #[arg(long, value_name = "DECAY", value_parser = checked(|d: &f32| (0.0..1.0).contains(d), "at least 0 and below 1"))]
+    motion_blur: Option<f32>,
+    /// Start with the field inspector (Shift + I) on, showing the values of the
+    /// cell under the cursor in the window title
+    #[arg(long)]
+    inspect: bool,
+    /// Start with dithering (Shift + D) off, showing the 8-bit window's banding
+    #[arg(long)]
+    no_dither: bool,
+    /// Color the dye and the velocity field view through flow (direction as hue),
+    /// inferno, viridis, ocean, fire, grayscale or a horizontal gradient PNG;
+    /// Shift + Q cycles it [default: flow]
+    #[arg(long, value_name = "NAME|FILE.png", value_parser = colormap)]
+    colormap: Option<(crate::colormap::Colormap, Option<String>)>,
+    /// Simulated seconds a long exposure (L) averages before it's shown and
+    /// saved as exposure-<time>.ppm [default: 4]
+    #[arg(long, value_name = "SECONDS", value_parser = checked(|s: &f32| *s > 0.0, "positive"))]
+    exposure: Option<f32>,
+    /// Grid edges: walls (closed box), wrap (opposite edges join, so the flow
+    /// tiles seamlessly) or outflow (open: what leaves is gone); W cycles it
+    /// [default: walls]
+    #[arg(long, value_name = "MODE")]
+    boundary: Option<Boundary>,
+    /// Ambient wind in cells/s, optionally gusting by ±GUST (a fraction)
+    /// every PERIOD seconds [default: 0,0:0:4]
+    #[arg(long, value_name = "X,Y[:GUST[:PERIOD]]", allow_hyphen_values = true, value_parser = wind)]
+    wind: Option<Wind>,
+    /// Initial vorticity confinement strength, 0 to disable [default: 12]
+    #[arg(long, value_name = "F")]
+    vorticity: Option<f32>,
+    /// Builtin scene name or scene .toml file [default: default]
+    #[arg(long, value_name = "NAME|PATH")]
+    scene: Option<String>,
+    /// Start from the embedded demo state (turbulence scene)
+    #[arg(long)]
+    demo: bool,
+    /// Start from a snapshot saved with F9
+    #[arg(long, value_name = "PATH")]
+    snapshot: Option<String>,
+    /// Start with the dye from a PNG or baseline JPEG, stretched over the grid,
+    /// instead of the scene's blobs
+    #[arg(long, value_name = "PATH")]
+    seed_image: Option<String>,
+    /// What of the image becomes dye [default: luminance]
+    #[arg(long, value_name = "luminance|alpha")]
+    seed_image_channel: Option<image_seed::Channel>,
+    /// Also start the fluid moving along the image's edges, peaking at SPEED
+    /// cells/s [default: 0]
+    #[arg(long, value_name = "SPEED", value_parser = checked(|s: &f32| *s >= 0.0 && s.is_finite(), "zero or positive"))]
+    seed_image_velocity: Option<f32>,
+    /// What F7 writes the raw fields as: one OpenEXR file, or a directory of
+    /// numpy arrays (native only) [default: exr]
+    #[arg(long, value_name = "exr|npy")]
+    field_format: Option<field_export::Format>,
+    /// Carry on from a checkpoint saved with F5: its scene, fields and parameters
+    /// (native only)
+    #[arg(long, value_name = "PATH")]
+    checkpoint: Option<String>,
+    /// Load a velocity field made elsewhere over the starting state, resampled
+    /// to the grid; F8 loads it again (native only)
+    #[arg(long, value_name = "FILE.exr|FILE.npy")]
+    velocity_field: Option<String>,
+    /// Keep the --velocity-field as it is, only carrying the dye along it
+    #[arg(long)]
+    freeze_velocity: bool,
+    /// Start with glass shading (Shift + W) on: the dye as a rippling clear surface
+    /// refracting the background
+    #[arg(long)]
+    glass: bool,
+    /// Picture glass shading refracts [default: pool tiles]
+    #[arg(long, value_name = "FILE.png")]
+    background: Option<String>,
+    /// Show a picture in place of the dye and let the flow smear it (R puts it
+    /// back together)
+    #[arg(long, value_name = "FILE.png")]
+    image: Option<String>,
+    /// Start every scene stirred by divergence-free noise with energy spectrum
+    /// E(k) ∝ k^SLOPE instead of at rest, e.g. -1.67 for Kolmogorov turbulence;
+    /// SEED picks the field [seed default: 1]
+    #[arg(long, value_name = "SLOPE[:SEED]", allow_hyphen_values = true)]
+    init_noise: Option<NoiseSpec>,
+    /// Don't restore the last 2D session from wgpu-fluid-session.toml (it's still
+    /// saved on exit)
+    #[arg(long)]
+    fresh: bool,
+    /// Seed for everything random: scene noise, particle respawns, the liquid
+    /// modes' starting layouts. random picks one and prints it [default: 0]
+    #[arg(long, value_name = "N|random")]
+    seed: Option<Seed>,
+    /// Snapshot to diff against in the difference view (D)
+    #[arg(long, value_name = "PATH")]
+    reference: Option<String>,
+    /// Run the sim and stream tiles to wall followers, e.g. 0.0.0.0:7878
+    #[arg(long, value_name = "ADDR")]
+    wall_leader: Option<String>,
+    /// Show one tile of a leader's sim instead of simulating (needs --wall-tile)
+    #[arg(long, value_name = "ADDR")]
+    wall_follower: Option<String>,
+    /// This follower's screen on the wall, e.g. 1,0/3x1
+    #[arg(long, value_name = "COL,ROW/COLSxROWS")]
+    wall_tile: Option<WallTile>,
+    /// Print a hash of the dye field every N steps, to compare runs across GPUs
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u64| *n >= 1, "at least 1"))]
+    hash_frames: Option<u64>,
+    /// Also write the --hash-frames hashes to this file
+    #[arg(long, value_name = "PATH")]
+    hash_out: Option<String>,
+    /// Print a JSON line of frame stats (fps, pass times, dye mass, max speed,
+    /// divergence) every frame, to stdout or to clients of ADDR, e.g. 127.0.0.1:7880
+    #[arg(long, value_name = "stdout|ADDR")]
+    stats_json: Option<String>,
+    /// Key that saves the window as screenshot-<time>.png: F1 to F12,
+    /// PrintScreen, Pause, ScrollLock, Insert, Home, End, PageUp or PageDown;
+    /// it takes over the key's own action (native only) [default: F12]
+    #[arg(long, value_name = "KEY", value_parser = |s: &str| parse_choice("screenshot key", s, &SCREENSHOT_KEYS))]
+    screenshot_key: Option<KeyCode>,
+    /// Start recording the window (F10) into PATH as an H.264 MP4 through ffmpeg,
+    /// which has to be on the PATH (native only)
+    #[arg(long, value_name = "PATH.mp4")]
+    record: Option<String>,
+    /// Frame rate of recordings; frames are repeated or dropped to keep real
+    /// time (1 to 240) [default: 60]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (1..=240).contains(n), "1 to 240"))]
+    record_fps: Option<u32>,
+    /// Frames F11 captures into capture-<time>.gif (1 to 1000, native only)
+    /// [default: 100]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (1..=1000).contains(n), "1 to 1000"))]
+    gif_frames: Option<u32>,
+    /// Frame rate of GIF captures (1 to 50) [default: 25]
+    // GIF delays are in hundredths, and most viewers slow anything under two
+    // of them down to ten
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| (1..=50).contains(n), "1 to 50"))]
+    gif_fps: Option<u32>,
+    /// Width GIF captures are scaled down to, 0 for the window's [default: 480]
+    #[arg(long, value_name = "PX")]
+    gif_width: Option<u32>,
+    /// Start with the HUD (F1) on: frame rate, frame time, GPU, compute dispatches
+    /// and the current parameters in the top left corner (native only)
+    #[arg(long)]
+    hud: bool,
+    /// Write total kinetic energy, enstrophy and dye mass after every step to a
+    /// CSV file (E plots them)
+    #[arg(long, value_name = "PATH")]
+    diagnostics: Option<String>,
+    /// Run the sim headless and stream it to --connect clients, e.g. 0.0.0.0:7879
+    #[arg(long, value_name = "ADDR")]
+    serve: Option<String>,
+    /// Draw a --serve server's sim and send it this window's input
+    #[arg(long, value_name = "ADDR")]
+    connect: Option<String>,
+    /// Render a thumbnail of every builtin scene headlessly into DIR as
+    /// <scene>.png, then exit
+    #[arg(long, value_name = "DIR")]
+    thumbnails: Option<String>,
+    /// Also write DIR/index.html showing the thumbnails with each scene's
+    /// description
+    #[arg(long)]
+    thumbnails_html: bool,
+    /// Run the scene headlessly with a fixed dt and write frames into DIR as
+    /// frame-00000.png, frame-00001.png, ..., then exit (native only)
+    #[arg(long, value_name = "DIR")]
+    export: Option<String>,
+    /// Frames to write [default: 240]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| *n >= 1, "at least 1"))]
+    export_frames: Option<u32>,
+    /// Steps between written frames [default: 1]
+    #[arg(long, value_name = "N", value_parser = checked(|n: &u32| *n >= 1, "at least 1"))]
+    export_every: Option<u32>,
+    /// Resolution of the written frames [default: the --window size]
+    #[arg(long, value_name = "WxH", value_parser = size)]
+    export_size: Option<(u32, u32)>,
+    /// Camera keys, param crossfades and emitter cues for --export, in the
+    /// scene files' TOML subset
+    #[arg(long, value_name = "FILE")]
+    export_script: Option<String>,
+    /// What O optimizes: energy, divergence or fps, by varying vorticity,
+    /// viscosity, diffusion, smoke-weight and/or heat-lift
+    /// [default: energy:vorticity,viscosity:120]
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "METRIC[:PARAMS[:STEPS]]")]
+    tune: Option<TuneSpec>,
+    /// Shader workarounds: auto, none, all or names, e.g. split-read-write
+    /// [default: auto, picked from the GPU and backend]
+    #[arg(long, value_name = "LIST", value_parser = workarounds)]
+    workarounds: Option<OrNone<Workarounds>>,
+}
+
+/// Startup options. Everything here is fixed for the lifetime of the app;
+/// per-frame tunables live in `SimParams`.
+#[derive(Clone, Debug)]
+// The web build reads only some: the rest are for native-only features
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub struct Config {
+    pub pressure_solver: SolverKind,
+    /// Solver for the clone made by the comparison toggle; `None` picks
+    /// `pressure_solver.contrast()`.
+    pub compare_solver: Option<SolverKind>,
+    /// How the 2D simulation advects velocity, and how the comparison
+    /// clone does if it differs.
+    pub advection: Advection,
+    pub compare_advection: Option<Advection>,
+    /// Watch the `.wgsl` files in the crate directory and rebuild pipelines
+    /// when they change. On by default in debug builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub hot_reload: bool,
+    /// Builtin scene name or path to a scene `.toml` file.
+    pub scene: String,
+    /// Start from the embedded demo snapshot instead of the scene's own
+    /// initial fields.
+    pub demo: bool,
+    /// Start from a saved snapshot file. Takes precedence over `demo`.
+    pub snapshot: Option<String>,
+    /// Picture to start the dye (and maybe velocity) from.
+    pub seed_image: Option<String>,
+    /// What of the picture becomes dye.
+    pub seed_image_channel: image_seed::Channel,
+    /// Peak speed of the picture's starting velocity; 0 leaves it at rest.
+    pub seed_image_velocity: f32,
+    /// What F7 writes the raw fields as.
+    pub field_format: field_export::Format,
+    /// Start from a saved checkpoint, scene and parameters included.
+    pub checkpoint: Option<String>,
+    /// OpenEXR or numpy velocity field to load over the starting state.
+    pub velocity_field: Option<String>,
+    /// Hold the imported velocity fixed and only advect the dye.
+    pub freeze_velocity: bool,
+    /// Start with glass shading on.
+    pub glass: bool,
+    /// PNG glass shading refracts, instead of the tiles.
+    pub background: Option<String>,
+    /// PNG the image distortion mode shows and smears, if on.
+    pub image: Option<String>,
+    /// Noise velocity every scene starts with, as if each had this
+    /// `[noise]` table.
+    pub init_noise: Option<NoiseSpec>,
+    /// Skip restoring the saved session.
+    pub fresh: bool,
+    /// The saved session's settings the command line left alone, for the
+    /// 2D window to restore over its scene.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub session: Option<Session>,
+    /// Where every random number of the session comes from.
+    pub seed: Seed,
+    /// Snapshot file preloaded as the difference view's reference state.
+    pub reference: Option<String>,
+    /// Address to accept video-wall followers on.
+    pub wall_leader: Option<String>,
+    /// Leader address and this screen's tile, when following.
+    pub wall_follower: Option<(String, WallTile)>,
+    /// Hash the dye field every this many steps.
+    pub hash_frames: Option<u64>,
+    /// File the hashes are also written to.
+    pub hash_out: Option<String>,
+    /// `stdout` or an address to stream per-frame stats to.
+    pub stats_json: Option<String>,
+    /// Key that saves a screenshot.
+    pub screenshot_key: KeyCode,
+    /// MP4 to record into from the start.
+    pub record: Option<String>,
+    /// Frame rate of recordings.
+    pub record_fps: u32,
+    /// Frames in a GIF capture.
+    pub gif_frames: u32,
+    /// Frame rate of GIF captures.
+    pub gif_fps: u32,
+    /// Widest a GIF capture gets, 0 for no limit.
+    pub gif_width: u32,
+    /// Start with the HUD showing.
+    pub hud: bool,
+    /// CSV file to log the energy, enstrophy and dye mass to.
+    pub diagnostics: Option<String>,
+    /// Address to accept clients on; runs without a window.
+    pub serve: Option<String>,
+    /// Directory to render the scene thumbnails into, headless, instead of
+    /// opening a window.
+    pub thumbnails: Option<String>,
+    /// Also write an HTML index of the thumbnails.
+    pub thumbnails_html: bool,
+    /// Directory to write an image sequence of the scene into, headless,
+    /// instead of opening a window.
+    pub export: Option<String>,
+    /// Frames in the image sequence.
+    pub export_frames: u32,
+    /// Steps between frames of the image sequence.
+    pub export_every: u32,
+    /// Resolution of the image sequence, if not the window's.
+    pub export_size: Option<(u32, u32)>,
+    /// Camera and parameter timeline for the image sequence.
+    pub export_script: Option<String>,
+    /// Server to draw and send input to instead of simulating locally.
+    pub connect: Option<String>,
+    /// Metric, parameters and trial length for the parameter search (O).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub tune: TuneSpec,
+    /// Shader workarounds to force; `None` picks them from the adapter.
+    pub workarounds: Option<Workarounds>,
+    /// Simulation cells per side.
+    pub grid_size: u32,
+    /// Cells per side of the 3D volume, when running the 3D mode instead.
+    pub volume: Option<u32>,
+    /// How the 3D mode shows the volume, and its eyes' distance apart in
+    /// cube widths.
+    pub stereo: Stereo,
+    pub eye_separation: f32,
+    /// 3D mode ray march samples per cube width, if not one per cell.
+    pub volume_steps: Option<u32>,
+    /// Particle count of the FLIP liquid, when running the FLIP mode instead.
+    pub flip: Option<u32>,
+    /// Particle count of the SPH liquid, when running the SPH mode instead.
+    pub sph: Option<u32>,
+    /// Run the shallow water mode instead.
+    pub shallow: bool,
+    /// Starting scene of the two-phase mode, when running it instead, and
+    /// the fluids to fill it with over the scene's own.
+    pub two_phase: Option<TwoPhaseScene>,
+    pub fluids: Option<[Phase; 2]>,
+    /// Run the level set liquid mode instead.
+    pub level_set: bool,
+    /// Run the fire mode instead.
+    pub fire: bool,
+    /// Starting pattern of the reaction-diffusion mode, when running it
+    /// instead.
+    pub gray_scott: Option<GrayScottPattern>,
+    /// Surface tension coefficient of the level set and two-phase modes.
+    pub surface_tension: f32,
+    /// Initial window size in logical pixels.
+    pub window_size: (u32, u32),
+    pub present_mode: wgpu::PresentMode,
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// Starting values for the matching `SimParams` fields. Scenes can
+    /// still override them.
+    pub viscosity: f32,
+    pub diffusion: f32,
+    pub vorticity: f32,
+    /// Fade models, turned into the per-step `SimParams` decay factors.
+    /// Scenes can override these too.
+    pub dye_decay: Decay,
+    pub velocity_decay: Decay,
+    pub temperature_decay: Decay,
+    /// Starting `SimParams::heat_lift`.
+    pub buoyancy: f32,
+    /// Starting `SimParams::dye_density`.
+    pub dye_density: [f32; 3],
+    /// Starting `SimParams::gravity` and what it acts on.
+    pub gravity: [f32; 2],
+    pub gravity_mode: GravityMode,
+    /// Starting `SimParams::well`.
+    pub well: f32,
+    /// Starting `SimParams::wake`.
+    pub wake: f32,
+    /// Starting `SimParams::subgrid`.
+    pub subgrid: f32,
+    /// Starting `SimParams::dye_mass` and `heat_expansion`.
+    pub dye_mass: f32,
+    pub heat_expansion: f32,
+    /// Starting strength of the advected detail layer, 0 = off.
+    pub detail: f32,
+    /// Vortex particles, 0 = off, and the longest one lives in seconds.
+    pub vortex_particles: u32,
+    pub vortex_life: f32,
+    /// Shear rate the wall shear outline saturates at, if it starts on.
+    pub wall_shear: Option<f32>,
+    /// Seeds per side of the streamline overlay, if it starts on.
+    pub streamlines: Option<u32>,
+    /// Velocity arrows per side, if they start on.
+    pub arrows: Option<u32>,
+    /// HDR rendering's starting curve and exposure, if on.
+    pub tonemap: Option<crate::tonemap::TonemapSpec>,
+    /// Scene samples per window pixel on each side, if above 1.
+    pub supersample: Option<u32>,
+    /// Start with the window's colors dithered.
+    pub dither: bool,
+    /// Motion blur's decay per frame, if it starts on.
+    pub motion_blur: Option<f32>,
+    /// Start with the field inspector showing values.
+    pub inspect: bool,
+    /// Starting dye shading (Shift + V).
+    pub shading: crate::shading::Shading,
+    /// Starting direction of lit shading's light, degrees counterclockwise
+    /// from the right.
+    pub light: f32,
+    /// Starting colormap (Shift + Q), custom with a file.
+    pub colormap: crate::colormap::Colormap,
+    /// PNG the custom colormap is loaded from.
+    pub colormap_file: Option<String>,
+    /// Simulated seconds a long exposure (L) stays open.
+    pub exposure: f32,
+    /// Starting `SimParams` wind and gusting.
+    pub wind: [f32; 2],
+    pub wind_gust: f32,
+    pub wind_gust_period: f32,
+    /// Starting color of the mouse's dye, and the rainbow color's period.
+    pub dye_color: DyeColor,
+    pub dye_cycle: f32,
+    /// How cursor movement maps to injected momentum.
+    pub mouse_force: MouseForce,
+    /// Starting `SimParams::boundary`.
+    pub boundary: Boundary,
+    /// The stirring rod's settings, if it starts on (`--rod`).
+    pub rod: Option<Rod>,
+    /// Timestep limit in cells per step, `None` for a fixed `dt`.
+    pub cfl: Option<f32>,
+    /// Simulation steps per frame.
+    pub substeps: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pressure_solver: SolverKind::default(),
+            compare_solver: None,
+            advection: Advection::SemiLagrangian,
+            compare_advection: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            hot_reload: cfg!(debug_assertions),
+            scene: "default".to_string(),
+            demo: false,
+            snapshot: None,
+            seed_image: None,
+            seed_image_channel: image_seed::Channel::Luminance,
+            seed_image_velocity: 0.0,
+            field_format: field_export::Format::Exr,
+            checkpoint: None,
+            velocity_field: None,
+            freeze_velocity: false,
+            glass: false,
+            background: None,
+            image: None,
+            init_noise: None,
+            fresh: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            session: None,
+            seed: Seed::default(),
+            reference: None,
+            wall_leader: None,
+            wall_follower: None,
+            hash_frames: None,
+            hash_out: None,
+            stats_json: None,
+            screenshot_key: KeyCode::F12,
+            record: None,
+            record_fps: DEFAULT_RECORD_FPS,
+            gif_frames: DEFAULT_GIF_FRAMES,
+            gif_fps: DEFAULT_GIF_FPS,
+            gif_width: DEFAULT_GIF_WIDTH,
+            hud: false,
+            diagnostics: None,
+            serve: None,
+            thumbnails: None,
+            thumbnails_html: false,
+            export: None,
+            export_frames: 240,
+            export_every: 1,
+            export_size: None,
+            export_script: None,
+            connect: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            tune: TuneSpec::default(),
+            workarounds: None,
+            grid_size: 256,
+            volume: None,
+            stereo: Stereo::Off,
+            eye_separation: 0.06,
+            volume_steps: None,
+            flip: None,
+            sph: None,
+            shallow: false,
+            two_phase: None,
+            level_set: false,
+            fire: false,
+            gray_scott: None,
+            surface_tension: 0.0,
+            fluids: None,
+            window_size: (800, 800),
+            present_mode: wgpu::PresentMode::AutoVsync,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            viscosity: 0.0,
+            diffusion: 0.0,
+            vorticity: 12.0,
+            dye_decay: Decay::HalfLife(5.5),
+            velocity_decay: Decay::HalfLife(5.5),
+            temperature_decay: Decay::HalfLife(3.0),
+            buoyancy: 20.0,
+            dye_density: [0.0; 3],
+            gravity: [0.0, 0.0],
+            gravity_mode: GravityMode::Dye,
+            well: 0.0,
+            wake: 0.0,
+            subgrid: 0.0,
+            dye_mass: 0.0,
+            heat_expansion: 0.0,
+            detail: 0.0,
+            vortex_particles: 0,
+            vortex_life: crate::vortex::DEFAULT_LIFE,
+            wall_shear: None,
+            streamlines: None,
+            arrows: None,
+            tonemap: None,
+            supersample: None,
+            dither: true,
+            motion_blur: None,
+            inspect: false,
+            shading: crate::shading::Shading::Plain,
+            light: 135.0,
+            colormap: crate::colormap::Colormap::Flow,
+            colormap_file: None,
+            exposure: 4.0,
+            wind: [0.0, 0.0],
+            wind_gust: 0.0,
+            wind_gust_period: 4.0,
+            dye_color: DyeColor::White,
+            dye_cycle: 6.0,
+            mouse_force: MouseForce::default(),
+            boundary: Boundary::Walls,
+            rod: None,
+            cfl: Some(4.0),
+            substeps: 1,
+        }
+    }
+}
+
+impl Config {
+    /// Defaults overridden by the process's command-line flags. Prints usage
+    /// and exits on `--help` or a bad flag.
+    pub fn from_args() -> Self {
+        let args = Args::parse();
+        if let Err(e) = args.validate() {
+            Args::command().error(clap::error::ErrorKind::ArgumentConflict, e).exit();
+        }
+        let config = args.into_config();
+        #[cfg(not(target_arch = "wasm32"))]
+        let config = config.with_session(&std::env::args().skip(1).collect::<Vec<_>>());
+        config
+    }
+
+    /// Whether this run is the interactive 2D window, which saves its
+    /// session on exit. Hashing runs skip it too, so a leftover session
+    /// can't change their results.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn keeps_session(&self) -> bool {
+        let elsewhere = [self.wall_leader.is_some(), self.wall_follower.is_some(), self.serve.is_some(), self.thumbnails.is_some(), self.export.is_some(), self.connect.is_some(), self.volume.is_some(), self.flip.is_some(), self.sph.is_some(), self.shallow, self.two_phase.is_some(), self.level_set, self.fire, self.gray_scott.is_some()];
+        !elsewhere.contains(&true) && self.hash_frames.is_none()
+    }
+
+    /// This with the saved session loaded under what `args` set.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn with_session(mut self, args: &[String]) -> Self {
+        if self.fresh || !self.keeps_session() {
+            return self;
+        }
+        if let Some(mut session) = Session::load() {
+            session.forget_given(args);
+            session.apply(&mut self);
+            self.session = Some(session);
+        }
+        self
+    }
+}
+
+impl Args {
+    /// The checks between flags, which clap leaves to us.
+    fn validate(&self) -> Result<(), String> {
+        match (&self.wall_follower, &self.wall_tile) {
+            (Some(_), None) => return Err("--wall-follower needs --wall-tile".to_string()),
+            (None, Some(_)) => return Err("--wall-tile only applies with --wall-follower".to_string()),
+            _ => {}
+        }
+        if self.thumbnails_html && self.thumbnails.is_none() {
+            return Err("--thumbnails-html needs --thumbnails".to_string());
+        }
+        let export_details = [self.export_frames.is_some(), self.export_every.is_some(), self.export_size.is_some(), self.export_script.is_some()];
+        if export_details.contains(&true) && self.export.is_none() {
+            return Err("--export-frames, --export-every, --export-size and --export-script need --export".to_string());
+        }
+        if self.init_noise.is_some() && (self.demo || self.snapshot.is_some() || self.seed_image.is_some()) {
+            return Err("--init-noise replaces the scene's start, so it can't combine with --demo, --snapshot or --seed-image".to_string());
+        }
+        if self.seed_image.is_some() && (self.demo || self.snapshot.is_some()) {
+            return Err("--seed-image, --snapshot and --demo each give the starting state; pick one".to_string());
+        }
+        if (self.seed_image_channel.is_some() || self.seed_image_velocity.is_some()) && self.seed_image.is_none() {
+            return Err("--seed-image-channel and --seed-image-velocity need --seed-image".to_string());
+        }
+        if self.checkpoint.is_some() && (self.scene.is_some() || self.demo || self.snapshot.is_some() || self.init_noise.is_some() || self.seed_image.is_some()) {
+            return Err("--checkpoint brings its own scene and state, so it can't combine with --scene, --demo, --snapshot, --init-noise or --seed-image".to_string());
+        }
+        if self.freeze_velocity && self.velocity_field.is_none() {
+            return Err("--freeze-velocity needs --velocity-field".to_string());
+        }
+        if self.hash_out.is_some() && self.hash_frames.is_none() {
+            return Err("--hash-out needs --hash-frames".to_string());
+        }
+        // Each mode that replaces the 2D window or runs elsewhere, by its flag
+        let modes = [
+            ("--wall-leader", self.wall_leader.is_some()), ("--wall-follower", self.wall_follower.is_some()),
+            ("--serve", self.serve.is_some()), ("--thumbnails", self.thumbnails.is_some()), ("--export", self.export.is_some()),
+            ("--connect", self.connect.is_some()), ("--3d", self.volume.is_some()), ("--flip", self.flip.is_some()),
+            ("--sph", self.sph.is_some()), ("--shallow", self.shallow), ("--two-phase", self.two_phase.is_some()),
+            ("--level-set", self.level_set), ("--fire", self.fire), ("--gray-scott", self.gray_scott.is_some()),
+        ];
+        let all_but = |skip: &[&str]| -> Vec<(&str, bool)> { modes.iter().filter(|(flag, _)| !skip.contains(flag)).copied().collect() };
+        let sim_here = all_but(&["--wall-leader", "--serve"]);
+        let sim_window = all_but(&["--wall-leader"]);
+        let other_window = all_but(&["--wall-leader", "--wall-follower", "--connect"]);
+        // The simulations that aren't the 2D fluid
+        let other_sims = &other_window[3..];
+        only_without(self.stats_json.is_some(), &["--stats-json"], "the 2D simulation running here", &sim_here)?;
+        only_without(self.hud, &["--hud"], "the 2D simulation in its window", &sim_window)?;
+        only_without(self.checkpoint.is_some(), &["--checkpoint"], "the 2D simulation in its window", &sim_window)?;
+        only_without(self.velocity_field.is_some(), &["--velocity-field"], "the 2D simulation in its window", &sim_window)?;
+        only_without(self.diagnostics.is_some(), &["--diagnostics"], "the 2D simulation in its window", &sim_window)?;
+        only_without(self.image.is_some(), &["--image"], "the 2D simulation in its window", &sim_window)?;
+        only_without(self.tonemap.is_some(), &["--tonemap"], "the 2D window", &other_window)?;
+        only_without(self.supersample.is_some(), &["--supersample"], "the 2D window", &other_window)?;
+        only_without(self.shading.is_some(), &["--shading"], "the 2D fluid's dye", &other_window[2..])?;
+        only_without(self.motion_blur.is_some(), &["--motion-blur"], "the 2D window", &other_window)?;
+        only_without(self.screenshot_key.is_some(), &["--screenshot-key"], "the 2D window", &other_window)?;
+        only_without(self.record.is_some() || self.record_fps.is_some(), &["--record", "--record-fps"], "the 2D window", &other_window)?;
+        let gif = [self.gif_frames.is_some(), self.gif_fps.is_some(), self.gif_width.is_some()];
+        only_without(gif.contains(&true), &["--gif-frames", "--gif-fps", "--gif-width"], "the 2D window", &other_window)?;
+        only_without(self.field_format.is_some(), &["--field-format"], "the 2D window", &other_window)?;
+        only_without(self.inspect, &["--inspect"], "the 2D window", &other_window)?;
+        only_without(self.no_dither, &["--no-dither"], "the 2D window", &other_window)?;
+        only_without(self.glass || self.background.is_some(), &["--glass", "--background"], "the 2D window", &other_window)?;
+        // The export and thumbnails draw through the colormap too
+        only_without(self.colormap.is_some(), &["--colormap"], "the 2D fluid's dye", other_sims)?;
+        if self.stereo.is_some() && self.volume.is_none() {
+            return Err("--stereo only applies with --3d".to_string());
+        }
+        if self.volume_steps.is_some() && self.volume.is_none() {
+            return Err("--volume-steps only applies with --3d".to_string());
+        }
+        if self.fluids.is_some() && self.two_phase.is_none() {
+            return Err("--fluids only applies with --two-phase".to_string());
+        }
+        if self.surface_tension.is_some() && !self.level_set && self.two_phase.is_none() {
+            return Err("--surface-tension only applies with --level-set or --two-phase".to_string());
+        }
+        if modes.iter().filter(|&&(_, on)| on).count() > 1 {
+            return Err(format!("{} are mutually exclusive", all_of(modes.iter().map(|&(flag, _)| flag))));
+        }
+        Ok(())
+    }
+
+    /// The defaults with what was given put over them.
+    fn into_config(self) -> Config {
+        let d = Config::default();
+        // The demo state was recorded in the turbulence scene; keep its params
+        // unless another scene was asked for.
+        let scene = match self.scene {
+            None if self.demo => "turbulence".to_string(),
+            scene => scene.unwrap_or(d.scene),
+        };
+        let (colormap, colormap_file) = self.colormap.unwrap_or((d.colormap, None));
+        let (wind, gust, period) = self.wind.unwrap_or((d.wind, None, None));
+        Config {
+            scene,
+            grid_size: self.grid.unwrap_or(d.grid_size),
+            stereo: self.stereo.unwrap_or(d.stereo),
+            eye_separation: self.eye_separation.unwrap_or(d.eye_separation),
+            surface_tension: self.surface_tension.unwrap_or(d.surface_tension),
+            pressure_solver: self.solver.unwrap_or(d.pressure_solver),
+            advection: self.advection.unwrap_or(d.advection),
+            window_size: self.window.unwrap_or(d.window_size),
+            present_mode: self.vsync.unwrap_or(d.present_mode),
+            backends: self.backend.unwrap_or(d.backends),
+            power_preference: self.power.unwrap_or(d.power_preference),
+            viscosity: self.viscosity.unwrap_or(d.viscosity),
+            diffusion: self.diffusion.unwrap_or(d.diffusion),
+            dye_color: self.dye_color.unwrap_or(d.dye_color),
+            dye_cycle: self.dye_cycle.unwrap_or(d.dye_cycle),
+            mouse_force: self.mouse_force.unwrap_or(d.mouse_force),
+            substeps: self.substeps.unwrap_or(d.substeps),
+            cfl: self.cfl.unwrap_or(d.cfl),
+            dye_decay: self.dye_decay.unwrap_or(d.dye_decay),
+            velocity_decay: self.velocity_decay.unwrap_or(d.velocity_decay),
+            buoyancy: self.buoyancy.unwrap_or(d.buoyancy),
+            temperature_decay: self.temperature_decay.unwrap_or(d.temperature_decay),
+            dye_density: self.dye_density.unwrap_or(d.dye_density),
+            gravity: self.gravity.unwrap_or(d.gravity),
+            gravity_mode: self.gravity_mode.unwrap_or(d.gravity_mode),
+            well: self.well.unwrap_or(d.well),
+            wake: self.wake.unwrap_or(d.wake),
+            subgrid: self.subgrid.unwrap_or(d.subgrid),
+            dye_mass: self.dye_mass.unwrap_or(d.dye_mass),
+            heat_expansion: self.heat_expansion.unwrap_or(d.heat_expansion),
+            detail: self.detail.unwrap_or(d.detail),
+            vortex_particles: self.vortex_particles.unwrap_or(d.vortex_particles),
+            vortex_life: self.vortex_life.unwrap_or(d.vortex_life),
+            shading: self.shading.unwrap_or(d.shading),
+            light: self.light.map_or(d.light, |degrees| degrees.rem_euclid(360.0)),
+            exposure: self.exposure.unwrap_or(d.exposure),
+            boundary: self.boundary.unwrap_or(d.boundary),
+            vorticity: self.vorticity.unwrap_or(d.vorticity),
+            seed_image_channel: self.seed_image_channel.unwrap_or(d.seed_image_channel),
+            seed_image_velocity: self.seed_image_velocity.unwrap_or(d.seed_image_velocity),
+            field_format: self.field_format.unwrap_or(d.field_format),
+            seed: self.seed.unwrap_or(d.seed),
+            screenshot_key: self.screenshot_key.unwrap_or(d.screenshot_key),
+            record_fps: self.record_fps.unwrap_or(d.record_fps),
+            gif_frames: self.gif_frames.unwrap_or(d.gif_frames),
+            gif_fps: self.gif_fps.unwrap_or(d.gif_fps),
+            gif_width: self.gif_width.unwrap_or(d.gif_width),
+            export_frames: self.export_frames.unwrap_or(d.export_frames),
+            export_every: self.export_every.unwrap_or(d.export_every),
+            workarounds: self.workarounds.unwrap_or(d.workarounds),
+            #[cfg(not(target_arch = "wasm32"))]
+            tune: self.tune.unwrap_or(d.tune),
+            colormap,
+            colormap_file,
+            wind,
+            wind_gust: gust.unwrap_or(d.wind_gust),
+            wind_gust_period: period.unwrap_or(d.wind_gust_period),
+            compare_solver: self.compare,
+            compare_advection: self.compare_advection,
+            demo: self.demo,
+            snapshot: self.snapshot,
+            seed_image: self.seed_image,
+            checkpoint: self.checkpoint,
+            velocity_field: self.velocity_field,
+            freeze_velocity: self.freeze_velocity,
+            glass: self.glass,
+            background: self.background,
+            image: self.image,
+            init_noise: self.init_noise,
+            fresh: self.fresh,
+            reference: self.reference,
+            wall_leader: self.wall_leader,
+            wall_follower: self.wall_follower.zip(self.wall_tile),
+            hash_frames: self.hash_frames,
+            hash_out: self.hash_out,
+            stats_json: self.stats_json,
+            record: self.record,
+            hud: self.hud,
+            diagnostics: self.diagnostics,
+            serve: self.serve,
+            thumbnails: self.thumbnails,
+            thumbnails_html: self.thumbnails_html,
+            export: self.export,
+            export_size: self.export_size,
+            export_script: self.export_script,
+            connect: self.connect,
+            volume: self.volume,
+            volume_steps: self.volume_steps,
+            flip: self.flip,
+            sph: self.sph,
+            shallow: self.shallow,
+            two_phase: self.two_phase,
+            fluids: self.fluids,
+            level_set: self.level_set,
+            fire: self.fire,
+            gray_scott: self.gray_scott,
+            wall_shear: self.wall_shear,
+            streamlines: self.streamlines,
+            arrows: self.arrows,
+            tonemap: self.tonemap,
+            supersample: self.supersample.filter(|&factor| factor > 1),
+            dither: !self.no_dither,
+            motion_blur: self.motion_blur,
+            inspect: self.inspect,
+            rod: self.rod,
+            #[cfg(not(target_arch = "wasm32"))]
+            hot_reload: d.hot_reload,
+            #[cfg(not(target_arch = "wasm32"))]
+            session: None,
+        }
+    }
+}
+
+/// A flag's value that can also turn its setting off (`--cfl off`,
+/// `--workarounds auto`). Named so clap doesn't read `Option<Option<_>>`
+/// as a flag whose value is optional.
+type OrNone<T> = Option<T>;
+
+/// `--wind`'s direction, and its gust and their period if given.
+type Wind = ([f32; 2], Option<f32>, Option<f32>);
+
+/// A `value_parser` for numbers that pass `ok`, `must` saying what they
+/// have to be otherwise.
+fn checked<T: std::str::FromStr + 'static>(ok: fn(&T) -> bool, must: impl std::fmt::Display + Clone + Send + Sync + 'static) -> impl Fn(&str) -> Result<T, String> + Clone + Send + Sync + 'static {
+    move |s| {
+        let value = parse_num(s)?;
+        if ok(&value) {
+            Ok(value)
+        } else {
+            Err(format!("must be {}", must))
+        }
+    }
+}
+
+/// `--window` and `--export-size`: `WxH`, at least 1x1.
+fn size(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s.split_once('x').ok_or("expected WxH")?;
+    let size: (u32, u32) = (parse_num(w)?, parse_num(h)?);
+    if size.0 == 0 || size.1 == 0 {
+        return Err("must be at least 1x1".to_string());
+    }
+    Ok(size)
+}
+
+/// `--gravity`: `X,Y`.
+fn xy(s: &str) -> Result<[f32; 2], String> {
+    let (x, y) = s.split_once(',').ok_or("expected X,Y")?;
+    Ok([parse_num(x)?, parse_num(y)?])
+}
+
+/// `--dye-density`: `R,G,B`.
+fn rgb(s: &str) -> Result<[f32; 3], String> {
+    let parts: Vec<&str> = s.split(',').collect();
+    let [r, g, b] = parts[..] else {
+        return Err("expected R,G,B".to_string());
+    };
+    Ok([parse_num(r)?, parse_num(g)?, parse_num(b)?])
+}
+
+/// `--cfl`: a positive limit in cells, or `off`.
+fn cfl(s: &str) -> Result<OrNone<f32>, String> {
+    match s {
+        "off" => Ok(None),
+        limit => match parse_num(limit)? {
+            cells if cells > 0.0 => Ok(Some(cells)),
+            _ => Err("must be positive or off".to_string()),
+        },
+    }
+}
+
+/// `--colormap`: a named colormap, or a gradient PNG to make the custom one
+/// from.
+fn colormap(s: &str) -> Result<(crate::colormap::Colormap, Option<String>), String> {
+    if s.to_ascii_lowercase().ends_with(".png") {
+        Ok((crate::colormap::Colormap::Custom, Some(s.to_string())))
+    } else {
+        Ok((s.parse()?, None))
+    }
+}
+
+/// `--wind`: `X,Y[:GUST[:PERIOD]]`.
+fn wind(s: &str) -> Result<Wind, String> {
+    let mut parts = s.split(':');
+    let dir = xy(parts.next().unwrap_or_default()).map_err(|_| "expected X,Y[:GUST[:PERIOD]]")?;
+    let gust = parts.next().map(parse_num).transpose()?;
+    let period = parts.next().map(parse_num::<f32>).transpose()?;
+    if period.is_some_and(|p| p <= 0.0) {
+        return Err("the gust period must be positive".to_string());
+    }
+    if parts.next().is_some() {
+        return Err("expected X,Y[:GUST[:PERIOD]]".to_string());
+    }
+    Ok((dir, gust, period))
+}
+
+/// `--workarounds`: `auto` to pick them from the adapter, or a list.
+fn workarounds(s: &str) -> Result<OrNone<Workarounds>, String> {
+    match s {
+        "auto" => Ok(None),
+        list => Ok(Some(list.parse()?)),
+    }
+}
+
+/// Default `--record-fps`, here rather than in the native-only recorder.
+const DEFAULT_RECORD_FPS: u32 = 60;
+/// Defaults of `--gif-frames`, `--gif-fps` and `--gif-width`: four seconds,
+/// small enough to paste into an issue.
+const DEFAULT_GIF_FRAMES: u32 = 100;
+const DEFAULT_GIF_FPS: u32 = 25;
+const DEFAULT_GIF_WIDTH: u32 = 480;
+
+/// What `--vsync`, `--backend` and `--power` take.
+const VSYNC_MODES: [(&str, wgpu::PresentMode); 5] = [
+    ("on", wgpu::PresentMode::AutoVsync), ("off", wgpu::PresentMode::AutoNoVsync), ("fifo", wgpu::PresentMode::Fifo),
+    ("mailbox", wgpu::PresentMode::Mailbox), ("immediate", wgpu::PresentMode::Immediate),
+];
+const BACKENDS: [(&str, wgpu::Backends); 7] = [
+    ("all", wgpu::Backends::all()), ("primary", wgpu::Backends::PRIMARY), ("vulkan", wgpu::Backends::VULKAN),
+    ("metal", wgpu::Backends::METAL), ("dx12", wgpu::Backends::DX12), ("gl", wgpu::Backends::GL),
+    ("webgpu", wgpu::Backends::BROWSER_WEBGPU),
+];
+const POWER_PREFERENCES: [(&str, wgpu::PowerPreference); 3] = [
+    ("high", wgpu::PowerPreference::HighPerformance), ("low", wgpu::PowerPreference::LowPower), ("none", wgpu::PowerPreference::None),
+];
+
+/// Keys `--screenshot-key` takes, by the name it takes them by.
+const SCREENSHOT_KEYS: [(&str, KeyCode); 20] = [
+    ("F1", KeyCode::F1), ("F2", KeyCode::F2), ("F3", KeyCode::F3), ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5), ("F6", KeyCode::F6), ("F7", KeyCode::F7), ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9), ("F10", KeyCode::F10), ("F11", KeyCode::F11), ("F12", KeyCode::F12),
+    ("PrintScreen", KeyCode::PrintScreen), ("Pause", KeyCode::Pause), ("ScrollLock", KeyCode::ScrollLock),
+    ("Insert", KeyCode::Insert), ("Home", KeyCode::Home), ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp), ("PageDown", KeyCode::PageDown),
+];
+
+/// How `--screenshot-key` names `code`.
+pub fn screenshot_key_name(code: KeyCode) -> &'static str {
+    SCREENSHOT_KEYS.iter().find(|&&(_, c)| c == code).map_or("?", |&(n, _)| n)
+}
+
+/// `value` as whichever of `choices` it names, ignoring case, for a flag or
+/// setting called `what`. The error lists the names, so each list of
+/// choices is only written down once.
+pub fn parse_choice<T: Copy>(what: &str, value: &str, choices: &[(&str, T)]) -> Result<T, String> {
+    choices
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|&(_, choice)| choice)
+        .ok_or_else(|| format!("unknown {} `{}` (expected {})", what, value, one_of(choices.iter().map(|&(name, _)| name))))
+}
+
+/// `names` as a list for an error message: "a, b or c".
+pub fn one_of<'a>(names: impl IntoIterator<Item = &'a str>) -> String {
+    let names: Vec<&str> = names.into_iter().collect();
+    match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// `names` as a list for an error message: "a, b and c".
+fn all_of<'a>(names: impl IntoIterator<Item = &'a str>) -> String {
+    let names: Vec<&str> = names.into_iter().collect();
+    match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// An error if `given`, one or more of `flags`, meets any of `modes` that's
+/// on: the flags only work in `place`, and the message names every mode
+/// they don't work with.
+fn only_without(given: bool, flags: &[&str], place: &str, modes: &[(&str, bool)]) -> Result<(), String> {
+    if !given || !modes.iter().any(|&(_, on)| on) {
+        return Ok(());
+    }
+    let verb = if flags.len() == 1 { "applies" } else { "apply" };
+    Err(format!("{} only {} to {} (not with {})", all_of(flags.iter().copied()), verb, place, one_of(modes.iter().map(|&(flag, _)| flag))))
+}
+
+fn parse_num<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.trim().parse().map_err(|_| format!("can't parse `{}`", s))
+}
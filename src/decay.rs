@@ -0,0 +1,59 @@
+//! How dye and velocity fade over time. Models are given in seconds of
+//! simulated time and turned into a per-step factor from `dt`, so the fade
+//! rate doesn't depend on the timestep or frame rate.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decay {
+    /// Values are kept as they are.
+    None,
+    /// Exponential fade: values halve every `half_life` seconds.
+    HalfLife(f32),
+    /// Exponential fade, and values whose magnitude drops below `threshold`
+    /// are cleared, so faint trails disappear instead of lingering forever.
+    Threshold { half_life: f32, threshold: f32 },
+}
+
+impl Decay {
+    /// Multiplier applied once per simulation step of `dt` seconds.
+    pub fn factor(self, dt: f32) -> f32 {
+        match self {
+            Decay::None => 1.0,
+            Decay::HalfLife(half_life) | Decay::Threshold { half_life, .. } => 0.5f32.powf(dt / half_life),
+        }
+    }
+
+    /// Magnitude below which values are cleared; 0 disables clamping.
+    pub fn threshold(self) -> f32 {
+        match self {
+            Decay::Threshold { threshold, .. } => threshold,
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::str::FromStr for Decay {
+    type Err = String;
+
+    /// `none`, `half-life:<seconds>` or `threshold:<seconds>:<min>`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let num = |v: &str, what: &str| -> Result<f32, String> {
+            v.parse::<f32>().map_err(|_| format!("bad {} `{}` in decay `{}`", what, v, s))
+        };
+        let decay = match parts.as_slice() {
+            ["none"] => Decay::None,
+            ["half-life", h] => Decay::HalfLife(num(h, "half-life")?),
+            ["threshold", h, t] => Decay::Threshold { half_life: num(h, "half-life")?, threshold: num(t, "threshold")? },
+            _ => return Err(format!("bad decay `{}` (expected none, half-life:<secs> or threshold:<secs>:<min>)", s)),
+        };
+        match decay {
+            Decay::HalfLife(h) | Decay::Threshold { half_life: h, .. } if h.is_nan() || h <= 0.0 => {
+                Err(format!("decay half-life must be positive, got {}", h))
+            }
+            Decay::Threshold { threshold, .. } if threshold.is_nan() || threshold < 0.0 => {
+                Err(format!("decay threshold must not be negative, got {}", threshold))
+            }
+            _ => Ok(decay),
+        }
+    }
+}
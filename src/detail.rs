@@ -0,0 +1,162 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::create_storage_tex;
+
+/// Largest detail texture side. A 4K window gets slightly coarser noise
+/// rather than two 128 MB textures.
+const MAX_SIZE: u32 = 2048;
+/// Strength J turns the detail layer on to when `--detail` left it off.
+pub const DEFAULT_STRENGTH: f32 = 0.5;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DetailParams {
+    grid_size: u32,
+    size: u32,
+    dt: f32,
+    time: f32,
+    cell_size: [f32; 2],
+    _pad: [f32; 2],
+}
+
+/// Noise at screen resolution, advected by the velocity field each frame and
+/// multiplied into the dye by the renderer. It gives the smoke texture far
+/// finer than the grid for the cost of one advection, no extra solver work.
+pub struct Detail {
+    params: DetailParams,
+    param_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    advect: wgpu::ComputePipeline,
+    copy: wgpu::ComputePipeline,
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    _tmp_view: wgpu::TextureView,
+}
+
+impl Detail {
+    /// Detail texture about the size of the window's longer side, seeded
+    /// with fresh noise.
+    pub fn new(
+        device: &wgpu::Device, queue: &wgpu::Queue, velocity_view: &wgpu::TextureView,
+        grid_size: u32, window: winit::dpi::PhysicalSize<u32>,
+    ) -> Self {
+        let size = window.width.max(window.height).clamp(grid_size, MAX_SIZE.max(grid_size));
+        let params = DetailParams { grid_size, size, dt: 0.0, time: 0.0, cell_size: [1.0; 2], _pad: [0.0; 2] };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("detail_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let (texture, view) = create_storage_tex(device, size);
+        let (_tmp, tmp_view) = create_storage_tex(device, size);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("detail_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../detail.wgsl").into()),
+        });
+
+        let storage_tex = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("detail_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("detail_bg"), layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&tmp_view) },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&layout),
+                module: &shader, entry_point: entry,
+            })
+        };
+        let seed = make_compute("seed_detail");
+
+        // Seed once up front so the layers are never blank, even where
+        // nothing ever steps them (a remote client or wall follower)
+        let groups = size.div_ceil(8);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("detail_seed"), timestamp_writes: None,
+            });
+            c.set_bind_group(0, &bind_group, &[]);
+            c.set_pipeline(&seed);
+            c.dispatch_workgroups(groups, groups, 1);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        Self {
+            params,
+            param_buffer,
+            bind_group,
+            advect: make_compute("advect_detail"),
+            copy: make_compute("copy_detail"),
+            _texture: texture,
+            view,
+            _tmp_view: tmp_view,
+        }
+    }
+
+    /// View of the detail layers, for sampling in the render pass.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Step length, simulated time after the step (`SimParams::time`, which
+    /// times the reseeds) and physical cell size for the next `dispatch`.
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: f32, time: f32, cell_size: [f32; 2]) {
+        self.params.dt = dt;
+        self.params.time = time;
+        self.params.cell_size = cell_size;
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// Record the advection of both layers, reseeding whichever is due.
+    /// Expects the velocity field to be up to date for this frame.
+    pub fn dispatch<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        let groups = self.params.size.div_ceil(8);
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.advect);
+        c.dispatch_workgroups(groups, groups, 1);
+        c.set_pipeline(&self.copy);
+        c.dispatch_workgroups(groups, groups, 1);
+    }
+}
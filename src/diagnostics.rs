@@ -0,0 +1,321 @@
+//! Flow diagnostics (E, `--diagnostics <PATH>`): total kinetic energy,
+//! enstrophy and dye mass, summed on the GPU after every step
+//! (`diagnostics.wgsl`). They're read back without waiting, so a sample
+//! arrives a frame or two after its step and a slow GPU just skips some.
+//! E plots the last few seconds in the window's corner; `--diagnostics`
+//! appends every sample to a CSV file, to compare how much energy each
+//! solver and advection scheme dissipates.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::readback::Readback;
+
+/// Samples the plot keeps. Must match the `Plot` array in `diagnostics.wgsl`.
+const HISTORY: usize = 256;
+/// Cells per `partial_sums` workgroup.
+const TILE: u32 = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DiagParams {
+    grid_size: u32,
+    tiles: u32,
+    cell_size: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Plot {
+    samples: [[f32; 4]; HISTORY],
+    count: u32,
+    _pad: [u32; 3],
+}
+
+/// Grid totals after one step.
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    /// Simulated seconds.
+    time: f32,
+    /// ½|v|² summed over the grid, in cells²/s².
+    energy: f32,
+    /// ½ω² summed over the grid, in 1/s².
+    enstrophy: f32,
+    /// Red, green and blue dye summed over the grid.
+    dye_mass: f32,
+}
+
+pub struct Diagnostics {
+    /// The plot is drawn (E).
+    pub showing: bool,
+    params: DiagParams,
+    param_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    partial_sums: wgpu::ComputePipeline,
+    total: wgpu::ComputePipeline,
+    result: wgpu::Buffer,
+    _partials: wgpu::Buffer,
+    /// The totals, tagged with the simulated time they were measured at.
+    readback: Readback<f32>,
+    history: VecDeque<Sample>,
+    log: Option<File>,
+    plot_buffer: wgpu::Buffer,
+    plot_bg: wgpu::BindGroup,
+    plot_pipeline: wgpu::RenderPipeline,
+}
+
+impl Diagnostics {
+    /// Measures only while the plot is shown or `log` is given, whose file
+    /// is created (or truncated) here.
+    pub fn new(
+        device: &wgpu::Device, velocity_view: &wgpu::TextureView, density_view: &wgpu::TextureView,
+        grid_size: u32, format: wgpu::TextureFormat, log: Option<&str>,
+    ) -> Self {
+        let log = log.and_then(|path| match File::create(path) {
+            Ok(mut file) => {
+                let header = format!("# flow diagnostics, {}² grid\ntime,energy,enstrophy,dye_mass\n", grid_size);
+                match file.write_all(header.as_bytes()) {
+                    Ok(()) => {
+                        eprintln!("Logging energy, enstrophy and dye mass to {}", path);
+                        Some(file)
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to write {}: {}", path, e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path, e);
+                None
+            }
+        });
+
+        let per_row = grid_size.div_ceil(TILE);
+        let params = DiagParams { grid_size, tiles: per_row * per_row, cell_size: [1.0; 2] };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("diagnostics_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let partials = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("diagnostics_partials"),
+            size: params.tiles as u64 * 16,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let result = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("diagnostics_result"),
+            size: 16,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("diagnostics_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../diagnostics.wgsl").into()),
+        });
+
+        // ---- Compute ----
+        let sampled_tex = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+        };
+        let storage_buf = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        };
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("diagnostics_compute_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_buf, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_buf, count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("diagnostics_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(density_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: partials.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: result.as_entire_binding() },
+            ],
+        });
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl), module: &shader, entry_point: entry,
+            })
+        };
+
+        // ---- Plot ----
+        let plot_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("diagnostics_plot"),
+            size: std::mem::size_of::<Plot>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let plot_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("diagnostics_plot_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 5, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false, min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let plot_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("diagnostics_plot_bg"), layout: &plot_bgl,
+            entries: &[wgpu::BindGroupEntry { binding: 5, resource: plot_buffer.as_entire_binding() }],
+        });
+        let plot_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&plot_bgl], push_constant_ranges: &[],
+        });
+        let plot_pipeline = crate::create_render_pipeline(device, &plot_pl, &shader, format, "fs_plot");
+
+        Self {
+            showing: false,
+            params, param_buffer, bind_group,
+            partial_sums: make_compute("partial_sums"),
+            total: make_compute("total"),
+            result, _partials: partials,
+            readback: Readback::new(device, "diagnostics_staging", 16),
+            history: VecDeque::with_capacity(HISTORY),
+            log,
+            plot_buffer, plot_bg, plot_pipeline,
+        }
+    }
+
+    /// Show or hide the plot.
+    pub fn toggle(&mut self, queue: &wgpu::Queue) {
+        self.showing = !self.showing;
+        if self.showing {
+            queue.write_buffer(&self.plot_buffer, 0, bytemuck::bytes_of(&self.plot()));
+            eprintln!("Diagnostics plot: on (orange energy, cyan enstrophy, white dye mass, each scaled to its peak)");
+        } else {
+            eprintln!("Diagnostics plot: off");
+        }
+    }
+
+    /// Measure the fields the step just recorded leaves behind, at
+    /// simulated `time`. Call `submitted` once the encoder is submitted.
+    pub fn record(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, cell_size: [f32; 2], time: f32) {
+        if !(self.showing || self.log.is_some()) || !self.readback.is_free() {
+            // Off, or still mapped for the last readback
+            return;
+        }
+        if self.params.cell_size != cell_size {
+            self.params.cell_size = cell_size;
+            queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+        }
+        {
+            let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("diagnostics"), timestamp_writes: None,
+            });
+            c.set_bind_group(0, &self.bind_group, &[]);
+            c.set_pipeline(&self.partial_sums);
+            let groups = self.params.grid_size.div_ceil(TILE);
+            c.dispatch_workgroups(groups, groups, 1);
+            c.set_pipeline(&self.total);
+            c.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.result, 0, self.readback.buffer(), 0, 16);
+        self.readback.recorded(time);
+    }
+
+    pub fn submitted(&mut self) {
+        self.readback.submitted();
+    }
+
+    /// Take in the last measurement if the GPU is done with it, without
+    /// waiting: log it and add it to the plot.
+    pub fn collect(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let sample = self.readback.collect(device, false, |time, bytes| {
+            let [energy, enstrophy, dye_mass, _]: [f32; 4] = bytemuck::pod_read_unaligned(bytes);
+            Sample { time, energy, enstrophy, dye_mass }
+        });
+        if let Some(sample) = sample {
+            self.push(sample);
+            if self.showing {
+                queue.write_buffer(&self.plot_buffer, 0, bytemuck::bytes_of(&self.plot()));
+            }
+        }
+    }
+
+    fn push(&mut self, sample: Sample) {
+        // A reset or new scene starts the clock over, and the plot with it
+        if self.history.back().is_some_and(|last| sample.time < last.time) {
+            self.history.clear();
+        }
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(sample);
+        if let Some(file) = &mut self.log {
+            let line = format!("{:.4},{},{},{}\n", sample.time, sample.energy, sample.enstrophy, sample.dye_mass);
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                eprintln!("Stopped logging diagnostics: {}", e);
+                self.log = None;
+            }
+        }
+    }
+
+    /// The history with each series scaled by its peak, so all three fill
+    /// the plot's height whatever their units.
+    fn plot(&self) -> Plot {
+        let peak = |value: fn(&Sample) -> f32| {
+            let max = self.history.iter().map(value).fold(0.0, f32::max);
+            if max > 0.0 { 1.0 / max } else { 0.0 }
+        };
+        let scale = [peak(|s| s.energy), peak(|s| s.enstrophy), peak(|s| s.dye_mass)];
+        let mut plot = Plot { samples: [[0.0; 4]; HISTORY], count: self.history.len() as u32, _pad: [0; 3] };
+        for (slot, s) in plot.samples.iter_mut().zip(&self.history) {
+            *slot = [s.energy * scale[0], s.enstrophy * scale[1], s.dye_mass * scale[2], 0.0];
+        }
+        plot
+    }
+
+    /// Draw the plot into the bottom left of a `width` × `height` target,
+    /// over whatever the pass drew before. Leaves the pass's viewport there.
+    pub fn draw<'a>(&'a self, r: &mut wgpu::RenderPass<'a>, width: u32, height: u32) {
+        if !self.showing || self.history.len() < 2 {
+            return;
+        }
+        let w = (width as f32 * 0.4).min(480.0);
+        let h = w * 0.4;
+        let margin = 12.0;
+        if w + 2.0 * margin > width as f32 || h + 2.0 * margin > height as f32 {
+            return;
+        }
+        r.set_viewport(margin, height as f32 - h - margin, w, h, 0.0, 1.0);
+        r.set_pipeline(&self.plot_pipeline);
+        r.set_bind_group(0, &self.plot_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,228 @@
+//! Image distortion (`--image FILE.png`): the window shows a picture instead
+//! of the dye, and the flow smears it. `distort.wgsl` advects, per cell,
+//! where in the picture that cell's fluid came from, and draws the picture
+//! looked up there. R and scene switches put the picture back together.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::create_storage_tex;
+use crate::png::Image;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DistortParams {
+    grid_size: u32,
+    dt: f32,
+    cell_size: [f32; 2],
+    viewport: [f32; 4],
+}
+
+pub struct Distort {
+    params: DistortParams,
+    param_buffer: wgpu::Buffer,
+    compute_bg: wgpu::BindGroup,
+    render_bg: wgpu::BindGroup,
+    advect: wgpu::ComputePipeline,
+    copy: wgpu::ComputePipeline,
+    render: wgpu::RenderPipeline,
+    offsets: wgpu::Texture,
+    _tmp: wgpu::Texture,
+    _picture: wgpu::Texture,
+}
+
+impl Distort {
+    /// `image` stretched over the whole domain, undistorted; `viewport` is
+    /// the part the window shows, as in `ViewParams`. Fails if the image is
+    /// too big for a texture.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device, queue: &wgpu::Queue, velocity_view: &wgpu::TextureView, obstacle_view: &wgpu::TextureView,
+        grid_size: u32, format: wgpu::TextureFormat, viewport: [f32; 4], image: &Image,
+    ) -> Result<Self, String> {
+        let limit = device.limits().max_texture_dimension_2d;
+        if image.width > limit || image.height > limit {
+            return Err(format!("{}×{} is larger than the GPU's {} pixel texture limit", image.width, image.height, limit));
+        }
+        let params = DistortParams { grid_size, dt: 0.0, cell_size: [1.0; 2], viewport };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("distort_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Zeroed at creation: every cell shows its own part of the picture
+        let (offsets, offsets_view) = create_storage_tex(device, grid_size);
+        let (tmp, tmp_view) = create_storage_tex(device, grid_size);
+
+        let size = wgpu::Extent3d { width: image.width, height: image.height, depth_or_array_layers: 1 };
+        let picture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("distort_picture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            picture.as_image_copy(),
+            &image.rgba,
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(image.width * 4), rows_per_image: None },
+            size,
+        );
+        let picture_view = picture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("distort_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../distort.wgsl").into()),
+        });
+
+        let uniform = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0, visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false, min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture = |binding, visibility, filterable| wgpu::BindGroupLayoutEntry {
+            binding, visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+                view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+            },
+            count: None,
+        };
+        let storage_tex = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("distort_compute_bgl"),
+            entries: &[
+                uniform(wgpu::ShaderStages::COMPUTE),
+                texture(1, wgpu::ShaderStages::COMPUTE, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+            ],
+        });
+        let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("distort_render_bgl"),
+            entries: &[
+                uniform(wgpu::ShaderStages::VERTEX_FRAGMENT),
+                texture(4, wgpu::ShaderStages::FRAGMENT, true),
+                texture(5, wgpu::ShaderStages::FRAGMENT, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture(7, wgpu::ShaderStages::FRAGMENT, true),
+            ],
+        });
+        let compute_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("distort_compute_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&offsets_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&tmp_view) },
+            ],
+        });
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("distort_render_bg"), layout: &render_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&offsets_view) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&picture_view) },
+                wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::Sampler(&sampler) },
+                wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(obstacle_view) },
+            ],
+        });
+
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl),
+                module: &shader, entry_point: entry,
+            })
+        };
+        let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+        });
+        let render = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fs_distort"), layout: Some(&render_pl),
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_distort", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader, entry_point: "fs_distort",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format, blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Ok(Self {
+            params,
+            param_buffer,
+            compute_bg,
+            render_bg,
+            advect: make_compute("advect_offsets"),
+            copy: make_compute("copy_offsets"),
+            render,
+            offsets,
+            _tmp: tmp,
+            _picture: picture,
+        })
+    }
+
+    /// Step length and physical cell size for the next `dispatch`.
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: f32, cell_size: [f32; 2]) {
+        self.params.dt = dt;
+        self.params.cell_size = cell_size;
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// Put the picture back together (R, scene switches).
+    pub fn reset(&self, queue: &wgpu::Queue) {
+        let n = self.params.grid_size;
+        crate::upload_field(queue, &self.offsets, n, &vec![[0.0; 4]; (n * n) as usize]);
+    }
+
+    /// Record one step of the smear. Expects the velocity field to be up
+    /// to date for this frame.
+    pub fn dispatch<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        let groups = self.params.grid_size.div_ceil(8);
+        c.set_bind_group(0, &self.compute_bg, &[]);
+        c.set_pipeline(&self.advect);
+        c.dispatch_workgroups(groups, groups, 1);
+        c.set_pipeline(&self.copy);
+        c.dispatch_workgroups(groups, groups, 1);
+    }
+
+    /// Draw the smeared picture over the whole target, in place of the dye.
+    pub fn draw<'a>(&'a self, r: &mut wgpu::RenderPass<'a>) {
+        r.set_pipeline(&self.render);
+        r.set_bind_group(0, &self.render_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,135 @@
+//! Color of the dye the mouse injects (X cycles it). The fluid carries red,
+//! green and blue dye as three independent channels, so strokes of
+//! different colors mix where they meet. The automatic modes pick a new hue
+//! every frame, so the color is laid down along the stroke and a long
+//! stroke leaves a rainbow trail.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DyeColor {
+    White,
+    Red,
+    Yellow,
+    Green,
+    Cyan,
+    Blue,
+    Magenta,
+    /// Hue cycles with the simulation time.
+    Rainbow,
+    /// Hue follows the stroke's direction, with the same hue wheel the
+    /// renderer tints the flow by.
+    Direction,
+}
+
+impl DyeColor {
+    const ALL: [DyeColor; 9] = [
+        DyeColor::White, DyeColor::Red, DyeColor::Yellow, DyeColor::Green,
+        DyeColor::Cyan, DyeColor::Blue, DyeColor::Magenta, DyeColor::Rainbow, DyeColor::Direction,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DyeColor::White => "white",
+            DyeColor::Red => "red",
+            DyeColor::Yellow => "yellow",
+            DyeColor::Green => "green",
+            DyeColor::Cyan => "cyan",
+            DyeColor::Blue => "blue",
+            DyeColor::Magenta => "magenta",
+            DyeColor::Rainbow => "rainbow",
+            DyeColor::Direction => "direction",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() as usize + 1) % Self::ALL.len()]
+    }
+
+    /// Position in the X cycle, for sending over the wire.
+    pub fn index(self) -> u32 {
+        Self::ALL.iter().position(|c| *c == self).unwrap_or(0) as u32
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_index(i: u32) -> Option<Self> {
+        Self::ALL.get(i as usize).copied()
+    }
+
+    /// The fixed colors; `None` for the automatic modes.
+    fn fixed_rgb(self) -> Option<[f32; 3]> {
+        match self {
+            DyeColor::White => Some([1.0, 1.0, 1.0]),
+            DyeColor::Red => Some([1.0, 0.0, 0.0]),
+            DyeColor::Yellow => Some([1.0, 1.0, 0.0]),
+            DyeColor::Green => Some([0.0, 1.0, 0.0]),
+            DyeColor::Cyan => Some([0.0, 1.0, 1.0]),
+            DyeColor::Blue => Some([0.0, 0.0, 1.0]),
+            DyeColor::Magenta => Some([1.0, 0.0, 1.0]),
+            DyeColor::Rainbow | DyeColor::Direction => None,
+        }
+    }
+}
+
+/// The mouse's dye: a `DyeColor` plus what the automatic modes need.
+#[derive(Clone, Copy, Debug)]
+pub struct DyeBrush {
+    pub color: DyeColor,
+    /// Seconds for `Rainbow` to go once around the hue circle.
+    pub cycle: f32,
+    /// `Direction`'s hue, kept while the mouse rests so a pause mid-stroke
+    /// doesn't change the color.
+    hue: f32,
+}
+
+impl DyeBrush {
+    pub fn new(color: DyeColor, cycle: f32) -> Self {
+        Self { color, cycle, hue: 0.0 }
+    }
+
+    /// Dye per unit of `SimParams::add_strength` this frame, at `time`
+    /// seconds into the scene with the mouse moving by `delta` (physical
+    /// units, y down). The brightest channel is always 1, so every color
+    /// injects as much dye as white does.
+    pub fn rgb(&mut self, time: f32, delta: [f32; 2]) -> [f32; 3] {
+        match self.color {
+            DyeColor::Rainbow => hue_rgb(time / self.cycle),
+            DyeColor::Direction => {
+                if delta != [0.0, 0.0] {
+                    self.hue = delta[1].atan2(delta[0]) / std::f32::consts::TAU + 0.5;
+                }
+                hue_rgb(self.hue)
+            }
+            fixed => fixed.fixed_rgb().unwrap_or([1.0; 3]),
+        }
+    }
+
+    /// What the log shows for the current color.
+    pub fn describe(&self) -> String {
+        match self.color {
+            DyeColor::Rainbow => format!("rainbow, every {} s", self.cycle),
+            DyeColor::Direction => "following the stroke direction".to_string(),
+            fixed => format!("{:?}", fixed).to_lowercase(),
+        }
+    }
+}
+
+impl std::str::FromStr for DyeColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("dye color", s, &Self::ALL.map(|c| (c.name(), c)))
+    }
+}
+
+/// Fully saturated color at `hue` turns around the color wheel (0 = red).
+fn hue_rgb(hue: f32) -> [f32; 3] {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
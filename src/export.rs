@@ -0,0 +1,255 @@
+//! Image-sequence export (`--export DIR`): runs the `--scene` (or the
+//! `--snapshot` / `--demo` state) headlessly and writes every
+//! `--export-every`th step as `DIR/frame-00000.png`, `frame-00001.png`, …,
+//! at `--export-size`, then exits. Nothing depends on the wall clock or on
+//! readbacks: every step takes the scene's dt, with no CFL limiting, so the
+//! same flags give the same frames each run on a given GPU, however long
+//! each one takes to draw and write.
+//!
+//! The frames are drawn as the window would draw them, particle trails and
+//! detail included, without the overlays. An `--export-script` (see
+//! `export_script`) moves the camera, crossfades params and switches
+//! emitters on and off as the frames go by.
+
+use std::sync::{Arc, Mutex};
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::config::Config;
+use crate::detail::Detail;
+use crate::export_script::Script;
+use crate::gpu_error::{self, ErrorLog};
+use crate::particles::Particles;
+use crate::simulation::{self, DiffField, FieldView, FluidPipelines, Simulation};
+use crate::scene::ParamOverrides;
+use crate::{create_storage_tex, GpuEmitter, RenderBindings, SimParams, ViewParams, MAX_EMITTERS};
+
+/// The swapchain's usual format, so `fs_draw`'s output is encoded as on
+/// screen and the bytes can go straight into the PNG.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+pub async fn run(config: Config, dir: &str) {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        eprintln!("Can't create {}: {}", dir, e);
+        std::process::exit(1);
+    }
+    let script = match &config.export_script {
+        Some(path) => Script::load(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load export script {}", e);
+            std::process::exit(1);
+        }),
+        None => Script::default(),
+    };
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: config.backends,
+        ..Default::default()
+    });
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: config.power_preference,
+        force_fallback_adapter: false,
+        compatible_surface: None,
+    })
+    .await
+    .expect("No suitable GPU adapter found");
+
+    eprintln!("GPU: {}", adapter.get_info().name);
+    eprintln!("Backend: {:?}", adapter.get_info().backend);
+
+    let (device, queue) = crate::request_device(&adapter).await;
+    let (width, height) = config.export_size.unwrap_or(config.window_size);
+    let max = device.limits().max_texture_dimension_2d;
+    if width > max || height > max {
+        eprintln!("--export-size {}x{} is larger than this GPU's {}-pixel textures", width, height, max);
+        std::process::exit(1);
+    }
+    let workarounds = crate::shader_workarounds(&config, &adapter);
+    let error_log = Arc::new(Mutex::new(ErrorLog::default()));
+    gpu_error::install_uncaptured_handler(&device, &error_log);
+    gpu_error::push_scopes(&device);
+
+    let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute_shader"),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
+    });
+    let render_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("render_shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../render.wgsl").into()),
+    });
+
+    let grid = config.grid_size;
+    let (obstacle_tex, obstacle_view) = create_storage_tex(&device, grid);
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let size = winit::dpi::PhysicalSize::new(width, height);
+    let base_params = crate::base_params(&config, crate::cell_size_for(size));
+    let base_decays = (config.dye_decay, config.velocity_decay, config.temperature_decay);
+    let mut sim_params = base_params;
+    let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&sim_params),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let compute_bgl = crate::create_compute_bgl(&device);
+    let render_bgl = crate::create_render_bgl(&device);
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+    });
+    let fluid_pipes = FluidPipelines::new(&device, &compute_pl, &compute_shader, workarounds);
+    let render_pipeline = crate::create_render_pipeline(&device, &render_pl, &render_shader, FORMAT, "fs_draw");
+
+    let shared = simulation::Shared {
+        device: &device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    let mut sim = Simulation::new(&shared, &compute_shader, config.pressure_solver);
+    sim.advection = config.advection;
+    eprintln!("Pressure solver: {}", sim.solver.name());
+    let mut detail = Detail::new(&device, &queue, &sim.velocity_view, grid, size);
+    let mut particles = Particles::new(&device, &sim.velocity_view, &sim.density_view, &obstacle_view, grid, config.seed, FORMAT);
+    particles.set_cell_size(sim_params.cell_size);
+
+    let start_state = crate::load_start_state(&config);
+    let scene = crate::load_scene_or_default(&config.scene, config.init_noise);
+    let targets = crate::scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed);
+    crate::apply_scene(&queue, &scene, start_state.as_ref(), &targets, &mut sim_params, &base_params, base_decays);
+    // The presets' params at the scene's dt, which the whole export keeps
+    let start_params = sim_params;
+    let preset_params: Vec<SimParams> = script
+        .presets()
+        .map(|o| {
+            let o = ParamOverrides { dt: Some(start_params.dt), ..o.clone() };
+            crate::scene_params(&o, &start_params, &base_params, base_decays)
+        })
+        .collect();
+
+    let lut = crate::colormap::Lut::new(&device, &queue);
+    let (colormap, _) = crate::colormap::startup(&lut, &queue, config.colormap, config.colormap_file.as_deref());
+    let mut view_params = ViewParams {
+        viewport: [0.0, 0.0, 1.0, 1.0], diff_field: DiffField::Dye as u32, diff_scale: DiffField::Dye.default_scale(),
+        time: 0.0, detail: config.detail, wall_shear: config.wall_shear.unwrap_or(0.0),
+        field: FieldView::Dye as u32, field_scale: 1.0, hdr: 0, colormap: colormap as u32, dither: 0, shading: 0, light_angle: 0.0,
+    };
+    let render_bindings = RenderBindings {
+        layout: render_bgl,
+        sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        }),
+        view: device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("view_params"),
+            contents: bytemuck::bytes_of(&view_params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        }),
+        lut,
+        background: crate::glass::Background::new(&device, &queue, None),
+    };
+    let render_bg = render_bindings.create(&device, &obstacle_view, particles.trail_view(), detail.view(), &sim, sim.fields());
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("export_frame"),
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let target_view = target.create_view(&Default::default());
+
+    if let Some(err) = gpu_error::pop_scopes(&device, "setup").await {
+        let info = adapter.get_info();
+        eprintln!("{}\n(GPU: {}, backend: {:?})", err, info.name, info.backend);
+        std::process::exit(1);
+    }
+
+    let substeps = config.substeps;
+    eprintln!(
+        "Exporting {} frames of {}×{}, one every {} steps of {} s, into {}",
+        config.export_frames, width, height, config.export_every, sim_params.dt, dir,
+    );
+    for frame in 0..config.export_frames {
+        gpu_error::push_scopes(&device);
+        // The first frame is the start; each after it is `export_every` steps on
+        let steps = if frame == 0 { 0 } else { config.export_every };
+        for _ in 0..steps {
+            sim_params = script.params(&sim_params, &start_params, &preset_params);
+            if script.has_emitters() {
+                let on = scene.emitters.iter().chain(script.emitters(sim_params.time));
+                sim_params.emitter_count = crate::upload_emitters(&queue, &emitter_buffer, grid, on);
+            }
+            let mut step_params = crate::substep_params(&sim_params, substeps);
+            sim_params.time += step_params.dt * substeps as f32;
+            step_params.time = sim_params.time;
+            if scene.has_moving_obstacles() {
+                crate::upload_obstacles(&queue, &scene, &obstacle_tex, grid, sim_params.time);
+            }
+            queue.write_buffer(&param_buffer, 0, bytemuck::bytes_of(&step_params));
+            sim.set_domain(&queue, &sim_params);
+            particles.update(&queue, step_params.dt * substeps as f32);
+            detail.update(&queue, step_params.dt * substeps as f32, sim_params.time, sim_params.cell_size);
+
+            let mut encoder = device.create_command_encoder(&Default::default());
+            {
+                let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("sim"), timestamp_writes: None });
+                for _ in 0..substeps {
+                    sim.step(&mut c, &fluid_pipes, &step_params);
+                }
+                particles.dispatch(&mut c);
+                if view_params.detail > 0.0 {
+                    detail.dispatch(&mut c);
+                }
+            }
+            queue.submit(Some(encoder.finish()));
+        }
+
+        view_params.time = sim_params.time;
+        view_params.viewport = script.viewport(sim_params.time);
+        queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("export"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target_view, resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            r.set_pipeline(&render_pipeline);
+            r.set_bind_group(0, &render_bg, &[]);
+            r.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+        let rgb = crate::gallery::read_rgb(&device, &queue, &target);
+        if let Some(err) = gpu_error::pop_scopes(&device, "export").await {
+            eprintln!("Frame {}: {}", frame, err);
+            std::process::exit(1);
+        }
+
+        let path = format!("{}/frame-{:05}.png", dir, frame);
+        if let Err(e) = std::fs::write(&path, crate::png::encode(width, height, &rgb)) {
+            eprintln!("Failed to save {}: {}", path, e);
+            std::process::exit(1);
+        }
+        eprintln!("Saved {} (t = {:.3} s)", path, sim_params.time);
+    }
+}
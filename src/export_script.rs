@@ -0,0 +1,372 @@
+//! Export scripts (`--export-script FILE`): a camera and parameter
+//! timeline for `--export`, so a finished clip, with pans and zooms, a
+//! change of character partway through and emitters switching on and off
+//! on cue, comes out of one command. Written in TOML, like the scene
+//! files. Times are simulated seconds from the first frame, so a script
+//! gives the same frames on every run.
+//!
+//! ```toml
+//! [[camera]]              # keyframes, eased between
+//! time = 0.0
+//! center = [0.5, 0.5]     # domain units (0..1, y down)
+//! zoom = 1.0              # 1 shows the whole domain
+//!
+//! [[camera]]
+//! time = 4.0
+//! center = [0.3, 0.6]
+//! zoom = 2.5
+//! ease = "smooth"         # from the key before: or "linear", "cut"
+//!
+//! [[preset]]              # crossfade to another scene's [params]
+//! time = 6.0
+//! scene = "honey"         # builtin name or .toml path
+//! fade = 2.0              # seconds; 0 switches at once
+//!
+//! [[emitter]]             # a scene [[emitter]], on for a while
+//! start = 1.0
+//! stop = 3.0              # omit to leave it on
+//! pos = [0.1, 0.5]
+//! radius = 0.04
+//! velocity = [80.0, 0.0]
+//! ```
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+use crate::scene::{self, Emitter, ParamOverrides, Scene};
+use crate::SimParams;
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Script {
+    /// In time order.
+    #[serde(rename = "camera")]
+    cameras: Vec<CameraKey>,
+    /// In time order.
+    #[serde(rename = "preset")]
+    presets: Vec<Preset>,
+    #[serde(rename = "emitter")]
+    cues: Vec<Cue>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKey {
+    time: f32,
+    #[serde(default = "middle")]
+    center: [f32; 2],
+    #[serde(default = "no_zoom", deserialize_with = "zoom")]
+    zoom: f32,
+    /// How the camera gets here from the key before.
+    #[serde(default = "smooth", deserialize_with = "scene::keyword")]
+    ease: Ease,
+}
+
+fn middle() -> [f32; 2] {
+    [0.5, 0.5]
+}
+
+fn no_zoom() -> f32 {
+    1.0
+}
+
+fn smooth() -> Ease {
+    Ease::Smooth
+}
+
+fn zoom<'de, D: Deserializer<'de>>(d: D) -> Result<f32, D::Error> {
+    match f32::deserialize(d)? {
+        zoom if zoom < 1.0 => Err(D::Error::custom("`zoom` must be at least 1")),
+        zoom => Ok(zoom),
+    }
+}
+
+/// How the camera moves between two keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ease {
+    /// Evenly from one key to the next.
+    Linear,
+    /// Starting and stopping gently (smoothstep).
+    Smooth,
+    /// Staying at the key before, then jumping.
+    Cut,
+}
+
+impl Ease {
+    const ALL: [Ease; 3] = [Ease::Linear, Ease::Smooth, Ease::Cut];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Ease::Linear => "linear",
+            Ease::Smooth => "smooth",
+            Ease::Cut => "cut",
+        }
+    }
+
+    /// Progress between two keys at `k` of the way in time.
+    fn apply(self, k: f32) -> f32 {
+        match self {
+            Ease::Linear => k,
+            Ease::Smooth => k * k * (3.0 - 2.0 * k),
+            Ease::Cut => 0.0,
+        }
+    }
+}
+
+impl std::str::FromStr for Ease {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("ease", s, &Self::ALL.map(|e| (e.name(), e)))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(try_from = "PresetTable")]
+struct Preset {
+    time: f32,
+    fade: f32,
+    params: ParamOverrides,
+}
+
+/// A `[[preset]]` as written, naming the scene to take `params` from.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PresetTable {
+    time: f32,
+    #[serde(default)]
+    fade: f32,
+    scene: String,
+}
+
+impl TryFrom<PresetTable> for Preset {
+    type Error = String;
+
+    fn try_from(t: PresetTable) -> Result<Self, String> {
+        if t.fade < 0.0 {
+            return Err("`fade` can't be negative".to_string());
+        }
+        Ok(Preset { time: t.time, fade: t.fade, params: Scene::load(&t.scene)?.params })
+    }
+}
+
+/// An emitter on between `start` and `stop`.
+struct Cue {
+    start: f32,
+    stop: f32,
+    emitter: Emitter,
+}
+
+impl<'de> Deserialize<'de> for Cue {
+    /// A scene `[[emitter]]` table with `start` and `stop` added.
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let mut table = toml::Table::deserialize(d)?;
+        let mut time = |key: &str, default: f32| match table.remove(key) {
+            Some(value) => f32::deserialize(value).map_err(D::Error::custom),
+            None => Ok(default),
+        };
+        let (start, stop) = (time("start", 0.0)?, time("stop", f32::INFINITY)?);
+        if stop <= start {
+            return Err(D::Error::custom("`stop` must come after `start`"));
+        }
+        let emitter = Emitter::deserialize(toml::Value::Table(table)).map_err(D::Error::custom)?;
+        Ok(Cue { start, stop, emitter })
+    }
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Script, String> {
+        let src = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Script::parse(&src).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(src: &str) -> Result<Script, String> {
+        let mut script: Script = scene::from_toml(src)?;
+        script.cameras.sort_by(|a, b| a.time.total_cmp(&b.time));
+        script.presets.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(script)
+    }
+
+    /// `ViewParams::viewport` at time `t`: the part of the domain the
+    /// camera sees, kept inside the domain. All of it without camera keys.
+    pub fn viewport(&self, t: f32) -> [f32; 4] {
+        let next = self.cameras.iter().position(|c| c.time > t);
+        let (center, zoom) = match next {
+            None if self.cameras.is_empty() => return [0.0, 0.0, 1.0, 1.0],
+            None => {
+                let last = self.cameras.last().unwrap();
+                (last.center, last.zoom)
+            }
+            Some(0) => (self.cameras[0].center, self.cameras[0].zoom),
+            Some(i) => {
+                let (a, b) = (&self.cameras[i - 1], &self.cameras[i]);
+                let k = b.ease.apply((t - a.time) / (b.time - a.time));
+                let lerp = |x: f32, y: f32| x + (y - x) * k;
+                // Zoom evenly in scale, so a zoom from 1 to 4 passes 2 halfway
+                let zoom = (a.zoom.ln() + (b.zoom.ln() - a.zoom.ln()) * k).exp();
+                ([lerp(a.center[0], b.center[0]), lerp(a.center[1], b.center[1])], zoom)
+            }
+        };
+        let half = 0.5 / zoom;
+        let center = center.map(|c| c.clamp(half, 1.0 - half));
+        [center[0] - half, center[1] - half, 2.0 * half, 2.0 * half]
+    }
+
+    /// The presets' param overrides, in time order. `params` wants them
+    /// as `SimParams`.
+    pub fn presets(&self) -> impl Iterator<Item = &ParamOverrides> {
+        self.presets.iter().map(|p| &p.params)
+    }
+
+    /// `current` with the params a scene sets taken from `start` and
+    /// crossfaded into each preset that has begun by `current.time`, in
+    /// turn. `targets` are the presets' params.
+    pub fn params(&self, current: &SimParams, start: &SimParams, targets: &[SimParams]) -> SimParams {
+        let t = current.time;
+        let mut params = mix(current, start, 1.0);
+        for (preset, target) in self.presets.iter().zip(targets) {
+            if t < preset.time {
+                break;
+            }
+            let k = if preset.fade > 0.0 { ((t - preset.time) / preset.fade).min(1.0) } else { 1.0 };
+            params = mix(&params, target, k);
+        }
+        params
+    }
+
+    /// Whether the script switches any emitters on.
+    pub fn has_emitters(&self) -> bool {
+        !self.cues.is_empty()
+    }
+
+    /// The script's emitters that are on at time `t`.
+    pub fn emitters(&self, t: f32) -> impl Iterator<Item = &Emitter> + '_ {
+        self.cues.iter().filter(move |c| (c.start..c.stop).contains(&t)).map(|c| &c.emitter)
+    }
+}
+
+/// `a`'s params `k` of the way to `b`'s, for the ones a scene sets. Those
+/// that are a choice (gravity mode, boundary) switch halfway. The rest of
+/// `a` (time, dt, the mouse, emitters) is kept.
+fn mix(a: &SimParams, b: &SimParams, k: f32) -> SimParams {
+    let f = |x: f32, y: f32| x + (y - x) * k;
+    let v2 = |x: [f32; 2], y: [f32; 2]| [f(x[0], y[0]), f(x[1], y[1])];
+    let v3 = |x: [f32; 3], y: [f32; 3]| [f(x[0], y[0]), f(x[1], y[1]), f(x[2], y[2])];
+    let pick = |x: u32, y: u32| if k < 0.5 { x } else { y };
+    SimParams {
+        viscosity: f(a.viscosity, b.viscosity),
+        diffusion: f(a.diffusion, b.diffusion),
+        dye_decay: f(a.dye_decay, b.dye_decay),
+        velocity_decay: f(a.velocity_decay, b.velocity_decay),
+        temperature_decay: f(a.temperature_decay, b.temperature_decay),
+        dye_threshold: f(a.dye_threshold, b.dye_threshold),
+        velocity_threshold: f(a.velocity_threshold, b.velocity_threshold),
+        smoke_weight: f(a.smoke_weight, b.smoke_weight),
+        dye_density: v3(a.dye_density, b.dye_density),
+        heat_lift: f(a.heat_lift, b.heat_lift),
+        heat_strength: f(a.heat_strength, b.heat_strength),
+        add_strength: f(a.add_strength, b.add_strength),
+        radius: f(a.radius, b.radius),
+        vorticity: f(a.vorticity, b.vorticity),
+        gravity: v2(a.gravity, b.gravity),
+        gravity_by_dye: pick(a.gravity_by_dye, b.gravity_by_dye),
+        wind: v2(a.wind, b.wind),
+        wind_gust: f(a.wind_gust, b.wind_gust),
+        wind_gust_period: f(a.wind_gust_period, b.wind_gust_period),
+        boundary: pick(a.boundary, b.boundary),
+        well: f(a.well, b.well),
+        wake: f(a.wake, b.wake),
+        subgrid: f(a.subgrid, b.subgrid),
+        dye_mass: f(a.dye_mass, b.dye_mass),
+        heat_expansion: f(a.heat_expansion, b.heat_expansion),
+        ..*a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> SimParams {
+        crate::base_params(&crate::config::Config::default(), [1.0, 1.0])
+    }
+
+    fn near(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn eases_between_keys() {
+        assert_eq!([0.0, 0.25, 1.0].map(|k| Ease::Linear.apply(k)), [0.0, 0.25, 1.0]);
+        assert_eq!([0.0, 0.25, 0.5, 1.0].map(|k| Ease::Smooth.apply(k)), [0.0, 0.15625, 0.5, 1.0]);
+        assert_eq!([0.0, 0.5, 0.99].map(|k| Ease::Cut.apply(k)), [0.0; 3]);
+
+        let script = Script::parse(
+            "[[camera]]\ntime = 2.0\ncenter = [0.3, 0.5]\nzoom = 2.0\n\n\
+             [[camera]]\ntime = 0.0\ncenter = [0.7, 0.5]\nzoom = 2.0\n\n\
+             [[camera]]\ntime = 4.0\ncenter = [0.5, 0.5]\nzoom = 2.0\nease = \"cut\"\n",
+        )
+        .unwrap();
+        // Keys are sorted by time; before the first and after the last, the
+        // camera holds still
+        let center_x = |t: f32| script.viewport(t)[0] + 0.25;
+        assert!(near(center_x(-1.0), 0.7));
+        assert!(near(center_x(1.0), 0.5), "smooth halfway is halfway");
+        assert!(near(center_x(1.5), 0.3 + 0.4 * (1.0 - Ease::Smooth.apply(0.75))));
+        assert!(near(center_x(3.9), 0.3), "a cut holds the key before");
+        assert!(near(center_x(4.0), 0.5));
+        assert!(near(center_x(9.0), 0.5));
+    }
+
+    #[test]
+    fn zooms_evenly_in_scale_and_stays_inside_the_domain() {
+        assert_eq!(Script::default().viewport(3.0), [0.0, 0.0, 1.0, 1.0]);
+        let script = Script::parse(
+            "[[camera]]\ntime = 0.0\ncenter = [0.05, 0.9]\nzoom = 1.0\n\n\
+             [[camera]]\ntime = 1.0\ncenter = [0.05, 0.9]\nzoom = 4.0\nease = \"linear\"\n",
+        )
+        .unwrap();
+        let [x, y, w, h] = script.viewport(0.5);
+        assert!(near(w, 0.5) && near(h, 0.5), "zoom 2 halfway from 1 to 4");
+        // Pushed in from the left and bottom edges
+        assert!(near(x, 0.0) && near(y, 0.5));
+        let [x, y, w, _] = script.viewport(1.0);
+        assert!(near(w, 0.25) && near(x, 0.0) && near(y, 0.75));
+        assert!(Script::parse("[[camera]]\ntime = 0.0\nzoom = 0.5\n").is_err());
+    }
+
+    #[test]
+    fn crossfades_presets_in_turn() {
+        let script = Script::parse(
+            "[[preset]]\ntime = 2.0\nscene = \"honey\"\nfade = 4.0\n\n\
+             [[preset]]\ntime = 10.0\nscene = \"default\"\n",
+        )
+        .unwrap();
+        assert_eq!(script.presets().count(), 2);
+        let start = SimParams { viscosity: 10.0, ..params() };
+        let targets = [SimParams { viscosity: 150.0, ..params() }, SimParams { viscosity: 30.0, ..params() }];
+        let at = |time: f32| {
+            // Time and the mouse belong to the run, not the presets
+            let current = SimParams { time, viscosity: -1.0, mouse_pos: [7.0, 8.0], ..params() };
+            let mixed = script.params(&current, &start, &targets);
+            assert_eq!((mixed.time, mixed.mouse_pos), (time, [7.0, 8.0]));
+            mixed.viscosity
+        };
+        assert!(near(at(1.0), 10.0), "before the first preset, the start's own");
+        assert!(near(at(3.0), 10.0 + 140.0 * 0.25));
+        assert!(near(at(6.0), 150.0));
+        assert!(near(at(10.0), 30.0), "no fade switches at once");
+    }
+
+    #[test]
+    fn cues_emitters_and_rejects_bad_sections() {
+        let script = Script::parse("[[emitter]]\nstart = 1.0\nstop = 3.0\npos = [0.1, 0.5]\nradius = 0.04\n").unwrap();
+        assert!(script.has_emitters());
+        assert_eq!([0.5, 1.0, 2.9, 3.0].map(|t| script.emitters(t).count()), [0, 1, 1, 0]);
+        assert!(Script::parse("[[emitter]]\nstart = 3.0\nstop = 1.0\n").is_err());
+        assert!(Script::parse("[[preset]]\ntime = 1.0\nscene = \"honey\"\nfade = -1.0\n").is_err());
+        assert!(Script::parse("[[preset]]\ntime = 1.0\n").is_err());
+        assert!(Script::parse("[lights]\n").is_err());
+        assert!(Script::parse("[[camera]]\ntime = 0.0\nease = \"bounce\"\n").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! Long-exposure stills (L). While an exposure is open, every simulated
+//! frame is also drawn into an `Rgba16Float` target and blended in as a
+//! running mean: frame n weighs 1/n, set through the blend constant, so the
+//! target always holds the average of the frames so far and never
+//! overflows. Once `duration` simulated seconds have passed, the mean is
+//! held on screen in place of the live view and (natively) saved as a PPM,
+//! like a camera with its shutter left open on the flow.
+
+/// Format of the accumulation target. Half floats keep a mean of values
+/// around 1 to about three decimal digits, plenty for an 8-bit still.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+enum State {
+    Idle,
+    /// `frames` drawn so far over `elapsed` simulated seconds.
+    Exposing { frames: u32, elapsed: f32 },
+    /// The finished still is on screen.
+    Showing,
+}
+
+struct Target {
+    /// Read back by `save`.
+    #[cfg(not(target_arch = "wasm32"))]
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+pub struct Exposure {
+    /// Simulated seconds an exposure stays open.
+    pub duration: f32,
+    state: State,
+    accum_pipeline: wgpu::RenderPipeline,
+    still_pipeline: wgpu::RenderPipeline,
+    still_bgl: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    target: Option<Target>,
+}
+
+/// `fs_draw` from `render.wgsl`, rendering into the accumulation target and
+/// blending by the constant: `mean + (frame - mean) / n`.
+pub fn accum_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule) -> wgpu::RenderPipeline {
+    let running_mean = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Constant,
+        dst_factor: wgpu::BlendFactor::OneMinusConstant,
+        operation: wgpu::BlendOperation::Add,
+    };
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("exposure_accum"), layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module, entry_point: "vs_fullscreen", buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module, entry_point: "fs_draw",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: FORMAT,
+                blend: Some(wgpu::BlendState { color: running_mean, alpha: running_mean }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+impl Exposure {
+    pub fn new(
+        device: &wgpu::Device, render_layout: &wgpu::PipelineLayout, render_module: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat, duration: f32,
+    ) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("exposure_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../exposure.wgsl").into()),
+        });
+        let still_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("exposure_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let still_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&still_bgl], push_constant_ranges: &[],
+        });
+        Self {
+            duration,
+            state: State::Idle,
+            accum_pipeline: accum_pipeline(device, render_layout, render_module),
+            still_pipeline: crate::create_render_pipeline(device, &still_pl, &module, format, "fs_still"),
+            still_bgl,
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+            target: None,
+        }
+    }
+
+    /// Replace the accumulation pipeline (hot reload of `render.wgsl`).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_accum_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.accum_pipeline = pipeline;
+    }
+
+    /// The finished still is on screen instead of the live view.
+    pub fn showing(&self) -> bool {
+        matches!(self.state, State::Showing)
+    }
+
+    /// L: open an exposure the size of the window, or cancel the one in
+    /// progress, or put the live view back after a finished one.
+    pub fn toggle(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.state = match self.state {
+            State::Idle => {
+                self.target = Some(self.create_target(device, width, height));
+                eprintln!("Exposure: open for {} s of simulated time", self.duration);
+                State::Exposing { frames: 0, elapsed: 0.0 }
+            }
+            State::Exposing { .. } => {
+                eprintln!("Exposure: cancelled");
+                State::Idle
+            }
+            State::Showing => State::Idle,
+        };
+    }
+
+    /// Drop an open exposure: its frames no longer match the window.
+    pub fn cancel(&mut self) {
+        if let State::Exposing { .. } = self.state {
+            eprintln!("Exposure: cancelled (window resized)");
+            self.state = State::Idle;
+        }
+    }
+
+    fn create_target(&self, device: &wgpu::Device, width: u32, height: u32) -> Target {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("exposure"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("exposure_bg"), layout: &self.still_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+            ],
+        });
+        Target {
+            #[cfg(not(target_arch = "wasm32"))]
+            texture,
+            view,
+            bind_group,
+        }
+    }
+
+    /// Blend this frame, drawn with `render_bg`, into an open exposure that
+    /// just advanced `dt` simulated seconds. Returns true when that closed
+    /// it, so the caller can `save` once the frame is submitted.
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder, render_bg: &wgpu::BindGroup, dt: f32) -> bool {
+        let (State::Exposing { frames, elapsed }, Some(target)) = (&mut self.state, &self.target) else { return false };
+        *frames += 1;
+        *elapsed += dt;
+        let weight = 1.0 / *frames as f64;
+        {
+            let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("exposure_accum"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view, resolve_target: None,
+                    ops: wgpu::Operations {
+                        // The first frame's weight of 1 replaces whatever was there
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            r.set_pipeline(&self.accum_pipeline);
+            r.set_bind_group(0, render_bg, &[]);
+            r.set_blend_constant(wgpu::Color { r: weight, g: weight, b: weight, a: weight });
+            r.draw(0..3, 0..1);
+        }
+        if *elapsed < self.duration {
+            return false;
+        }
+        eprintln!("Exposure: done, {} frames over {:.1} s (L returns to the live view)", frames, elapsed);
+        self.state = State::Showing;
+        true
+    }
+
+    /// Draw the finished still, in place of the live view.
+    pub fn draw_still<'a>(&'a self, r: &mut wgpu::RenderPass<'a>) {
+        if let Some(target) = &self.target {
+            r.set_pipeline(&self.still_pipeline);
+            r.set_bind_group(0, &target.bind_group, &[]);
+            r.draw(0..3, 0..1);
+        }
+    }
+
+    /// Write the finished still to `exposure-<time>.ppm`, sRGB encoded.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let Some(target) = &self.target else { return };
+        let (width, height) = (target.texture.width(), target.texture.height());
+        let row_bytes = width * 8;
+        let padded = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("exposure_readback"),
+            size: (padded * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("exposure_readback") });
+        encoder.copy_texture_to_buffer(
+            target.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(padded), rows_per_image: Some(height) },
+            },
+            target.texture.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for row in slice.get_mapped_range().chunks(padded as usize) {
+            for texel in row[..row_bytes as usize].chunks_exact(8) {
+                for c in 0..3 {
+                    let linear = crate::f16_to_f32(u16::from_le_bytes([texel[c * 2], texel[c * 2 + 1]]));
+                    ppm.push((srgb_encode(linear) * 255.0).round() as u8);
+                }
+            }
+        }
+        buffer.unmap();
+
+        let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let path = format!("exposure-{}.ppm", secs);
+        match std::fs::write(&path, ppm) {
+            Ok(()) => eprintln!("Saved {}", path),
+            Err(e) => eprintln!("Failed to save {}: {}", path, e),
+        }
+    }
+}
+
+/// Linear to sRGB transfer, clamped to [0, 1], as the swapchain's sRGB
+/// format would apply it.
+#[cfg(not(target_arch = "wasm32"))]
+fn srgb_encode(linear: f32) -> f32 {
+    let c = linear.clamp(0.0, 1.0);
+    if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
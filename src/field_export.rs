@@ -0,0 +1,313 @@
+//! Raw field export (F7, `--field-format`): the velocity, dye, temperature
+//! and pressure fields read back at full precision, for post-processing in
+//! Python, Matlab and the like. Either one OpenEXR file with a layer per
+//! field, or a directory of numpy `.npy` arrays. Both are written here,
+//! uncompressed and as 32-bit floats, so no codec crate is needed.
+//!
+//! Rows run top to bottom as on screen, velocities are in x-cells per
+//! second (+y down), and the rest are in the simulation's own units. Cells
+//! take the window's aspect (`SimParams::cell_size` is (1, height/width)),
+//! so vy is not in rows per second: divide it by height/width for that.
+
+/// `--field-format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One `.exr` with `velocity.X/Y`, `dye.R/G/B`, `temperature` and
+    /// `pressure` channels.
+    Exr,
+    /// A directory of `.npy` arrays shaped (rows, columns[, components]).
+    Npy,
+}
+
+impl Format {
+    const ALL: [Format; 2] = [Format::Exr, Format::Npy];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Exr => "exr",
+            Format::Npy => "npy",
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("field format", s, &Self::ALL.map(|f| (f.name(), f)))
+    }
+}
+
+/// The fields of a `grid`² simulation, one value per cell each.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Fields {
+    pub grid: u32,
+    pub velocity: Vec<[f32; 2]>,
+    pub dye: Vec<[f32; 3]>,
+    pub temperature: Vec<f32>,
+    pub pressure: Vec<f32>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Fields {
+    /// From the simulation's textures as `read_field` returns them: dye is
+    /// (red, temperature, green, blue).
+    pub fn new(grid: u32, velocity: &[[f32; 4]], density: &[[f32; 4]], pressure: &[[f32; 4]]) -> Self {
+        Self {
+            grid,
+            velocity: velocity.iter().map(|v| [v[0], v[1]]).collect(),
+            dye: density.iter().map(|d| [d[0], d[2], d[3]]).collect(),
+            temperature: density.iter().map(|d| d[1]).collect(),
+            pressure: pressure.iter().map(|p| p[0]).collect(),
+        }
+    }
+
+    /// Write `<stem>.exr`, or `<stem>/` holding `velocity.npy`, `dye.npy`,
+    /// `temperature.npy` and `pressure.npy`. Returns what was written.
+    pub fn save(&self, format: Format, stem: &str) -> std::io::Result<String> {
+        let n = self.grid as usize;
+        match format {
+            Format::Exr => {
+                let path = format!("{}.exr", stem);
+                std::fs::write(&path, self.exr())?;
+                Ok(path)
+            }
+            Format::Npy => {
+                std::fs::create_dir_all(stem)?;
+                let arrays: [(&str, &[usize], &[f32]); 4] = [
+                    ("velocity", &[n, n, 2], bytemuck::cast_slice(&self.velocity)),
+                    ("dye", &[n, n, 3], bytemuck::cast_slice(&self.dye)),
+                    ("temperature", &[n, n], &self.temperature),
+                    ("pressure", &[n, n], &self.pressure),
+                ];
+                for (name, shape, data) in arrays {
+                    std::fs::write(format!("{}/{}.npy", stem, name), npy(shape, data))?;
+                }
+                Ok(format!("{}/", stem))
+            }
+        }
+    }
+
+    /// A single-part scanline OpenEXR file, one scanline per block, with
+    /// FLOAT channels and no compression.
+    fn exr(&self) -> Vec<u8> {
+        let n = self.grid as usize;
+        let mut channels = vec![
+            ("velocity.X", self.velocity.iter().map(|v| v[0]).collect()),
+            ("velocity.Y", self.velocity.iter().map(|v| v[1]).collect()),
+            ("dye.R", self.dye.iter().map(|d| d[0]).collect()),
+            ("dye.G", self.dye.iter().map(|d| d[1]).collect()),
+            ("dye.B", self.dye.iter().map(|d| d[2]).collect()),
+            ("temperature", self.temperature.clone()),
+            ("pressure", self.pressure.clone()),
+        ];
+        // Readers expect the channel list, and each scanline's data, sorted
+        channels.sort_by_key(|(name, _)| *name);
+
+        let mut out = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        let mut chlist = Vec::new();
+        for (name, _) in &channels {
+            chlist.extend_from_slice(name.as_bytes());
+            chlist.push(0);
+            // FLOAT, not perceptually linear, reserved, x and y sampling 1
+            for v in [2i32, 0, 1, 1] {
+                chlist.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        chlist.push(0);
+        let window: Vec<u8> = [0i32, 0, n as i32 - 1, n as i32 - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let attributes: [(&str, &str, &[u8]); 8] = [
+            ("channels", "chlist", &chlist),
+            ("compression", "compression", &[0]),
+            ("dataWindow", "box2i", &window),
+            ("displayWindow", "box2i", &window),
+            ("lineOrder", "lineOrder", &[0]),
+            ("pixelAspectRatio", "float", &1.0f32.to_le_bytes()),
+            ("screenWindowCenter", "v2f", &[0; 8]),
+            ("screenWindowWidth", "float", &1.0f32.to_le_bytes()),
+        ];
+        for (name, kind, value) in attributes {
+            out.extend_from_slice(name.as_bytes());
+            out.push(0);
+            out.extend_from_slice(kind.as_bytes());
+            out.push(0);
+            out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            out.extend_from_slice(value);
+        }
+        out.push(0);
+
+        let line_bytes = channels.len() * n * 4;
+        let first_line = out.len() + n * 8;
+        for y in 0..n {
+            out.extend_from_slice(&((first_line + y * (8 + line_bytes)) as u64).to_le_bytes());
+        }
+        for y in 0..n {
+            out.extend_from_slice(&(y as i32).to_le_bytes());
+            out.extend_from_slice(&(line_bytes as i32).to_le_bytes());
+            for (_, values) in &channels {
+                for v in &values[y * n..(y + 1) * n] {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+}
+
+/// A version 1.0 `.npy` file of little-endian f32s in C order.
+#[cfg(not(target_arch = "wasm32"))]
+fn npy(shape: &[usize], data: &[f32]) -> Vec<u8> {
+    let dims: Vec<String> = shape.iter().map(|d| d.to_string()).collect();
+    let shape = if let [d] = &dims[..] { format!("{},", d) } else { dims.join(", ") };
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}), }}", shape);
+    // Padded with spaces so the data starts 64-byte aligned, ending in a newline
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+    let mut out = Vec::with_capacity(10 + header.len() + data.len() * 4);
+    out.extend_from_slice(b"\x93NUMPY\x01\x00");
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend_from_slice(header.as_bytes());
+    for v in data {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3² field with a different value in every channel of every cell.
+    fn fields() -> Fields {
+        let n = 3;
+        let cell = |i: usize, c: usize| i as f32 * 10.0 + c as f32 + 0.25;
+        Fields {
+            grid: n as u32,
+            velocity: (0..n * n).map(|i| [cell(i, 0), -cell(i, 1)]).collect(),
+            dye: (0..n * n).map(|i| [cell(i, 2), cell(i, 3), cell(i, 4)]).collect(),
+            temperature: (0..n * n).map(|i| cell(i, 5)).collect(),
+            pressure: (0..n * n).map(|i| -cell(i, 6)).collect(),
+        }
+    }
+
+    /// A scratch directory of the test's own, emptied first.
+    fn scratch(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("wgpu-fluid-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.to_str().unwrap().to_string()
+    }
+
+    fn le_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn floats(bytes: &[u8]) -> Vec<f32> {
+        bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect()
+    }
+
+    /// The shape and data of a `.npy` file, checking its header says f32s
+    /// in C order.
+    fn parse_npy(bytes: &[u8]) -> (Vec<usize>, Vec<f32>) {
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + len) % 64, 0, "data isn't aligned");
+        let header = std::str::from_utf8(&bytes[10..10 + len]).unwrap();
+        assert!(header.ends_with('\n'));
+        assert!(header.contains("'descr': '<f4'") && header.contains("'fortran_order': False"), "{}", header);
+        let shape = &header[header.find("'shape': (").unwrap() + 10..header.find(')').unwrap()];
+        let shape = shape.split(',').map(str::trim).filter(|d| !d.is_empty()).map(|d| d.parse().unwrap()).collect();
+        (shape, floats(&bytes[10 + len..]))
+    }
+
+    #[test]
+    fn npy_round_trips() {
+        let fields = fields();
+        let dir = scratch("npy");
+        let stem = format!("{}/fields", dir);
+        assert_eq!(fields.save(Format::Npy, &stem).unwrap(), format!("{}/", stem));
+        let read = |name: &str| parse_npy(&std::fs::read(format!("{}/{}.npy", stem, name)).unwrap());
+
+        let (shape, velocity) = read("velocity");
+        assert_eq!(shape, [3, 3, 2]);
+        assert_eq!(velocity, fields.velocity.concat());
+        let (shape, dye) = read("dye");
+        assert_eq!(shape, [3, 3, 3]);
+        assert_eq!(dye, fields.dye.concat());
+        assert_eq!(read("temperature"), (vec![3, 3], fields.temperature.clone()));
+        assert_eq!(read("pressure"), (vec![3, 3], fields.pressure.clone()));
+        // One-dimensional shapes keep their trailing comma
+        assert_eq!(parse_npy(&npy(&[2], &[1.0, 2.0])), (vec![2], vec![1.0, 2.0]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exr_round_trips() {
+        let fields = fields();
+        let dir = scratch("exr");
+        let path = fields.save(Format::Exr, &format!("{}/fields", dir)).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // Magic number, version 2, single-part scanline
+        assert_eq!(&bytes[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        let mut at = 8;
+        let string = |at: &mut usize| {
+            let end = *at + bytes[*at..].iter().position(|&b| b == 0).unwrap();
+            let s = std::str::from_utf8(&bytes[*at..end]).unwrap();
+            *at = end + 1;
+            s
+        };
+        let mut attributes = std::collections::HashMap::new();
+        loop {
+            let name = string(&mut at);
+            if name.is_empty() {
+                break;
+            }
+            let kind = string(&mut at);
+            let size = le_i32(&bytes, at) as usize;
+            attributes.insert(name, (kind, &bytes[at + 4..at + 4 + size]));
+            at += 4 + size;
+        }
+        assert_eq!(attributes["compression"], ("compression", &[0][..]));
+        assert_eq!(attributes["dataWindow"].0, "box2i");
+        assert_eq!([0, 4, 8, 12].map(|i| le_i32(attributes["dataWindow"].1, i)), [0, 0, 2, 2]);
+
+        let (kind, mut chlist) = attributes["channels"];
+        assert_eq!(kind, "chlist");
+        let mut channels = Vec::new();
+        while chlist[0] != 0 {
+            let end = chlist.iter().position(|&b| b == 0).unwrap();
+            channels.push(std::str::from_utf8(&chlist[..end]).unwrap());
+            // FLOAT, sampled every pixel
+            assert_eq!([0, 8, 12].map(|i| le_i32(chlist, end + 1 + i)), [2, 1, 1]);
+            chlist = &chlist[end + 17..];
+        }
+        assert_eq!(channels, ["dye.B", "dye.G", "dye.R", "pressure", "temperature", "velocity.X", "velocity.Y"]);
+
+        let n = 3;
+        let mut values = vec![Vec::new(); channels.len()];
+        for y in 0..n {
+            let line = u64::from_le_bytes(bytes[at + y * 8..at + y * 8 + 8].try_into().unwrap()) as usize;
+            assert_eq!(le_i32(&bytes, line), y as i32);
+            let size = le_i32(&bytes, line + 4) as usize;
+            assert_eq!(size, channels.len() * n * 4);
+            for (c, row) in floats(&bytes[line + 8..line + 8 + size]).chunks(n).enumerate() {
+                values[c].extend_from_slice(row);
+            }
+        }
+        let column = |f: fn(&Fields) -> Vec<f32>| f(&fields);
+        assert_eq!(values[0], column(|f| f.dye.iter().map(|d| d[2]).collect()));
+        assert_eq!(values[1], column(|f| f.dye.iter().map(|d| d[1]).collect()));
+        assert_eq!(values[2], column(|f| f.dye.iter().map(|d| d[0]).collect()));
+        assert_eq!(values[3], fields.pressure);
+        assert_eq!(values[4], fields.temperature);
+        assert_eq!(values[5], column(|f| f.velocity.iter().map(|v| v[0]).collect()));
+        assert_eq!(values[6], column(|f| f.velocity.iter().map(|v| v[1]).collect()));
+        // The last line ends the file
+        let last = u64::from_le_bytes(bytes[at + 16..at + 24].try_into().unwrap()) as usize;
+        assert_eq!(last + 8 + channels.len() * n * 4, bytes.len());
+    }
+}
@@ -0,0 +1,351 @@
+//! Fire mode (`--fire`): flames from a simple combustion model on the 2D
+//! solver. Fuel, in a texture of its own, is carried by the flow and burns
+//! wherever the gas is above its ignition temperature, giving off heat and
+//! soot. Both of those go in the fluid's dye, temperature where it always
+//! is, so the solver's own buoyancy lifts the hot gas, vorticity
+//! confinement makes it flicker and its temperature decay cools it. The
+//! kernels and the blackbody renderer are in `fire.wgsl`.
+
+use std::sync::Arc;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    dpi::PhysicalSize,
+    event::MouseButton,
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::decay::Decay;
+use crate::mode::{self, Mode};
+use crate::scene::Boundary;
+use crate::simulation::{self, FluidPipelines, Simulation};
+use crate::{create_storage_tex, GpuEmitter, SimParams, MAX_EMITTERS};
+
+/// How fast the hot gas cools, whatever `--temperature-decay` says: the
+/// flame's height is about how far the gas rises in this long.
+const COOLING: Decay = Decay::HalfLife(0.4);
+/// Soot is a little heavier than the air around it.
+const SOOT_WEIGHT: f32 = 2.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct FireParams {
+    grid_size: u32,
+    dt: f32,
+    cell_size: [f32; 2],
+    /// Brush center and its movement this frame, in grid cells.
+    mouse_pos: [f32; 2],
+    mouse_delta: [f32; 2],
+    radius: f32,
+    /// 0 idle, 1 spraying fuel, 2 the torch.
+    mouse_mode: u32,
+    /// Nonzero while the burner along the floor is on.
+    burner: u32,
+    time: f32,
+}
+
+/// The fuel, the combustion kernels and the renderer. The flow itself is a
+/// `Simulation`'s, passed to `step`.
+struct Fire {
+    params: FireParams,
+    param_buffer: wgpu::Buffer,
+    fuel: wgpu::Texture,
+    _views: Vec<wgpu::TextureView>,
+    bind_group: wgpu::BindGroup,
+    advect: wgpu::ComputePipeline,
+    burn: wgpu::ComputePipeline,
+    render_bg: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl Fire {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sim: &Simulation, grid: u32) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("fire_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../fire.wgsl").into()),
+        });
+
+        let params = FireParams {
+            grid_size: grid, dt: 0.016, cell_size: [1.0; 2],
+            mouse_pos: [0.0; 2], mouse_delta: [0.0; 2], radius: grid as f32 * 0.04, mouse_mode: 0,
+            burner: 1, time: 0.0,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("fire_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let (fuel, fuel_view) = create_storage_tex(device, grid);
+        let (_fuel_tmp, fuel_tmp_view) = create_storage_tex(device, grid);
+
+        // ---- Compute ----
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false, min_binding_size: None,
+            },
+            count: None,
+        }];
+        entries.extend((1..=4).map(|binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::ReadWrite,
+                format: wgpu::TextureFormat::Rgba16Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        }));
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fire_compute_bgl"), entries: &entries,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fire_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&sim.velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&sim.density_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&fuel_view) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&fuel_tmp_view) },
+            ],
+        });
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl), module: &module, entry_point: entry,
+            })
+        };
+
+        // ---- Render ----
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+            },
+            count: None,
+        };
+        let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fire_render_bgl"),
+            entries: &[
+                texture_entry(5),
+                texture_entry(6),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fire_render_bg"), layout: &render_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&sim.density_view) },
+                wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&fuel_view) },
+                wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+        let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+        });
+        let render_pipeline = crate::create_render_pipeline(device, &render_pl, &module, format, "fs_fire");
+
+        Self {
+            params, param_buffer, fuel,
+            _views: vec![fuel_view, fuel_tmp_view],
+            bind_group,
+            advect: make_compute("advect_fuel"),
+            burn: make_compute("burn"),
+            render_bg, render_pipeline,
+        }
+    }
+
+    /// Move and burn the fuel, then one step of the fluid, which lifts,
+    /// carries and cools what burnt.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, sim: &'a Simulation, pipes: &'a FluidPipelines, sim_params: &SimParams) {
+        let grid = self.params.grid_size.div_ceil(8);
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.advect);
+        c.dispatch_workgroups(grid, grid, 1);
+        c.set_pipeline(&self.burn);
+        c.dispatch_workgroups(grid, grid, 1);
+        sim.step(c, pipes, sim_params);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("fire_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.render_pipeline);
+        r.set_bind_group(0, &self.render_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+
+    /// Put the fire out: no fuel, heat, soot or flow.
+    fn reset(&self, queue: &wgpu::Queue, sim: &Simulation) {
+        let grid = self.params.grid_size;
+        let empty = vec![[0.0; 4]; (grid * grid) as usize];
+        crate::upload_field(queue, &self.fuel, grid, &empty);
+        crate::upload_field(queue, &sim.velocity, grid, &empty);
+        crate::upload_field(queue, &sim.density, grid, &empty);
+    }
+}
+
+/// The fire window loop, taking over from `run` once the window and device
+/// exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let grid = config.grid_size;
+    let workarounds = crate::shader_workarounds(&config, &adapter);
+    let gpu = mode::Gpu::new(&config, window, surface, adapter, device, queue);
+    let (device, queue, format) = (&gpu.device, &gpu.queue, gpu.format());
+    let win_size = gpu.window.inner_size();
+
+    // ---- Grid ----
+    // The fluid's own step, open at the edges so the smoke can leave; the
+    // obstacle mask and emitters stay empty
+    let fluid_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute_shader"),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
+    });
+    let (_obstacle_tex, obstacle_view) = create_storage_tex(device, grid);
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let base = crate::base_params(&config, crate::cell_size_for(win_size));
+    let sim_params = SimParams {
+        boundary: Boundary::Outflow.code(),
+        dye_decay: config.dye_decay.factor(base.dt),
+        dye_threshold: config.dye_decay.threshold(),
+        temperature_decay: COOLING.factor(base.dt),
+        smoke_weight: SOOT_WEIGHT,
+        ..base
+    };
+    let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&sim_params),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let compute_bgl = crate::create_compute_bgl(device);
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let fluid_pipes = FluidPipelines::new(device, &compute_pl, &fluid_module, workarounds);
+    let shared = simulation::Shared {
+        device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    let sim = Simulation::new(&shared, &fluid_module, config.pressure_solver);
+
+    let fire = Fire::new(device, format, &sim, grid);
+    fire.reset(queue, &sim);
+    eprintln!("Fire mode: a burner on a {}² grid", grid);
+
+    let state = FireMode { fire, sim, fluid_pipes, sim_params, param_buffer, cursor: [0.0; 2], last_cursor: None };
+    mode::run(gpu, event_loop, grid, state).await;
+}
+
+/// The fire window's state between frames.
+struct FireMode {
+    fire: Fire,
+    sim: Simulation,
+    fluid_pipes: FluidPipelines,
+    sim_params: SimParams,
+    param_buffer: wgpu::Buffer,
+    cursor: [f32; 2],
+    last_cursor: Option<[f32; 2]>,
+}
+
+impl Mode for FireMode {
+    const STEP: &'static str = "fire_step";
+
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue) {
+        match code {
+            KeyCode::KeyR => {
+                self.fire.reset(queue, &self.sim);
+                eprintln!("Fire put out");
+            }
+            KeyCode::KeyB => {
+                self.fire.params.burner ^= 1;
+                eprintln!("Burner {}", if self.fire.params.burner != 0 { "on" } else { "off" });
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse(&mut self, button: MouseButton, pressed: bool) {
+        let mode = if button == MouseButton::Left { 1 } else { 2 };
+        let p = &mut self.fire.params;
+        if pressed {
+            p.mouse_mode = mode;
+        } else if p.mouse_mode == mode {
+            p.mouse_mode = 0;
+        }
+        self.last_cursor = None;
+    }
+
+    fn cursor(&mut self, cursor: [f32; 2]) {
+        self.cursor = cursor;
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.sim_params.cell_size = crate::cell_size_for(size);
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue, _aspect: f32, paused: bool) {
+        let cursor = self.cursor;
+        let p = &mut self.fire.params;
+        p.dt = self.sim_params.dt;
+        p.cell_size = self.sim_params.cell_size;
+        p.mouse_delta = self.last_cursor.map_or([0.0; 2], |last| [cursor[0] - last[0], cursor[1] - last[1]]);
+        p.mouse_pos = cursor;
+        if p.mouse_mode != 0 {
+            self.last_cursor = Some(cursor);
+        }
+        if !paused {
+            p.time += self.sim_params.dt;
+            self.sim_params.time = p.time;
+        }
+        queue.write_buffer(&self.fire.param_buffer, 0, bytemuck::bytes_of(&self.fire.params));
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.sim_params));
+        self.sim.set_domain(queue, &self.sim_params);
+    }
+
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        self.fire.step(c, &self.sim, &self.fluid_pipes, &self.sim_params);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.fire.render(encoder, view);
+    }
+}
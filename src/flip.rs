@@ -0,0 +1,427 @@
+//! FLIP/PIC liquid mode (`--flip N`): N particles carry the velocity and
+//! the grid is only used to make it incompressible. Each step the kernels
+//! in `flip.wgsl` splat the particles onto the fluid grid, the 2D solver's
+//! own projection runs on it with a free surface (cells without liquid are
+//! air at zero pressure), and the particles take back the grid's change and
+//! move. The liquid starts as a dam about to break, falls under gravity and
+//! splashes, which the grid-only solver can't do: it has no surface to
+//! splash.
+
+use std::sync::Arc;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    dpi::PhysicalSize,
+    event::MouseButton,
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::mode::{self, Mode};
+use crate::pressure::SolverKind;
+use crate::rng::Seed;
+use crate::scene::Boundary;
+use crate::simulation::{self, FluidPipelines, Simulation};
+use crate::{create_storage_tex, GpuEmitter, SimParams, MAX_EMITTERS};
+
+/// Particles seeded per liquid cell. Must match `PARTICLES_PER_CELL` in
+/// `flip.wgsl`, which reads the liquid fraction off it.
+const PARTICLES_PER_CELL: f32 = 4.0;
+/// Share of FLIP in the FLIP/PIC blend: FLIP keeps small splashes alive,
+/// a little PIC damps its particle noise.
+const FLIP_RATIO: f32 = 0.95;
+/// The free surface is only handled by the Jacobi kernels, and a liquid
+/// column needs more sweeps than the smoke does.
+const PRESSURE_ITERATIONS: u32 = 80;
+/// Gravity when `--gravity` leaves it off, in cells/s² down.
+const DEFAULT_GRAVITY: f32 = 250.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct FlipParams {
+    grid_size: u32,
+    count: u32,
+    dt: f32,
+    flip_ratio: f32,
+    cell_size: [f32; 2],
+    gravity: [f32; 2],
+    /// Brush center and its movement this frame, in grid cells.
+    mouse_pos: [f32; 2],
+    mouse_delta: [f32; 2],
+    radius: f32,
+    mouse_down: u32,
+    aspect: f32,
+    /// Sprite radius in cells.
+    sprite: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    pos: [f32; 2],
+    vel: [f32; 2],
+}
+
+/// A dam at rest against the left wall, holding `count` particles at
+/// `PARTICLES_PER_CELL`, jittered so they don't start on a lattice.
+fn dam_break(count: u32, grid: u32, seed: Seed) -> Vec<Particle> {
+    let n = grid as f32;
+    let cells = count as f32 / PARTICLES_PER_CELL;
+    let height = (cells * 1.5).sqrt().min(n * 0.9);
+    let width = (cells / height).min(n * 0.9);
+    let mut rng = seed.rng("flip-dam");
+    (0..count).map(|_| {
+        let x = 0.5 + rng.next_f32() * width;
+        let y = n - 0.5 - rng.next_f32() * height;
+        Particle { pos: [x, y], vel: [0.0; 2] }
+    }).collect()
+}
+
+fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding, visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn storage_tex_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding, visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+/// The particles, the transfer kernels and the sprite renderer. The grid
+/// itself is a `Simulation`'s, passed to `step`.
+struct Flip {
+    params: FlipParams,
+    param_buffer: wgpu::Buffer,
+    particles: wgpu::Buffer,
+    _accum: wgpu::Buffer,
+    _before: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    clear_grid: wgpu::ComputePipeline,
+    particle_to_grid: wgpu::ComputePipeline,
+    grid_normalize: wgpu::ComputePipeline,
+    grid_to_particle: wgpu::ComputePipeline,
+    sprite_pipeline: wgpu::RenderPipeline,
+    sprite_bg: wgpu::BindGroup,
+    /// The dam's jitter is drawn from this on every reset.
+    seed: Seed,
+}
+
+impl Flip {
+    fn new(
+        device: &wgpu::Device, format: wgpu::TextureFormat, sim: &Simulation, grid: u32, count: u32, gravity: [f32; 2], seed: Seed,
+    ) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("flip_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../flip.wgsl").into()),
+        });
+
+        let params = FlipParams {
+            grid_size: grid, count, dt: 0.016, flip_ratio: FLIP_RATIO, cell_size: [1.0; 2], gravity,
+            mouse_pos: [0.0; 2], mouse_delta: [0.0; 2], radius: grid as f32 * 0.06, mouse_down: 0,
+            aspect: 1.0, sprite: 0.6,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("flip_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let particles = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("flip_particles"),
+            contents: bytemuck::cast_slice(&dam_break(count, grid, seed)),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let cells = grid as u64 * grid as u64;
+        let accum = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("flip_accum"), size: cells * 3 * 4,
+            usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false,
+        });
+        let before = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("flip_before"), size: cells * 8,
+            usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false,
+        });
+
+        // ---- Compute ----
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("flip_compute_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+                storage_entry(2),
+                storage_tex_entry(3),
+                storage_tex_entry(4),
+                storage_entry(5),
+            ],
+        });
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl), module: &module, entry_point: entry,
+            })
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("flip_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: particles.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: accum.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&sim.velocity_view) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&sim.density_view) },
+                wgpu::BindGroupEntry { binding: 5, resource: before.as_entire_binding() },
+            ],
+        });
+
+        // ---- Render ----
+        let sprite_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("flip_sprite_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false, min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let sprite_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("flip_sprite_bg"), layout: &sprite_bgl,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() }],
+        });
+        let sprite_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&sprite_bgl], push_constant_ranges: &[],
+        });
+        // One instance per particle, read straight from the storage buffer
+        let sprite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fs_sprite"), layout: Some(&sprite_pl),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_sprite",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Particle>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_sprite",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format, blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            params, param_buffer, particles, _accum: accum, _before: before, bind_group,
+            clear_grid: make_compute("clear_grid"),
+            particle_to_grid: make_compute("particle_to_grid"),
+            grid_normalize: make_compute("grid_normalize"),
+            grid_to_particle: make_compute("grid_to_particle"),
+            sprite_pipeline, sprite_bg, seed,
+        }
+    }
+
+    /// Particles to grid, the fluid projection, grid to particles.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, sim: &'a Simulation, pipes: &'a FluidPipelines) {
+        let grid = self.params.grid_size.div_ceil(8);
+        let particles = self.params.count.div_ceil(64);
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.clear_grid);
+        c.dispatch_workgroups(grid, grid, 1);
+        c.set_pipeline(&self.particle_to_grid);
+        c.dispatch_workgroups(particles, 1, 1);
+        c.set_pipeline(&self.grid_normalize);
+        c.dispatch_workgroups(grid, grid, 1);
+        sim.project(c, pipes);
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.grid_to_particle);
+        c.dispatch_workgroups(particles, 1, 1);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("flip_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.02, g: 0.02, b: 0.03, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.sprite_pipeline);
+        r.set_bind_group(0, &self.sprite_bg, &[]);
+        r.set_vertex_buffer(0, self.particles.slice(..));
+        r.draw(0..6, 0..self.params.count);
+    }
+
+    /// Put the dam back up.
+    fn reset(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.particles, 0, bytemuck::cast_slice(&dam_break(self.params.count, self.params.grid_size, self.seed)));
+    }
+}
+
+/// The FLIP window loop, taking over from `run` once the window and device
+/// exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let count = config.flip.unwrap_or(32768);
+    let grid = config.grid_size;
+    let workarounds = crate::shader_workarounds(&config, &adapter);
+    let gpu = mode::Gpu::new(&config, window, surface, adapter, device, queue);
+    let (device, format) = (&gpu.device, gpu.format());
+    let win_size = gpu.window.inner_size();
+
+    // ---- Grid ----
+    // The fluid's own bind group and projection; the obstacle mask and
+    // emitters stay empty
+    let fluid_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute_shader"),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
+    });
+    let (_obstacle_tex, obstacle_view) = create_storage_tex(device, grid);
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let sim_params = SimParams {
+        liquid: 1, boundary: Boundary::Walls.code(), cell_size: crate::cell_size_for(win_size),
+        gravity: if config.gravity == [0.0, 0.0] { [0.0, DEFAULT_GRAVITY] } else { config.gravity },
+        gravity_by_dye: 0,
+        ..crate::base_params(&config, crate::cell_size_for(win_size))
+    };
+    let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&sim_params),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let compute_bgl = crate::create_compute_bgl(device);
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let fluid_pipes = FluidPipelines::new(device, &compute_pl, &fluid_module, workarounds);
+    let shared = simulation::Shared {
+        device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    if !matches!(config.pressure_solver, SolverKind::Jacobi { .. }) {
+        eprintln!("FLIP mode projects with Jacobi: the free surface isn't supported by the other solvers");
+    }
+    let sim = Simulation::new(&shared, &fluid_module, SolverKind::Jacobi { iterations: PRESSURE_ITERATIONS, half_res: false });
+
+    let flip = Flip::new(device, format, &sim, grid, count, sim_params.gravity, config.seed);
+    eprintln!("FLIP mode: {} particles on a {}² grid", count, grid);
+
+    let state = FlipMode {
+        flip, sim, fluid_pipes, param_buffer, stored_gravity: sim_params.gravity, sim_params,
+        cursor: [0.0; 2], last_cursor: None,
+    };
+    mode::run(gpu, event_loop, grid, state).await;
+}
+
+/// The FLIP window's state between frames.
+struct FlipMode {
+    flip: Flip,
+    sim: Simulation,
+    fluid_pipes: FluidPipelines,
+    sim_params: SimParams,
+    param_buffer: wgpu::Buffer,
+    cursor: [f32; 2],
+    last_cursor: Option<[f32; 2]>,
+    stored_gravity: [f32; 2],
+}
+
+impl Mode for FlipMode {
+    const STEP: &'static str = "flip_step";
+
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue) {
+        match code {
+            KeyCode::KeyR => {
+                self.flip.reset(queue);
+                eprintln!("Dam reset");
+            }
+            KeyCode::KeyG | KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::ArrowLeft | KeyCode::ArrowRight
+            | KeyCode::Minus | KeyCode::Equal => {
+                // The liquid is all there is, so Shift + G has nothing to switch
+                crate::adjust_gravity(code, false, &mut self.sim_params, &mut self.stored_gravity);
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse(&mut self, button: MouseButton, pressed: bool) {
+        if button == MouseButton::Left {
+            self.flip.params.mouse_down = pressed as u32;
+            self.last_cursor = None;
+        }
+    }
+
+    fn cursor(&mut self, cursor: [f32; 2]) {
+        self.cursor = cursor;
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.sim_params.cell_size = crate::cell_size_for(size);
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue, aspect: f32, _paused: bool) {
+        let cursor = self.cursor;
+        let p = &mut self.flip.params;
+        p.cell_size = self.sim_params.cell_size;
+        p.gravity = self.sim_params.gravity;
+        p.aspect = aspect;
+        p.mouse_delta = self.last_cursor.map_or([0.0; 2], |last| [cursor[0] - last[0], cursor[1] - last[1]]);
+        p.mouse_pos = cursor;
+        if p.mouse_down != 0 {
+            self.last_cursor = Some(cursor);
+        }
+        queue.write_buffer(&self.flip.param_buffer, 0, bytemuck::bytes_of(&self.flip.params));
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.sim_params));
+    }
+
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        self.flip.step(c, &self.sim, &self.fluid_pipes);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.flip.render(encoder, view);
+    }
+}
@@ -0,0 +1,168 @@
+//! Copying the window's frames out of the swapchain without waiting, for
+//! screenshots, video recording and GIF capture. A `CaptureRing` copies a
+//! frame into one of a few readbacks of its size, made as they're needed,
+//! and hands the mapped frames back in the order they were taken once the
+//! GPU is done with them. While every readback is busy, frames aren't
+//! taken, so a slow consumer costs frames rather than stalling the
+//! simulation.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::readback::Readback;
+
+pub struct CaptureRing {
+    label: &'static str,
+    /// Readbacks at most.
+    size: usize,
+    width: u32,
+    height: u32,
+    /// Bytes per row in the readbacks, padded to the copy alignment.
+    padded: u32,
+    bgra: bool,
+    /// Copied into by an encoder that isn't submitted yet. Frames are
+    /// tagged with the time they were given when taken.
+    recorded: Option<Readback<Duration>>,
+    in_flight: VecDeque<Readback<Duration>>,
+    free: Vec<Readback<Duration>>,
+    /// Readbacks made so far, up to `size`.
+    made: usize,
+}
+
+/// A frame the GPU is done copying.
+pub struct Frame<'a> {
+    texels: &'a [u8],
+    width: u32,
+    height: u32,
+    padded: u32,
+    bgra: bool,
+    /// The time it was given when taken.
+    pub at: Duration,
+}
+
+impl CaptureRing {
+    /// A ring of up to `size` readbacks for `width` × `height` frames of
+    /// `format`, or `None` if that isn't 8-bit RGBA or BGRA.
+    pub fn new(label: &'static str, size: usize, format: wgpu::TextureFormat, width: u32, height: u32) -> Option<Self> {
+        let bgra = match format.remove_srgb_suffix() {
+            wgpu::TextureFormat::Rgba8Unorm => false,
+            wgpu::TextureFormat::Bgra8Unorm => true,
+            _ => return None,
+        };
+        Some(Self {
+            label, size, width, height,
+            padded: (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT,
+            bgra, recorded: None, in_flight: VecDeque::new(), free: Vec::new(), made: 0,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Whether the frames' bytes are in BGRA order rather than RGBA.
+    pub fn bgra(&self) -> bool {
+        self.bgra
+    }
+
+    /// No frame is being copied or mapped.
+    pub fn is_idle(&self) -> bool {
+        self.recorded.is_none() && self.in_flight.is_empty()
+    }
+
+    /// Copy the finished frame in `texture`, tagged `at`, unless every
+    /// readback is still in use. Returns whether it was taken. Call
+    /// `submitted` once the encoder is submitted.
+    pub fn record(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture, at: Duration) -> bool {
+        let readback = match self.free.pop() {
+            Some(readback) => readback,
+            None if self.made < self.size => {
+                self.made += 1;
+                Readback::new(device, self.label, (self.padded * self.height) as u64)
+            }
+            None => return false,
+        };
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: readback.buffer(),
+                layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(self.padded), rows_per_image: Some(self.height) },
+            },
+            texture.size(),
+        );
+        readback.recorded(at);
+        self.recorded = Some(readback);
+        true
+    }
+
+    pub fn submitted(&mut self) {
+        if let Some(readback) = self.recorded.take() {
+            readback.submitted();
+            self.in_flight.push_back(readback);
+        }
+    }
+
+    /// Hand the frames the GPU is done with to `take`, oldest first,
+    /// without waiting, or with `wait` after waiting for all of them.
+    /// Frames a lost device never delivers are skipped.
+    pub fn collect(&mut self, device: &wgpu::Device, wait: bool, mut take: impl FnMut(&Frame)) {
+        let (width, height, padded, bgra) = (self.width, self.height, self.padded, self.bgra);
+        while let Some(readback) = self.in_flight.front() {
+            let done = readback.collect(device, wait, |at, texels| take(&Frame { texels, width, height, padded, bgra, at }));
+            if done.is_none() && !readback.is_free() {
+                return;
+            }
+            let readback = self.in_flight.pop_front().unwrap();
+            if done.is_some() {
+                self.free.push(readback);
+            }
+        }
+    }
+}
+
+impl Frame<'_> {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Row `y` (0 at the top) as the swapchain stores it, RGBA or BGRA.
+    pub fn row(&self, y: u32) -> &[u8] {
+        let start = (y * self.padded) as usize;
+        &self.texels[start..start + self.width as usize * 4]
+    }
+
+    /// The rows, top first, as the swapchain stores them.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    /// The pixel at (`x`, `y`) as RGB: what the window shows, since an sRGB
+    /// swapchain stores its bytes already encoded.
+    pub fn rgb(&self, x: u32, y: u32) -> [u8; 3] {
+        let t = &self.row(y)[x as usize * 4..x as usize * 4 + 3];
+        if self.bgra { [t[2], t[1], t[0]] } else { [t[0], t[1], t[2]] }
+    }
+
+    /// The whole frame as packed RGB rows, top row first.
+    pub fn rgb_rows(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.width as usize * self.height as usize * 3);
+        for row in self.rows() {
+            for texel in row.chunks_exact(4) {
+                if self.bgra {
+                    rgb.extend_from_slice(&[texel[2], texel[1], texel[0]]);
+                } else {
+                    rgb.extend_from_slice(&texel[..3]);
+                }
+            }
+        }
+        rgb
+    }
+}
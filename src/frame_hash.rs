@@ -0,0 +1,56 @@
+//! Frame hashes for cross-platform consistency checks (`--hash-frames`).
+//! Every N simulation steps the dye/temperature texture is read back and
+//! hashed (64-bit FNV-1a over the texel values), so runs on different
+//! GPUs, drivers and backends can be compared by swapping a few lines of
+//! text instead of whole snapshots.
+//!
+//! Hashes only match between runs that see the same input: start from the
+//! same scene or snapshot at the same `--grid` and `--solver`, and don't
+//! touch the mouse. The step count, not wall time, decides when to hash.
+
+use std::fs::File;
+use std::io::{self, Write};
+
+pub struct FrameHasher {
+    every: u64,
+    out: Option<File>,
+}
+
+impl FrameHasher {
+    /// Hash every `every` steps, also appending to `path` (created, with
+    /// `header` as its first line) if given.
+    pub fn new(every: u64, path: Option<&str>, header: &str) -> io::Result<Self> {
+        let out = match path {
+            Some(path) => {
+                let mut file = File::create(path)?;
+                writeln!(file, "# {}", header)?;
+                Some(file)
+            }
+            None => None,
+        };
+        eprintln!("Hashing the dye field every {} steps ({})", every, header);
+        Ok(Self { every, out })
+    }
+
+    /// Call after `step` has been submitted; reads the field back (blocking)
+    /// when it's due.
+    pub fn after_step(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, density: &wgpu::Texture, grid: u32, step: u64) {
+        if !step.is_multiple_of(self.every) {
+            return;
+        }
+        let texels = crate::read_field(device, queue, density, grid);
+        let hash = fnv1a(texels.iter().flatten().flat_map(|v| v.to_le_bytes()));
+        let line = format!("step {} density {:016x}", step, hash);
+        eprintln!("{}", line);
+        if let Some(file) = &mut self.out {
+            if let Err(e) = writeln!(file, "{}", line) {
+                eprintln!("Failed to write frame hash: {}", e);
+                self.out = None;
+            }
+        }
+    }
+}
+
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
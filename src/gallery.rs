@@ -0,0 +1,310 @@
+//! Scene gallery (`--thumbnails DIR`): renders every builtin scene headlessly
+//! into DIR, one `<scene>.png` each, then exits. Each scene runs from its own
+//! start for `SECONDS` of simulated time with nobody touching it, then is
+//! drawn as the window would draw it, particle trails and all.
+//! `--thumbnails-html` also writes an `index.html` that lays them out with
+//! each scene's description and the command that loads it.
+//!
+//! The PNGs are written by `png::encode`, uncompressed (stored deflate
+//! blocks), which is fine for a few hundred kilobytes of thumbnails.
+
+use std::sync::{Arc, Mutex};
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::cfl::Cfl;
+use crate::config::Config;
+use crate::detail::Detail;
+use crate::gpu_error::{self, ErrorLog};
+use crate::particles::Particles;
+use crate::scene::{self, Scene};
+use crate::simulation::{self, DiffField, FieldView, FluidPipelines, Simulation};
+use crate::{create_storage_tex, GpuEmitter, RenderBindings, ViewParams, MAX_EMITTERS};
+
+/// Thumbnail width and height in pixels.
+const SIZE: u32 = 256;
+/// Simulated seconds each scene runs before it's drawn: long enough for
+/// emitters to fill in and blobs to start curling.
+const SECONDS: f32 = 4.0;
+/// The swapchain's usual format, so `fs_draw`'s output is encoded as on
+/// screen and the bytes can go straight into the PNG.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+pub async fn run(config: Config, dir: &str) {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        eprintln!("Can't create {}: {}", dir, e);
+        std::process::exit(1);
+    }
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: config.backends,
+        ..Default::default()
+    });
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: config.power_preference,
+        force_fallback_adapter: false,
+        compatible_surface: None,
+    })
+    .await
+    .expect("No suitable GPU adapter found");
+
+    eprintln!("GPU: {}", adapter.get_info().name);
+    eprintln!("Backend: {:?}", adapter.get_info().backend);
+
+    let (device, queue) = crate::request_device(&adapter).await;
+    let workarounds = crate::shader_workarounds(&config, &adapter);
+    let error_log = Arc::new(Mutex::new(ErrorLog::default()));
+    gpu_error::install_uncaptured_handler(&device, &error_log);
+    gpu_error::push_scopes(&device);
+
+    let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute_shader"),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
+    });
+    let render_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("render_shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../render.wgsl").into()),
+    });
+
+    let grid = config.grid_size;
+    let (obstacle_tex, obstacle_view) = create_storage_tex(&device, grid);
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let size = winit::dpi::PhysicalSize::new(SIZE, SIZE);
+    let base_params = crate::base_params(&config, crate::cell_size_for(size));
+    let base_decays = (config.dye_decay, config.velocity_decay, config.temperature_decay);
+    let mut sim_params = base_params;
+    let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&sim_params),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let compute_bgl = crate::create_compute_bgl(&device);
+    let render_bgl = crate::create_render_bgl(&device);
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+    });
+    let fluid_pipes = FluidPipelines::new(&device, &compute_pl, &compute_shader, workarounds);
+    let render_pipeline = crate::create_render_pipeline(&device, &render_pl, &render_shader, FORMAT, "fs_draw");
+
+    let shared = simulation::Shared {
+        device: &device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    let mut sim = Simulation::new(&shared, &compute_shader, config.pressure_solver);
+    sim.advection = config.advection;
+    eprintln!("Pressure solver: {}", sim.solver.name());
+    let mut cfl = config.cfl.map(|limit| Cfl::new(&device, &sim.velocity_view, grid, limit));
+    let mut detail = Detail::new(&device, &queue, &sim.velocity_view, grid, size);
+
+    let lut = crate::colormap::Lut::new(&device, &queue);
+    let (colormap, _) = crate::colormap::startup(&lut, &queue, config.colormap, config.colormap_file.as_deref());
+    let mut view_params = ViewParams {
+        viewport: [0.0, 0.0, 1.0, 1.0], diff_field: DiffField::Dye as u32, diff_scale: DiffField::Dye.default_scale(),
+        time: 0.0, detail: config.detail, wall_shear: config.wall_shear.unwrap_or(0.0),
+        field: FieldView::Dye as u32, field_scale: 1.0, hdr: 0, colormap: colormap as u32, dither: 0, shading: 0, light_angle: 0.0,
+    };
+    let render_bindings = RenderBindings {
+        layout: render_bgl,
+        sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        }),
+        view: device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("view_params"),
+            contents: bytemuck::bytes_of(&view_params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        }),
+        lut,
+        background: crate::glass::Background::new(&device, &queue, None),
+    };
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("thumbnail"),
+        size: wgpu::Extent3d { width: SIZE, height: SIZE, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let target_view = target.create_view(&Default::default());
+
+    if let Some(err) = gpu_error::pop_scopes(&device, "setup").await {
+        let info = adapter.get_info();
+        eprintln!("{}\n(GPU: {}, backend: {:?})", err, info.name, info.backend);
+        std::process::exit(1);
+    }
+
+    let substeps = config.substeps;
+    let mut scenes = Vec::new();
+    for (name, _) in scene::BUILTIN {
+        let mut scene = Scene::load(name).expect("builtin scenes are valid");
+        if config.init_noise.is_some() {
+            scene.noise = config.init_noise;
+        }
+        // Fresh particles, so no trails carry over from the last scene
+        let mut particles = Particles::new(&device, &sim.velocity_view, &sim.density_view, &obstacle_view, grid, config.seed, FORMAT);
+        particles.set_cell_size(sim_params.cell_size);
+        let targets = crate::scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed);
+        crate::apply_scene(&queue, &scene, None, &targets, &mut sim_params, &base_params, base_decays);
+        let render_bg = render_bindings.create(&device, &obstacle_view, particles.trail_view(), detail.view(), &sim, sim.fields());
+
+        gpu_error::push_scopes(&device);
+        while sim_params.time < SECONDS {
+            let mut step_params = crate::substep_params(&sim_params, substeps);
+            if let Some(cfl) = &mut cfl {
+                step_params = cfl.apply(&device, &step_params);
+            }
+            sim_params.time += step_params.dt * substeps as f32;
+            step_params.time = sim_params.time;
+            if scene.has_moving_obstacles() {
+                crate::upload_obstacles(&queue, &scene, &obstacle_tex, grid, sim_params.time);
+            }
+            queue.write_buffer(&param_buffer, 0, bytemuck::bytes_of(&step_params));
+            sim.set_domain(&queue, &sim_params);
+            particles.update(&queue, step_params.dt * substeps as f32);
+            detail.update(&queue, step_params.dt * substeps as f32, sim_params.time, sim_params.cell_size);
+
+            let mut encoder = device.create_command_encoder(&Default::default());
+            {
+                let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("sim"), timestamp_writes: None });
+                for _ in 0..substeps {
+                    sim.step(&mut c, &fluid_pipes, &step_params);
+                }
+                particles.dispatch(&mut c);
+                if view_params.detail > 0.0 {
+                    detail.dispatch(&mut c);
+                }
+            }
+            if let Some(cfl) = &mut cfl {
+                cfl.record(&mut encoder);
+            }
+            queue.submit(Some(encoder.finish()));
+            if let Some(cfl) = &mut cfl {
+                cfl.submitted();
+            }
+        }
+
+        view_params.time = sim_params.time;
+        queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("thumbnail"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target_view, resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            r.set_pipeline(&render_pipeline);
+            r.set_bind_group(0, &render_bg, &[]);
+            r.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+        let rgb = read_rgb(&device, &queue, &target);
+        if let Some(err) = gpu_error::pop_scopes(&device, "thumbnail").await {
+            eprintln!("{}: {}", name, err);
+            std::process::exit(1);
+        }
+
+        let path = format!("{}/{}.png", dir, name);
+        if let Err(e) = std::fs::write(&path, crate::png::encode(SIZE, SIZE, &rgb)) {
+            eprintln!("Failed to save {}: {}", path, e);
+            std::process::exit(1);
+        }
+        eprintln!("Saved {}", path);
+        scenes.push((name, scene.description));
+    }
+
+    if config.thumbnails_html {
+        let path = format!("{}/index.html", dir);
+        match std::fs::write(&path, index_html(&scenes)) {
+            Ok(()) => eprintln!("Saved {}", path),
+            Err(e) => {
+                eprintln!("Failed to save {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// `texture`'s pixels as packed RGB rows, top row first. Waits for the GPU.
+pub fn read_rgb(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Vec<u8> {
+    let (width, height) = (texture.width(), texture.height());
+    let row_bytes = width * 4;
+    let padded = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("thumbnail_readback"),
+        size: (padded * height) as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("thumbnail_readback") });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(padded), rows_per_image: Some(height) },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+    for row in slice.get_mapped_range().chunks(padded as usize) {
+        for texel in row[..row_bytes as usize].chunks_exact(4) {
+            rgb.extend_from_slice(&texel[..3]);
+        }
+    }
+    buffer.unmap();
+    rgb
+}
+
+/// A page showing every thumbnail, given as (scene name, description), with
+/// the command that loads it.
+fn index_html(scenes: &[(&str, String)]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>wgpu-fluid scenes</title>\n<style>\n\
+         body { background: #111; color: #ddd; font-family: sans-serif; margin: 2em; }\n\
+         main { display: grid; grid-template-columns: repeat(auto-fill, minmax(256px, 1fr)); gap: 1.5em; }\n\
+         img { width: 100%; border-radius: 4px; }\n\
+         code { color: #9cf; }\n\
+         </style>\n</head>\n<body>\n<h1>wgpu-fluid scenes</h1>\n<main>\n",
+    );
+    for (name, description) in scenes {
+        let name = escape_html(name);
+        html.push_str(&format!(
+            "<figure>\n<img src=\"{0}.png\" alt=\"{0}\">\n<figcaption><strong>{0}</strong><br>{1}<br><code>cargo run -- --scene {0}</code></figcaption>\n</figure>\n",
+            name, escape_html(description),
+        ));
+    }
+    html.push_str("</main>\n</body>\n</html>\n");
+    html
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,356 @@
+//! A small animated GIF writer for F11 captures, so no image crate is
+//! needed. All frames share one palette, picked by median cut over every
+//! frame's colors, so nothing shimmers from frame to frame. After the
+//! first, each frame only stores the rectangle that changed, with the
+//! pixels inside it that didn't change left transparent, which the LZW
+//! coding squeezes down to almost nothing where the flow is calm. Frames
+//! that didn't change at all are dropped and their time given to the one
+//! before. No dithering: smooth gradients band a little.
+
+/// One frame: packed RGB rows, top row first, and how long it shows.
+pub struct Frame {
+    pub rgb: Vec<u8>,
+    /// In hundredths of a second, GIF's unit.
+    pub delay: u16,
+}
+
+/// Palette index left for "unchanged from the last frame".
+const TRANSPARENT: u8 = 255;
+/// Bits kept per channel when counting colors.
+const HIST_BITS: u32 = 5;
+
+/// A looping GIF of `frames`, each `width` × `height`.
+pub fn encode(width: u16, height: u16, frames: &[Frame]) -> Vec<u8> {
+    let palette = median_cut(frames.iter().map(|f| f.rgb.as_slice()), TRANSPARENT as usize);
+    let lookup = nearest_lookup(&palette);
+    let (w, h) = (width as usize, height as usize);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"GIF89a");
+    out.extend_from_slice(&width.to_le_bytes());
+    out.extend_from_slice(&height.to_le_bytes());
+    // Global color table of 256 entries, 8 bits per primary
+    out.extend_from_slice(&[0xF7, 0, 0]);
+    for i in 0..256 {
+        out.extend_from_slice(&palette.get(i).copied().unwrap_or([0; 3]));
+    }
+    // Loop forever
+    out.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+    out.extend_from_slice(b"NETSCAPE2.0");
+    out.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    // What's on screen after each frame so far, and the frames to write:
+    // their rectangle, its indices and the delay
+    let mut canvas: Vec<u8> = Vec::new();
+    let mut pieces: Vec<([usize; 4], Vec<u8>, u16)> = Vec::new();
+    for frame in frames {
+        let indices: Vec<u8> = frame.rgb.chunks_exact(3).map(|p| lookup[histogram_bin(p)]).collect();
+        if canvas.is_empty() {
+            pieces.push(([0, 0, w, h], indices.clone(), frame.delay));
+            canvas = indices;
+            continue;
+        }
+        let changed = |i: usize| indices[i] != canvas[i];
+        let rows: Vec<usize> = (0..h).filter(|&y| (0..w).any(|x| changed(y * w + x))).collect();
+        let (Some(&top), Some(&bottom)) = (rows.first(), rows.last()) else {
+            let last = pieces.last_mut().unwrap();
+            last.2 = last.2.saturating_add(frame.delay);
+            continue;
+        };
+        let column_changed = |x: usize| (top..=bottom).any(|y| changed(y * w + x));
+        let left = (0..w).find(|&x| column_changed(x)).unwrap();
+        let right = (0..w).rev().find(|&x| column_changed(x)).unwrap();
+        let mut rect = Vec::with_capacity((right - left + 1) * (bottom - top + 1));
+        for y in top..=bottom {
+            for x in left..=right {
+                let i = y * w + x;
+                rect.push(if changed(i) { indices[i] } else { TRANSPARENT });
+            }
+        }
+        pieces.push(([left, top, right - left + 1, bottom - top + 1], rect, frame.delay));
+        canvas = indices;
+    }
+
+    for ([x, y, pw, ph], indices, delay) in pieces {
+        // Graphic control: leave the frame in place, transparent index set
+        out.extend_from_slice(&[0x21, 0xF9, 0x04, 0x05]);
+        out.extend_from_slice(&delay.to_le_bytes());
+        out.extend_from_slice(&[TRANSPARENT, 0x00]);
+        out.push(0x2C);
+        for v in [x, y, pw, ph] {
+            out.extend_from_slice(&(v as u16).to_le_bytes());
+        }
+        out.push(0x00);
+        lzw(&indices, &mut out);
+    }
+    out.push(0x3B);
+    out
+}
+
+fn histogram_bin(p: &[u8]) -> usize {
+    let shift = 8 - HIST_BITS;
+    ((p[0] as usize >> shift) << (2 * HIST_BITS)) | ((p[1] as usize >> shift) << HIST_BITS) | (p[2] as usize >> shift)
+}
+
+/// Up to `colors` colors for `images`: the histogram's occupied bins are
+/// split at the median of their widest channel, box with the widest range
+/// first, and each box is represented by its pixels' mean.
+fn median_cut<'a>(images: impl Iterator<Item = &'a [u8]>, colors: usize) -> Vec<[u8; 3]> {
+    let bins = 1 << (3 * HIST_BITS);
+    let mut count = vec![0u64; bins];
+    let mut sum = vec![[0u64; 3]; bins];
+    for rgb in images {
+        for p in rgb.chunks_exact(3) {
+            let b = histogram_bin(p);
+            count[b] += 1;
+            for c in 0..3 {
+                sum[b][c] += p[c] as u64;
+            }
+        }
+    }
+    // Each occupied bin by its mean color
+    let mean = |b: usize| std::array::from_fn::<u8, 3, _>(|c| (sum[b][c] / count[b]) as u8);
+    let mut boxes: Vec<Vec<usize>> = vec![(0..bins).filter(|&b| count[b] > 0).collect()];
+    let range = |bins: &[usize]| {
+        let mut span = [(255u8, 0u8); 3];
+        for &b in bins {
+            let m = mean(b);
+            for c in 0..3 {
+                span[c] = (span[c].0.min(m[c]), span[c].1.max(m[c]));
+            }
+        }
+        let widths = span.map(|(lo, hi)| hi.saturating_sub(lo));
+        let channel = (0..3).max_by_key(|&c| widths[c]).unwrap();
+        (widths[channel], channel)
+    };
+    while boxes.len() < colors {
+        let Some((i, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, range(b)))
+            .max_by_key(|&(i, (width, _))| width as u64 * boxes[i].iter().map(|&b| count[b]).sum::<u64>())
+            .map(|(i, (_, channel))| (i, channel))
+        else {
+            break;
+        };
+        let mut split = std::mem::take(&mut boxes[i]);
+        split.sort_by_key(|&b| mean(b)[channel]);
+        let half: u64 = split.iter().map(|&b| count[b]).sum::<u64>() / 2;
+        let mut seen = 0;
+        let at = split.iter().position(|&b| {
+            seen += count[b];
+            seen > half
+        });
+        let at = at.unwrap_or(0).clamp(1, split.len() - 1);
+        boxes[i] = split.split_off(at);
+        boxes.push(split);
+    }
+    boxes
+        .iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| {
+            let n: u64 = b.iter().map(|&i| count[i]).sum();
+            std::array::from_fn(|c| (b.iter().map(|&i| sum[i][c]).sum::<u64>() / n) as u8)
+        })
+        .collect()
+}
+
+/// The nearest palette index for every histogram bin's center.
+fn nearest_lookup(palette: &[[u8; 3]]) -> Vec<u8> {
+    let shift = 8 - HIST_BITS;
+    let mask = (1 << HIST_BITS) - 1;
+    (0..1usize << (3 * HIST_BITS))
+        .map(|b| {
+            let center = [b >> (2 * HIST_BITS), b >> HIST_BITS, b].map(|v| ((v & mask) << shift | (1 << (shift - 1))) as i32);
+            (0..palette.len())
+                .min_by_key(|&i| (0..3).map(|c| (center[c] - palette[i][c] as i32).pow(2)).sum::<i32>())
+                .unwrap_or(0) as u8
+        })
+        .collect()
+}
+
+/// Image data: LZW with 8-bit minimum codes, in sub-blocks of up to 255
+/// bytes.
+fn lzw(indices: &[u8], out: &mut Vec<u8>) {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+    const MAX_CODES: u16 = 4096;
+    let mut data = Vec::new();
+    let (mut acc, mut acc_bits) = (0u32, 0u32);
+    let mut write = |code: u16, width: u32, data: &mut Vec<u8>| {
+        acc |= (code as u32) << acc_bits;
+        acc_bits += width;
+        while acc_bits >= 8 {
+            data.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+    };
+
+    // The code for each (prefix code, next index), 0 for none yet
+    let mut table = vec![0u16; MAX_CODES as usize * 256];
+    let mut next = END + 1;
+    let mut width = 9;
+    write(CLEAR, width, &mut data);
+    let mut prefix = indices[0] as u16;
+    for &k in &indices[1..] {
+        let slot = prefix as usize * 256 + k as usize;
+        if table[slot] != 0 {
+            prefix = table[slot];
+            continue;
+        }
+        write(prefix, width, &mut data);
+        if next < MAX_CODES {
+            table[slot] = next;
+            if next == 1 << width {
+                width += 1;
+            }
+            next += 1;
+        } else {
+            write(CLEAR, width, &mut data);
+            table.fill(0);
+            next = END + 1;
+            width = 9;
+        }
+        prefix = k as u16;
+    }
+    write(prefix, width, &mut data);
+    write(END, width, &mut data);
+    if acc_bits > 0 {
+        data.push(acc as u8);
+    }
+
+    out.push(8);
+    for block in data.chunks(255) {
+        out.push(block.len() as u8);
+        out.extend_from_slice(block);
+    }
+    out.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The codes and indices in `lzw`'s output, and the widest code read.
+    fn unlzw(encoded: &[u8]) -> (Vec<u8>, u32, usize) {
+        assert_eq!(encoded[0], 8, "minimum code size");
+        let mut data = Vec::new();
+        let mut at = 1;
+        while encoded[at] != 0 {
+            let len = encoded[at] as usize;
+            data.extend_from_slice(&encoded[at + 1..at + 1 + len]);
+            at += 1 + len;
+        }
+        assert_eq!(at + 1, encoded.len(), "data after the terminator");
+
+        let (mut bit, mut width, mut widest, mut clears) = (0usize, 9u32, 9u32, 0usize);
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut prev: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        loop {
+            let mut code = 0usize;
+            for i in 0..width as usize {
+                let b = bit + i;
+                code |= ((data[b / 8] >> (b % 8)) as usize & 1) << i;
+            }
+            bit += width as usize;
+            widest = widest.max(width);
+            match code {
+                256 => {
+                    table = (0..=255u8).map(|i| vec![i]).chain([vec![], vec![]]).collect();
+                    width = 9;
+                    prev = None;
+                    clears += 1;
+                    continue;
+                }
+                257 => break,
+                _ => {}
+            }
+            let entry = match (&prev, table.get(code)) {
+                (_, Some(entry)) => entry.clone(),
+                (Some(p), None) if code == table.len() => [p.as_slice(), &p[..1]].concat(),
+                _ => panic!("code {} before it was defined", code),
+            };
+            out.extend_from_slice(&entry);
+            if let Some(p) = prev.take() {
+                if table.len() < 4096 {
+                    table.push([p.as_slice(), &entry[..1]].concat());
+                    if table.len() == 1 << width && width < 12 {
+                        width += 1;
+                    }
+                }
+            }
+            prev = Some(entry);
+        }
+        (out, widest, clears)
+    }
+
+    #[test]
+    fn lzw_round_trips_through_a_full_table() {
+        let mut seed = 1u32;
+        let indices: Vec<u8> = (0..60_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 24) as u8 % 24
+            })
+            .collect();
+        let mut encoded = Vec::new();
+        lzw(&indices, &mut encoded);
+        let (decoded, widest, clears) = unlzw(&encoded);
+        assert_eq!(decoded, indices);
+        assert_eq!(widest, 12, "codes grow to 12 bits");
+        assert!(clears >= 2, "the table is cleared once it's full, not only at the start");
+    }
+
+    #[test]
+    fn lzw_round_trips_a_single_index() {
+        let mut encoded = Vec::new();
+        lzw(&[7], &mut encoded);
+        assert_eq!(unlzw(&encoded), (vec![7], 9, 1));
+    }
+
+    #[test]
+    fn frames_decode_to_their_colors() {
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        let (w, h) = (8u16, 6u16);
+        let first: Vec<u8> = (0..w as usize * h as usize).flat_map(|i| colors[i % 4]).collect();
+        let mut second = first.clone();
+        // One pixel at (5, 2) changes
+        second[(2 * 8 + 5) * 3..(2 * 8 + 5) * 3 + 3].copy_from_slice(&colors[3]);
+        let frames = [Frame { rgb: first.clone(), delay: 4 }, Frame { rgb: second, delay: 4 }];
+        let gif = encode(w, h, &frames);
+
+        assert_eq!(&gif[..6], b"GIF89a");
+        let palette = &gif[13..13 + 768];
+        // Skip the looping extension
+        let mut at = 13 + 768 + 19;
+        let mut images = Vec::new();
+        while gif[at] == 0x21 {
+            assert_eq!(&gif[at + 1..at + 3], &[0xF9, 0x04]);
+            at += 8;
+            assert_eq!(gif[at], 0x2C);
+            let field = |i: usize| u16::from_le_bytes([gif[at + 1 + 2 * i], gif[at + 2 + 2 * i]]);
+            let rect = [field(0), field(1), field(2), field(3)];
+            at += 10;
+            let start = at;
+            at += 1;
+            while gif[at] != 0 {
+                at += 1 + gif[at] as usize;
+            }
+            at += 1;
+            images.push((rect, unlzw(&gif[start..at]).0));
+        }
+        assert_eq!(&gif[at..], &[0x3B]);
+
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].0, [0, 0, w, h]);
+        let rgb: Vec<u8> = images[0].1.iter().flat_map(|&i| palette[i as usize * 3..i as usize * 3 + 3].to_vec()).collect();
+        assert_eq!(rgb, first);
+        // Only the changed pixel, in white
+        assert_eq!(images[1].0, [5, 2, 1, 1]);
+        let i = images[1].1[0] as usize;
+        assert_eq!(&palette[i * 3..i * 3 + 3], &colors[3]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Animated GIF capture (F11): the next `--gif-frames` frames the window
+//! shows, taken `--gif-fps` times a second by the wall clock, scaled down
+//! to `--gif-width` and written to `capture-<time>.gif`. Frames are copied
+//! and mapped without waiting, like the video recorder's, and shrunk as
+//! they arrive; the palette and encoding (see `gif`) wait for the last one
+//! and happen on a thread. Each frame shows for as long as really passed
+//! until the next, so the GIF plays at real speed even if frames were
+//! missed.
+
+use std::time::{Duration, Instant};
+
+use crate::frame_capture::{self, CaptureRing};
+
+/// Frames copied and not yet shrunk, at most.
+const RING: usize = 3;
+
+struct Session {
+    /// Copies frames at the window's size, tagged with when they were
+    /// shown, since `started`.
+    ring: CaptureRing,
+    /// The GIF's size.
+    out_width: u32,
+    out_height: u32,
+    started: Instant,
+    /// When the next frame should be taken.
+    next_due: Duration,
+    /// Frames copied so far, shrunk or not.
+    taken: u32,
+    /// Shrunk frames and when they were shown.
+    frames: Vec<(Vec<u8>, Duration)>,
+}
+
+pub struct GifCapture {
+    frames: u32,
+    fps: u32,
+    /// Widest the GIF gets; 0 keeps the window's width.
+    max_width: u32,
+    /// The swapchain allows copies, so there's anything to capture.
+    supported: bool,
+    session: Option<Session>,
+}
+
+impl GifCapture {
+    pub fn new(frames: u32, fps: u32, max_width: u32, supported: bool) -> Self {
+        Self { frames, fps, max_width, supported, session: None }
+    }
+
+    /// F11: start capturing frames of `width` × `height`, or finish early
+    /// and write what's been taken.
+    pub fn toggle(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) {
+        if self.session.is_some() {
+            self.finish(device);
+            return;
+        }
+        if !self.supported {
+            eprintln!("GIF capture needs a swapchain that can be copied from, and this one can't");
+            return;
+        }
+        let Some(ring) = CaptureRing::new("gif_readback", RING, format, width, height) else {
+            eprintln!("Can't capture a {:?} swapchain", format);
+            return;
+        };
+        // GIF sides are 16 bits
+        let out_width = if self.max_width == 0 { width } else { width.min(self.max_width) }.min(u16::MAX as u32);
+        let out_height = ((height as u64 * out_width as u64 + width as u64 / 2) / width as u64).clamp(1, u16::MAX as u64) as u32;
+        eprintln!(
+            "Capturing {} frames at {} fps as a {}×{} GIF (F11 stops early)",
+            self.frames, self.fps, out_width, out_height,
+        );
+        self.session = Some(Session {
+            ring, out_width, out_height, started: Instant::now(), next_due: Duration::ZERO, taken: 0, frames: Vec::new(),
+        });
+    }
+
+    /// The window is now `width` × `height`. A GIF can't follow a change
+    /// of size, so the capture ends with the frames taken so far.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.session.as_ref().is_some_and(|s| [s.ring.width(), s.ring.height()] != [width, height]) {
+            eprintln!("The window was resized, so the GIF ends here");
+            self.finish(device);
+        }
+    }
+
+    /// Copy the finished frame in `texture` if one is due and a buffer of
+    /// the ring is free. Call `submitted` once the encoder is submitted.
+    pub fn record(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let Some(session) = &mut self.session else { return };
+        let at = session.started.elapsed();
+        if session.taken >= self.frames || at < session.next_due || !session.ring.record(device, encoder, texture, at) {
+            return;
+        }
+        session.taken += 1;
+        // Behind by more than a frame: start counting again from now rather
+        // than taking the missed ones back to back
+        let period = Duration::from_secs(1) / self.fps;
+        session.next_due += period;
+        if session.next_due <= at {
+            session.next_due = at + period;
+        }
+    }
+
+    pub fn submitted(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.ring.submitted();
+        }
+    }
+
+    /// Shrink the frames the GPU is done with, without waiting, and write
+    /// the GIF once the last one is in.
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        let Some(session) = &mut self.session else { return };
+        shrink_mapped(device, session, false);
+        if session.taken >= self.frames && session.ring.is_idle() {
+            self.finish(device);
+        }
+    }
+
+    /// Wait for the frames still being copied, then encode and write the
+    /// GIF on a thread.
+    fn finish(&mut self, device: &wgpu::Device) {
+        let Some(mut session) = self.session.take() else { return };
+        shrink_mapped(device, &mut session, true);
+        if session.frames.is_empty() {
+            eprintln!("GIF capture stopped before any frames were taken");
+            return;
+        }
+        // Each frame lasts until the next was shown, rounded so the
+        // hundredths add up; the last gets one period
+        let last = session.frames.last().unwrap().1 + Duration::from_secs(1) / self.fps;
+        let ends: Vec<Duration> = session.frames.iter().skip(1).map(|f| f.1).chain([last]).collect();
+        let centis = |d: Duration| (d.as_millis() as i64 + 5) / 10;
+        let frames: Vec<crate::gif::Frame> = session
+            .frames
+            .into_iter()
+            .zip(ends)
+            .map(|((rgb, at), end)| crate::gif::Frame { rgb, delay: (centis(end) - centis(at)).clamp(1, u16::MAX as i64) as u16 })
+            .collect();
+        let (width, height) = (session.out_width, session.out_height);
+        std::thread::spawn(move || {
+            let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            let path = format!("capture-{}.gif", secs);
+            let bytes = crate::gif::encode(width as u16, height as u16, &frames);
+            match std::fs::write(&path, &bytes) {
+                Ok(()) => eprintln!("Saved {} ({} frames, {}×{}, {} KB)", path, frames.len(), width, height, bytes.len() / 1024),
+                Err(e) => eprintln!("Failed to save {}: {}", path, e),
+            }
+        });
+    }
+}
+
+/// Shrink the mapped frames at the front of the queue, in order. With
+/// `wait`, wait for the ones still being copied.
+fn shrink_mapped(device: &wgpu::Device, session: &mut Session, wait: bool) {
+    let Session { ring, frames, out_width, out_height, .. } = session;
+    ring.collect(device, wait, |frame| frames.push((shrink(frame, *out_width, *out_height), frame.at)));
+}
+
+/// `frame` at `out_width` × `out_height` as packed RGB, each pixel the
+/// mean of the window pixels it covers.
+fn shrink(frame: &frame_capture::Frame, out_width: u32, out_height: u32) -> Vec<u8> {
+    let (w, h) = (frame.width() as usize, frame.height() as usize);
+    let (ow, oh) = (out_width as usize, out_height as usize);
+    let span = |i: usize, size: usize, out: usize| (i * size / out)..((i + 1) * size / out).max(i * size / out + 1);
+    let mut rgb = Vec::with_capacity(ow * oh * 3);
+    for oy in 0..oh {
+        let rows = span(oy, h, oh);
+        for ox in 0..ow {
+            let columns = span(ox, w, ow);
+            let mut sum = [0u32; 3];
+            for y in rows.clone() {
+                for x in columns.clone() {
+                    let p = frame.rgb(x as u32, y as u32);
+                    for c in 0..3 {
+                        sum[c] += p[c] as u32;
+                    }
+                }
+            }
+            let n = (rows.len() * columns.len()) as u32;
+            rgb.extend(sum.map(|s| ((s + n / 2) / n) as u8));
+        }
+    }
+    rgb
+}
@@ -0,0 +1,68 @@
+//! Glass shading (Shift + W, `--glass`): the dye drawn as a clear, rippling
+//! surface instead of glowing smoke. `fs_glass` in `render.wgsl` takes the
+//! dye amount as a height field and refracts a background through it: the
+//! picture given with `--background FILE.png`, or pool tiles built here.
+
+/// Side of the built-in tile background, in texels.
+const TILES_SIZE: u32 = 512;
+/// Texels per tile.
+const TILE: u32 = 32;
+
+/// The background texture, bound to `render.wgsl` at binding 14.
+pub struct Background {
+    _texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl Background {
+    /// `file` if given and readable, the tiles otherwise.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, file: Option<&str>) -> Self {
+        let image = file.and_then(|path| {
+            let limit = device.limits().max_texture_dimension_2d;
+            let image = std::fs::read(path).map_err(|e| e.to_string()).and_then(|bytes| crate::png::decode(&bytes));
+            let image = image.and_then(|image| match image.width.max(image.height) {
+                side if side > limit => Err(format!("{}×{} is larger than the GPU's {} pixel texture limit", image.width, image.height, limit)),
+                _ => Ok(image),
+            });
+            image.map_err(|e| eprintln!("Failed to load background {}: {}", path, e)).ok()
+        });
+        let image = image.unwrap_or_else(tiles);
+        let size = wgpu::Extent3d { width: image.width, height: image.height, depth_or_array_layers: 1 };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glass_background"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &image.rgba,
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(image.width * 4), rows_per_image: None },
+            size,
+        );
+        let view = texture.create_view(&Default::default());
+        Self { _texture: texture, view }
+    }
+}
+
+/// Pale aqua pool tiles with darker grout, which show every bend of the
+/// light through the surface.
+fn tiles() -> crate::png::Image {
+    let mut rgba = Vec::with_capacity((TILES_SIZE * TILES_SIZE * 4) as usize);
+    for y in 0..TILES_SIZE {
+        for x in 0..TILES_SIZE {
+            let grout = x % TILE < 2 || y % TILE < 2;
+            // Every other tile a shade darker, and light falling off downward
+            let shade = if (x / TILE + y / TILE).is_multiple_of(2) { 1.0 } else { 0.93 };
+            let fall = 1.0 - 0.25 * y as f32 / TILES_SIZE as f32;
+            let [r, g, b] = if grout { [0.25, 0.42, 0.5] } else { [0.62, 0.86, 0.9] };
+            rgba.extend([r, g, b].map(|c| (c * shade * fall * 255.0).round() as u8));
+            rgba.push(255);
+        }
+    }
+    crate::png::Image { width: TILES_SIZE, height: TILES_SIZE, rgba }
+}
@@ -0,0 +1,127 @@
+//! GPU error capture. Setup and every frame run inside wgpu error scopes, so
+//! validation and out-of-memory errors come back as `GpuError`s that are
+//! logged and shown in the window title, instead of reaching wgpu's default
+//! uncaptured-error handler (which panics).
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GpuError {
+    /// API misuse: a bad descriptor, a shader/layout mismatch, a bad binding.
+    Validation { context: &'static str, message: String },
+    OutOfMemory { context: &'static str },
+}
+
+impl GpuError {
+    pub fn new(context: &'static str, err: wgpu::Error) -> Self {
+        match err {
+            wgpu::Error::Validation { description, .. } => GpuError::Validation { context, message: description },
+            wgpu::Error::OutOfMemory { .. } => GpuError::OutOfMemory { context },
+        }
+    }
+
+    /// One line for the window title: the innermost cause (the last line of
+    /// wgpu's message), shortened.
+    pub fn headline(&self) -> String {
+        match self {
+            GpuError::Validation { context, message } => {
+                let cause = message.lines().map(str::trim).rev().find(|l| !l.is_empty()).unwrap_or("");
+                let short: String = cause.chars().take(100).collect();
+                let ellipsis = if short.len() < cause.len() { "…" } else { "" };
+                format!("GPU validation error ({}): {}{}", context, short, ellipsis)
+            }
+            GpuError::OutOfMemory { context } => format!("GPU out of memory ({})", context),
+        }
+    }
+}
+
+impl std::fmt::Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GpuError::Validation { context, message } => write!(f, "GPU validation error during {}:\n{}", context, message.trim_end()),
+            GpuError::OutOfMemory { context } => write!(f, "GPU out of memory during {}", context),
+        }
+    }
+}
+
+/// Open an out-of-memory and a validation scope. Close with `pop_scopes`.
+pub fn push_scopes(device: &wgpu::Device) {
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+}
+
+/// Close the scopes opened by `push_scopes`, yielding the first error caught.
+/// Both scopes are popped before this returns; only the results are awaited.
+pub fn pop_scopes(device: &wgpu::Device, context: &'static str) -> impl Future<Output = Option<GpuError>> {
+    let validation = device.pop_error_scope();
+    let oom = device.pop_error_scope();
+    async move {
+        let validation = validation.await;
+        let oom = oom.await;
+        validation.or(oom).map(|e| GpuError::new(context, e))
+    }
+}
+
+/// Errors seen so far. A broken pipeline fails the same way every frame, so
+/// repeats are counted rather than printed each time.
+#[derive(Default)]
+pub struct ErrorLog {
+    last: Option<GpuError>,
+    repeats: u32,
+    /// Set when `last` changes; cleared by `take_headline`.
+    changed: bool,
+}
+
+impl ErrorLog {
+    pub fn record(&mut self, err: GpuError) {
+        if self.last.as_ref() == Some(&err) {
+            self.repeats += 1;
+            if self.repeats.is_power_of_two() {
+                eprintln!("(last GPU error seen {} more times)", self.repeats);
+            }
+            return;
+        }
+        eprintln!("{}", err);
+        self.last = Some(err);
+        self.repeats = 0;
+        self.changed = true;
+    }
+
+    /// Headline of the latest error if it changed since the last call.
+    pub fn take_headline(&mut self) -> Option<String> {
+        if !std::mem::take(&mut self.changed) {
+            return None;
+        }
+        self.last.as_ref().map(GpuError::headline)
+    }
+}
+
+/// Route errors that escape every scope into `log` instead of panicking.
+pub fn install_uncaptured_handler(device: &wgpu::Device, log: &Arc<Mutex<ErrorLog>>) {
+    let log = log.clone();
+    device.on_uncaptured_error(Box::new(move |err| {
+        if let Ok(mut log) = log.lock() {
+            log.record(GpuError::new("an unscoped call", err));
+        }
+    }));
+}
+
+/// `pop_scopes`, recording any error in `log`. Native backends report
+/// synchronously; on the web the result lands a little later.
+pub fn pop_scopes_into(device: &wgpu::Device, context: &'static str, log: &Arc<Mutex<ErrorLog>>) {
+    let result = pop_scopes(device, context);
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(err) = pollster::block_on(result) {
+        log.lock().unwrap().record(err);
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let log = log.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Some(err) = result.await {
+                log.lock().unwrap().record(err);
+            }
+        });
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{RecursiveMode, Watcher};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderFile {
+    Fluid,
+    Particles,
+    Render,
+}
+
+impl ShaderFile {
+    const ALL: [ShaderFile; 3] = [ShaderFile::Fluid, ShaderFile::Particles, ShaderFile::Render];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ShaderFile::Fluid => "fluid.wgsl",
+            ShaderFile::Particles => "particles.wgsl",
+            ShaderFile::Render => "render.wgsl",
+        }
+    }
+}
+
+/// Watches the WGSL sources in the crate directory with `notify`. The
+/// binary always starts from the shaders embedded at compile time; this
+/// only kicks in once a file on disk is edited.
+///
+/// The directory is watched rather than the files, so editors that save by
+/// writing a new file and renaming it over the old one are still seen.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    events: Receiver<notify::Result<notify::Event>>,
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ShaderWatcher {
+    /// `wake` is called from the watcher's thread after each event, so an
+    /// idle frame loop gets round to `changed`.
+    pub fn new(wake: impl Fn() + Send + 'static) -> Result<Self, notify::Error> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            if tx.send(event).is_ok() {
+                wake();
+            }
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        Ok(Self { dir, events, _watcher: watcher })
+    }
+
+    /// Drains the events since the last call and returns the shaders whose
+    /// file was written or replaced, with their new source. Files that
+    /// vanish or can't be read are skipped.
+    pub fn changed(&mut self) -> Vec<(ShaderFile, String)> {
+        let mut files = Vec::new();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Hot reload: {}", e);
+                    continue;
+                }
+            };
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                continue;
+            }
+            for path in &event.paths {
+                let named = |f: &ShaderFile| path.file_name().is_some_and(|n| n == f.file_name());
+                if let Some(&file) = ShaderFile::ALL.iter().find(|f| named(f)) {
+                    if !files.contains(&file) {
+                        files.push(file);
+                    }
+                }
+            }
+        }
+
+        let mut changed = Vec::new();
+        for file in files {
+            match std::fs::read_to_string(self.dir.join(file.file_name())) {
+                Ok(source) => changed.push((file, source)),
+                Err(e) => eprintln!("Hot reload: can't read {}: {}", file.file_name(), e),
+            }
+        }
+        changed
+    }
+}
+
+/// Run `build` inside a validation error scope. Shader compile errors and
+/// pipeline/layout mismatches come back as `Err` instead of hitting the
+/// uncaptured error handler, so the caller can keep its last good objects.
+pub fn try_build<T>(device: &wgpu::Device, build: impl FnOnce() -> T) -> Result<T, wgpu::Error> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = build();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(err),
+        None => Ok(value),
+    }
+}
+
+pub fn create_module(device: &wgpu::Device, file: ShaderFile, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(file.file_name()),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
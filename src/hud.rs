@@ -0,0 +1,197 @@
+//! HUD (F1, `--hud`): frame rate, frame time, the GPU, the fluid step's
+//! compute dispatches and its current parameters, in the window's top left
+//! corner. `glyphon` lays the text out and draws it in DejaVu Sans Mono,
+//! embedded from `assets/`, over a panel `hud.wgsl` fills; the lines are
+//! rebuilt twice a second so they can be read while they change.
+
+use std::time::{Duration, Instant};
+
+use glyphon::{
+    fontdb, Attrs, Buffer, Color, Family, FontSystem, Metrics, Resolution, Shaping, SwashCache, TextArea, TextAtlas,
+    TextBounds, TextRenderer, Wrap,
+};
+
+/// The HUD's typeface, and its name for `Family::Name`.
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+const FONT_NAME: &str = "DejaVu Sans Mono";
+/// Font size and line height in window pixels, before `Hud::scale`.
+const METRICS: Metrics = Metrics::new(13.0, 17.0);
+/// How often the lines are rebuilt.
+const REFRESH: Duration = Duration::from_millis(500);
+/// Window pixels around the text, before `Hud::scale`.
+const MARGIN: f32 = 6.0;
+/// Window pixels between the panel and the window's edges.
+const INSET: f32 = 12.0;
+
+/// What the HUD shows besides the frame times, as of this frame.
+pub struct Readout<'a> {
+    pub grid: u32,
+    pub scene: &'a str,
+    pub paused: bool,
+    /// Simulated seconds so far, and the step size.
+    pub time: f32,
+    pub dt: f32,
+    pub substeps: u32,
+    /// `Simulation::label`, and the iterations `jacobi:auto` picked.
+    pub solver: String,
+    pub pressure_iterations: Option<u32>,
+    /// Compute dispatches of one fluid step.
+    pub step_dispatches: u32,
+    pub viscosity: f32,
+    pub vorticity: f32,
+    pub dye_decay: f32,
+}
+
+pub struct Hud {
+    /// The panel is drawn (F1).
+    pub showing: bool,
+    /// Adapter name and backend.
+    gpu: String,
+    font_system: FontSystem,
+    swash_cache: SwashCache,
+    atlas: TextAtlas,
+    renderer: TextRenderer,
+    buffer: Buffer,
+    /// Size of the laid out text at a scale of 1.
+    extent: [f32; 2],
+    /// Text and panel size multiplier, larger on tall windows.
+    scale: f32,
+    /// Fills the panel behind the text.
+    pipeline: wgpu::RenderPipeline,
+    last_frame: Option<Instant>,
+    /// Frame times since the lines were last rebuilt.
+    frame_times: Vec<Duration>,
+    last_refresh: Instant,
+}
+
+impl Hud {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat, adapter: &wgpu::AdapterInfo, showing: bool) -> Self {
+        // Only the embedded face, so the HUD looks the same everywhere and
+        // the system's fonts aren't scanned at startup
+        let mut db = fontdb::Database::new();
+        db.load_font_data(FONT.to_vec());
+        let mut font_system = FontSystem::new_with_locale_and_db("en-US".to_string(), db);
+        let mut atlas = TextAtlas::new(device, queue, format);
+        let renderer = TextRenderer::new(&mut atlas, device, wgpu::MultisampleState::default(), None);
+        let mut buffer = Buffer::new(&mut font_system, METRICS);
+        buffer.set_wrap(&mut font_system, Wrap::None);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("hud_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../hud.wgsl").into()),
+        });
+        let pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[], push_constant_ranges: &[],
+        });
+        let pipeline = crate::create_render_pipeline(device, &pl, &shader, format, "fs_hud");
+        Self {
+            showing,
+            gpu: format!("{} ({:?})", adapter.name, adapter.backend),
+            font_system, swash_cache: SwashCache::new(), atlas, renderer, buffer,
+            extent: [0.0; 2], scale: 1.0, pipeline,
+            last_frame: None, frame_times: Vec::new(),
+            // Due at the first frame
+            last_refresh: Instant::now() - REFRESH,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.showing = !self.showing;
+        eprintln!("HUD: {}", if self.showing { "on" } else { "off" });
+    }
+
+    /// Count a frame, rebuild the lines if they're due, and lay them out
+    /// for a `width` × `height` window.
+    pub fn frame(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32, readout: &Readout) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame.replace(now) {
+            self.frame_times.push(now - last);
+        }
+        if !self.showing {
+            self.frame_times.clear();
+            return;
+        }
+        if now - self.last_refresh >= REFRESH {
+            self.last_refresh = now;
+            self.rebuild(readout);
+        }
+
+        self.scale = (height as f32 / 540.0).clamp(1.0, 3.0);
+        let [w, h] = self.panel_size();
+        let text = TextArea {
+            buffer: &self.buffer,
+            left: INSET + MARGIN * self.scale,
+            top: INSET + MARGIN * self.scale,
+            scale: self.scale,
+            bounds: TextBounds { left: INSET as i32, top: INSET as i32, right: (INSET + w) as i32, bottom: (INSET + h) as i32 },
+            default_color: Color::rgb(217, 224, 230),
+        };
+        // Drop the glyphs the last frame drew and this one doesn't
+        self.atlas.trim();
+        let resolution = Resolution { width, height };
+        if let Err(e) = self.renderer.prepare(device, queue, &mut self.font_system, &mut self.atlas, resolution, [text], &mut self.swash_cache) {
+            eprintln!("HUD: {}", e);
+        }
+    }
+
+    /// Sets the text to the lines for `readout` and the frames since the
+    /// last rebuild.
+    fn rebuild(&mut self, readout: &Readout) {
+        let total: Duration = self.frame_times.iter().sum();
+        let mean = total.as_secs_f64() / self.frame_times.len().max(1) as f64;
+        let worst = self.frame_times.iter().max().copied().unwrap_or_default().as_secs_f64();
+        self.frame_times.clear();
+
+        let lines = [
+            format!("{:.0} fps  {:.1} ms a frame, worst {:.1} ms", 1.0 / mean, mean * 1000.0, worst * 1000.0),
+            self.gpu.clone(),
+            format!(
+                "{}×{} {}  t {:.1} s{}",
+                readout.grid, readout.grid, readout.scene, readout.time, if readout.paused { "  paused" } else { "" },
+            ),
+            format!("dt {:.2} ms × {} substeps", readout.dt * 1000.0, readout.substeps),
+            match readout.pressure_iterations {
+                Some(iterations) => format!("solver {}, {} iterations", readout.solver, iterations),
+                None => format!("solver {}", readout.solver),
+            },
+            format!(
+                "{} dispatches a step, {} a frame",
+                readout.step_dispatches, readout.step_dispatches * readout.substeps,
+            ),
+            format!(
+                "viscosity {}  vorticity {}  dye decay {}",
+                readout.viscosity, readout.vorticity, readout.dye_decay,
+            ),
+        ];
+        let font = Attrs::new().family(Family::Name(FONT_NAME));
+        self.buffer.set_size(&mut self.font_system, f32::MAX, f32::MAX);
+        self.buffer.set_text(&mut self.font_system, &lines.join("\n"), font, Shaping::Advanced);
+        self.buffer.shape_until_scroll(&mut self.font_system);
+        let runs = self.buffer.layout_runs();
+        let (width, rows) = runs.fold((0.0f32, 0), |(width, rows), run| (width.max(run.line_w), rows + 1));
+        self.extent = [width, rows as f32 * METRICS.line_height];
+    }
+
+    /// Window pixels the panel covers.
+    fn panel_size(&self) -> [f32; 2] {
+        self.extent.map(|e| (e + 2.0 * MARGIN) * self.scale)
+    }
+
+    /// Draw the panel into the top left of a `width` × `height` target,
+    /// over whatever the pass drew before. Leaves the pass's viewport
+    /// covering the whole target.
+    pub fn draw<'a>(&'a self, r: &mut wgpu::RenderPass<'a>, width: u32, height: u32) {
+        let [w, h] = self.panel_size();
+        if !self.showing || self.extent[1] == 0.0 || w + 2.0 * INSET > width as f32 || h + 2.0 * INSET > height as f32 {
+            return;
+        }
+        r.set_viewport(INSET, INSET, w, h, 0.0, 1.0);
+        r.set_pipeline(&self.pipeline);
+        r.draw(0..3, 0..1);
+        // glyphon places the text in window pixels
+        r.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+        if let Err(e) = self.renderer.render(&self.atlas, r) {
+            eprintln!("HUD: {}", e);
+        }
+    }
+}
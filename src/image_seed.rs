@@ -0,0 +1,144 @@
+//! Starting state from a picture (`--seed-image FILE`): a PNG or baseline
+//! JPEG is stretched over the grid and its luminance (or alpha) becomes the
+//! dye, in place of the scene's blobs. With `--seed-image-velocity`, the
+//! picture also sets the fluid moving along its edges: its brightness is
+//! used as a stream function, whose gradient turned a quarter turn is a
+//! divergence-free velocity. The plain gradient would point across edges
+//! and be curl-free, which the first projection would remove entirely.
+//!
+//! A picture dropped on the window is added to the running simulation's
+//! dye instead, in its own colors and proportions (`dye`).
+
+use crate::png::Image;
+use crate::snapshot::Snapshot;
+
+/// What of the picture becomes dye.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Rec. 709 luma of the stored values: bright is dense.
+    Luminance,
+    /// Opacity, for cut-outs whose color doesn't matter.
+    Alpha,
+}
+
+impl Channel {
+    const ALL: [Channel; 2] = [Channel::Luminance, Channel::Alpha];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Luminance => "luminance",
+            Channel::Alpha => "alpha",
+        }
+    }
+}
+
+impl std::str::FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("image channel", s, &Self::ALL.map(|c| (c.name(), c)))
+    }
+}
+
+/// Decode a PNG or JPEG file's bytes, told apart by their signature.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        crate::jpeg::decode(bytes)
+    } else {
+        crate::png::decode(bytes).map_err(|e| if e == "not a PNG file" { "not a PNG or JPEG file".to_string() } else { e })
+    }
+}
+
+/// The `grid`² starting state for `image`: dye from `channel`, 0 to 1, and
+/// a velocity along its contours peaking at `speed` grid cells per second
+/// (none at 0).
+pub fn seed(image: &Image, grid: u32, channel: Channel, speed: f32) -> Snapshot {
+    let n = grid as usize;
+    let (w, h) = (image.width as usize, image.height as usize);
+    let value = |x: usize, y: usize| {
+        let p = &image.rgba[(y * w + x) * 4..][..4];
+        match channel {
+            Channel::Luminance => (0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32) / 255.0,
+            Channel::Alpha => p[3] as f32 / 255.0,
+        }
+    };
+    // Each cell averages the pixels it covers, or takes the nearest when
+    // the picture is smaller than the grid
+    let span = |i: usize, size: usize| {
+        let start = (i * size / n).min(size - 1);
+        start..((i + 1) * size / n).clamp(start + 1, size)
+    };
+    let mut density = Vec::with_capacity(n * n);
+    for y in 0..n {
+        let rows = span(y, h);
+        for x in 0..n {
+            let columns = span(x, w);
+            let count = rows.len() * columns.len();
+            let sum: f32 = rows.clone().flat_map(|py| columns.clone().map(move |px| (px, py))).map(|(px, py)| value(px, py)).sum();
+            density.push(sum / count as f32);
+        }
+    }
+
+    let mut velocity = vec![[0.0f32; 2]; n * n];
+    if speed > 0.0 && n > 2 {
+        // u = ∂ψ/∂y, v = -∂ψ/∂x with ψ the dye, by central differences
+        let at = |x: usize, y: usize| density[y * n + x];
+        let mut peak = 0.0f32;
+        for y in 1..n - 1 {
+            for x in 1..n - 1 {
+                let v = [at(x, y + 1) - at(x, y - 1), at(x - 1, y) - at(x + 1, y)];
+                peak = peak.max(v[0].hypot(v[1]));
+                velocity[y * n + x] = v;
+            }
+        }
+        if peak > 0.0 {
+            for v in &mut velocity {
+                *v = v.map(|c| c * speed / peak);
+            }
+        }
+    }
+    Snapshot { grid, density: density.into_iter().map(|d| [d; 3]).collect(), velocity }
+}
+
+/// Colored dye for `image` dropped on a window `aspect` times as wide as
+/// tall: the picture fitted inside it, centered and keeping its
+/// proportions on screen, each cell the mean of the pixels it covers
+/// times their alpha. Black, transparent and uncovered cells get none.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn dye(image: &Image, grid: u32, aspect: f32) -> Vec<[f32; 3]> {
+    let n = grid as usize;
+    let (w, h) = (image.width as usize, image.height as usize);
+    // The window shows the square grid stretched, so the picture's share
+    // of each side follows from the two aspect ratios
+    let picture = w as f32 / h as f32;
+    let (fx, fy) = if picture > aspect { (1.0, aspect / picture) } else { (picture / aspect, 1.0) };
+    let cells = |fraction: f32| ((fraction * n as f32).round() as usize).clamp(1, n);
+    let (cw, ch) = (cells(fx), cells(fy));
+    let (x0, y0) = ((n - cw) / 2, (n - ch) / 2);
+    // Pixels under cell `i` of `count`, at least the nearest one
+    let span = |i: usize, count: usize, size: usize| {
+        let start = (i * size / count).min(size - 1);
+        start..((i + 1) * size / count).clamp(start + 1, size)
+    };
+
+    let mut dye = vec![[0.0f32; 3]; n * n];
+    for cy in 0..ch {
+        let rows = span(cy, ch, h);
+        for cx in 0..cw {
+            let columns = span(cx, cw, w);
+            let mut sum = [0.0f32; 3];
+            for py in rows.clone() {
+                for px in columns.clone() {
+                    let p = &image.rgba[(py * w + px) * 4..][..4];
+                    let alpha = p[3] as f32 / 255.0;
+                    for c in 0..3 {
+                        sum[c] += p[c] as f32 / 255.0 * alpha;
+                    }
+                }
+            }
+            let count = (rows.len() * columns.len()) as f32;
+            dye[(y0 + cy) * n + x0 + cx] = sum.map(|s| s / count);
+        }
+    }
+    dye
+}
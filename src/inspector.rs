@@ -0,0 +1,198 @@
+//! Field inspector (Shift + I, `--inspect`): the values of every field at
+//! the cell under the cursor, in the window title. Each frame copies that
+//! one texel of the dye, velocity, pressure, divergence and curl textures
+//! into a small staging buffer and maps it without waiting, so the title
+//! trails the view by a frame or two and the simulation never stalls.
+//! `inspector.wgsl` outlines the cell, and in the grid mode draws every
+//! cell's boundary too, for checking what a shader does cell by cell.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::readback::Readback;
+
+/// Fields copied per readback, one `Rgba16Float` texel each.
+const FIELDS: usize = 5;
+const TEXEL_BYTES: u64 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Off,
+    /// The cell's values, and its outline.
+    Values,
+    /// The values, with grid lines over the whole view.
+    Grid,
+}
+
+impl Mode {
+    pub fn next(self) -> Self {
+        match self {
+            Mode::Off => Mode::Values,
+            Mode::Values => Mode::Grid,
+            Mode::Grid => Mode::Off,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct InspectParams {
+    viewport: [f32; 4],
+    cell: [i32; 2],
+    grid_size: u32,
+    show_grid: u32,
+}
+
+/// The textures read, in the order of the staging buffer.
+pub struct Sources<'a> {
+    pub density: &'a wgpu::Texture,
+    pub velocity: &'a wgpu::Texture,
+    pub pressure: &'a wgpu::Texture,
+    pub divergence: &'a wgpu::Texture,
+    pub curl: &'a wgpu::Texture,
+}
+
+pub struct Inspector {
+    pub mode: Mode,
+    params: InspectParams,
+    param_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// The fields' texels, tagged with their cell.
+    readback: Readback<[u32; 2]>,
+    /// Cell under the cursor, if it's over the window.
+    hovered: Option<[u32; 2]>,
+}
+
+impl Inspector {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, grid_size: u32, viewport: [f32; 4], mode: Mode) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("inspector_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../inspector.wgsl").into()),
+        });
+        let params = InspectParams { viewport, cell: [-1; 2], grid_size, show_grid: (mode == Mode::Grid) as u32 };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("inspect_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("inspector_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false, min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("inspector_bg"), layout: &bgl,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() }],
+        });
+        let pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        // Blended over the view with the other overlays
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fs_inspect"), layout: Some(&pl),
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_inspect", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader, entry_point: "fs_inspect",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format, blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self {
+            mode, params, param_buffer, bind_group, pipeline,
+            readback: Readback::new(device, "inspector_staging", FIELDS as u64 * TEXEL_BYTES),
+            hovered: None,
+        }
+    }
+
+    /// Shift + I: off → values → values and grid lines.
+    pub fn cycle(&mut self, queue: &wgpu::Queue) {
+        self.mode = self.mode.next();
+        self.params.show_grid = (self.mode == Mode::Grid) as u32;
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+        eprintln!("Field inspector: {}", match self.mode {
+            Mode::Off => "off",
+            Mode::Values => "values under the cursor, in the window title",
+            Mode::Grid => "values and grid lines",
+        });
+    }
+
+    /// The cursor moved to `position` in a window of `size` pixels, or
+    /// left it.
+    pub fn hover(&mut self, queue: &wgpu::Queue, position: Option<[f32; 2]>, size: [u32; 2]) {
+        let n = self.params.grid_size;
+        self.hovered = position.map(|p| {
+            let v = self.params.viewport;
+            [0, 1].map(|axis| {
+                let uv = v[axis] + p[axis] / size[axis].max(1) as f32 * v[axis + 2];
+                ((uv * n as f32) as u32).min(n - 1)
+            })
+        });
+        self.params.cell = self.hovered.map_or([-1; 2], |c| c.map(|c| c as i32));
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// Copy the hovered cell's values, as the frame so far has left them.
+    /// Call `submitted` once the encoder is submitted.
+    pub fn record(&mut self, encoder: &mut wgpu::CommandEncoder, sources: Sources) {
+        let Some(cell) = self.hovered.filter(|_| self.mode != Mode::Off && self.readback.is_free()) else { return };
+        let textures = [sources.density, sources.velocity, sources.pressure, sources.divergence, sources.curl];
+        for (i, texture) in textures.into_iter().enumerate() {
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: cell[0], y: cell[1], z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: self.readback.buffer(),
+                    layout: wgpu::ImageDataLayout { offset: i as u64 * TEXEL_BYTES, bytes_per_row: None, rows_per_image: None },
+                },
+                wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            );
+        }
+        self.readback.recorded(cell);
+    }
+
+    pub fn submitted(&mut self) {
+        self.readback.submitted();
+    }
+
+    /// The last readback as a line for the title, if the GPU is done with
+    /// it, without waiting.
+    pub fn collect(&mut self, device: &wgpu::Device) -> Option<String> {
+        let line = self.readback.collect(device, false, |cell, bytes| {
+            let halves: [[u16; 4]; FIELDS] = bytemuck::pod_read_unaligned(bytes);
+            let [density, velocity, pressure, divergence, curl] = halves.map(|t| t.map(crate::f16_to_f32));
+            format!(
+                "cell ({}, {}): dye {:.3}, temperature {:.3}, velocity ({:.2}, {:.2}), pressure {:.3}, divergence {:.4}, vorticity {:.3}",
+                cell[0], cell[1],
+                density[0].max(density[2]).max(density[3]), density[1],
+                velocity[0], velocity[1], pressure[0], divergence[0], curl[0],
+            )
+        });
+        line.filter(|_| self.mode != Mode::Off)
+    }
+
+    /// The cell outline, and the grid lines in grid mode.
+    pub fn draw<'a>(&'a self, r: &mut wgpu::RenderPass<'a>) {
+        if self.mode == Mode::Off {
+            return;
+        }
+        r.set_pipeline(&self.pipeline);
+        r.set_bind_group(0, &self.bind_group, &[]);
+        r.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,454 @@
+//! A small baseline JPEG reader for seeding the fields from a photo
+//! (`--seed-image`): Huffman-coded 8-bit images with one (grayscale) or
+//! three (YCbCr) components, any chroma subsampling and restart intervals,
+//! interleaved or not. Progressive and arithmetic-coded files are refused;
+//! re-saving them as baseline (or as PNG) works. Chroma is upsampled by
+//! repetition, which is plenty for a density field.
+
+use crate::png::Image;
+
+/// Coefficient order within a block: `ZIGZAG[k]` is where the `k`th
+/// coefficient of the stream goes in row-major order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5,
+    12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// A canonical Huffman table: codes of each length are consecutive, so a
+/// code of `len` bits is valid if it's at most `max_code[len]`.
+#[derive(Clone, Default)]
+struct Huffman {
+    max_code: [i32; 17],
+    /// Index in `values` of the first code of each length, less that code.
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: &[u8]) -> Self {
+        let mut table = Huffman { max_code: [-1; 17], offset: [0; 17], values: values.to_vec() };
+        let (mut code, mut index) = (0i32, 0i32);
+        for len in 1..=16 {
+            let count = counts[len - 1] as i32;
+            table.offset[len] = index - code;
+            code += count;
+            index += count;
+            table.max_code[len] = if count > 0 { code - 1 } else { -1 };
+            code <<= 1;
+        }
+        table
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u8, String> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = code << 1 | bits.bit() as i32;
+            if code <= self.max_code[len] {
+                return self.values.get((code + self.offset[len]) as usize).copied().ok_or_else(corrupt);
+            }
+        }
+        Err(corrupt())
+    }
+}
+
+/// Entropy-coded data, with the 0xFF 0x00 stuffing taken out. Reads zeros
+/// past the end or at a marker, as decoders conventionally do.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> u32 {
+        if self.count == 0 {
+            let mut byte = 0;
+            if let Some(&b) = self.data.get(self.pos) {
+                if b != 0xFF {
+                    byte = b;
+                    self.pos += 1;
+                } else if self.data.get(self.pos + 1) == Some(&0) {
+                    byte = 0xFF;
+                    self.pos += 2;
+                }
+            }
+            self.acc = byte as u32;
+            self.count = 8;
+        }
+        self.count -= 1;
+        (self.acc >> self.count) & 1
+    }
+
+    fn bits(&mut self, n: u32) -> u32 {
+        (0..n).fold(0, |v, _| v << 1 | self.bit())
+    }
+
+    /// An `n`-bit value in JPEG's sign-folded form. Sizes come from the
+    /// file's Huffman tables, and a corrupt one can claim anything up to
+    /// 255 bits; baseline coefficients never take more than 16.
+    fn signed(&mut self, n: u32) -> Result<i32, String> {
+        if n == 0 {
+            return Ok(0);
+        }
+        if n > 16 {
+            return Err(corrupt());
+        }
+        let v = self.bits(n) as i32;
+        Ok(if v < 1 << (n - 1) { v - (1 << n) + 1 } else { v })
+    }
+
+    /// Skip to the byte after the next restart marker.
+    fn restart(&mut self) {
+        self.count = 0;
+        while self.pos + 1 < self.data.len() && !(self.data[self.pos] == 0xFF && (0xD0..=0xD7).contains(&self.data[self.pos + 1])) {
+            self.pos += 1;
+        }
+        self.pos += 2;
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    /// Samples, padded out to whole MCUs.
+    plane: Vec<u8>,
+    stride: usize,
+}
+
+fn corrupt() -> String {
+    "JPEG data is corrupt".to_string()
+}
+
+/// Decode a baseline JPEG file's bytes.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    let mut rest = bytes.strip_prefix(&[0xFF, 0xD8]).ok_or("not a JPEG file")?;
+    let mut quant = [[0u16; 64]; 4];
+    let (mut dc_tables, mut ac_tables) = (vec![Huffman::default(); 4], vec![Huffman::default(); 4]);
+    let mut components: Vec<Component> = Vec::new();
+    let (mut width, mut height) = (0usize, 0usize);
+    let (mut h_max, mut v_max) = (1, 1);
+    let mut restart_interval = 0usize;
+    let mut scanned = false;
+
+    loop {
+        // Fill bytes may pad between segments
+        while rest.first() == Some(&0xFF) && rest.get(1) == Some(&0xFF) {
+            rest = &rest[1..];
+        }
+        let [0xFF, marker, ..] = *rest else { return Err(corrupt()) };
+        if marker == 0xD9 {
+            break;
+        }
+        let len = rest.get(2..4).map(|l| u16::from_be_bytes([l[0], l[1]]) as usize).ok_or_else(corrupt)?;
+        let segment = rest.get(4..2 + len).ok_or("truncated JPEG segment")?;
+        rest = &rest[2 + len..];
+        match marker {
+            0xDB => {
+                let mut s = segment;
+                while let [info, ..] = *s {
+                    let (precision, id) = (info >> 4, (info & 3) as usize);
+                    let size = if precision == 0 { 64 } else { 128 };
+                    let table = s.get(1..1 + size).ok_or_else(corrupt)?;
+                    for k in 0..64 {
+                        quant[id][k] = if precision == 0 { table[k] as u16 } else { u16::from_be_bytes([table[2 * k], table[2 * k + 1]]) };
+                    }
+                    s = &s[1 + size..];
+                }
+            }
+            0xC4 => {
+                let mut s = segment;
+                while s.len() >= 17 {
+                    let (class, id) = (s[0] >> 4, (s[0] & 3) as usize);
+                    let counts = &s[1..17];
+                    let total: usize = counts.iter().map(|&c| c as usize).sum();
+                    let values = s.get(17..17 + total).ok_or_else(corrupt)?;
+                    let table = Huffman::new(counts, values);
+                    if class == 0 { dc_tables[id] = table } else { ac_tables[id] = table }
+                    s = &s[17 + total..];
+                }
+            }
+            0xDD => restart_interval = segment.get(..2).map(|r| u16::from_be_bytes([r[0], r[1]]) as usize).ok_or_else(corrupt)?,
+            0xC0 | 0xC1 => {
+                if segment.len() < 6 || segment[0] != 8 {
+                    return Err("only 8-bit JPEGs are supported".to_string());
+                }
+                height = u16::from_be_bytes([segment[1], segment[2]]) as usize;
+                width = u16::from_be_bytes([segment[3], segment[4]]) as usize;
+                let count = segment[5] as usize;
+                if width == 0 || height == 0 {
+                    return Err("JPEG is empty".to_string());
+                }
+                if count != 1 && count != 3 {
+                    return Err(format!("JPEGs with {} components aren't supported", count));
+                }
+                for c in segment.get(6..6 + 3 * count).ok_or_else(corrupt)?.chunks_exact(3) {
+                    let (h, v) = ((c[1] >> 4) as usize, (c[1] & 15) as usize);
+                    if !(1..=4).contains(&h) || !(1..=4).contains(&v) {
+                        return Err(corrupt());
+                    }
+                    components.push(Component { id: c[0], h, v, quant: (c[2] & 3) as usize, plane: Vec::new(), stride: 0 });
+                }
+                h_max = components.iter().map(|c| c.h).max().unwrap();
+                v_max = components.iter().map(|c| c.v).max().unwrap();
+                let (mcus_x, mcus_y) = (width.div_ceil(8 * h_max), height.div_ceil(8 * v_max));
+                for c in &mut components {
+                    c.stride = mcus_x * c.h * 8;
+                    c.plane = vec![0; c.stride * mcus_y * c.v * 8];
+                }
+            }
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                return Err("only baseline JPEGs are supported (not progressive, lossless or arithmetic-coded)".to_string());
+            }
+            0xDA => {
+                if components.is_empty() {
+                    return Err("JPEG scan before its frame header".to_string());
+                }
+                let count = *segment.first().ok_or_else(corrupt)? as usize;
+                let mut scan = Vec::with_capacity(count);
+                for s in segment.get(1..1 + 2 * count).ok_or_else(corrupt)?.chunks_exact(2) {
+                    let index = components.iter().position(|c| c.id == s[0]).ok_or_else(corrupt)?;
+                    scan.push((index, (s[1] >> 4) as usize & 3, (s[1] & 3) as usize));
+                }
+                // The entropy-coded data runs to the next marker that isn't
+                // stuffing or a restart
+                let mut end = 0;
+                while end + 1 < rest.len() && !(rest[end] == 0xFF && rest[end + 1] != 0 && !(0xD0..=0xD7).contains(&rest[end + 1])) {
+                    end += 1;
+                }
+                let mut bits = Bits { data: &rest[..end], pos: 0, acc: 0, count: 0 };
+                decode_scan(&mut bits, &mut components, &scan, &quant, &dc_tables, &ac_tables, [width, height], [h_max, v_max], restart_interval)?;
+                rest = &rest[end..];
+                scanned = true;
+            }
+            _ => {}
+        }
+    }
+    if !scanned {
+        return Err("JPEG has no image data".to_string());
+    }
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let sample = |c: &Component| c.plane[(y * c.v / v_max) * c.stride + x * c.h / h_max] as f32;
+            if let [luma] = &components[..] {
+                let l = sample(luma) as u8;
+                rgba.extend_from_slice(&[l, l, l, 255]);
+            } else {
+                let (l, cb, cr) = (sample(&components[0]), sample(&components[1]) - 128.0, sample(&components[2]) - 128.0);
+                let rgb = [l + 1.402 * cr, l - 0.344136 * cb - 0.714136 * cr, l + 1.772 * cb];
+                rgba.extend(rgb.map(|v| v.round().clamp(0.0, 255.0) as u8));
+                rgba.push(255);
+            }
+        }
+    }
+    Ok(Image { width: width as u32, height: height as u32, rgba })
+}
+
+/// Decode one scan into the components' planes. `scan` lists (component,
+/// DC table, AC table); with one component the blocks aren't interleaved
+/// and cover only the component's own extent.
+#[allow(clippy::too_many_arguments)]
+fn decode_scan(
+    bits: &mut Bits, components: &mut [Component], scan: &[(usize, usize, usize)], quant: &[[u16; 64]; 4],
+    dc_tables: &[Huffman], ac_tables: &[Huffman], [width, height]: [usize; 2], [h_max, v_max]: [usize; 2], restart_interval: usize,
+) -> Result<(), String> {
+    let mut predictors = vec![0i32; scan.len()];
+    let (units_x, units_y, interleaved) = match scan {
+        [(c, _, _)] => {
+            let c = &components[*c];
+            ((width * c.h).div_ceil(h_max).div_ceil(8), (height * c.v).div_ceil(v_max).div_ceil(8), false)
+        }
+        _ => (width.div_ceil(8 * h_max), height.div_ceil(8 * v_max), true),
+    };
+    for unit in 0..units_x * units_y {
+        if restart_interval > 0 && unit > 0 && unit % restart_interval == 0 {
+            bits.restart();
+            predictors.fill(0);
+        }
+        let (ux, uy) = (unit % units_x, unit / units_x);
+        for (i, &(c, dc, ac)) in scan.iter().enumerate() {
+            let (h, v) = if interleaved { (components[c].h, components[c].v) } else { (1, 1) };
+            for by in 0..v {
+                for bx in 0..h {
+                    let mut block = [0f32; 64];
+                    let s = dc_tables[dc].decode(bits)? as u32;
+                    // Wrapping: a corrupt file can add up differences past i32
+                    predictors[i] = predictors[i].wrapping_add(bits.signed(s)?);
+                    let q = &quant[components[c].quant];
+                    block[0] = predictors[i].wrapping_mul(q[0] as i32) as f32;
+                    let mut k = 1;
+                    while k < 64 {
+                        let rs = ac_tables[ac].decode(bits)?;
+                        let (run, size) = ((rs >> 4) as usize, (rs & 15) as u32);
+                        if size == 0 {
+                            if run != 15 {
+                                break;
+                            }
+                            k += 16;
+                            continue;
+                        }
+                        k += run;
+                        if k > 63 {
+                            return Err(corrupt());
+                        }
+                        block[ZIGZAG[k]] = (bits.signed(size)? * q[k] as i32) as f32;
+                        k += 1;
+                    }
+                    let component = &mut components[c];
+                    let origin = (uy * v + by) * 8 * component.stride + (ux * h + bx) * 8;
+                    idct(&block, &mut component.plane[origin..], component.stride);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Inverse DCT of a dequantized block into 8×8 samples of `out`, rows
+/// `stride` apart, level-shifted back to 0..255.
+fn idct(block: &[f32; 64], out: &mut [u8], stride: usize) {
+    // basis[x][u] = C(u) / 2 · cos((2x + 1)uπ / 16)
+    let basis: [[f32; 8]; 8] = std::array::from_fn(|x| std::array::from_fn(|u| {
+        let c = if u == 0 { std::f32::consts::FRAC_1_SQRT_2 } else { 1.0 };
+        0.5 * c * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos()
+    }));
+    let mut rows = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8).map(|u| basis[x][u] * block[v * 8 + u]).sum();
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            let value: f32 = (0..8).map(|v| basis[y][v] * rows[v * 8 + x]).sum();
+            out[y * stride + x] = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written by the jpeg-encoder crate. An 8×8 grayscale ramp, black to
+    // light gray left to right, at quality 90
+    const GRAY: &str = "\
+        ffd8ffe000104a46494600010200000100010000ffc0000b080008000801001100ffdb00430003020203020203030303\
+        04030304050805050404050a070706080c0a0c0c0b0a0b0b0d0e12100d0e110e0b0b1016101113141515150c0f171816\
+        141812141514ffdb00430103040405040509050509140d0b0d1414141414141414141414141414141414141414141414\
+        141414141414141414141414141414141414141414141414141414ffc4001f0000010501010101010100000000000000\
+        000102030405060708090a0bffc400b5100002010303020403050504040000017d010203000411051221314106135161\
+        07227114328191a1082342b1c11552d1f02433627282090a161718191a25262728292a3435363738393a434445464748\
+        494a535455565758595a636465666768696a737475767778797a838485868788898a92939495969798999aa2a3a4a5a6\
+        a7a8a9aab2b3b4b5b6b7b8b9bac2c3c4c5c6c7c8c9cad2d3d4d5d6d7d8d9dae1e2e3e4e5e6e7e8e9eaf1f2f3f4f5f6f7\
+        f8f9faffda0008010000003f00e53fe09b7ff30bff0080d7ffd9\
+    ";
+
+    // 32×16 in YCbCr with 4:2:0 chroma and a restart after each of its two
+    // MCUs: red, green, blue and white quadrants in the left half, gray in
+    // the right, at quality 95
+    const QUADRANTS: &str = "\
+        ffd8ffe000104a46494600010200000100010000ffc00011080010002003002200011101021101ffdb00430002010101\
+        01010201010102020202020403020202020504040304060506060605060606070908060709070606080b08090a0a0a0a\
+        0a06080b0c0b0a0c090a0a0affdb004301020202020202050303050a0706070a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a\
+        0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0affc4001f0000010501010101010100\
+        000000000000000102030405060708090a0bffc400b5100002010303020403050504040000017d010203000411051221\
+        31410613516107227114328191a1082342b1c11552d1f02433627282090a161718191a25262728292a3435363738393a\
+        434445464748494a535455565758595a636465666768696a737475767778797a838485868788898a9293949596979899\
+        9aa2a3a4a5a6a7a8a9aab2b3b4b5b6b7b8b9bac2c3c4c5c6c7c8c9cad2d3d4d5d6d7d8d9dae1e2e3e4e5e6e7e8e9eaf1\
+        f2f3f4f5f6f7f8f9faffc4001f0100030101010101010101010000000000000102030405060708090a0bffc400b51100\
+        020102040403040705040400010277000102031104052131061241510761711322328108144291a1b1c109233352f015\
+        6272d10a162434e125f11718191a262728292a35363738393a434445464748494a535455565758595a63646566676869\
+        6a737475767778797a82838485868788898a92939495969798999aa2a3a4a5a6a7a8a9aab2b3b4b5b6b7b8b9bac2c3c4\
+        c5c6c7c8c9cad2d3d4d5d6d7d8d9dae2e3e4e5e6e7e8e9eaf2f3f4f5f6f7f8f9faffdd00040001ffda000c0300000111\
+        0211003f00f8bebf4a2bf06ebfb88a3e931f419ff8871fd95ff1907b7f6fedff00e613939793d8ff00d44cef7e7f2b5b\
+        adf45f4c1e25ff0089d1fec4fdd7f657f657d67ed7d6bdafd6beafe587e4e4fabff7f9b9fecf2fbdffd028a28a00ffd9\
+    ";
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn pixel(image: &Image, x: u32, y: u32) -> [u8; 3] {
+        let i = (y * image.width + x) as usize * 4;
+        [image.rgba[i], image.rgba[i + 1], image.rgba[i + 2]]
+    }
+
+    /// Within what quality 90 and up keeps of flat colors.
+    fn assert_near(got: [u8; 3], want: [u8; 3]) {
+        assert!(got.iter().zip(want).all(|(&g, w)| g.abs_diff(w) <= 8), "got {:?}, want about {:?}", got, want);
+    }
+
+    #[test]
+    fn decodes_grayscale() {
+        let image = decode(&bytes(GRAY)).unwrap();
+        assert_eq!([image.width, image.height], [8, 8]);
+        for x in 0..8 {
+            let v = (x * 32) as u8;
+            assert_near(pixel(&image, x, 0), [v; 3]);
+            assert_near(pixel(&image, x, 7), [v; 3]);
+        }
+    }
+
+    #[test]
+    fn decodes_subsampled_color_with_restarts() {
+        let image = decode(&bytes(QUADRANTS)).unwrap();
+        assert_eq!([image.width, image.height], [32, 16]);
+        assert_near(pixel(&image, 2, 2), [255, 0, 0]);
+        assert_near(pixel(&image, 13, 2), [0, 255, 0]);
+        assert_near(pixel(&image, 2, 13), [0, 0, 255]);
+        assert_near(pixel(&image, 13, 13), [255, 255, 255]);
+        // Past the restart marker
+        assert_near(pixel(&image, 24, 8), [128, 128, 128]);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        for fixture in [GRAY, QUADRANTS] {
+            let full = bytes(fixture);
+            for len in 0..full.len() {
+                assert!(decode(&full[..len]).is_err(), "{} of {} bytes decoded", len, full.len());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_coefficients() {
+        // Every DC difference claims 17 bits
+        let mut file = bytes(GRAY);
+        let table = file.windows(5).position(|w| w == [0xFF, 0xC4, 0x00, 0x1F, 0x00]).unwrap();
+        file[table + 5 + 16..table + 5 + 16 + 12].fill(17);
+        assert_eq!(decode(&file).err(), Some(corrupt()));
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        assert!(decode(b"\x89PNG\r\n\x1a\n").is_err());
+        // Start of frame, progressive
+        let mut file = bytes(GRAY);
+        let frame = file.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        file[frame + 1] = 0xC2;
+        assert!(decode(&file).err().unwrap().contains("baseline"));
+    }
+
+    #[test]
+    fn survives_corrupt_bytes() {
+        // Any answer will do, as long as it's an answer and not a panic
+        for fixture in [GRAY, QUADRANTS] {
+            let full = bytes(fixture);
+            for i in 0..full.len() {
+                for flip in [0x01, 0xFF] {
+                    let mut file = full.clone();
+                    file[i] ^= flip;
+                    let _ = decode(&file);
+                }
+            }
+        }
+    }
+}
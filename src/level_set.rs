@@ -0,0 +1,384 @@
+//! Level set liquid mode (`--level-set`): a free-surface liquid on the
+//! grid alone. The liquid is where a signed distance field φ is negative;
+//! the kernels in `level_set.wgsl` advect φ with the flow, redistance it
+//! and mark the cells outside it as air, and the 2D solver's own projection
+//! then holds the air at zero pressure, as in FLIP mode. Unlike FLIP there
+//! are no particles: the surface is φ's zero crossing, smooth at any
+//! resolution, but thin sheets and small drops thinner than a cell or two
+//! lose volume and vanish.
+
+use std::sync::Arc;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    dpi::PhysicalSize,
+    event::MouseButton,
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::mode::{self, Mode};
+use crate::pressure::SolverKind;
+use crate::scene::Boundary;
+use crate::simulation::{self, FluidPipelines, Simulation};
+use crate::{create_storage_tex, GpuEmitter, SimParams, MAX_EMITTERS};
+
+/// φ is clamped to this many cells either side of the surface. Must match
+/// `BAND` in `level_set.wgsl`.
+const BAND: f32 = 6.0;
+/// Redistancing sweeps per step. Odd, so φ ends up back in the first
+/// texture after the advection's one pass into the second.
+const REDISTANCE_SWEEPS: u32 = 3;
+/// As in FLIP mode, only the Jacobi kernels know about air.
+const PRESSURE_ITERATIONS: u32 = 80;
+/// Gravity when `--gravity` leaves it off, in cells/s² down.
+const DEFAULT_GRAVITY: f32 = 250.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LevelSetParams {
+    grid_size: u32,
+    dt: f32,
+    cell_size: [f32; 2],
+    gravity: [f32; 2],
+    /// Brush center and its movement this frame, in grid cells.
+    mouse_pos: [f32; 2],
+    mouse_delta: [f32; 2],
+    radius: f32,
+    /// 0 idle, 1 dragging the liquid, 2 pouring more.
+    mouse_mode: u32,
+    /// Surface tension coefficient in cells³/s².
+    surface_tension: f32,
+    _pad: [f32; 3],
+}
+
+/// A dam at rest against the left wall over a shallow pool, as φ texels.
+fn dam_over_pool(grid: u32) -> Vec<[f32; 4]> {
+    let n = grid as f32;
+    let (dam_width, dam_top, pool_top) = (n * 0.35, n * 0.35, n * 0.88);
+    (0..grid * grid).map(|i| {
+        let (x, y) = ((i % grid) as f32 + 0.5, (i / grid) as f32 + 0.5);
+        // The dam runs on past the wall and floor, so only its top and
+        // right side are surface
+        let (dx, dy) = (x - dam_width, dam_top - y);
+        let dam = if dx > 0.0 && dy > 0.0 { dx.hypot(dy) } else { dx.max(dy) };
+        let pool = pool_top - y;
+        [dam.min(pool).clamp(-BAND, BAND), 0.0, 0.0, 0.0]
+    }).collect()
+}
+
+/// φ, the extended velocity, the level set kernels and the renderer. The
+/// grid itself is a `Simulation`'s, passed to `step`.
+struct LevelSet {
+    params: LevelSetParams,
+    param_buffer: wgpu::Buffer,
+    phi: [wgpu::Texture; 2],
+    _views: Vec<wgpu::TextureView>,
+    /// [0] reads the first φ texture and writes the second, [1] the other
+    /// way round.
+    bind_groups: [wgpu::BindGroup; 2],
+    extrapolate: wgpu::ComputePipeline,
+    advect: wgpu::ComputePipeline,
+    redistance: wgpu::ComputePipeline,
+    render_bg: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl LevelSet {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sim: &Simulation, grid: u32, gravity: [f32; 2], surface_tension: f32) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("level_set_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../level_set.wgsl").into()),
+        });
+
+        let params = LevelSetParams {
+            grid_size: grid, dt: 0.016, cell_size: [1.0; 2], gravity,
+            mouse_pos: [0.0; 2], mouse_delta: [0.0; 2], radius: grid as f32 * 0.05, mouse_mode: 0,
+            surface_tension, _pad: [0.0; 3],
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("level_set_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let (phi_a, phi_a_view) = create_storage_tex(device, grid);
+        let (phi_b, phi_b_view) = create_storage_tex(device, grid);
+        let (_extended, extended_view) = create_storage_tex(device, grid);
+
+        // ---- Compute ----
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false, min_binding_size: None,
+            },
+            count: None,
+        }];
+        entries.extend((1..=5).map(|binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::ReadWrite,
+                format: wgpu::TextureFormat::Rgba16Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        }));
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("level_set_compute_bgl"), entries: &entries,
+        });
+        let bind_group = |src: &wgpu::TextureView, dst: &wgpu::TextureView| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("level_set_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&sim.velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&sim.density_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(src) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(dst) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&extended_view) },
+            ],
+        });
+        let bind_groups = [bind_group(&phi_a_view, &phi_b_view), bind_group(&phi_b_view, &phi_a_view)];
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl), module: &module, entry_point: entry,
+            })
+        };
+
+        // ---- Render ----
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+            },
+            count: None,
+        };
+        let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("level_set_render_bgl"),
+            entries: &[
+                texture_entry(6),
+                texture_entry(7),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("level_set_render_bg"), layout: &render_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&phi_a_view) },
+                wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(&sim.velocity_view) },
+                wgpu::BindGroupEntry { binding: 8, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+        let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+        });
+        let render_pipeline = crate::create_render_pipeline(device, &render_pl, &module, format, "fs_level_set");
+
+        Self {
+            params, param_buffer, phi: [phi_a, phi_b],
+            _views: vec![phi_a_view, phi_b_view, extended_view],
+            bind_groups,
+            extrapolate: make_compute("extrapolate"),
+            advect: make_compute("advect"),
+            redistance: make_compute("redistance"),
+            render_bg, render_pipeline,
+        }
+    }
+
+    /// Extrapolate, advect, redistance, then the fluid projection with the
+    /// cells outside the liquid as air.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, sim: &'a Simulation, pipes: &'a FluidPipelines) {
+        let grid = self.params.grid_size.div_ceil(8);
+        c.set_bind_group(0, &self.bind_groups[0], &[]);
+        c.set_pipeline(&self.extrapolate);
+        c.dispatch_workgroups(grid, grid, 1);
+        c.set_pipeline(&self.advect);
+        c.dispatch_workgroups(grid, grid, 1);
+        c.set_pipeline(&self.redistance);
+        for i in 0..REDISTANCE_SWEEPS as usize {
+            c.set_bind_group(0, &self.bind_groups[1 - i % 2], &[]);
+            c.dispatch_workgroups(grid, grid, 1);
+        }
+        sim.project(c, pipes);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("level_set_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.render_pipeline);
+        r.set_bind_group(0, &self.render_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+
+    /// Put the dam back up, with the liquid at rest.
+    fn reset(&self, queue: &wgpu::Queue, sim: &Simulation) {
+        let grid = self.params.grid_size;
+        crate::upload_field(queue, &self.phi[0], grid, &dam_over_pool(grid));
+        crate::upload_field(queue, &sim.velocity, grid, &vec![[0.0; 4]; (grid * grid) as usize]);
+    }
+}
+
+/// The level set window loop, taking over from `run` once the window and
+/// device exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let grid = config.grid_size;
+    let workarounds = crate::shader_workarounds(&config, &adapter);
+    let gpu = mode::Gpu::new(&config, window, surface, adapter, device, queue);
+    let (device, queue, format) = (&gpu.device, &gpu.queue, gpu.format());
+    let win_size = gpu.window.inner_size();
+
+    // ---- Grid ----
+    // The fluid's own bind group and projection; the obstacle mask and
+    // emitters stay empty
+    let fluid_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute_shader"),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
+    });
+    let (_obstacle_tex, obstacle_view) = create_storage_tex(device, grid);
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let sim_params = SimParams {
+        liquid: 1, boundary: Boundary::Walls.code(), cell_size: crate::cell_size_for(win_size),
+        gravity: if config.gravity == [0.0, 0.0] { [0.0, DEFAULT_GRAVITY] } else { config.gravity },
+        gravity_by_dye: 0,
+        ..crate::base_params(&config, crate::cell_size_for(win_size))
+    };
+    let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&sim_params),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let compute_bgl = crate::create_compute_bgl(device);
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let fluid_pipes = FluidPipelines::new(device, &compute_pl, &fluid_module, workarounds);
+    let shared = simulation::Shared {
+        device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    if !matches!(config.pressure_solver, SolverKind::Jacobi { .. }) {
+        eprintln!("Level set mode projects with Jacobi: the free surface isn't supported by the other solvers");
+    }
+    let sim = Simulation::new(&shared, &fluid_module, SolverKind::Jacobi { iterations: PRESSURE_ITERATIONS, half_res: false });
+
+    let level_set = LevelSet::new(device, format, &sim, grid, sim_params.gravity, config.surface_tension);
+    level_set.reset(queue, &sim);
+    eprintln!("Level set mode: a dam over a pool on a {}² grid", grid);
+
+    let state = LevelSetMode {
+        level_set, sim, fluid_pipes, param_buffer, stored_gravity: sim_params.gravity, sim_params,
+        cursor: [0.0; 2], last_cursor: None,
+    };
+    mode::run(gpu, event_loop, grid, state).await;
+}
+
+/// The level set window's state between frames.
+struct LevelSetMode {
+    level_set: LevelSet,
+    sim: Simulation,
+    fluid_pipes: FluidPipelines,
+    sim_params: SimParams,
+    param_buffer: wgpu::Buffer,
+    cursor: [f32; 2],
+    last_cursor: Option<[f32; 2]>,
+    stored_gravity: [f32; 2],
+}
+
+impl Mode for LevelSetMode {
+    const STEP: &'static str = "level_set_step";
+
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue) {
+        match code {
+            KeyCode::KeyR => {
+                self.level_set.reset(queue, &self.sim);
+                eprintln!("Dam reset");
+            }
+            KeyCode::KeyG | KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::ArrowLeft | KeyCode::ArrowRight
+            | KeyCode::Minus | KeyCode::Equal => {
+                // The liquid is all there is, so Shift + G has nothing to switch
+                crate::adjust_gravity(code, false, &mut self.sim_params, &mut self.stored_gravity);
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse(&mut self, button: MouseButton, pressed: bool) {
+        let mode = if button == MouseButton::Left { 1 } else { 2 };
+        let p = &mut self.level_set.params;
+        if pressed {
+            p.mouse_mode = mode;
+        } else if p.mouse_mode == mode {
+            p.mouse_mode = 0;
+        }
+        self.last_cursor = None;
+    }
+
+    fn cursor(&mut self, cursor: [f32; 2]) {
+        self.cursor = cursor;
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.sim_params.cell_size = crate::cell_size_for(size);
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue, _aspect: f32, _paused: bool) {
+        let cursor = self.cursor;
+        let p = &mut self.level_set.params;
+        p.dt = self.sim_params.dt;
+        p.cell_size = self.sim_params.cell_size;
+        p.gravity = self.sim_params.gravity;
+        p.mouse_delta = self.last_cursor.map_or([0.0; 2], |last| [cursor[0] - last[0], cursor[1] - last[1]]);
+        p.mouse_pos = cursor;
+        if p.mouse_mode != 0 {
+            self.last_cursor = Some(cursor);
+        }
+        queue.write_buffer(&self.level_set.param_buffer, 0, bytemuck::bytes_of(&self.level_set.params));
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.sim_params));
+    }
+
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        self.level_set.step(c, &self.sim, &self.fluid_pipes);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.level_set.render(encoder, view);
+    }
+}
@@ -1,11 +1,129 @@
-use std::sync::Arc;
+// Standard error goes nowhere in the browser, so there the messages go to
+// the console instead. Defined ahead of the modules so it covers them too.
+#[cfg(target_arch = "wasm32")]
+macro_rules! eprintln {
+    ($($arg:tt)*) => {
+        web_sys::console::log_1(&format!($($arg)*).into())
+    };
+}
+
+mod arrows;
+mod benchmark;
+mod cfl;
+#[cfg(not(target_arch = "wasm32"))]
+mod checkpoint;
+mod colormap;
+mod config;
+mod decay;
+mod detail;
+mod diagnostics;
+mod distort;
+mod dye;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+#[cfg(not(target_arch = "wasm32"))]
+mod export_script;
+mod exposure;
+mod field_export;
+mod fire;
+mod flip;
+#[cfg(not(target_arch = "wasm32"))]
+mod frame_capture;
+#[cfg(not(target_arch = "wasm32"))]
+mod frame_hash;
+#[cfg(not(target_arch = "wasm32"))]
+mod gallery;
+#[cfg(not(target_arch = "wasm32"))]
+mod gif;
+#[cfg(not(target_arch = "wasm32"))]
+mod gif_capture;
+mod glass;
+mod gpu_error;
+#[cfg(not(target_arch = "wasm32"))]
+mod hot_reload;
+#[cfg(not(target_arch = "wasm32"))]
+mod hud;
+mod image_seed;
+mod inspector;
+mod jpeg;
+mod level_set;
+mod mode;
+mod motion_blur;
+mod mouse_force;
+mod noise;
+mod paint;
+mod palette;
+mod png;
+mod particles;
+mod pressure;
+mod readback;
+mod reaction;
+#[cfg(not(target_arch = "wasm32"))]
+mod recording;
+mod remote;
+mod rng;
+mod rod;
+mod scene;
+#[cfg(not(target_arch = "wasm32"))]
+mod screenshot;
+#[cfg(not(target_arch = "wasm32"))]
+mod session;
+mod shading;
+mod shallow;
+mod simulation;
+mod snapshot;
+mod sph;
+#[cfg(not(target_arch = "wasm32"))]
+mod stats;
+mod streamlines;
+mod supersample;
+mod tonemap;
+#[cfg(not(target_arch = "wasm32"))]
+mod tune;
+mod two_phase;
+#[cfg(not(target_arch = "wasm32"))]
+mod velocity_import;
+mod volume;
+mod vortex;
+mod wall;
+mod wall_shear;
+mod workarounds;
+
+use arrows::Arrows;
+use cfl::Cfl;
+#[cfg(not(target_arch = "wasm32"))]
+use checkpoint::Checkpoint;
+use config::Config;
+use decay::Decay;
+use detail::Detail;
+use diagnostics::Diagnostics;
+use distort::Distort;
+use dye::DyeBrush;
+use exposure::Exposure;
+use gpu_error::ErrorLog;
+use inspector::Inspector;
+use motion_blur::MotionBlur;
+use noise::NoiseSpec;
+use particles::Particles;
+use rng::Seed;
+use scene::{Boundary, GravityMode, Scene};
+use shading::Shading;
+use simulation::{CompareView, DiffField, FieldView, Fields, FluidPipelines, Reference, Simulation};
+use snapshot::Snapshot;
+use streamlines::Streamlines;
+use supersample::Supersample;
+use tonemap::Tonemap;
+use vortex::VortexParticles;
+use std::sync::{Arc, Mutex};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use winit::{
     dpi::LogicalSize,
     event::*,
-    event_loop::EventLoop,
+    event_loop::{ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
     window::WindowBuilder,
 };
+use workarounds::Workarounds;
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
@@ -13,17 +131,219 @@ struct SimParams {
     grid_size: u32,
     mouse_down: u32,
     dt: f32,
+    /// Kinematic viscosity in cells²/s, applied by the implicit diffusion
+    /// pass. 0 skips it.
     viscosity: f32,
-    dissipation: f32,
+    /// Per-step dye multiplier, from `Decay::factor(dt)`.
+    dye_decay: f32,
     add_strength: f32,
     mouse_pos: [f32; 2],
     mouse_delta: [f32; 2],
     radius: f32,
-    _pad0: f32,
-    _pad1: [f32; 4],
+    emitter_count: u32,
+    vorticity: f32,
+    /// Per-step velocity multiplier, from `Decay::factor(dt)`.
+    velocity_decay: f32,
+    /// Physical (dx, dy) of a cell. dx is 1, so velocities stay in x-cells
+    /// per second; dy follows the window aspect so non-square windows don't
+    /// stretch the flow.
+    cell_size: [f32; 2],
+    /// Dye / speed below these is cleared after decaying; 0 keeps everything.
+    dye_threshold: f32,
+    velocity_threshold: f32,
+    /// Nonzero while the cost heat map is shown; kernels then count their
+    /// work into the cost buffer.
+    instrument: u32,
+    /// Dye diffusivity in cells²/s, diffused alongside velocity.
+    diffusion: f32,
+    /// Buoyant acceleration (cells/s²) per unit of dye, pulling down, and
+    /// per unit of temperature, pushing up.
+    smoke_weight: f32,
+    heat_lift: f32,
+    /// Per-step temperature multiplier, from `Decay::factor(dt)`.
+    temperature_decay: f32,
+    /// Temperature added by the mouse each frame.
+    heat_strength: f32,
+    /// Body force in cells/s² (+y down). 0 skips the pass.
+    gravity: [f32; 2],
+    /// Nonzero scales `gravity` by the local dye (`GravityMode::Dye`).
+    gravity_by_dye: u32,
+    /// Seconds since the scene was applied. Drives moving obstacles and
+    /// wind gusts.
+    time: f32,
+    /// Ambient wind in cells/s that the fluid is gently pulled toward. 0
+    /// for none. Gusts scale it by `1 + wind_gust * sin(2π time / period)`.
+    wind: [f32; 2],
+    wind_gust: f32,
+    wind_gust_period: f32,
+    /// Red, green and blue dye the mouse adds per unit of `add_strength`,
+    /// from `DyeColor::rgb` each frame.
+    dye_color: [f32; 3],
+    /// What happens at the grid edges, a `Boundary::code`.
+    boundary: u32,
+    /// Nonzero while the stirring rod stands in for the mouse: the fluid
+    /// under it is dragged along at `mouse_delta / dt` instead of pushed.
+    rod: u32,
+    /// Peak pull of the gravity well at the domain center, in cells/s² per
+    /// unit of dye; 0 turns it off.
+    well: f32,
+    _pad: [u32; 2],
+    /// Extra downward acceleration (cells/s²) per unit of red, green and
+    /// blue dye, on top of `smoke_weight`; negative makes a color rise.
+    dye_density: [f32; 3],
+    /// Curl noise stirred into sheared flow downstream of obstacles, as an
+    /// acceleration in cells/s²; 0 turns it off.
+    wake: f32,
+    /// Nonzero in the FLIP liquid mode: cells with too little liquid (the
+    /// dye amount) are air, at zero pressure.
+    liquid: u32,
+    /// Sub-grid turbulence produced per unit of |curl| per second, which
+    /// the renderer turns into fine dye breakup; 0 turns it off.
+    subgrid: f32,
+    /// Density a unit of dye adds to the clear fluid's 1, and a unit of
+    /// temperature takes away. Nonzero weights the Jacobi projection by
+    /// 1/ρ, so under uniform gravity heavy fluid sinks through light.
+    dye_mass: f32,
+    heat_expansion: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuEmitter {
+    pos: [f32; 2],
+    velocity: [f32; 2],
+    color: [f32; 3],
+    radius: f32,
+    density: f32,
+    temperature: f32,
+    _pad: [f32; 2],
+}
+
+const MAX_EMITTERS: usize = 16;
+const WINDOW_TITLE: &str = "WGPU Fluid Simulation";
+/// Uniform for the render pass: what the difference view shows.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ViewParams {
+    /// Part of the domain drawn, as (x, y, w, h) in texture coordinates.
+    /// All of it except on a video-wall follower.
+    viewport: [f32; 4],
+    /// `DiffField` as an index, in declaration order.
+    diff_field: u32,
+    /// Difference drawn at full color.
+    diff_scale: f32,
+    /// `SimParams::time`, animating the sub-grid detail and fading the
+    /// advected detail layers.
+    time: f32,
+    /// Strength of the advected detail layer (J), 0 = off.
+    detail: f32,
+    /// Wall shear rate in 1/s outlined thickest and hottest (S), 0 = no
+    /// outline.
+    wall_shear: f32,
+    /// `FieldView` as an index, in declaration order.
+    field: u32,
+    /// Field value drawn at full color.
+    field_scale: f32,
+    /// 1 while drawing into the tonemapper's HDR target (`--tonemap`).
+    hdr: u32,
+    /// `Colormap` as an index, in declaration order (Shift + Q).
+    colormap: u32,
+    /// Dithering of the window's colors: 0 off, else `dither_mode`.
+    dither: u32,
+    /// Dye shading (Shift + V), a `shading::Shading`.
+    shading: u32,
+    /// Direction the lit shading's light comes from (Shift + L), radians
+    /// counterclockwise from the right of the screen.
+    light_angle: f32,
+}
+
+/// Layout, sampler, view uniform, colormap LUTs and glass background shared
+/// by every render bind group.
+struct RenderBindings {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    view: wgpu::Buffer,
+    lut: colormap::Lut,
+    background: glass::Background,
+}
+
+impl RenderBindings {
+    /// Bind group for drawing `sim`, with `other` (the comparison clone or
+    /// the stored reference) bound for the difference view.
+    fn create(
+        &self, device: &wgpu::Device, obstacles: &wgpu::TextureView, trail: &wgpu::TextureView,
+        detail: &wgpu::TextureView, sim: &Simulation, other: Fields,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("render_bg"), layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&sim.density_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&sim.velocity_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(trail) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(obstacles) },
+                wgpu::BindGroupEntry { binding: 5, resource: sim.cost.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(other.density) },
+                wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(other.velocity) },
+                wgpu::BindGroupEntry { binding: 8, resource: self.view.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 9, resource: wgpu::BindingResource::TextureView(&sim.curl_view) },
+                wgpu::BindGroupEntry { binding: 10, resource: wgpu::BindingResource::TextureView(detail) },
+                wgpu::BindGroupEntry { binding: 11, resource: wgpu::BindingResource::TextureView(&sim.pressure_view) },
+                wgpu::BindGroupEntry { binding: 12, resource: wgpu::BindingResource::TextureView(&sim.divergence_view) },
+                wgpu::BindGroupEntry { binding: 13, resource: wgpu::BindingResource::TextureView(&self.lut.view) },
+                wgpu::BindGroupEntry { binding: 14, resource: wgpu::BindingResource::TextureView(&self.background.view) },
+            ],
+        })
+    }
+}
+
+/// How `dither` in the shaders treats a target: 1 for 8-bit unorm, 2 for
+/// 8-bit sRGB, 0 (no dithering) for anything finer, which doesn't band.
+fn dither_mode(format: wgpu::TextureFormat) -> u32 {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Bgra8Unorm => 1,
+        wgpu::TextureFormat::Rgba8UnormSrgb | wgpu::TextureFormat::Bgra8UnormSrgb => 2,
+        _ => 0,
+    }
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    module: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    fragment_entry: &str,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(fragment_entry), layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module, entry_point: "vs_fullscreen", buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module, entry_point: fragment_entry,
+            targets: &[Some(wgpu::ColorTargetState {
+                format, blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
 }
 
-const GRID_SIZE: u32 = 256;
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half as u32) & 0x8000) << 16;
+    let exp = ((half >> 10) & 0x1F) as u32;
+    let frac = (half & 0x3FF) as u32;
+    let bits = match exp {
+        0 => sign, // zero (subnormals flushed, matching f32_to_f16)
+        31 => sign | 0x7F80_0000 | (frac << 13),
+        _ => sign | ((exp + 127 - 15) << 23) | (frac << 13),
+    };
+    f32::from_bits(bits)
+}
 
 fn f32_to_f16(value: f32) -> u16 {
     let bits = value.to_bits();
@@ -35,6 +355,21 @@ fn f32_to_f16(value: f32) -> u16 {
     else { (sign | ((exp as u32) << 10) | (frac >> 13)) as u16 }
 }
 
+/// Cell size for a window: x cells are the unit, y cells are stretched or
+/// squashed to the window's aspect ratio.
+fn cell_size_for(size: winit::dpi::PhysicalSize<u32>) -> [f32; 2] {
+    [1.0, size.height.max(1) as f32 / size.width.max(1) as f32]
+}
+
+/// 0 for the `1` key through 8 for `9`, then 9 for `0`.
+fn digit_index(code: KeyCode) -> Option<usize> {
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5,
+        KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9, KeyCode::Digit0,
+    ];
+    DIGITS.iter().position(|&d| d == code)
+}
+
 fn create_storage_tex(device: &wgpu::Device, size: u32) -> (wgpu::Texture, wgpu::TextureView) {
     let tex = device.create_texture(&wgpu::TextureDescriptor {
         label: None,
@@ -53,193 +388,822 @@ fn create_storage_tex(device: &wgpu::Device, size: u32) -> (wgpu::Texture, wgpu:
     (tex, view)
 }
 
+/// Upload per-cell RGBA values into a square `Rgba16Float` grid texture.
+fn upload_field(queue: &wgpu::Queue, tex: &wgpu::Texture, size: u32, data: &[[f32; 4]]) {
+    let half: Vec<[u16; 4]> = data.iter().map(|v| v.map(f32_to_f16)).collect();
+    upload_texels(queue, tex, size, &half);
+}
+
+/// Upload half-float texels as they are into a square `Rgba16Float` grid
+/// texture.
+fn upload_texels(queue: &wgpu::Queue, tex: &wgpu::Texture, size: u32, half: &[[u16; 4]]) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: tex, mip_level: 0,
+            origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All,
+        },
+        bytemuck::cast_slice(half),
+        wgpu::ImageDataLayout {
+            offset: 0, bytes_per_row: Some(size * 8), rows_per_image: Some(size),
+        },
+        wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+    );
+}
+
+/// Copy a square `Rgba16Float` grid texture back to the CPU. Blocks until
+/// the GPU has caught up, so only for occasional use (snapshots).
+#[cfg(not(target_arch = "wasm32"))]
+fn read_field(device: &wgpu::Device, queue: &wgpu::Queue, tex: &wgpu::Texture, size: u32) -> Vec<[f32; 4]> {
+    read_texels(device, queue, tex, size).into_iter().map(|t| t.map(f16_to_f32)).collect()
+}
+
+/// `read_field`'s half floats, as they are (checkpoints).
+#[cfg(not(target_arch = "wasm32"))]
+fn read_texels(device: &wgpu::Device, queue: &wgpu::Queue, tex: &wgpu::Texture, size: u32) -> Vec<[u16; 4]> {
+    let row_bytes = size * 8;
+    let padded = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback"),
+        size: (padded * size) as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("readback") });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: tex, mip_level: 0,
+            origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(padded), rows_per_image: Some(size) },
+        },
+        wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = slice.get_mapped_range();
+    let mut out = Vec::with_capacity((size * size) as usize);
+    for row in data.chunks(padded as usize) {
+        out.extend(row[..row_bytes as usize].chunks_exact(8).map(|t| {
+            std::array::from_fn(|c| u16::from_le_bytes([t[c * 2], t[c * 2 + 1]]))
+        }));
+    }
+    drop(data);
+    buffer.unmap();
+    out
+}
+
+/// GPU resources a scene writes into, and the session seed its noise is
+/// drawn from.
+struct SceneTargets<'a> {
+    velocity: &'a wgpu::Texture,
+    density: &'a wgpu::Texture,
+    obstacles: &'a wgpu::Texture,
+    emitters: &'a wgpu::Buffer,
+    seed: Seed,
+}
+
+fn scene_targets<'a>(sim: &'a Simulation, obstacles: &'a wgpu::Texture, emitters: &'a wgpu::Buffer, seed: Seed) -> SceneTargets<'a> {
+    SceneTargets { velocity: &sim.velocity, density: &sim.density, obstacles, emitters, seed }
+}
+
+/// A clone of the simulation stepped with a second solver (K), so the
+/// solver's effect can be judged from exactly the same starting flow.
+struct Comparison {
+    sim: Simulation,
+    /// Draws the clone, with the primary's fields bound as the other state.
+    render_bg: wgpu::BindGroup,
+    view: CompareView,
+}
+
+/// Reset the simulation to `scene`: velocity and dye are re-seeded from its
+/// blobs (or from `start`, if given), emitters and obstacles replaced, and
+/// its param overrides applied on top of `base` and the default
+/// (dye, velocity, temperature) `decays`.
+fn apply_scene(
+    queue: &wgpu::Queue, scene: &Scene, start: Option<&Snapshot>, targets: &SceneTargets,
+    params: &mut SimParams, base: &SimParams, decays: (Decay, Decay, Decay),
+) {
+    let g = base.grid_size;
+    let (density, velocity) = match start {
+        Some(snap) => {
+            let snap = snap.resample(g);
+            (snap.density, snap.velocity)
+        }
+        None => scene.initial_fields(g, targets.seed),
+    };
+    let dens_data: Vec<[f32; 4]> = density.iter().map(|&[r, g, b]| [r, 0.0, g, b]).collect();
+    let vel_data: Vec<[f32; 4]> = velocity.iter().map(|v| [v[0], v[1], 0.0, 0.0]).collect();
+    upload_field(queue, targets.density, g, &dens_data);
+    upload_field(queue, targets.velocity, g, &vel_data);
+    upload_obstacles(queue, scene, targets.obstacles, g, 0.0);
+
+    if scene.emitters.len() > MAX_EMITTERS {
+        eprintln!("Scene has {} emitters, only the first {} are used", scene.emitters.len(), MAX_EMITTERS);
+    }
+    let emitter_count = upload_emitters(queue, targets.emitters, g, &scene.emitters);
+    *params = SimParams { emitter_count, ..scene_params(&scene.params, params, base, decays) };
+    eprintln!("Scene: {}{}", scene.name, if scene.description.is_empty() { String::new() } else { format!(" — {}", scene.description) });
+}
+
+/// Write up to `MAX_EMITTERS` of `emitters` into the emitter buffer for a
+/// `grid`² simulation. Returns how many, for `SimParams::emitter_count`.
+fn upload_emitters<'a>(queue: &wgpu::Queue, buffer: &wgpu::Buffer, grid: u32, emitters: impl IntoIterator<Item = &'a scene::Emitter>) -> u32 {
+    let g = grid as f32;
+    let emitters: Vec<GpuEmitter> = emitters.into_iter().take(MAX_EMITTERS).map(|e| GpuEmitter {
+        pos: [e.pos[0] * g, e.pos[1] * g],
+        velocity: e.velocity,
+        color: e.color,
+        radius: e.radius * g,
+        density: e.density,
+        temperature: e.temperature,
+        _pad: [0.0; 2],
+    }).collect();
+    if !emitters.is_empty() {
+        queue.write_buffer(buffer, 0, bytemuck::cast_slice(&emitters));
+    }
+    emitters.len() as u32
+}
+
+/// A scene's param overrides on top of `base` and the default (dye,
+/// velocity, temperature) `decays`, keeping the window's cell size, the
+/// mouse and the heat map from `params`, and its emitter count.
+fn scene_params(o: &scene::ParamOverrides, params: &SimParams, base: &SimParams, decays: (Decay, Decay, Decay)) -> SimParams {
+    let dt = o.dt.unwrap_or(base.dt);
+    let dye_decay = o.dye_decay.unwrap_or(decays.0);
+    let velocity_decay = o.velocity_decay.unwrap_or(decays.1);
+    let temperature_decay = o.temperature_decay.unwrap_or(decays.2);
+    SimParams {
+        mouse_down: params.mouse_down,
+        mouse_pos: params.mouse_pos,
+        mouse_delta: params.mouse_delta,
+        cell_size: params.cell_size,
+        instrument: params.instrument,
+        emitter_count: params.emitter_count,
+        dt,
+        dye_decay: dye_decay.factor(dt),
+        velocity_decay: velocity_decay.factor(dt),
+        temperature_decay: temperature_decay.factor(dt),
+        dye_threshold: dye_decay.threshold(),
+        velocity_threshold: velocity_decay.threshold(),
+        viscosity: o.viscosity.unwrap_or(base.viscosity),
+        diffusion: o.diffusion.unwrap_or(base.diffusion),
+        smoke_weight: o.smoke_weight.unwrap_or(base.smoke_weight),
+        dye_density: o.dye_density.unwrap_or(base.dye_density),
+        heat_lift: o.heat_lift.unwrap_or(base.heat_lift),
+        heat_strength: o.heat_strength.unwrap_or(base.heat_strength),
+        add_strength: o.add_strength.unwrap_or(base.add_strength),
+        radius: o.radius.unwrap_or(base.radius),
+        vorticity: o.vorticity.unwrap_or(base.vorticity),
+        gravity: o.gravity.unwrap_or(base.gravity),
+        gravity_by_dye: o.gravity_mode.map_or(base.gravity_by_dye, |m| (m == GravityMode::Dye) as u32),
+        wind: o.wind.unwrap_or(base.wind),
+        wind_gust: o.wind_gust.unwrap_or(base.wind_gust),
+        wind_gust_period: o.wind_gust_period.unwrap_or(base.wind_gust_period),
+        boundary: o.boundary.map_or(base.boundary, Boundary::code),
+        well: o.well.unwrap_or(base.well),
+        wake: o.wake.unwrap_or(base.wake),
+        subgrid: o.subgrid.unwrap_or(base.subgrid),
+        dye_mass: o.dye_mass.unwrap_or(base.dye_mass),
+        heat_expansion: o.heat_expansion.unwrap_or(base.heat_expansion),
+        ..*base
+    }
+}
+
+/// Rasterize `scene`'s obstacles at time `t` into the obstacle texture:
+/// solid mask, signed distance, and the solid's velocity in grid cells per
+/// second.
+fn upload_obstacles(queue: &wgpu::Queue, scene: &Scene, tex: &wgpu::Texture, grid: u32, t: f32) {
+    let (mask, velocity) = scene.obstacle_fields(grid, t);
+    let sdf = scene::signed_distance(&mask, grid);
+    let data: Vec<[f32; 4]> = mask.iter().zip(&sdf).zip(&velocity).map(|((&m, &d), v)| [m, d, v[0], v[1]]).collect();
+    upload_field(queue, tex, grid, &data);
+}
+
+/// The snapshot to start from, if `--snapshot`, `--demo` or `--seed-image`
+/// asked for one.
+fn load_start_state(config: &Config) -> Option<Snapshot> {
+    if let Some(path) = &config.seed_image {
+        let image = std::fs::read(path).map_err(|e| e.to_string()).and_then(|bytes| image_seed::decode(&bytes));
+        let image = image.map_err(|e| eprintln!("Failed to load seed image {}: {}", path, e)).ok()?;
+        eprintln!("Seed image: {} ({}×{}, {})", path, image.width, image.height, config.seed_image_channel.name());
+        return Some(image_seed::seed(&image, config.grid_size, config.seed_image_channel, config.seed_image_velocity));
+    }
+    match &config.snapshot {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(path) => load_snapshot_file(path),
+        _ if config.demo => Snapshot::decode(snapshot::DEMO).map_err(|e| eprintln!("Failed to load snapshot demo: {}", e)).ok(),
+        _ => None,
+    }
+}
+
+/// Read and decode a snapshot file saved with F9, logging any failure.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_snapshot_file(path: &str) -> Option<Snapshot> {
+    let bytes = std::fs::read(path).map_err(|e| eprintln!("Failed to read snapshot {}: {}", path, e)).ok()?;
+    Snapshot::decode(&bytes).map_err(|e| eprintln!("Failed to load snapshot {}: {}", path, e)).ok()
+}
+
+/// Read and decode a checkpoint saved with F5 for a `grid`² simulation,
+/// logging any failure.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_checkpoint_file(path: &str, grid: u32) -> Option<Checkpoint> {
+    let bytes = std::fs::read(path).map_err(|e| eprintln!("Failed to read checkpoint {}: {}", path, e)).ok()?;
+    let checkpoint = Checkpoint::decode(&bytes).map_err(|e| eprintln!("Failed to load checkpoint {}: {}", path, e)).ok()?;
+    if checkpoint.grid != grid {
+        eprintln!("Checkpoint {} is of a {}² grid; run with --grid {} to load it", path, checkpoint.grid, checkpoint.grid);
+        return None;
+    }
+    Some(checkpoint)
+}
+
+/// Read a velocity field file (`--velocity-field`, F8 or dropped on the
+/// window) into each of `textures`. Returns whether it loaded.
+#[cfg(not(target_arch = "wasm32"))]
+fn import_velocity<'a>(queue: &wgpu::Queue, path: &str, grid: u32, textures: impl IntoIterator<Item = &'a wgpu::Texture>) -> bool {
+    match velocity_import::load(path, grid) {
+        Ok(velocity) => {
+            let texels: Vec<[f32; 4]> = velocity.iter().map(|v| [v[0], v[1], 0.0, 0.0]).collect();
+            for tex in textures {
+                upload_field(queue, tex, grid, &texels);
+            }
+            eprintln!("Velocity field: {}", path);
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to load velocity field {}: {}", path, e);
+            false
+        }
+    }
+}
+
+/// Add a dropped picture's dye (see `image_seed::dye`) to a simulation's
+/// density texture. Blocks on the readback, like F9.
+#[cfg(not(target_arch = "wasm32"))]
+fn add_dye(device: &wgpu::Device, queue: &wgpu::Queue, density: &wgpu::Texture, grid: u32, dye: &[[f32; 3]]) {
+    let mut texels = read_field(device, queue, density, grid);
+    for (t, d) in texels.iter_mut().zip(dye) {
+        // Density holds (red, temperature, green, blue)
+        t[0] += d[0];
+        t[2] += d[1];
+        t[3] += d[2];
+    }
+    upload_field(queue, density, grid, &texels);
+}
+
+/// Carry on from `checkpoint`: its scene's emitters and obstacles, then its
+/// fields and params over them, keeping the window's cell size, the mouse
+/// and the heat map. Returns the scene.
+#[cfg(not(target_arch = "wasm32"))]
+fn restore_checkpoint(
+    queue: &wgpu::Queue, checkpoint: &Checkpoint, sim: &Simulation, targets: &SceneTargets,
+    params: &mut SimParams, base: &SimParams, decays: (Decay, Decay, Decay),
+) -> Scene {
+    let scene = load_scene_or_default(&checkpoint.scene, None);
+    apply_scene(queue, &scene, None, targets, params, base, decays);
+    checkpoint.restore(queue, sim);
+    *params = SimParams {
+        mouse_down: params.mouse_down,
+        mouse_pos: params.mouse_pos,
+        mouse_delta: params.mouse_delta,
+        cell_size: params.cell_size,
+        instrument: params.instrument,
+        ..checkpoint.params
+    };
+    eprintln!("Restored the checkpoint at t = {:.2} s", params.time);
+    scene
+}
+
+/// The `--hash-frames` hasher, if asked for. The header records what a run
+/// has to match for its hashes to be comparable.
+#[cfg(not(target_arch = "wasm32"))]
+fn frame_hasher(config: &Config, adapter: &wgpu::Adapter, solver: &str) -> Option<frame_hash::FrameHasher> {
+    let every = config.hash_frames?;
+    let info = adapter.get_info();
+    let header = format!(
+        "grid {}, scene {}, solver {}, GPU {} ({:?})",
+        config.grid_size, config.scene, solver, info.name, info.backend,
+    );
+    Some(frame_hash::FrameHasher::new(every, config.hash_out.as_deref(), &header).unwrap_or_else(|e| {
+        eprintln!("Can't write frame hashes to {}: {}", config.hash_out.as_deref().unwrap_or_default(), e);
+        std::process::exit(1);
+    }))
+}
+
+/// The `--stats-json` stream, if asked for.
+#[cfg(not(target_arch = "wasm32"))]
+fn stats_stream(config: &Config, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<stats::StatsStream> {
+    let target = config.stats_json.as_deref()?;
+    Some(stats::StatsStream::open(target, device, queue).unwrap_or_else(|e| {
+        eprintln!("Can't stream frame stats to {}: {}", target, e);
+        std::process::exit(1);
+    }))
+}
+
+/// Load a scene by builtin name or path, falling back to the default scene.
+fn load_scene_or_default(name: &str, init_noise: Option<NoiseSpec>) -> Scene {
+    let mut scene = Scene::load(name).unwrap_or_else(|e| {
+        eprintln!("Failed to load scene {}", e);
+        Scene::load("default").expect("builtin default scene is valid")
+    });
+    if init_noise.is_some() {
+        scene.noise = init_noise;
+    }
+    scene
+}
+
 fn main() {
-    env_logger::init();
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        env_logger::init();
+
+        // WSL2/WSLg has flaky Wayland. Force X11 by clearing WAYLAND_DISPLAY.
+        // Must happen BEFORE EventLoop::new().
+        std::env::set_var("WAYLAND_DISPLAY", "");
+
+        pollster::block_on(run());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        // Panics would otherwise vanish silently in the browser.
+        std::panic::set_hook(Box::new(|info| {
+            web_sys::console::error_1(&info.to_string().into());
+        }));
+        // wgpu's validation errors and warnings come through `log`
+        if log::set_logger(&ConsoleLog).is_ok() {
+            log::set_max_level(log::LevelFilter::Warn);
+        }
+        wasm_bindgen_futures::spawn_local(run());
+    }
+}
 
-    // WSL2/WSLg has flaky Wayland. Force X11 by clearing WAYLAND_DISPLAY.
-    // Must happen BEFORE EventLoop::new().
-    std::env::set_var("WAYLAND_DISPLAY", "");
+/// The browser console as the `log` backend, in place of env_logger.
+#[cfg(target_arch = "wasm32")]
+struct ConsoleLog;
+
+#[cfg(target_arch = "wasm32")]
+impl log::Log for ConsoleLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let text = format!("{}: {}", record.target(), record.args()).into();
+        match record.level() {
+            log::Level::Error => web_sys::console::error_1(&text),
+            _ => web_sys::console::warn_1(&text),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+    adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            // Only what the adapter has: browsers never offer the
+            // native-only format features, and asking for a missing one
+            // fails the whole request. Timestamps time the passes for
+            // --stats-json
+            required_features: adapter.features()
+                & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES | wgpu::Features::TIMESTAMP_QUERY),
+            required_limits: wgpu::Limits {
+                max_storage_textures_per_shader_stage: 8,
+                ..wgpu::Limits::default()
+            },
+        },
+        None,
+    )
+    .await
+    .expect("Failed to create device")
+}
+
+/// The `--workarounds` override, or whatever the adapter's driver needs.
+fn shader_workarounds(config: &Config, adapter: &wgpu::Adapter) -> Workarounds {
+    let (workarounds, how) = match config.workarounds {
+        Some(w) => (w, "forced"),
+        None => (Workarounds::detect(&adapter.get_info()), "detected"),
+    };
+    eprintln!("Shader workarounds: {} ({})", workarounds, how);
+    workarounds
+}
+
+/// Layout of the fluid kernels' bind group: params, the seven field
+/// textures, emitters, obstacles, curl and the cost counters.
+fn create_compute_bgl(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let mut compute_entries: Vec<_> = (0..8u32).map(|i| wgpu::BindGroupLayoutEntry {
+        binding: i,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: if i == 0 {
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false, min_binding_size: None,
+            }
+        } else {
+            wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::ReadWrite,
+                format: wgpu::TextureFormat::Rgba16Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            }
+        },
+        count: None,
+    }).collect();
+    compute_entries.push(wgpu::BindGroupLayoutEntry {
+        binding: 8, visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false, min_binding_size: None,
+        },
+        count: None,
+    });
+    compute_entries.push(wgpu::BindGroupLayoutEntry {
+        binding: 9, visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+        },
+        count: None,
+    });
+    compute_entries.push(wgpu::BindGroupLayoutEntry {
+        binding: 10, visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    });
+    compute_entries.push(wgpu::BindGroupLayoutEntry {
+        binding: 11, visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        },
+        count: None,
+    });
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("compute_bgl"),
+        entries: &compute_entries,
+    })
+}
+
+/// Layout of the render bind group: the fields `render.wgsl` draws from,
+/// the other state for the difference view and the view uniform.
+fn create_render_bgl(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("render_bgl"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false, min_binding_size: None,
+                },
+                count: None,
+            },
+            // The other state's dye and velocity, for the difference view
+            wgpu::BindGroupLayoutEntry {
+                binding: 6, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 7, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 8, visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false, min_binding_size: None,
+                },
+                count: None,
+            },
+            // Curl and sub-grid turbulence, for the dye breakup
+            wgpu::BindGroupLayoutEntry {
+                binding: 9, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            // Advected detail layers
+            wgpu::BindGroupLayoutEntry {
+                binding: 10, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            // Pressure and divergence, for the field views (Q)
+            wgpu::BindGroupLayoutEntry {
+                binding: 11, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 12, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            // Colormap LUTs (Shift + Q)
+            wgpu::BindGroupLayoutEntry {
+                binding: 13, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+            // Background for glass shading (Shift + W)
+            wgpu::BindGroupLayoutEntry {
+                binding: 14, visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+/// The mouse's motion this frame in physical units, for `DyeBrush::rgb`.
+fn stroke_direction(params: &SimParams) -> [f32; 2] {
+    [params.mouse_delta[0] * params.cell_size[0], params.mouse_delta[1] * params.cell_size[1]]
+}
+
+/// Most simulation steps N / `--substeps` run per frame.
+const MAX_SUBSTEPS: u32 = 16;
+
+/// `params` for one of `n` substeps of a frame: `dt` split n ways, along
+/// with the mouse's once-per-frame impulse, dye and heat, so a frame adds
+/// the same in total however it's split. The rod's motion is a velocity
+/// and `with_dt` already scales it.
+fn substep_params(params: &SimParams, n: u32) -> SimParams {
+    let k = 1.0 / n as f32;
+    let mut p = cfl::with_dt(params, params.dt * k);
+    if p.rod == 0 {
+        p.mouse_delta = p.mouse_delta.map(|d| d * k);
+    }
+    p.add_strength *= k;
+    p.heat_strength *= k;
+    p
+}
+
+/// `SimParams` before any scene overrides.
+fn base_params(config: &Config, cell_size: [f32; 2]) -> SimParams {
+    let grid = config.grid_size;
+    SimParams {
+        grid_size: grid, mouse_down: 0, dt: 0.016, viscosity: config.viscosity,
+        dye_decay: 1.0, add_strength: 2.0, mouse_pos: [grid as f32 * 0.5; 2],
+        mouse_delta: [0.0, 0.0], radius: 35.0, emitter_count: 0, vorticity: config.vorticity,
+        velocity_decay: 1.0, cell_size,
+        dye_threshold: 0.0, velocity_threshold: 0.0, instrument: 0,
+        diffusion: config.diffusion, smoke_weight: 0.0, heat_lift: config.buoyancy,
+        temperature_decay: 1.0, heat_strength: 1.0,
+        gravity: config.gravity, gravity_by_dye: (config.gravity_mode == GravityMode::Dye) as u32, time: 0.0,
+        wind: config.wind, wind_gust: config.wind_gust, wind_gust_period: config.wind_gust_period,
+        dye_color: DyeBrush::new(config.dye_color, config.dye_cycle).rgb(0.0, [0.0, 0.0]), boundary: config.boundary.code(),
+        rod: 0, well: config.well, _pad: [0; 2], dye_density: config.dye_density, wake: config.wake,
+        liquid: 0, subgrid: config.subgrid, dye_mass: config.dye_mass, heat_expansion: config.heat_expansion,
+    }
+}
+
+/// Gravity keys: G toggles it (restoring `stored` when off), Shift+G flips
+/// what it acts on, the arrows point it (keeping its strength) and - / =
+/// halve and double it.
+fn adjust_gravity(code: KeyCode, shift: bool, params: &mut SimParams, stored: &mut [f32; 2]) {
+    let off = params.gravity == [0.0, 0.0];
+    match code {
+        KeyCode::KeyG if shift => params.gravity_by_dye ^= 1,
+        KeyCode::KeyG if off => params.gravity = *stored,
+        KeyCode::KeyG => {
+            *stored = params.gravity;
+            params.gravity = [0.0, 0.0];
+        }
+        KeyCode::Minus | KeyCode::Equal => {
+            let k = if code == KeyCode::Minus { 0.5 } else { 2.0 };
+            *stored = stored.map(|g| g * k);
+            params.gravity = params.gravity.map(|g| g * k);
+        }
+        _ => {
+            let g = if off { *stored } else { params.gravity };
+            let strength = (g[0] * g[0] + g[1] * g[1]).sqrt();
+            params.gravity = match code {
+                KeyCode::ArrowUp => [0.0, -strength],
+                KeyCode::ArrowDown => [0.0, strength],
+                KeyCode::ArrowLeft => [-strength, 0.0],
+                _ => [strength, 0.0],
+            };
+        }
+    }
+    let target = if params.gravity_by_dye != 0 { "dye" } else { "all fluid" };
+    if params.gravity == [0.0, 0.0] {
+        eprintln!("Gravity: off (acts on {} when on)", target);
+    } else {
+        eprintln!("Gravity: ({}, {}) cells/s² on {}", params.gravity[0], params.gravity[1], target);
+    }
+}
+
+async fn run() {
+    let config = Config::from_args();
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(addr) = config.serve.clone() {
+        return serve(config, &addr).await;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(dir) = config.thumbnails.clone() {
+        return gallery::run(config, &dir).await;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(dir) = config.export.clone() {
+        return export::run(config, &dir).await;
+    }
 
     let event_loop = EventLoop::new().unwrap();
     let window = Arc::new(
         WindowBuilder::new()
-            .with_title("WGPU Fluid Simulation")
-            .with_inner_size(LogicalSize::new(800.0, 800.0))
+            .with_title(WINDOW_TITLE)
+            .with_inner_size(LogicalSize::new(config.window_size.0, config.window_size.1))
             .build(&event_loop)
             .unwrap(),
     );
 
     let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
+        backends: config.backends,
         ..Default::default()
     });
 
+    #[cfg(not(target_arch = "wasm32"))]
     let surface = instance.create_surface(window.clone()).unwrap();
 
-    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
+    // On the web, winit creates a canvas that isn't attached to the page yet.
+    // Put it in the document body and build the surface straight from it.
+    #[cfg(target_arch = "wasm32")]
+    let surface = {
+        use winit::platform::web::WindowExtWebSys;
+        let canvas = window.canvas().expect("winit window has no canvas");
+        web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.body())
+            .and_then(|body| body.append_child(&canvas).ok())
+            .expect("Failed to attach canvas to document body");
+        instance.create_surface(wgpu::SurfaceTarget::Canvas(canvas)).unwrap()
+    };
+
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: config.power_preference,
         force_fallback_adapter: false,
         compatible_surface: Some(&surface),
-    }))
+    })
+    .await
     .expect("No suitable GPU adapter found");
 
     eprintln!("GPU: {}", adapter.get_info().name);
     eprintln!("Backend: {:?}", adapter.get_info().backend);
 
-    let (device, queue) = pollster::block_on(adapter.request_device(
-        &wgpu::DeviceDescriptor {
-            label: None,
-            required_features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-            required_limits: wgpu::Limits {
-                max_storage_textures_per_shader_stage: 8,
-                ..wgpu::Limits::default()
-            },
-        },
-        None,
-    ))
-    .expect("Failed to create device");
+    let (device, queue) = request_device(&adapter).await;
+    if config.volume.is_some() {
+        return volume::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    if config.flip.is_some() {
+        return flip::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    if config.sph.is_some() {
+        return sph::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    if config.shallow {
+        return shallow::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    if config.two_phase.is_some() {
+        return two_phase::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    if config.level_set {
+        return level_set::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    if config.fire {
+        return fire::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    if config.gray_scott.is_some() {
+        return reaction::run(config, event_loop, window, surface, adapter, device, queue).await;
+    }
+    let workarounds = shader_workarounds(&config, &adapter);
+
+    // Everything from here to the end of setup runs in one error scope, so a
+    // bad descriptor or shader is reported once with its full message.
+    let error_log = Arc::new(Mutex::new(ErrorLog::default()));
+    gpu_error::install_uncaptured_handler(&device, &error_log);
+    gpu_error::push_scopes(&device);
 
     let caps = surface.get_capabilities(&adapter);
     let format = caps.formats.iter().find(|f| f.is_srgb()).copied().unwrap_or(caps.formats[0]);
     let win_size = window.inner_size();
 
-    let mut config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    // Screenshots copy from the swapchain, where it allows that
+    let copyable = cfg!(not(target_arch = "wasm32")) && caps.usages.contains(wgpu::TextureUsages::COPY_SRC);
+    let mut surface_config = wgpu::SurfaceConfiguration {
+        usage: if copyable { wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC } else { wgpu::TextureUsages::RENDER_ATTACHMENT },
         format,
         width: win_size.width.max(1),
         height: win_size.height.max(1),
-        present_mode: wgpu::PresentMode::AutoVsync,
+        present_mode: config.present_mode,
         alpha_mode: caps.alpha_modes[0],
         view_formats: vec![],
         desired_maximum_frame_latency: 2,
     };
-    surface.configure(&device, &config);
+    surface.configure(&device, &surface_config);
 
     // ---- Shaders ----
     let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("compute_shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("../fluid.wgsl").into()),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
     });
 
     let render_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("render_shader"),
-        source: wgpu::ShaderSource::Wgsl(r#"
-@group(0) @binding(0) var render_density_tex: texture_2d<f32>;
-@group(0) @binding(1) var render_sampler: sampler;
-@group(0) @binding(2) var render_velocity_tex: texture_2d<f32>;
-
-struct VSOut {
-    @builtin(position) pos: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-}
-
-@vertex
-fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
-    var positions = array<vec2<f32>, 3>(
-        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
-    );
-    var uvs = array<vec2<f32>, 3>(
-        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
-    );
-    var out: VSOut;
-    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
-    out.uv = uvs[vid];
-    return out;
-}
-
-// HSV to RGB conversion
-fn hsv2rgb(h: f32, s: f32, v: f32) -> vec3<f32> {
-    let c = v * s;
-    let hp = h * 6.0;
-    let x = c * (1.0 - abs(hp % 2.0 - 1.0));
-    let m = v - c;
-    var rgb: vec3<f32>;
-    if (hp < 1.0) { rgb = vec3<f32>(c, x, 0.0); }
-    else if (hp < 2.0) { rgb = vec3<f32>(x, c, 0.0); }
-    else if (hp < 3.0) { rgb = vec3<f32>(0.0, c, x); }
-    else if (hp < 4.0) { rgb = vec3<f32>(0.0, x, c); }
-    else if (hp < 5.0) { rgb = vec3<f32>(x, 0.0, c); }
-    else { rgb = vec3<f32>(c, 0.0, x); }
-    return rgb + vec3<f32>(m, m, m);
-}
-
-@fragment
-fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
-    let dens = textureSampleLevel(render_density_tex, render_sampler, in.uv, 0.0).x;
-    let vel = textureSampleLevel(render_velocity_tex, render_sampler, in.uv, 0.0).xy;
-
-    // Velocity magnitude and direction
-    let speed = length(vel);
-    let angle = atan2(vel.y, vel.x); // -pi to pi
-
-    // Map velocity direction to hue (0..1), speed controls saturation
-    let hue = fract(angle / 6.2832 + 0.5);  // normalize -pi..pi to 0..1
-    let sat = clamp(speed * 3.0, 0.2, 1.0);  // more speed = more saturated
-    let intensity = clamp(dens, 0.0, 1.0);
-
-    // Base color from velocity direction
-    let base_color = hsv2rgb(hue, sat, 1.0);
-
-    // Glow: boost bright areas with a power curve
-    let glow = pow(intensity, 0.6);        // softer falloff for thin wisps
-    let bloom = pow(intensity, 3.0) * 0.8; // hot-white core on dense areas
-
-    // Subtle dark background gradient (not pure black)
-    let bg = vec3<f32>(0.01, 0.01, 0.03);
-
-    // Composite: colored fluid + white bloom on top
-    let fluid = base_color * glow;
-    let white_bloom = vec3<f32>(bloom, bloom, bloom);
-    let color = bg * (1.0 - intensity) + fluid + white_bloom;
-
-    return vec4<f32>(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
-}
-"#.into()),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../render.wgsl").into()),
     });
 
     // ---- Textures ----
-    let wg = ((GRID_SIZE + 7) / 8, (GRID_SIZE + 7) / 8);
+    let grid = config.grid_size;
 
-    let (_vel, vel_view) = create_storage_tex(&device, GRID_SIZE);
-    let (_vel_tmp, vel_tmp_view) = create_storage_tex(&device, GRID_SIZE);
-    let (dens_tex, dens_view) = create_storage_tex(&device, GRID_SIZE);
-    let (_dens_tmp, dens_tmp_view) = create_storage_tex(&device, GRID_SIZE);
-    let (_press, press_view) = create_storage_tex(&device, GRID_SIZE);
-    let (_press_tmp, press_tmp_view) = create_storage_tex(&device, GRID_SIZE);
-    let (_div, div_view) = create_storage_tex(&device, GRID_SIZE);
+    let (obstacle_tex, obstacle_view) = create_storage_tex(&device, grid);
 
-    // Seed density blob
-    {
-        let g = GRID_SIZE;
-        let mut data = vec![[0u16; 4]; (g * g) as usize];
-        let (cx, cy, r) = (g as f32 / 2.0, g as f32 / 2.0, 30.0f32);
-        for y in 0..g {
-            for x in 0..g {
-                let (dx, dy) = (x as f32 - cx, y as f32 - cy);
-                let val = (1.0 - (dx * dx + dy * dy) / (r * r)).max(0.0);
-                data[(y * g + x) as usize][0] = f32_to_f16(val);
-            }
-        }
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &dens_tex, mip_level: 0,
-                origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All,
-            },
-            bytemuck::cast_slice(&data),
-            wgpu::ImageDataLayout {
-                offset: 0, bytes_per_row: Some(g * 8), rows_per_image: Some(g),
-            },
-            wgpu::Extent3d { width: g, height: g, depth_or_array_layers: 1 },
-        );
-    }
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
 
     // ---- Uniform buffer ----
-    let mut sim_params = SimParams {
-        grid_size: GRID_SIZE, mouse_down: 0, dt: 0.016, viscosity: 0.0001,
-        dissipation: 0.998, add_strength: 2.0, mouse_pos: [128.0, 128.0],
-        mouse_delta: [0.0, 0.0], radius: 35.0, _pad0: 0.0, _pad1: [0.0; 4],
-    };
+    let base_params = base_params(&config, cell_size_for(win_size));
+    let base_decays = (config.dye_decay, config.velocity_decay, config.temperature_decay);
+    let mut sim_params = base_params;
 
+    // Written every frame, so the scene's params land before the first step
     let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
         label: Some("params"),
         contents: bytemuck::bytes_of(&sim_params),
@@ -247,148 +1211,419 @@ fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
     });
 
     // ---- Bind group layouts ----
-    let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("compute_bgl"),
-        entries: &(0..8u32).map(|i| wgpu::BindGroupLayoutEntry {
-            binding: i,
-            visibility: wgpu::ShaderStages::COMPUTE,
-            ty: if i == 0 {
-                wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false, min_binding_size: None,
-                }
-            } else {
-                wgpu::BindingType::StorageTexture {
-                    access: wgpu::StorageTextureAccess::ReadWrite,
-                    format: wgpu::TextureFormat::Rgba16Float,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                }
-            },
-            count: None,
-        }).collect::<Vec<_>>(),
+    let compute_bgl = create_compute_bgl(&device);
+
+    let render_bgl = create_render_bgl(&device);
+
+    // ---- Pipelines ----
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
     });
 
-    let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("render_bgl"),
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1, visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 2, visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
-                },
-                count: None,
-            },
-        ],
+    // Replaced by a shader hot reload, which the web doesn't have
+    #[cfg_attr(target_arch = "wasm32", allow(unused_mut))]
+    let mut fluid_pipes = FluidPipelines::new(&device, &compute_pl, &compute_shader, workarounds);
+    // Latest good fluid.wgsl, for solvers built after a hot reload
+    #[cfg_attr(target_arch = "wasm32", allow(unused_mut))]
+    let mut fluid_module = compute_shader;
+
+    // ---- HDR and tonemapping (--tonemap, U, Y) ----
+    // The fluid and what's drawn into it go to the HDR target; the overlays
+    // go over the tonemapped window
+    let mut tonemap = config.tonemap.map(|spec| {
+        Tonemap::new(&device, format, surface_config.width, surface_config.height, spec, config.dither)
     });
+    let scene_format = if tonemap.is_some() { tonemap::HDR_FORMAT } else { format };
+    // Supersampling (--supersample) draws the scene bigger still and
+    // averages it down into whichever of those comes next
+    // Motion blur (Shift + B, --motion-blur) takes the frame at window
+    // size, after any downsample and before tonemapping
+    let mut motion_blur = MotionBlur::new(&device, scene_format, config.motion_blur.unwrap_or(motion_blur::DEFAULT_DECAY), config.dither);
+    if config.motion_blur.is_some() {
+        motion_blur.toggle(&device, surface_config.width, surface_config.height);
+    }
+    let mut supersample = config.supersample.map(|factor| {
+        Supersample::new(&device, scene_format, surface_config.width, surface_config.height, factor, config.dither)
+    });
+
+    // Replaced by a hot reload too
+    #[cfg_attr(target_arch = "wasm32", allow(unused_mut))]
+    let [mut render_pipeline, mut heat_pipeline, mut diff_pipeline, mut field_pipeline, mut glass_pipeline] =
+        ["fs_draw", "fs_heat", "fs_diff", "fs_field", "fs_glass"]
+            .map(|entry| create_render_pipeline(&device, &render_pl, &render_shader, scene_format, entry));
+    // Long exposure (L), drawing fs_draw into its own target
+    let mut exposure = Exposure::new(&device, &render_pl, &render_shader, scene_format, config.exposure);
+
+    // ---- Simulation ----
+    let shared = simulation::Shared {
+        device: &device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    let mut sim = Simulation::new(&shared, &fluid_module, config.pressure_solver);
+    sim.advection = config.advection;
+    eprintln!("Pressure solver: {}", sim.solver.name());
+    if (config.dye_mass != 0.0 || config.heat_expansion != 0.0) && !config.pressure_solver.weights_density() {
+        eprintln!("Variable density needs --solver jacobi: {} solves as if the density were uniform", config.pressure_solver);
+    }
+    // Clone stepped with `compare_solver` while comparing (K)
+    let mut compare: Option<Comparison> = None;
+
+    // ---- Particles ----
+    let mut particles = Particles::new(&device, &sim.velocity_view, &sim.density_view, &obstacle_view, grid, config.seed, scene_format);
+    let mut vortex = (config.vortex_particles > 0).then(|| {
+        VortexParticles::new(
+            &device, &sim.velocity_view, &sim.curl_view, &obstacle_view, grid,
+            config.vortex_particles, config.vortex_life, config.seed,
+        )
+    });
+
+    // ---- Render-time detail ----
+    let mut detail = Detail::new(&device, &queue, &sim.velocity_view, grid, win_size);
+    // What J turns the detail layer back on to
+    let mut stored_detail = if config.detail > 0.0 { config.detail } else { detail::DEFAULT_STRENGTH };
+    let wall_shear_scale = config.wall_shear.unwrap_or(wall_shear::DEFAULT_SCALE);
+
+    // ---- Timestep limit ----
+    let mut cfl = config.cfl.map(|limit| Cfl::new(&device, &sim.velocity_view, grid, limit));
+    particles.set_cell_size(sim_params.cell_size);
+
+    // ---- Energy, enstrophy and dye mass (E, --diagnostics) ----
+    let mut diagnostics = Diagnostics::new(&device, &sim.velocity_view, &sim.density_view, grid, format, config.diagnostics.as_deref());
+
+    // ---- Scene ----
+    let mut scene_name = config.scene.clone();
+    let mut dye_brush = DyeBrush::new(config.dye_color, config.dye_cycle);
+    let mut mouse_force = config.mouse_force;
+    // Stirring rod (I), standing in for the mouse while on
+    let mut rod = config.rod;
+    // Simulation steps per frame (N / Shift + N)
+    let mut substeps = config.substeps;
+    let mut start_state = load_start_state(&config);
+    let mut scene = load_scene_or_default(&scene_name, config.init_noise);
+    apply_scene(&queue, &scene, start_state.as_ref(), &scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed), &mut sim_params, &base_params, base_decays);
+    // The restored session's parameters and toggles go over the scene's
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(session) = &config.session {
+        session.restore_params(&mut sim_params);
+        particles.enabled = session.trails.unwrap_or(particles.enabled);
+        particles.color_mode = session.particle_colors.unwrap_or(particles.color_mode);
+        particles.reseed_mode = session.reseed.unwrap_or(particles.reseed_mode);
+        if let Some(on) = session.heat_map {
+            sim_params.instrument = on as u32;
+        }
+    }
+    // What F6 goes back to: the last checkpoint saved or loaded
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut checkpoint_path = config.checkpoint.clone();
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(checkpoint) = checkpoint_path.as_deref().and_then(|path| load_checkpoint_file(path, grid)) {
+        scene_name = checkpoint.scene.clone();
+        start_state = None;
+        scene = restore_checkpoint(&queue, &checkpoint, &sim, &scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed), &mut sim_params, &base_params, base_decays);
+    }
+    // An imported velocity field goes over whatever the fluid started with.
+    // Frozen, a copy of it is put back over the simulation's every frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut velocity_path = config.velocity_field.clone();
+    #[cfg(not(target_arch = "wasm32"))]
+    let frozen_velocity = config.freeze_velocity.then(|| create_storage_tex(&device, grid).0);
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        sim.frozen = frozen_velocity.is_some();
+        if let Some(path) = &velocity_path {
+            import_velocity(&queue, path, grid, std::iter::once(&sim.velocity).chain(&frozen_velocity));
+        }
+    }
+    eprintln!("Scenes (number keys): {}", scene::BUILTIN.iter().enumerate()
+        .map(|(i, (name, _))| format!("{}={}", i + 1, name)).collect::<Vec<_>>().join(" "));
 
     // ---- Bind groups ----
-    let compute_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("compute_bg"), layout: &compute_bgl,
-        entries: &[
-            wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
-            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&vel_view) },
-            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&vel_tmp_view) },
-            wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&dens_view) },
-            wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&dens_tmp_view) },
-            wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&press_view) },
-            wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&press_tmp_view) },
-            wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(&div_view) },
-        ],
+    let mut diff_field = DiffField::Dye;
+    let viewport = config.wall_follower.as_ref().map_or([0.0, 0.0, 1.0, 1.0], |(_, tile)| tile.viewport(grid));
+    let lut = colormap::Lut::new(&device, &queue);
+    let (mut colormap, mut custom_colormap) = colormap::startup(&lut, &queue, config.colormap, config.colormap_file.as_deref());
+    let mut view_params = ViewParams {
+        viewport, diff_field: diff_field as u32, diff_scale: diff_field.default_scale(), time: 0.0, detail: config.detail,
+        wall_shear: config.wall_shear.unwrap_or(0.0), field: FieldView::Dye as u32, field_scale: 1.0, hdr: config.tonemap.is_some() as u32,
+        colormap: colormap as u32, dither: (config.dither && tonemap.is_none()) as u32 * dither_mode(format),
+        shading: config.shading as u32, light_angle: config.light.to_radians(),
+    };
+
+    // ---- Streamline overlay (Z, --streamlines) ----
+    let seeds = config.streamlines.unwrap_or(streamlines::DEFAULT_SEEDS);
+    let mut streamlines = Streamlines::new(&device, &sim.velocity_view, &obstacle_view, grid, format, seeds, viewport);
+    streamlines.showing = config.streamlines.is_some();
+
+    // ---- Velocity arrows (A, --arrows) ----
+    let per_side = config.arrows.unwrap_or(arrows::DEFAULT_PER_SIDE);
+    let mut arrows = Arrows::new(&device, &sim.velocity_view, &obstacle_view, grid, format, per_side, viewport);
+    arrows.showing = config.arrows.is_some();
+    arrows.set_cell_size(&queue, sim_params.cell_size);
+
+    // ---- Field inspector (Shift + I, --inspect) ----
+    let inspect_mode = if config.inspect { inspector::Mode::Values } else { inspector::Mode::Off };
+    let mut inspector = Inspector::new(&device, format, grid, viewport, inspect_mode);
+
+    // ---- Image distortion (--image) ----
+    let mut distort = config.image.as_deref().and_then(|path| {
+        let image = std::fs::read(path).map_err(|e| e.to_string()).and_then(|bytes| png::decode(&bytes));
+        let distort = image.and_then(|image| {
+            let distort = Distort::new(&device, &queue, &sim.velocity_view, &obstacle_view, grid, scene_format, viewport, &image)?;
+            eprintln!("Image distortion: {} ({}×{}, R puts it back together)", path, image.width, image.height);
+            Ok(distort)
+        });
+        distort.map_err(|e| eprintln!("Failed to load image {}: {}", path, e)).ok()
     });
+    let render_bindings = RenderBindings {
+        layout: render_bgl,
+        sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        }),
+        view: device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("view_params"),
+            contents: bytemuck::bytes_of(&view_params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        }),
+        lut,
+        background: glass::Background::new(&device, &queue, config.background.as_deref()),
+    };
+
+    // State to diff against when not comparing (B / D)
+    let mut reference = Reference::new(&device, grid);
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(snap) = config.reference.as_deref().and_then(load_snapshot_file) {
+        let snap = snap.resample(grid);
+        upload_field(&queue, &reference.density, grid, &snap.density.iter().map(|&[r, g, b]| [r, 0.0, g, b]).collect::<Vec<_>>());
+        upload_field(&queue, &reference.velocity, grid, &snap.velocity.iter().map(|v| [v[0], v[1], 0.0, 0.0]).collect::<Vec<_>>());
+        reference.valid = true;
+    }
+    let mut show_diff = false;
+    let mut field_view = FieldView::Dye;
+    let mut glass = config.glass;
+    let mut dither = config.dither;
+    let mut shading = config.shading;
 
-    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-        mag_filter: wgpu::FilterMode::Linear,
-        min_filter: wgpu::FilterMode::Linear,
-        address_mode_u: wgpu::AddressMode::ClampToEdge,
-        address_mode_v: wgpu::AddressMode::ClampToEdge,
-        ..Default::default()
-    });
+    let mut render_bg = render_bindings.create(&device, &obstacle_view, particles.trail_view(), detail.view(), &sim, reference.fields());
 
-    let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("render_bg"), layout: &render_bgl,
-        entries: &[
-            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&dens_view) },
-            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
-            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&vel_view) },
-        ],
-    });
+    if let Some(err) = gpu_error::pop_scopes(&device, "setup").await {
+        let info = adapter.get_info();
+        eprintln!("{}\n(GPU: {}, backend: {:?})", err, info.name, info.backend);
+        #[cfg(not(target_arch = "wasm32"))]
+        std::process::exit(1);
+        #[cfg(target_arch = "wasm32")]
+        return;
+    }
 
-    // ---- Pipelines ----
-    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
-    });
-    let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
-    });
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut shader_watcher = None;
+    #[cfg(not(target_arch = "wasm32"))]
+    if config.hot_reload {
+        // Wakes the loop, so an edit is picked up while paused too
+        let proxy = event_loop.create_proxy();
+        match hot_reload::ShaderWatcher::new(move || {
+            let _ = proxy.send_event(());
+        }) {
+            Ok(watcher) => {
+                eprintln!("Watching shaders for changes in {}", env!("CARGO_MANIFEST_DIR"));
+                shader_watcher = Some(watcher);
+            }
+            Err(e) => eprintln!("Can't watch the shaders for changes: {}", e),
+        }
+    }
 
-    let make_compute = |entry: &str| {
-        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some(entry), layout: Some(&compute_pl),
-            module: &compute_shader, entry_point: entry,
-        })
-    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut frame_hasher = frame_hasher(&config, &adapter, &sim.label());
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut steps: u64 = 0;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut tuner: Option<tune::Tuner> = None;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut stats = stats_stream(&config, &device, &queue);
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut hud = hud::Hud::new(&device, &queue, format, &adapter.get_info(), config.hud);
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut screenshots = screenshot::Screenshots::new(copyable);
+    #[cfg(not(target_arch = "wasm32"))]
+    let screenshot_key = config.screenshot_key;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut recorder = recording::Recorder::new(config.record_fps, copyable);
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = &config.record {
+        recorder.start(path.clone(), format, surface_config.width, surface_config.height);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut gif_capture = gif_capture::GifCapture::new(config.gif_frames, config.gif_fps, config.gif_width, copyable);
 
-    let add_source_pipe = make_compute("add_source");
-    let advect_vel_pipe = make_compute("advect_vel");
-    let copy_vel_pipe = make_compute("copy_vel");
-    let advect_dens_pipe = make_compute("advect_dens");
-    let copy_dens_pipe = make_compute("copy_dens");
-    let divergence_pipe = make_compute("compute_divergence");
-    let pressure_a_pipe = make_compute("pressure_jacobi_a");
-    let pressure_b_pipe = make_compute("pressure_jacobi_b");
-    let gradient_pipe = make_compute("subtract_gradient");
-
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("render_pipeline"), layout: Some(&render_pl),
-        vertex: wgpu::VertexState {
-            module: &render_shader, entry_point: "vs_fullscreen", buffers: &[],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &render_shader, entry_point: "fs_draw",
-            targets: &[Some(wgpu::ColorTargetState {
-                format, blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-        }),
-        primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
-        multiview: None,
-    });
+    // ---- Video wall ----
+    let wall_leader = config.wall_leader.as_deref().map(|addr| wall::Leader::listen(addr).unwrap_or_else(|e| {
+        eprintln!("Can't listen for wall followers on {}: {}", addr, e);
+        std::process::exit(1);
+    }));
+    // A follower only draws what the leader sends, so it runs no sim or particles
+    let wall_follower = config.wall_follower.as_ref().map(|(addr, tile)| wall::Follower::connect(addr, *tile, grid).unwrap_or_else(|e| {
+        eprintln!("Can't reach wall leader {}: {}", addr, e);
+        std::process::exit(1);
+    }));
+
+    // ---- Remote simulation ----
+    // A client draws what the server sends and forwards its input instead
+    let client = config.connect.as_deref().map(|addr| remote::Client::connect(addr, grid).unwrap_or_else(|e| {
+        eprintln!("Can't reach simulation server {}: {}", addr, e);
+        std::process::exit(1);
+    }));
+    if let Some(client) = &client {
+        client.send(remote::Input::CellSize(sim_params.cell_size));
+    }
+    let simulating = wall_follower.is_none() && client.is_none();
+
+    // ---- Obstacle painting ----
+    let mut painter = paint::ObstaclePainter::new(&device, &obstacle_view, grid);
 
     // ---- State ----
+    let mut modifiers = winit::keyboard::ModifiersState::empty();
+    let mut palette = palette::Palette::new(cfg!(not(target_arch = "wasm32")).then_some(config.screenshot_key));
+    // Some(true) while Shift-dragging solid cells in, Some(false) while erasing
+    let mut painting: Option<bool> = None;
+    // What G turns gravity back on to
+    let mut stored_gravity = if config.gravity == [0.0, 0.0] { [0.0, 40.0] } else { config.gravity };
     let mut last_mouse: Option<(f32, f32)> = None;
     let mut window_size = window.inner_size();
+    #[cfg(not(target_arch = "wasm32"))]
     let mut frame_count: u64 = 0;
+    // Space: stop stepping. While paused the window only redraws after input
+    let mut paused = false;
 
     eprintln!("Starting event loop...");
 
     // ---- Event loop ----
     event_loop.run(move |event, target| {
+        if let Event::WindowEvent { event: ref e, .. } = event {
+            if paused && !matches!(e, WindowEvent::RedrawRequested) {
+                window.request_redraw();
+            }
+        }
         match event {
             Event::WindowEvent { event: ref win_event, .. } => match win_event {
-                WindowEvent::CloseRequested => target.exit(),
+                WindowEvent::CloseRequested => {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if config.keeps_session() {
+                        let (width, height) = window_size.to_logical::<u32>(window.scale_factor()).into();
+                        session::Session {
+                            scene: Some(scene_name.clone()),
+                            window_size: Some((width, height)),
+                            substeps: Some(substeps),
+                            dye_color: Some(dye_brush.color),
+                            dye_cycle: Some(dye_brush.cycle),
+                            mouse_force: Some(mouse_force),
+                            rod: rod.map(|r| rod::Rod::new(r.mass, r.drag)),
+                            detail: Some(view_params.detail),
+                            wall_shear: (view_params.wall_shear > 0.0).then_some(view_params.wall_shear),
+                            gravity: Some(sim_params.gravity),
+                            gravity_mode: Some(if sim_params.gravity_by_dye != 0 { GravityMode::Dye } else { GravityMode::Uniform }),
+                            boundary: Boundary::from_code(sim_params.boundary),
+                            viscosity: Some(sim_params.viscosity),
+                            vorticity: Some(sim_params.vorticity),
+                            trails: Some(particles.enabled),
+                            particle_colors: Some(particles.color_mode),
+                            reseed: Some(particles.reseed_mode),
+                            heat_map: Some(sim_params.instrument != 0),
+                        }.save();
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    recorder.stop(&device);
+                    target.exit();
+                }
+
+                WindowEvent::Resized(new_size) if new_size.width > 0 && new_size.height > 0 => {
+                    surface_config.width = new_size.width;
+                    surface_config.height = new_size.height;
+                    window_size = *new_size;
+                    surface.configure(&device, &surface_config);
+                    exposure.cancel();
+                    if let Some(tonemap) = &mut tonemap {
+                        tonemap.resize(&device, new_size.width, new_size.height);
+                    }
+                    if let Some(supersample) = &mut supersample {
+                        supersample.resize(&device, &queue, new_size.width, new_size.height);
+                    }
+                    motion_blur.resize(&device, new_size.width, new_size.height);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    recorder.resize(&device, new_size.width, new_size.height);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    gif_capture.resize(&device, new_size.width, new_size.height);
+                    sim_params.cell_size = cell_size_for(window_size);
+                    particles.set_cell_size(sim_params.cell_size);
+                    arrows.set_cell_size(&queue, sim_params.cell_size);
+                    if let Some(client) = &client {
+                        client.send(remote::Input::CellSize(sim_params.cell_size));
+                    }
+                }
+
+                WindowEvent::ModifiersChanged(m) => modifiers = m.state(),
+
+                // A velocity field dropped on the window is imported, a
+                // gradient strip becomes the colormap and any other picture
+                // is added as dye
+                WindowEvent::DroppedFile(path) => {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("exr") || e.eq_ignore_ascii_case("npy")) {
+                        let path = path.to_string_lossy().into_owned();
+                        let compare_velocity = compare.as_ref().map(|cmp| &cmp.sim.velocity);
+                        if import_velocity(&queue, &path, grid, std::iter::once(&sim.velocity).chain(compare_velocity).chain(&frozen_velocity)) {
+                            velocity_path = Some(path);
+                        }
+                        return;
+                    }
+                    let path = path.to_string_lossy();
+                    #[cfg(not(target_arch = "wasm32"))]
+                    match std::fs::read(&*path).map_err(|e| e.to_string()).and_then(|bytes| image_seed::decode(&bytes)) {
+                        // Colormap gradients are at least four times as wide as tall
+                        Ok(image) if image.width >= image.height * 4 => {}
+                        Ok(image) => {
+                            let dye = image_seed::dye(&image, grid, window_size.width as f32 / window_size.height.max(1) as f32);
+                            for density in std::iter::once(&sim.density).chain(compare.as_ref().map(|cmp| &cmp.sim.density)) {
+                                add_dye(&device, &queue, density, grid, &dye);
+                            }
+                            eprintln!("Dye: {} ({}×{})", path, image.width, image.height);
+                            return;
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to load {}: {}", path, e);
+                            return;
+                        }
+                    }
+                    match render_bindings.lut.load(&queue, &path) {
+                        Ok(()) => {
+                            (colormap, custom_colormap) = (colormap::Colormap::Custom, true);
+                            view_params.colormap = colormap as u32;
+                            queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                            eprintln!("Colormap: {}", path);
+                        }
+                        Err(e) => eprintln!("Failed to load colormap {}: {}", path, e),
+                    }
+                }
 
-                WindowEvent::Resized(new_size) => {
-                    if new_size.width > 0 && new_size.height > 0 {
-                        config.width = new_size.width;
-                        config.height = new_size.height;
-                        window_size = *new_size;
-                        surface.configure(&device, &config);
+                WindowEvent::MouseInput { state, button, .. } if painting.is_some() || (modifiers.shift_key() && *state == ElementState::Pressed) => {
+                    if *state == ElementState::Pressed {
+                        let solid = *button != MouseButton::Right;
+                        painting = Some(solid);
+                        match &client {
+                            Some(client) => client.send(remote::Input::Paint { pos: sim_params.mouse_pos, solid }),
+                            None => painter.stroke(&queue, sim_params.mouse_pos, solid),
+                        }
+                    } else {
+                        painting = None;
+                        match &client {
+                            Some(client) => client.send(remote::Input::PaintEnd),
+                            None => painter.end(),
+                        }
                     }
                 }
 
@@ -402,21 +1637,31 @@ fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
                 }
 
                 WindowEvent::CursorMoved { position, .. } => {
-                    let sx = GRID_SIZE as f32 / window_size.width.max(1) as f32;
-                    let sy = GRID_SIZE as f32 / window_size.height.max(1) as f32;
+                    let sx = grid as f32 / window_size.width.max(1) as f32;
+                    let sy = grid as f32 / window_size.height.max(1) as f32;
                     let mx = position.x as f32 * sx;
                     let my = position.y as f32 * sy;
 
                     if let Some((px, py)) = last_mouse {
-                        sim_params.mouse_delta = [mx - px, my - py];
+                        sim_params.mouse_delta = mouse_force.map([mx - px, my - py]);
                     }
                     sim_params.mouse_pos = [mx, my];
                     last_mouse = Some((mx, my));
+                    let size = [window_size.width, window_size.height];
+                    inspector.hover(&queue, Some([position.x as f32, position.y as f32]), size);
+                    if let Some(solid) = painting {
+                        match &client {
+                            Some(client) => client.send(remote::Input::Paint { pos: [mx, my], solid }),
+                            None => painter.stroke(&queue, [mx, my], solid),
+                        }
+                    }
                 }
 
+                WindowEvent::CursorLeft { .. } => inspector.hover(&queue, None, [window_size.width, window_size.height]),
+
                 WindowEvent::Touch(touch) => {
-                    let sx = GRID_SIZE as f32 / window_size.width.max(1) as f32;
-                    let sy = GRID_SIZE as f32 / window_size.height.max(1) as f32;
+                    let sx = grid as f32 / window_size.width.max(1) as f32;
+                    let sy = grid as f32 / window_size.height.max(1) as f32;
                     let mx = touch.location.x as f32 * sx;
                     let my = touch.location.y as f32 * sy;
 
@@ -429,7 +1674,7 @@ fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
                         }
                         TouchPhase::Moved => {
                             if let Some((px, py)) = last_mouse {
-                                sim_params.mouse_delta = [mx - px, my - py];
+                                sim_params.mouse_delta = mouse_force.map([mx - px, my - py]);
                             }
                             sim_params.mouse_pos = [mx, my];
                             last_mouse = Some((mx, my));
@@ -443,23 +1688,435 @@ fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
                     }
                 }
 
+                WindowEvent::KeyboardInput {
+                    event: event @ KeyEvent { physical_key: PhysicalKey::Code(pressed), state: ElementState::Pressed, .. },
+                    ..
+                } => {
+                    // Ctrl + P opens the command palette, which then takes
+                    // every key until it runs a command or closes
+                    let command = if palette.is_open() || (modifiers.control_key() && *pressed == KeyCode::KeyP) {
+                        let command = palette.key(*pressed, event.text.as_deref(), modifiers.control_key());
+                        window.set_title(&palette.title(WINDOW_TITLE));
+                        match command {
+                            Some(command) => command,
+                            None => return,
+                        }
+                    } else if event.repeat {
+                        return;
+                    } else {
+                        palette::Command::Key(*pressed, modifiers.shift_key())
+                    };
+                    let mut scene_pick = None;
+                    match &command {
+                        palette::Command::Scene(i) => scene_pick = Some(*i),
+                        palette::Command::Key(code, shift) => match code {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        code if *code == screenshot_key => screenshots.request(),
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F10 => recorder.toggle(&device, format, surface_config.width, surface_config.height),
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F11 => gif_capture.toggle(&device, format, surface_config.width, surface_config.height),
+                        KeyCode::KeyT if *shift => {
+                            particles.sprites = !particles.sprites;
+                            eprintln!("Particle sprites: {}", if particles.sprites { "on" } else { "off" });
+                        }
+                        KeyCode::KeyT => {
+                            particles.enabled = !particles.enabled;
+                            eprintln!("Particle trails: {}", if particles.enabled { "on" } else { "off" });
+                        }
+                        KeyCode::KeyC => {
+                            particles.color_mode = particles.color_mode.next();
+                            eprintln!("Particle colors: {:?}", particles.color_mode);
+                        }
+                        KeyCode::KeyP => {
+                            particles.reseed_mode = particles.reseed_mode.next();
+                            eprintln!("Particle reseeding: {:?}", particles.reseed_mode);
+                        }
+                        KeyCode::KeyL if *shift => {
+                            let degrees = (view_params.light_angle.to_degrees() + shading::LIGHT_STEP).rem_euclid(360.0).round();
+                            view_params.light_angle = degrees.to_radians();
+                            queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                            eprintln!("Light: from {}°{}", degrees, if shading == Shading::Lit { "" } else { " (lit shading is off, Shift + V)" });
+                        }
+                        KeyCode::KeyL => exposure.toggle(&device, surface_config.width, surface_config.height),
+                        KeyCode::KeyE => diagnostics.toggle(&queue),
+                        KeyCode::KeyZ => streamlines.toggle(),
+                        KeyCode::KeyA => arrows.toggle(),
+                        KeyCode::KeyU | KeyCode::KeyY => match &mut tonemap {
+                            Some(tonemap) if *code == KeyCode::KeyU => tonemap.cycle(&queue),
+                            Some(tonemap) => tonemap.step_exposure(&queue, !*shift),
+                            None => eprintln!("Tonemapping is off (start with --tonemap)"),
+                        },
+                        KeyCode::KeyJ => {
+                            if view_params.detail > 0.0 {
+                                stored_detail = view_params.detail;
+                                view_params.detail = 0.0;
+                            } else {
+                                view_params.detail = stored_detail;
+                            }
+                            eprintln!("Detail layer: {}", if view_params.detail > 0.0 { "on" } else { "off" });
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::KeyS if *shift => {
+                            let velocity = read_field(&device, &queue, &sim.velocity, grid);
+                            let obstacles = read_field(&device, &queue, &obstacle_tex, grid);
+                            let samples = wall_shear::wall_samples(&velocity, &obstacles, grid, sim_params.cell_size);
+                            wall_shear::save_csv(&samples, grid, sim_params.viscosity, sim_params.time);
+                        }
+                        KeyCode::KeyS => {
+                            view_params.wall_shear = if view_params.wall_shear > 0.0 { 0.0 } else { wall_shear_scale };
+                            eprintln!("Wall shear outline: {}", if view_params.wall_shear > 0.0 {
+                                format!("on ({}/s at full thickness)", view_params.wall_shear)
+                            } else {
+                                "off".to_string()
+                            });
+                        }
+                        KeyCode::KeyH => {
+                            sim_params.instrument ^= 1;
+                            eprintln!("Cost heat map: {}", if sim_params.instrument != 0 { "on" } else { "off" });
+                        }
+                        KeyCode::KeyR => {
+                            if let Some(client) = &client {
+                                client.send(remote::Input::Reset);
+                                return;
+                            }
+                            // Re-read the scene too, so edits to a scene file show up on reset
+                            scene = load_scene_or_default(&scene_name, config.init_noise);
+                            apply_scene(&queue, &scene, start_state.as_ref(), &scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed), &mut sim_params, &base_params, base_decays);
+                            if let Some(vortex) = &vortex {
+                                vortex.clear(&queue);
+                            }
+                            if let Some(distort) = &distort {
+                                distort.reset(&queue);
+                            }
+                            if let Some(cmp) = &compare {
+                                cmp.sim.copy_from(&device, &queue, &sim);
+                            }
+                        }
+                        KeyCode::Space if simulating => {
+                            paused = !paused;
+                            eprintln!("Simulation: {}", if paused { "paused" } else { "running" });
+                        }
+                        KeyCode::KeyX => {
+                            dye_brush.color = dye_brush.color.next();
+                            eprintln!("Dye color: {}", dye_brush.describe());
+                            if let Some(client) = &client {
+                                client.send(remote::Input::DyeColor { color: dye_brush.color, cycle: dye_brush.cycle });
+                            }
+                        }
+                        KeyCode::KeyW if *shift => {
+                            glass = !glass;
+                            eprintln!("Glass shading: {}", if glass { "on" } else { "off" });
+                        }
+                        KeyCode::KeyW => {
+                            let boundary = Boundary::from_code(sim_params.boundary).unwrap_or(Boundary::Walls).next();
+                            sim_params.boundary = boundary.code();
+                            eprintln!("Boundary: {}", boundary.name());
+                            if let Some(client) = &client {
+                                client.send(remote::Input::Boundary(boundary));
+                            }
+                        }
+                        KeyCode::KeyI if *shift => {
+                            inspector.cycle(&queue);
+                            if inspector.mode == inspector::Mode::Off && !palette.is_open() {
+                                window.set_title(WINDOW_TITLE);
+                            }
+                        }
+                        KeyCode::KeyI => {
+                            rod = match rod {
+                                Some(_) => None,
+                                None => Some(config.rod.unwrap_or_default()),
+                            };
+                            match &rod {
+                                Some(r) => eprintln!("Stirring rod: {}", r),
+                                None => eprintln!("Stirring rod: off"),
+                            }
+                            if let Some(client) = &client {
+                                client.send(remote::Input::Rod(rod));
+                            }
+                        }
+                        KeyCode::KeyN => {
+                            substeps = if *shift { substeps - 1 } else { substeps + 1 }.clamp(1, MAX_SUBSTEPS);
+                            eprintln!("Substeps: {} per frame", substeps);
+                            if let Some(client) = &client {
+                                client.send(remote::Input::Substeps(substeps));
+                            }
+                        }
+                        KeyCode::KeyM => {
+                            mouse_force.curve = mouse_force.curve.next();
+                            eprintln!("Mouse force: {}", mouse_force);
+                        }
+                        KeyCode::Comma | KeyCode::Period => {
+                            mouse_force.sensitivity *= if *code == KeyCode::Comma { 0.5 } else { 2.0 };
+                            eprintln!("Mouse force: {}", mouse_force);
+                        }
+                        KeyCode::KeyG | KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::ArrowLeft | KeyCode::ArrowRight
+                        | KeyCode::Minus | KeyCode::Equal => {
+                            adjust_gravity(*code, *shift, &mut sim_params, &mut stored_gravity);
+                            if let Some(client) = &client {
+                                client.send(remote::Input::Gravity { gravity: sim_params.gravity, by_dye: sim_params.gravity_by_dye != 0 });
+                            }
+                        }
+                        KeyCode::KeyK => {
+                            if compare.take().is_some() {
+                                render_bg = render_bindings.create(&device, &obstacle_view, particles.trail_view(), detail.view(), &sim, reference.fields());
+                                eprintln!("Solver comparison: off");
+                            } else {
+                                // Comparing advection alone keeps the solver unless --compare says otherwise
+                                let kind = config.compare_solver.unwrap_or(match config.compare_advection {
+                                    Some(_) => config.pressure_solver,
+                                    None => config.pressure_solver.contrast(),
+                                });
+                                let shared = simulation::Shared {
+                                    device: &device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+                                    params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+                                };
+                                let mut other = Simulation::new(&shared, &fluid_module, kind);
+                                other.advection = config.compare_advection.unwrap_or(sim.advection);
+                                other.frozen = sim.frozen;
+                                other.copy_from(&device, &queue, &sim);
+                                render_bg = render_bindings.create(&device, &obstacle_view, particles.trail_view(), detail.view(), &sim, other.fields());
+                                let other_bg = render_bindings.create(&device, &obstacle_view, particles.trail_view(), detail.view(), &other, sim.fields());
+                                eprintln!("Solver comparison: {} vs {} (V switches the view)", sim.label(), other.label());
+                                compare = Some(Comparison { sim: other, render_bg: other_bg, view: CompareView::Primary });
+                            }
+                        }
+                        KeyCode::KeyV if *shift => {
+                            shading = shading.next();
+                            view_params.shading = shading as u32;
+                            queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                            eprintln!("Dye shading: {}", shading.name());
+                        }
+                        KeyCode::KeyV => {
+                            if let Some(cmp) = &mut compare {
+                                cmp.view = cmp.view.next();
+                                let kind = match cmp.view {
+                                    CompareView::Primary => sim.label(),
+                                    CompareView::Secondary => cmp.sim.label(),
+                                    CompareView::Difference => format!("{} - {}", sim.label(), cmp.sim.label()),
+                                };
+                                eprintln!("Comparison view: {:?} ({})", cmp.view, kind);
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::KeyO if simulating => {
+                            if tuner.take().is_some() {
+                                eprintln!("Tuning cancelled");
+                            } else {
+                                let shared = simulation::Shared {
+                                    device: &device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+                                    params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+                                };
+                                tuner = Some(tune::Tuner::new(&shared, &fluid_module, &queue, &sim, &sim_params, config.tune.clone()));
+                            }
+                        }
+                        KeyCode::KeyB if *shift => motion_blur.toggle(&device, surface_config.width, surface_config.height),
+                        KeyCode::KeyB => {
+                            reference.capture(&device, &queue, &sim);
+                            eprintln!("Reference state captured (D shows the difference)");
+                        }
+                        KeyCode::KeyD if *shift => {
+                            dither = !dither;
+                            view_params.dither = (dither && tonemap.is_none()) as u32 * dither_mode(format);
+                            if let Some(tonemap) = &mut tonemap {
+                                tonemap.set_dither(&queue, dither);
+                            }
+                            if let Some(supersample) = &mut supersample {
+                                supersample.set_dither(&queue, dither);
+                            }
+                            motion_blur.set_dither(&queue, dither);
+                            eprintln!("Dithering: {}", if dither { "on" } else { "off" });
+                        }
+                        KeyCode::KeyD => {
+                            if compare.is_some() {
+                                eprintln!("Difference view: use V while comparing solvers");
+                            } else {
+                                show_diff = !show_diff;
+                                if show_diff && !reference.valid {
+                                    reference.capture(&device, &queue, &sim);
+                                }
+                                eprintln!("Difference view: {}", if show_diff { "live - reference" } else { "off" });
+                            }
+                        }
+                        KeyCode::KeyF => {
+                            diff_field = diff_field.next();
+                            view_params = ViewParams { diff_field: diff_field as u32, diff_scale: diff_field.default_scale(), ..view_params };
+                            queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                            eprintln!("Difference field: {:?} (±{} at full color)", diff_field, view_params.diff_scale);
+                        }
+                        KeyCode::KeyQ if *shift => {
+                            colormap = colormap.next(custom_colormap);
+                            view_params.colormap = colormap as u32;
+                            queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                            eprintln!("Colormap: {}", colormap.name());
+                        }
+                        KeyCode::KeyQ => {
+                            field_view = field_view.next();
+                            view_params = ViewParams { field: field_view as u32, field_scale: field_view.default_scale(), ..view_params };
+                            queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                            match field_view {
+                                FieldView::Dye => eprintln!("Field view: dye"),
+                                FieldView::Lic => eprintln!("Field view: line integral convolution (full tint at {} cells/s)", view_params.field_scale),
+                                FieldView::Schlieren => eprintln!("Field view: schlieren (full shade at {} dye per cell)", view_params.field_scale),
+                                _ => eprintln!("Field view: {:?} (±{} at full color)", field_view, view_params.field_scale),
+                            }
+                        }
+                        // The difference view's scale while it's showing, the field view's otherwise
+                        KeyCode::BracketLeft | KeyCode::BracketRight => {
+                            let factor = if *code == KeyCode::BracketLeft { 0.5 } else { 2.0 };
+                            let diffing = compare.as_ref().map_or(show_diff, |cmp| cmp.view == CompareView::Difference);
+                            if field_view != FieldView::Dye && !diffing {
+                                view_params.field_scale *= factor;
+                                eprintln!("Field scale: ±{} at full color", view_params.field_scale);
+                            } else {
+                                view_params.diff_scale *= factor;
+                                eprintln!("Difference scale: ±{} at full color", view_params.diff_scale);
+                            }
+                            queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F1 => hud.toggle(),
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F5 => {
+                            let checkpoint = Checkpoint::capture(&device, &queue, &sim, grid, &scene_name, &sim_params);
+                            let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                            let path = format!("checkpoint-{}.wfc", secs);
+                            match std::fs::write(&path, checkpoint.encode()) {
+                                Ok(()) => {
+                                    eprintln!("Saved {} (F6 goes back to it, --checkpoint {} starts from it)", path, path);
+                                    checkpoint_path = Some(path);
+                                }
+                                Err(e) => eprintln!("Failed to save {}: {}", path, e),
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F6 => match checkpoint_path.as_deref().and_then(|path| load_checkpoint_file(path, grid)) {
+                            Some(checkpoint) => {
+                                scene_name = checkpoint.scene.clone();
+                                start_state = None;
+                                scene = restore_checkpoint(&queue, &checkpoint, &sim, &scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed), &mut sim_params, &base_params, base_decays);
+                                if let Some(vortex) = &vortex {
+                                    vortex.clear(&queue);
+                                }
+                                if let Some(distort) = &distort {
+                                    distort.reset(&queue);
+                                }
+                                if let Some(cmp) = &compare {
+                                    cmp.sim.copy_from(&device, &queue, &sim);
+                                }
+                            }
+                            None if checkpoint_path.is_none() => eprintln!("No checkpoint to go back to yet (F5 saves one)"),
+                            None => {}
+                        },
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F7 => {
+                            let fields = field_export::Fields::new(
+                                grid,
+                                &read_field(&device, &queue, &sim.velocity, grid),
+                                &read_field(&device, &queue, &sim.density, grid),
+                                &read_field(&device, &queue, &sim.pressure, grid),
+                            );
+                            let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                            let stem = format!("fields-{}", secs);
+                            match fields.save(config.field_format, &stem) {
+                                Ok(path) => eprintln!("Saved {} (t = {:.2} s)", path, sim_params.time),
+                                Err(e) => eprintln!("Failed to save {}: {}", stem, e),
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F8 => match &velocity_path {
+                            Some(path) => {
+                                let compare_velocity = compare.as_ref().map(|cmp| &cmp.sim.velocity);
+                                import_velocity(&queue, path, grid, std::iter::once(&sim.velocity).chain(compare_velocity).chain(&frozen_velocity));
+                            }
+                            None => eprintln!("No velocity field to load (give --velocity-field, or drop a .exr or .npy on the window)"),
+                        },
+                        #[cfg(not(target_arch = "wasm32"))]
+                        KeyCode::F9 => {
+                            let dens = read_field(&device, &queue, &sim.density, grid);
+                            let vel = read_field(&device, &queue, &sim.velocity, grid);
+                            let snap = Snapshot {
+                                grid,
+                                density: dens.iter().map(|d| [d[0], d[2], d[3]]).collect(),
+                                velocity: vel.iter().map(|v| [v[0], v[1]]).collect(),
+                            };
+                            let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+                            let path = format!("snapshot-{}.wfs", secs);
+                            match std::fs::write(&path, snap.encode()) {
+                                Ok(()) => eprintln!("Saved {} (load with --snapshot {})", path, path),
+                                Err(e) => eprintln!("Failed to save {}: {}", path, e),
+                            }
+                        }
+                        code => scene_pick = digit_index(*code),
+                        },
+                    }
+                    if let (Some(client), Some(i)) = (&client, scene_pick) {
+                        client.send(remote::Input::Scene(i as u32));
+                    } else if let Some((name, _)) = scene_pick.and_then(|i| scene::BUILTIN.get(i)) {
+                        // Switching scenes leaves the demo/snapshot start state behind
+                        scene_name = name.to_string();
+                        start_state = None;
+                        scene = load_scene_or_default(&scene_name, config.init_noise);
+                        apply_scene(&queue, &scene, None, &scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed), &mut sim_params, &base_params, base_decays);
+                        if let Some(vortex) = &vortex {
+                            vortex.clear(&queue);
+                        }
+                        if let Some(distort) = &distort {
+                            distort.reset(&queue);
+                        }
+                        if let Some(cmp) = &compare {
+                            cmp.sim.copy_from(&device, &queue, &sim);
+                        }
+                    }
+                }
+
                 WindowEvent::RedrawRequested => {
-                    frame_count += 1;
-                    if frame_count % 120 == 0 {
-                        eprintln!(
-                            "[frame {}] down={} pos=[{:.0},{:.0}] delta=[{:.1},{:.1}]",
-                            frame_count, sim_params.mouse_down,
-                            sim_params.mouse_pos[0], sim_params.mouse_pos[1],
-                            sim_params.mouse_delta[0], sim_params.mouse_delta[1],
-                        );
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        frame_count += 1;
                     }
 
-                    queue.write_buffer(&param_buffer, 0, bytemuck::bytes_of(&sim_params));
+                    let stepping = simulating && !paused;
+                    if stepping {
+                        if let Some(rod) = &mut rod {
+                            rod.step(&sim_params);
+                        }
+                    }
+                    // While the stirring rod is in use it stands in for the mouse
+                    let mut step_params = rod.as_ref().map_or(sim_params, |rod| rod.apply(&sim_params));
+                    if stepping {
+                        step_params = substep_params(&step_params, substeps);
+                        if let Some(cfl) = &mut cfl {
+                            step_params = cfl.apply(&device, &step_params);
+                        }
+                        sim_params.time += step_params.dt * substeps as f32;
+                        step_params.time = sim_params.time;
+                        if scene.has_moving_obstacles() {
+                            upload_obstacles(&queue, &scene, &obstacle_tex, grid, sim_params.time);
+                        }
+                        step_params.dye_color = dye_brush.rgb(step_params.time, stroke_direction(&step_params));
+                    }
+                    queue.write_buffer(&param_buffer, 0, bytemuck::bytes_of(&step_params));
+                    view_params.time = sim_params.time;
+                    queue.write_buffer(&render_bindings.view, 0, bytemuck::bytes_of(&view_params));
+                    sim.set_domain(&queue, &sim_params);
+                    if let Some(cmp) = &compare {
+                        cmp.sim.set_domain(&queue, &sim_params);
+                    }
+                    particles.set_emitter(step_params.mouse_down != 0, step_params.mouse_pos, step_params.radius * 0.5);
+                    particles.update(&queue, step_params.dt * substeps as f32);
+                    if let Some(vortex) = vortex.as_mut() {
+                        vortex.update(&queue, step_params.dt * substeps as f32, sim_params.cell_size);
+                    }
+                    detail.update(&queue, step_params.dt * substeps as f32, sim_params.time, sim_params.cell_size);
+                    if let Some(distort) = &mut distort {
+                        distort.update(&queue, step_params.dt * substeps as f32, sim_params.cell_size);
+                    }
 
                     let frame = match surface.get_current_texture() {
                         Ok(f) => f,
                         Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                            surface.configure(&device, &config);
+                            surface.configure(&device, &surface_config);
                             return;
                         }
                         Err(e) => {
@@ -468,59 +2125,240 @@ fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
                         }
                     };
 
+                    gpu_error::push_scopes(&device);
                     let view = frame.texture.create_view(&Default::default());
                     let mut encoder = device.create_command_encoder(&Default::default());
+                    if sim_params.instrument != 0 {
+                        encoder.clear_buffer(&sim.cost, 0, None);
+                        if let Some(cmp) = &compare {
+                            encoder.clear_buffer(&cmp.sim.cost, 0, None);
+                        }
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(frozen) = frozen_velocity.as_ref().filter(|_| stepping) {
+                        for velocity in std::iter::once(&sim.velocity).chain(compare.as_ref().map(|cmp| &cmp.sim.velocity)) {
+                            encoder.copy_texture_to_texture(frozen.as_image_copy(), velocity.as_image_copy(), frozen.size());
+                        }
+                    }
 
                     // Compute pass
-                    {
+                    if let Some(follower) = &wall_follower {
+                        follower.upload_latest(&queue, [&sim.density, &sim.velocity, &obstacle_tex]);
+                    } else if let Some(client) = &client {
+                        client.send(remote::Input::Pointer {
+                            pos: sim_params.mouse_pos, delta: sim_params.mouse_delta, down: sim_params.mouse_down != 0,
+                        });
+                        client.upload_latest(&queue, [&sim.density, &sim.velocity, &obstacle_tex]);
+                    } else {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let timestamp_writes = stats.as_ref().and_then(|s| s.sim_writes());
+                        #[cfg(target_arch = "wasm32")]
+                        let timestamp_writes = None;
                         let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                            label: Some("sim"), timestamp_writes: None,
+                            label: Some("sim"), timestamp_writes,
                         });
-                        c.set_bind_group(0, &compute_bg, &[]);
-
-                        c.set_pipeline(&add_source_pipe);
-                        c.dispatch_workgroups(wg.0, wg.1, 1);
-                        c.set_pipeline(&advect_vel_pipe);
-                        c.dispatch_workgroups(wg.0, wg.1, 1);
-                        c.set_pipeline(&copy_vel_pipe);
-                        c.dispatch_workgroups(wg.0, wg.1, 1);
-                        c.set_pipeline(&advect_dens_pipe);
-                        c.dispatch_workgroups(wg.0, wg.1, 1);
-                        c.set_pipeline(&copy_dens_pipe);
-                        c.dispatch_workgroups(wg.0, wg.1, 1);
-                        c.set_pipeline(&divergence_pipe);
-                        c.dispatch_workgroups(wg.0, wg.1, 1);
-                        for _ in 0..20 {
-                            c.set_pipeline(&pressure_a_pipe);
-                            c.dispatch_workgroups(wg.0, wg.1, 1);
-                            c.set_pipeline(&pressure_b_pipe);
-                            c.dispatch_workgroups(wg.0, wg.1, 1);
-                        }
-                        c.set_pipeline(&gradient_pipe);
-                        c.dispatch_workgroups(wg.0, wg.1, 1);
+                        painter.dispatch(&mut c);
+                        if stepping {
+                            for _ in 0..substeps {
+                                sim.step(&mut c, &fluid_pipes, &step_params);
+                                if let Some(cmp) = &compare {
+                                    cmp.sim.step(&mut c, &fluid_pipes, &step_params);
+                                }
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if stats.is_some() {
+                                sim.measure_divergence(&mut c, &fluid_pipes);
+                            }
+                            if let Some(vortex) = &vortex {
+                                vortex.dispatch(&mut c);
+                            }
+                            particles.dispatch(&mut c);
+                            if view_params.detail > 0.0 {
+                                detail.dispatch(&mut c);
+                            }
+                            if let Some(distort) = &distort {
+                                distort.dispatch(&mut c);
+                            }
+                        }
+                    }
+                    sim.end_frame(&device, &mut encoder);
+                    if let Some(cmp) = &compare {
+                        cmp.sim.end_frame(&device, &mut encoder);
+                    }
+                    if let Some(cfl) = cfl.as_mut().filter(|_| stepping) {
+                        cfl.record(&mut encoder);
                     }
+                    diagnostics.collect(&device, &queue);
+                    if stepping {
+                        diagnostics.record(&queue, &mut encoder, sim_params.cell_size, sim_params.time);
+                    }
+                    streamlines.record(&queue, &mut encoder, sim_params.cell_size);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    screenshots.collect(&device);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    recorder.collect(&device);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    gif_capture.collect(&device);
+                    if let Some(readout) = inspector.collect(&device).filter(|_| !palette.is_open()) {
+                        window.set_title(&format!("{} — {}", WINDOW_TITLE, readout));
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    hud.frame(&device, &queue, surface_config.width, surface_config.height, &hud::Readout {
+                        grid, scene: &scene_name, paused, time: sim_params.time, dt: step_params.dt, substeps,
+                        solver: sim.label(), pressure_iterations: sim.solver.adaptive_iterations(),
+                        step_dispatches: if simulating { sim.step_dispatches(&fluid_pipes, &step_params) } else { 0 },
+                        viscosity: sim_params.viscosity, vorticity: sim_params.vorticity, dye_decay: sim_params.dye_decay,
+                    });
+                    inspector.record(&mut encoder, inspector::Sources {
+                        density: &sim.density, velocity: &sim.velocity, pressure: &sim.pressure,
+                        divergence: &sim.divergence, curl: &sim.curl,
+                    });
 
                     // Render pass
+                    let tonemap_target = tonemap.as_ref().map_or(&view, |t| t.target());
+                    let scene_target = motion_blur.target().unwrap_or(tonemap_target);
+                    let target = supersample.as_ref().map_or(scene_target, |s| s.target());
                     {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let timestamp_writes = stats.as_ref().and_then(|s| s.render_writes());
+                        #[cfg(target_arch = "wasm32")]
+                        let timestamp_writes = None;
                         let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                             label: Some("render"),
                             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                view: &view, resolve_target: None,
+                                view: target, resolve_target: None,
                                 ops: wgpu::Operations {
                                     load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                                     store: wgpu::StoreOp::Store,
                                 },
                             })],
                             depth_stencil_attachment: None,
+                            timestamp_writes,
+                            occlusion_query_set: None,
+                        });
+                        let view = match &compare {
+                            Some(cmp) => cmp.view,
+                            None if show_diff => CompareView::Difference,
+                            None => CompareView::Primary,
+                        };
+                        // The picture stands in for the dye, not for the debug and field views
+                        let picture = distort.as_ref().filter(|_| {
+                            view == CompareView::Primary && sim_params.instrument == 0 && field_view == FieldView::Dye
+                        });
+                        if exposure.showing() {
+                            exposure.draw_still(&mut r);
+                        } else if let Some(distort) = picture {
+                            distort.draw(&mut r);
+                            particles.draw_sprites(&mut r);
+                        } else {
+                            r.set_pipeline(match view {
+                                _ if sim_params.instrument != 0 => &heat_pipeline,
+                                CompareView::Difference => &diff_pipeline,
+                                _ if field_view != FieldView::Dye => &field_pipeline,
+                                _ if glass => &glass_pipeline,
+                                _ => &render_pipeline,
+                            });
+                            match &compare {
+                                Some(cmp) if view == CompareView::Secondary => r.set_bind_group(0, &cmp.render_bg, &[]),
+                                _ => r.set_bind_group(0, &render_bg, &[]),
+                            }
+                            r.draw(0..3, 0..1);
+                            particles.draw_sprites(&mut r);
+                        }
+                    }
+                    if let Some(supersample) = &supersample {
+                        supersample.apply(&mut encoder, scene_target);
+                    }
+                    motion_blur.apply(&mut encoder, tonemap_target);
+                    if let Some(tonemap) = &tonemap {
+                        tonemap.apply(&mut encoder, &view);
+                    }
+                    // Overlays, in the window's own colors
+                    {
+                        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some("overlays"),
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: &view, resolve_target: None,
+                                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                            })],
+                            depth_stencil_attachment: None,
                             timestamp_writes: None,
                             occlusion_query_set: None,
                         });
-                        r.set_pipeline(&render_pipeline);
-                        r.set_bind_group(0, &render_bg, &[]);
-                        r.draw(0..3, 0..1);
+                        if !exposure.showing() {
+                            streamlines.draw(&mut r);
+                            arrows.draw(&mut r);
+                            inspector.draw(&mut r);
+                        }
+                        diagnostics.draw(&mut r, surface_config.width, surface_config.height);
+                        #[cfg(not(target_arch = "wasm32"))]
+                        hud.draw(&mut r, surface_config.width, surface_config.height);
                     }
+                    // An exposure runs on simulated time, so only stepped frames count
+                    let exposed = stepping && exposure.record(&mut encoder, &render_bg, step_params.dt * substeps as f32);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(stats) = &stats {
+                        stats.resolve(&mut encoder);
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    screenshots.record(&device, &mut encoder, &frame.texture);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    recorder.record(&device, &mut encoder, &frame.texture);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    gif_capture.record(&device, &mut encoder, &frame.texture);
 
                     queue.submit(Some(encoder.finish()));
+                    if exposed {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        exposure.save(&device, &queue);
+                    }
+                    if let Some(cfl) = &mut cfl {
+                        cfl.submitted();
+                    }
+                    diagnostics.submitted();
+                    inspector.submitted();
+                    #[cfg(not(target_arch = "wasm32"))]
+                    screenshots.submitted();
+                    #[cfg(not(target_arch = "wasm32"))]
+                    recorder.submitted();
+                    #[cfg(not(target_arch = "wasm32"))]
+                    gif_capture.submitted();
+                    sim.submitted();
+                    if let Some(cmp) = &compare {
+                        cmp.sim.submitted();
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if stepping {
+                        steps += 1;
+                        if let Some(hasher) = &mut frame_hasher {
+                            hasher.after_step(&device, &queue, &sim.density, grid, steps);
+                        }
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(stats) = &mut stats {
+                        let frame = stats::FrameStats {
+                            frame: frame_count, time: sim_params.time, dt: step_params.dt, substeps,
+                            pressure_iterations: sim.solver.adaptive_iterations(),
+                        };
+                        let compare_velocity = compare.as_ref().map(|cmp| &cmp.sim.velocity);
+                        stats.after_frame(&device, &queue, [&sim.velocity, &sim.density, &sim.divergence], compare_velocity, grid, &frame);
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if simulating {
+                        if let Some(t) = &mut tuner {
+                            if let Some(best) = t.run_trial(&device, &queue, &fluid_pipes, grid) {
+                                t.apply(&best, &mut sim_params);
+                                tuner = None;
+                            }
+                        }
+                    }
+                    if let Some(leader) = &wall_leader {
+                        leader.broadcast(&device, &queue, [&sim.density, &sim.velocity, &obstacle_tex], grid);
+                    }
+                    gpu_error::pop_scopes_into(&device, "frame", &error_log);
+                    if let Some(headline) = error_log.lock().unwrap().take_headline() {
+                        window.set_title(&format!("{} — {}", WINDOW_TITLE, headline));
+                    }
                     frame.present();
                     sim_params.mouse_delta = [0.0, 0.0];
                 }
@@ -529,10 +2367,271 @@ fn fs_draw(in: VSOut) -> @location(0) vec4<f32> {
             },
 
             Event::AboutToWait => {
-                window.request_redraw();
+                #[cfg(not(target_arch = "wasm32"))]
+                for (file, source) in shader_watcher.as_mut().map(|w| w.changed()).unwrap_or_default() {
+                    use hot_reload::ShaderFile;
+                    let result = match file {
+                        ShaderFile::Fluid => hot_reload::try_build(&device, || {
+                            let module = hot_reload::create_module(&device, file, &workarounds.apply(&source));
+                            let shared = simulation::Shared {
+                                device: &device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+                                params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+                            };
+                            let solver = sim.create_solver(&shared, &module);
+                            let compare_solver = compare.as_ref().map(|cmp| cmp.sim.create_solver(&shared, &module));
+                            (FluidPipelines::new(&device, &compute_pl, &module, workarounds), solver, compare_solver, module)
+                        })
+                        .map(|(pipes, solver, compare_solver, module)| {
+                            fluid_pipes = pipes;
+                            fluid_module = module;
+                            sim.solver = solver;
+                            if let (Some(cmp), Some(solver)) = (&mut compare, compare_solver) {
+                                cmp.sim.solver = solver;
+                            }
+                        }),
+                        ShaderFile::Particles => hot_reload::try_build(&device, || {
+                            let module = hot_reload::create_module(&device, file, &source);
+                            particles.build_pipelines(&device, &module)
+                        })
+                        .map(|pipes| particles.set_pipelines(pipes)),
+                        ShaderFile::Render => hot_reload::try_build(&device, || {
+                            let module = hot_reload::create_module(&device, file, &source);
+                            (
+                                create_render_pipeline(&device, &render_pl, &module, scene_format, "fs_draw"),
+                                create_render_pipeline(&device, &render_pl, &module, scene_format, "fs_heat"),
+                                create_render_pipeline(&device, &render_pl, &module, scene_format, "fs_diff"),
+                                create_render_pipeline(&device, &render_pl, &module, scene_format, "fs_field"),
+                                create_render_pipeline(&device, &render_pl, &module, scene_format, "fs_glass"),
+                                exposure::accum_pipeline(&device, &render_pl, &module),
+                            )
+                        })
+                        .map(|(draw, heat, diff, field, glass, accum)| {
+                            render_pipeline = draw;
+                            heat_pipeline = heat;
+                            diff_pipeline = diff;
+                            field_pipeline = field;
+                            glass_pipeline = glass;
+                            exposure.set_accum_pipeline(accum);
+                        }),
+                    };
+                    match result {
+                        Ok(()) => {
+                            eprintln!("Reloaded {}", file.file_name());
+                            window.request_redraw();
+                        }
+                        Err(e) => eprintln!("{} failed to compile, keeping last good version:\n{}", file.file_name(), e),
+                    }
+                }
+
+                // A parameter search keeps running trials while paused
+                #[cfg(not(target_arch = "wasm32"))]
+                let idle = paused && tuner.is_none();
+                #[cfg(target_arch = "wasm32")]
+                let idle = paused;
+                target.set_control_flow(ControlFlow::Wait);
+                if !idle {
+                    window.request_redraw();
+                }
             }
 
             _ => {}
         }
     }).ok();
-}
\ No newline at end of file
+}
+/// Headless server (`--serve`): steps the simulation with no window or
+/// surface, applies input from `--connect` clients and streams them the
+/// fields after every step.
+#[cfg(not(target_arch = "wasm32"))]
+async fn serve(config: Config, addr: &str) {
+    use remote::Input;
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: config.backends,
+        ..Default::default()
+    });
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: config.power_preference,
+        force_fallback_adapter: false,
+        compatible_surface: None,
+    })
+    .await
+    .expect("No suitable GPU adapter found");
+
+    eprintln!("GPU: {}", adapter.get_info().name);
+    eprintln!("Backend: {:?}", adapter.get_info().backend);
+
+    let (device, queue) = request_device(&adapter).await;
+    let workarounds = shader_workarounds(&config, &adapter);
+    let error_log = Arc::new(Mutex::new(ErrorLog::default()));
+    gpu_error::install_uncaptured_handler(&device, &error_log);
+    gpu_error::push_scopes(&device);
+
+    let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute_shader"),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
+    });
+
+    let grid = config.grid_size;
+    let (obstacle_tex, obstacle_view) = create_storage_tex(&device, grid);
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    // Until a client reports its window, assume one of --window's shape
+    let window_size = winit::dpi::PhysicalSize::new(config.window_size.0, config.window_size.1);
+    let base_params = base_params(&config, cell_size_for(window_size));
+    let base_decays = (config.dye_decay, config.velocity_decay, config.temperature_decay);
+    let mut sim_params = base_params;
+    let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&sim_params),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let compute_bgl = create_compute_bgl(&device);
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let fluid_pipes = FluidPipelines::new(&device, &compute_pl, &compute_shader, workarounds);
+    let shared = simulation::Shared {
+        device: &device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    let mut sim = Simulation::new(&shared, &compute_shader, config.pressure_solver);
+    sim.advection = config.advection;
+    eprintln!("Pressure solver: {}", sim.solver.name());
+    let mut cfl = config.cfl.map(|limit| Cfl::new(&device, &sim.velocity_view, grid, limit));
+
+    let mut scene_name = config.scene.clone();
+    let mut dye_brush = DyeBrush::new(config.dye_color, config.dye_cycle);
+    let mut rod = config.rod;
+    let mut substeps = config.substeps;
+    let mut start_state = load_start_state(&config);
+    let mut scene = load_scene_or_default(&scene_name, config.init_noise);
+    apply_scene(&queue, &scene, start_state.as_ref(), &scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed), &mut sim_params, &base_params, base_decays);
+
+    let mut painter = paint::ObstaclePainter::new(&device, &obstacle_view, grid);
+
+    if let Some(err) = gpu_error::pop_scopes(&device, "setup").await {
+        let info = adapter.get_info();
+        eprintln!("{}\n(GPU: {}, backend: {:?})", err, info.name, info.backend);
+        std::process::exit(1);
+    }
+
+    let mut frame_hasher = frame_hasher(&config, &adapter, &sim.label());
+    let mut stats = stats_stream(&config, &device, &queue);
+    let mut steps: u64 = 0;
+
+    let server = remote::Server::listen(addr, grid).unwrap_or_else(|e| {
+        eprintln!("Can't listen for clients on {}: {}", addr, e);
+        std::process::exit(1);
+    });
+
+    loop {
+        let started = std::time::Instant::now();
+
+        let mut reset = false;
+        for input in server.inputs() {
+            match input {
+                Input::Pointer { pos, delta, down } => {
+                    // Deltas from several client frames add up until the next step
+                    sim_params.mouse_pos = pos;
+                    sim_params.mouse_delta = [sim_params.mouse_delta[0] + delta[0], sim_params.mouse_delta[1] + delta[1]];
+                    sim_params.mouse_down = down as u32;
+                }
+                Input::Paint { pos, solid } => painter.stroke(&queue, pos, solid),
+                Input::PaintEnd => painter.end(),
+                Input::Reset => reset = true,
+                Input::Scene(i) => {
+                    if let Some((name, _)) = scene::BUILTIN.get(i as usize) {
+                        scene_name = name.to_string();
+                        start_state = None;
+                        reset = true;
+                    }
+                }
+                Input::Gravity { gravity, by_dye } => {
+                    sim_params.gravity = gravity;
+                    sim_params.gravity_by_dye = by_dye as u32;
+                }
+                Input::CellSize(cell_size) => sim_params.cell_size = cell_size,
+                Input::DyeColor { color, cycle } => {
+                    dye_brush.color = color;
+                    dye_brush.cycle = cycle;
+                }
+                Input::Boundary(boundary) => sim_params.boundary = boundary.code(),
+                Input::Rod(r) => rod = r,
+                Input::Substeps(n) => substeps = n,
+            }
+        }
+        if reset {
+            scene = load_scene_or_default(&scene_name, config.init_noise);
+            apply_scene(&queue, &scene, start_state.as_ref(), &scene_targets(&sim, &obstacle_tex, &emitter_buffer, config.seed), &mut sim_params, &base_params, base_decays);
+        }
+
+        if let Some(rod) = &mut rod {
+            rod.step(&sim_params);
+        }
+        let mut step_params = substep_params(&rod.as_ref().map_or(sim_params, |rod| rod.apply(&sim_params)), substeps);
+        if let Some(cfl) = &mut cfl {
+            step_params = cfl.apply(&device, &step_params);
+        }
+        sim_params.time += step_params.dt * substeps as f32;
+        step_params.time = sim_params.time;
+        if scene.has_moving_obstacles() {
+            upload_obstacles(&queue, &scene, &obstacle_tex, grid, sim_params.time);
+        }
+        step_params.dye_color = dye_brush.rgb(step_params.time, stroke_direction(&step_params));
+        queue.write_buffer(&param_buffer, 0, bytemuck::bytes_of(&step_params));
+        sim.set_domain(&queue, &sim_params);
+
+        gpu_error::push_scopes(&device);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("sim"), timestamp_writes: stats.as_ref().and_then(|s| s.sim_writes()),
+            });
+            painter.dispatch(&mut c);
+            for _ in 0..substeps {
+                sim.step(&mut c, &fluid_pipes, &step_params);
+            }
+            if stats.is_some() {
+                sim.measure_divergence(&mut c, &fluid_pipes);
+            }
+        }
+        sim.end_frame(&device, &mut encoder);
+        if let Some(cfl) = &mut cfl {
+            cfl.record(&mut encoder);
+        }
+        if let Some(stats) = &stats {
+            stats.resolve(&mut encoder);
+        }
+        queue.submit(Some(encoder.finish()));
+        if let Some(cfl) = &mut cfl {
+            cfl.submitted();
+        }
+        sim.submitted();
+        steps += 1;
+        if let Some(hasher) = &mut frame_hasher {
+            hasher.after_step(&device, &queue, &sim.density, grid, steps);
+        }
+        if let Some(stats) = &mut stats {
+            let frame = stats::FrameStats {
+                frame: steps, time: sim_params.time, dt: step_params.dt, substeps,
+                pressure_iterations: sim.solver.adaptive_iterations(),
+            };
+            stats.after_frame(&device, &queue, [&sim.velocity, &sim.density, &sim.divergence], None, grid, &frame);
+        }
+        server.broadcast(&device, &queue, [&sim.density, &sim.velocity, &obstacle_tex], grid);
+        gpu_error::pop_scopes_into(&device, "step", &error_log);
+        device.poll(wgpu::Maintain::Wait);
+        sim_params.mouse_delta = [0.0, 0.0];
+
+        // Run in real time: one step of `dt` per `dt` of wall clock
+        if let Some(rest) = std::time::Duration::from_secs_f32(sim_params.dt).checked_sub(started.elapsed()) {
+            std::thread::sleep(rest);
+        }
+    }
+}
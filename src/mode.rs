@@ -0,0 +1,194 @@
+//! The window loop the 2D side modes share (`--flip`, `--sph`, `--shallow`,
+//! `--two-phase`, `--level-set`, `--fire`, `--gray-scott`): closing and
+//! resizing the window, Space to pause, the mouse buttons, the cursor in
+//! grid cells, and a frame of stepping and drawing. Each mode brings its own
+//! state, keys and passes through `Mode`.
+
+use std::sync::{Arc, Mutex};
+
+use winit::{
+    dpi::PhysicalSize,
+    event::*,
+    event_loop::{ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::gpu_error::{self, ErrorLog};
+
+/// One of the side modes, as `run` drives it.
+pub trait Mode {
+    /// Label of the compute pass `step` records into.
+    const STEP: &'static str;
+
+    /// A key went down. Space is taken for pausing.
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue);
+
+    /// The left or right button went down (`pressed`) or up.
+    fn mouse(&mut self, button: MouseButton, pressed: bool);
+
+    /// The cursor moved to `cursor`, in grid cells.
+    fn cursor(&mut self, cursor: [f32; 2]);
+
+    /// The window is now `size`, which isn't zero.
+    fn resize(&mut self, _size: PhysicalSize<u32>) {}
+
+    /// Write this frame's parameters, before it's stepped and drawn. The
+    /// window is `aspect` times as wide as it's tall.
+    fn prepare(&mut self, queue: &wgpu::Queue, aspect: f32, paused: bool);
+
+    /// Record a frame's worth of simulation. Not called while paused.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>);
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView);
+}
+
+/// The window and GPU a mode is set up on. `new` configures the surface and
+/// opens the error scopes `run` checks the setup with.
+pub struct Gpu {
+    pub window: Arc<Window>,
+    pub surface: wgpu::Surface<'static>,
+    pub surface_config: wgpu::SurfaceConfiguration,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub error_log: Arc<Mutex<ErrorLog>>,
+}
+
+impl Gpu {
+    pub fn new(
+        config: &Config,
+        window: Arc<Window>,
+        surface: wgpu::Surface<'static>,
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Self {
+        let error_log = Arc::new(Mutex::new(ErrorLog::default()));
+        gpu_error::install_uncaptured_handler(&device, &error_log);
+        gpu_error::push_scopes(&device);
+
+        let caps = surface.get_capabilities(&adapter);
+        let format = caps.formats.iter().find(|f| f.is_srgb()).copied().unwrap_or(caps.formats[0]);
+        let win_size = window.inner_size();
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: win_size.width.max(1),
+            height: win_size.height.max(1),
+            present_mode: config.present_mode,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &surface_config);
+        Self { window, surface, surface_config, adapter, device, queue, error_log }
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.surface_config.format
+    }
+}
+
+/// Report any GPU error from the mode's setup, then run its window on a
+/// `grid`² domain until it's closed.
+pub async fn run<M: Mode + 'static>(gpu: Gpu, event_loop: EventLoop<()>, grid: u32, mut mode: M) {
+    let Gpu { window, surface, mut surface_config, adapter, device, queue, error_log } = gpu;
+    if let Some(err) = gpu_error::pop_scopes(&device, "setup").await {
+        let info = adapter.get_info();
+        eprintln!("{}\n(GPU: {}, backend: {:?})", err, info.name, info.backend);
+        #[cfg(not(target_arch = "wasm32"))]
+        std::process::exit(1);
+        #[cfg(target_arch = "wasm32")]
+        return;
+    }
+
+    // Space: stop stepping. While paused the window only redraws after input
+    let mut paused = false;
+
+    event_loop.run(move |event, target| {
+        if let Event::WindowEvent { event: ref e, .. } = event {
+            if paused && !matches!(e, WindowEvent::RedrawRequested) {
+                window.request_redraw();
+            }
+        }
+        match event {
+            Event::WindowEvent { event: ref win_event, .. } => match win_event {
+                WindowEvent::CloseRequested => target.exit(),
+
+                WindowEvent::Resized(new_size) if new_size.width > 0 && new_size.height > 0 => {
+                    surface_config.width = new_size.width;
+                    surface_config.height = new_size.height;
+                    surface.configure(&device, &surface_config);
+                    mode.resize(*new_size);
+                }
+
+                WindowEvent::MouseInput { state, button: button @ (MouseButton::Left | MouseButton::Right), .. } => {
+                    mode.mouse(*button, *state == ElementState::Pressed);
+                }
+
+                WindowEvent::CursorMoved { position, .. } => {
+                    let n = grid as f32;
+                    mode.cursor([
+                        position.x as f32 / surface_config.width as f32 * n,
+                        position.y as f32 / surface_config.height as f32 * n,
+                    ]);
+                }
+
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent { physical_key: PhysicalKey::Code(code), state: ElementState::Pressed, .. },
+                    ..
+                } => match code {
+                    KeyCode::Space => {
+                        paused = !paused;
+                        eprintln!("{}", if paused { "Paused" } else { "Resumed" });
+                    }
+                    _ => mode.key(*code, &queue),
+                },
+
+                WindowEvent::RedrawRequested => {
+                    mode.prepare(&queue, surface_config.width as f32 / surface_config.height as f32, paused);
+
+                    let frame = match surface.get_current_texture() {
+                        Ok(f) => f,
+                        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                            surface.configure(&device, &surface_config);
+                            return;
+                        }
+                        Err(e) => {
+                            eprintln!("Surface error: {:?}", e);
+                            return;
+                        }
+                    };
+
+                    gpu_error::push_scopes(&device);
+                    let view = frame.texture.create_view(&Default::default());
+                    let mut encoder = device.create_command_encoder(&Default::default());
+                    if !paused {
+                        let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(M::STEP), timestamp_writes: None });
+                        mode.step(&mut c);
+                    }
+                    mode.render(&mut encoder, &view);
+                    queue.submit(Some(encoder.finish()));
+                    gpu_error::pop_scopes_into(&device, "frame", &error_log);
+                    if let Some(headline) = error_log.lock().unwrap().take_headline() {
+                        window.set_title(&format!("{} — {}", crate::WINDOW_TITLE, headline));
+                    }
+                    frame.present();
+                }
+
+                _ => {}
+            },
+
+            Event::AboutToWait => {
+                target.set_control_flow(ControlFlow::Wait);
+                if !paused {
+                    window.request_redraw();
+                }
+            }
+
+            _ => {}
+        }
+    }).ok();
+}
@@ -0,0 +1,195 @@
+//! Motion blur (Shift + B, `--motion-blur DECAY`): the scene is drawn into
+//! a window-sized frame target, and `motion_blur.wgsl` blends each frame
+//! into an `Rgba16Float` accumulation with the blend constant: the old
+//! frames keep `decay` of their weight each frame, so fast dye leaves
+//! smooth fading trails and a slow simulation's jumps between steps blur
+//! together. The accumulation is then shown in the frame's place. Unlike a
+//! long exposure (L) it never closes: it's the live view, smeared.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+/// Format of the accumulation. Half floats keep the faint tail of a long
+/// decay, which an 8-bit target would round away into a stuck ghost.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Decay Shift + B starts with when `--motion-blur` didn't give one.
+pub const DEFAULT_DECAY: f32 = 0.8;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurParams {
+    dither: u32,
+    _pad: [u32; 3],
+}
+
+struct Targets {
+    _frame: wgpu::Texture,
+    frame_view: wgpu::TextureView,
+    _accum: wgpu::Texture,
+    accum_view: wgpu::TextureView,
+    frame_bg: wgpu::BindGroup,
+    accum_bg: wgpu::BindGroup,
+}
+
+pub struct MotionBlur {
+    /// Weight the accumulation keeps each frame, below 1.
+    decay: f32,
+    /// The scene's format, which the frame is drawn in and the
+    /// accumulation shown in.
+    format: wgpu::TextureFormat,
+    /// `crate::dither_mode` of `format`, 0 with dithering off.
+    dither: u32,
+    param_buffer: wgpu::Buffer,
+    bgl: wgpu::BindGroupLayout,
+    accum_pipeline: wgpu::RenderPipeline,
+    present_pipeline: wgpu::RenderPipeline,
+    /// Present while on.
+    targets: Option<Targets>,
+    /// The next frame replaces the accumulation instead of blending in,
+    /// so turning on or resizing doesn't fade in from black.
+    fresh: bool,
+}
+
+impl MotionBlur {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, decay: f32, dither: bool) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("motion_blur_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../motion_blur.wgsl").into()),
+        });
+        let dither = dither as u32 * crate::dither_mode(format);
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("motion_blur_params"),
+            contents: bytemuck::bytes_of(&BlurParams { dither, _pad: [0; 3] }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("motion_blur_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let fade = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::OneMinusConstant,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let accum_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("motion_blur_accum"), layout: Some(&pl),
+            vertex: wgpu::VertexState { module: &module, entry_point: "vs_fullscreen", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_accumulate",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: FORMAT,
+                    blend: Some(wgpu::BlendState { color: fade, alpha: fade }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let present_pipeline = crate::create_render_pipeline(device, &pl, &module, format, "fs_present");
+        Self { decay, format, dither, param_buffer, bgl, accum_pipeline, present_pipeline, targets: None, fresh: true }
+    }
+
+    /// Shift + B: start blurring at this window size, or stop.
+    pub fn toggle(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.targets.take().is_some() {
+            eprintln!("Motion blur: off");
+            return;
+        }
+        self.targets = Some(self.create_targets(device, width, height));
+        self.fresh = true;
+        eprintln!("Motion blur: on, keeping {} of the trail each frame", self.decay);
+    }
+
+    /// Match a resized window, starting the trails over.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.targets.is_some() {
+            self.targets = Some(self.create_targets(device, width, height));
+            self.fresh = true;
+        }
+    }
+
+    /// Dither the shown colors or not (Shift + D).
+    pub fn set_dither(&mut self, queue: &wgpu::Queue, on: bool) {
+        self.dither = on as u32 * crate::dither_mode(self.format);
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&BlurParams { dither: self.dither, _pad: [0; 3] }));
+    }
+
+    fn create_targets(&self, device: &wgpu::Device, width: u32, height: u32) -> Targets {
+        let create = |label, format| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&Default::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label), layout: &self.bgl,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                    wgpu::BindGroupEntry { binding: 1, resource: self.param_buffer.as_entire_binding() },
+                ],
+            });
+            (texture, view, bind_group)
+        };
+        let (frame, frame_view, frame_bg) = create("motion_blur_frame", self.format);
+        let (accum, accum_view, accum_bg) = create("motion_blur_accum", FORMAT);
+        Targets { _frame: frame, frame_view, _accum: accum, accum_view, frame_bg, accum_bg }
+    }
+
+    /// Where the scene is drawn while blurring.
+    pub fn target(&self) -> Option<&wgpu::TextureView> {
+        self.targets.as_ref().map(|t| &t.frame_view)
+    }
+
+    /// Blend this frame into the accumulation and show that in `view`,
+    /// replacing what's there.
+    pub fn apply(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let Some(targets) = &self.targets else { return };
+        let weight = if self.fresh { 1.0 } else { 1.0 - self.decay as f64 };
+        self.fresh = false;
+        let mut pass = |label, view, load, pipeline, bind_group| {
+            let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view, resolve_target: None,
+                    ops: wgpu::Operations { load, store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            r.set_pipeline(pipeline);
+            r.set_bind_group(0, bind_group, &[]);
+            r.set_blend_constant(wgpu::Color { r: weight, g: weight, b: weight, a: weight });
+            r.draw(0..3, 0..1);
+        };
+        pass("motion_blur_accum", &targets.accum_view, wgpu::LoadOp::Load, &self.accum_pipeline, &targets.frame_bg);
+        pass("motion_blur_present", view, wgpu::LoadOp::Clear(wgpu::Color::BLACK), &self.present_pipeline, &targets.accum_bg);
+    }
+}
@@ -0,0 +1,101 @@
+//! How a mouse or touch movement turns into injected momentum. The cursor's
+//! movement since the last event, in grid cells, goes through a curve
+//! before it becomes `SimParams::mouse_delta`, so a trackpad's short
+//! strokes and a fast mouse's long ones can both land in a useful range.
+//! The mapping happens where the input does, so a `--connect` client
+//! strokes with its own setting.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    /// Movement times the sensitivity.
+    Linear,
+    /// Grows with the square of the movement, matching `Linear` at `cap`
+    /// cells: slow strokes are gentler, fast flicks hit harder.
+    Squared,
+    /// `Linear`, but never longer than `cap` cells.
+    Capped,
+}
+
+impl Curve {
+    const ALL: [Curve; 3] = [Curve::Linear, Curve::Squared, Curve::Capped];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(Self::ALL.iter().position(|c| *c == self).unwrap_or(0) + 1) % Self::ALL.len()]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Curve::Linear => "linear",
+            Curve::Squared => "squared",
+            Curve::Capped => "capped",
+        }
+    }
+}
+
+/// The curve plus its settings (`--mouse-force`, M and , / .).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MouseForce {
+    pub curve: Curve,
+    /// Multiplier on the movement, applied before the curve.
+    pub sensitivity: f32,
+    /// Cells per event where `Squared` crosses `Linear`, and `Capped`'s
+    /// limit.
+    pub cap: f32,
+}
+
+impl Default for MouseForce {
+    fn default() -> Self {
+        Self { curve: Curve::Linear, sensitivity: 1.0, cap: 8.0 }
+    }
+}
+
+impl MouseForce {
+    /// The `mouse_delta` for a cursor movement of `delta` cells.
+    pub fn map(&self, delta: [f32; 2]) -> [f32; 2] {
+        let d = [delta[0] * self.sensitivity, delta[1] * self.sensitivity];
+        let len = d[0].hypot(d[1]);
+        let scale = match self.curve {
+            Curve::Linear => 1.0,
+            Curve::Squared => len / self.cap,
+            Curve::Capped if len > self.cap => self.cap / len,
+            Curve::Capped => 1.0,
+        };
+        [d[0] * scale, d[1] * scale]
+    }
+}
+
+impl std::str::FromStr for MouseForce {
+    type Err = String;
+
+    /// `CURVE`, `CURVE:SENSITIVITY` or `CURVE:SENSITIVITY:CAP`, e.g.
+    /// `capped:2:6`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default();
+        let curve = crate::config::parse_choice("mouse force curve", name, &Curve::ALL.map(|c| (c.name(), c)))?;
+        let mut force = MouseForce { curve, ..MouseForce::default() };
+        let positive = |what: &str, v: &str| match v.parse::<f32>() {
+            Ok(n) if n > 0.0 => Ok(n),
+            _ => Err(format!("bad {} `{}` in `{}` (expected a positive number)", what, v, s)),
+        };
+        if let Some(v) = parts.next() {
+            force.sensitivity = positive("sensitivity", v)?;
+        }
+        if let Some(v) = parts.next() {
+            force.cap = positive("cap", v)?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("too many fields in `{}` (expected CURVE[:SENSITIVITY[:CAP]])", s));
+        }
+        Ok(force)
+    }
+}
+
+impl fmt::Display for MouseForce {
+    /// The same syntax `from_str` accepts.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.curve.name(), self.sensitivity, self.cap)
+    }
+}
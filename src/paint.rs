@@ -0,0 +1,114 @@
+//! Live obstacle sculpting: Shift + drag stamps solid cells into the
+//! obstacle mask, Shift + right drag erases them.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PaintParams {
+    start: [f32; 2],
+    end: [f32; 2],
+    radius: f32,
+    solid: u32,
+    grid_size: u32,
+    _pad: f32,
+}
+
+pub struct ObstaclePainter {
+    params: PaintParams,
+    param_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    /// Cursor position at the last stamp, `None` between strokes.
+    last: Option<[f32; 2]>,
+    /// A stamp was queued since the last `dispatch`.
+    pending: bool,
+}
+
+impl ObstaclePainter {
+    pub fn new(device: &wgpu::Device, obstacle_view: &wgpu::TextureView, grid_size: u32) -> Self {
+        // About 5 cells on the default 256² grid
+        let params = PaintParams {
+            start: [0.0; 2], end: [0.0; 2], radius: grid_size as f32 / 48.0, solid: 1, grid_size, _pad: 0.0,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("paint_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("paint_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../paint.wgsl").into()),
+        });
+
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("paint_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::ReadWrite,
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("paint_bg"), layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(obstacle_view) },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("paint_obstacles"), layout: Some(&layout), module: &shader, entry_point: "paint_obstacles",
+        });
+
+        Self { params, param_buffer, bind_group, pipeline, last: None, pending: false }
+    }
+
+    /// Stamp the brush along the segment from the previous stroke position to
+    /// `pos` (grid cells), painting solid or erasing. Takes effect on the next
+    /// `dispatch`; moves within one frame merge into a single straight
+    /// segment. Call `end` between strokes.
+    pub fn stroke(&mut self, queue: &wgpu::Queue, pos: [f32; 2], solid: bool) {
+        if !self.pending {
+            self.params.start = self.last.unwrap_or(pos);
+        }
+        self.params.end = pos;
+        self.params.solid = solid as u32;
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+        self.last = Some(pos);
+        self.pending = true;
+    }
+
+    pub fn end(&mut self) {
+        self.last = None;
+    }
+
+    /// Record the queued stamp, if any. Runs before the fluid step so the
+    /// flow sees the new walls the same frame.
+    pub fn dispatch<'a>(&'a mut self, c: &mut wgpu::ComputePass<'a>) {
+        if !std::mem::take(&mut self.pending) {
+            return;
+        }
+        let wg = self.params.grid_size.div_ceil(8);
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.pipeline);
+        c.dispatch_workgroups(wg, wg, 1);
+    }
+}
@@ -0,0 +1,212 @@
+//! Command palette (Ctrl + P): fuzzy search over every 2D action by name,
+//! for when the hotkey isn't at hand. The app draws no text of its own, so
+//! the palette lives in the window title: the query, the best match and its
+//! key. Typing filters, ↑ / ↓ move through the matches, Enter runs the
+//! selected one and Esc closes. Actions run exactly as their keys do; a
+//! scene can be picked by name even past the ten the digit keys reach.
+
+use winit::keyboard::KeyCode;
+
+use crate::scene;
+
+/// What running a palette entry does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Press this key, with Shift if set.
+    Key(KeyCode, bool),
+    /// Switch to this builtin scene.
+    Scene(usize),
+}
+
+struct Action {
+    name: String,
+    /// The hotkey, as the README's controls list writes it.
+    keys: String,
+    command: Command,
+}
+
+/// The 2D window's actions, in the README's order, with the screenshot key
+/// where screenshots can be taken.
+fn actions(screenshot_key: Option<KeyCode>) -> Vec<Action> {
+    let key = |name: &str, keys: &str, code, shift| Action { name: name.to_string(), keys: keys.to_string(), command: Command::Key(code, shift) };
+    let mut actions = vec![
+        key("Toggle particle trails", "T", KeyCode::KeyT, false),
+        key("Toggle particle sprites", "Shift+T", KeyCode::KeyT, true),
+        key("Toggle detail layer", "J", KeyCode::KeyJ, false),
+        key("Long exposure", "L", KeyCode::KeyL, false),
+        key("Turn the light", "Shift+L", KeyCode::KeyL, true),
+        key("Toggle energy and enstrophy plot", "E", KeyCode::KeyE, false),
+        key("Toggle streamlines", "Z", KeyCode::KeyZ, false),
+        key("Toggle velocity arrows", "A", KeyCode::KeyA, false),
+        key("Cycle tonemap curve", "U", KeyCode::KeyU, false),
+        key("Brighten tonemap exposure", "Y", KeyCode::KeyY, false),
+        key("Darken tonemap exposure", "Shift+Y", KeyCode::KeyY, true),
+        key("Cycle boundary", "W", KeyCode::KeyW, false),
+        key("Toggle glass shading", "Shift+W", KeyCode::KeyW, true),
+        key("Toggle stirring rod", "I", KeyCode::KeyI, false),
+        key("Cycle field inspector", "Shift+I", KeyCode::KeyI, true),
+        key("More substeps", "N", KeyCode::KeyN, false),
+        key("Fewer substeps", "Shift+N", KeyCode::KeyN, true),
+        key("Cycle mouse force curve", "M", KeyCode::KeyM, false),
+        key("Halve mouse sensitivity", ",", KeyCode::Comma, false),
+        key("Double mouse sensitivity", ".", KeyCode::Period, false),
+        key("Cycle dye color", "X", KeyCode::KeyX, false),
+        key("Cycle particle reseeding", "P", KeyCode::KeyP, false),
+        key("Toggle gravity", "G", KeyCode::KeyG, false),
+        key("Switch gravity between dye and all fluid", "Shift+G", KeyCode::KeyG, true),
+        key("Point gravity up", "Up", KeyCode::ArrowUp, false),
+        key("Point gravity down", "Down", KeyCode::ArrowDown, false),
+        key("Point gravity left", "Left", KeyCode::ArrowLeft, false),
+        key("Point gravity right", "Right", KeyCode::ArrowRight, false),
+        key("Halve gravity", "-", KeyCode::Minus, false),
+        key("Double gravity", "=", KeyCode::Equal, false),
+        key("Toggle cost heat map", "H", KeyCode::KeyH, false),
+        key("Toggle wall shear outline", "S", KeyCode::KeyS, false),
+        key("Toggle solver comparison", "K", KeyCode::KeyK, false),
+        key("Cycle comparison view", "V", KeyCode::KeyV, false),
+        key("Cycle dye shading", "Shift+V", KeyCode::KeyV, true),
+        key("Capture difference reference", "B", KeyCode::KeyB, false),
+        key("Toggle motion blur", "Shift+B", KeyCode::KeyB, true),
+        key("Toggle difference view", "D", KeyCode::KeyD, false),
+        key("Toggle dithering", "Shift+D", KeyCode::KeyD, true),
+        key("Cycle difference field", "F", KeyCode::KeyF, false),
+        key("Cycle field view", "Q", KeyCode::KeyQ, false),
+        key("Cycle colormap", "Shift+Q", KeyCode::KeyQ, true),
+        key("Halve difference or field scale", "[", KeyCode::BracketLeft, false),
+        key("Double difference or field scale", "]", KeyCode::BracketRight, false),
+        key("Pause / resume", "Space", KeyCode::Space, false),
+        key("Reset scene", "R", KeyCode::KeyR, false),
+        key("Cycle particle colors", "C", KeyCode::KeyC, false),
+    ];
+    #[cfg(not(target_arch = "wasm32"))]
+    actions.extend([
+        key("Export wall shear CSV", "Shift+S", KeyCode::KeyS, true),
+        key("Tune parameters", "O", KeyCode::KeyO, false),
+        key("Toggle HUD", "F1", KeyCode::F1, false),
+        key("Save checkpoint", "F5", KeyCode::F5, false),
+        key("Restore last checkpoint", "F6", KeyCode::F6, false),
+        key("Export raw fields", "F7", KeyCode::F7, false),
+        key("Reload velocity field", "F8", KeyCode::F8, false),
+        key("Export snapshot", "F9", KeyCode::F9, false),
+        key("Start or stop recording", "F10", KeyCode::F10, false),
+        key("Capture a GIF", "F11", KeyCode::F11, false),
+    ]);
+    actions.extend(screenshot_key.map(|code| key("Save screenshot", crate::config::screenshot_key_name(code), code, false)));
+    actions.extend(scene::BUILTIN.iter().enumerate().map(|(i, (name, _))| Action {
+        name: format!("Scene: {}", name),
+        keys: if i < 10 { ((i + 1) % 10).to_string() } else { String::new() },
+        command: Command::Scene(i),
+    }));
+    actions
+}
+
+/// How well `query` matches `name`: `None` unless its characters all
+/// appear in order (ignoring case). Runs of consecutive characters and
+/// hits at the start of a word score higher, skipped characters lower.
+fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut at = 0;
+    let mut last: Option<usize> = None;
+    for q in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        let i = at + name[at..].iter().position(|&c| c == q)?;
+        score += 1;
+        if last == Some(i.wrapping_sub(1)) {
+            score += 4;
+        }
+        if i == 0 || !name[i - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (i - at) as i32 / 4;
+        last = Some(i);
+        at = i + 1;
+    }
+    Some(score)
+}
+
+#[derive(Default)]
+pub struct Palette {
+    actions: Vec<Action>,
+    open: bool,
+    query: String,
+    /// Indices into `actions` matching `query`, best first.
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new(screenshot_key: Option<KeyCode>) -> Self {
+        let actions = actions(screenshot_key);
+        let matches = (0..actions.len()).collect();
+        Self { actions, matches, ..Default::default() }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Feed the palette a key press, with the text it types if any. Ctrl + P
+    /// opens and closes it; Enter closes it and returns the chosen command.
+    pub fn key(&mut self, code: KeyCode, text: Option<&str>, ctrl: bool) -> Option<Command> {
+        match code {
+            KeyCode::KeyP if ctrl => {
+                self.open = !self.open;
+                self.query.clear();
+                self.refilter();
+                if self.open {
+                    eprintln!("Command palette: type to search, Up / Down to choose, Enter to run, Esc to close");
+                }
+            }
+            KeyCode::Escape => self.open = false,
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                self.open = false;
+                let chosen = self.matches.get(self.selected).map(|&i| &self.actions[i]);
+                if let Some(action) = chosen {
+                    eprintln!("Command: {}", action.name);
+                }
+                return chosen.map(|action| action.command);
+            }
+            KeyCode::ArrowUp if !self.matches.is_empty() => {
+                self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+            }
+            KeyCode::ArrowDown if !self.matches.is_empty() => {
+                self.selected = (self.selected + 1) % self.matches.len();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            _ => {
+                if let Some(text) = text.filter(|t| !ctrl && t.chars().all(|c| !c.is_control())) {
+                    self.query.push_str(text);
+                    self.refilter();
+                }
+            }
+        }
+        None
+    }
+
+    fn refilter(&mut self) {
+        let mut scored: Vec<(i32, usize)> = self.actions.iter().enumerate()
+            .filter_map(|(i, a)| fuzzy_score(&self.query, &a.name).map(|s| (s, i)))
+            .collect();
+        // Stable, so equal scores keep the list's order
+        scored.sort_by_key(|&(s, _)| -s);
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    /// The window title: `base` while closed, else the query and the
+    /// selected match.
+    pub fn title(&self, base: &str) -> String {
+        if !self.open {
+            return base.to_string();
+        }
+        let selected = match self.matches.get(self.selected).map(|&i| &self.actions[i]) {
+            Some(a) if a.keys.is_empty() => format!("{}  ({}/{})", a.name, self.selected + 1, self.matches.len()),
+            Some(a) => format!("{} [{}]  ({}/{})", a.name, a.keys, self.selected + 1, self.matches.len()),
+            None => "no matches".to_string(),
+        };
+        format!("> {}▏ → {}", self.query, selected)
+    }
+}
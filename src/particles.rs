@@ -0,0 +1,498 @@
+use bytemuck::Zeroable;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::create_storage_tex;
+use crate::rng::Seed;
+
+pub const PARTICLE_COUNT: u32 = 32768;
+/// Max particles respawned per frame. Keeps emission smooth instead of
+/// reviving every dead particle in one burst.
+const SPAWN_BUDGET: u32 = 1024;
+/// Trail texture resolution relative to the sim grid. Streaks look much
+/// cleaner when they are not limited to the 256² fluid cells.
+const TRAIL_SCALE: u32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleParams {
+    grid_size: u32,
+    trail_size: u32,
+    count: u32,
+    /// Counts up every update; the respawn kernel hashes it. It starts at
+    /// a number drawn from the session seed, so `--seed` picks where the
+    /// particles respawn.
+    frame: u32,
+    dt: f32,
+    fade: f32,
+    max_life: f32,
+    color_mode: u32,
+    emitter_pos: [f32; 2],
+    emitter_active: u32,
+    emitter_radius: f32,
+    emitter_color: [f32; 4],
+    reseed_mode: u32,
+    spawn_budget: u32,
+    cell_size: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    pos: [f32; 2],
+    prev: [f32; 2],
+    life: f32,
+    age: f32,
+    _pad: [f32; 2],
+    color: [f32; 4],
+}
+
+/// How particles tint the trail they leave. Values match the `COLOR_*`
+/// constants in `particles.wgsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    White = 0,
+    /// Color assigned at spawn: each cursor stroke gets its own hue, ambient
+    /// spawns are colored by where in the domain they were born.
+    Origin = 1,
+    /// Gradient from hot white (newborn) to violet (about to expire).
+    Age = 2,
+}
+
+impl ColorMode {
+    const ALL: [ColorMode; 3] = [ColorMode::White, ColorMode::Origin, ColorMode::Age];
+
+    pub fn next(self) -> Self {
+        match self {
+            ColorMode::White => ColorMode::Origin,
+            ColorMode::Origin => ColorMode::Age,
+            ColorMode::Age => ColorMode::White,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorMode::White => "white",
+            ColorMode::Origin => "origin",
+            ColorMode::Age => "age",
+        }
+    }
+}
+
+impl std::str::FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("particle color mode", s, &Self::ALL.map(|m| (m.name(), m)))
+    }
+}
+
+/// Where dead particles are respawned. Values match the `RESEED_*` constants
+/// in `particles.wgsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReseedMode {
+    Uniform = 0,
+    /// Rejection-sampled by dye density, so tracers follow the visible smoke.
+    Density = 1,
+    /// Only on domain edges where the flow is entering.
+    Inflow = 2,
+    /// Always around the cursor, pressed or not.
+    Cursor = 3,
+}
+
+impl ReseedMode {
+    const ALL: [ReseedMode; 4] = [ReseedMode::Uniform, ReseedMode::Density, ReseedMode::Inflow, ReseedMode::Cursor];
+
+    pub fn next(self) -> Self {
+        match self {
+            ReseedMode::Uniform => ReseedMode::Density,
+            ReseedMode::Density => ReseedMode::Inflow,
+            ReseedMode::Inflow => ReseedMode::Cursor,
+            ReseedMode::Cursor => ReseedMode::Uniform,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ReseedMode::Uniform => "uniform",
+            ReseedMode::Density => "density",
+            ReseedMode::Inflow => "inflow",
+            ReseedMode::Cursor => "cursor",
+        }
+    }
+}
+
+impl std::str::FromStr for ReseedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("particle reseed mode", s, &Self::ALL.map(|m| (m.name(), m)))
+    }
+}
+
+/// Same cosine rainbow as `palette()` in `particles.wgsl`.
+fn palette(h: f32) -> [f32; 4] {
+    let c = |o: f32| 0.5 + 0.5 * (std::f32::consts::TAU * (h + o)).cos();
+    [c(0.0), c(0.33), c(0.67), 1.0]
+}
+
+/// Tracer particles that stamp their motion into a persistent trail texture.
+/// The trail is itself advected by the velocity field and faded each frame,
+/// so particles leave long, flow-aligned streaks. The particles themselves
+/// can also be drawn as glowing sprites at the head of their streaks.
+pub struct Particles {
+    params: ParticleParams,
+    param_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// What `build_pipelines` builds with (hot reload).
+    #[cfg(not(target_arch = "wasm32"))]
+    layout: wgpu::PipelineLayout,
+    #[cfg(not(target_arch = "wasm32"))]
+    sprite_layout: wgpu::PipelineLayout,
+    sprite_bg: wgpu::BindGroup,
+    #[cfg(not(target_arch = "wasm32"))]
+    format: wgpu::TextureFormat,
+    pipes: ParticlePipelines,
+    _trail: wgpu::Texture,
+    trail_view: wgpu::TextureView,
+    _trail_tmp_view: wgpu::TextureView,
+    particle_buffer: wgpu::Buffer,
+    _dead_list: wgpu::Buffer,
+    _counters: wgpu::Buffer,
+    stroke_hue: f32,
+    pub color_mode: ColorMode,
+    pub reseed_mode: ReseedMode,
+    /// When disabled the particles stop emitting, but existing trails keep
+    /// advecting and fade out naturally.
+    pub enabled: bool,
+    /// Live particles are drawn as additive sprites (Shift + T).
+    pub sprites: bool,
+}
+
+impl Particles {
+    pub fn new(
+        device: &wgpu::Device,
+        velocity_view: &wgpu::TextureView,
+        density_view: &wgpu::TextureView,
+        obstacle_view: &wgpu::TextureView,
+        grid_size: u32,
+        seed: Seed,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let trail_size = grid_size * TRAIL_SCALE;
+        let params = ParticleParams {
+            grid_size, trail_size, count: PARTICLE_COUNT, frame: seed.rng("particles").next_u64() as u32,
+            dt: 0.016, fade: 0.985, max_life: 6.0, color_mode: ColorMode::White as u32,
+            emitter_pos: [0.0; 2], emitter_active: 0, emitter_radius: 0.0, emitter_color: [1.0; 4],
+            reseed_mode: ReseedMode::Uniform as u32, spawn_budget: SPAWN_BUDGET, cell_size: [1.0; 2],
+        };
+
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("particle_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // All particles start dead and get respawned SPAWN_BUDGET at a time
+        // with randomized lifetimes, so they don't all expire on the same frame.
+        let particle_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("particles"),
+            contents: bytemuck::cast_slice(&vec![Particle::zeroed(); PARTICLE_COUNT as usize]),
+            // Also the sprites' instance buffer
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+        });
+
+        // Indices of dead particles, rebuilt by `compact_dead` every frame.
+        let dead_list = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle_dead_list"),
+            size: PARTICLE_COUNT as u64 * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let counters = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle_counters"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let (trail, trail_view) = create_storage_tex(device, trail_size);
+        let (_trail_tmp, trail_tmp_view) = create_storage_tex(device, trail_size);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("particle_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../particles.wgsl").into()),
+        });
+
+        let storage_buf = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        };
+        let sampled_tex = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+        };
+        let storage_tex = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_buf, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_buf, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_buf, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle_bg"), layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: particle_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&trail_view) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&trail_tmp_view) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(density_view) },
+                wgpu::BindGroupEntry { binding: 6, resource: dead_list.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 7, resource: counters.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 8, resource: wgpu::BindingResource::TextureView(obstacle_view) },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+
+        // The sprites only read the params
+        let sprite_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_sprite_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false, min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let sprite_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle_sprite_bg"), layout: &sprite_bgl,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() }],
+        });
+        let sprite_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&sprite_bgl], push_constant_ranges: &[],
+        });
+        let pipes = ParticlePipelines::new(device, &layout, &sprite_layout, &shader, format);
+
+        Self {
+            params,
+            param_buffer,
+            bind_group,
+            #[cfg(not(target_arch = "wasm32"))]
+            layout,
+            #[cfg(not(target_arch = "wasm32"))]
+            sprite_layout,
+            sprite_bg,
+            #[cfg(not(target_arch = "wasm32"))]
+            format,
+            pipes,
+            _trail: trail,
+            trail_view,
+            _trail_tmp_view: trail_tmp_view,
+            particle_buffer,
+            _dead_list: dead_list,
+            _counters: counters,
+            stroke_hue: 0.0,
+            color_mode: ColorMode::White,
+            reseed_mode: ReseedMode::Uniform,
+            enabled: true,
+            sprites: false,
+        }
+    }
+
+    /// Build pipelines from a new `particles.wgsl` module (hot reload). They
+    /// only replace the current ones once passed to `set_pipelines`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn build_pipelines(&self, device: &wgpu::Device, module: &wgpu::ShaderModule) -> ParticlePipelines {
+        ParticlePipelines::new(device, &self.layout, &self.sprite_layout, module, self.format)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_pipelines(&mut self, pipes: ParticlePipelines) {
+        self.pipes = pipes;
+    }
+
+    /// View of the trail accumulation texture, for sampling in the render pass.
+    pub fn trail_view(&self) -> &wgpu::TextureView {
+        &self.trail_view
+    }
+
+    /// Point the particle emitter at the cursor. Each new stroke (inactive ->
+    /// active) picks a fresh hue so parcels from different strokes can be told
+    /// apart in `ColorMode::Origin`.
+    pub fn set_emitter(&mut self, active: bool, pos: [f32; 2], radius: f32) {
+        if active && self.params.emitter_active == 0 {
+            self.stroke_hue = (self.stroke_hue + 0.618_034).fract();
+            self.params.emitter_color = palette(self.stroke_hue);
+        }
+        self.params.emitter_active = active as u32;
+        self.params.emitter_pos = pos;
+        self.params.emitter_radius = radius;
+    }
+
+    /// Physical cell size, matching `SimParams::cell_size`. Takes effect on
+    /// the next `update`.
+    pub fn set_cell_size(&mut self, cell_size: [f32; 2]) {
+        self.params.cell_size = cell_size;
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: f32) {
+        self.params.color_mode = self.color_mode as u32;
+        self.params.reseed_mode = self.reseed_mode as u32;
+        self.params.dt = dt;
+        self.params.frame = self.params.frame.wrapping_add(1);
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// Record the trail fade/advection and, if enabled, the particle step,
+    /// dead-particle compaction, reseeding and splat. Expects the velocity
+    /// field to be up to date for this frame.
+    pub fn dispatch<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        let tw = self.params.trail_size.div_ceil(8);
+        let pw = self.params.count.div_ceil(64);
+        let sw = self.params.spawn_budget.div_ceil(64);
+
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.pipes.advect_trail);
+        c.dispatch_workgroups(tw, tw, 1);
+        c.set_pipeline(&self.pipes.copy_trail);
+        c.dispatch_workgroups(tw, tw, 1);
+
+        if self.enabled {
+            c.set_pipeline(&self.pipes.reset_counters);
+            c.dispatch_workgroups(1, 1, 1);
+            c.set_pipeline(&self.pipes.advect);
+            c.dispatch_workgroups(pw, 1, 1);
+            c.set_pipeline(&self.pipes.compact_dead);
+            c.dispatch_workgroups(pw, 1, 1);
+            c.set_pipeline(&self.pipes.reseed);
+            c.dispatch_workgroups(sw, 1, 1);
+            c.set_pipeline(&self.pipes.splat);
+            c.dispatch_workgroups(pw, 1, 1);
+        }
+    }
+
+    /// Draw the live particles as sprites over whatever the pass drew
+    /// before, if `sprites` is on.
+    pub fn draw_sprites<'a>(&'a self, r: &mut wgpu::RenderPass<'a>) {
+        if !self.sprites {
+            return;
+        }
+        r.set_pipeline(&self.pipes.sprite);
+        r.set_bind_group(0, &self.sprite_bg, &[]);
+        r.set_vertex_buffer(0, self.particle_buffer.slice(..));
+        r.draw(0..4, 0..self.params.count);
+    }
+}
+
+pub struct ParticlePipelines {
+    reset_counters: wgpu::ComputePipeline,
+    advect: wgpu::ComputePipeline,
+    compact_dead: wgpu::ComputePipeline,
+    reseed: wgpu::ComputePipeline,
+    splat: wgpu::ComputePipeline,
+    advect_trail: wgpu::ComputePipeline,
+    copy_trail: wgpu::ComputePipeline,
+    sprite: wgpu::RenderPipeline,
+}
+
+impl ParticlePipelines {
+    fn new(
+        device: &wgpu::Device, layout: &wgpu::PipelineLayout, sprite_layout: &wgpu::PipelineLayout,
+        module: &wgpu::ShaderModule, format: wgpu::TextureFormat,
+    ) -> Self {
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(layout),
+                module, entry_point: entry,
+            })
+        };
+        Self {
+            reset_counters: make_compute("reset_counters"),
+            advect: make_compute("advect_particles"),
+            compact_dead: make_compute("compact_dead"),
+            reseed: make_compute("reseed_particles"),
+            splat: make_compute("splat_particles"),
+            advect_trail: make_compute("advect_trail"),
+            copy_trail: make_compute("copy_trail"),
+            sprite: device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("fs_sprite"), layout: Some(sprite_layout),
+                vertex: wgpu::VertexState {
+                    module, entry_point: "vs_sprite",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Particle>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        // pos, then life and age, then color
+                        attributes: &[
+                            wgpu::VertexAttribute { format: wgpu::VertexFormat::Float32x2, offset: 0, shader_location: 0 },
+                            wgpu::VertexAttribute { format: wgpu::VertexFormat::Float32x2, offset: 16, shader_location: 1 },
+                            wgpu::VertexAttribute { format: wgpu::VertexFormat::Float32x4, offset: 32, shader_location: 2 },
+                        ],
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module, entry_point: "fs_sprite",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::One,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent::OVER,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        }
+    }
+}
@@ -0,0 +1,673 @@
+//! A small PNG reader for custom colormaps (`--colormap FILE.png`), image
+//! distortion (`--image`) and seed images (`--seed-image`): non-interlaced grayscale, RGB, palette and
+//! alpha images at 8 bits per channel (16-bit images keep their high byte),
+//! inflated here so no image crate is needed.
+//! Chunk CRCs and the zlib checksum aren't checked.
+//!
+//! Also its counterpart, a writer of uncompressed RGB PNGs for the scene
+//! gallery's thumbnails and screenshots (native only).
+
+/// A decoded image, 8-bit RGBA rows top to bottom.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Decode a PNG file's bytes.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    let mut rest = bytes.strip_prefix(b"\x89PNG\r\n\x1a\n").ok_or("not a PNG file")?;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut zlib = Vec::new();
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + len).ok_or("truncated PNG chunk")?;
+        match kind {
+            b"IHDR" => header = Some(data),
+            b"PLTE" => palette = data,
+            b"IDAT" => zlib.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + len..).ok_or("truncated PNG chunk")?;
+    }
+    let header = header.filter(|h| h.len() == 13).ok_or("PNG has no header")?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    if width == 0 || height == 0 {
+        return Err("PNG is empty".to_string());
+    }
+    if interlace != 0 {
+        return Err("interlaced PNGs aren't supported".to_string());
+    }
+    let channels = match (color_type, depth) {
+        (3, 8) => 1,
+        (0, 8 | 16) => 1,
+        (4, 8 | 16) => 2,
+        (2, 8 | 16) => 3,
+        (6, 8 | 16) => 4,
+        _ => return Err(format!("unsupported PNG format (color type {}, {} bits)", color_type, depth)),
+    };
+    let pixel_bytes = channels * depth as usize / 8;
+    let row_bytes = pixel_bytes * width as usize;
+
+    if zlib.len() < 2 || zlib[0] & 0x0f != 8 || zlib[1] & 0x20 != 0 {
+        return Err("bad zlib stream in PNG".to_string());
+    }
+    let raw = inflate(&zlib[2..])?;
+    if raw.len() < (row_bytes + 1) * height as usize {
+        return Err("PNG image data is short".to_string());
+    }
+
+    // Undo the per-row filters, each against the previous row
+    let mut pixels = vec![0u8; row_bytes * height as usize];
+    for y in 0..height as usize {
+        let filter = raw[y * (row_bytes + 1)];
+        let line = &raw[y * (row_bytes + 1) + 1..(y + 1) * (row_bytes + 1)];
+        let (done, row) = pixels.split_at_mut(y * row_bytes);
+        let row = &mut row[..row_bytes];
+        let above = if y > 0 { &done[(y - 1) * row_bytes..] } else { &[][..] };
+        for i in 0..row_bytes {
+            let a = if i >= pixel_bytes { row[i - pixel_bytes] } else { 0 };
+            let b = above.get(i).copied().unwrap_or(0);
+            let c = if i >= pixel_bytes { above.get(i - pixel_bytes).copied().unwrap_or(0) } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("bad PNG row filter {}", filter)),
+            };
+            row[i] = line[i].wrapping_add(predicted);
+        }
+    }
+
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for pixel in pixels.chunks_exact(pixel_bytes) {
+        // High byte of each channel
+        let c = |i: usize| pixel[i * depth as usize / 8];
+        match color_type {
+            3 => {
+                let entry = palette.get(c(0) as usize * 3..c(0) as usize * 3 + 3).ok_or("PNG palette index out of range")?;
+                rgba.extend_from_slice(&[entry[0], entry[1], entry[2], 255]);
+            }
+            0 => rgba.extend_from_slice(&[c(0), c(0), c(0), 255]),
+            4 => rgba.extend_from_slice(&[c(0), c(0), c(0), c(1)]),
+            2 => rgba.extend_from_slice(&[c(0), c(1), c(2), 255]),
+            _ => rgba.extend_from_slice(&[c(0), c(1), c(2), c(3)]),
+        }
+    }
+    Ok(Image { width, height, rgba })
+}
+
+/// PNG's Paeth predictor: whichever of left, above and upper left is
+/// closest to left + above − upper left.
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+/// Deflate's length and distance codes: base value and extra bits.
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order the code length code lengths are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Bits of a deflate stream, least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = *self.data.get(self.pos).ok_or("PNG image data ends early")?;
+        let b = (byte >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Ok(b as u32)
+    }
+
+    fn bits(&mut self, n: u8) -> Result<u32, String> {
+        let mut v = 0;
+        for i in 0..n {
+            v |= self.bit()? << i;
+        }
+        Ok(v)
+    }
+
+    /// Skip to the next byte boundary.
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code, as codes per length and symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for l in 1..15 {
+            offsets[l + 1] = offsets[l] + counts[l];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = symbol as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        // Codes of each length follow on from the shorter ones
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bit()? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("bad Huffman code in PNG image data".to_string())
+    }
+}
+
+/// Decompress a raw deflate stream. Also used for ZIP-compressed OpenEXR
+/// velocity fields.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut bits = Bits { data, pos: 0, bit: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bit()? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data.get(bits.pos..bits.pos + 4).ok_or("PNG image data ends early")?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let block = data.get(bits.pos + 4..bits.pos + 4 + len).ok_or("PNG image data ends early")?;
+                out.extend_from_slice(block);
+                bits.pos += 4 + len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                inflate_block(&mut bits, &mut out, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            }
+            2 => {
+                let literals = bits.bits(5)? as usize + 257;
+                let distances = bits.bits(5)? as usize + 1;
+                let code_lengths = bits.bits(4)? as usize + 4;
+                let mut lengths = [0u8; 19];
+                for &i in &CODE_LENGTH_ORDER[..code_lengths] {
+                    lengths[i] = bits.bits(3)? as u8;
+                }
+                let code = Huffman::new(&lengths);
+                let mut lengths = Vec::with_capacity(literals + distances);
+                while lengths.len() < literals + distances {
+                    let (value, repeat) = match code.decode(&mut bits)? {
+                        l @ 0..=15 => (l as u8, 1),
+                        16 => (*lengths.last().ok_or("bad code lengths in PNG image data")?, 3 + bits.bits(2)?),
+                        17 => (0, 3 + bits.bits(3)?),
+                        _ => (0, 11 + bits.bits(7)?),
+                    };
+                    lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                }
+                if lengths.len() > literals + distances {
+                    return Err("bad code lengths in PNG image data".to_string());
+                }
+                let (lit, dist) = lengths.split_at(literals);
+                inflate_block(&mut bits, &mut out, &Huffman::new(lit), &Huffman::new(dist))?;
+            }
+            _ => return Err("bad deflate block in PNG image data".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// One Huffman-coded block: literals, and lengths copied from a distance
+/// back, up to the end-of-block code.
+fn inflate_block(bits: &mut Bits, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err("bad length code in PNG image data".to_string());
+                }
+                let len = LENGTH_BASE[i] as usize + bits.bits(LENGTH_EXTRA[i])? as usize;
+                let d = distances.decode(bits)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err("bad distance code in PNG image data".to_string());
+                }
+                let dist = DIST_BASE[d] as usize + bits.bits(DIST_EXTRA[d])? as usize;
+                if dist > out.len() {
+                    return Err("bad distance in PNG image data".to_string());
+                }
+                // Byte by byte, since the copy may overlap what it writes
+                let start = out.len() - dist;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+}
+
+/// An 8-bit RGB PNG of `rgb`, its zlib stream made of stored (uncompressed)
+/// deflate blocks.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn encode(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    // Every row starts with its filter type, 0 = none
+    let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+    for row in rgb.chunks(width as usize * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), default compression and filters,
+    // not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", &header), (b"IDAT", &zlib), (b"IEND", &Vec::new())] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    png
+}
+
+/// CRC-32 (ISO-HDLC), as PNG chunks are checked with.
+#[cfg(not(target_arch = "wasm32"))]
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Adler-32, the zlib stream's checksum.
+#[cfg(not(target_arch = "wasm32"))]
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A deflate stream being written, least significant bit first.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bit: u32,
+    }
+
+    impl BitWriter {
+        fn put(&mut self, value: u32, n: u32) {
+            for i in 0..n {
+                if self.bit == 0 {
+                    self.bytes.push(0);
+                }
+                *self.bytes.last_mut().unwrap() |= ((value >> i & 1) as u8) << self.bit;
+                self.bit = (self.bit + 1) % 8;
+            }
+        }
+
+        /// A Huffman code, which goes most significant bit first.
+        fn code(&mut self, (code, len): (u32, u8)) {
+            self.put(code.reverse_bits() >> (32 - len as u32), len as u32);
+        }
+    }
+
+    /// The canonical codes for `lengths`, as deflate assigns them.
+    fn codes(lengths: &[u8]) -> Vec<(u32, u8)> {
+        let mut next = [0u32; 17];
+        let mut code = 0;
+        for len in 1..16 {
+            code = (code + lengths.iter().filter(|&&l| l as usize == len).count() as u32) << 1;
+            next[len + 1] = code;
+        }
+        lengths
+            .iter()
+            .map(|&l| {
+                let c = next[l as usize];
+                next[l as usize] += 1;
+                (c, l)
+            })
+            .collect()
+    }
+
+    /// Huffman code lengths for symbols used `counts` times, at most
+    /// `limit` bits.
+    fn lengths(counts: &[u32], limit: u8) -> Vec<u8> {
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+        let mut heap: BinaryHeap<_> = counts.iter().enumerate().filter(|c| *c.1 > 0).map(|(s, &c)| Reverse((c, vec![s]))).collect();
+        let mut lengths = vec![0u8; counts.len()];
+        if heap.len() == 1 {
+            let Reverse((_, symbols)) = heap.pop().unwrap();
+            lengths[symbols[0]] = 1;
+        }
+        while heap.len() > 1 {
+            let (Reverse((a, mut x)), Reverse((b, y))) = (heap.pop().unwrap(), heap.pop().unwrap());
+            for &s in x.iter().chain(&y) {
+                lengths[s] += 1;
+            }
+            x.extend(y);
+            heap.push(Reverse((a + b, x)));
+        }
+        assert!(lengths.iter().all(|&l| l <= limit), "the test data needs codes over {} bits", limit);
+        lengths
+    }
+
+    enum Token {
+        Literal(u8),
+        Copy { len: usize, dist: usize },
+    }
+
+    /// `data` as literals and the longest earlier copies, greedily.
+    fn tokens(data: &[u8]) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let (mut len, mut dist) = (0, 0);
+            for start in i.saturating_sub(32768)..i {
+                let l = (0..258.min(data.len() - i)).take_while(|&k| data[start + k] == data[i + k]).count();
+                if l > len {
+                    (len, dist) = (l, i - start);
+                }
+            }
+            if len >= 3 {
+                tokens.push(Token::Copy { len, dist });
+                i += len;
+            } else {
+                tokens.push(Token::Literal(data[i]));
+                i += 1;
+            }
+        }
+        tokens
+    }
+
+    /// The length or distance code for `value`, and its extra bits.
+    fn base(value: usize, bases: &[u16], extra: &[u8]) -> (usize, u32, u8) {
+        let i = bases.iter().rposition(|&b| b as usize <= value).unwrap();
+        (i, (value - bases[i] as usize) as u32, extra[i])
+    }
+
+    fn put_tokens(out: &mut BitWriter, tokens: &[Token], literals: &[(u32, u8)], distances: &[(u32, u8)]) {
+        for token in tokens {
+            match *token {
+                Token::Literal(b) => out.code(literals[b as usize]),
+                Token::Copy { len, dist } => {
+                    let (l, bits, n) = base(len, &LENGTH_BASE, &LENGTH_EXTRA);
+                    out.code(literals[257 + l]);
+                    out.put(bits, n as u32);
+                    let (d, bits, n) = base(dist, &DIST_BASE, &DIST_EXTRA);
+                    out.code(distances[d]);
+                    out.put(bits, n as u32);
+                }
+            }
+        }
+        out.code(literals[256]);
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    enum Block {
+        Stored,
+        Fixed,
+        Dynamic,
+    }
+
+    /// `data` deflated as one block of `kind`.
+    fn deflate(data: &[u8], kind: Block) -> Vec<u8> {
+        let mut out = BitWriter::default();
+        match kind {
+            Block::Stored => {
+                out.put(1, 3);
+                out.bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                out.bytes.extend_from_slice(&(!(data.len() as u16)).to_le_bytes());
+                out.bytes.extend_from_slice(data);
+            }
+            Block::Fixed => {
+                out.put(1 | 1 << 1, 3);
+                let literals: Vec<u8> = (0..288).map(|s| match s { 0..=143 => 8, 144..=255 => 9, 256..=279 => 7, _ => 8 }).collect();
+                put_tokens(&mut out, &tokens(data), &codes(&literals), &codes(&[5; 30]));
+            }
+            Block::Dynamic => {
+                out.put(1 | 2 << 1, 3);
+                let tokens = tokens(data);
+                let (mut literal_counts, mut distance_counts) = (vec![0u32; 286], vec![0u32; 30]);
+                literal_counts[256] = 1;
+                for token in &tokens {
+                    match *token {
+                        Token::Literal(b) => literal_counts[b as usize] += 1,
+                        Token::Copy { len, dist } => {
+                            literal_counts[257 + base(len, &LENGTH_BASE, &LENGTH_EXTRA).0] += 1;
+                            distance_counts[base(dist, &DIST_BASE, &DIST_EXTRA).0] += 1;
+                        }
+                    }
+                }
+                let literal_lengths = lengths(&literal_counts, 15);
+                let distance_lengths = lengths(&distance_counts, 15);
+                // Both lists of lengths as one, run-length coded: 16
+                // repeats the last length, 17 and 18 are runs of zeros
+                let all: Vec<u8> = literal_lengths.iter().chain(&distance_lengths).copied().collect();
+                let mut runs: Vec<(u8, u32)> = Vec::new();
+                let mut i = 0;
+                while i < all.len() {
+                    let run = all[i..].iter().take_while(|&&l| l == all[i]).count();
+                    match (all[i], run) {
+                        (0, 11..) => runs.push((18, run.min(138) as u32 - 11)),
+                        (0, 3..) => runs.push((17, run as u32 - 3)),
+                        (l, 4..) if i > 0 && all[i - 1] == l => runs.push((16, run.min(6) as u32 - 3)),
+                        (l, _) => {
+                            runs.push((l, 0));
+                            i += 1;
+                            continue;
+                        }
+                    }
+                    i += match runs.last().unwrap() {
+                        (18, extra) => 11 + *extra as usize,
+                        (_, extra) => 3 + *extra as usize,
+                    };
+                }
+                let mut run_counts = vec![0u32; 19];
+                for &(symbol, _) in &runs {
+                    run_counts[symbol as usize] += 1;
+                }
+                let run_lengths = lengths(&run_counts, 7);
+                out.put(286 - 257, 5);
+                out.put(30 - 1, 5);
+                out.put(19 - 4, 4);
+                for &i in &CODE_LENGTH_ORDER {
+                    out.put(run_lengths[i] as u32, 3);
+                }
+                let run_codes = codes(&run_lengths);
+                for (symbol, extra) in runs {
+                    out.code(run_codes[symbol as usize]);
+                    match symbol {
+                        16 => out.put(extra, 2),
+                        17 => out.put(extra, 3),
+                        18 => out.put(extra, 7),
+                        _ => {}
+                    }
+                }
+                put_tokens(&mut out, &tokens, &codes(&literal_lengths), &codes(&distance_lengths));
+            }
+        }
+        out.bytes
+    }
+
+    /// `rgba` as a PNG whose row `y` is filtered with `filter(y)` and
+    /// deflated as one `kind` block.
+    fn png(width: usize, rgba: &[u8], filter: impl Fn(usize) -> u8, kind: Block) -> Vec<u8> {
+        let row_bytes = width * 4;
+        let mut raw = Vec::new();
+        for (y, row) in rgba.chunks(row_bytes).enumerate() {
+            let above = if y > 0 { &rgba[(y - 1) * row_bytes..y * row_bytes] } else { &[0; 0][..] };
+            let filter = filter(y);
+            raw.push(filter);
+            for i in 0..row_bytes {
+                let a = if i >= 4 { row[i - 4] } else { 0 };
+                let b = above.get(i).copied().unwrap_or(0);
+                let c = if i >= 4 { above.get(i - 4).copied().unwrap_or(0) } else { 0 };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                raw.push(row[i].wrapping_sub(predicted));
+            }
+        }
+        let mut zlib = vec![0x78, 0x01];
+        zlib.extend(deflate(&raw, kind));
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let height = rgba.len() / row_bytes;
+        let mut header = Vec::new();
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8-bit RGBA
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        let mut file = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, data) in [(b"IHDR", &header), (b"IDAT", &zlib), (b"IEND", &Vec::new())] {
+            file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            file.extend_from_slice(kind);
+            file.extend_from_slice(data);
+            file.extend_from_slice(&[0; 4]);
+        }
+        file
+    }
+
+    /// Gradients with a repeated band, so rows filter to small values and
+    /// deflate finds copies, and a splash of noise for the literals.
+    fn test_image(width: usize, height: usize) -> Vec<u8> {
+        let mut seed = 7u32;
+        let mut rgba = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = if (x + y) % 7 == 0 { (seed >> 24) as u8 } else { 0 };
+                rgba.extend_from_slice(&[(x * 9) as u8, (y * 13) as u8, ((x ^ y) % 4 * 60) as u8 ^ noise, 255 - (x * y) as u8]);
+            }
+        }
+        rgba
+    }
+
+    #[test]
+    fn every_block_and_filter_round_trips() {
+        let (w, h) = (13, 11);
+        let rgba = test_image(w, h);
+        for kind in [Block::Stored, Block::Fixed, Block::Dynamic] {
+            // Each filter alone, then all of them in turn
+            for filter in 0..6 {
+                let file = png(w, &rgba, |y| if filter < 5 { filter } else { (y % 5) as u8 }, kind);
+                let image = decode(&file).unwrap_or_else(|e| panic!("{:?} block, filter {}: {}", kind, filter, e));
+                assert_eq!([image.width, image.height], [w as u32, h as u32]);
+                assert!(image.rgba == rgba, "{:?} block, filter {} decoded wrong", kind, filter);
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_blocks_use_copies() {
+        // The round trip above proves little if every block was literals
+        let raw = test_image(13, 11);
+        let copies = tokens(&raw).iter().filter(|t| matches!(t, Token::Copy { .. })).count();
+        assert!(copies >= 10, "only {} copies", copies);
+    }
+
+    #[test]
+    fn encoded_screenshots_round_trip() {
+        // Big enough to take two stored blocks
+        let (w, h) = (200, 120);
+        let rgb: Vec<u8> = test_image(w, h).chunks(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+        let image = decode(&encode(w as u32, h as u32, &rgb)).unwrap();
+        assert_eq!([image.width, image.height], [w as u32, h as u32]);
+        let back: Vec<u8> = image.rgba.chunks(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+        assert!(back == rgb);
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let rgba = test_image(5, 4);
+        let file = png(5, &rgba, |_| 4, Block::Dynamic);
+        assert_eq!(decode(b"GIF89a").err().as_deref(), Some("not a PNG file"));
+        // Cut short anywhere up to the end chunk, which is optional here
+        for len in 8..file.len() - 12 {
+            assert!(decode(&file[..len]).is_err(), "{} of {} bytes decoded", len, file.len());
+        }
+        let mut bad_filter = png(5, &rgba, |_| 0, Block::Stored);
+        // The first row's filter byte, after the zlib header and the stored
+        // block's header
+        let idat = bad_filter.windows(4).position(|w| w == b"IDAT").unwrap();
+        bad_filter[idat + 4 + 2 + 5] = 9;
+        assert_eq!(decode(&bad_filter).err().as_deref(), Some("bad PNG row filter 9"));
+    }
+}
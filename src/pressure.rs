@@ -0,0 +1,957 @@
+use std::cell::Cell;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::create_storage_tex;
+use crate::readback::Readback;
+use crate::scene::Boundary;
+
+/// Everything a solver backend needs to build its pipelines against the
+/// shared fluid bind group layout. Backends with their own bind groups get
+/// the pressure, pressure scratch and divergence textures directly.
+pub struct SolverContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub layout: &'a wgpu::PipelineLayout,
+    pub module: &'a wgpu::ShaderModule,
+    pub workgroups: (u32, u32),
+    pub grid_size: u32,
+    pub pressure: &'a wgpu::TextureView,
+    pub pressure_tmp: &'a wgpu::TextureView,
+    pub divergence: &'a wgpu::TextureView,
+}
+
+/// Which pressure solver backend to use. Selected once at startup.
+///
+/// `half_res` solves on a grid of half the resolution instead (the `@half`
+/// suffix): the divergence is averaged down 2×2, solved there, and the
+/// pressure interpolated back up for the full-resolution gradient.
+#[derive(Clone, Copy, Debug)]
+pub enum SolverKind {
+    Jacobi { iterations: u32, half_res: bool },
+    /// Jacobi with the iteration count picked each frame from the last
+    /// measured residual, to bring it to `target` times the initial one
+    /// in at most `max_iterations`.
+    AutoJacobi { target: f32, max_iterations: u32 },
+    Multigrid { cycles: u32, half_res: bool },
+    /// Runs until the residual drops below `tolerance` times the initial
+    /// one, or `max_iterations` is reached.
+    Cg { tolerance: f32, max_iterations: u32 },
+}
+
+impl std::str::FromStr for SolverKind {
+    type Err = String;
+
+    /// `jacobi`, `jacobi:40`, `jacobi:auto`, `jacobi:auto:0.05:200`,
+    /// `multigrid`, `multigrid:2`, `cg`, `cg:1e-5` or `cg:1e-5:50`. For
+    /// jacobi/multigrid the number is the iteration or V-cycle count; for
+    /// jacobi:auto and cg it's the relative tolerance, then the iteration
+    /// cap. Fixed Jacobi and multigrid take an `@half` or `@full` suffix,
+    /// e.g. `multigrid:2@half`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (spec, half_res) = match s.split_once('@') {
+            None => (s, false),
+            Some((spec, res)) => (spec, crate::config::parse_choice("pressure resolution", res, &[("full", false), ("half", true)])?),
+        };
+        let mut parts = spec.split(':');
+        let name = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts.collect();
+        let count = |n: &str| n.parse::<u32>().map_err(|_| format!("bad count in solver `{}`", s));
+        let tolerance = |t: Option<&&str>, default: f32| match t {
+            Some(t) => t.parse::<f32>().ok().filter(|t| *t > 0.0).ok_or_else(|| format!("bad tolerance in solver `{}`", s)),
+            None => Ok(default),
+        };
+        match (name, args.as_slice()) {
+            ("jacobi", ["auto", ..]) if half_res => Err(format!("solver `{}`: jacobi:auto only solves at full resolution", s)),
+            ("jacobi", ["auto", rest @ ..]) if rest.len() <= 2 => {
+                let target = tolerance(rest.first(), AUTO_TARGET)?;
+                let max_iterations = rest.get(1).map(|n| count(n)).transpose()?.unwrap_or(AUTO_MAX_ITERATIONS);
+                if max_iterations < AUTO_MIN_ITERATIONS {
+                    return Err(format!("solver `{}`: jacobi:auto needs an iteration cap of at least {}", s, AUTO_MIN_ITERATIONS));
+                }
+                Ok(SolverKind::AutoJacobi { target, max_iterations })
+            }
+            ("jacobi", []) => Ok(SolverKind::Jacobi { iterations: 20, half_res }),
+            ("jacobi", [n]) => Ok(SolverKind::Jacobi { iterations: count(n)?, half_res }),
+            ("multigrid", []) => Ok(SolverKind::Multigrid { cycles: 1, half_res }),
+            ("multigrid", [n]) => Ok(SolverKind::Multigrid { cycles: count(n)?, half_res }),
+            ("cg", _) if half_res => Err(format!("solver `{}`: only jacobi and multigrid solve at half resolution", s)),
+            ("cg", rest) if rest.len() <= 2 => {
+                let tolerance = tolerance(rest.first(), CG_TOLERANCE)?;
+                let max_iterations = rest.get(1).map(|n| count(n)).transpose()?.unwrap_or(CG_MAX_ITERATIONS);
+                Ok(SolverKind::Cg { tolerance, max_iterations })
+            }
+            ("jacobi" | "multigrid" | "cg", _) => Err(format!("too many arguments in solver `{}`", s)),
+            _ => Err(format!("unknown solver `{}` (expected {})", name, crate::config::one_of(["jacobi", "multigrid", "cg"]))),
+        }
+    }
+}
+
+impl std::fmt::Display for SolverKind {
+    /// The same syntax `from_str` accepts.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SolverKind::Jacobi { iterations, half_res } => write!(f, "jacobi:{}{}", iterations, if *half_res { "@half" } else { "" }),
+            SolverKind::AutoJacobi { target, max_iterations } => write!(f, "jacobi:auto:{}:{}", target, max_iterations),
+            SolverKind::Multigrid { cycles, half_res } => write!(f, "multigrid:{}{}", cycles, if *half_res { "@half" } else { "" }),
+            SolverKind::Cg { tolerance, max_iterations } => write!(f, "cg:{}:{}", tolerance, max_iterations),
+        }
+    }
+}
+
+impl SolverKind {
+    /// A sensibly different solver to compare against when none is given:
+    /// the same one at full resolution for a half-resolution solve,
+    /// multigrid for Jacobi, Jacobi for the others.
+    pub fn contrast(self) -> Self {
+        match self {
+            SolverKind::Jacobi { iterations, half_res: true } => SolverKind::Jacobi { iterations, half_res: false },
+            SolverKind::Multigrid { cycles, half_res: true } => SolverKind::Multigrid { cycles, half_res: false },
+            SolverKind::Jacobi { .. } => SolverKind::Multigrid { cycles: 1, half_res: false },
+            _ => SolverKind::default(),
+        }
+    }
+
+    /// Whether the solve weights by 1/ρ when `dye_mass` or `heat_expansion`
+    /// make the density vary. Only the full-resolution Jacobi sweeps run
+    /// fluid.wgsl's own kernels; the others solve as if it were uniform.
+    pub fn weights_density(self) -> bool {
+        matches!(self, SolverKind::Jacobi { half_res: false, .. } | SolverKind::AutoJacobi { .. })
+    }
+}
+
+impl Default for SolverKind {
+    fn default() -> Self {
+        SolverKind::Jacobi { iterations: 20, half_res: false }
+    }
+}
+
+/// Solves the pressure Poisson equation for the current divergence field.
+///
+/// `solve` is recorded between `compute_divergence` (which has already
+/// zeroed `pressure`/`pressure_tmp` and filled `divergence_tex`) and
+/// `subtract_gradient`, which reads the result from `pressure`. It is called
+/// with `fluid_group` bound at group 0; a backend that rebinds group 0 must
+/// restore it before returning.
+pub trait PressureSolver {
+    fn name(&self) -> &'static str;
+    /// Called before each step with the physical cell size and grid
+    /// boundary (`SimParams::cell_size` / `boundary`). Backends that only
+    /// use the fluid bind group see both through `SimParams` and can ignore
+    /// this.
+    fn set_domain(&self, _queue: &wgpu::Queue, _cell_size: [f32; 2], _boundary: Boundary) {}
+    fn solve<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, fluid_group: &'a wgpu::BindGroup);
+    /// Compute dispatches one `solve` records now, for the HUD.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dispatches(&self) -> u32;
+    /// Called with the frame's encoder after the compute pass that holds
+    /// its steps, for backends that read back what they measured.
+    fn end_frame(&self, _device: &wgpu::Device, _encoder: &mut wgpu::CommandEncoder) {}
+    /// Called once that encoder is submitted.
+    fn submitted(&self) {}
+    /// Iterations the solve runs now, for backends that pick them as they
+    /// go.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn adaptive_iterations(&self) -> Option<u32> {
+        None
+    }
+}
+
+pub fn create(kind: SolverKind, ctx: &SolverContext) -> Box<dyn PressureSolver> {
+    match kind {
+        SolverKind::Jacobi { iterations, half_res: false } => Box::new(Jacobi::new(ctx, iterations)),
+        SolverKind::AutoJacobi { target, max_iterations } => Box::new(Jacobi::adaptive(ctx, target, max_iterations)),
+        SolverKind::Multigrid { cycles, half_res: false } => Box::new(Multigrid::new(ctx, cycles)),
+        SolverKind::Jacobi { iterations, half_res: true } => Box::new(HalfRes::new(ctx, HalfResWork::Sweeps(iterations))),
+        SolverKind::Multigrid { cycles, half_res: true } => Box::new(HalfRes::new(ctx, HalfResWork::Cycles(cycles))),
+        SolverKind::Cg { tolerance, max_iterations } => Box::new(Cg::new(ctx, tolerance, max_iterations)),
+    }
+}
+
+fn make_compute(ctx: &SolverContext, entry: &str) -> wgpu::ComputePipeline {
+    ctx.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(entry), layout: Some(ctx.layout),
+        module: ctx.module, entry_point: entry,
+    })
+}
+
+/// Ping-pong Jacobi iterations between `pressure` and `pressure_tmp`.
+pub struct Jacobi {
+    iterations: Cell<u32>,
+    workgroups: (u32, u32),
+    pressure_a_pipe: wgpu::ComputePipeline,
+    pressure_b_pipe: wgpu::ComputePipeline,
+    /// Picks `iterations` each frame (`jacobi:auto`).
+    auto: Option<AutoIterations>,
+}
+
+impl Jacobi {
+    pub fn new(ctx: &SolverContext, iterations: u32) -> Self {
+        Self {
+            iterations: Cell::new(iterations),
+            workgroups: ctx.workgroups,
+            pressure_a_pipe: make_compute(ctx, "pressure_jacobi_a"),
+            pressure_b_pipe: make_compute(ctx, "pressure_jacobi_b"),
+            auto: None,
+        }
+    }
+
+    /// Starts at the fixed solver's 20 iterations and adapts from there.
+    pub fn adaptive(ctx: &SolverContext, target: f32, max_iterations: u32) -> Self {
+        Self {
+            iterations: Cell::new(20.min(max_iterations)),
+            auto: Some(AutoIterations::new(ctx, target, max_iterations)),
+            ..Self::new(ctx, 0)
+        }
+    }
+
+    fn sweep<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, pipeline: &'a wgpu::ComputePipeline) {
+        let (wx, wy) = self.workgroups;
+        c.set_pipeline(pipeline);
+        c.dispatch_workgroups(wx, wy, 1);
+    }
+}
+
+impl PressureSolver for Jacobi {
+    fn name(&self) -> &'static str {
+        "jacobi"
+    }
+
+    fn solve<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, fluid_group: &'a wgpu::BindGroup) {
+        // Only the frame's first solve is measured, and only while no
+        // readback is in flight
+        let measure = self.auto.as_ref().filter(|auto| auto.start_measuring());
+        // Each iteration is an a/b pair, so the result always ends up back in `pressure`.
+        for i in 0..self.iterations.get() {
+            self.sweep(c, &self.pressure_a_pipe);
+            if let Some(auto) = measure.filter(|_| i == 0) {
+                auto.dispatch(c, &auto.initial_norm);
+                c.set_bind_group(0, fluid_group, &[]);
+            }
+            self.sweep(c, &self.pressure_b_pipe);
+        }
+        if let Some(auto) = measure {
+            auto.dispatch(c, &auto.final_norm);
+            c.set_pipeline(&auto.total);
+            c.dispatch_workgroups(1, 1, 1);
+            c.set_bind_group(0, fluid_group, &[]);
+        }
+    }
+
+    /// Leaves out the residual measurement, which only some frames run.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dispatches(&self) -> u32 {
+        2 * self.iterations.get()
+    }
+
+    fn end_frame(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(auto) = &self.auto {
+            if let Some(iterations) = auto.collect(device, self.iterations.get()) {
+                self.iterations.set(iterations);
+            }
+            auto.copy_result(encoder);
+        }
+    }
+
+    fn submitted(&self) {
+        if let Some(auto) = &self.auto {
+            auto.submitted();
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn adaptive_iterations(&self) -> Option<u32> {
+        self.auto.as_ref().map(|_| self.iterations.get())
+    }
+}
+
+/// Default relative residual for `--solver jacobi:auto`.
+const AUTO_TARGET: f32 = 0.1;
+/// Default iteration cap for `jacobi:auto`.
+const AUTO_MAX_ITERATIONS: u32 = 100;
+/// Fewest iterations `jacobi:auto` runs, however calm the flow.
+const AUTO_MIN_ITERATIONS: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ResidualParams {
+    grid_size: u32,
+    tiles: u32,
+    _pad: [u32; 2],
+}
+
+/// The residual measurement behind `jacobi:auto` (`residual.wgsl`): how far
+/// the first and last sweeps of a solve moved the pressure, summed on the
+/// GPU and read back without waiting. The ratio is the solve's relative
+/// residual, and the next frames run as many iterations as it takes to
+/// bring that to `target`, assuming it keeps falling at the rate it did.
+struct AutoIterations {
+    target: f32,
+    max_iterations: u32,
+    /// Workgroups across the grid for the norm kernels.
+    groups: u32,
+    bind_group: wgpu::BindGroup,
+    initial_norm: wgpu::ComputePipeline,
+    final_norm: wgpu::ComputePipeline,
+    total: wgpu::ComputePipeline,
+    result: wgpu::Buffer,
+    readback: Readback,
+    _buffers: [wgpu::Buffer; 2],
+    /// A measurement was dispatched this frame and not copied out yet.
+    dispatched: Cell<bool>,
+}
+
+impl AutoIterations {
+    fn new(ctx: &SolverContext, target: f32, max_iterations: u32) -> Self {
+        let device = ctx.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("residual_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../residual.wgsl").into()),
+        });
+        let groups = ctx.grid_size.div_ceil(16);
+        let tiles = groups * groups;
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("residual_params"),
+            contents: bytemuck::bytes_of(&ResidualParams { grid_size: ctx.grid_size, tiles, _pad: [0; 2] }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let partials = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("residual_partials"),
+            size: tiles as u64 * 8,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let result = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("residual_result"),
+            size: 8,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let sampled_tex = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+        };
+        let storage_buf = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        };
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::COMPUTE, ty, count: None,
+        };
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("residual_bgl"),
+            entries: &[
+                entry(0, wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false, min_binding_size: None,
+                }),
+                entry(1, sampled_tex),
+                entry(2, sampled_tex),
+                entry(3, storage_buf),
+                entry(4, storage_buf),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("residual_bg"), layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(ctx.pressure) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(ctx.pressure_tmp) },
+                wgpu::BindGroupEntry { binding: 3, resource: partials.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: result.as_entire_binding() },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let pipe = |entry: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry), layout: Some(&layout), module: &module, entry_point: entry,
+        });
+
+        Self {
+            target, max_iterations, groups, bind_group,
+            initial_norm: pipe("initial_norm"),
+            final_norm: pipe("final_norm"),
+            total: pipe("total"),
+            result,
+            readback: Readback::new(device, "residual_staging", 8),
+            _buffers: [params, partials],
+            dispatched: Cell::new(false),
+        }
+    }
+
+    /// Whether the solve about to be recorded should be measured.
+    fn start_measuring(&self) -> bool {
+        if self.dispatched.get() || !self.readback.is_free() {
+            return false;
+        }
+        self.dispatched.set(true);
+        true
+    }
+
+    /// Bind our group and run one of the norm kernels over the grid. The
+    /// caller restores the fluid group.
+    fn dispatch<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, pipeline: &'a wgpu::ComputePipeline) {
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(pipeline);
+        c.dispatch_workgroups(self.groups, self.groups, 1);
+    }
+
+    fn copy_result(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.dispatched.replace(false) {
+            encoder.copy_buffer_to_buffer(&self.result, 0, self.readback.buffer(), 0, 8);
+            self.readback.recorded(());
+        }
+    }
+
+    fn submitted(&self) {
+        self.readback.submitted();
+    }
+
+    /// The iteration count the last measurement calls for, once it has
+    /// arrived. `iterations` is what that solve ran.
+    fn collect(&self, device: &wgpu::Device, iterations: u32) -> Option<u32> {
+        let [initial, last]: [f32; 2] = self.readback.collect(device, false, |(), bytes| bytemuck::pod_read_unaligned(bytes))?;
+        Some(self.next_iterations(iterations, (last / initial).sqrt()))
+    }
+
+    /// A Jacobi solve's residual falls roughly geometrically, so a ratio of
+    /// `ratio` after `iterations` reaches `target` after about
+    /// iterations · ln(target) / ln(ratio). The count at most doubles or
+    /// halves per measurement, so one odd frame can't swing it far.
+    fn next_iterations(&self, iterations: u32, ratio: f32) -> u32 {
+        let wanted = if !ratio.is_finite() {
+            // No divergence to remove
+            AUTO_MIN_ITERATIONS
+        } else if ratio >= 1.0 {
+            // Not converging: f16 rounding has stalled the sweeps
+            2 * iterations
+        } else {
+            (iterations as f32 * self.target.ln() / ratio.ln()).ceil() as u32
+        };
+        wanted.clamp(iterations.div_ceil(2), iterations * 2).clamp(AUTO_MIN_ITERATIONS, self.max_iterations)
+    }
+}
+
+/// Smoothing sweeps (red + black) before restricting and after prolongating.
+const MG_SMOOTH: u32 = 2;
+/// Sweeps on the coarsest level, standing in for an exact solve.
+const MG_COARSE_SMOOTH: u32 = 16;
+/// Stop coarsening once a level is this small.
+const MG_MIN_SIZE: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LevelParams {
+    size: u32,
+    coarse_size: u32,
+    inv_h2: [f32; 2],
+    boundary: u32,
+    _pad: u32,
+}
+
+struct Level {
+    size: u32,
+    coarse_size: u32,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+}
+
+/// V-cycle geometric multigrid (`multigrid.wgsl`) over a hierarchy of
+/// half-resolution levels, with red-black Gauss-Seidel smoothing. Level 0
+/// works directly on the fluid's `pressure` and `divergence_tex`.
+pub struct Multigrid {
+    cycles: u32,
+    levels: Vec<Level>,
+    _textures: Vec<wgpu::Texture>,
+    _gate: Option<wgpu::Buffer>,
+    smooth_red: wgpu::ComputePipeline,
+    smooth_black: wgpu::ComputePipeline,
+    residual: wgpu::ComputePipeline,
+    restrict: wgpu::ComputePipeline,
+    prolongate: wgpu::ComputePipeline,
+}
+
+impl Multigrid {
+    pub fn new(ctx: &SolverContext, cycles: u32) -> Self {
+        Self::with_gate(ctx, cycles, None)
+    }
+
+    /// `gate` is a storage buffer whose first u32, when nonzero, turns every
+    /// kernel into a no-op (see `multigrid.wgsl`). Without one the V-cycle
+    /// always runs.
+    fn with_gate(ctx: &SolverContext, cycles: u32, gate: Option<&wgpu::Buffer>) -> Self {
+        let device = ctx.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("multigrid_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../multigrid.wgsl").into()),
+        });
+
+        let storage_tex = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false, min_binding_size: None,
+            },
+            count: None,
+        }];
+        entries.extend((1..=5).map(|binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+        }));
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 6, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false, min_binding_size: None,
+            },
+            count: None,
+        });
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("multigrid_bgl"), entries: &entries,
+        });
+        let own_gate = gate.is_none().then(|| device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("multigrid_gate"), contents: &[0; 4], usage: wgpu::BufferUsages::STORAGE,
+        }));
+        let gate = gate.or(own_gate.as_ref()).unwrap();
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let pipe = |entry: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry), layout: Some(&layout), module: &module, entry_point: entry,
+        });
+
+        // Level sizes: grid, grid/2, ... down to MG_MIN_SIZE
+        let mut sizes = vec![ctx.grid_size];
+        while *sizes.last().unwrap() > MG_MIN_SIZE {
+            sizes.push(sizes.last().unwrap().div_ceil(2));
+        }
+
+        // Pressure and rhs per level (level 0 borrows the fluid's), plus a
+        // residual per level. The coarsest level has no coarser neighbour, so
+        // a tiny placeholder fills its coarse slots.
+        let mut owned: Vec<(wgpu::Texture, wgpu::TextureView)> = Vec::new();
+        let mut p_views = Vec::new();
+        let mut b_views = Vec::new();
+        for (l, &size) in sizes.iter().enumerate() {
+            if l > 0 {
+                owned.push(create_storage_tex(device, size));
+                p_views.push(owned.len() - 1);
+                owned.push(create_storage_tex(device, size));
+                b_views.push(owned.len() - 1);
+            }
+        }
+        let residuals: Vec<_> = sizes.iter().map(|&size| create_storage_tex(device, size)).collect();
+        let placeholder = (create_storage_tex(device, 1), create_storage_tex(device, 1));
+
+        let p_view = |l: usize| if l == 0 { ctx.pressure } else { &owned[p_views[l - 1]].1 };
+        let b_view = |l: usize| if l == 0 { ctx.divergence } else { &owned[b_views[l - 1]].1 };
+
+        let levels = sizes.iter().enumerate().map(|(l, &size)| {
+            let coarsest = l + 1 == sizes.len();
+            let coarse_size = if coarsest { 1 } else { sizes[l + 1] };
+            let params = level_params(size, coarse_size, l, [1.0, 1.0], Boundary::Walls);
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("multigrid_level"),
+                contents: bytemuck::bytes_of(&params),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let (pc, bc) = if coarsest { (&placeholder.0 .1, &placeholder.1 .1) } else { (p_view(l + 1), b_view(l + 1)) };
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("multigrid_bg"), layout: &bgl,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(p_view(l)) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(b_view(l)) },
+                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&residuals[l].1) },
+                    wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(pc) },
+                    wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(bc) },
+                    wgpu::BindGroupEntry { binding: 6, resource: gate.as_entire_binding() },
+                ],
+            });
+            Level { size, coarse_size, bind_group, params: buffer }
+        }).collect();
+
+        let mut textures: Vec<wgpu::Texture> = owned.into_iter().map(|(t, _)| t).collect();
+        textures.extend(residuals.into_iter().map(|(t, _)| t));
+        textures.extend([placeholder.0 .0, placeholder.1 .0]);
+
+        Self {
+            cycles,
+            levels,
+            _textures: textures,
+            _gate: own_gate,
+            smooth_red: pipe("smooth_red"),
+            smooth_black: pipe("smooth_black"),
+            residual: pipe("residual"),
+            restrict: pipe("restrict_residual"),
+            prolongate: pipe("prolongate"),
+        }
+    }
+
+    fn smooth<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, groups: u32, sweeps: u32) {
+        for _ in 0..sweeps {
+            c.set_pipeline(&self.smooth_red);
+            c.dispatch_workgroups(groups, groups, 1);
+            c.set_pipeline(&self.smooth_black);
+            c.dispatch_workgroups(groups, groups, 1);
+        }
+    }
+
+    /// One V-cycle from level `first` down to the coarsest and back.
+    /// Leaves some level's bind group bound.
+    fn v_cycle<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, first: usize) {
+        let last = self.levels.len() - 1;
+        // Down: smooth, then hand the residual to the next level
+        for level in &self.levels[first..last] {
+            let groups = level.size.div_ceil(8);
+            c.set_bind_group(0, &level.bind_group, &[]);
+            self.smooth(c, groups, MG_SMOOTH);
+            c.set_pipeline(&self.residual);
+            c.dispatch_workgroups(groups, groups, 1);
+            let coarse_groups = level.size.div_ceil(2).div_ceil(8);
+            c.set_pipeline(&self.restrict);
+            c.dispatch_workgroups(coarse_groups, coarse_groups, 1);
+        }
+
+        let coarsest = &self.levels[last];
+        c.set_bind_group(0, &coarsest.bind_group, &[]);
+        self.smooth(c, coarsest.size.div_ceil(8), MG_COARSE_SMOOTH);
+
+        // Up: add the coarse correction, then smooth it in
+        for level in self.levels[first..last].iter().rev() {
+            let groups = level.size.div_ceil(8);
+            c.set_bind_group(0, &level.bind_group, &[]);
+            c.set_pipeline(&self.prolongate);
+            c.dispatch_workgroups(groups, groups, 1);
+            self.smooth(c, groups, MG_SMOOTH);
+        }
+    }
+
+    /// Dispatches `v_cycle(first)` records.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn v_cycle_dispatches(&self, first: usize) -> u32 {
+        let steps = (self.levels.len() - 1 - first) as u32;
+        // Smoothing, residual and restriction on the way down, prolongation
+        // and smoothing back up
+        steps * (4 * MG_SMOOTH + 3) + 2 * MG_COARSE_SMOOTH
+    }
+}
+
+/// Level `l` has cells 2^l times the simulation's in each direction.
+fn level_params(size: u32, coarse_size: u32, l: usize, cell_size: [f32; 2], boundary: Boundary) -> LevelParams {
+    let scale = (1u32 << l) as f32;
+    let (hx, hy) = (cell_size[0] * scale, cell_size[1] * scale);
+    LevelParams { size, coarse_size, inv_h2: [1.0 / (hx * hx), 1.0 / (hy * hy)], boundary: boundary.code(), _pad: 0 }
+}
+
+impl PressureSolver for Multigrid {
+    fn name(&self) -> &'static str {
+        "multigrid"
+    }
+
+    fn set_domain(&self, queue: &wgpu::Queue, cell_size: [f32; 2], boundary: Boundary) {
+        for (l, level) in self.levels.iter().enumerate() {
+            let params = level_params(level.size, level.coarse_size, l, cell_size, boundary);
+            queue.write_buffer(&level.params, 0, bytemuck::bytes_of(&params));
+        }
+    }
+
+    fn solve<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, fluid_group: &'a wgpu::BindGroup) {
+        for _ in 0..self.cycles {
+            self.v_cycle(c, 0);
+        }
+        c.set_bind_group(0, fluid_group, &[]);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dispatches(&self) -> u32 {
+        self.cycles * self.v_cycle_dispatches(0)
+    }
+}
+
+/// What `HalfRes` runs on the half-resolution grid.
+pub enum HalfResWork {
+    /// Red-black Gauss-Seidel sweeps, standing in for Jacobi iterations.
+    Sweeps(u32),
+    /// Multigrid V-cycles from the half-resolution level down.
+    Cycles(u32),
+}
+
+/// Pressure solved at half the grid resolution (`jacobi@half`,
+/// `multigrid@half`), on a quarter of the cells. Borrows the multigrid
+/// hierarchy: level 0 is the fluid's grid, level 1 the half-resolution one.
+/// The divergence is restricted to level 1, solved there, and prolongated
+/// back onto the fluid's pressure, which `compute_divergence` left at zero,
+/// so the interpolated coarse pressure replaces it. The fluid's own
+/// gradient subtraction then runs at full resolution on that pressure.
+///
+/// Divergence that varies within a 2×2 block averages out on the way down,
+/// so the projection can't remove it: cell-sized compressions survive it.
+/// Obstacles aren't seen by the coarse solve, as with multigrid.
+pub struct HalfRes {
+    grid: Multigrid,
+    work: HalfResWork,
+}
+
+impl HalfRes {
+    pub fn new(ctx: &SolverContext, work: HalfResWork) -> Self {
+        Self { grid: Multigrid::new(ctx, 1), work }
+    }
+}
+
+impl PressureSolver for HalfRes {
+    fn name(&self) -> &'static str {
+        match self.work {
+            HalfResWork::Sweeps(_) => "jacobi@half",
+            HalfResWork::Cycles(_) => "multigrid@half",
+        }
+    }
+
+    fn set_domain(&self, queue: &wgpu::Queue, cell_size: [f32; 2], boundary: Boundary) {
+        self.grid.set_domain(queue, cell_size, boundary);
+    }
+
+    fn solve<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, fluid_group: &'a wgpu::BindGroup) {
+        let (fine, coarse) = (&self.grid.levels[0], &self.grid.levels[1]);
+        let groups = fine.size.div_ceil(8);
+        // With the pressure at zero, the fine residual is the divergence itself
+        c.set_bind_group(0, &fine.bind_group, &[]);
+        c.set_pipeline(&self.grid.residual);
+        c.dispatch_workgroups(groups, groups, 1);
+        let coarse_groups = coarse.size.div_ceil(8);
+        c.set_pipeline(&self.grid.restrict);
+        c.dispatch_workgroups(coarse_groups, coarse_groups, 1);
+
+        match self.work {
+            HalfResWork::Sweeps(sweeps) => {
+                c.set_bind_group(0, &coarse.bind_group, &[]);
+                self.grid.smooth(c, coarse_groups, sweeps);
+            }
+            HalfResWork::Cycles(cycles) => {
+                for _ in 0..cycles {
+                    self.grid.v_cycle(c, 1);
+                }
+            }
+        }
+
+        c.set_bind_group(0, &fine.bind_group, &[]);
+        c.set_pipeline(&self.grid.prolongate);
+        c.dispatch_workgroups(groups, groups, 1);
+        c.set_bind_group(0, fluid_group, &[]);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dispatches(&self) -> u32 {
+        3 + match self.work {
+            HalfResWork::Sweeps(sweeps) => 2 * sweeps,
+            HalfResWork::Cycles(cycles) => cycles * self.grid.v_cycle_dispatches(1),
+        }
+    }
+}
+
+/// Default relative residual tolerance for `--solver cg`.
+const CG_TOLERANCE: f32 = 1e-4;
+/// Default iteration cap. With the multigrid preconditioner the tolerance
+/// is usually met in well under ten.
+const CG_MAX_ITERATIONS: u32 = 20;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CgParams {
+    size: u32,
+    groups: u32,
+    tolerance: f32,
+    boundary: u32,
+    inv_h2: [f32; 2],
+}
+
+/// Conjugate gradient (`cg.wgsl`) preconditioned with one multigrid
+/// V-cycle, iterating in f32 buffers until the residual meets `tolerance`.
+/// The whole loop is recorded up front; after convergence the remaining
+/// iterations see the `done` flag on the GPU and skip their work, so the
+/// frame never waits on a readback.
+pub struct Cg {
+    max_iterations: u32,
+    groups: u32,
+    size: u32,
+    tolerance: f32,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    preconditioner: Multigrid,
+    _buffers: Vec<wgpu::Buffer>,
+    _textures: [wgpu::Texture; 2],
+    init: wgpu::ComputePipeline,
+    reduce_mean: wgpu::ComputePipeline,
+    init_residual: wgpu::ComputePipeline,
+    reduce_init: wgpu::ComputePipeline,
+    precond_in: wgpu::ComputePipeline,
+    precond_out: wgpu::ComputePipeline,
+    reduce_beta: wgpu::ComputePipeline,
+    update_d: wgpu::ComputePipeline,
+    apply: wgpu::ComputePipeline,
+    reduce_alpha: wgpu::ComputePipeline,
+    update_x: wgpu::ComputePipeline,
+    reduce_residual: wgpu::ComputePipeline,
+    write_pressure: wgpu::ComputePipeline,
+}
+
+impl Cg {
+    pub fn new(ctx: &SolverContext, tolerance: f32, max_iterations: u32) -> Self {
+        let device = ctx.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("cg_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../cg.wgsl").into()),
+        });
+
+        let storage_tex = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let storage_buf = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        };
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::COMPUTE, ty, count: None,
+        };
+        let mut entries = vec![entry(0, wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false, min_binding_size: None,
+        })];
+        entries.extend((1..=4).map(|binding| entry(binding, storage_tex)));
+        entries.extend((5..=10).map(|binding| entry(binding, storage_buf)));
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cg_bgl"), entries: &entries,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let pipe = |entry: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry), layout: Some(&layout), module: &module, entry_point: entry,
+        });
+
+        let size = ctx.grid_size;
+        let groups = size.div_ceil(8);
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("cg_params"),
+            contents: bytemuck::bytes_of(&cg_params(size, tolerance, [1.0, 1.0], Boundary::Walls)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // x, r, d, Ad, then the per-workgroup partial sums (vec2) and the
+        // 32-byte state block.
+        let cells = (size * size) as u64;
+        let sizes = [cells * 4, cells * 4, cells * 4, cells * 4, (groups * groups) as u64 * 8, 32];
+        let buffers: Vec<wgpu::Buffer> = sizes.iter().map(|&bytes| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cg_vector"), size: bytes,
+            usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false,
+        })).collect();
+        let state = &buffers[5];
+
+        // The preconditioner's level 0 reads `rhs` and writes `out`
+        let (rhs_tex, rhs_view) = create_storage_tex(device, size);
+        let (out_tex, out_view) = create_storage_tex(device, size);
+        let preconditioner = Multigrid::with_gate(
+            &SolverContext { pressure: &out_view, divergence: &rhs_view, ..*ctx }, 1, Some(state),
+        );
+
+        let mut group_entries = vec![
+            wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(ctx.pressure) },
+            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(ctx.divergence) },
+            wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&rhs_view) },
+            wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&out_view) },
+        ];
+        group_entries.extend(buffers.iter().zip(5..).map(|(buffer, binding)| wgpu::BindGroupEntry {
+            binding, resource: buffer.as_entire_binding(),
+        }));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("cg_bg"), layout: &bgl, entries: &group_entries,
+        });
+
+        Self {
+            max_iterations,
+            groups,
+            size,
+            tolerance,
+            bind_group,
+            params,
+            preconditioner,
+            _buffers: buffers,
+            _textures: [rhs_tex, out_tex],
+            init: pipe("cg_init"),
+            reduce_mean: pipe("cg_reduce_mean"),
+            init_residual: pipe("cg_init_residual"),
+            reduce_init: pipe("cg_reduce_init"),
+            precond_in: pipe("cg_precond_in"),
+            precond_out: pipe("cg_precond_out"),
+            reduce_beta: pipe("cg_reduce_beta"),
+            update_d: pipe("cg_update_d"),
+            apply: pipe("cg_apply"),
+            reduce_alpha: pipe("cg_reduce_alpha"),
+            update_x: pipe("cg_update_x"),
+            reduce_residual: pipe("cg_reduce_residual"),
+            write_pressure: pipe("cg_write_pressure"),
+        }
+    }
+}
+
+fn cg_params(size: u32, tolerance: f32, cell_size: [f32; 2], boundary: Boundary) -> CgParams {
+    let (hx, hy) = (cell_size[0], cell_size[1]);
+    CgParams { size, groups: size.div_ceil(8), tolerance, boundary: boundary.code(), inv_h2: [1.0 / (hx * hx), 1.0 / (hy * hy)] }
+}
+
+impl PressureSolver for Cg {
+    fn name(&self) -> &'static str {
+        "cg"
+    }
+
+    fn set_domain(&self, queue: &wgpu::Queue, cell_size: [f32; 2], boundary: Boundary) {
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&cg_params(self.size, self.tolerance, cell_size, boundary)));
+        self.preconditioner.set_domain(queue, cell_size, boundary);
+    }
+
+    fn solve<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, fluid_group: &'a wgpu::BindGroup) {
+        let g = self.groups;
+        let grid = |c: &mut wgpu::ComputePass<'a>, pipeline: &'a wgpu::ComputePipeline| {
+            c.set_pipeline(pipeline);
+            c.dispatch_workgroups(g, g, 1);
+        };
+        let single = |c: &mut wgpu::ComputePass<'a>, pipeline: &'a wgpu::ComputePipeline| {
+            c.set_pipeline(pipeline);
+            c.dispatch_workgroups(1, 1, 1);
+        };
+
+        c.set_bind_group(0, &self.bind_group, &[]);
+        grid(c, &self.init);
+        single(c, &self.reduce_mean);
+        grid(c, &self.init_residual);
+        single(c, &self.reduce_init);
+        for _ in 0..self.max_iterations {
+            grid(c, &self.precond_in);
+            // Leaves our bind group bound again when it's done
+            self.preconditioner.solve(c, &self.bind_group);
+            grid(c, &self.precond_out);
+            single(c, &self.reduce_beta);
+            grid(c, &self.update_d);
+            grid(c, &self.apply);
+            single(c, &self.reduce_alpha);
+            grid(c, &self.update_x);
+            single(c, &self.reduce_residual);
+        }
+        grid(c, &self.write_pressure);
+        c.set_bind_group(0, fluid_group, &[]);
+    }
+
+    /// Every iteration is recorded; the ones after convergence return early
+    /// on the GPU, but still count.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dispatches(&self) -> u32 {
+        5 + self.max_iterations * (8 + self.preconditioner.dispatches())
+    }
+}
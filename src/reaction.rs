@@ -0,0 +1,414 @@
+//! Reaction-diffusion mode (`--gray-scott`): the Gray–Scott model on the
+//! 2D grid, its two chemicals carried by a `Simulation`'s flow. Left drag
+//! stirs the fluid as in 2D, and the pattern is smeared along the swirls
+//! and grows back into them; right drag seeds new growth. The kernels and
+//! the renderer are in `reaction.wgsl`.
+
+use std::sync::Arc;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    dpi::PhysicalSize,
+    event::MouseButton,
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::mode::{self, Mode};
+use crate::rng::Seed;
+use crate::simulation::{self, FluidPipelines, Simulation};
+use crate::{create_storage_tex, GpuEmitter, SimParams, MAX_EMITTERS};
+
+/// Reaction steps per frame. Odd, so with the advection's pass the
+/// chemicals end up back in the first texture.
+const REACTION_STEPS: u32 = 15;
+/// Seed patches scattered over the grid by a reset.
+const SEED_PATCHES: usize = 24;
+
+/// Which pattern the feed and kill rates grow (`--gray-scott`, 1 to 4).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GrayScottPattern {
+    /// Branching growth that fills the grid.
+    Coral,
+    /// Winding stripes.
+    Fingerprint,
+    /// Isolated dots.
+    Spots,
+    /// Dots that grow and split in two.
+    Mitosis,
+}
+
+impl GrayScottPattern {
+    const ALL: [GrayScottPattern; 4] = [
+        GrayScottPattern::Coral, GrayScottPattern::Fingerprint, GrayScottPattern::Spots, GrayScottPattern::Mitosis,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GrayScottPattern::Coral => "coral",
+            GrayScottPattern::Fingerprint => "fingerprint",
+            GrayScottPattern::Spots => "spots",
+            GrayScottPattern::Mitosis => "mitosis",
+        }
+    }
+
+    /// Feed and kill rates per reaction step.
+    fn rates(self) -> (f32, f32) {
+        match self {
+            GrayScottPattern::Coral => (0.0545, 0.062),
+            GrayScottPattern::Fingerprint => (0.037, 0.06),
+            GrayScottPattern::Spots => (0.035, 0.065),
+            GrayScottPattern::Mitosis => (0.0367, 0.0649),
+        }
+    }
+}
+
+impl std::str::FromStr for GrayScottPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("Gray–Scott pattern", s, &Self::ALL.map(|p| (p.name(), p)))
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ReactionParams {
+    grid_size: u32,
+    dt: f32,
+    cell_size: [f32; 2],
+    feed: f32,
+    kill: f32,
+    /// Nonzero while the chemicals ride the flow.
+    advect: u32,
+    /// 1 while seeding.
+    mouse_mode: u32,
+    mouse_pos: [f32; 2],
+    radius: f32,
+    _pad: f32,
+}
+
+/// Chemicals all u, with small square patches of v scattered over them to
+/// grow from.
+fn seeded(grid: u32, seed: Seed) -> Vec<[f32; 4]> {
+    let mut texels = vec![[1.0, 0.0, 0.0, 0.0]; (grid * grid) as usize];
+    let mut rng = seed.rng("gray-scott");
+    let half = (grid / 40).max(2) as i32;
+    for _ in 0..SEED_PATCHES {
+        let (cx, cy) = ((rng.next_f32() * grid as f32) as i32, (rng.next_f32() * grid as f32) as i32);
+        for y in (cy - half).max(0)..(cy + half).min(grid as i32) {
+            for x in (cx - half).max(0)..(cx + half).min(grid as i32) {
+                texels[(y as u32 * grid + x as u32) as usize] = [0.5, 0.25, 0.0, 0.0];
+            }
+        }
+    }
+    texels
+}
+
+/// The chemicals, the reaction kernels and the renderer. The flow is a
+/// `Simulation`'s, passed to `step`.
+struct GrayScott {
+    params: ReactionParams,
+    param_buffer: wgpu::Buffer,
+    chem: wgpu::Texture,
+    _views: Vec<wgpu::TextureView>,
+    /// [0] reads the first chemical texture and writes the second, [1] the
+    /// other way round.
+    bind_groups: [wgpu::BindGroup; 2],
+    advect: wgpu::ComputePipeline,
+    react: wgpu::ComputePipeline,
+    render_bg: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl GrayScott {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sim: &Simulation, grid: u32, pattern: GrayScottPattern) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("reaction_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../reaction.wgsl").into()),
+        });
+
+        let (feed, kill) = pattern.rates();
+        let params = ReactionParams {
+            grid_size: grid, dt: 0.016, cell_size: [1.0; 2], feed, kill, advect: 1,
+            mouse_mode: 0, mouse_pos: [0.0; 2], radius: grid as f32 * 0.03, _pad: 0.0,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("reaction_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let (chem_a, chem_a_view) = create_storage_tex(device, grid);
+        let (_chem_b, chem_b_view) = create_storage_tex(device, grid);
+
+        // ---- Compute ----
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false, min_binding_size: None,
+            },
+            count: None,
+        }];
+        entries.extend((1..=3).map(|binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::ReadWrite,
+                format: wgpu::TextureFormat::Rgba16Float,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        }));
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("reaction_compute_bgl"), entries: &entries,
+        });
+        let bind_group = |src: &wgpu::TextureView, dst: &wgpu::TextureView| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("reaction_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&sim.velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(src) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(dst) },
+            ],
+        });
+        let bind_groups = [bind_group(&chem_a_view, &chem_b_view), bind_group(&chem_b_view, &chem_a_view)];
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl), module: &module, entry_point: entry,
+            })
+        };
+
+        // ---- Render ----
+        let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("reaction_render_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("reaction_render_bg"), layout: &render_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&chem_a_view) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+        let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+        });
+        let render_pipeline = crate::create_render_pipeline(device, &render_pl, &module, format, "fs_reaction");
+
+        Self {
+            params, param_buffer, chem: chem_a,
+            _views: vec![chem_a_view, chem_b_view],
+            bind_groups,
+            advect: make_compute("advect"),
+            react: make_compute("react"),
+            render_bg, render_pipeline,
+        }
+    }
+
+    fn set_pattern(&mut self, pattern: GrayScottPattern) {
+        (self.params.feed, self.params.kill) = pattern.rates();
+        eprintln!("Gray–Scott pattern: {} (feed {}, kill {})", pattern.name(), self.params.feed, self.params.kill);
+    }
+
+    /// One fluid step, then carry the chemicals along and react them.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, sim: &'a Simulation, pipes: &'a FluidPipelines, sim_params: &SimParams) {
+        if self.params.advect != 0 {
+            sim.step(c, pipes, sim_params);
+        }
+        let grid = self.params.grid_size.div_ceil(8);
+        c.set_bind_group(0, &self.bind_groups[0], &[]);
+        c.set_pipeline(&self.advect);
+        c.dispatch_workgroups(grid, grid, 1);
+        c.set_pipeline(&self.react);
+        for i in 0..REACTION_STEPS as usize {
+            c.set_bind_group(0, &self.bind_groups[1 - i % 2], &[]);
+            c.dispatch_workgroups(grid, grid, 1);
+        }
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("reaction_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.render_pipeline);
+        r.set_bind_group(0, &self.render_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+
+    /// Scatter fresh seeds over still fluid.
+    fn reset(&self, queue: &wgpu::Queue, sim: &Simulation, seed: Seed) {
+        let grid = self.params.grid_size;
+        crate::upload_field(queue, &self.chem, grid, &seeded(grid, seed));
+        crate::upload_field(queue, &sim.velocity, grid, &vec![[0.0; 4]; (grid * grid) as usize]);
+    }
+}
+
+/// The reaction-diffusion window loop, taking over from `run` once the
+/// window and device exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let grid = config.grid_size;
+    let pattern = config.gray_scott.unwrap_or(GrayScottPattern::Coral);
+    let workarounds = crate::shader_workarounds(&config, &adapter);
+    let gpu = mode::Gpu::new(&config, window, surface, adapter, device, queue);
+    let (device, queue, format) = (&gpu.device, &gpu.queue, gpu.format());
+    let win_size = gpu.window.inner_size();
+
+    // ---- Grid ----
+    // The fluid's own step, stirred by the mouse but with no dye or heat to
+    // add; the obstacle mask and emitters stay empty
+    let fluid_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("compute_shader"),
+        source: wgpu::ShaderSource::Wgsl(workarounds.apply(include_str!("../fluid.wgsl")).into()),
+    });
+    let (_obstacle_tex, obstacle_view) = create_storage_tex(device, grid);
+    let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("emitters"),
+        size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let sim_params = SimParams {
+        add_strength: 0.0, heat_strength: 0.0, radius: grid as f32 * 0.06,
+        ..crate::base_params(&config, crate::cell_size_for(win_size))
+    };
+    let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&sim_params),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    let compute_bgl = crate::create_compute_bgl(device);
+    let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+    });
+    let fluid_pipes = FluidPipelines::new(device, &compute_pl, &fluid_module, workarounds);
+    let shared = simulation::Shared {
+        device, bind_group_layout: &compute_bgl, layout: &compute_pl,
+        params: &param_buffer, emitters: &emitter_buffer, obstacles: &obstacle_view, grid_size: grid,
+    };
+    let sim = Simulation::new(&shared, &fluid_module, config.pressure_solver);
+
+    let gray_scott = GrayScott::new(device, format, &sim, grid, pattern);
+    gray_scott.reset(queue, &sim, config.seed);
+    eprintln!("Gray–Scott mode: {} on a {}² grid", pattern.name(), grid);
+
+    let state = ReactionMode { gray_scott, sim, fluid_pipes, sim_params, param_buffer, seed: config.seed, last_cursor: None };
+    mode::run(gpu, event_loop, grid, state).await;
+}
+
+/// The reaction–diffusion window's state between frames.
+struct ReactionMode {
+    gray_scott: GrayScott,
+    sim: Simulation,
+    fluid_pipes: FluidPipelines,
+    sim_params: SimParams,
+    param_buffer: wgpu::Buffer,
+    /// Where R reseeds from.
+    seed: Seed,
+    last_cursor: Option<[f32; 2]>,
+}
+
+impl Mode for ReactionMode {
+    const STEP: &'static str = "reaction_step";
+
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue) {
+        match code {
+            KeyCode::KeyR => {
+                self.gray_scott.reset(queue, &self.sim, self.seed);
+                eprintln!("Reseeded");
+            }
+            KeyCode::KeyA => {
+                let p = &mut self.gray_scott.params;
+                p.advect ^= 1;
+                eprintln!("Advection by the flow: {}", if p.advect != 0 { "on" } else { "off" });
+            }
+            KeyCode::Digit1 | KeyCode::Digit2 | KeyCode::Digit3 | KeyCode::Digit4 => {
+                let i = crate::digit_index(code).unwrap_or(0);
+                self.gray_scott.set_pattern(GrayScottPattern::ALL[i]);
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse(&mut self, button: MouseButton, pressed: bool) {
+        if button == MouseButton::Left {
+            self.sim_params.mouse_down = pressed as u32;
+        } else {
+            self.gray_scott.params.mouse_mode = pressed as u32;
+        }
+        self.last_cursor = None;
+    }
+
+    fn cursor(&mut self, cursor: [f32; 2]) {
+        if let Some(last) = self.last_cursor {
+            self.sim_params.mouse_delta = [cursor[0] - last[0], cursor[1] - last[1]];
+        }
+        self.sim_params.mouse_pos = cursor;
+        self.gray_scott.params.mouse_pos = cursor;
+        self.last_cursor = Some(cursor);
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.sim_params.cell_size = crate::cell_size_for(size);
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue, _aspect: f32, _paused: bool) {
+        let p = &mut self.gray_scott.params;
+        p.dt = self.sim_params.dt;
+        p.cell_size = self.sim_params.cell_size;
+        queue.write_buffer(&self.gray_scott.param_buffer, 0, bytemuck::bytes_of(&self.gray_scott.params));
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.sim_params));
+        self.sim.set_domain(queue, &self.sim_params);
+        // Each stroke moves the fluid once
+        self.sim_params.mouse_delta = [0.0, 0.0];
+    }
+
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        self.gray_scott.step(c, &self.sim, &self.fluid_pipes, &self.sim_params);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.gray_scott.render(encoder, view);
+    }
+}
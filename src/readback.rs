@@ -0,0 +1,93 @@
+//! Reading a buffer back from the GPU without stalling the frame: a copy
+//! is recorded into a mappable buffer, mapped once its encoder is
+//! submitted, and collected a frame or two later when the map callback
+//! says it's done. Each `Readback` is one such buffer, with the state the
+//! callback sets and a tag for what the copy was of (the cell, the time),
+//! which comes back with the bytes.
+//!
+//! Takes `&self` throughout, so a solver that records through a shared
+//! reference can own one.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// States of a map, set by its callback.
+const WAITING: u8 = 0;
+const OK: u8 = 1;
+const FAILED: u8 = 2;
+
+pub struct Readback<T: Copy = ()> {
+    buffer: wgpu::Buffer,
+    /// One of the states above, for the pending copy.
+    state: Arc<AtomicU8>,
+    /// Copied into by an encoder that isn't submitted yet.
+    recorded: Cell<Option<T>>,
+    /// Being mapped, and not collected yet.
+    pending: Cell<Option<T>>,
+}
+
+impl<T: Copy> Readback<T> {
+    pub fn new(device: &wgpu::Device, label: &str, size: u64) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer, state: Arc::new(AtomicU8::new(WAITING)), recorded: Cell::new(None), pending: Cell::new(None) }
+    }
+
+    /// The buffer to copy into. Only while `is_free`; say so with
+    /// `recorded` after the copy.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Neither copied into nor mapped, so it can take a new copy.
+    pub fn is_free(&self) -> bool {
+        self.recorded.get().is_none() && self.pending.get().is_none()
+    }
+
+    /// A copy of `tag` was recorded into `buffer`.
+    pub fn recorded(&self, tag: T) {
+        self.recorded.set(Some(tag));
+    }
+
+    /// The encoder holding the copy was submitted: start mapping.
+    pub fn submitted(&self) {
+        let Some(tag) = self.recorded.take() else { return };
+        self.pending.set(Some(tag));
+        self.state.store(WAITING, Ordering::Release);
+        let state = self.state.clone();
+        self.buffer.slice(..).map_async(wgpu::MapMode::Read, move |r| {
+            state.store(if r.is_ok() { OK } else { FAILED }, Ordering::Release);
+        });
+    }
+
+    /// `read` of the copy's tag and bytes once the GPU is done with it,
+    /// after which the buffer is free again. With `wait`, blocks until it
+    /// is (natively; the web can't block). `None` with nothing to collect,
+    /// while the copy is still on its way (it isn't `is_free` then), and if
+    /// the map failed, which only happens with a lost device and leaves
+    /// nothing to read.
+    pub fn collect<R>(&self, device: &wgpu::Device, wait: bool, read: impl FnOnce(T, &[u8]) -> R) -> Option<R> {
+        let tag = self.pending.get()?;
+        device.poll(wgpu::Maintain::Poll);
+        if wait && self.state.load(Ordering::Acquire) == WAITING {
+            device.poll(wgpu::Maintain::Wait);
+        }
+        let out = match self.state.load(Ordering::Acquire) {
+            WAITING => return None,
+            OK => {
+                let out = read(tag, &self.buffer.slice(..).get_mapped_range());
+                self.buffer.unmap();
+                Some(out)
+            }
+            // Lost device: nothing to unmap
+            _ => None,
+        };
+        self.pending.set(None);
+        out
+    }
+}
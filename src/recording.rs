@@ -0,0 +1,194 @@
+//! Video recording (F10, `--record PATH.mp4`): every frame the window shows
+//! is copied from the swapchain into one of a small ring of buffers and
+//! mapped without waiting, and the finished ones are piped as raw RGBA into
+//! an `ffmpeg` process, which encodes an H.264 MP4. The video runs at a
+//! fixed `--record-fps`: each frame is written as many times as output
+//! frames came due since the last one, by the wall clock, so a slow or
+//! uneven frame rate still plays back at real speed. Writing happens on a
+//! thread, so a slow encoder drops frames instead of stalling the
+//! simulation, and the next frame is repeated to cover them.
+
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use crate::frame_capture::CaptureRing;
+
+/// Frames copied and not yet handed to the writer, at most.
+const RING: usize = 4;
+/// Frames the writer can fall behind by before new ones are dropped.
+const QUEUE: usize = 8;
+
+/// A frame for the writer, and how many times to write it.
+struct Frame {
+    rgba: Vec<u8>,
+    repeats: u32,
+}
+
+struct Session {
+    path: String,
+    /// Frames are tagged with when they were shown, since `started`.
+    ring: CaptureRing,
+    started: Instant,
+    /// Output frames sent to the writer so far.
+    written: u64,
+    sender: Option<SyncSender<Frame>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+pub struct Recorder {
+    fps: u32,
+    /// The swapchain allows copies, so there's anything to record.
+    supported: bool,
+    session: Option<Session>,
+}
+
+impl Recorder {
+    pub fn new(fps: u32, supported: bool) -> Self {
+        Self { fps, supported, session: None }
+    }
+
+    /// F10: start recording frames of `width` × `height` into
+    /// `recording-<time>.mp4`, or finish the recording.
+    pub fn toggle(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) {
+        if self.session.is_some() {
+            self.stop(device);
+        } else {
+            let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            self.start(format!("recording-{}.mp4", secs), format, width, height);
+        }
+    }
+
+    /// Start recording into `path`, replacing any file there.
+    pub fn start(&mut self, path: String, format: wgpu::TextureFormat, width: u32, height: u32) {
+        if !self.supported {
+            eprintln!("Recording needs a swapchain that can be copied from, and this one can't");
+            return;
+        }
+        let Some(ring) = CaptureRing::new("recording_readback", RING, format, width, height) else {
+            eprintln!("Can't record a {:?} swapchain", format);
+            return;
+        };
+        let pixel_format = if ring.bgra() { "bgra" } else { "rgba" };
+        // yuv420p, which players expect, needs even sides; the pad adds
+        // a black row or column where they're odd
+        let child = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", pixel_format])
+            .args(["-s", &format!("{}x{}", width, height), "-framerate", &self.fps.to_string(), "-i", "-"])
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-c:v", "libx264", "-preset", "veryfast", "-crf", "18"])
+            .args(["-pix_fmt", "yuv420p", "-movflags", "+faststart", &path])
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Can't start ffmpeg ({}); recording needs it installed and on the PATH", e);
+                return;
+            }
+        };
+        let stdin = child.stdin.take().expect("ffmpeg's stdin was piped");
+        let (sender, receiver) = mpsc::sync_channel(QUEUE);
+        let writer = std::thread::spawn(move || write_frames(receiver, stdin, child));
+        eprintln!("Recording {}×{} at {} fps into {} (F10 stops)", width, height, self.fps, path);
+        self.session = Some(Session {
+            path, ring, started: Instant::now(), written: 0, sender: Some(sender), writer: Some(writer),
+        });
+    }
+
+    /// Finish the recording: wait for the frames still being copied, then
+    /// for ffmpeg to write the file.
+    pub fn stop(&mut self, device: &wgpu::Device) {
+        let Some(mut session) = self.session.take() else { return };
+        self.send_mapped(device, &mut session, true);
+        // Closing the pipe ends ffmpeg's input
+        drop(session.sender.take());
+        if let Some(writer) = session.writer.take() {
+            let _ = writer.join();
+        }
+        let seconds = session.written as f64 / self.fps as f64;
+        eprintln!("Recording stopped: {:.1} s in {}", seconds, session.path);
+    }
+
+    /// The window is now `width` × `height`. A video can't follow a change
+    /// of size, so that ends the recording.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if self.session.as_ref().is_some_and(|s| [s.ring.width(), s.ring.height()] != [width, height]) {
+            eprintln!("The window was resized, so the recording ends here");
+            self.stop(device);
+        }
+    }
+
+    /// Copy the finished frame in `texture`, unless every buffer of the
+    /// ring is still in use. Call `submitted` once the encoder is submitted.
+    pub fn record(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let Some(session) = self.session.as_mut().filter(|s| s.sender.is_some()) else { return };
+        session.ring.record(device, encoder, texture, session.started.elapsed());
+    }
+
+    pub fn submitted(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.ring.submitted();
+        }
+    }
+
+    /// Hand the frames the GPU is done with to the writer, in order,
+    /// without waiting.
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        let Some(mut session) = self.session.take() else { return };
+        self.send_mapped(device, &mut session, false);
+        self.session = Some(session);
+    }
+
+    /// With `wait`, wait for the frames still being copied, and queue
+    /// behind a busy writer instead of dropping frames.
+    fn send_mapped(&self, device: &wgpu::Device, session: &mut Session, wait: bool) {
+        let Session { ring, written, sender, .. } = session;
+        ring.collect(device, wait, |frame| {
+            // Output frames due by the time this one was shown
+            let due = (frame.at.as_secs_f64() * self.fps as f64) as u64 + 1;
+            if due <= *written {
+                return;
+            }
+            let mut rgba = Vec::new();
+            for row in frame.rows() {
+                rgba.extend_from_slice(row);
+            }
+            let out = Frame { rgba, repeats: (due - *written) as u32 };
+            let sent = match sender {
+                Some(sender) if wait => sender.send(out).map_err(|e| TrySendError::Disconnected(e.0)),
+                Some(sender) => sender.try_send(out),
+                None => Err(TrySendError::Disconnected(out)),
+            };
+            match sent {
+                Ok(()) => *written = due,
+                // Dropped; the next frame covers for it
+                Err(TrySendError::Full(_)) => {}
+                Err(TrySendError::Disconnected(_)) => {
+                    eprintln!("ffmpeg stopped taking frames; the recording ends here");
+                    *sender = None;
+                }
+            }
+        });
+    }
+}
+
+/// The writer thread: frames into ffmpeg until the channel closes, then
+/// wait for it to finish the file.
+fn write_frames(receiver: mpsc::Receiver<Frame>, mut stdin: ChildStdin, mut child: Child) {
+    'frames: for frame in receiver {
+        for _ in 0..frame.repeats {
+            if let Err(e) = stdin.write_all(&frame.rgba) {
+                eprintln!("Can't write to ffmpeg: {}", e);
+                break 'frames;
+            }
+        }
+    }
+    drop(stdin);
+    match child.wait() {
+        Ok(status) if !status.success() => eprintln!("ffmpeg failed ({})", status),
+        Err(e) => eprintln!("Can't wait for ffmpeg: {}", e),
+        Ok(_) => {}
+    }
+}
@@ -0,0 +1,510 @@
+//! Client/server split: a headless server (`--serve`) runs the solver with no
+//! window, and thin clients (`--connect`) draw the fields it streams and send
+//! their mouse, painting and scene keys back. Lets a machine without a
+//! capable GPU drive a simulation running on one that has.
+//!
+//! Wire format (TCP, little endian). A client opens with `HELLO` and its
+//! grid size as a u32, then sends `Input`s: a tag byte and its payload. The
+//! server sends frames: `FRAME`, the grid size and the payload length as
+//! u32s, then the payload. A payload is the density, velocity and obstacle
+//! layers as raw `Rgba16Float` rows, XORed with the previous frame sent to
+//! that client (all zeros before the first) and run-length coded: pairs of
+//! LEB128 counts, zero bytes then literal bytes, each followed by the
+//! literals. Unchanged texels cost next to nothing, so still obstacles and
+//! empty regions are almost free.
+
+use crate::dye::DyeColor;
+use crate::rod::Rod;
+use crate::scene::Boundary;
+use crate::wall::{LAYERS, TEXEL};
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+// The server's; the web only has the client
+#[cfg(not(target_arch = "wasm32"))]
+use crate::wall;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{SocketAddr, TcpListener};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TrySendError};
+
+const HELLO: [u8; 4] = *b"WFRH";
+const FRAME: [u8; 4] = *b"WFRF";
+
+/// What a client asks the server's simulation to do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    /// Mouse state, in grid cells. `delta` is the motion since the last
+    /// `Pointer` sent.
+    Pointer { pos: [f32; 2], delta: [f32; 2], down: bool },
+    /// Shift + drag: stamp (or erase) obstacle cells up to `pos`.
+    Paint { pos: [f32; 2], solid: bool },
+    PaintEnd,
+    /// R: reload and reset the current scene.
+    Reset,
+    /// Number keys: switch to the builtin scene at this index.
+    Scene(u32),
+    /// The client window's cell aspect, see `SimParams::cell_size`.
+    CellSize([f32; 2]),
+    /// Gravity keys: the new `SimParams::gravity` and whether it acts on dye.
+    Gravity { gravity: [f32; 2], by_dye: bool },
+    /// X: the color the mouse now injects, and the client's `--dye-cycle`.
+    DyeColor { color: DyeColor, cycle: f32 },
+    /// W: the new grid boundary.
+    Boundary(Boundary),
+    /// I: the stirring rod's settings, or `None` once it's off. The server
+    /// moves the rod, so it stirs in step with the simulation.
+    Rod(Option<Rod>),
+    /// N / Shift + N: simulation steps per frame.
+    Substeps(u32),
+}
+
+impl Input {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(21);
+        let floats = |out: &mut Vec<u8>, v: &[f32]| v.iter().for_each(|f| out.extend_from_slice(&f.to_le_bytes()));
+        match *self {
+            Input::Pointer { pos, delta, down } => {
+                out.push(0);
+                floats(&mut out, &[pos[0], pos[1], delta[0], delta[1]]);
+                out.push(down as u8);
+            }
+            Input::Paint { pos, solid } => {
+                out.push(1);
+                floats(&mut out, &pos);
+                out.push(solid as u8);
+            }
+            Input::PaintEnd => out.push(2),
+            Input::Reset => out.push(3),
+            Input::Scene(i) => {
+                out.push(4);
+                out.extend_from_slice(&i.to_le_bytes());
+            }
+            Input::CellSize(c) => {
+                out.push(5);
+                floats(&mut out, &c);
+            }
+            Input::Gravity { gravity, by_dye } => {
+                out.push(6);
+                floats(&mut out, &gravity);
+                out.push(by_dye as u8);
+            }
+            Input::DyeColor { color, cycle } => {
+                out.push(7);
+                floats(&mut out, &[cycle]);
+                out.extend_from_slice(&color.index().to_le_bytes());
+            }
+            Input::Boundary(b) => {
+                out.push(8);
+                out.extend_from_slice(&b.code().to_le_bytes());
+            }
+            Input::Rod(rod) => {
+                out.push(9);
+                let r = rod.unwrap_or_default();
+                floats(&mut out, &[r.mass, r.drag]);
+                out.push(rod.is_some() as u8);
+            }
+            Input::Substeps(n) => {
+                out.push(10);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read(r: &mut impl Read) -> io::Result<Self> {
+        let mut tag = [0u8; 1];
+        r.read_exact(&mut tag)?;
+        let mut f32s = |n: usize| -> io::Result<Vec<f32>> {
+            let mut buf = vec![0u8; n * 4];
+            r.read_exact(&mut buf)?;
+            Ok(buf.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect())
+        };
+        let input = match tag[0] {
+            0 => {
+                let v = f32s(4)?;
+                Input::Pointer { pos: [v[0], v[1]], delta: [v[2], v[3]], down: read_u8(r)? != 0 }
+            }
+            1 => {
+                let v = f32s(2)?;
+                Input::Paint { pos: [v[0], v[1]], solid: read_u8(r)? != 0 }
+            }
+            2 => Input::PaintEnd,
+            3 => Input::Reset,
+            4 => Input::Scene(read_u32(r)?),
+            5 => {
+                let v = f32s(2)?;
+                Input::CellSize([v[0], v[1]])
+            }
+            6 => {
+                let v = f32s(2)?;
+                Input::Gravity { gravity: [v[0], v[1]], by_dye: read_u8(r)? != 0 }
+            }
+            7 => {
+                let cycle = f32s(1)?[0];
+                let i = read_u32(r)?;
+                let color = DyeColor::from_index(i).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unknown dye color {}", i)))?;
+                Input::DyeColor { color, cycle }
+            }
+            8 => {
+                let code = read_u32(r)?;
+                Input::Boundary(Boundary::from_code(code).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unknown boundary {}", code)))?)
+            }
+            9 => {
+                let v = f32s(2)?;
+                let on = read_u8(r)? != 0;
+                Input::Rod(on.then(|| Rod::new(v[0], v[1])))
+            }
+            10 => Input::Substeps(read_u32(r)?.clamp(1, crate::MAX_SUBSTEPS)),
+            t => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown input tag {}", t))),
+        };
+        Ok(input)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+// ---- Compression ----
+
+#[cfg(not(target_arch = "wasm32"))]
+fn push_count(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn take_count(data: &[u8], at: &mut usize) -> Option<usize> {
+    let mut n = 0usize;
+    for shift in (0..64).step_by(7) {
+        let b = *data.get(*at)?;
+        *at += 1;
+        n |= ((b & 0x7F) as usize) << shift;
+        if b & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// Encode `cur` against `prev` and make `cur` the new `prev`.
+#[cfg(not(target_arch = "wasm32"))]
+fn compress(prev: &mut [u8], cur: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < cur.len() {
+        let zeros = cur[i..].iter().zip(&prev[i..]).take_while(|(a, b)| a == b).count();
+        let start = i + zeros;
+        // A literal run ends at the first stretch of 4 unchanged bytes, so
+        // a single matching byte inside a changed texel doesn't split it
+        let mut end = start;
+        while end < cur.len() && cur[end..(end + 4).min(cur.len())] != prev[end..(end + 4).min(cur.len())] {
+            end += 1;
+        }
+        push_count(&mut out, zeros);
+        push_count(&mut out, end - start);
+        out.extend(cur[start..end].iter().zip(&prev[start..end]).map(|(a, b)| a ^ b));
+        i = end;
+    }
+    prev.copy_from_slice(cur);
+    out
+}
+
+/// The longest `compress` can make a payload for `len` bytes: every run
+/// but the last holds at least one changed byte and the four unchanged
+/// ones that end it, and costs two counts of at most 10 bytes.
+fn max_compressed(len: usize) -> usize {
+    len + (len / 5 + 1) * 20
+}
+
+/// Apply a `compress`ed payload to `prev` in place. `None` if it's corrupt
+/// or doesn't cover all of `prev`. Counts come off the network, so every
+/// sum is checked.
+fn decompress(prev: &mut [u8], data: &[u8]) -> Option<()> {
+    let (mut at, mut i) = (0, 0usize);
+    while at < data.len() {
+        i = i.checked_add(take_count(data, &mut at)?)?;
+        let n = take_count(data, &mut at)?;
+        let literals = data.get(at..at.checked_add(n)?)?;
+        prev.get_mut(i..i.checked_add(n)?)?.iter_mut().zip(literals).for_each(|(p, x)| *p ^= x);
+        at += n;
+        i += n;
+    }
+    (i == prev.len()).then_some(())
+}
+
+// ---- Server ----
+
+/// Accepts clients in the background, collects their input and streams them
+/// the fields.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Server {
+    clients: Arc<Mutex<Vec<Link>>>,
+    inputs: Receiver<Input>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Link {
+    peer: SocketAddr,
+    /// Holds at most one frame; a client that hasn't finished receiving the
+    /// previous one skips the next.
+    frames: SyncSender<Arc<Vec<u8>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Server {
+    pub fn listen(addr: &str, grid: u32) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        eprintln!("Serving the simulation on {}", listener.local_addr()?);
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let (input_tx, inputs) = channel();
+        let list = clients.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream.and_then(|s| accept(s, grid, input_tx.clone())) {
+                    Ok(link) => {
+                        eprintln!("Client {} connected", link.peer);
+                        list.lock().unwrap().push(link);
+                    }
+                    Err(e) => eprintln!("Client rejected: {}", e),
+                }
+            }
+        });
+        Ok(Self { clients, inputs })
+    }
+
+    /// Input received from any client since the last call, oldest first.
+    pub fn inputs(&self) -> impl Iterator<Item = Input> + '_ {
+        self.inputs.try_iter()
+    }
+
+    /// Read `fields` back and queue them for every client. Blocks on the
+    /// GPU, but only while someone is connected.
+    pub fn broadcast(&self, device: &wgpu::Device, queue: &wgpu::Queue, fields: [&wgpu::Texture; LAYERS], grid: u32) {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return;
+        }
+        let frame = Arc::new(wall::read_layers(device, queue, fields, grid).concat());
+        clients.retain(|link| match link.frames.try_send(frame.clone()) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => {
+                eprintln!("Client {} disconnected", link.peer);
+                false
+            }
+        });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn accept(mut stream: TcpStream, grid: u32, inputs: Sender<Input>) -> io::Result<Link> {
+    let mut hello = [0u8; 8];
+    stream.read_exact(&mut hello)?;
+    if hello[..4] != HELLO {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a wgpu-fluid client"));
+    }
+    let client_grid = u32::from_le_bytes(hello[4..].try_into().unwrap());
+    stream.set_nodelay(true)?;
+    let peer = stream.peer_addr()?;
+    // The client checks the grid size in the first frame header and reports
+    // the mismatch itself; the server just logs it
+    if client_grid != grid {
+        eprintln!("Client {} runs a {1}x{1} grid, this server {2}x{2}", peer, client_grid, grid);
+    }
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    std::thread::spawn(move || {
+        while let Ok(input) = Input::read(&mut reader) {
+            if inputs.send(input).is_err() {
+                break;
+            }
+        }
+    });
+
+    let (frames, pending) = sync_channel::<Arc<Vec<u8>>>(1);
+    std::thread::spawn(move || {
+        let mut prev = vec![0u8; (grid * grid * TEXEL) as usize * LAYERS];
+        for frame in pending {
+            let payload = compress(&mut prev, &frame);
+            let mut out = Vec::with_capacity(12 + payload.len());
+            out.extend_from_slice(&FRAME);
+            out.extend_from_slice(&grid.to_le_bytes());
+            out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            out.extend_from_slice(&payload);
+            if stream.write_all(&out).is_err() {
+                break;
+            }
+        }
+        // Unblock the input reader
+        stream.shutdown(std::net::Shutdown::Both).ok();
+    });
+    Ok(Link { peer, frames })
+}
+
+// ---- Client ----
+
+/// Connection to a server. A background thread keeps the newest frame.
+pub struct Client {
+    stream: Mutex<TcpStream>,
+    latest: Arc<Mutex<Option<Vec<u8>>>>,
+    grid: u32,
+}
+
+impl Client {
+    pub fn connect(addr: &str, grid: u32) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut hello = HELLO.to_vec();
+        hello.extend_from_slice(&grid.to_le_bytes());
+        stream.write_all(&hello)?;
+        eprintln!("Connected to simulation server {}", addr);
+
+        let latest = Arc::new(Mutex::new(None));
+        let slot = latest.clone();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        std::thread::spawn(move || {
+            let mut fields = vec![0u8; (grid * grid * TEXEL) as usize * LAYERS];
+            loop {
+                match read_frame(&mut reader, grid, &mut fields) {
+                    Ok(()) => *slot.lock().unwrap() = Some(fields.clone()),
+                    Err(e) => {
+                        eprintln!("Simulation server connection lost: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { stream: Mutex::new(stream), latest, grid })
+    }
+
+    /// Send `input` to the server. Failures are dropped; the reader thread
+    /// reports a lost connection.
+    pub fn send(&self, input: Input) {
+        self.stream.lock().unwrap().write_all(&input.encode()).ok();
+    }
+
+    /// Upload the newest frame from the server into `fields`, if one arrived
+    /// since the last call.
+    pub fn upload_latest(&self, queue: &wgpu::Queue, fields: [&wgpu::Texture; LAYERS]) {
+        let Some(texels) = self.latest.lock().unwrap().take() else { return };
+        let g = self.grid;
+        for (tex, layer) in fields.iter().zip(texels.chunks((g * g * TEXEL) as usize)) {
+            queue.write_texture(
+                tex.as_image_copy(),
+                layer,
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(g * TEXEL), rows_per_image: Some(g) },
+                wgpu::Extent3d { width: g, height: g, depth_or_array_layers: 1 },
+            );
+        }
+    }
+}
+
+fn read_frame(r: &mut impl Read, grid: u32, fields: &mut [u8]) -> io::Result<()> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if magic != FRAME {
+        return Err(invalid("unexpected data from the server".to_string()));
+    }
+    let server_grid = read_u32(r)?;
+    if server_grid != grid {
+        return Err(invalid(format!("the server runs a {0}x{0} grid, start this client with --grid {0}", server_grid)));
+    }
+    let len = read_u32(r)? as usize;
+    if len > max_compressed(fields.len()) {
+        return Err(invalid(format!("a {} byte frame is too large for a {1}x{1} grid", len, grid)));
+    }
+    let mut payload = vec![0u8; len];
+    r.read_exact(&mut payload)?;
+    decompress(fields, &payload).ok_or_else(|| invalid("corrupt frame from the server".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two frames of `len` bytes: the second changes a few texels and a
+    /// long stretch, the way a moving blob of dye does.
+    fn frames(len: usize) -> (Vec<u8>, Vec<u8>) {
+        let first: Vec<u8> = (0..len).map(|i| (i / 8 % 3) as u8).collect();
+        let mut second = first.clone();
+        for i in [0, 9, 10, 500, len - 1] {
+            second[i] ^= 0x5a;
+        }
+        second[1000..1400].iter_mut().for_each(|b| *b = b.wrapping_add(1));
+        (first, second)
+    }
+
+    #[test]
+    fn compress_round_trips() {
+        let len = 4096;
+        let (first, second) = frames(len);
+        let (mut sent, mut received) = (vec![0u8; len], vec![0u8; len]);
+        for frame in [&first, &second, &second] {
+            let payload = compress(&mut sent, frame);
+            assert!(payload.len() <= max_compressed(len));
+            decompress(&mut received, &payload).unwrap();
+            assert!(received == *frame);
+        }
+        // An unchanged frame is one run of zeros
+        assert_eq!(compress(&mut sent, &second), [0x80, 0x20, 0]);
+        // The worst case: a changed byte in every fifth
+        let mut prev = vec![0u8; len];
+        let sparse: Vec<u8> = (0..len).map(|i| (i % 5 == 0) as u8).collect();
+        assert!(compress(&mut prev, &sparse).len() <= max_compressed(len));
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_payloads() {
+        let len = 4096;
+        let (first, second) = frames(len);
+        let mut sent = first.clone();
+        let payload = compress(&mut sent, &second);
+        for cut in 0..payload.len() {
+            let mut received = first.clone();
+            assert!(decompress(&mut received, &payload[..cut]).is_none(), "{} of {} bytes applied", cut, payload.len());
+        }
+        // Counts that run off the frame, or would overflow on adding up
+        let mut received = first.clone();
+        assert!(decompress(&mut received, &[0x80, 0x20, 1, 7]).is_none());
+        // usize::MAX, which wraps past 0 when added to anything
+        let huge = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert!(decompress(&mut received, &[&[5, 0][..], &huge[..], &[0]].concat()).is_none());
+        assert!(decompress(&mut received, &[&[0][..], &huge[..], &[1]].concat()).is_none());
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let grid = 4;
+        let mut fields = vec![0u8; (grid * grid * TEXEL) as usize * LAYERS];
+        let mut wire = FRAME.to_vec();
+        wire.extend_from_slice(&grid.to_le_bytes());
+        wire.extend_from_slice(&u32::MAX.to_le_bytes());
+        let e = read_frame(&mut &wire[..], grid, &mut fields).unwrap_err();
+        assert!(e.to_string().contains("too large"), "{}", e);
+
+        // A well-formed frame for the same grid goes through
+        let frame: Vec<u8> = (0..fields.len()).map(|i| i as u8).collect();
+        let payload = compress(&mut vec![0u8; fields.len()], &frame);
+        let mut wire = FRAME.to_vec();
+        wire.extend_from_slice(&grid.to_le_bytes());
+        wire.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        wire.extend_from_slice(&payload);
+        read_frame(&mut &wire[..], grid, &mut fields).unwrap();
+        assert!(fields == frame);
+    }
+}
@@ -0,0 +1,71 @@
+//! The session's random numbers (`--seed`). Everything random on the CPU
+//! (scene noise, the liquid modes' starting particles and ripples) and the
+//! GPU particle respawns draw from one seed, so a run can be repeated
+//! exactly by passing the seed it printed.
+//!
+//! Each user gets its own stream by name instead of sharing one generator:
+//! that way a new draw in one place doesn't shift the numbers every other
+//! place gets.
+
+use std::str::FromStr;
+
+/// Seed used when `--seed` isn't given.
+pub const DEFAULT_SEED: u64 = 0;
+
+/// Small seedable generator.
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Seed(pub u64);
+
+impl Seed {
+    /// A seed from the clock, for `--seed random`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn random() -> Self {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        // Spread out so seeds a moment apart look nothing alike, and keep
+        // them short enough to type back in
+        let seed = Seed(SplitMix64(nanos).next_u64() % 1_000_000_000);
+        eprintln!("Random seed {0} (--seed {0} repeats this run)", seed.0);
+        seed
+    }
+
+    /// The generator for `stream`, e.g. `"flip-dam"`.
+    pub fn rng(self, stream: &str) -> SplitMix64 {
+        // FNV-1a of the name, then one SplitMix64 step to mix in the seed
+        let name = stream.bytes().fold(0xCBF2_9CE4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01B3));
+        SplitMix64(SplitMix64(self.0 ^ name).next_u64())
+    }
+}
+
+impl Default for Seed {
+    fn default() -> Self {
+        Seed(DEFAULT_SEED)
+    }
+}
+
+impl FromStr for Seed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            #[cfg(not(target_arch = "wasm32"))]
+            "random" => Ok(Seed::random()),
+            n => n.trim().parse().map(Seed).map_err(|_| format!("--seed expects a number or `random`, got `{}`", s)),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Stirring rod tool (I). Instead of splatting the raw mouse motion into the
+//! fluid, the cursor holds a round rod through a spring. The rod has mass
+//! and feels drag, so it lags behind quick jerks, swings past where the
+//! cursor stops and coasts on after the button is let go. The fluid under
+//! it is dragged along at the rod's own, smoothed velocity.
+
+use crate::SimParams;
+use std::fmt;
+
+/// Spring constant of the hand holding the rod, per unit of mass at mass 1.
+const STIFFNESS: f32 = 400.0;
+/// Below this speed (grid cells/s) a let-go rod has stopped and is lifted
+/// out of the fluid.
+const REST_SPEED: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rod {
+    /// Heavier rods lag and overshoot more.
+    pub mass: f32,
+    /// Damping of the rod's motion through the fluid, per second at mass 1.
+    pub drag: f32,
+    /// Rod position and velocity, in grid cells and grid cells per second.
+    pos: [f32; 2],
+    vel: [f32; 2],
+    /// Held or still coasting, so in the fluid.
+    active: bool,
+}
+
+impl Default for Rod {
+    fn default() -> Self {
+        Self::new(1.0, 6.0)
+    }
+}
+
+impl Rod {
+    pub fn new(mass: f32, drag: f32) -> Self {
+        Self { mass, drag, pos: [0.0; 2], vel: [0.0; 2], active: false }
+    }
+
+    /// Advance the rod one step of `params.dt`, pulled toward the cursor at
+    /// `params.mouse_pos` while the button is down.
+    pub fn step(&mut self, params: &SimParams) {
+        let held = params.mouse_down != 0;
+        if held && !self.active {
+            // Dipped in where the cursor is, at rest
+            self.pos = params.mouse_pos;
+            self.vel = [0.0; 2];
+        }
+        // Backward Euler on m a = k (target - x) - c v, which stays stable
+        // for any mass, drag and dt
+        let dt = params.dt;
+        let k = if held { STIFFNESS } else { 0.0 };
+        let denom = 1.0 + dt * self.drag / self.mass + dt * dt * k / self.mass;
+        for i in 0..2 {
+            let pull = k * (params.mouse_pos[i] - self.pos[i]);
+            self.vel[i] = (self.vel[i] + dt * pull / self.mass) / denom;
+            self.pos[i] += dt * self.vel[i];
+        }
+        self.active = held || self.vel[0].hypot(self.vel[1]) > REST_SPEED;
+    }
+
+    /// `params` with the mouse replaced by the rod: it stirs where the rod
+    /// is, by the rod's motion this step, for as long as it's in the fluid.
+    pub fn apply(&self, params: &SimParams) -> SimParams {
+        SimParams {
+            mouse_pos: self.pos,
+            mouse_delta: [self.vel[0] * params.dt, self.vel[1] * params.dt],
+            mouse_down: self.active as u32,
+            rod: 1,
+            ..*params
+        }
+    }
+}
+
+impl std::str::FromStr for Rod {
+    type Err = String;
+
+    /// `MASS` or `MASS:DRAG`, e.g. `2:4`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (mass, drag) = s.split_once(':').unwrap_or((s, ""));
+        let mass = mass.parse().ok().filter(|m| *m > 0.0).ok_or_else(|| format!("bad rod mass in `{}` (expected a positive number)", s))?;
+        let drag = match drag {
+            "" => Rod::default().drag,
+            d => d.parse().ok().filter(|d| *d >= 0.0).ok_or_else(|| format!("bad rod drag in `{}` (expected a non-negative number)", s))?,
+        };
+        Ok(Rod::new(mass, drag))
+    }
+}
+
+impl fmt::Display for Rod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mass {}, drag {}", self.mass, self.drag)
+    }
+}
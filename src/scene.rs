@@ -0,0 +1,719 @@
+//! Scene descriptions: initial dye/velocity blobs, continuous emitters,
+//! solid obstacles and `SimParams` overrides, written in TOML. All positions and sizes are in normalized domain units (0..1, y
+//! down), so a scene works at any grid size.
+//!
+//! ```toml
+//! name = "Wind tunnel"
+//!
+//! [params]
+//! dye_decay = "half-life:11"   # or "none", "threshold:<secs>:<min>"
+//! velocity_decay = "half-life:11"
+//! viscosity = 0.0                # cells²/s; also `diffusion` for dye
+//! gravity = [0.0, 40.0]          # cells/s², +y is down
+//! gravity_mode = "dye"           # or "uniform"
+//! wind = [20.0, 0.0]             # ambient drift, cells/s
+//! wind_gust = 0.5                # ± fraction of the wind...
+//! wind_gust_period = 4.0         # ...over this many seconds
+//! boundary = "walls"             # or "wrap" (opposite edges join), "outflow" (open)
+//! well = 60.0                    # pull of a gravity well at the center, on dye
+//! dye_density = [30.0, 0.0, -30.0] # per red/green/blue dye: + sinks, - rises
+//! wake = 60.0                    # eddies stirred into obstacle wakes, cells/s²
+//! subgrid = 0.15                 # sub-grid turbulence from shear, drawn as dye breakup
+//! dye_mass = 2.0                 # variable density: dye makes the fluid this much heavier...
+//! heat_expansion = 0.5           # ...and heat this much lighter, per unit
+//!
+//! [[blob]]
+//! pos = [0.5, 0.5]
+//! radius = 0.12
+//! density = 1.0
+//! color = [1.0, 0.3, 0.1] # dye color, red/green/blue; white if omitted
+//! velocity = [0.0, 0.0]   # grid cells per second
+//!
+//! [[emitter]]
+//! pos = [0.05, 0.5]
+//! radius = 0.04
+//! velocity = [60.0, 0.0]  # grid cells per second
+//! density = 1.5           # dye per second
+//! color = [0.2, 0.6, 1.0]
+//! temperature = 2.0       # heat per second (rises, see heat_lift)
+//!
+//! [[obstacle]]
+//! shape = "circle"        # or "box" with min/max corners
+//! center = [0.35, 0.5]
+//! radius = 0.06
+//! motion = "oscillate"    # or "rotate"; omit for a fixed obstacle
+//! amplitude = [0.0, 0.1]  # oscillate: peak offset
+//! period = 2.0            # oscillate: seconds per cycle
+//! # rotate: angular_velocity (rad/s, clockwise on screen), optional pivot
+//!
+//! [noise]                 # divergence-free random initial velocity
+//! spectrum = "power"      # E(k) ∝ k^slope, or "peaked" around k_peak
+//! slope = -1.667
+//! k_min = 2               # wavenumber band, in cycles across the domain
+//! k_max = 24
+//! rms = 30.0              # grid cells per second
+//! seed = 7                # combined with --seed
+//!
+//! [benchmark]             # a canonical initial flow instead of rest
+//! flow = "taylor_green"   # or "kelvin_helmholtz", "double_shear"
+//! speed = 40.0            # peak speed, grid cells per second
+//! modes = 2               # taylor_green: vortex pairs across the domain
+//! ```
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer};
+
+use crate::benchmark::{BenchmarkSpec, Flow};
+use crate::decay::Decay;
+use crate::noise::{NoiseSpec, Spectrum};
+use crate::rng::Seed;
+
+/// Scenes shipped with the binary, embedded so they work without the
+/// `scenes/` directory (and on the web).
+pub const BUILTIN: [(&str, &str); 14] = [
+    ("default", include_str!("../scenes/default.toml")),
+    ("twin_jets", include_str!("../scenes/twin_jets.toml")),
+    ("wind_tunnel", include_str!("../scenes/wind_tunnel.toml")),
+    ("ink_drops", include_str!("../scenes/ink_drops.toml")),
+    ("turbulence", include_str!("../scenes/turbulence.toml")),
+    ("honey", include_str!("../scenes/honey.toml")),
+    ("smoke", include_str!("../scenes/smoke.toml")),
+    ("paddle", include_str!("../scenes/paddle.toml")),
+    ("falling_ink", include_str!("../scenes/falling_ink.toml")),
+    ("galaxy", include_str!("../scenes/galaxy.toml")),
+    ("lava_lamp", include_str!("../scenes/lava_lamp.toml")),
+    ("kelvin_helmholtz", include_str!("../scenes/kelvin_helmholtz.toml")),
+    ("taylor_green", include_str!("../scenes/taylor_green.toml")),
+    ("double_shear", include_str!("../scenes/double_shear.toml")),
+];
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scene {
+    pub name: String,
+    pub description: String,
+    pub params: ParamOverrides,
+    #[serde(rename = "blob")]
+    pub blobs: Vec<Blob>,
+    #[serde(rename = "emitter")]
+    pub emitters: Vec<Emitter>,
+    #[serde(rename = "obstacle")]
+    pub obstacles: Vec<Obstacle>,
+    /// Random divergence-free velocity added under the blobs.
+    #[serde(deserialize_with = "noise")]
+    pub noise: Option<NoiseSpec>,
+    /// Canonical flow and dye the blobs and noise are added to.
+    #[serde(deserialize_with = "benchmark")]
+    pub benchmark: Option<BenchmarkSpec>,
+}
+
+/// Optional `SimParams` values; unset fields keep the app defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParamOverrides {
+    pub dt: Option<f32>,
+    pub viscosity: Option<f32>,
+    pub diffusion: Option<f32>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub dye_decay: Option<Decay>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub velocity_decay: Option<Decay>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub temperature_decay: Option<Decay>,
+    pub smoke_weight: Option<f32>,
+    pub dye_density: Option<[f32; 3]>,
+    pub heat_lift: Option<f32>,
+    pub heat_strength: Option<f32>,
+    pub add_strength: Option<f32>,
+    pub radius: Option<f32>,
+    pub vorticity: Option<f32>,
+    pub gravity: Option<[f32; 2]>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub gravity_mode: Option<GravityMode>,
+    pub wind: Option<[f32; 2]>,
+    pub wind_gust: Option<f32>,
+    #[serde(deserialize_with = "gust_period")]
+    pub wind_gust_period: Option<f32>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub boundary: Option<Boundary>,
+    pub well: Option<f32>,
+    pub wake: Option<f32>,
+    pub subgrid: Option<f32>,
+    pub dye_mass: Option<f32>,
+    pub heat_expansion: Option<f32>,
+}
+
+/// What the gravity body force acts on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GravityMode {
+    /// Scaled by the local dye, so ink falls through clear fluid.
+    Dye,
+    /// The same everywhere. Pressure balances it unless something (an
+    /// obstacle, an emitter) lets fluid move.
+    Uniform,
+}
+
+impl GravityMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityMode::Dye => "dye",
+            GravityMode::Uniform => "uniform",
+        }
+    }
+}
+
+impl std::str::FromStr for GravityMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("gravity mode", s, &[GravityMode::Dye, GravityMode::Uniform].map(|g| (g.name(), g)))
+    }
+}
+
+/// What happens at the edges of the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    /// Closed box: flow and dye stop at the edge.
+    Walls,
+    /// Opposite edges join, making the domain a torus. Whatever leaves one
+    /// side comes back in on the other, so the fields tile seamlessly.
+    Wrap,
+    /// Open edges: flow and dye leaving the grid are gone, and anything
+    /// drawn in from outside is clear fluid at ambient pressure.
+    Outflow,
+}
+
+impl Boundary {
+    const ALL: [Boundary; 3] = [Boundary::Walls, Boundary::Wrap, Boundary::Outflow];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.code() as usize + 1) % Self::ALL.len()]
+    }
+
+    /// `SimParams::boundary`, and the matching codes in the shaders.
+    pub fn code(self) -> u32 {
+        match self {
+            Boundary::Walls => 0,
+            Boundary::Wrap => 1,
+            Boundary::Outflow => 2,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Boundary::Walls => "walls",
+            Boundary::Wrap => "wrap",
+            Boundary::Outflow => "outflow",
+        }
+    }
+}
+
+impl std::str::FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("boundary", s, &Self::ALL.map(|b| (b.name(), b)))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Blob {
+    pub pos: [f32; 2],
+    pub radius: f32,
+    #[serde(default = "one")]
+    pub density: f32,
+    /// Red, green and blue dye per unit of `density`.
+    #[serde(default = "white", deserialize_with = "color")]
+    pub color: [f32; 3],
+    #[serde(default)]
+    pub velocity: [f32; 2],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Emitter {
+    pub pos: [f32; 2],
+    pub radius: f32,
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    #[serde(default = "one")]
+    pub density: f32,
+    #[serde(default = "white", deserialize_with = "color")]
+    pub color: [f32; 3],
+    /// Heat added per second; rises with `heat_lift`.
+    #[serde(default)]
+    pub temperature: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "ObstacleTable")]
+pub struct Obstacle {
+    pub shape: Shape,
+    pub motion: Motion,
+}
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Circle { center: [f32; 2], radius: f32 },
+    Box { min: [f32; 2], max: [f32; 2] },
+}
+
+impl Shape {
+    pub fn contains(&self, p: [f32; 2]) -> bool {
+        match *self {
+            Shape::Circle { center, radius } => {
+                let (dx, dy) = (p[0] - center[0], p[1] - center[1]);
+                dx * dx + dy * dy <= radius * radius
+            }
+            Shape::Box { min, max } => p[0] >= min[0] && p[0] <= max[0] && p[1] >= min[1] && p[1] <= max[1],
+        }
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        match *self {
+            Shape::Circle { center, .. } => center,
+            Shape::Box { min, max } => [(min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5],
+        }
+    }
+}
+
+/// Prescribed movement of an obstacle. The fluid sees the solid's own
+/// velocity at its cells, so moving obstacles push the flow along.
+#[derive(Clone, Debug)]
+pub enum Motion {
+    Static,
+    /// Spin about `pivot` at `angular_velocity` rad/s, clockwise on screen
+    /// (y points down).
+    Rotate { pivot: [f32; 2], angular_velocity: f32 },
+    /// Sinusoidal translation, offset `amplitude * sin(2π t / period)`.
+    Oscillate { amplitude: [f32; 2], period: f32 },
+}
+
+impl Obstacle {
+    /// Whether the solid at time `t` covers domain point `p`, and if so the
+    /// solid's velocity there in domain units per second.
+    pub fn sample(&self, p: [f32; 2], t: f32) -> Option<[f32; 2]> {
+        match self.motion {
+            Motion::Static => self.shape.contains(p).then_some([0.0, 0.0]),
+            Motion::Rotate { pivot, angular_velocity: w } => {
+                // Rotate p back into the rest pose
+                let (sin, cos) = (-w * t).sin_cos();
+                let (dx, dy) = (p[0] - pivot[0], p[1] - pivot[1]);
+                let rest = [pivot[0] + dx * cos - dy * sin, pivot[1] + dx * sin + dy * cos];
+                self.shape.contains(rest).then_some([-w * dy, w * dx])
+            }
+            Motion::Oscillate { amplitude, period } => {
+                let phase = std::f32::consts::TAU * t / period;
+                let (sin, cos) = phase.sin_cos();
+                let rest = [p[0] - amplitude[0] * sin, p[1] - amplitude[1] * sin];
+                let speed = std::f32::consts::TAU / period * cos;
+                self.shape.contains(rest).then_some([amplitude[0] * speed, amplitude[1] * speed])
+            }
+        }
+    }
+}
+
+impl Scene {
+    /// Look up a builtin scene by name, or (native only) load a `.toml` file.
+    pub fn load(name_or_path: &str) -> Result<Scene, String> {
+        if let Some((_, src)) = BUILTIN.iter().find(|(n, _)| *n == name_or_path) {
+            return Scene::parse(src).map_err(|e| format!("{}: {}", name_or_path, e));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let src = std::fs::read_to_string(name_or_path)
+                .map_err(|e| format!("{}: {}", name_or_path, e))?;
+            Scene::parse(&src).map_err(|e| format!("{}: {}", name_or_path, e))
+        }
+        #[cfg(target_arch = "wasm32")]
+        Err(format!("unknown scene '{}'", name_or_path))
+    }
+
+    pub fn parse(src: &str) -> Result<Scene, String> {
+        from_toml(src)
+    }
+
+    /// Rasterize the benchmark flow, noise (drawn from the session `seed`)
+    /// and blobs, whichever the scene has, into per-cell (density, velocity)
+    /// values.
+    pub fn initial_fields(&self, grid: u32, seed: Seed) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+        let n = (grid * grid) as usize;
+        let (mut density, mut velocity) = match &self.benchmark {
+            Some(spec) => crate::benchmark::fields(spec, grid),
+            None => (vec![[0.0f32; 3]; n], vec![[0.0f32; 2]; n]),
+        };
+        if let Some(spec) = &self.noise {
+            for (v, w) in velocity.iter_mut().zip(crate::noise::divergence_free(spec, grid, seed)) {
+                v[0] += w[0];
+                v[1] += w[1];
+            }
+        }
+        let g = grid as f32;
+        for y in 0..grid {
+            for x in 0..grid {
+                let i = (y * grid + x) as usize;
+                for b in &self.blobs {
+                    let (dx, dy) = (x as f32 - b.pos[0] * g, y as f32 - b.pos[1] * g);
+                    let r = b.radius * g;
+                    let w = (1.0 - (dx * dx + dy * dy) / (r * r)).max(0.0);
+                    for (d, c) in density[i].iter_mut().zip(b.color) {
+                        *d += w * b.density * c;
+                    }
+                    if w > 0.0 {
+                        velocity[i][0] += b.velocity[0];
+                        velocity[i][1] += b.velocity[1];
+                    }
+                }
+            }
+        }
+        (density, velocity)
+    }
+
+    /// Rasterize the obstacles at time `t` (seconds since the scene was
+    /// loaded): 1.0 in solid cells and 0.0 in fluid, plus the solid's
+    /// velocity in grid cells per second (zero in fluid). Where obstacles
+    /// overlap, the first listed wins.
+    pub fn obstacle_fields(&self, grid: u32, t: f32) -> (Vec<f32>, Vec<[f32; 2]>) {
+        let g = grid as f32;
+        (0..grid * grid)
+            .map(|i| {
+                let p = [((i % grid) as f32 + 0.5) / g, ((i / grid) as f32 + 0.5) / g];
+                match self.obstacles.iter().find_map(|o| o.sample(p, t)) {
+                    Some(v) => (1.0, [v[0] * g, v[1] * g]),
+                    None => (0.0, [0.0, 0.0]),
+                }
+            })
+            .unzip()
+    }
+
+    /// Whether any obstacle moves, so the mask needs rebuilding each step.
+    pub fn has_moving_obstacles(&self) -> bool {
+        self.obstacles.iter().any(|o| !matches!(o.motion, Motion::Static))
+    }
+}
+
+/// Signed distance in cells to the nearest solid/fluid boundary of an
+/// obstacle mask: positive in fluid, negative inside solids. Works on any
+/// mask, not just the analytic scene shapes. Distances are capped at about
+/// `2 * grid`, which is also what every cell gets when there is no boundary.
+pub fn signed_distance(mask: &[f32], grid: u32) -> Vec<f32> {
+    let outside = distance_transform(mask, grid, |m| m > 0.5);
+    let inside = distance_transform(mask, grid, |m| m <= 0.5);
+    let far = 2.0 * grid as f64;
+    mask.iter().zip(outside.iter().zip(&inside))
+        .map(|(&m, (&o, &i))| {
+            // Distances are between cell centers; the surface sits half a cell out.
+            if m > 0.5 { -(i.min(far) - 0.5) as f32 } else { (o.min(far) - 0.5) as f32 }
+        })
+        .collect()
+}
+
+/// Exact Euclidean distance from every cell to the nearest cell where
+/// `target` holds (Felzenszwalb & Huttenlocher), separably over rows then
+/// columns.
+fn distance_transform(mask: &[f32], grid: u32, target: impl Fn(f32) -> bool) -> Vec<f64> {
+    const INF: f64 = 1e20;
+    let n = grid as usize;
+    let mut d: Vec<f64> = mask.iter().map(|&m| if target(m) { 0.0 } else { INF }).collect();
+    let mut line = vec![0.0; n];
+    let mut out = vec![0.0; n];
+    for y in 0..n {
+        line.copy_from_slice(&d[y * n..(y + 1) * n]);
+        edt_1d(&line, &mut out);
+        d[y * n..(y + 1) * n].copy_from_slice(&out);
+    }
+    for x in 0..n {
+        for y in 0..n {
+            line[y] = d[y * n + x];
+        }
+        edt_1d(&line, &mut out);
+        for y in 0..n {
+            d[y * n + x] = out[y];
+        }
+    }
+    d.into_iter().map(f64::sqrt).collect()
+}
+
+/// Squared 1D distance transform: lower envelope of the parabolas rooted at
+/// each sample.
+fn edt_1d(f: &[f64], out: &mut [f64]) {
+    let n = f.len();
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f64; n + 1];
+    let mut k = 0;
+    z[0] = f64::NEG_INFINITY;
+    z[1] = f64::INFINITY;
+    for q in 1..n {
+        let intersect = |p: usize| ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2 * q - 2 * p) as f64;
+        let mut s = intersect(v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersect(v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f64::INFINITY;
+    }
+    k = 0;
+    for (q, o) in out.iter_mut().enumerate() {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+        let dq = q as f64 - v[k] as f64;
+        *o = dq * dq + f[v[k]];
+    }
+}
+
+// ---- TOML ----
+
+/// Parse a TOML file into `T`, with errors as "line N: message".
+pub(crate) fn from_toml<T: DeserializeOwned>(src: &str) -> Result<T, String> {
+    toml::from_str(src).map_err(|e: toml::de::Error| {
+        // Syntax errors can come with no message, or one over several lines
+        let message = match e.message().trim() {
+            "" => "can't parse this line".to_string(),
+            message => message.replace('\n', ", "),
+        };
+        match e.span() {
+            Some(span) => format!("line {}: {}", src[..span.start].matches('\n').count() + 1, message),
+            None => message,
+        }
+    })
+}
+
+/// A keyword option, parsed with `FromStr`; for `deserialize_with`.
+pub(crate) fn keyword<'de, D: Deserializer<'de>, T: std::str::FromStr<Err = String>>(d: D) -> Result<T, D::Error> {
+    String::deserialize(d)?.parse().map_err(D::Error::custom)
+}
+
+/// `keyword` for a field that can be left out.
+pub(crate) fn optional_keyword<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr<Err = String>,
+{
+    Option::<String>::deserialize(d)?.map(|s| s.parse().map_err(D::Error::custom)).transpose()
+}
+
+fn gust_period<'de, D: Deserializer<'de>>(d: D) -> Result<Option<f32>, D::Error> {
+    match Option::<f32>::deserialize(d)? {
+        Some(v) if v <= 0.0 => Err(D::Error::custom("`wind_gust_period` must be positive")),
+        v => Ok(v),
+    }
+}
+
+fn color<'de, D: Deserializer<'de>>(d: D) -> Result<[f32; 3], D::Error> {
+    let rgb = <[f32; 3]>::deserialize(d)?;
+    match rgb.iter().all(|c| *c >= 0.0) {
+        true => Ok(rgb),
+        false => Err(D::Error::custom("a color is three non-negative numbers (red, green, blue)")),
+    }
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn white() -> [f32; 3] {
+    [1.0; 3]
+}
+
+/// An `[[obstacle]]` as written. Which keys it needs depends on its shape
+/// and motion, so they're checked when it becomes an `Obstacle`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObstacleTable {
+    shape: String,
+    center: Option<[f32; 2]>,
+    radius: Option<f32>,
+    min: Option<[f32; 2]>,
+    max: Option<[f32; 2]>,
+    motion: Option<String>,
+    pivot: Option<[f32; 2]>,
+    angular_velocity: Option<f32>,
+    amplitude: Option<[f32; 2]>,
+    period: Option<f32>,
+}
+
+impl TryFrom<ObstacleTable> for Obstacle {
+    type Error = String;
+
+    fn try_from(t: ObstacleTable) -> Result<Self, String> {
+        let missing = |key: &str| format!("[[obstacle]] is missing `{}`", key);
+        let (shape, mut wanted) = match t.shape.as_str() {
+            "circle" => {
+                let center = t.center.ok_or_else(|| missing("center"))?;
+                let radius = t.radius.ok_or_else(|| missing("radius"))?;
+                (Shape::Circle { center, radius }, vec!["center", "radius"])
+            }
+            "box" => {
+                let (min, max) = (t.min.ok_or_else(|| missing("min"))?, t.max.ok_or_else(|| missing("max"))?);
+                (Shape::Box { min, max }, vec!["min", "max"])
+            }
+            other => return Err(format!("unknown obstacle shape '{}'", other)),
+        };
+        let motion = match t.motion.as_deref() {
+            None | Some("static") => Motion::Static,
+            Some("rotate") => {
+                wanted.extend(["pivot", "angular_velocity"]);
+                Motion::Rotate {
+                    pivot: t.pivot.unwrap_or(shape.center()),
+                    angular_velocity: t.angular_velocity.unwrap_or(1.0),
+                }
+            }
+            Some("oscillate") => {
+                wanted.extend(["amplitude", "period"]);
+                let period = t.period.unwrap_or(2.0);
+                if period <= 0.0 {
+                    return Err("`period` must be positive".to_string());
+                }
+                Motion::Oscillate { amplitude: t.amplitude.ok_or_else(|| missing("amplitude"))?, period }
+            }
+            Some(other) => return Err(format!("unknown obstacle motion '{}'", other)),
+        };
+        let given = [
+            ("center", t.center.is_some()),
+            ("radius", t.radius.is_some()),
+            ("min", t.min.is_some()),
+            ("max", t.max.is_some()),
+            ("pivot", t.pivot.is_some()),
+            ("angular_velocity", t.angular_velocity.is_some()),
+            ("amplitude", t.amplitude.is_some()),
+            ("period", t.period.is_some()),
+        ];
+        match given.iter().find(|(key, set)| *set && !wanted.contains(key)) {
+            Some((key, _)) => Err(format!("`{}` doesn't go with a {} obstacle's shape and motion", key, t.shape)),
+            None => Ok(Obstacle { shape, motion }),
+        }
+    }
+}
+
+/// The `[noise]` table as written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoiseTable {
+    spectrum: Option<String>,
+    slope: Option<f32>,
+    k_peak: Option<f32>,
+    k_min: Option<f32>,
+    k_max: Option<f32>,
+    rms: Option<f32>,
+    seed: Option<u64>,
+}
+
+fn noise<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NoiseSpec>, D::Error> {
+    let Some(t) = Option::<NoiseTable>::deserialize(d)? else {
+        return Ok(None);
+    };
+    let spectrum = match (t.spectrum.as_deref(), t.slope, t.k_peak) {
+        (None | Some("power"), slope, None) => Spectrum::PowerLaw { slope: slope.unwrap_or(-5.0 / 3.0) },
+        (Some("peaked"), None, Some(k_peak)) => Spectrum::Peaked { k_peak },
+        (None | Some("power"), _, Some(_)) => return Err(D::Error::custom("`k_peak` needs spectrum = \"peaked\"")),
+        (Some("peaked"), Some(_), _) => return Err(D::Error::custom("`slope` needs spectrum = \"power\"")),
+        (Some("peaked"), None, None) => return Err(D::Error::custom("[noise] is missing `k_peak`")),
+        (Some(other), _, _) => return Err(D::Error::custom(format!("unknown noise spectrum '{}'", other))),
+    };
+    let d = NoiseSpec::default();
+    Ok(Some(NoiseSpec {
+        seed: t.seed.unwrap_or(d.seed),
+        k_min: t.k_min.unwrap_or(d.k_min),
+        k_max: t.k_max.unwrap_or(d.k_max),
+        spectrum,
+        rms: t.rms.unwrap_or(d.rms),
+    }))
+}
+
+/// The `[benchmark]` table as written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BenchmarkTable {
+    #[serde(deserialize_with = "keyword")]
+    flow: Flow,
+    #[serde(default = "benchmark_speed")]
+    speed: f32,
+    #[serde(default = "benchmark_modes")]
+    modes: u32,
+}
+
+fn benchmark_speed() -> f32 {
+    40.0
+}
+
+fn benchmark_modes() -> u32 {
+    2
+}
+
+fn benchmark<'de, D: Deserializer<'de>>(d: D) -> Result<Option<BenchmarkSpec>, D::Error> {
+    match Option::<BenchmarkTable>::deserialize(d)? {
+        Some(t) if t.modes == 0 => Err(D::Error::custom("`modes` must be at least 1")),
+        t => Ok(t.map(|t| BenchmarkSpec { flow: t.flow, speed: t.speed, modes: t.modes })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_scenes_parse() {
+        for (name, src) in BUILTIN {
+            Scene::parse(src).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        }
+    }
+
+    #[test]
+    fn reads_arrays_of_tables_in_order() {
+        let scene = Scene::parse(
+            "[[blob]]\npos = [0.2, 0.3]\nradius = 0.1\n\n[[emitter]]\npos = [0.0, 0.5]\nradius = 0.05\n\n\
+             [[blob]]\npos = [0.7, 0.8]\nradius = 0.2\ncolor = [0.0, 1.0, 0.0]\n",
+        )
+        .unwrap();
+        assert_eq!(scene.blobs.iter().map(|b| b.pos).collect::<Vec<_>>(), [[0.2, 0.3], [0.7, 0.8]]);
+        assert_eq!(scene.blobs[0].color, [1.0; 3], "white if omitted");
+        assert_eq!(scene.blobs[1].color, [0.0, 1.0, 0.0]);
+        assert_eq!(scene.emitters.len(), 1);
+        assert_eq!(scene.emitters[0].density, 1.0);
+    }
+
+    #[test]
+    fn skips_comments_but_not_hashes_in_strings() {
+        let scene = Scene::parse(
+            "# a scene\nname = \"Jet #2\"   # the second\n\n[params]  # overrides\n\
+             viscosity = 0.5 # cells²/s\n# dt = 1.0\n",
+        )
+        .unwrap();
+        assert_eq!(scene.name, "Jet #2");
+        assert_eq!(scene.params.viscosity, Some(0.5));
+        assert_eq!(scene.params.dt, None);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_sections_with_their_line() {
+        let err = |src: &str| Scene::parse(src).unwrap_err();
+        assert!(err("name = \"x\"\n\n[params]\nviscosty = 0.5\n").starts_with("line 4:"));
+        assert!(err("[[blob]]\npos = [0.5, 0.5]\nradius = 0.1\nsize = 2\n").starts_with("line 4:"));
+        assert!(err("[[emitter]]\npos = [0.5, 0.5]\nradius = 0.1\nspeed = 2\n").contains("speed"));
+        assert!(err("[lights]\n").contains("lights"));
+        assert!(err("[[obstacle]]\nshape = \"box\"\nmin = [0.1, 0.1]\nmax = [0.2, 0.2]\nradius = 0.1\n").contains("radius"));
+    }
+
+    #[test]
+    fn checks_values() {
+        let obstacle = |extra: &str| Scene::parse(&format!("[[obstacle]]\nshape = \"circle\"\ncenter = [0.5, 0.5]\n{}", extra));
+        assert!(obstacle("").is_err(), "a circle needs a radius");
+        assert!(obstacle("radius = 0.1\nmotion = \"oscillate\"\namplitude = [0.0, 0.1]\nperiod = 0.0\n").is_err());
+        assert!(obstacle("radius = 0.1\nmotion = \"spin\"\n").is_err());
+        assert!(matches!(obstacle("radius = 0.1\nmotion = \"rotate\"\n").unwrap().obstacles[0].motion, Motion::Rotate { pivot: [0.5, 0.5], .. }));
+        assert!(Scene::parse("[[blob]]\npos = [0.5, 0.5]\nradius = 0.1\ncolor = [1.0, -1.0, 0.0]\n").is_err());
+        assert!(Scene::parse("[params]\nboundary = \"bouncy\"\n").is_err());
+        assert!(Scene::parse("[params]\nwind_gust_period = 0.0\n").is_err());
+        assert!(Scene::parse("[benchmark]\nspeed = 10.0\n").is_err(), "needs a flow");
+        assert!(Scene::parse("[noise]\nspectrum = \"peaked\"\n").is_err(), "needs k_peak");
+    }
+}
@@ -0,0 +1,92 @@
+//! Screenshots (F12, or `--screenshot-key`): the next frame, as the window
+//! shows it with its overlays, is copied from the swapchain texture into a
+//! buffer and mapped without waiting. Once the GPU is done with it, a
+//! background thread writes it to `screenshot-<time>.png`, so taking one
+//! doesn't stall the simulation. Needs a swapchain that can be copied from,
+//! which `main` asks for when the surface allows it.
+
+use std::time::Duration;
+
+use crate::frame_capture::CaptureRing;
+
+pub struct Screenshots {
+    /// The swapchain allows copies, so screenshots can be taken at all.
+    supported: bool,
+    /// The key was pressed; the next frame is taken.
+    requested: bool,
+    /// The frame being taken, until it's handed to the writer.
+    capture: Option<CaptureRing>,
+}
+
+impl Screenshots {
+    pub fn new(supported: bool) -> Self {
+        Self { supported, requested: false, capture: None }
+    }
+
+    /// The key was pressed: take the next frame.
+    pub fn request(&mut self) {
+        if !self.supported {
+            eprintln!("Screenshots need a swapchain that can be copied from, and this one can't");
+        } else if self.capture.is_some() {
+            eprintln!("Still saving the last screenshot");
+        } else {
+            self.requested = true;
+        }
+    }
+
+    /// Copy the finished frame in `texture` if one was asked for. Call
+    /// `submitted` once the encoder is submitted.
+    pub fn record(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        if !std::mem::take(&mut self.requested) {
+            return;
+        }
+        let Some(mut capture) = CaptureRing::new("screenshot_readback", 1, texture.format(), texture.width(), texture.height()) else {
+            eprintln!("Can't save a screenshot of a {:?} swapchain", texture.format());
+            return;
+        };
+        capture.record(device, encoder, texture, Duration::ZERO);
+        self.capture = Some(capture);
+    }
+
+    pub fn submitted(&mut self) {
+        if let Some(capture) = &mut self.capture {
+            capture.submitted();
+        }
+    }
+
+    /// Hand the pending capture to a writer thread if the GPU is done with
+    /// it, without waiting.
+    pub fn collect(&mut self, device: &wgpu::Device) {
+        let Some(capture) = &mut self.capture else { return };
+        let mut rgb = None;
+        capture.collect(device, false, |frame| rgb = Some(frame.rgb_rows()));
+        let (width, height) = (capture.width(), capture.height());
+        match rgb {
+            Some(rgb) => {
+                std::thread::spawn(move || {
+                    let path = free_path();
+                    match std::fs::write(&path, crate::png::encode(width, height, &rgb)) {
+                        Ok(()) => eprintln!("Saved {} ({}×{})", path, width, height),
+                        Err(e) => eprintln!("Failed to save {}: {}", path, e),
+                    }
+                });
+            }
+            None if !capture.is_idle() => return,
+            None => eprintln!("Failed to read the screenshot back from the GPU"),
+        }
+        self.capture = None;
+    }
+}
+
+/// `screenshot-<time>.png`, numbered when a file of that second is there.
+fn free_path() -> String {
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let mut path = format!("screenshot-{}.png", secs);
+    for n in 2.. {
+        if !std::path::Path::new(&path).exists() {
+            break;
+        }
+        path = format!("screenshot-{}-{}.png", secs, n);
+    }
+    path
+}
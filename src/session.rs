@@ -0,0 +1,208 @@
+//! The 2D window's session, saved to `wgpu-fluid-session.toml` on exit and
+//! restored on the next launch (`--fresh` skips it): the window size, the
+//! scene, the parameters changed while it ran, the tools picked (dye color,
+//! mouse force, rod, substeps) and the view toggles. Anything given on the
+//! command line wins over the saved value, and picking a scene there
+//! (`--scene`, `--demo`, `--snapshot`) drops the parameters saved with the
+//! old one.
+//!
+//! Settings with a flag go into the `Config` before the window opens. The
+//! rest go over the scene once it's loaded, since the scene would otherwise
+//! reset them.
+
+use serde::{Deserialize, Deserializer};
+
+use crate::config::Config;
+use crate::dye::DyeColor;
+use crate::mouse_force::MouseForce;
+use crate::particles::{ColorMode, ReseedMode};
+use crate::rod::Rod;
+use crate::scene::{self, optional_keyword, Boundary, GravityMode};
+use crate::SimParams;
+
+/// Written to, and read from, the working directory, like snapshots.
+pub const PATH: &str = "wgpu-fluid-session.toml";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Session {
+    // Settings with a flag, applied to the `Config`
+    pub scene: Option<String>,
+    /// Logical pixels, as `--window`.
+    #[serde(rename = "window", deserialize_with = "window")]
+    pub window_size: Option<(u32, u32)>,
+    #[serde(deserialize_with = "substeps")]
+    pub substeps: Option<u32>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub dye_color: Option<DyeColor>,
+    pub dye_cycle: Option<f32>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub mouse_force: Option<MouseForce>,
+    /// The rod, if it was on.
+    #[serde(deserialize_with = "rod")]
+    pub rod: Option<Rod>,
+    pub detail: Option<f32>,
+    /// The outline's scale, if it was on.
+    pub wall_shear: Option<f32>,
+    // Scene parameters, applied over the scene
+    pub gravity: Option<[f32; 2]>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub gravity_mode: Option<GravityMode>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub boundary: Option<Boundary>,
+    pub viscosity: Option<f32>,
+    pub vorticity: Option<f32>,
+    // View toggles with no flag
+    #[serde(deserialize_with = "switch")]
+    pub trails: Option<bool>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub particle_colors: Option<ColorMode>,
+    #[serde(deserialize_with = "optional_keyword")]
+    pub reseed: Option<ReseedMode>,
+    #[serde(deserialize_with = "switch")]
+    pub heat_map: Option<bool>,
+}
+
+fn window<'de, D: Deserializer<'de>>(d: D) -> Result<Option<(u32, u32)>, D::Error> {
+    let size = Option::<[f32; 2]>::deserialize(d)?;
+    Ok(size.map(|[w, h]| (w.max(1.0) as u32, h.max(1.0) as u32)))
+}
+
+fn substeps<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u32>, D::Error> {
+    Ok(Option::<f32>::deserialize(d)?.map(|n| (n as u32).clamp(1, crate::MAX_SUBSTEPS)))
+}
+
+/// `[mass, drag]`.
+fn rod<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Rod>, D::Error> {
+    Ok(Option::<[f32; 2]>::deserialize(d)?.map(|[mass, drag]| Rod::new(mass, drag)))
+}
+
+/// 1 for on, 0 for off.
+fn switch<'de, D: Deserializer<'de>>(d: D) -> Result<Option<bool>, D::Error> {
+    Ok(Option::<f32>::deserialize(d)?.map(|v| v != 0.0))
+}
+
+impl Session {
+    /// The saved session, if there is one. A file that doesn't parse is
+    /// reported and ignored.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Option<Session> {
+        let src = std::fs::read_to_string(PATH).ok()?;
+        match Session::parse(&src) {
+            Ok(session) => {
+                eprintln!("Restoring the session from {} (--fresh skips it)", PATH);
+                Some(session)
+            }
+            Err(e) => {
+                eprintln!("Ignoring {}: {}", PATH, e);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) {
+        match std::fs::write(PATH, self.encode()) {
+            Ok(()) => eprintln!("Saved the session to {}", PATH),
+            Err(e) => eprintln!("Failed to save {}: {}", PATH, e),
+        }
+    }
+
+    fn parse(src: &str) -> Result<Session, String> {
+        scene::from_toml(src)
+    }
+
+    fn encode(&self) -> String {
+        let mut out = String::from("# wgpu-fluid session, saved on exit. Delete it or pass --fresh to start clean.\n");
+        let mut line = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                out.push_str(&format!("{} = {}\n", key, value));
+            }
+        };
+        line("scene", self.scene.as_ref().map(|s| format!("{:?}", s)));
+        line("window", self.window_size.map(|(w, h)| format!("[{}, {}]", w, h)));
+        line("substeps", self.substeps.map(|n| n.to_string()));
+        line("dye_color", self.dye_color.map(|c| format!("\"{:?}\"", c).to_lowercase()));
+        line("dye_cycle", self.dye_cycle.map(|c| c.to_string()));
+        line("mouse_force", self.mouse_force.map(|m| format!("\"{}\"", m)));
+        line("rod", self.rod.map(|r| format!("[{}, {}]", r.mass, r.drag)));
+        line("detail", self.detail.map(|d| d.to_string()));
+        line("wall_shear", self.wall_shear.map(|s| s.to_string()));
+        line("gravity", self.gravity.map(|g| format!("[{}, {}]", g[0], g[1])));
+        line("gravity_mode", self.gravity_mode.map(|m| format!("\"{}\"", m.name())));
+        line("boundary", self.boundary.map(|b| format!("\"{}\"", b.name())));
+        line("viscosity", self.viscosity.map(|v| v.to_string()));
+        line("vorticity", self.vorticity.map(|v| v.to_string()));
+        line("trails", self.trails.map(|on| (on as u32).to_string()));
+        line("particle_colors", self.particle_colors.map(|m| format!("\"{}\"", m.name())));
+        line("reseed", self.reseed.map(|m| format!("\"{}\"", m.name())));
+        line("heat_map", self.heat_map.map(|on| (on as u32).to_string()));
+        out
+    }
+
+    /// Drop what `args` sets itself, so the command line wins.
+    pub fn forget_given(&mut self, args: &[String]) {
+        for arg in args {
+            match arg.split('=').next().unwrap_or_default() {
+                "--scene" | "--demo" | "--snapshot" | "--checkpoint" => {
+                    // The parameters were the old scene's
+                    self.scene = None;
+                    self.gravity = None;
+                    self.gravity_mode = None;
+                    self.boundary = None;
+                    self.viscosity = None;
+                    self.vorticity = None;
+                }
+                "--window" => self.window_size = None,
+                "--substeps" => self.substeps = None,
+                "--dye-color" => self.dye_color = None,
+                "--dye-cycle" => self.dye_cycle = None,
+                "--mouse-force" => self.mouse_force = None,
+                "--rod" => self.rod = None,
+                "--detail" => self.detail = None,
+                "--wall-shear" => self.wall_shear = None,
+                "--gravity" => self.gravity = None,
+                "--gravity-mode" => self.gravity_mode = None,
+                "--boundary" => self.boundary = None,
+                "--viscosity" => self.viscosity = None,
+                "--vorticity" => self.vorticity = None,
+                _ => {}
+            }
+        }
+    }
+
+    /// The saved settings that have a flag, into `config`.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(scene) = &self.scene {
+            config.scene = scene.clone();
+        }
+        config.window_size = self.window_size.unwrap_or(config.window_size);
+        config.substeps = self.substeps.unwrap_or(config.substeps);
+        config.dye_color = self.dye_color.unwrap_or(config.dye_color);
+        config.dye_cycle = self.dye_cycle.unwrap_or(config.dye_cycle);
+        config.mouse_force = self.mouse_force.unwrap_or(config.mouse_force);
+        config.rod = self.rod.or(config.rod);
+        config.detail = self.detail.unwrap_or(config.detail);
+        config.wall_shear = self.wall_shear.or(config.wall_shear);
+    }
+
+    /// The saved scene parameters over `params`, once the scene has set
+    /// its own.
+    pub fn restore_params(&self, params: &mut SimParams) {
+        if let Some(gravity) = self.gravity {
+            params.gravity = gravity;
+        }
+        if let Some(mode) = self.gravity_mode {
+            params.gravity_by_dye = (mode == GravityMode::Dye) as u32;
+        }
+        if let Some(boundary) = self.boundary {
+            params.boundary = boundary.code();
+        }
+        if let Some(viscosity) = self.viscosity {
+            params.viscosity = viscosity;
+        }
+        if let Some(vorticity) = self.vorticity {
+            params.vorticity = vorticity;
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Dye shading (Shift + V, `--shading`): extra shading `fs_draw` puts on
+//! the dye, on top of its color. Vorticity comes from the curl the step
+//! already computes for confinement, so it costs one more texture read a
+//! pixel; lighting reads the dye around the pixel for its slope.
+
+/// Degrees Shift + L turns the light by.
+pub const LIGHT_STEP: f32 = 45.0;
+
+/// Values match `view.shading` in `render.wgsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shading {
+    /// The dye as it is.
+    Plain = 0,
+    /// Tinted by the local vorticity's sign: red where the fluid turns
+    /// clockwise on screen, blue where it turns counterclockwise.
+    Spin = 1,
+    /// Darkened by the local vorticity's magnitude, so shear layers and
+    /// vortex cores show as dark seams through the smoke.
+    Shear = 2,
+    /// Lit as a relief: the dye amount is a height field, whose slope
+    /// catches diffuse light and highlights from a light that Shift + L
+    /// moves around.
+    Lit = 3,
+}
+
+impl Shading {
+    const ALL: [Shading; 4] = [Shading::Plain, Shading::Spin, Shading::Shear, Shading::Lit];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shading::Plain => "plain",
+            Shading::Spin => "spin",
+            Shading::Shear => "shear",
+            Shading::Lit => "lit",
+        }
+    }
+}
+
+impl std::str::FromStr for Shading {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("shading", s, &Self::ALL.map(|m| (m.name(), m)))
+    }
+}
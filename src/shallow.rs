@@ -0,0 +1,289 @@
+//! Shallow water mode (`--shallow`): an interactive pond. The state is a
+//! height field, the water's depth over a flat bed and its depth-integrated
+//! momentum, instead of the 2D solver's incompressible velocity. Waves
+//! travel at √(g·depth), so a poke sends rings across the surface that
+//! bounce off the walls and interfere. The kernels in `shallow.wgsl` step
+//! it with Rusanov fluxes, and the renderer lights the surface and shows
+//! the pool floor refracted through it.
+
+use std::sync::Arc;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    event::MouseButton,
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::create_storage_tex;
+use crate::mode::{self, Mode};
+
+/// Depth of the still pond, in cells.
+const REST_DEPTH: f32 = 8.0;
+/// In cells/s², for waves of about 45 cells/s at rest depth.
+const GRAVITY: f32 = 250.0;
+/// Steps per frame. Even, so the state always ends up back in the first
+/// texture, the one the renderer reads.
+const SUBSTEPS: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaterParams {
+    size: u32,
+    dt: f32,
+    gravity: f32,
+    rest_depth: f32,
+    /// Finger center in cells.
+    mouse_pos: [f32; 2],
+    radius: f32,
+    mouse_down: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ViewParams {
+    size: f32,
+    rest_depth: f32,
+    _pad: [f32; 2],
+}
+
+/// A still pond with one drop about to spread from its center, as
+/// elevation and momentum texels.
+fn drop_in_pond(size: u32) -> Vec<[f32; 4]> {
+    let n = size as f32;
+    let radius = n * 0.03;
+    (0..size * size).map(|i| {
+        let d = [(i % size) as f32 + 0.5 - n * 0.5, (i / size) as f32 + 0.5 - n * 0.5];
+        let bump = 2.0 * (-(d[0] * d[0] + d[1] * d[1]) / (radius * radius)).exp();
+        [bump, 0.0, 0.0, 0.0]
+    }).collect()
+}
+
+/// The ping-pong water textures, the step kernel and the water renderer.
+struct Water {
+    params: WaterParams,
+    param_buffer: wgpu::Buffer,
+    textures: [wgpu::Texture; 2],
+    _views: [wgpu::TextureView; 2],
+    /// [0] steps the first texture into the second, [1] back again.
+    step_bgs: [wgpu::BindGroup; 2],
+    step: wgpu::ComputePipeline,
+    render_bg: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl Water {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat, size: u32, dt: f32) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shallow_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shallow.wgsl").into()),
+        });
+
+        let params = WaterParams {
+            size, dt: dt / SUBSTEPS as f32, gravity: GRAVITY, rest_depth: REST_DEPTH,
+            mouse_pos: [0.0; 2], radius: size as f32 * 0.02, mouse_down: 0,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("water_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let view_params = ViewParams { size: size as f32, rest_depth: REST_DEPTH, _pad: [0.0; 2] };
+        let view_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("water_view"),
+            contents: bytemuck::bytes_of(&view_params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let (tex_a, view_a) = create_storage_tex(device, size);
+        let (tex_b, view_b) = create_storage_tex(device, size);
+        crate::upload_field(queue, &tex_a, size, &drop_in_pond(size));
+
+        // ---- Compute ----
+        let storage_tex = wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format: wgpu::TextureFormat::Rgba16Float,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let step_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("water_step_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_tex, count: None,
+                },
+            ],
+        });
+        let step_bg = |src: &wgpu::TextureView, dst: &wgpu::TextureView| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("water_step_bg"), layout: &step_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(src) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(dst) },
+            ],
+        });
+        let step_bgs = [step_bg(&view_a, &view_b), step_bg(&view_b, &view_a)];
+        let step_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&step_bgl], push_constant_ranges: &[],
+        });
+        let step = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("step_water"), layout: Some(&step_pl), module: &module, entry_point: "step_water",
+        });
+
+        // ---- Render ----
+        let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("water_render_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("water_render_bg"), layout: &render_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 3, resource: view_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&view_a) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+        let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+        });
+        let render_pipeline = crate::create_render_pipeline(device, &render_pl, &module, format, "fs_water");
+
+        Self {
+            params, param_buffer, textures: [tex_a, tex_b], _views: [view_a, view_b],
+            step_bgs, step, render_bg, render_pipeline,
+        }
+    }
+
+    /// `SUBSTEPS` steps, ending back in the first texture.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        let groups = self.params.size.div_ceil(8);
+        c.set_pipeline(&self.step);
+        for i in 0..SUBSTEPS as usize {
+            c.set_bind_group(0, &self.step_bgs[i % 2], &[]);
+            c.dispatch_workgroups(groups, groups, 1);
+        }
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("water_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.render_pipeline);
+        r.set_bind_group(0, &self.render_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+
+    /// Calm the pond and drop a new drop in.
+    fn reset(&self, queue: &wgpu::Queue) {
+        crate::upload_field(queue, &self.textures[0], self.params.size, &drop_in_pond(self.params.size));
+    }
+}
+
+/// The shallow water window loop, taking over from `run` once the window
+/// and device exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let size = config.grid_size;
+    let gpu = mode::Gpu::new(&config, window, surface, adapter, device, queue);
+    let (device, queue, format) = (&gpu.device, &gpu.queue, gpu.format());
+    let win_size = gpu.window.inner_size();
+
+    let dt = crate::base_params(&config, crate::cell_size_for(win_size)).dt;
+    let water = Water::new(device, queue, format, size, dt);
+    eprintln!("Shallow water mode: {}² pond, {} cells deep", size, REST_DEPTH);
+
+    mode::run(gpu, event_loop, size, ShallowMode { water }).await;
+}
+
+/// The shallow water window's state between frames.
+struct ShallowMode {
+    water: Water,
+}
+
+impl Mode for ShallowMode {
+    const STEP: &'static str = "water_step";
+
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue) {
+        if code == KeyCode::KeyR {
+            self.water.reset(queue);
+            eprintln!("Pond reset");
+        }
+    }
+
+    fn mouse(&mut self, button: MouseButton, pressed: bool) {
+        if button == MouseButton::Left {
+            self.water.params.mouse_down = pressed as u32;
+        }
+    }
+
+    fn cursor(&mut self, cursor: [f32; 2]) {
+        self.water.params.mouse_pos = cursor;
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue, _aspect: f32, _paused: bool) {
+        queue.write_buffer(&self.water.param_buffer, 0, bytemuck::bytes_of(&self.water.params));
+    }
+
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        self.water.step(c);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.water.render(encoder, view);
+    }
+}
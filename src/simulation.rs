@@ -0,0 +1,560 @@
+//! One copy of the evolving fluid state (velocity, dye, pressure scratch,
+//! ...) together with the bind group and pressure solver that step it. The
+//! app normally runs a single copy; solver comparison (K) clones it into a
+//! second copy that is stepped with a different solver from the same flow.
+
+use crate::pressure::{self, PressureSolver, SolverKind};
+use crate::scene::Boundary;
+use crate::workarounds::Workarounds;
+use crate::{create_storage_tex, SimParams};
+
+/// Jacobi sweeps (a/b pairs) for the implicit diffusion solve.
+const DIFFUSE_ITERATIONS: u32 = 20;
+
+/// How a step moves the velocity field along itself (`--advection`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Advection {
+    /// One semi-Lagrangian backtrace over the whole step, then one
+    /// projection.
+    #[default]
+    SemiLagrangian,
+    /// Advection-reflection: half a step, a projection, the other half
+    /// carrying the reflected field, a second projection. Keeps much more
+    /// of the swirl's energy for the cost of the extra pressure solve.
+    Reflection,
+}
+
+impl Advection {
+    pub fn name(self) -> &'static str {
+        match self {
+            Advection::SemiLagrangian => "semi-lagrangian",
+            Advection::Reflection => "reflection",
+        }
+    }
+}
+
+impl std::str::FromStr for Advection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("advection scheme", s, &[Advection::SemiLagrangian, Advection::Reflection].map(|a| (a.name(), a)))
+    }
+}
+
+/// Compute pipelines built from `fluid.wgsl` (the pressure solver builds its
+/// own). Shared by every copy, and rebuilt wholesale when the shader is
+/// hot-reloaded.
+pub struct FluidPipelines {
+    /// The module was built with `WA_SPLIT_READ_WRITE`, so in-place kernels
+    /// need their field stashed first.
+    split_read_write: bool,
+    stash_vel: wgpu::ComputePipeline,
+    stash_dens: wgpu::ComputePipeline,
+    add_source: wgpu::ComputePipeline,
+    curl: wgpu::ComputePipeline,
+    subgrid: wgpu::ComputePipeline,
+    confinement: wgpu::ComputePipeline,
+    wake: wgpu::ComputePipeline,
+    buoyancy: wgpu::ComputePipeline,
+    gravity: wgpu::ComputePipeline,
+    advect_vel: wgpu::ComputePipeline,
+    copy_vel: wgpu::ComputePipeline,
+    advect_vel_half: wgpu::ComputePipeline,
+    advect_vel_reflected: wgpu::ComputePipeline,
+    copy_reflected: wgpu::ComputePipeline,
+    advect_dens: wgpu::ComputePipeline,
+    copy_dens: wgpu::ComputePipeline,
+    diffuse_a: wgpu::ComputePipeline,
+    diffuse_b: wgpu::ComputePipeline,
+    divergence: wgpu::ComputePipeline,
+    gradient: wgpu::ComputePipeline,
+    obstacles: wgpu::ComputePipeline,
+}
+
+impl FluidPipelines {
+    /// `module` must have been built from source with `workarounds` applied.
+    pub fn new(device: &wgpu::Device, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule, workarounds: Workarounds) -> Self {
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(layout),
+                module, entry_point: entry,
+            })
+        };
+        Self {
+            split_read_write: workarounds.split_read_write,
+            stash_vel: make_compute("stash_vel"),
+            stash_dens: make_compute("stash_dens"),
+            add_source: make_compute("add_source"),
+            curl: make_compute("compute_curl"),
+            subgrid: make_compute("subgrid_energy"),
+            confinement: make_compute("vorticity_confinement"),
+            wake: make_compute("wake_turbulence"),
+            buoyancy: make_compute("apply_buoyancy"),
+            gravity: make_compute("apply_gravity"),
+            advect_vel: make_compute("advect_vel"),
+            copy_vel: make_compute("copy_vel"),
+            advect_vel_half: make_compute("advect_vel_half"),
+            advect_vel_reflected: make_compute("advect_vel_reflected"),
+            copy_reflected: make_compute("copy_reflected"),
+            advect_dens: make_compute("advect_dens"),
+            copy_dens: make_compute("copy_dens"),
+            diffuse_a: make_compute("diffuse_a"),
+            diffuse_b: make_compute("diffuse_b"),
+            divergence: make_compute("compute_divergence"),
+            gradient: make_compute("subtract_gradient"),
+            obstacles: make_compute("enforce_obstacles"),
+        }
+    }
+
+    /// Under the split-read-write workaround, copy velocity (and dye, if
+    /// `dye`) into the scratch textures the next in-place kernel reads from.
+    fn stash<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, wg: (u32, u32), dye: bool) {
+        if !self.split_read_write {
+            return;
+        }
+        c.set_pipeline(&self.stash_vel);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+        if dye {
+            c.set_pipeline(&self.stash_dens);
+            c.dispatch_workgroups(wg.0, wg.1, 1);
+        }
+    }
+}
+
+/// Resources every copy binds but doesn't own: the params uniform, scene
+/// emitters and obstacle mask, and the fluid layouts. The shader module is
+/// passed separately since hot reload replaces it.
+#[derive(Clone, Copy)]
+pub struct Shared<'a> {
+    pub device: &'a wgpu::Device,
+    pub bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub layout: &'a wgpu::PipelineLayout,
+    pub params: &'a wgpu::Buffer,
+    pub emitters: &'a wgpu::Buffer,
+    pub obstacles: &'a wgpu::TextureView,
+    pub grid_size: u32,
+}
+
+pub struct Simulation {
+    pub velocity: wgpu::Texture,
+    pub velocity_view: wgpu::TextureView,
+    pub density: wgpu::Texture,
+    pub density_view: wgpu::TextureView,
+    /// What the last pressure solve left, until the next step's
+    /// `compute_divergence` (or `measure_divergence`) zeroes it.
+    pub pressure: wgpu::Texture,
+    pub pressure_view: wgpu::TextureView,
+    /// For solvers built later (hot reload).
+    #[cfg(not(target_arch = "wasm32"))]
+    pressure_tmp_view: wgpu::TextureView,
+    /// Velocity divergence before the last projection, or after it once
+    /// `measure_divergence` has run.
+    pub divergence: wgpu::Texture,
+    pub divergence_view: wgpu::TextureView,
+    /// Curl, and the sub-grid turbulence the renderer reads.
+    pub curl: wgpu::Texture,
+    pub curl_view: wgpu::TextureView,
+    /// Textures that only live inside a frame (advection targets, pressure
+    /// ping-pong), kept alive for the bind group.
+    _scratch: Vec<wgpu::Texture>,
+    /// Heat map counters: one per cell plus the frame maximum.
+    pub cost: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub solver_kind: SolverKind,
+    pub solver: Box<dyn PressureSolver>,
+    /// Semi-Lagrangian unless set otherwise after `new`.
+    pub advection: Advection,
+    /// Only add sources and carry the dye; the velocity is put back from
+    /// an imported field each frame (`--freeze-velocity`).
+    pub frozen: bool,
+    workgroups: (u32, u32),
+}
+
+impl Simulation {
+    pub fn new(shared: &Shared, module: &wgpu::ShaderModule, solver_kind: SolverKind) -> Self {
+        let (device, grid) = (shared.device, shared.grid_size);
+        let (velocity, velocity_view) = create_storage_tex(device, grid);
+        let (vel_tmp, vel_tmp_view) = create_storage_tex(device, grid);
+        let (density, density_view) = create_storage_tex(device, grid);
+        let (dens_tmp, dens_tmp_view) = create_storage_tex(device, grid);
+        let (press, pressure_view) = create_storage_tex(device, grid);
+        let (press_tmp, pressure_tmp_view) = create_storage_tex(device, grid);
+        let (divergence, divergence_view) = create_storage_tex(device, grid);
+        let (curl, curl_view) = create_storage_tex(device, grid);
+
+        let cost = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cost"),
+            size: (grid as u64 * grid as u64 + 1) * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("compute_bg"), layout: shared.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: shared.params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&vel_tmp_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&density_view) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&dens_tmp_view) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&pressure_view) },
+                wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&pressure_tmp_view) },
+                wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(&divergence_view) },
+                wgpu::BindGroupEntry { binding: 8, resource: shared.emitters.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 9, resource: wgpu::BindingResource::TextureView(shared.obstacles) },
+                wgpu::BindGroupEntry { binding: 10, resource: wgpu::BindingResource::TextureView(&curl_view) },
+                wgpu::BindGroupEntry { binding: 11, resource: cost.as_entire_binding() },
+            ],
+        });
+
+        let workgroups = (grid.div_ceil(8), grid.div_ceil(8));
+        let solver = pressure::create(solver_kind, &pressure::SolverContext {
+            device, layout: shared.layout, module, workgroups,
+            grid_size: grid, pressure: &pressure_view, pressure_tmp: &pressure_tmp_view, divergence: &divergence_view,
+        });
+
+        Self {
+            velocity, velocity_view, density, density_view, pressure: press, pressure_view, divergence, divergence_view,
+            #[cfg(not(target_arch = "wasm32"))]
+            pressure_tmp_view,
+            curl, curl_view, _scratch: vec![vel_tmp, dens_tmp, press_tmp],
+            cost, bind_group, solver_kind, solver, advection: Advection::SemiLagrangian, frozen: false, workgroups,
+        }
+    }
+
+    /// A fresh solver of this copy's kind, built from `module`. Used by hot
+    /// reload, which swaps it in only if the new shader is valid.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn create_solver(&self, shared: &Shared, module: &wgpu::ShaderModule) -> Box<dyn PressureSolver> {
+        pressure::create(self.solver_kind, &pressure::SolverContext {
+            device: shared.device, layout: shared.layout, module,
+            workgroups: self.workgroups, grid_size: shared.grid_size,
+            pressure: &self.pressure_view, pressure_tmp: &self.pressure_tmp_view, divergence: &self.divergence_view,
+        })
+    }
+
+    /// Hand the solver this frame's cell size and grid boundary. Written
+    /// alongside the params uniform each frame, so resizes, scene changes
+    /// and W all reach solvers with their own uniforms.
+    pub fn set_domain(&self, queue: &wgpu::Queue, params: &SimParams) {
+        let boundary = Boundary::from_code(params.boundary).unwrap_or(Boundary::Walls);
+        self.solver.set_domain(queue, params.cell_size, boundary);
+    }
+
+    /// Let the solver read back what it measured this frame. Call with the
+    /// frame's encoder once its compute pass is recorded.
+    pub fn end_frame(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        self.solver.end_frame(device, encoder);
+    }
+
+    /// Call once the encoder given to `end_frame` is submitted.
+    pub fn submitted(&self) {
+        self.solver.submitted();
+    }
+
+    /// Record one simulation step. `params` is what the uniform holds this
+    /// frame; it decides which optional passes run.
+    pub fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, pipes: &'a FluidPipelines, params: &SimParams) {
+        let wg = self.workgroups;
+        c.set_bind_group(0, &self.bind_group, &[]);
+
+        pipes.stash(c, wg, true);
+        c.set_pipeline(&pipes.add_source);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+        if self.frozen {
+            self.advect_dye(c, pipes);
+            return;
+        }
+        c.set_pipeline(&pipes.curl);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+        if params.subgrid > 0.0 {
+            c.set_pipeline(&pipes.subgrid);
+            c.dispatch_workgroups(wg.0, wg.1, 1);
+        }
+        pipes.stash(c, wg, false);
+        c.set_pipeline(&pipes.confinement);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+        if params.wake != 0.0 {
+            pipes.stash(c, wg, false);
+            c.set_pipeline(&pipes.wake);
+            c.dispatch_workgroups(wg.0, wg.1, 1);
+        }
+        if params.smoke_weight != 0.0 || params.heat_lift != 0.0 || params.dye_density != [0.0; 3] {
+            pipes.stash(c, wg, false);
+            c.set_pipeline(&pipes.buoyancy);
+            c.dispatch_workgroups(wg.0, wg.1, 1);
+        }
+        if params.gravity != [0.0, 0.0] || params.well != 0.0 {
+            pipes.stash(c, wg, false);
+            c.set_pipeline(&pipes.gravity);
+            c.dispatch_workgroups(wg.0, wg.1, 1);
+        }
+        match self.advection {
+            Advection::SemiLagrangian => {
+                c.set_pipeline(&pipes.advect_vel);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+                c.set_pipeline(&pipes.copy_vel);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+                self.advect_dye(c, pipes);
+            }
+            Advection::Reflection => {
+                c.set_pipeline(&pipes.advect_vel_half);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+                c.set_pipeline(&pipes.copy_vel);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+                // Leaves the unprojected half step in velocity_tmp for the
+                // reflection
+                self.project(c, pipes);
+                // Dye moves with the midpoint velocity u½
+                self.advect_dye(c, pipes);
+                c.set_pipeline(&pipes.advect_vel_reflected);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+                c.set_pipeline(&pipes.copy_reflected);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+            }
+        }
+        if params.viscosity > 0.0 || params.diffusion > 0.0 {
+            for _ in 0..DIFFUSE_ITERATIONS {
+                c.set_pipeline(&pipes.diffuse_a);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+                c.set_pipeline(&pipes.diffuse_b);
+                c.dispatch_workgroups(wg.0, wg.1, 1);
+            }
+        }
+        self.project(c, pipes);
+    }
+
+    /// Compute dispatches `step` records with these parameters, for the HUD.
+    /// Follows `step` branch for branch.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn step_dispatches(&self, pipes: &FluidPipelines, params: &SimParams) -> u32 {
+        let stash = |dye: bool| if pipes.split_read_write { 1 + dye as u32 } else { 0 };
+        let optional = |on: bool| if on { stash(false) + 1 } else { 0 };
+        if self.frozen {
+            return stash(true) + 3;
+        }
+        let mut n = stash(true) + 2 + (params.subgrid > 0.0) as u32 + stash(false) + 1;
+        n += optional(params.wake != 0.0);
+        n += optional(params.smoke_weight != 0.0 || params.heat_lift != 0.0 || params.dye_density != [0.0; 3]);
+        n += optional(params.gravity != [0.0, 0.0] || params.well != 0.0);
+        n += match self.advection {
+            Advection::SemiLagrangian => 4,
+            Advection::Reflection => 6 + self.project_dispatches(pipes),
+        };
+        if params.viscosity > 0.0 || params.diffusion > 0.0 {
+            n += 2 * DIFFUSE_ITERATIONS;
+        }
+        n + self.project_dispatches(pipes)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn project_dispatches(&self, pipes: &FluidPipelines) -> u32 {
+        let stash = if pipes.split_read_write { 1 } else { 0 };
+        3 + stash + self.solver.dispatches()
+    }
+
+    fn advect_dye<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, pipes: &'a FluidPipelines) {
+        let wg = self.workgroups;
+        c.set_pipeline(&pipes.advect_dens);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+        c.set_pipeline(&pipes.copy_dens);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+    }
+
+    /// What the comparison messages call this copy: its solver, and its
+    /// advection unless that's the default.
+    pub fn label(&self) -> String {
+        match self.advection {
+            Advection::SemiLagrangian => self.solver_kind.to_string(),
+            other => format!("{} with {} advection", self.solver_kind, other.name()),
+        }
+    }
+
+    /// Record the projection that ends every step: divergence, pressure
+    /// solve, gradient subtraction, then the obstacles. The FLIP mode runs
+    /// just this on the velocity its particles splat.
+    pub fn project<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, pipes: &'a FluidPipelines) {
+        let wg = self.workgroups;
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&pipes.divergence);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+        self.solver.solve(c, &self.bind_group);
+        pipes.stash(c, wg, false);
+        c.set_pipeline(&pipes.gradient);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+        c.set_pipeline(&pipes.obstacles);
+        c.dispatch_workgroups(wg.0, wg.1, 1);
+    }
+
+    /// Recompute `divergence` from the current (projected) velocity, for
+    /// judging how well the solver did. Clobbers the pressure scratch.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn measure_divergence<'a>(&'a self, c: &mut wgpu::ComputePass<'a>, pipes: &'a FluidPipelines) {
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&pipes.divergence);
+        c.dispatch_workgroups(self.workgroups.0, self.workgroups.1, 1);
+    }
+
+    /// Copy `other`'s velocity, dye and temperature into this copy. Everything
+    /// else is rebuilt from those within a step.
+    pub fn copy_from(&self, device: &wgpu::Device, queue: &wgpu::Queue, other: &Simulation) {
+        copy_textures(device, queue, [(&other.velocity, &self.velocity), (&other.density, &self.density)]);
+    }
+
+    /// Reset this copy to a stored state.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(&self, device: &wgpu::Device, queue: &wgpu::Queue, state: &Reference) {
+        copy_textures(device, queue, [(&state.velocity, &self.velocity), (&state.density, &self.density)]);
+    }
+
+    pub fn fields(&self) -> Fields<'_> {
+        Fields { velocity: &self.velocity_view, density: &self.density_view }
+    }
+}
+
+fn copy_textures(device: &wgpu::Device, queue: &wgpu::Queue, pairs: [(&wgpu::Texture, &wgpu::Texture); 2]) {
+    let mut encoder = device.create_command_encoder(&Default::default());
+    for (src, dst) in pairs {
+        encoder.copy_texture_to_texture(src.as_image_copy(), dst.as_image_copy(), src.size());
+    }
+    queue.submit(Some(encoder.finish()));
+}
+
+/// Velocity and dye/temperature views of some state, as the render pass
+/// binds them.
+#[derive(Clone, Copy)]
+pub struct Fields<'a> {
+    pub velocity: &'a wgpu::TextureView,
+    pub density: &'a wgpu::TextureView,
+}
+
+/// A stored velocity/dye/temperature state that the live one is diffed
+/// against (B captures it, `--reference` loads it from a snapshot file).
+pub struct Reference {
+    pub velocity: wgpu::Texture,
+    velocity_view: wgpu::TextureView,
+    pub density: wgpu::Texture,
+    density_view: wgpu::TextureView,
+    /// False until something has been captured or loaded.
+    pub valid: bool,
+}
+
+impl Reference {
+    pub fn new(device: &wgpu::Device, grid: u32) -> Self {
+        let (velocity, velocity_view) = create_storage_tex(device, grid);
+        let (density, density_view) = create_storage_tex(device, grid);
+        Self { velocity, velocity_view, density, density_view, valid: false }
+    }
+
+    pub fn capture(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sim: &Simulation) {
+        copy_textures(device, queue, [(&sim.velocity, &self.velocity), (&sim.density, &self.density)]);
+        self.valid = true;
+    }
+
+    pub fn fields(&self) -> Fields<'_> {
+        Fields { velocity: &self.velocity_view, density: &self.density_view }
+    }
+}
+
+/// What the window shows while a comparison is running.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareView {
+    /// The original copy, stepped with `--solver`.
+    Primary,
+    /// The clone, stepped with `--compare`.
+    Secondary,
+    /// Signed primary - clone difference of the selected `DiffField`.
+    Difference,
+}
+
+impl CompareView {
+    pub fn next(self) -> Self {
+        match self {
+            CompareView::Primary => CompareView::Secondary,
+            CompareView::Secondary => CompareView::Difference,
+            CompareView::Difference => CompareView::Primary,
+        }
+    }
+}
+
+/// Which field the window shows (Q): the dye as usual, or one of the
+/// solver's own through a colormap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldView {
+    Dye,
+    /// Direction as hue, speed as brightness.
+    Velocity,
+    /// What the last pressure solve left.
+    Pressure,
+    /// Divergence before the last projection, or after it once
+    /// `measure_divergence` has run.
+    Divergence,
+    Vorticity,
+    /// Noise smeared along the streamlines (line integral convolution),
+    /// tinted by speed.
+    Lic,
+    /// Synthetic schlieren: the dye's gradient as a gray wind-tunnel
+    /// photograph, shaded across a knife edge.
+    Schlieren,
+}
+
+impl FieldView {
+    pub fn next(self) -> Self {
+        match self {
+            FieldView::Dye => FieldView::Velocity,
+            FieldView::Velocity => FieldView::Pressure,
+            FieldView::Pressure => FieldView::Divergence,
+            FieldView::Divergence => FieldView::Vorticity,
+            FieldView::Vorticity => FieldView::Lic,
+            FieldView::Lic => FieldView::Schlieren,
+            FieldView::Schlieren => FieldView::Dye,
+        }
+    }
+
+    /// Value shown at full color: speed in cells per second for velocity
+    /// and the LIC's tint, dye per cell for the schlieren's gradient, the
+    /// others in their own units (1/s for divergence and vorticity).
+    pub fn default_scale(self) -> f32 {
+        match self {
+            FieldView::Dye => 1.0,
+            FieldView::Velocity => 100.0,
+            FieldView::Pressure => 50.0,
+            FieldView::Divergence => 5.0,
+            FieldView::Vorticity => 10.0,
+            FieldView::Lic => 100.0,
+            FieldView::Schlieren => 0.1,
+        }
+    }
+}
+
+/// Which field the difference view shows, as (this state) - (other state).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiffField {
+    Dye,
+    Temperature,
+    VelocityX,
+    VelocityY,
+    /// Difference of the speeds, not the speed of the difference.
+    Speed,
+}
+
+impl DiffField {
+    pub fn next(self) -> Self {
+        match self {
+            DiffField::Dye => DiffField::Temperature,
+            DiffField::Temperature => DiffField::VelocityX,
+            DiffField::VelocityX => DiffField::VelocityY,
+            DiffField::VelocityY => DiffField::Speed,
+            DiffField::Speed => DiffField::Dye,
+        }
+    }
+
+    /// Difference shown at full color: dye and temperature in their own
+    /// units, velocities in cells per second.
+    pub fn default_scale(self) -> f32 {
+        match self {
+            DiffField::Dye | DiffField::Temperature => 0.25,
+            DiffField::VelocityX | DiffField::VelocityY | DiffField::Speed => 10.0,
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Compressed simulation snapshots (dye + velocity). Fields are quantized to
+//! 16-bit fixed point, delta-coded along rows, split into low/high byte
+//! planes and LZ-compressed, which roughly halves a 256² state (384 KiB
+//! raw). Used for the embedded `--demo` start state, `--snapshot` files and
+//! saving the current state with F9.
+//!
+//! Layout: `b"WFS2"`, grid size (u32 LE), raw payload length (u32 LE), then
+//! the compressed payload: red, green and blue dye, then x and y velocity.
+//! Version 1 files (`b"WFS1"`), from before the dye had color, hold one dye
+//! field in place of the three and load as gray.
+
+const MAGIC: &[u8; 3] = b"WFS";
+/// The version written, after `MAGIC`.
+const VERSION: u8 = b'2';
+/// Velocity steps of 1/16 cell/s, range ±2048 cells/s.
+const VEL_SCALE: f32 = 16.0;
+/// Dye steps of 1/1024, range ±32.
+const DENS_SCALE: f32 = 1024.0;
+
+/// Snapshot loaded by `--demo`: the `turbulence` scene after a couple of
+/// seconds of mixing.
+pub const DEMO: &[u8] = include_bytes!("../assets/demo.wfs");
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub grid: u32,
+    /// Red, green and blue dye.
+    pub density: Vec<[f32; 3]>,
+    /// Grid cells per second.
+    pub velocity: Vec<[f32; 2]>,
+}
+
+impl Snapshot {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn encode(&self) -> Vec<u8> {
+        let n = self.grid as usize;
+        let mut raw = Vec::with_capacity(n * n * 10);
+        for c in 0..3 {
+            let dye: Vec<f32> = self.density.iter().map(|d| d[c]).collect();
+            push_planes(&mut raw, n, &dye, DENS_SCALE);
+        }
+        for c in 0..2 {
+            let velocity: Vec<f32> = self.velocity.iter().map(|v| v[c]).collect();
+            push_planes(&mut raw, n, &velocity, VEL_SCALE);
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.grid.to_le_bytes());
+        out.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        lz_compress(&raw, &mut out);
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Snapshot, String> {
+        if bytes.get(..3) != Some(&MAGIC[..]) {
+            return Err("not a snapshot file".to_string());
+        }
+        let dye_fields = match bytes.get(3) {
+            Some(b'1') => 1,
+            Some(&VERSION) => 3,
+            _ => return Err("saved by a different version of wgpu-fluid".to_string()),
+        };
+        if bytes.len() < 12 {
+            return Err("snapshot file is truncated".to_string());
+        }
+        let grid = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let raw_len = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let n = grid as usize;
+        if raw_len != n * n * 2 * (dye_fields + 2) {
+            return Err(format!("payload size {} doesn't match a {}² grid", raw_len, grid));
+        }
+        let raw = lz_decompress(&bytes[12..], raw_len)?;
+
+        let scales = std::iter::repeat_n(DENS_SCALE, dye_fields).chain([VEL_SCALE, VEL_SCALE]);
+        let mut fields = raw.chunks(n * n * 2).zip(scales).map(|(planes, scale)| {
+            let (lo, hi) = planes.split_at(n * n);
+            let mut out = Vec::with_capacity(n * n);
+            for (row_lo, row_hi) in lo.chunks(n).zip(hi.chunks(n)) {
+                let mut prev = 0i16;
+                for (&l, &h) in row_lo.iter().zip(row_hi) {
+                    let zz = l as u16 | (h as u16) << 8;
+                    let d = ((zz >> 1) as i16) ^ -((zz & 1) as i16);
+                    prev = prev.wrapping_add(d);
+                    out.push(prev as f32 / scale);
+                }
+            }
+            out
+        });
+        let density = match dye_fields {
+            1 => fields.next().unwrap().into_iter().map(|d| [d; 3]).collect(),
+            _ => {
+                let (r, g, b) = (fields.next().unwrap(), fields.next().unwrap(), fields.next().unwrap());
+                r.into_iter().zip(g).zip(b).map(|((r, g), b)| [r, g, b]).collect()
+            }
+        };
+        let (vx, vy) = (fields.next().unwrap(), fields.next().unwrap());
+        let velocity = vx.into_iter().zip(vy).map(|(x, y)| [x, y]).collect();
+        Ok(Snapshot { grid, density, velocity })
+    }
+
+    /// Bilinearly resample to another grid size. Velocities are in cells per
+    /// second, so they're scaled along with the grid.
+    pub fn resample(&self, grid: u32) -> Snapshot {
+        if grid == self.grid {
+            return self.clone();
+        }
+        let (src, dst) = (self.grid as usize, grid as usize);
+        let ratio = src as f32 / dst as f32;
+        let speed = 1.0 / ratio;
+        let mut density = Vec::with_capacity(dst * dst);
+        let mut velocity = Vec::with_capacity(dst * dst);
+        for y in 0..dst {
+            for x in 0..dst {
+                let sx = ((x as f32 + 0.5) * ratio - 0.5).clamp(0.0, (src - 1) as f32);
+                let sy = ((y as f32 + 0.5) * ratio - 0.5).clamp(0.0, (src - 1) as f32);
+                let (x0, y0) = (sx as usize, sy as usize);
+                let (x1, y1) = ((x0 + 1).min(src - 1), (y0 + 1).min(src - 1));
+                let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+                let weights = [(x0, y0, (1.0 - fx) * (1.0 - fy)), (x1, y0, fx * (1.0 - fy)), (x0, y1, (1.0 - fx) * fy), (x1, y1, fx * fy)];
+                let (mut d, mut v) = ([0.0; 3], [0.0; 2]);
+                for (cx, cy, w) in weights {
+                    let i = cy * src + cx;
+                    for (d, s) in d.iter_mut().zip(self.density[i]) {
+                        *d += s * w;
+                    }
+                    v[0] += self.velocity[i][0] * w;
+                    v[1] += self.velocity[i][1] * w;
+                }
+                density.push(d);
+                velocity.push([v[0] * speed, v[1] * speed]);
+            }
+        }
+        Snapshot { grid, density, velocity }
+    }
+}
+
+/// `field`'s rows at `scale` as 16-bit fixed point, each value the
+/// zigzagged difference from the one before it, as low then high bytes.
+#[cfg(not(target_arch = "wasm32"))]
+fn push_planes(raw: &mut Vec<u8>, n: usize, field: &[f32], scale: f32) {
+    let mut lo = Vec::with_capacity(n * n);
+    let mut hi = Vec::with_capacity(n * n);
+    for row in field.chunks(n) {
+        let mut prev = 0i16;
+        for &v in row {
+            let q = (v * scale).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+            let d = q.wrapping_sub(prev);
+            let zz = ((d << 1) ^ (d >> 15)) as u16;
+            lo.push(zz as u8);
+            hi.push((zz >> 8) as u8);
+            prev = q;
+        }
+    }
+    raw.extend_from_slice(&lo);
+    raw.extend_from_slice(&hi);
+}
+
+// ---- Byte-oriented LZ77 ----
+//
+// LZ4-style sequences: a token byte with the literal count in the high
+// nibble and (match length - MIN_MATCH) in the low nibble, 15 meaning "more
+// length bytes follow" (each added, 255 meaning keep going). Then the
+// literals, then a u16 LE back-reference offset. The final sequence has
+// literals only.
+
+const MIN_MATCH: usize = 4;
+#[cfg(not(target_arch = "wasm32"))]
+const HASH_BITS: u32 = 14;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn lz_compress(src: &[u8], out: &mut Vec<u8>) {
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let hash = |i: usize| {
+        let v = u32::from_le_bytes(src[i..i + 4].try_into().unwrap());
+        (v.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+    };
+
+    let mut anchor = 0;
+    let mut i = 0;
+    while i + MIN_MATCH <= src.len() {
+        let h = hash(i);
+        let candidate = table[h];
+        table[h] = i;
+        if candidate != usize::MAX && i - candidate <= u16::MAX as usize && src[candidate..candidate + MIN_MATCH] == src[i..i + MIN_MATCH] {
+            let mut len = MIN_MATCH;
+            while i + len < src.len() && src[candidate + len] == src[i + len] {
+                len += 1;
+            }
+            write_sequence(out, &src[anchor..i], Some(((i - candidate) as u16, len)));
+            i += len;
+            anchor = i;
+        } else {
+            i += 1;
+        }
+    }
+    write_sequence(out, &src[anchor..], None);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(u16, usize)>) {
+    let lit_len = literals.len();
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((lit_len.min(15) as u8) << 4) | match_len.min(15) as u8);
+    write_length(out, lit_len);
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&offset.to_le_bytes());
+        write_length(out, match_len);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_length(out: &mut Vec<u8>, len: usize) {
+    if len < 15 {
+        return;
+    }
+    let mut rest = len - 15;
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+pub fn lz_decompress(src: &[u8], expected: usize) -> Result<Vec<u8>, String> {
+    let corrupt = || "data is corrupt".to_string();
+    let mut out = Vec::with_capacity(expected);
+    let mut i = 0;
+    // Whether the final, literals-only sequence was read
+    let mut ended = false;
+    let read_length = |i: &mut usize, nibble: usize| -> Result<usize, String> {
+        let mut len = nibble;
+        if nibble == 15 {
+            loop {
+                let b = *src.get(*i).ok_or_else(corrupt)?;
+                *i += 1;
+                len += b as usize;
+                if b != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(len)
+    };
+    while i < src.len() {
+        let token = src[i];
+        i += 1;
+        let lit_len = read_length(&mut i, (token >> 4) as usize)?;
+        out.extend_from_slice(src.get(i..i + lit_len).ok_or_else(corrupt)?);
+        i += lit_len;
+        if i == src.len() {
+            ended = true;
+            break;
+        }
+        let offset = u16::from_le_bytes(src.get(i..i + 2).ok_or_else(corrupt)?.try_into().unwrap()) as usize;
+        i += 2;
+        let len = read_length(&mut i, (token & 15) as usize)? + MIN_MATCH;
+        if offset == 0 || offset > out.len() || out.len() + len > expected {
+            return Err(corrupt());
+        }
+        // Byte by byte: matches may overlap the bytes they produce.
+        let start = out.len() - offset;
+        for k in 0..len {
+            out.push(out[start + k]);
+        }
+    }
+    if !ended || out.len() != expected {
+        return Err(corrupt());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16² state with swirls of velocity and dye over most of the
+    /// quantized range.
+    fn snapshot() -> Snapshot {
+        let n = 16;
+        let (density, velocity) = (0..n * n)
+            .map(|i| {
+                let (x, y) = ((i % n) as f32, (i / n) as f32);
+                let dye = [(x * 0.7).sin() * 20.0 + y * 0.5, y * 0.1, (x + y).cos()];
+                (dye, [(y * 0.3).cos() * 1500.0, x * x - 100.0])
+            })
+            .unzip();
+        Snapshot { grid: n as u32, density, velocity }
+    }
+
+    #[test]
+    fn round_trips_to_the_quantization_step() {
+        let before = snapshot();
+        let after = Snapshot::decode(&before.encode()).unwrap();
+        assert_eq!(after.grid, before.grid);
+        for (a, b) in after.density.iter().zip(&before.density) {
+            for c in 0..3 {
+                assert!((a[c] - b[c]).abs() <= 0.5 / DENS_SCALE, "dye {:?} came back as {:?}", b, a);
+            }
+        }
+        for (a, b) in after.velocity.iter().zip(&before.velocity) {
+            for c in 0..2 {
+                assert!((a[c] - b[c]).abs() <= 0.5 / VEL_SCALE, "velocity {:?} came back as {:?}", b, a);
+            }
+        }
+        // Quantized values come back exactly
+        assert_eq!(Snapshot::decode(&after.encode()).unwrap().velocity, after.velocity);
+    }
+
+    #[test]
+    fn demo_decodes() {
+        let demo = Snapshot::decode(DEMO).unwrap();
+        let cells = (demo.grid * demo.grid) as usize;
+        assert_eq!([demo.density.len(), demo.velocity.len()], [cells, cells]);
+    }
+
+    #[test]
+    fn upgrades_version_1_files_to_gray_dye() {
+        let before = snapshot();
+        let n = before.grid as usize;
+        let mut raw = Vec::new();
+        let dye: Vec<f32> = before.density.iter().map(|d| d[0]).collect();
+        push_planes(&mut raw, n, &dye, DENS_SCALE);
+        for c in 0..2 {
+            let velocity: Vec<f32> = before.velocity.iter().map(|v| v[c]).collect();
+            push_planes(&mut raw, n, &velocity, VEL_SCALE);
+        }
+        let mut file = b"WFS1".to_vec();
+        file.extend_from_slice(&before.grid.to_le_bytes());
+        file.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        lz_compress(&raw, &mut file);
+
+        let after = Snapshot::decode(&file).unwrap();
+        for (a, b) in after.density.iter().zip(&before.density) {
+            assert_eq!([a[1], a[2]], [a[0], a[0]]);
+            assert!((a[0] - b[0]).abs() <= 0.5 / DENS_SCALE, "dye {} came back as {}", b[0], a[0]);
+        }
+        assert_eq!(Snapshot::decode(&after.encode()).unwrap().velocity, after.velocity);
+    }
+
+    #[test]
+    fn rejects_truncated_and_other_files() {
+        let file = snapshot().encode();
+        for len in 0..file.len() {
+            assert!(Snapshot::decode(&file[..len]).is_err(), "{} of {} bytes decoded", len, file.len());
+        }
+        let mut newer = file.clone();
+        newer[3] = b'9';
+        assert_eq!(Snapshot::decode(&newer).err().unwrap(), "saved by a different version of wgpu-fluid");
+        assert_eq!(Snapshot::decode(b"WFC1 a checkpoint").err().unwrap(), "not a snapshot file");
+        let mut wrong_grid = file.clone();
+        wrong_grid[4] = 17;
+        assert!(Snapshot::decode(&wrong_grid).err().unwrap().contains("doesn't match"));
+    }
+
+    #[test]
+    fn lz_round_trips_long_runs_and_literals() {
+        // Runs and literal stretches both past the 15 + 255 length bytes
+        let mut seed = 3u32;
+        let mut src = vec![7u8; 1000];
+        src.extend((0..700).map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 24) as u8
+        }));
+        src.extend_from_within(..1200);
+        let mut packed = Vec::new();
+        lz_compress(&src, &mut packed);
+        assert!(packed.len() < src.len());
+        assert_eq!(lz_decompress(&packed, src.len()).unwrap(), src);
+        assert!(lz_decompress(&packed, src.len() - 1).is_err());
+        assert!(lz_decompress(&packed, src.len() + 1).is_err());
+    }
+}
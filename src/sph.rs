@@ -0,0 +1,410 @@
+//! Weakly compressible SPH mode (`--sph N`): N particles are the fluid,
+//! with no grid but the neighbor bins. Each substep the kernels in
+//! `sph.wgsl` file the particles into bins two cells across, sum each
+//! particle's density over the neighbors in its 3×3 bins, turn compression
+//! into pressure with a stiff equation of state, and integrate pressure,
+//! viscosity, gravity and the mouse. The liquid starts as a dam and has a
+//! free surface for nothing: where there are no particles there is no
+//! fluid.
+
+use std::sync::Arc;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    event::MouseButton,
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::mode::{self, Mode};
+use crate::rng::Seed;
+use crate::SimParams;
+
+/// Kernel radius in cells. Must match `SMOOTHING` in `sph.wgsl`.
+const SMOOTHING: f32 = 2.0;
+/// Particles per neighbor bin the buffers make room for. Must match
+/// `BIN_CAPACITY` in `sph.wgsl`.
+const BIN_CAPACITY: u64 = 16;
+/// Particle spacing at rest, in cells: one particle per cell.
+const SPACING: f32 = 1.0;
+/// Speed of sound in cells/s. About four times the fastest a dam's water
+/// falls, which keeps compression to a few percent.
+const SOUND_SPEED: f32 = 1000.0;
+/// Steps per frame. A step can't carry a pressure wave further than about
+/// a fifth of the kernel radius, so stiff liquid needs many short ones.
+const SUBSTEPS: u32 = 20;
+const VISCOSITY: f32 = 1.5;
+/// Gravity when `--gravity` leaves it off, in cells/s² down, as in FLIP mode.
+const DEFAULT_GRAVITY: f32 = 250.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SphParams {
+    count: u32,
+    domain: f32,
+    bins: u32,
+    dt: f32,
+    rest_density: f32,
+    stiffness: f32,
+    viscosity: f32,
+    aspect: f32,
+    gravity: [f32; 2],
+    /// Brush center in cells and the cursor's velocity in cells/s.
+    mouse_pos: [f32; 2],
+    mouse_vel: [f32; 2],
+    radius: f32,
+    mouse_down: u32,
+    /// Sprite radius in cells.
+    sprite: f32,
+    _pad: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Particle {
+    pos: [f32; 2],
+    vel: [f32; 2],
+}
+
+/// Same as `poly6` in `sph.wgsl`.
+fn poly6(r2: f32) -> f32 {
+    let d = SMOOTHING * SMOOTHING - r2;
+    4.0 / (std::f32::consts::PI * SMOOTHING.powi(8)) * d * d * d
+}
+
+/// Density a particle sees inside a block of liquid at rest, on a square
+/// lattice `SPACING` apart (itself included).
+fn rest_density() -> f32 {
+    let reach = (SMOOTHING / SPACING).ceil() as i32;
+    let mut density = 0.0;
+    for y in -reach..=reach {
+        for x in -reach..=reach {
+            let r2 = ((x * x + y * y) as f32) * SPACING * SPACING;
+            if r2 < SMOOTHING * SMOOTHING {
+                density += poly6(r2);
+            }
+        }
+    }
+    density
+}
+
+/// A dam at rest against the left wall: `count` particles on the rest
+/// lattice, jittered a little so they don't stack in perfect columns.
+fn dam_break(count: u32, domain: f32, seed: Seed) -> Vec<Particle> {
+    let columns = ((count as f32 / 1.5).sqrt().ceil() as u32).min(((domain - 1.0) / SPACING) as u32).max(1);
+    let mut rng = seed.rng("sph-dam");
+    (0..count).map(|i| {
+        let (col, row) = (i % columns, i / columns);
+        let jitter = [rng.next_f32() - 0.5, rng.next_f32() - 0.5].map(|j| j * 0.1 * SPACING);
+        let x = 0.5 + (col as f32 + 0.5) * SPACING + jitter[0];
+        let y = domain - 0.5 - (row as f32 + 0.5) * SPACING + jitter[1];
+        Particle { pos: [x, y.max(0.5)], vel: [0.0; 2] }
+    }).collect()
+}
+
+fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding, visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// The particles, neighbor bins, force kernels and sprite renderer.
+struct Sph {
+    params: SphParams,
+    param_buffer: wgpu::Buffer,
+    particles: wgpu::Buffer,
+    _fluid: wgpu::Buffer,
+    _accel: wgpu::Buffer,
+    _bin_counts: wgpu::Buffer,
+    _bin_slots: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    clear_bins: wgpu::ComputePipeline,
+    bin_particles: wgpu::ComputePipeline,
+    compute_density: wgpu::ComputePipeline,
+    compute_forces: wgpu::ComputePipeline,
+    integrate: wgpu::ComputePipeline,
+    sprite_pipeline: wgpu::RenderPipeline,
+    sprite_bg: wgpu::BindGroup,
+    /// The dam's jitter is drawn from this on every reset.
+    seed: Seed,
+}
+
+impl Sph {
+    fn new(
+        device: &wgpu::Device, format: wgpu::TextureFormat, domain: u32, count: u32, dt: f32, gravity: [f32; 2], seed: Seed,
+    ) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sph_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../sph.wgsl").into()),
+        });
+
+        let bins = (domain as f32 / SMOOTHING).ceil() as u32;
+        let params = SphParams {
+            count, domain: domain as f32, bins, dt: dt / SUBSTEPS as f32, rest_density: rest_density(),
+            stiffness: SOUND_SPEED * SOUND_SPEED, viscosity: VISCOSITY, aspect: 1.0, gravity,
+            mouse_pos: [0.0; 2], mouse_vel: [0.0; 2], radius: domain as f32 * 0.06, mouse_down: 0,
+            sprite: 0.6 * SPACING, _pad: 0.0,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("sph_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let particles = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("sph_particles"),
+            contents: bytemuck::cast_slice(&dam_break(count, domain as f32, seed)),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+        let per_particle = |label| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label), size: count as u64 * 8,
+            usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false,
+        });
+        let fluid = per_particle("sph_fluid");
+        let accel = per_particle("sph_accel");
+        let bin_cells = bins as u64 * bins as u64;
+        let bin_counts = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sph_bin_counts"), size: bin_cells * 4,
+            usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false,
+        });
+        let bin_slots = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sph_bin_slots"), size: bin_cells * BIN_CAPACITY * 4,
+            usage: wgpu::BufferUsages::STORAGE, mapped_at_creation: false,
+        });
+
+        // ---- Compute ----
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sph_compute_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+                storage_entry(2),
+                storage_entry(3),
+                storage_entry(4),
+                storage_entry(5),
+            ],
+        });
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl), module: &module, entry_point: entry,
+            })
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sph_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: particles.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: fluid.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: accel.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: bin_counts.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: bin_slots.as_entire_binding() },
+            ],
+        });
+
+        // ---- Render ----
+        let sprite_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sph_sprite_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false, min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let sprite_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sph_sprite_bg"), layout: &sprite_bgl,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() }],
+        });
+        let sprite_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&sprite_bgl], push_constant_ranges: &[],
+        });
+        // One instance per particle, read straight from the storage buffer
+        let sprite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fs_sprite"), layout: Some(&sprite_pl),
+            vertex: wgpu::VertexState {
+                module: &module, entry_point: "vs_sprite",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Particle>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module, entry_point: "fs_sprite",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format, blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            params, param_buffer, particles, _fluid: fluid, _accel: accel,
+            _bin_counts: bin_counts, _bin_slots: bin_slots, bind_group,
+            clear_bins: make_compute("clear_bins"),
+            bin_particles: make_compute("bin_particles"),
+            compute_density: make_compute("compute_density"),
+            compute_forces: make_compute("compute_forces"),
+            integrate: make_compute("integrate"),
+            sprite_pipeline, sprite_bg, seed,
+        }
+    }
+
+    /// One frame: `SUBSTEPS` rounds of binning, density, forces and
+    /// integration.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        let bins = (self.params.bins * self.params.bins).div_ceil(64);
+        let particles = self.params.count.div_ceil(64);
+        c.set_bind_group(0, &self.bind_group, &[]);
+        for _ in 0..SUBSTEPS {
+            c.set_pipeline(&self.clear_bins);
+            c.dispatch_workgroups(bins, 1, 1);
+            c.set_pipeline(&self.bin_particles);
+            c.dispatch_workgroups(particles, 1, 1);
+            c.set_pipeline(&self.compute_density);
+            c.dispatch_workgroups(particles, 1, 1);
+            c.set_pipeline(&self.compute_forces);
+            c.dispatch_workgroups(particles, 1, 1);
+            c.set_pipeline(&self.integrate);
+            c.dispatch_workgroups(particles, 1, 1);
+        }
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("sph_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.02, g: 0.02, b: 0.03, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.sprite_pipeline);
+        r.set_bind_group(0, &self.sprite_bg, &[]);
+        r.set_vertex_buffer(0, self.particles.slice(..));
+        r.draw(0..6, 0..self.params.count);
+    }
+
+    /// Put the dam back up.
+    fn reset(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.particles, 0, bytemuck::cast_slice(&dam_break(self.params.count, self.params.domain, self.seed)));
+    }
+}
+
+/// The SPH window loop, taking over from `run` once the window and device
+/// exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let count = config.sph.unwrap_or(16384);
+    let domain = config.grid_size;
+    let gpu = mode::Gpu::new(&config, window, surface, adapter, device, queue);
+    let (device, format) = (&gpu.device, gpu.format());
+    let win_size = gpu.window.inner_size();
+
+    // Only the gravity is used, so the shared gravity keys work on it
+    let sim_params = SimParams {
+        gravity: if config.gravity == [0.0, 0.0] { [0.0, DEFAULT_GRAVITY] } else { config.gravity },
+        gravity_by_dye: 0,
+        ..crate::base_params(&config, crate::cell_size_for(win_size))
+    };
+    let sph = Sph::new(device, format, domain, count, sim_params.dt, sim_params.gravity, config.seed);
+    eprintln!("SPH mode: {} particles in a {}² box, {} substeps per frame", count, domain, SUBSTEPS);
+
+    let state = SphMode { sph, stored_gravity: sim_params.gravity, sim_params, cursor: [0.0; 2], last_cursor: None };
+    mode::run(gpu, event_loop, domain, state).await;
+}
+
+/// The SPH window's state between frames.
+struct SphMode {
+    sph: Sph,
+    sim_params: SimParams,
+    cursor: [f32; 2],
+    last_cursor: Option<[f32; 2]>,
+    stored_gravity: [f32; 2],
+}
+
+impl Mode for SphMode {
+    const STEP: &'static str = "sph_step";
+
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue) {
+        match code {
+            KeyCode::KeyR => {
+                self.sph.reset(queue);
+                eprintln!("Dam reset");
+            }
+            KeyCode::KeyG | KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::ArrowLeft | KeyCode::ArrowRight
+            | KeyCode::Minus | KeyCode::Equal => {
+                crate::adjust_gravity(code, false, &mut self.sim_params, &mut self.stored_gravity);
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse(&mut self, button: MouseButton, pressed: bool) {
+        if button == MouseButton::Left {
+            self.sph.params.mouse_down = pressed as u32;
+            self.last_cursor = None;
+        }
+    }
+
+    fn cursor(&mut self, cursor: [f32; 2]) {
+        self.cursor = cursor;
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue, aspect: f32, _paused: bool) {
+        let cursor = self.cursor;
+        let p = &mut self.sph.params;
+        p.gravity = self.sim_params.gravity;
+        p.aspect = aspect;
+        // The cursor's velocity over the last frame
+        p.mouse_vel = self.last_cursor.map_or([0.0; 2], |last| [cursor[0] - last[0], cursor[1] - last[1]])
+            .map(|d| d / self.sim_params.dt);
+        p.mouse_pos = cursor;
+        if p.mouse_down != 0 {
+            self.last_cursor = Some(cursor);
+        }
+        queue.write_buffer(&self.sph.param_buffer, 0, bytemuck::bytes_of(&self.sph.params));
+    }
+
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        self.sph.step(c);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.sph.render(encoder, view);
+    }
+}
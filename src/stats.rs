@@ -0,0 +1,197 @@
+//! Live telemetry (`--stats-json`). After every frame one JSON object goes
+//! out on its own line: frame rate, GPU time of the simulation and render
+//! passes, dye mass, kinetic energy, top speed and the RMS divergence left
+//! after the projection. Dashboards and test harnesses read it from stdout, or
+//! connect to a TCP address and get the lines from then on.
+//!
+//! The fields are read back every frame and the GPU is waited on, so the
+//! frame rate reported is a little below what the app runs at without it.
+//! Pass times need the adapter's timestamp queries; without them they're
+//! `null`.
+
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Timestamps written per frame: start and end of the sim and render passes.
+const TIMESTAMPS: u32 = 4;
+
+/// Where the lines go.
+enum Sink {
+    Stdout,
+    /// Clients accepted in the background; one that can't keep up or has
+    /// gone is dropped.
+    Clients(Arc<Mutex<Vec<TcpStream>>>),
+}
+
+/// GPU timestamps around the sim and render passes.
+struct PassTimer {
+    queries: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    staging: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f64,
+}
+
+/// One frame's numbers, ready to print.
+pub struct FrameStats {
+    pub frame: u64,
+    /// Simulated seconds so far, and this frame's step size.
+    pub time: f32,
+    pub dt: f32,
+    pub substeps: u32,
+    /// Jacobi iterations per solve, where `jacobi:auto` picks them.
+    pub pressure_iterations: Option<u32>,
+}
+
+pub struct StatsStream {
+    sink: Sink,
+    timer: Option<PassTimer>,
+    last_frame: Option<Instant>,
+}
+
+impl StatsStream {
+    /// Stream to stdout for `target` `stdout`, otherwise listen for clients
+    /// on it as an address.
+    pub fn open(target: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> io::Result<Self> {
+        let sink = if target == "stdout" {
+            eprintln!("Streaming frame stats to stdout");
+            Sink::Stdout
+        } else {
+            let listener = TcpListener::bind(target)?;
+            eprintln!("Streaming frame stats to clients of {}", listener.local_addr()?);
+            let clients = Arc::new(Mutex::new(Vec::new()));
+            let list = clients.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if let Ok(peer) = stream.peer_addr() {
+                        eprintln!("Stats client {} connected", peer);
+                    }
+                    let _ = stream.set_nodelay(true);
+                    list.lock().unwrap().push(stream);
+                }
+            });
+            Sink::Clients(clients)
+        };
+        let timer = device.features().contains(wgpu::Features::TIMESTAMP_QUERY).then(|| PassTimer::new(device, queue));
+        if timer.is_none() {
+            eprintln!("No timestamp queries on this adapter; pass times will be null");
+        }
+        Ok(Self { sink, timer, last_frame: None })
+    }
+
+    /// Timestamp writes for the frame's compute pass.
+    pub fn sim_writes(&self) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.timer.as_ref().map(|t| wgpu::ComputePassTimestampWrites {
+            query_set: &t.queries, beginning_of_pass_write_index: Some(0), end_of_pass_write_index: Some(1),
+        })
+    }
+
+    /// Timestamp writes for the frame's render pass.
+    pub fn render_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.timer.as_ref().map(|t| wgpu::RenderPassTimestampWrites {
+            query_set: &t.queries, beginning_of_pass_write_index: Some(2), end_of_pass_write_index: Some(3),
+        })
+    }
+
+    /// Copy the timestamps out; record after the passes, before submitting.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(t) = &self.timer {
+            encoder.resolve_query_set(&t.queries, 0..TIMESTAMPS, &t.resolve, 0);
+            encoder.copy_buffer_to_buffer(&t.resolve, 0, &t.staging, 0, t.resolve.size());
+        }
+    }
+
+    /// Measure the submitted frame and send its line. `divergence` must
+    /// hold the projected velocity's divergence (`measure_divergence`).
+    /// `compare` is the comparison clone's velocity, while there is one.
+    /// Blocks until the GPU is done.
+    pub fn after_frame(
+        &mut self, device: &wgpu::Device, queue: &wgpu::Queue, fields: [&wgpu::Texture; 3], compare: Option<&wgpu::Texture>,
+        grid: u32, frame: &FrameStats,
+    ) {
+        let now = Instant::now();
+        let fps = self.last_frame.map(|last| 1.0 / now.duration_since(last).as_secs_f64());
+        self.last_frame = Some(now);
+
+        let [velocity, density, divergence] = fields.map(|tex| crate::read_field(device, queue, tex, grid));
+        // Dye is red, temperature, green, blue
+        let mass = [0, 2, 3].map(|c| density.iter().map(|t| t[c] as f64).sum::<f64>());
+        let energy = crate::tune::kinetic_energy(&velocity);
+        let compare_energy = compare.map(|tex| crate::tune::kinetic_energy(&crate::read_field(device, queue, tex, grid)));
+        let max_speed = velocity.iter().map(|v| (v[0] as f64).hypot(v[1] as f64)).fold(0.0, f64::max);
+        let divergence_rms = (divergence.iter().map(|t| (t[0] as f64).powi(2)).sum::<f64>() / divergence.len().max(1) as f64).sqrt();
+        let [sim_ms, render_ms] = match &self.timer {
+            Some(t) => t.read(device),
+            None => [None, None],
+        };
+
+        let line = format!(
+            "{{\"frame\":{},\"time\":{},\"dt\":{},\"substeps\":{},\"fps\":{},\"sim_ms\":{},\"render_ms\":{},\"mass\":[{},{},{}],\"energy\":{},\"compare_energy\":{},\"max_speed\":{},\"divergence_rms\":{},\"pressure_iterations\":{}}}\n",
+            frame.frame, num(frame.time as f64), num(frame.dt as f64), frame.substeps, opt(fps), opt(sim_ms), opt(render_ms),
+            num(mass[0]), num(mass[1]), num(mass[2]), num(energy), opt(compare_energy), num(max_speed), num(divergence_rms),
+            frame.pressure_iterations.map_or("null".to_string(), |n| n.to_string()),
+        );
+        match &self.sink {
+            Sink::Stdout => {
+                let mut out = io::stdout().lock();
+                if let Err(e) = out.write_all(line.as_bytes()).and_then(|()| out.flush()) {
+                    eprintln!("Failed to write frame stats: {}", e);
+                }
+            }
+            Sink::Clients(clients) => clients.lock().unwrap().retain_mut(|client| {
+                let sent = client.write_all(line.as_bytes()).is_ok();
+                if !sent {
+                    eprintln!("Stats client left");
+                }
+                sent
+            }),
+        }
+    }
+}
+
+impl PassTimer {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = TIMESTAMPS as u64 * 8;
+        Self {
+            queries: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("stats_timestamps"), ty: wgpu::QueryType::Timestamp, count: TIMESTAMPS,
+            }),
+            resolve: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("stats_resolve"), size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            staging: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("stats_staging"), size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period() as f64,
+        }
+    }
+
+    /// Milliseconds spent in the sim and render passes. A pass that didn't
+    /// run this frame leaves zeros, which come out as `None`.
+    fn read(&self, device: &wgpu::Device) -> [Option<f64>; 2] {
+        let slice = self.staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let ticks: [u64; TIMESTAMPS as usize] = bytemuck::pod_read_unaligned(&slice.get_mapped_range());
+        self.staging.unmap();
+        [0, 2].map(|i| {
+            let (begin, end) = (ticks[i], ticks[i + 1]);
+            (begin != 0 && end >= begin).then(|| (end - begin) as f64 * self.period / 1e6)
+        })
+    }
+}
+
+/// A JSON number, or `null` for the values JSON can't hold.
+fn num(v: f64) -> String {
+    if v.is_finite() { v.to_string() } else { "null".to_string() }
+}
+
+fn opt(v: Option<f64>) -> String {
+    v.map_or_else(|| "null".to_string(), num)
+}
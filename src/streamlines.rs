@@ -0,0 +1,215 @@
+//! Streamline overlay (Z, `--streamlines <SEEDS>`): lines tangent to the
+//! velocity field, traced every frame from a jittered grid of seeds by
+//! `trace` in `streamlines.wgsl` and drawn over the fluid as line
+//! primitives straight from the storage buffer the trace writes.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+/// Seeds per side when `--streamlines` doesn't say.
+pub const DEFAULT_SEEDS: u32 = 32;
+/// Segments traced downstream and upstream of each seed.
+const STEPS: u32 = 24;
+/// How many seed spacings a whole line spans when the flow lets it.
+const LINE_LENGTH: f32 = 3.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct StreamParams {
+    grid_size: u32,
+    seeds: u32,
+    steps: u32,
+    _pad0: u32,
+    cell_size: [f32; 2],
+    step_len: f32,
+    _pad1: f32,
+    viewport: [f32; 4],
+}
+
+/// Mirrors `Segment` in `streamlines.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Segment {
+    ends: [f32; 4],
+    speed: f32,
+    along: f32,
+    _pad: [f32; 2],
+}
+
+pub struct Streamlines {
+    /// The overlay is traced and drawn (Z).
+    pub showing: bool,
+    params: StreamParams,
+    param_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    trace: wgpu::ComputePipeline,
+    segments: wgpu::Buffer,
+    line_bg: wgpu::BindGroup,
+    line_pipeline: wgpu::RenderPipeline,
+}
+
+impl Streamlines {
+    /// `seeds` per side of the grid; `viewport` is the part of the domain
+    /// the window shows, as in `ViewParams`.
+    pub fn new(
+        device: &wgpu::Device, velocity_view: &wgpu::TextureView, obstacle_view: &wgpu::TextureView,
+        grid_size: u32, format: wgpu::TextureFormat, seeds: u32, viewport: [f32; 4],
+    ) -> Self {
+        let spacing = grid_size as f32 / seeds as f32;
+        let params = StreamParams {
+            grid_size, seeds, steps: STEPS, _pad0: 0,
+            cell_size: [1.0; 2],
+            step_len: LINE_LENGTH * spacing / (2 * STEPS) as f32,
+            _pad1: 0.0,
+            viewport,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("streamline_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // Both halves of every line, each `STEPS` segments
+        let segments = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("streamline_segments"),
+            size: (2 * seeds * seeds * STEPS) as u64 * std::mem::size_of::<Segment>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("streamline_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../streamlines.wgsl").into()),
+        });
+
+        // ---- Trace ----
+        let uniform = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false, min_binding_size: None,
+        };
+        let sampled_tex = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+        };
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("streamline_compute_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE, ty: uniform, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("streamline_bg"), layout: &compute_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(obstacle_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: segments.as_entire_binding() },
+            ],
+        });
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let trace = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("trace"), layout: Some(&compute_pl), module: &shader, entry_point: "trace",
+        });
+
+        // ---- Lines ----
+        let line_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("streamline_line_bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0, visibility: wgpu::ShaderStages::VERTEX, ty: uniform, count: None,
+            }],
+        });
+        let line_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("streamline_line_bg"), layout: &line_bgl,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() }],
+        });
+        let line_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&line_bgl], push_constant_ranges: &[],
+        });
+        // One two-vertex line per segment instance, blended over the fluid
+        let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fs_line"), layout: Some(&line_pl),
+            vertex: wgpu::VertexState {
+                module: &shader, entry_point: "vs_line",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Segment>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32, 2 => Float32],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader, entry_point: "fs_line",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format, blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            showing: false,
+            params, param_buffer, bind_group, trace, segments,
+            line_bg, line_pipeline,
+        }
+    }
+
+    /// Show or hide the overlay.
+    pub fn toggle(&mut self) {
+        self.showing = !self.showing;
+        eprintln!("Streamlines: {}", if self.showing {
+            format!("on ({}×{} seeds)", self.params.seeds, self.params.seeds)
+        } else {
+            "off".to_string()
+        });
+    }
+
+    /// Trace the lines through the current velocity, if showing.
+    pub fn record(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, cell_size: [f32; 2]) {
+        if !self.showing {
+            return;
+        }
+        if self.params.cell_size != cell_size {
+            self.params.cell_size = cell_size;
+            queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+        }
+        let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("streamlines"), timestamp_writes: None,
+        });
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.trace);
+        c.dispatch_workgroups((2 * self.params.seeds * self.params.seeds).div_ceil(64), 1, 1);
+    }
+
+    /// Draw the lines over whatever the pass drew before.
+    pub fn draw<'a>(&'a self, r: &mut wgpu::RenderPass<'a>) {
+        if !self.showing {
+            return;
+        }
+        r.set_pipeline(&self.line_pipeline);
+        r.set_bind_group(0, &self.line_bg, &[]);
+        r.set_vertex_buffer(0, self.segments.slice(..));
+        r.draw(0..2, 0..2 * self.params.seeds * self.params.seeds * STEPS);
+    }
+}
@@ -0,0 +1,167 @@
+//! Supersampling (`--supersample N`): the scene is drawn into a target N
+//! times the window's size on each side, in the scene's own format, and
+//! `supersample.wgsl` averages each N×N block of it into a pixel of the
+//! next target: the tonemapper's with `--tonemap`, the window otherwise.
+//! The grid is still bilinearly upsampled, but every pixel now sees N²
+//! samples of it, which smooths the stair steps on dye edges, obstacle
+//! outlines and sprites on large displays.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+/// Largest `--supersample` factor: 16 samples a pixel.
+pub const MAX_FACTOR: u32 = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DownsampleParams {
+    factor: u32,
+    dither: u32,
+    _pad: [u32; 2],
+}
+
+pub struct Supersample {
+    /// `--supersample`, and what fits the window now.
+    requested: u32,
+    factor: u32,
+    format: wgpu::TextureFormat,
+    /// `crate::dither_mode` of the next target, 0 with dithering off.
+    dither: u32,
+    param_buffer: wgpu::Buffer,
+    bgl: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    _target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+}
+
+impl Supersample {
+    /// `factor` is lowered, with a warning, until the target fits the GPU's
+    /// texture limit at this window size. `format` is the scene's, which is
+    /// also the next target's.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32, factor: u32, dither: bool) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("supersample_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../supersample.wgsl").into()),
+        });
+        let requested = factor;
+        let factor = Self::fit(device, width, height, requested);
+        let dither = dither as u32 * crate::dither_mode(format);
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("downsample_params"),
+            contents: bytemuck::bytes_of(&DownsampleParams { factor, dither, _pad: [0; 2] }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("supersample_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let pipeline = crate::create_render_pipeline(device, &pl, &module, format, "fs_downsample");
+        let (target, target_view) = Self::create_target(device, format, width * factor, height * factor);
+        let bind_group = Self::create_bind_group(device, &bgl, &target_view, &param_buffer);
+        eprintln!("Supersampling: {}×{} samples a pixel", factor, factor);
+        Self { requested, factor, format, dither, param_buffer, bgl, bind_group, pipeline, _target: target, target_view }
+    }
+
+    /// The largest factor up to `factor` whose target fits the GPU.
+    fn fit(device: &wgpu::Device, width: u32, height: u32, factor: u32) -> u32 {
+        let limit = device.limits().max_texture_dimension_2d;
+        let fitting = (1..=factor).rev().find(|f| width.max(height) * f <= limit).unwrap_or(1);
+        if fitting < factor {
+            eprintln!("Supersampling {}× would pass the GPU's {} pixel texture limit; using {}×", factor, limit, fitting);
+        }
+        fitting
+    }
+
+    fn create_target(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("supersample_target"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        (texture, view)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device, bgl: &wgpu::BindGroupLayout, target_view: &wgpu::TextureView, param_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("supersample_bg"), layout: bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(target_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: param_buffer.as_entire_binding() },
+            ],
+        })
+    }
+
+    /// Match a resized window, at the requested factor again if it fits.
+    pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) {
+        let factor = Self::fit(device, width, height, self.requested);
+        if factor != self.factor {
+            self.factor = factor;
+            self.write_params(queue);
+        }
+        let (target, target_view) = Self::create_target(device, self.format, width * factor, height * factor);
+        self.bind_group = Self::create_bind_group(device, &self.bgl, &target_view, &self.param_buffer);
+        self._target = target;
+        self.target_view = target_view;
+    }
+
+    /// Dither the averaged colors or not (Shift + D).
+    pub fn set_dither(&mut self, queue: &wgpu::Queue, on: bool) {
+        self.dither = on as u32 * crate::dither_mode(self.format);
+        self.write_params(queue);
+    }
+
+    fn write_params(&self, queue: &wgpu::Queue) {
+        let params = DownsampleParams { factor: self.factor, dither: self.dither, _pad: [0; 2] };
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    /// Where the scene is drawn.
+    pub fn target(&self) -> &wgpu::TextureView {
+        &self.target_view
+    }
+
+    /// Average the target down into `view`, replacing what's there.
+    pub fn apply(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("downsample"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.pipeline);
+        r.set_bind_group(0, &self.bind_group, &[]);
+        r.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,225 @@
+//! HDR rendering (`--tonemap <CURVE[:STOPS]>`): the scene is drawn into a
+//! window-sized `Rgba16Float` target, where `fs_draw` lets dense dye glow
+//! past 1, and `tonemap.wgsl` maps it into the window through a curve
+//! after an exposure in stops. U cycles the curve, Y / Shift + Y step the
+//! exposure.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+/// Format the scene is drawn in while tonemapping.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// Stops Y and Shift + Y move the exposure by.
+const STOP_STEP: f32 = 0.5;
+
+/// Values match `tone.curve` in `tonemap.wgsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    /// Clip at 1, as without `--tonemap`, but after the exposure.
+    Clamp = 0,
+    /// Extended Reinhard, reaching white at 4.
+    Reinhard = 1,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces = 2,
+}
+
+impl Curve {
+    const ALL: [Curve; 3] = [Curve::Clamp, Curve::Reinhard, Curve::Aces];
+
+    pub fn next(self) -> Self {
+        match self {
+            Curve::Clamp => Curve::Reinhard,
+            Curve::Reinhard => Curve::Aces,
+            Curve::Aces => Curve::Clamp,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Curve::Clamp => "clamp",
+            Curve::Reinhard => "reinhard",
+            Curve::Aces => "aces",
+        }
+    }
+}
+
+impl std::str::FromStr for Curve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("tonemap curve", s, &Self::ALL.map(|c| (c.name(), c)))
+    }
+}
+
+/// `--tonemap CURVE[:STOPS]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TonemapSpec {
+    pub curve: Curve,
+    /// Exposure in stops, 0 = as drawn.
+    pub stops: f32,
+}
+
+impl std::str::FromStr for TonemapSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (curve, stops) = match s.split_once(':') {
+            Some((curve, stops)) => {
+                let stops: f32 = stops.parse().map_err(|_| format!("bad tonemap exposure `{}` (expected stops, e.g. -0.5)", stops))?;
+                if !stops.is_finite() {
+                    return Err(format!("bad tonemap exposure `{}`", s));
+                }
+                (curve, stops)
+            }
+            None => (s, 0.0),
+        };
+        Ok(TonemapSpec { curve: curve.parse()?, stops })
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ToneParams {
+    curve: u32,
+    exposure: f32,
+    dither: u32,
+    _pad: f32,
+}
+
+pub struct Tonemap {
+    spec: TonemapSpec,
+    /// The window's, for `crate::dither_mode`.
+    format: wgpu::TextureFormat,
+    /// `crate::dither_mode` of the window, 0 with dithering off.
+    dither: u32,
+    param_buffer: wgpu::Buffer,
+    bgl: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    _target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+}
+
+impl Tonemap {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32, spec: TonemapSpec, dither: bool) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("tonemap_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../tonemap.wgsl").into()),
+        });
+        let dither = dither as u32 * crate::dither_mode(format);
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("tonemap_params"),
+            contents: bytemuck::bytes_of(&Self::params(spec, dither)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tonemap_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let pipeline = crate::create_render_pipeline(device, &pl, &module, format, "fs_tonemap");
+        let (target, target_view) = Self::create_target(device, width, height);
+        let bind_group = Self::create_bind_group(device, &bgl, &target_view, &param_buffer);
+        eprintln!("Tonemapping: {} at {:+} stops (U cycles the curve, Y / Shift + Y step the exposure)", spec.curve.name(), spec.stops);
+        Self { spec, format, dither, param_buffer, bgl, bind_group, pipeline, _target: target, target_view }
+    }
+
+    fn params(spec: TonemapSpec, dither: u32) -> ToneParams {
+        ToneParams { curve: spec.curve as u32, exposure: spec.stops.exp2(), dither, _pad: 0.0 }
+    }
+
+    fn create_target(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("hdr_target"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        (texture, view)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device, bgl: &wgpu::BindGroupLayout, target_view: &wgpu::TextureView, param_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tonemap_bg"), layout: bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(target_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: param_buffer.as_entire_binding() },
+            ],
+        })
+    }
+
+    /// Match a resized window.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let (target, target_view) = Self::create_target(device, width, height);
+        self.bind_group = Self::create_bind_group(device, &self.bgl, &target_view, &self.param_buffer);
+        self._target = target;
+        self.target_view = target_view;
+    }
+
+    /// Where the scene is drawn, in `HDR_FORMAT`.
+    pub fn target(&self) -> &wgpu::TextureView {
+        &self.target_view
+    }
+
+    /// Next curve (U).
+    pub fn cycle(&mut self, queue: &wgpu::Queue) {
+        self.spec.curve = self.spec.curve.next();
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&Self::params(self.spec, self.dither)));
+        eprintln!("Tonemap curve: {}", self.spec.curve.name());
+    }
+
+    /// Half a stop brighter, or darker (Y / Shift + Y).
+    pub fn step_exposure(&mut self, queue: &wgpu::Queue, brighter: bool) {
+        self.spec.stops += if brighter { STOP_STEP } else { -STOP_STEP };
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&Self::params(self.spec, self.dither)));
+        eprintln!("Tonemap exposure: {:+} stops", self.spec.stops);
+    }
+
+    /// Dither the mapped colors or not (Shift + D).
+    pub fn set_dither(&mut self, queue: &wgpu::Queue, on: bool) {
+        self.dither = on as u32 * crate::dither_mode(self.format);
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&Self::params(self.spec, self.dither)));
+    }
+
+    /// Map the target into `view`, replacing what's there.
+    pub fn apply(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("tonemap"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.pipeline);
+        r.set_bind_group(0, &self.bind_group, &[]);
+        r.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,311 @@
+//! In-app parameter search (O). The live flow is captured and short trials
+//! are run from it on an offscreen copy of the simulation, each with a few
+//! parameters varied and scored by one metric. When the search ends, the
+//! best set is applied to the live simulation.
+//!
+//! The search is a coordinate search. It visits each parameter in turn,
+//! halves and doubles it, and keeps whichever value scores best. It repeats
+//! this for `ROUNDS` rounds. A parameter at 0 tries its `seed` value instead.
+//! One trial runs per frame, so the window stays responsive, if slow,
+//! during the search.
+
+use crate::simulation::{FluidPipelines, Reference, Shared, Simulation};
+use crate::SimParams;
+use std::fmt;
+
+/// Passes over the parameter list.
+const ROUNDS: usize = 2;
+
+/// What a trial is scored by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    /// Kinetic energy left at the end of the trial (higher is better).
+    Energy,
+    /// RMS divergence of the final, projected velocity (lower is better).
+    Divergence,
+    /// Simulation steps per second, GPU wait included (higher is better).
+    Fps,
+}
+
+impl std::str::FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("tuning metric", s, &[("energy", Metric::Energy), ("divergence", Metric::Divergence), ("fps", Metric::Fps)])
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Metric::Energy => "energy",
+            Metric::Divergence => "divergence",
+            Metric::Fps => "fps",
+        })
+    }
+}
+
+/// A `SimParams` value the search may vary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+    Vorticity,
+    Viscosity,
+    Diffusion,
+    SmokeWeight,
+    HeatLift,
+}
+
+impl Param {
+    const ALL: [Param; 5] = [Param::Vorticity, Param::Viscosity, Param::Diffusion, Param::SmokeWeight, Param::HeatLift];
+
+    fn name(self) -> &'static str {
+        match self {
+            Param::Vorticity => "vorticity",
+            Param::Viscosity => "viscosity",
+            Param::Diffusion => "diffusion",
+            Param::SmokeWeight => "smoke-weight",
+            Param::HeatLift => "heat-lift",
+        }
+    }
+
+    fn get(self, params: &SimParams) -> f32 {
+        match self {
+            Param::Vorticity => params.vorticity,
+            Param::Viscosity => params.viscosity,
+            Param::Diffusion => params.diffusion,
+            Param::SmokeWeight => params.smoke_weight,
+            Param::HeatLift => params.heat_lift,
+        }
+    }
+
+    fn set(self, params: &mut SimParams, value: f32) {
+        match self {
+            Param::Vorticity => params.vorticity = value,
+            Param::Viscosity => params.viscosity = value,
+            Param::Diffusion => params.diffusion = value,
+            Param::SmokeWeight => params.smoke_weight = value,
+            Param::HeatLift => params.heat_lift = value,
+        }
+    }
+
+    /// Tried when the current value is 0, where halving and doubling stay
+    /// put.
+    fn seed(self) -> f32 {
+        match self {
+            Param::Vorticity => 5.0,
+            Param::Viscosity => 1.0,
+            Param::Diffusion => 0.5,
+            Param::SmokeWeight => 2.0,
+            Param::HeatLift => 20.0,
+        }
+    }
+}
+
+/// What the search optimizes (`--tune`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuneSpec {
+    pub metric: Metric,
+    pub params: Vec<Param>,
+    /// Simulation steps per trial.
+    pub steps: u32,
+}
+
+impl Default for TuneSpec {
+    fn default() -> Self {
+        Self { metric: Metric::Energy, params: vec![Param::Vorticity, Param::Viscosity], steps: 120 }
+    }
+}
+
+impl std::str::FromStr for TuneSpec {
+    type Err = String;
+
+    /// `METRIC`, `METRIC:PARAM,...` or `METRIC:PARAM,...:STEPS`, e.g.
+    /// `divergence:vorticity,viscosity:240`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut parts = s.split(':');
+        let mut spec = TuneSpec { metric: parts.next().unwrap_or_default().parse()?, ..TuneSpec::default() };
+        if let Some(list) = parts.next() {
+            spec.params = list
+                .split(',')
+                .map(|name| {
+                    crate::config::parse_choice("tuning parameter", name.trim(), &Param::ALL.map(|p| (p.name(), p)))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(steps) = parts.next() {
+            spec.steps = steps.parse().ok().filter(|n| *n > 0).ok_or_else(|| format!("bad step count in `{}`", s))?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("too many fields in `{}` (expected METRIC[:PARAMS[:STEPS]])", s));
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for TuneSpec {
+    /// The same syntax `from_str` accepts.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = self.params.iter().map(|p| p.name()).collect();
+        write!(f, "{}:{}:{}", self.metric, names.join(","), self.steps)
+    }
+}
+
+/// A search in progress. Owns the trial copy and its own parameter
+/// uniform, so trials never disturb the live simulation.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Tuner {
+    spec: TuneSpec,
+    trial: Simulation,
+    params: wgpu::Buffer,
+    /// The live flow when the search started; every trial starts from it.
+    start: Reference,
+    /// Kinetic energy of `start`, to report energy as a fraction kept.
+    start_energy: f64,
+    /// The parameters the search started from, and their score.
+    initial: SimParams,
+    initial_score: Option<f64>,
+    /// Best parameters so far and their score (higher is better).
+    best: SimParams,
+    best_score: f64,
+    /// Candidates for the current parameter still to try.
+    pending: Vec<SimParams>,
+    /// Parameters visited so far, across rounds.
+    visited: usize,
+    trials: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Tuner {
+    /// Capture `live` and queue a baseline trial with `params`. `shared` is
+    /// the live simulation's; the trial copy gets its own uniform.
+    pub fn new(shared: &Shared, module: &wgpu::ShaderModule, queue: &wgpu::Queue, live: &Simulation, params: &SimParams, spec: TuneSpec) -> Self {
+        let device = shared.device;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tune_params"),
+            size: std::mem::size_of::<SimParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut trial = Simulation::new(&Shared { params: &buffer, ..*shared }, module, live.solver_kind);
+        trial.advection = live.advection;
+        trial.set_domain(queue, params);
+        let mut start = Reference::new(device, shared.grid_size);
+        start.capture(device, queue, live);
+        let start_energy = kinetic_energy(&crate::read_field(device, queue, &start.velocity, shared.grid_size));
+
+        // Trials run without the mouse or the heat map
+        let initial = SimParams { mouse_down: 0, mouse_delta: [0.0, 0.0], instrument: 0, ..*params };
+        eprintln!("Tuning {} (O cancels)", spec);
+        Self {
+            spec, trial, params: buffer, start, start_energy,
+            initial, initial_score: None, best: initial, best_score: f64::NEG_INFINITY,
+            pending: vec![initial], visited: 0, trials: 0,
+        }
+    }
+
+    /// Run the next trial, blocking until it's done. Returns the best
+    /// parameters once the search is over.
+    pub fn run_trial(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, pipes: &FluidPipelines, grid: u32) -> Option<SimParams> {
+        let Some(candidate) = self.pending.pop() else {
+            return Some(self.best);
+        };
+        self.trial.load(device, queue, &self.start);
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&candidate));
+
+        let started = std::time::Instant::now();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("tune") });
+        {
+            let mut c = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("tune"), timestamp_writes: None });
+            for _ in 0..self.spec.steps {
+                self.trial.step(&mut c, pipes, &candidate);
+            }
+            if self.spec.metric == Metric::Divergence {
+                self.trial.measure_divergence(&mut c, pipes);
+            }
+        }
+        queue.submit(Some(encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+        let secs = started.elapsed().as_secs_f64();
+
+        let score = match self.spec.metric {
+            Metric::Energy => kinetic_energy(&crate::read_field(device, queue, &self.trial.velocity, grid)),
+            Metric::Divergence => -rms(&crate::read_field(device, queue, &self.trial.divergence, grid)),
+            Metric::Fps => self.spec.steps as f64 / secs.max(1e-9),
+        };
+        self.trials += 1;
+        eprintln!("  trial {}: {} -> {}", self.trials, self.describe_params(&candidate), self.describe_score(score));
+        self.initial_score.get_or_insert(score);
+        if score > self.best_score {
+            self.best = candidate;
+            self.best_score = score;
+        }
+
+        while self.pending.is_empty() {
+            if self.visited == ROUNDS * self.spec.params.len() {
+                self.report();
+                return Some(self.best);
+            }
+            let param = self.spec.params[self.visited % self.spec.params.len()];
+            self.visited += 1;
+            let current = param.get(&self.best);
+            let values = if current == 0.0 { vec![param.seed()] } else { vec![current * 0.5, current * 2.0] };
+            self.pending = values
+                .into_iter()
+                .map(|v| {
+                    let mut p = self.best;
+                    param.set(&mut p, v);
+                    p
+                })
+                .collect();
+        }
+        None
+    }
+
+    /// Copy the tuned values in `best` into `params`, leaving the rest alone.
+    pub fn apply(&self, best: &SimParams, params: &mut SimParams) {
+        for p in &self.spec.params {
+            p.set(params, p.get(best));
+        }
+    }
+
+    fn report(&self) {
+        let initial = self.describe_score(self.initial_score.unwrap_or(self.best_score));
+        if self.best_score > self.initial_score.unwrap_or(f64::NEG_INFINITY) {
+            let changes: Vec<_> = self
+                .spec
+                .params
+                .iter()
+                .map(|p| format!("{} {} -> {}", p.name(), p.get(&self.initial), p.get(&self.best)))
+                .collect();
+            eprintln!("Tuned after {} trials: {} ({} -> {})", self.trials, changes.join(", "), initial, self.describe_score(self.best_score));
+        } else {
+            eprintln!("Tuning kept the current parameters after {} trials ({})", self.trials, initial);
+        }
+    }
+
+    fn describe_params(&self, params: &SimParams) -> String {
+        let values: Vec<_> = self.spec.params.iter().map(|p| format!("{} {}", p.name(), p.get(params))).collect();
+        values.join(", ")
+    }
+
+    fn describe_score(&self, score: f64) -> String {
+        match self.spec.metric {
+            Metric::Energy if self.start_energy > 0.0 => format!("{:.1}% energy kept", 100.0 * score / self.start_energy),
+            Metric::Energy => format!("energy {:.3e}", score),
+            Metric::Divergence => format!("divergence RMS {:.3e}/s", -score),
+            Metric::Fps => format!("{:.0} steps/s", score),
+        }
+    }
+}
+
+/// Sum of ½|v|² over the grid.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn kinetic_energy(velocity: &[[f32; 4]]) -> f64 {
+    velocity.iter().map(|v| 0.5 * (v[0] as f64).powi(2) + 0.5 * (v[1] as f64).powi(2)).sum()
+}
+
+/// Root mean square of the first channel.
+#[cfg(not(target_arch = "wasm32"))]
+fn rms(field: &[[f32; 4]]) -> f64 {
+    (field.iter().map(|t| (t[0] as f64).powi(2)).sum::<f64>() / field.len().max(1) as f64).sqrt()
+}
@@ -0,0 +1,471 @@
+//! Two-phase mode (`--two-phase SCENE`): two immiscible fluids with their
+//! own density and viscosity share the box. Which one fills a cell is the
+//! advected volume fraction of the first fluid, kept sharp with a limited
+//! MacCormack step, and the projection weights the pressure gradient by
+//! 1/ρ, so under gravity the heavy fluid sinks through the light one. The
+//! kernels are in `two_phase.wgsl`.
+
+use std::sync::Arc;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    event::MouseButton,
+    event_loop::EventLoop,
+    keyboard::KeyCode,
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::create_storage_tex;
+use crate::mode::{self, Mode};
+use crate::rng::Seed;
+use crate::SimParams;
+
+/// Steps per frame, each with its own projection.
+const SUBSTEPS: u32 = 2;
+/// Jacobi sweeps per step. Even, so the pressure ends up back in the first
+/// texture, the one `project` and the next step's warm start read.
+const PRESSURE_ITERATIONS: u32 = 40;
+/// Gravity when `--gravity` leaves it off, in cells/s² down.
+const DEFAULT_GRAVITY: f32 = 200.0;
+
+/// The starting arrangement of the two fluids (`--two-phase`, 1 / 2).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TwoPhaseScene {
+    /// A heavy layer resting on a light one, its underside rippled so the
+    /// instability has something to grow from.
+    RayleighTaylor,
+    /// A blob of viscous oil let go at the bottom of a tank of water.
+    OilWater,
+}
+
+impl TwoPhaseScene {
+    const ALL: [TwoPhaseScene; 2] = [TwoPhaseScene::RayleighTaylor, TwoPhaseScene::OilWater];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TwoPhaseScene::RayleighTaylor => "rayleigh-taylor",
+            TwoPhaseScene::OilWater => "oil-water",
+        }
+    }
+
+    /// The first and second fluid, unless `--fluids` says otherwise.
+    fn fluids(self) -> [Phase; 2] {
+        match self {
+            TwoPhaseScene::RayleighTaylor => [
+                Phase { density: 3.0, viscosity: 0.5 },
+                Phase { density: 1.0, viscosity: 0.5 },
+            ],
+            TwoPhaseScene::OilWater => [
+                Phase { density: 1.0, viscosity: 0.5 },
+                Phase { density: 0.8, viscosity: 8.0 },
+            ],
+        }
+    }
+
+    /// The first fluid's fraction in each cell, as state texels at rest.
+    fn layout(self, size: u32, seed: Seed) -> Vec<[f32; 4]> {
+        let n = size as f32;
+        let fraction: Box<dyn Fn(f32, f32) -> f32> = match self {
+            TwoPhaseScene::RayleighTaylor => {
+                // A few long cosines with random phases, a cell or two high
+                let mut rng = seed.rng("rayleigh-taylor");
+                let modes: Vec<(f32, f32)> = (1..=6)
+                    .map(|k| (k as f32, rng.next_f32() * std::f32::consts::TAU))
+                    .collect();
+                Box::new(move |x, y| {
+                    let ripple: f32 = modes.iter()
+                        .map(|(k, phase)| (k * std::f32::consts::TAU * x / n + phase).cos() / k)
+                        .sum();
+                    n * 0.5 + 1.5 * ripple - y + 0.5
+                })
+            }
+            TwoPhaseScene::OilWater => {
+                let (center, radius) = ([n * 0.5, n * 0.75], n * 0.15);
+                Box::new(move |x, y| (x - center[0]).hypot(y - center[1]) - radius + 0.5)
+            }
+        };
+        (0..size * size).map(|i| {
+            let (x, y) = ((i % size) as f32 + 0.5, (i / size) as f32 + 0.5);
+            [0.0, 0.0, fraction(x, y).clamp(0.0, 1.0), 0.0]
+        }).collect()
+    }
+}
+
+impl std::str::FromStr for TwoPhaseScene {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("two-phase scene", s, &Self::ALL.map(|m| (m.name(), m)))
+    }
+}
+
+/// One fluid's density and kinematic viscosity in cells²/s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Phase {
+    pub density: f32,
+    pub viscosity: f32,
+}
+
+impl Phase {
+    /// `--fluids`: `D1[:V1],D2[:V2]`, the first fluid then the second. A
+    /// left-out viscosity is 0.5.
+    pub fn parse_pair(s: &str) -> Result<[Phase; 2], String> {
+        let (first, second) = s.split_once(',')
+            .ok_or_else(|| format!("--fluids expects D1[:V1],D2[:V2], got `{}`", s))?;
+        Ok([first.parse()?, second.parse()?])
+    }
+}
+
+impl std::str::FromStr for Phase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (density, viscosity) = s.split_once(':').unwrap_or((s, "0.5"));
+        let parse = |v: &str| v.trim().parse::<f32>().map_err(|_| format!("--fluids: can't parse `{}`", v));
+        let phase = Phase { density: parse(density)?, viscosity: parse(viscosity)? };
+        if !(phase.density > 0.0 && phase.density.is_finite()) {
+            return Err(format!("--fluids: density must be positive, got {}", phase.density));
+        }
+        if !(phase.viscosity >= 0.0 && phase.viscosity.is_finite()) {
+            return Err(format!("--fluids: viscosity can't be negative, got {}", phase.viscosity));
+        }
+        Ok(phase)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PhaseParams {
+    size: u32,
+    dt: f32,
+    /// First fluid's, then the second's.
+    density: [f32; 2],
+    viscosity: [f32; 2],
+    gravity: [f32; 2],
+    /// Brush center, and how far it moves per step, in cells.
+    mouse_pos: [f32; 2],
+    mouse_delta: [f32; 2],
+    radius: f32,
+    mouse_down: u32,
+    /// Surface tension coefficient in cells³/s².
+    surface_tension: f32,
+    _pad: f32,
+}
+
+/// The state and its scratch textures, the step kernels and the renderer.
+struct TwoPhase {
+    params: PhaseParams,
+    param_buffer: wgpu::Buffer,
+    state: wgpu::Texture,
+    pressure: [wgpu::Texture; 2],
+    _views: Vec<wgpu::TextureView>,
+    /// [0] reads the first pressure texture and writes the second, [1] the
+    /// other way round. Everything but the Jacobi sweeps uses [0].
+    step_bgs: [wgpu::BindGroup; 2],
+    advect: wgpu::ComputePipeline,
+    reverse: wgpu::ComputePipeline,
+    divergence: wgpu::ComputePipeline,
+    jacobi: wgpu::ComputePipeline,
+    project: wgpu::ComputePipeline,
+    render_bg: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+    /// The Rayleigh–Taylor ripples are drawn from this on every reset.
+    seed: Seed,
+}
+
+impl TwoPhase {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, size: u32, dt: f32, gravity: [f32; 2], surface_tension: f32, seed: Seed) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("two_phase_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../two_phase.wgsl").into()),
+        });
+
+        let params = PhaseParams {
+            size, dt: dt / SUBSTEPS as f32, density: [1.0; 2], viscosity: [0.0; 2], gravity,
+            mouse_pos: [0.0; 2], mouse_delta: [0.0; 2], radius: size as f32 * 0.04, mouse_down: 0,
+            surface_tension, _pad: 0.0,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("two_phase_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let (state, state_view) = create_storage_tex(device, size);
+        let (_advected, advected_view) = create_storage_tex(device, size);
+        let (_scratch, scratch_view) = create_storage_tex(device, size);
+        let (_divergence, divergence_view) = create_storage_tex(device, size);
+        let pressure_tex = || {
+            let tex = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("two_phase_pressure"),
+                size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R32Float,
+                usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+            (tex, view)
+        };
+        let (pressure_a, pressure_a_view) = pressure_tex();
+        let (pressure_b, pressure_b_view) = pressure_tex();
+
+        // ---- Compute ----
+        let storage_tex = |format| wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::ReadWrite,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false, min_binding_size: None,
+            },
+            count: None,
+        }];
+        entries.extend((1..=6).map(|binding| wgpu::BindGroupLayoutEntry {
+            binding, visibility: wgpu::ShaderStages::COMPUTE,
+            ty: storage_tex(if binding <= 4 { wgpu::TextureFormat::Rgba16Float } else { wgpu::TextureFormat::R32Float }),
+            count: None,
+        }));
+        let step_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("two_phase_step_bgl"), entries: &entries,
+        });
+        let step_bg = |src: &wgpu::TextureView, dst: &wgpu::TextureView| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("two_phase_step_bg"), layout: &step_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&state_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&advected_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&scratch_view) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&divergence_view) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(src) },
+                wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(dst) },
+            ],
+        });
+        let step_bgs = [step_bg(&pressure_a_view, &pressure_b_view), step_bg(&pressure_b_view, &pressure_a_view)];
+        let step_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&step_bgl], push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point), layout: Some(&step_pl), module: &module, entry_point,
+        });
+
+        // ---- Render ----
+        let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("two_phase_render_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8, visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("two_phase_render_bg"), layout: &render_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(&state_view) },
+                wgpu::BindGroupEntry { binding: 8, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+        let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+        });
+        let render_pipeline = crate::create_render_pipeline(device, &render_pl, &module, format, "fs_two_phase");
+
+        Self {
+            params, param_buffer, state, pressure: [pressure_a, pressure_b],
+            _views: vec![state_view, advected_view, scratch_view, divergence_view, pressure_a_view, pressure_b_view],
+            advect: pipeline("advect"), reverse: pipeline("reverse"), divergence: pipeline("compute_divergence"),
+            jacobi: pipeline("jacobi"), project: pipeline("project"),
+            step_bgs, render_bg, render_pipeline, seed,
+        }
+    }
+
+    /// `SUBSTEPS` steps: advect, correct the fraction, project.
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        let groups = self.params.size.div_ceil(8);
+        for _ in 0..SUBSTEPS {
+            c.set_bind_group(0, &self.step_bgs[0], &[]);
+            for pipeline in [&self.advect, &self.reverse, &self.divergence] {
+                c.set_pipeline(pipeline);
+                c.dispatch_workgroups(groups, groups, 1);
+            }
+            c.set_pipeline(&self.jacobi);
+            for i in 0..PRESSURE_ITERATIONS as usize {
+                c.set_bind_group(0, &self.step_bgs[i % 2], &[]);
+                c.dispatch_workgroups(groups, groups, 1);
+            }
+            c.set_bind_group(0, &self.step_bgs[0], &[]);
+            c.set_pipeline(&self.project);
+            c.dispatch_workgroups(groups, groups, 1);
+        }
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("two_phase_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.render_pipeline);
+        r.set_bind_group(0, &self.render_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+
+    /// Lay the scene's fluids out at rest, with no pressure left over from
+    /// before.
+    fn reset(&mut self, queue: &wgpu::Queue, scene: TwoPhaseScene, fluids: [Phase; 2]) {
+        let size = self.params.size;
+        self.params.density = fluids.map(|f| f.density);
+        self.params.viscosity = fluids.map(|f| f.viscosity);
+        crate::upload_field(queue, &self.state, size, &scene.layout(size, self.seed));
+        let zeros = vec![0u8; (size * size * 4) as usize];
+        for tex in &self.pressure {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: tex, mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All,
+                },
+                &zeros,
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(size * 4), rows_per_image: Some(size) },
+                wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            );
+        }
+    }
+}
+
+/// The two-phase window loop, taking over from `run` once the window and
+/// device exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let size = config.grid_size;
+    let gpu = mode::Gpu::new(&config, window, surface, adapter, device, queue);
+    let (device, queue, format) = (&gpu.device, &gpu.queue, gpu.format());
+    let win_size = gpu.window.inner_size();
+
+    // Only the gravity of these is used, so the arrow and G keys work as
+    // they do everywhere else
+    let sim_params = SimParams {
+        gravity: if config.gravity == [0.0, 0.0] { [0.0, DEFAULT_GRAVITY] } else { config.gravity },
+        gravity_by_dye: 0,
+        ..crate::base_params(&config, crate::cell_size_for(win_size))
+    };
+    let scene = config.two_phase.unwrap_or(TwoPhaseScene::RayleighTaylor);
+    let fluid = TwoPhase::new(device, format, size, sim_params.dt, sim_params.gravity, config.surface_tension, config.seed);
+    let mut state = TwoPhaseMode {
+        fluid, scene, fluids: config.fluids, stored_gravity: sim_params.gravity, sim_params,
+        cursor: [0.0; 2], last_cursor: None,
+    };
+    state.reset(queue);
+    eprintln!("Two-phase mode: {} on a {}² grid", scene.name(), size);
+
+    mode::run(gpu, event_loop, size, state).await;
+}
+
+/// The two-phase window's state between frames.
+struct TwoPhaseMode {
+    fluid: TwoPhase,
+    scene: TwoPhaseScene,
+    /// `--fluids`, over each scene's own.
+    fluids: Option<[Phase; 2]>,
+    sim_params: SimParams,
+    cursor: [f32; 2],
+    last_cursor: Option<[f32; 2]>,
+    stored_gravity: [f32; 2],
+}
+
+impl TwoPhaseMode {
+    fn reset(&mut self, queue: &wgpu::Queue) {
+        let fluids = self.fluids.unwrap_or_else(|| self.scene.fluids());
+        self.fluid.reset(queue, self.scene, fluids);
+    }
+}
+
+impl Mode for TwoPhaseMode {
+    const STEP: &'static str = "two_phase_step";
+
+    fn key(&mut self, code: KeyCode, queue: &wgpu::Queue) {
+        match code {
+            KeyCode::KeyR => {
+                self.reset(queue);
+                eprintln!("Scene reset");
+            }
+            KeyCode::Digit1 | KeyCode::Digit2 => {
+                self.scene = TwoPhaseScene::ALL[(code == KeyCode::Digit2) as usize];
+                self.reset(queue);
+                eprintln!("Scene: {}", self.scene.name());
+            }
+            KeyCode::KeyG | KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::ArrowLeft | KeyCode::ArrowRight
+            | KeyCode::Minus | KeyCode::Equal => {
+                crate::adjust_gravity(code, false, &mut self.sim_params, &mut self.stored_gravity);
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse(&mut self, button: MouseButton, pressed: bool) {
+        if button == MouseButton::Left {
+            self.fluid.params.mouse_down = pressed as u32;
+            self.last_cursor = None;
+        }
+    }
+
+    fn cursor(&mut self, cursor: [f32; 2]) {
+        self.cursor = cursor;
+    }
+
+    fn prepare(&mut self, queue: &wgpu::Queue, _aspect: f32, _paused: bool) {
+        let cursor = self.cursor;
+        let p = &mut self.fluid.params;
+        p.gravity = self.sim_params.gravity;
+        p.mouse_delta = self.last_cursor.map_or([0.0; 2], |last| {
+            [(cursor[0] - last[0]) / SUBSTEPS as f32, (cursor[1] - last[1]) / SUBSTEPS as f32]
+        });
+        p.mouse_pos = cursor;
+        if p.mouse_down != 0 {
+            self.last_cursor = Some(cursor);
+        }
+        queue.write_buffer(&self.fluid.param_buffer, 0, bytemuck::bytes_of(&self.fluid.params));
+    }
+
+    fn step<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        self.fluid.step(c);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.fluid.render(encoder, view);
+    }
+}
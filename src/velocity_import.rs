@@ -0,0 +1,412 @@
+//! External velocity fields (`--velocity-field`, F8): a 2D velocity worked
+//! out offline or by another solver, read from OpenEXR or numpy and
+//! resampled onto the grid, so the dye can be carried along it. With
+//! `--freeze-velocity` the field is put back every step and nothing else
+//! moves it, which leaves this crate a pure advector and visualizer.
+//!
+//! The conventions are those F7 writes (see `field_export`): rows top to
+//! bottom, velocities in the file's own cells per second, +y down. So a
+//! field exported at one grid size imports at any other unchanged.
+//!
+//! EXR files are single-part scanline images, uncompressed or with RLE,
+//! ZIPS or ZIP compression, whose `velocity.X` / `velocity.Y`, `X` / `Y` or
+//! `R` / `G` channels are HALF or FLOAT. Numpy arrays are shaped
+//! (rows, columns, 2 or more) of little-endian f2, f4 or f8 in C order.
+
+/// A field as stored, `width` × `height` cells.
+struct Field {
+    width: usize,
+    height: usize,
+    velocity: Vec<[f32; 2]>,
+}
+
+/// Read a `.exr` or `.npy` file, told apart by its signature, and resample
+/// it to `grid`² cells.
+pub fn load(path: &str, grid: u32) -> Result<Vec<[f32; 2]>, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let field = if bytes.starts_with(&[0x76, 0x2f, 0x31, 0x01]) {
+        exr(&bytes)?
+    } else if bytes.starts_with(b"\x93NUMPY") {
+        npy(&bytes)?
+    } else {
+        return Err("not an OpenEXR or numpy file".to_string());
+    };
+    if field.velocity.iter().any(|v| !v[0].is_finite() || !v[1].is_finite()) {
+        return Err("velocity has NaN or infinite values".to_string());
+    }
+    Ok(resample(&field, grid as usize))
+}
+
+/// Bilinear between cell centers, with each component scaled from the
+/// file's cells to the grid's.
+fn resample(field: &Field, n: usize) -> Vec<[f32; 2]> {
+    let (w, h) = (field.width, field.height);
+    let scale = [n as f32 / w as f32, n as f32 / h as f32];
+    let at = |x: usize, y: usize| field.velocity[y * w + x];
+    let source = |i: usize, size: usize| {
+        let s = ((i as f32 + 0.5) * size as f32 / n as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+        let i0 = s as usize;
+        (i0, (i0 + 1).min(size - 1), s - i0 as f32)
+    };
+    let mut out = Vec::with_capacity(n * n);
+    for y in 0..n {
+        let (y0, y1, fy) = source(y, h);
+        for x in 0..n {
+            let (x0, x1, fx) = source(x, w);
+            out.push(std::array::from_fn(|c| {
+                let top = at(x0, y0)[c] * (1.0 - fx) + at(x1, y0)[c] * fx;
+                let bottom = at(x0, y1)[c] * (1.0 - fx) + at(x1, y1)[c] * fx;
+                (top * (1.0 - fy) + bottom * fy) * scale[c]
+            }));
+        }
+    }
+    out
+}
+
+fn npy(bytes: &[u8]) -> Result<Field, String> {
+    let (header_len, start) = match bytes.get(6) {
+        Some(1) if bytes.len() >= 10 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        Some(2 | 3) if bytes.len() >= 12 => (u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize, 12),
+        _ => return Err("unsupported numpy file version".to_string()),
+    };
+    let header = bytes.get(start..start + header_len).ok_or("numpy header is truncated")?;
+    let header = std::str::from_utf8(header).map_err(|_| "numpy header is corrupt")?;
+    let value = |key: &str| {
+        let at = header.find(&format!("'{}':", key)).ok_or_else(|| format!("numpy header has no '{}'", key))?;
+        Ok::<_, String>(header[at + key.len() + 3..].trim_start())
+    };
+
+    let descr = value("descr")?;
+    let descr = descr.get(1..4).filter(|_| descr.starts_with('\'')).ok_or("numpy header is corrupt")?;
+    let size = match descr {
+        "<f2" => 2,
+        "<f4" => 4,
+        "<f8" => 8,
+        _ => return Err(format!("numpy dtype {} isn't a little-endian float", descr)),
+    };
+    if value("fortran_order")?.starts_with("True") {
+        return Err("numpy array is in Fortran order; save it with np.ascontiguousarray".to_string());
+    }
+    let shape = value("shape")?;
+    let shape = shape.strip_prefix('(').and_then(|s| s.split(')').next()).ok_or("numpy header is corrupt")?;
+    let shape: Vec<usize> = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| d.parse().map_err(|_| "numpy header is corrupt".to_string()))
+        .collect::<Result<_, _>>()?;
+    let [height, width, components] = shape[..] else {
+        return Err(format!("numpy array is shaped {:?}; expected (rows, columns, 2)", shape));
+    };
+    if width == 0 || height == 0 || components < 2 {
+        return Err(format!("numpy array is shaped {:?}; expected (rows, columns, 2)", shape));
+    }
+
+    let data = &bytes[start + header_len..];
+    if data.len() < width * height * components * size {
+        return Err("numpy data is truncated".to_string());
+    }
+    let read = |i: usize| {
+        let b = &data[i * size..(i + 1) * size];
+        match size {
+            2 => crate::f16_to_f32(u16::from_le_bytes([b[0], b[1]])),
+            4 => f32::from_le_bytes(b.try_into().unwrap()),
+            _ => f64::from_le_bytes(b.try_into().unwrap()) as f32,
+        }
+    };
+    let velocity = (0..width * height).map(|i| [read(i * components), read(i * components + 1)]).collect();
+    Ok(Field { width, height, velocity })
+}
+
+/// One entry of an EXR channel list.
+struct Channel {
+    /// Empty for UINT channels, which are skipped.
+    name: String,
+    /// Bytes per sample: 2 for HALF, 4 for FLOAT and UINT.
+    size: usize,
+    half: bool,
+}
+
+fn exr(bytes: &[u8]) -> Result<Field, String> {
+    let truncated = || "EXR file is truncated".to_string();
+    let flags = u32::from_le_bytes(bytes.get(4..8).ok_or_else(truncated)?.try_into().unwrap());
+    if flags & 0x200 != 0 {
+        return Err("tiled EXR files aren't supported; save it as scanlines".to_string());
+    }
+    if flags & 0x1800 != 0 {
+        return Err("multi-part and deep EXR files aren't supported".to_string());
+    }
+
+    let mut pos = 8;
+    let string = |pos: &mut usize| {
+        let end = bytes.get(*pos..).and_then(|rest| rest.iter().position(|&b| b == 0)).ok_or_else(truncated)?;
+        let s = String::from_utf8_lossy(&bytes[*pos..*pos + end]).into_owned();
+        *pos += end + 1;
+        Ok::<_, String>(s)
+    };
+    let mut channels = Vec::new();
+    let mut compression = 0;
+    let mut window = None;
+    loop {
+        let name = string(&mut pos)?;
+        if name.is_empty() {
+            break;
+        }
+        let _kind = string(&mut pos)?;
+        let len = i32::from_le_bytes(bytes.get(pos..pos + 4).ok_or_else(truncated)?.try_into().unwrap()) as usize;
+        let value = bytes.get(pos + 4..pos + 4 + len).ok_or_else(truncated)?;
+        pos += 4 + len;
+        match name.as_str() {
+            "channels" => {
+                let mut at = 0;
+                while value.get(at).is_some_and(|&b| b != 0) {
+                    let end = value[at..].iter().position(|&b| b == 0).ok_or_else(truncated)?;
+                    let name = String::from_utf8_lossy(&value[at..at + end]).into_owned();
+                    let info = value.get(at + end + 1..at + end + 17).ok_or_else(truncated)?;
+                    let kind = i32::from_le_bytes(info[..4].try_into().unwrap());
+                    let sampling = [&info[8..12], &info[12..16]].map(|s| i32::from_le_bytes(s.try_into().unwrap()));
+                    if sampling != [1, 1] {
+                        return Err(format!("EXR channel {} is subsampled", name));
+                    }
+                    let name = if kind == 0 { String::new() } else { name };
+                    channels.push(Channel { name, size: if kind == 1 { 2 } else { 4 }, half: kind == 1 });
+                    at += end + 17;
+                }
+            }
+            "compression" => compression = *value.first().ok_or_else(truncated)?,
+            "dataWindow" if len == 16 => {
+                let v: [i32; 4] = std::array::from_fn(|i| i32::from_le_bytes(value[i * 4..i * 4 + 4].try_into().unwrap()));
+                window = Some(v);
+            }
+            _ => {}
+        }
+    }
+    let [x_min, y_min, x_max, y_max] = window.ok_or("EXR file has no dataWindow")?;
+    if x_max < x_min || y_max < y_min {
+        return Err("EXR file is empty".to_string());
+    }
+    let (width, height) = ((x_max - x_min + 1) as usize, (y_max - y_min + 1) as usize);
+    let lines_per_block = match compression {
+        0..=2 => 1,
+        3 => 16,
+        c => return Err(format!("EXR compression {} isn't supported (use none, RLE, ZIPS or ZIP)", c)),
+    };
+
+    let find = |x: &str, y: &str| {
+        let index = |name: &str| channels.iter().position(|c| c.name == name);
+        index(x).zip(index(y))
+    };
+    let (cx, cy) = find("velocity.X", "velocity.Y")
+        .or_else(|| find("X", "Y"))
+        .or_else(|| find("R", "G"))
+        .ok_or("EXR file has no velocity.X/Y, X/Y or R/G channels")?;
+    let line_bytes: usize = channels.iter().map(|c| c.size * width).sum();
+    let channel_start = |i: usize| channels[..i].iter().map(|c| c.size * width).sum::<usize>();
+    let (start_x, start_y) = (channel_start(cx), channel_start(cy));
+
+    let mut velocity = vec![[0.0f32; 2]; width * height];
+    let blocks = height.div_ceil(lines_per_block);
+    for block in 0..blocks {
+        let at = pos + block * 8;
+        let offset = u64::from_le_bytes(bytes.get(at..at + 8).ok_or_else(truncated)?.try_into().unwrap()) as usize;
+        let chunk = bytes.get(offset..offset + 8).ok_or_else(truncated)?;
+        let y = i32::from_le_bytes(chunk[..4].try_into().unwrap());
+        let packed = i32::from_le_bytes(chunk[4..].try_into().unwrap()) as usize;
+        let packed = bytes.get(offset + 8..offset + 8 + packed).ok_or_else(truncated)?;
+        let first = (y - y_min) as usize;
+        if y < y_min || first >= height {
+            return Err("EXR file is corrupt".to_string());
+        }
+        let lines = lines_per_block.min(height - first);
+        let expected = lines * line_bytes;
+        let data = if compression == 0 || packed.len() >= expected {
+            // Blocks that wouldn't shrink are stored as they are
+            packed.to_vec()
+        } else {
+            let raw = match compression {
+                1 => unrle(packed)?,
+                _ => crate::png::inflate(packed.get(2..).ok_or_else(truncated)?).map_err(|_| "EXR data is corrupt".to_string())?,
+            };
+            unpredict(&raw)
+        };
+        if data.len() < expected {
+            return Err("EXR data is corrupt".to_string());
+        }
+        for line in 0..lines {
+            let row = &data[line * line_bytes..];
+            for x in 0..width {
+                let sample = |start: usize, channel: &Channel| {
+                    let b = &row[start + x * channel.size..];
+                    if channel.half {
+                        crate::f16_to_f32(u16::from_le_bytes([b[0], b[1]]))
+                    } else {
+                        f32::from_le_bytes(b[..4].try_into().unwrap())
+                    }
+                };
+                velocity[(first + line) * width + x] = [sample(start_x, &channels[cx]), sample(start_y, &channels[cy])];
+            }
+        }
+    }
+    Ok(Field { width, height, velocity })
+}
+
+/// EXR's run-length coding: a negative count is that many literal bytes,
+/// any other is one byte repeated count + 1 times.
+fn unrle(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let count = data[pos] as i8;
+        if count < 0 {
+            let run = data.get(pos + 1..pos + 1 + (-count as usize)).ok_or("EXR data is corrupt")?;
+            out.extend_from_slice(run);
+            pos += 1 + run.len();
+        } else {
+            let byte = *data.get(pos + 1).ok_or("EXR data is corrupt")?;
+            out.extend(std::iter::repeat_n(byte, count as usize + 1));
+            pos += 2;
+        }
+    }
+    Ok(out)
+}
+
+/// Undo the delta predictor RLE and ZIP blocks are coded with, then put
+/// back together the two halves the bytes were split into.
+fn unpredict(raw: &[u8]) -> Vec<u8> {
+    let mut t = raw.to_vec();
+    for i in 1..t.len() {
+        t[i] = t[i - 1].wrapping_add(t[i]).wrapping_sub(128);
+    }
+    let (low, high) = t.split_at(t.len().div_ceil(2));
+    let mut out = Vec::with_capacity(t.len());
+    for (i, &b) in low.iter().enumerate() {
+        out.push(b);
+        if let Some(&h) = high.get(i) {
+            out.push(h);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field_export::{Fields, Format};
+
+    /// A 4² field F7 would write, each cell's velocity different.
+    fn fields() -> Fields {
+        let n = 4;
+        Fields {
+            grid: n as u32,
+            velocity: (0..n * n).map(|i| [i as f32 * 1.5, 20.0 - i as f32]).collect(),
+            dye: vec![[0.0; 3]; n * n],
+            temperature: vec![0.0; n * n],
+            pressure: vec![0.0; n * n],
+        }
+    }
+
+    /// Where `fields` was saved as `format`, in a scratch directory of the
+    /// test's own.
+    fn saved(fields: &Fields, format: Format, name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("wgpu-fluid-import-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let stem = dir.join("fields");
+        let path = fields.save(format, stem.to_str().unwrap()).unwrap();
+        match format {
+            Format::Exr => path,
+            Format::Npy => format!("{}velocity.npy", path),
+        }
+    }
+
+    fn exported(format: Format, name: &str) -> Vec<u8> {
+        let path = saved(&fields(), format, name);
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+        bytes
+    }
+
+    /// A version 1.0 `.npy` file with `header` as its dictionary.
+    fn npy_file(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut out = b"\x93NUMPY\x01\x00".to_vec();
+        out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        out.extend_from_slice(header.as_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn exported_fields_load_unchanged() {
+        let fields = fields();
+        for (format, name) in [(Format::Exr, "exr"), (Format::Npy, "npy")] {
+            let path = saved(&fields, format, name);
+            assert_eq!(load(&path, fields.grid).unwrap(), fields.velocity, "{}", name);
+            std::fs::remove_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn resampling_scales_to_the_grid() {
+        // A uniform field at half the grid moves twice as many cells a second
+        let field = Field { width: 2, height: 2, velocity: vec![[3.0, -1.0]; 4] };
+        assert!(resample(&field, 4).iter().all(|&v| v == [6.0, -2.0]));
+    }
+
+    #[test]
+    fn reads_other_float_widths() {
+        let data: Vec<u8> = [0.5f64, -2.0, 1.0, 4.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let field = npy(&npy_file("{'descr': '<f8', 'fortran_order': False, 'shape': (1, 2, 2), }\n", &data)).unwrap();
+        assert_eq!([field.width, field.height], [2, 1]);
+        assert_eq!(field.velocity, [[0.5, -2.0], [1.0, 4.0]]);
+        // Half floats: 1.0 and -2.0, and a third component that's skipped
+        let data: Vec<u8> = [0x3c00u16, 0xc000, 0x7c00].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let field = npy(&npy_file("{'descr': '<f2', 'fortran_order': False, 'shape': (1, 1, 3), }\n", &data)).unwrap();
+        assert_eq!(field.velocity, [[1.0, -2.0]]);
+    }
+
+    #[test]
+    fn rejects_malformed_npy_headers() {
+        let data = [0u8; 64];
+        let header = |h: &str| npy(&npy_file(h, &data)).err().unwrap();
+        assert_eq!(header("{'fortran_order': False, 'shape': (2, 2, 2), }"), "numpy header has no 'descr'");
+        assert!(header("{'descr': '>f4', 'fortran_order': False, 'shape': (2, 2, 2), }").contains("little-endian float"));
+        assert!(header("{'descr': '<i4', 'fortran_order': False, 'shape': (2, 2, 2), }").contains("little-endian float"));
+        assert!(header("{'descr': '<f4', 'fortran_order': True, 'shape': (2, 2, 2), }").contains("Fortran order"));
+        assert!(header("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 2), }").contains("expected (rows, columns, 2)"));
+        assert!(header("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 2, 1), }").contains("expected (rows, columns, 2)"));
+        assert_eq!(header("{'descr': '<f4', 'fortran_order': False, 'shape': (2, x, 2), }"), "numpy header is corrupt");
+        assert_eq!(header("{'descr': '<f4', 'fortran_order': False, 'shape': (4, 4, 2), }"), "numpy data is truncated");
+        assert_eq!(header("{'descr': <f4, 'fortran_order': False, 'shape': (2, 2, 2), }"), "numpy header is corrupt");
+
+        let mut file = exported(Format::Npy, "bad-npy");
+        assert!(npy(&file).is_ok());
+        file[6] = 9;
+        assert_eq!(npy(&file).err().unwrap(), "unsupported numpy file version");
+        file[6] = 1;
+        for len in 0..file.len() {
+            assert!(npy(&file[..len]).is_err(), "{} of {} bytes read", len, file.len());
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_exr_headers() {
+        let file = exported(Format::Exr, "bad-exr");
+        assert!(exr(&file).is_ok());
+        let patched = |find: &[u8], replace: &[u8]| {
+            let at = file.windows(find.len()).position(|w| w == find).unwrap();
+            let mut bad = file.clone();
+            bad[at..at + replace.len()].copy_from_slice(replace);
+            exr(&bad).err().unwrap()
+        };
+        assert!(patched(&[0x76, 0x2f, 0x31, 0x01, 2, 0], &[0x76, 0x2f, 0x31, 0x01, 2, 2]).contains("tiled"));
+        assert!(patched(&[0x76, 0x2f, 0x31, 0x01, 2, 0], &[0x76, 0x2f, 0x31, 0x01, 2, 0x10]).contains("multi-part"));
+        assert_eq!(patched(b"dataWindow\0", b"dataWindoX\0"), "EXR file has no dataWindow");
+        assert!(patched(b"velocity.X\0", b"velocity.Z\0").contains("no velocity.X/Y"));
+        assert!(patched(b"compression\0compression\0\x01\0\0\0\0", b"compression\0compression\0\x01\0\0\0\x07").contains("compression 7"));
+        // Sampled every other column
+        assert!(patched(b"velocity.X\0\x02\0\0\0\0\0\0\0\x01", b"velocity.X\0\x02\0\0\0\0\0\0\0\x02").contains("subsampled"));
+        for len in 0..file.len() {
+            assert!(exr(&file[..len]).is_err(), "{} of {} bytes read", len, file.len());
+        }
+    }
+}
@@ -0,0 +1,621 @@
+//! 3D mode (`--3d N`): an N³ cube of fluid in 3D textures, stepped by the
+//! kernels in `volume.wgsl` and ray-marched to the window. It's a separate,
+//! smaller sim than the 2D one: splat, advect, project with Jacobi, with a
+//! warm plume rising from the floor so there's something to look at.
+//! Left drag stirs on the plane through the cube's center facing the
+//! camera, right drag orbits and the wheel zooms. The ray march lights the
+//! dye from above with shadows (L) at `--volume-steps` samples per cube
+//! width (- / =). `--stereo` renders a view per eye, as a red-cyan
+//! anaglyph or side by side.
+
+use std::sync::{Arc, Mutex};
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::{
+    event::*,
+    event_loop::{ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::Window,
+};
+
+use crate::config::Config;
+use crate::dye::DyeBrush;
+use crate::gpu_error::{self, ErrorLog};
+
+/// Jacobi iterations per step. The 7-point stencil converges slower than
+/// the 2D one, but a volume this coarse doesn't need much.
+const PRESSURE_ITERATIONS: u32 = 30;
+/// Must match `TAN_HALF_FOV` in `volume.wgsl`.
+const TAN_HALF_FOV: f32 = 0.5;
+const CENTER: [f32; 3] = [0.5; 3];
+/// Largest eye separation `]` goes to, in cube widths.
+pub const MAX_EYE_SEPARATION: f32 = 0.3;
+/// Range of ray march samples per cube width (`--volume-steps`, - / =).
+pub const MIN_STEPS: u32 = 8;
+pub const MAX_STEPS: u32 = 1024;
+
+/// How the volume is shown: one view, or one per eye (`--stereo`, V).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stereo {
+    Off,
+    /// Red-cyan glasses: the left eye's gray in red, the right eye's color
+    /// in green and blue.
+    Anaglyph,
+    /// Left eye on the left half, right eye on the right, each squeezed to
+    /// half width the way 3D displays' side-by-side input expects.
+    SideBySide,
+}
+
+impl Stereo {
+    const ALL: [Stereo; 3] = [Stereo::Off, Stereo::Anaglyph, Stereo::SideBySide];
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.code() as usize + 1) % Self::ALL.len()]
+    }
+
+    /// `VolumeParams::stereo`, and the matching codes in `volume.wgsl`.
+    pub fn code(self) -> u32 {
+        match self {
+            Stereo::Off => 0,
+            Stereo::Anaglyph => 1,
+            Stereo::SideBySide => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stereo::Off => "off",
+            Stereo::Anaglyph => "anaglyph",
+            Stereo::SideBySide => "sbs",
+        }
+    }
+}
+
+impl std::str::FromStr for Stereo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        crate::config::parse_choice("stereo mode", s, &Self::ALL.map(|m| (m.name(), m)))
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct VolumeParams {
+    size: u32,
+    dt: f32,
+    radius: f32,
+    /// Dye added per second at the brush center.
+    strength: f32,
+    /// Brush center and its movement this frame, in cells, y up.
+    mouse_pos: [f32; 3],
+    mouse_down: u32,
+    mouse_delta: [f32; 3],
+    velocity_decay: f32,
+    dye_color: [f32; 3],
+    dye_decay: f32,
+    /// Camera position and axes in texture coordinates.
+    eye: [f32; 3],
+    aspect: f32,
+    right: [f32; 3],
+    /// Upward acceleration in cells/s² per unit of dye.
+    lift: f32,
+    up: [f32; 3],
+    plume: u32,
+    forward: [f32; 3],
+    time: f32,
+    /// `Stereo::code`, and the distance between the eyes in cube widths.
+    stereo: u32,
+    eye_separation: f32,
+    /// Ray march samples per cube width, and nonzero while the dye is lit.
+    steps: u32,
+    light: u32,
+}
+
+/// Orbit camera looking at the cube's center.
+struct Camera {
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl Camera {
+    /// Eye position, then the right, up and forward axes.
+    fn frame(&self) -> [[f32; 3]; 4] {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        let eye = [CENTER[0] + self.distance * cp * sy, CENTER[1] + self.distance * sp, CENTER[2] + self.distance * cp * cy];
+        let forward = normalize(sub(CENTER, eye));
+        let right = normalize(cross(forward, [0.0, 1.0, 0.0]));
+        let up = cross(right, forward);
+        [eye, right, up, forward]
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = dot(a, a).sqrt();
+    a.map(|v| v / len)
+}
+
+fn create_volume_tex(device: &wgpu::Device, size: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let tex = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: size },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: wgpu::TextureFormat::Rgba16Float,
+        usage: wgpu::TextureUsages::STORAGE_BINDING
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+    (tex, view)
+}
+
+fn texture_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding, visibility,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D3, multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn uniform_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: 0, visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false, min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn sampler_entry(visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: 5, visibility,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    }
+}
+
+/// One compute pass: a kernel and the fields it reads and writes.
+struct Pass {
+    pipeline: usize,
+    bind_group: wgpu::BindGroup,
+}
+
+/// The volume's fields, pipelines and the passes of one step.
+struct Volume {
+    size: u32,
+    params: VolumeParams,
+    param_buffer: wgpu::Buffer,
+    /// Velocity (xyz) and dye (rgb times amount, amount in w), ping-ponged.
+    velocity: [wgpu::Texture; 2],
+    dye: [wgpu::Texture; 2],
+    _pressure: [wgpu::Texture; 2],
+    _divergence: wgpu::Texture,
+    pipelines: Vec<wgpu::ComputePipeline>,
+    splat: [Pass; 2],
+    advect: [Pass; 2],
+    divergence: Pass,
+    jacobi: [Pass; 2],
+    gradient: Pass,
+    render_pipeline: wgpu::RenderPipeline,
+    render_bg: wgpu::BindGroup,
+}
+
+const KERNELS: [&str; 6] = ["splat_velocity", "splat_dye", "advect", "divergence", "jacobi", "subtract_gradient"];
+
+impl Volume {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, config: &Config, size: u32) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("volume_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../volume.wgsl").into()),
+        });
+
+        let dt = 0.016;
+        let params = VolumeParams {
+            size, dt, radius: size as f32 * 0.08, strength: 20.0,
+            mouse_pos: [size as f32 * 0.5; 3], mouse_down: 0, mouse_delta: [0.0; 3],
+            velocity_decay: config.velocity_decay.factor(dt), dye_color: [1.0; 3], dye_decay: config.dye_decay.factor(dt),
+            eye: [0.0; 3], aspect: 1.0, right: [0.0; 3], lift: config.buoyancy, up: [0.0; 3], plume: 1,
+            forward: [0.0; 3], time: 0.0,
+            stereo: config.stereo.code(), eye_separation: config.eye_separation,
+            steps: config.volume_steps.unwrap_or(size), light: 1,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("volume_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let [(vel_a, vel_a_view), (vel_b, vel_b_view), (dye_a, dye_a_view), (dye_b, dye_b_view), (p_a, p_a_view), (p_b, p_b_view), (div, div_view)] =
+            [(); 7].map(|_| create_volume_tex(device, size));
+
+        // ---- Compute ----
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let compute_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("volume_compute_bgl"),
+            entries: &[
+                uniform_entry(compute),
+                texture_entry(1, compute),
+                texture_entry(2, compute),
+                texture_entry(3, compute),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4, visibility: compute,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+                sampler_entry(compute),
+            ],
+        });
+        let compute_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&compute_bgl], push_constant_ranges: &[],
+        });
+        let pipelines = KERNELS.iter().map(|&entry| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&compute_pl), module: &module, entry_point: entry,
+            })
+        }).collect();
+
+        // A texture can't be read and written in the same pass, so unused
+        // read slots get a field the pass doesn't write
+        let pass = |pipeline: &str, src: &wgpu::TextureView, vel: &wgpu::TextureView, aux: &wgpu::TextureView, dst: &wgpu::TextureView| Pass {
+            pipeline: KERNELS.iter().position(|&k| k == pipeline).unwrap(),
+            bind_group: device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(pipeline), layout: &compute_bgl,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(src) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(vel) },
+                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(aux) },
+                    wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(dst) },
+                    wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::Sampler(&sampler) },
+                ],
+            }),
+        };
+        // Each step starts and ends with the current fields in the A textures
+        let splat = [
+            pass("splat_velocity", &vel_a_view, &vel_a_view, &dye_a_view, &vel_b_view),
+            pass("splat_dye", &dye_a_view, &vel_a_view, &dye_a_view, &dye_b_view),
+        ];
+        let advect = [
+            pass("advect", &vel_b_view, &vel_b_view, &div_view, &vel_a_view),
+            pass("advect", &dye_b_view, &vel_a_view, &div_view, &dye_a_view),
+        ];
+        let divergence = pass("divergence", &vel_a_view, &vel_a_view, &p_a_view, &div_view);
+        let jacobi = [
+            pass("jacobi", &p_a_view, &vel_a_view, &div_view, &p_b_view),
+            pass("jacobi", &p_b_view, &vel_a_view, &div_view, &p_a_view),
+        ];
+        let gradient = pass("subtract_gradient", &vel_a_view, &vel_a_view, &p_a_view, &vel_b_view);
+
+        // ---- Render ----
+        let fragment = wgpu::ShaderStages::FRAGMENT;
+        let render_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("volume_render_bgl"),
+            entries: &[uniform_entry(fragment), texture_entry(1, fragment), sampler_entry(fragment)],
+        });
+        let render_pl = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&render_bgl], push_constant_ranges: &[],
+        });
+        let render_pipeline = crate::create_render_pipeline(device, &render_pl, &module, format, "fs_volume");
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("volume_render_bg"), layout: &render_bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&dye_a_view) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+
+        Self {
+            size, params, param_buffer,
+            velocity: [vel_a, vel_b], dye: [dye_a, dye_b], _pressure: [p_a, p_b], _divergence: div,
+            pipelines, splat, advect, divergence, jacobi, gradient, render_pipeline, render_bg,
+        }
+    }
+
+    fn step(&self, encoder: &mut wgpu::CommandEncoder) {
+        let groups = self.size.div_ceil(4);
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("volume_step"), timestamp_writes: None });
+            let passes = self.splat.iter()
+                .chain(&self.advect)
+                .chain([&self.divergence])
+                .chain(self.jacobi.iter().cycle().take(2 * PRESSURE_ITERATIONS as usize))
+                .chain([&self.gradient]);
+            for p in passes {
+                pass.set_pipeline(&self.pipelines[p.pipeline]);
+                pass.set_bind_group(0, &p.bind_group, &[]);
+                pass.dispatch_workgroups(groups, groups, groups);
+            }
+        }
+        // The projected velocity lands in B; bring it home
+        let extent = wgpu::Extent3d { width: self.size, height: self.size, depth_or_array_layers: self.size };
+        encoder.copy_texture_to_texture(self.velocity[1].as_image_copy(), self.velocity[0].as_image_copy(), extent);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut r = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("volume_render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view, resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        r.set_pipeline(&self.render_pipeline);
+        r.set_bind_group(0, &self.render_bg, &[]);
+        r.draw(0..3, 0..1);
+    }
+
+    /// Empty the cube: no dye, no motion.
+    fn clear(&self, queue: &wgpu::Queue) {
+        let size = self.size;
+        let zeros = vec![0u8; (size * size * size * 8) as usize];
+        for tex in self.velocity.iter().chain(&self.dye) {
+            queue.write_texture(
+                tex.as_image_copy(),
+                &zeros,
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(size * 8), rows_per_image: Some(size) },
+                wgpu::Extent3d { width: size, height: size, depth_or_array_layers: size },
+            );
+        }
+    }
+}
+
+/// The 3D window loop, taking over from `run` once the window and device
+/// exist.
+pub async fn run(
+    config: Config,
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+) {
+    let size = config.volume.unwrap_or(64);
+    let error_log = Arc::new(Mutex::new(ErrorLog::default()));
+    gpu_error::install_uncaptured_handler(&device, &error_log);
+    gpu_error::push_scopes(&device);
+
+    let caps = surface.get_capabilities(&adapter);
+    let format = caps.formats.iter().find(|f| f.is_srgb()).copied().unwrap_or(caps.formats[0]);
+    let win_size = window.inner_size();
+    let mut surface_config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        width: win_size.width.max(1),
+        height: win_size.height.max(1),
+        present_mode: config.present_mode,
+        alpha_mode: caps.alpha_modes[0],
+        view_formats: vec![],
+        desired_maximum_frame_latency: 2,
+    };
+    surface.configure(&device, &surface_config);
+
+    let mut volume = Volume::new(&device, format, &config, size);
+    eprintln!("3D mode: {}³ cells, {} ray steps per cube width", size, volume.params.steps);
+
+    if let Some(err) = gpu_error::pop_scopes(&device, "setup").await {
+        let info = adapter.get_info();
+        eprintln!("{}\n(GPU: {}, backend: {:?})", err, info.name, info.backend);
+        #[cfg(not(target_arch = "wasm32"))]
+        std::process::exit(1);
+        #[cfg(target_arch = "wasm32")]
+        return;
+    }
+
+    // ---- State ----
+    let mut camera = Camera { yaw: 0.6, pitch: 0.35, distance: 2.2 };
+    let mut dye_brush = DyeBrush::new(config.dye_color, config.dye_cycle);
+    let mut cursor = [0.0f32; 2];
+    // Where the stroke was last frame, in cells, and the screen-space drag
+    // for the direction dye color
+    let mut last_point: Option<[f32; 3]> = None;
+    let mut stroke = [0.0f32; 2];
+    let mut orbiting = false;
+    // Space: stop stepping. While paused the window only redraws after input
+    let mut paused = false;
+    let mut stereo = config.stereo;
+
+    event_loop.run(move |event, target| {
+        if let Event::WindowEvent { event: ref e, .. } = event {
+            if paused && !matches!(e, WindowEvent::RedrawRequested) {
+                window.request_redraw();
+            }
+        }
+        match event {
+            Event::WindowEvent { event: ref win_event, .. } => match win_event {
+                WindowEvent::CloseRequested => target.exit(),
+
+                WindowEvent::Resized(new_size) if new_size.width > 0 && new_size.height > 0 => {
+                    surface_config.width = new_size.width;
+                    surface_config.height = new_size.height;
+                    surface.configure(&device, &surface_config);
+                }
+
+                WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                    volume.params.mouse_down = (*state == ElementState::Pressed) as u32;
+                    last_point = None;
+                }
+
+                WindowEvent::MouseInput { state, button: MouseButton::Right, .. } => {
+                    orbiting = *state == ElementState::Pressed;
+                }
+
+                WindowEvent::CursorMoved { position, .. } => {
+                    let pos = [position.x as f32, position.y as f32];
+                    if orbiting {
+                        camera.yaw -= (pos[0] - cursor[0]) * 0.01;
+                        camera.pitch = (camera.pitch + (pos[1] - cursor[1]) * 0.01).clamp(-1.4, 1.4);
+                    }
+                    stroke = [pos[0] - cursor[0], pos[1] - cursor[1]];
+                    cursor = pos;
+                }
+
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let lines = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => *y,
+                        MouseScrollDelta::PixelDelta(p) => p.y as f32 / 40.0,
+                    };
+                    camera.distance = (camera.distance * 0.9f32.powf(lines)).clamp(1.0, 5.0);
+                }
+
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent { physical_key: PhysicalKey::Code(code), state: ElementState::Pressed, .. },
+                    ..
+                } => match code {
+                    KeyCode::Space => {
+                        paused = !paused;
+                        eprintln!("{}", if paused { "Paused" } else { "Resumed" });
+                    }
+                    KeyCode::KeyR => {
+                        volume.clear(&queue);
+                        volume.params.time = 0.0;
+                        eprintln!("Volume cleared");
+                    }
+                    KeyCode::KeyE => {
+                        volume.params.plume ^= 1;
+                        eprintln!("Plume: {}", if volume.params.plume != 0 { "on" } else { "off" });
+                    }
+                    KeyCode::KeyX => {
+                        dye_brush.color = dye_brush.color.next();
+                        eprintln!("Dye color: {}", dye_brush.describe());
+                    }
+                    KeyCode::KeyV => {
+                        stereo = stereo.next();
+                        volume.params.stereo = stereo.code();
+                        eprintln!("Stereo: {}", stereo.name());
+                    }
+                    KeyCode::KeyL => {
+                        volume.params.light ^= 1;
+                        eprintln!("Lighting: {}", if volume.params.light != 0 { "on" } else { "off" });
+                    }
+                    KeyCode::Minus | KeyCode::Equal => {
+                        let p = &mut volume.params;
+                        p.steps = if *code == KeyCode::Minus { p.steps / 2 } else { p.steps * 2 }.clamp(MIN_STEPS, MAX_STEPS);
+                        eprintln!("Ray steps: {} per cube width", p.steps);
+                    }
+                    KeyCode::BracketLeft | KeyCode::BracketRight => {
+                        let step = if *code == KeyCode::BracketLeft { -0.01 } else { 0.01 };
+                        let p = &mut volume.params;
+                        p.eye_separation = (p.eye_separation + step).clamp(0.0, MAX_EYE_SEPARATION);
+                        eprintln!("Eye separation: {:.2}", p.eye_separation);
+                    }
+                    _ => {}
+                },
+
+                WindowEvent::RedrawRequested => {
+                    let w = surface_config.width as f32;
+                    let h = surface_config.height as f32;
+                    let [eye, right, up, forward] = camera.frame();
+                    let p = &mut volume.params;
+                    p.eye = eye;
+                    p.right = right;
+                    p.up = up;
+                    p.forward = forward;
+                    p.aspect = w / h;
+
+                    // The cursor's ray, cut by the plane through the center
+                    // that faces the camera. Both eyes converge on that
+                    // plane, so stereo views agree there; side by side, the
+                    // cursor points into the half it's over.
+                    let mut ndc = [2.0 * cursor[0] / w - 1.0, 1.0 - 2.0 * cursor[1] / h];
+                    if stereo == Stereo::SideBySide {
+                        ndc[0] = 2.0 * ndc[0] - ndc[0].signum();
+                    }
+                    let dir: [f32; 3] = std::array::from_fn(|i| {
+                        forward[i] + right[i] * ndc[0] * p.aspect * TAN_HALF_FOV + up[i] * ndc[1] * TAN_HALF_FOV
+                    });
+                    let t = dot(sub(CENTER, eye), forward) / dot(dir, forward);
+                    let point = std::array::from_fn(|i| (eye[i] + dir[i] * t) * size as f32);
+                    p.mouse_delta = last_point.map_or([0.0; 3], |last| sub(point, last));
+                    p.mouse_pos = point;
+                    if p.mouse_down != 0 {
+                        last_point = Some(point);
+                    }
+
+                    if !paused {
+                        p.time += p.dt;
+                        p.dye_color = dye_brush.rgb(p.time, stroke);
+                    }
+                    queue.write_buffer(&volume.param_buffer, 0, bytemuck::bytes_of(&volume.params));
+
+                    let frame = match surface.get_current_texture() {
+                        Ok(f) => f,
+                        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                            surface.configure(&device, &surface_config);
+                            return;
+                        }
+                        Err(e) => {
+                            eprintln!("Surface error: {:?}", e);
+                            return;
+                        }
+                    };
+
+                    gpu_error::push_scopes(&device);
+                    let view = frame.texture.create_view(&Default::default());
+                    let mut encoder = device.create_command_encoder(&Default::default());
+                    if !paused {
+                        volume.step(&mut encoder);
+                    }
+                    volume.render(&mut encoder, &view);
+                    queue.submit(Some(encoder.finish()));
+                    gpu_error::pop_scopes_into(&device, "frame", &error_log);
+                    if let Some(headline) = error_log.lock().unwrap().take_headline() {
+                        window.set_title(&format!("{} — {}", crate::WINDOW_TITLE, headline));
+                    }
+                    frame.present();
+                    stroke = [0.0, 0.0];
+                }
+
+                _ => {}
+            },
+
+            Event::AboutToWait => {
+                target.set_control_flow(ControlFlow::Wait);
+                if !paused {
+                    window.request_redraw();
+                }
+            }
+
+            _ => {}
+        }
+    }).ok();
+}
@@ -0,0 +1,178 @@
+//! Vortex particles (`--vortex-particles <N>`): Lagrangian carriers of the
+//! flow's curl. They're seeded where the grid is swirling, ride the flow for
+//! a few seconds (`--vortex-life`) and splat back small vortices wherever the
+//! grid's curl has fallen below what they carry, so eddies last longer than
+//! the grid's numerical diffusion alone would let them.
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::rng::Seed;
+
+/// Longest a vortex particle lives when `--vortex-life` isn't given, in
+/// seconds.
+pub const DEFAULT_LIFE: f32 = 4.0;
+/// Most vortex particles `--vortex-particles` takes. Each one splats over a
+/// few hundred cells per frame.
+pub const MAX_COUNT: u32 = 16384;
+/// Bytes per particle: position, vorticity, life.
+const PARTICLE_SIZE: u64 = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct VortexParams {
+    grid_size: u32,
+    count: u32,
+    dt: f32,
+    life: f32,
+    cell_size: [f32; 2],
+    frame: u32,
+    _pad: u32,
+}
+
+/// Vortex particles and the fixed-point buffer they splat velocity into.
+pub struct VortexParticles {
+    params: VortexParams,
+    param_buffer: wgpu::Buffer,
+    particle_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    update: wgpu::ComputePipeline,
+    apply: wgpu::ComputePipeline,
+    _splat: wgpu::Buffer,
+}
+
+impl VortexParticles {
+    /// `count` free slots, which fill up as the flow starts to swirl.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device, velocity_view: &wgpu::TextureView, curl_view: &wgpu::TextureView,
+        obstacle_view: &wgpu::TextureView, grid_size: u32, count: u32, life: f32, seed: Seed,
+    ) -> Self {
+        let params = VortexParams {
+            grid_size, count, dt: 0.0, life, cell_size: [1.0; 2],
+            frame: seed.rng("vortex-particles").next_u64() as u32, _pad: 0,
+        };
+        let param_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("vortex_params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // Zeroed, so every slot starts free
+        let particle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vortex_particles"),
+            size: count as u64 * PARTICLE_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let splat = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vortex_splat"),
+            size: (grid_size * grid_size) as u64 * 2 * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("vortex_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../vortex.wgsl").into()),
+        });
+
+        let storage_buf = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false, min_binding_size: None,
+        };
+        let sampled_tex = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2, multisampled: false,
+        };
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vortex_bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false, min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::ReadWrite,
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3, visibility: wgpu::ShaderStages::COMPUTE, ty: sampled_tex, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_buf, count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5, visibility: wgpu::ShaderStages::COMPUTE, ty: storage_buf, count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vortex_bg"), layout: &bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: param_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(velocity_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(curl_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(obstacle_view) },
+                wgpu::BindGroupEntry { binding: 4, resource: particle_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: splat.as_entire_binding() },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None, bind_group_layouts: &[&bgl], push_constant_ranges: &[],
+        });
+        let make_compute = |entry: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry), layout: Some(&layout),
+                module: &shader, entry_point: entry,
+            })
+        };
+
+        Self {
+            params,
+            param_buffer,
+            particle_buffer,
+            bind_group,
+            update: make_compute("update"),
+            apply: make_compute("apply"),
+            _splat: splat,
+        }
+    }
+
+    /// Step length and physical cell size for the next `dispatch`.
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: f32, cell_size: [f32; 2]) {
+        self.params.dt = dt;
+        self.params.cell_size = cell_size;
+        self.params.frame = self.params.frame.wrapping_add(1);
+        queue.write_buffer(&self.param_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    /// Free every slot, so vortices from the last scene don't spin up the
+    /// new one.
+    pub fn clear(&self, queue: &wgpu::Queue) {
+        let zeros = vec![0u8; (self.params.count as u64 * PARTICLE_SIZE) as usize];
+        queue.write_buffer(&self.particle_buffer, 0, &zeros);
+    }
+
+    /// Record the particle step and the splat into the velocity field.
+    /// Expects the curl to be up to date, as it is after a fluid step.
+    pub fn dispatch<'a>(&'a self, c: &mut wgpu::ComputePass<'a>) {
+        c.set_bind_group(0, &self.bind_group, &[]);
+        c.set_pipeline(&self.update);
+        c.dispatch_workgroups(self.params.count.div_ceil(64), 1, 1);
+        c.set_pipeline(&self.apply);
+        let groups = self.params.grid_size.div_ceil(8);
+        c.dispatch_workgroups(groups, groups, 1);
+    }
+}
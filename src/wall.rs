@@ -0,0 +1,297 @@
+//! Video-wall mode: one leader instance runs the simulation and streams each
+//! follower the part of the fields its screen shows. Followers don't
+//! simulate; they upload whatever arrives and draw their tile of the domain.
+//!
+//! Wire format (TCP, little endian): a follower opens with `HELLO` and its
+//! tile as four u32s (col, row, cols, rows). The leader then sends frames:
+//! `FRAME`, the grid size and the region's x, y, w, h as u32s, followed by
+//! the region's density, velocity and obstacle texels as raw `Rgba16Float`
+//! rows.
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+const HELLO: [u8; 4] = *b"WFWH";
+const FRAME: [u8; 4] = *b"WFWF";
+/// Fields streamed each frame, in order: density, velocity, obstacles.
+pub const LAYERS: usize = 3;
+/// Bytes per `Rgba16Float` texel.
+pub const TEXEL: u32 = 8;
+
+/// Which screen of a `cols` x `rows` wall this instance is, counted from the
+/// top left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WallTile {
+    pub col: u32,
+    pub row: u32,
+    pub cols: u32,
+    pub rows: u32,
+}
+
+impl std::str::FromStr for WallTile {
+    type Err = String;
+
+    /// `COL,ROW/COLSxROWS`, e.g. `1,0/3x1` for the middle of three screens.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let bad = || format!("--wall-tile expects COL,ROW/COLSxROWS, got `{}`", s);
+        let (pos, size) = s.split_once('/').ok_or_else(bad)?;
+        let (col, row) = pos.split_once(',').ok_or_else(bad)?;
+        let (cols, rows) = size.split_once('x').ok_or_else(bad)?;
+        let num = |v: &str| v.trim().parse::<u32>().map_err(|_| bad());
+        let tile = WallTile { col: num(col)?, row: num(row)?, cols: num(cols)?, rows: num(rows)? };
+        if tile.col >= tile.cols || tile.row >= tile.rows {
+            return Err(format!("--wall-tile {} is outside a {}x{} wall", s, tile.cols, tile.rows));
+        }
+        Ok(tile)
+    }
+}
+
+/// A rectangle of grid cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl WallTile {
+    /// Cells this tile shows, as [x0, y0, x1, y1).
+    fn cells(&self, grid: u32) -> [u32; 4] {
+        [
+            self.col * grid / self.cols, self.row * grid / self.rows,
+            (self.col + 1) * grid / self.cols, (self.row + 1) * grid / self.rows,
+        ]
+    }
+
+    /// The tile's cells plus a one-cell apron, so bilinear filtering at the
+    /// seams matches the neighbouring screens.
+    pub fn region(&self, grid: u32) -> Region {
+        let [x0, y0, x1, y1] = self.cells(grid);
+        let (x0, y0) = (x0.saturating_sub(1), y0.saturating_sub(1));
+        let (x1, y1) = ((x1 + 1).min(grid), (y1 + 1).min(grid));
+        Region { x: x0, y: y0, w: x1 - x0, h: y1 - y0 }
+    }
+
+    /// Part of the domain this screen draws, as (x, y, w, h) in texture
+    /// coordinates.
+    pub fn viewport(&self, grid: u32) -> [f32; 4] {
+        let [x0, y0, x1, y1] = self.cells(grid).map(|c| c as f32 / grid as f32);
+        [x0, y0, x1 - x0, y1 - y0]
+    }
+}
+
+// ---- Leader ----
+
+/// Accepts followers in the background and streams them their regions.
+pub struct Leader {
+    followers: Arc<Mutex<Vec<Link>>>,
+}
+
+struct Link {
+    tile: WallTile,
+    peer: SocketAddr,
+    /// Holds at most one frame; a follower that hasn't finished receiving the
+    /// previous one skips the next.
+    frames: SyncSender<Vec<u8>>,
+}
+
+impl Leader {
+    pub fn listen(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        eprintln!("Wall leader listening on {}", listener.local_addr()?);
+        let followers = Arc::new(Mutex::new(Vec::new()));
+        let list = followers.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream.and_then(accept) {
+                    Ok(link) => {
+                        eprintln!("Wall follower {} joined as tile {},{} of {}x{}",
+                            link.peer, link.tile.col, link.tile.row, link.tile.cols, link.tile.rows);
+                        list.lock().unwrap().push(link);
+                    }
+                    Err(e) => eprintln!("Wall follower rejected: {}", e),
+                }
+            }
+        });
+        Ok(Self { followers })
+    }
+
+    /// Read `fields` back and queue each follower's region of them. Blocks on
+    /// the GPU, but only while someone is connected.
+    pub fn broadcast(&self, device: &wgpu::Device, queue: &wgpu::Queue, fields: [&wgpu::Texture; LAYERS], grid: u32) {
+        let mut followers = self.followers.lock().unwrap();
+        if followers.is_empty() {
+            return;
+        }
+        let layers = read_layers(device, queue, fields, grid);
+        followers.retain(|link| {
+            let region = link.tile.region(grid);
+            match link.frames.try_send(encode_frame(&layers, grid, region)) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => {
+                    eprintln!("Wall follower {} left", link.peer);
+                    false
+                }
+            }
+        });
+    }
+}
+
+fn accept(mut stream: TcpStream) -> io::Result<Link> {
+    let mut hello = [0u8; 20];
+    stream.read_exact(&mut hello)?;
+    if hello[..4] != HELLO {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a wgpu-fluid wall follower"));
+    }
+    let [col, row, cols, rows] = std::array::from_fn(|i| u32::from_le_bytes(hello[4 + 4 * i..8 + 4 * i].try_into().unwrap()));
+    if col >= cols || row >= rows {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("tile {},{} is outside a {}x{} wall", col, row, cols, rows)));
+    }
+    stream.set_nodelay(true)?;
+    let peer = stream.peer_addr()?;
+    let (frames, pending) = sync_channel::<Vec<u8>>(1);
+    std::thread::spawn(move || {
+        for frame in pending {
+            if stream.write_all(&frame).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(Link { tile: WallTile { col, row, cols, rows }, peer, frames })
+}
+
+/// Copy whole grid textures back as tightly packed rows of texels.
+pub fn read_layers(device: &wgpu::Device, queue: &wgpu::Queue, fields: [&wgpu::Texture; LAYERS], grid: u32) -> [Vec<u8>; LAYERS] {
+    let row_bytes = grid * TEXEL;
+    let padded = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let layer_bytes = (padded * grid) as u64;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("wall_readback"),
+        size: layer_bytes * LAYERS as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("wall_readback") });
+    for (i, tex) in fields.iter().enumerate() {
+        encoder.copy_texture_to_buffer(
+            tex.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: layer_bytes * i as u64, bytes_per_row: Some(padded), rows_per_image: Some(grid),
+                },
+            },
+            wgpu::Extent3d { width: grid, height: grid, depth_or_array_layers: 1 },
+        );
+    }
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = slice.get_mapped_range();
+    let layers = std::array::from_fn(|i| {
+        let layer = &data[i * layer_bytes as usize..(i + 1) * layer_bytes as usize];
+        layer.chunks(padded as usize).flat_map(|row| &row[..row_bytes as usize]).copied().collect()
+    });
+    drop(data);
+    buffer.unmap();
+    layers
+}
+
+fn encode_frame(layers: &[Vec<u8>; LAYERS], grid: u32, r: Region) -> Vec<u8> {
+    let row_bytes = (r.w * TEXEL) as usize;
+    let mut out = Vec::with_capacity(24 + row_bytes * r.h as usize * LAYERS);
+    out.extend_from_slice(&FRAME);
+    for v in [grid, r.x, r.y, r.w, r.h] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    for layer in layers {
+        for y in r.y..r.y + r.h {
+            let start = ((y * grid + r.x) * TEXEL) as usize;
+            out.extend_from_slice(&layer[start..start + row_bytes]);
+        }
+    }
+    out
+}
+
+// ---- Follower ----
+
+/// Connection to the leader. A background thread keeps the newest frame.
+pub struct Follower {
+    latest: Arc<Mutex<Option<Frame>>>,
+}
+
+struct Frame {
+    region: Region,
+    /// `LAYERS` blocks of `region.w * region.h` texels.
+    texels: Vec<u8>,
+}
+
+impl Follower {
+    pub fn connect(addr: &str, tile: WallTile, grid: u32) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        let mut hello = HELLO.to_vec();
+        for v in [tile.col, tile.row, tile.cols, tile.rows] {
+            hello.extend_from_slice(&v.to_le_bytes());
+        }
+        stream.write_all(&hello)?;
+        eprintln!("Wall follower connected to {} as tile {},{} of {}x{}", addr, tile.col, tile.row, tile.cols, tile.rows);
+
+        let latest = Arc::new(Mutex::new(None));
+        let slot = latest.clone();
+        std::thread::spawn(move || loop {
+            match read_frame(&mut stream, grid) {
+                Ok(frame) => *slot.lock().unwrap() = Some(frame),
+                Err(e) => {
+                    eprintln!("Wall leader connection lost: {}", e);
+                    break;
+                }
+            }
+        });
+        Ok(Self { latest })
+    }
+
+    /// Upload the newest frame from the leader into `fields`, if one arrived
+    /// since the last call.
+    pub fn upload_latest(&self, queue: &wgpu::Queue, fields: [&wgpu::Texture; LAYERS]) {
+        let Some(frame) = self.latest.lock().unwrap().take() else { return };
+        let r = frame.region;
+        let layer_bytes = (r.w * r.h * TEXEL) as usize;
+        for (tex, texels) in fields.iter().zip(frame.texels.chunks(layer_bytes)) {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: tex, mip_level: 0,
+                    origin: wgpu::Origin3d { x: r.x, y: r.y, z: 0 }, aspect: wgpu::TextureAspect::All,
+                },
+                texels,
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(r.w * TEXEL), rows_per_image: Some(r.h) },
+                wgpu::Extent3d { width: r.w, height: r.h, depth_or_array_layers: 1 },
+            );
+        }
+    }
+}
+
+fn read_frame(stream: &mut TcpStream, grid: u32) -> io::Result<Frame> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut header = [0u8; 24];
+    stream.read_exact(&mut header)?;
+    if header[..4] != FRAME {
+        return Err(invalid("unexpected data from the leader".to_string()));
+    }
+    let [leader_grid, x, y, w, h] = std::array::from_fn(|i| u32::from_le_bytes(header[4 + 4 * i..8 + 4 * i].try_into().unwrap()));
+    if leader_grid != grid {
+        return Err(invalid(format!("the leader runs a {0}x{0} grid, start this follower with --grid {0}", leader_grid)));
+    }
+    // Checked: the sums come off the network and could wrap back onto the grid
+    let off_grid = |start: u32, len: u32| start.checked_add(len).is_none_or(|end| end > grid);
+    if off_grid(x, w) || off_grid(y, h) {
+        return Err(invalid(format!("region {}x{} at ({}, {}) is off the grid", w, h, x, y)));
+    }
+    let mut texels = vec![0u8; (w * h * TEXEL) as usize * LAYERS];
+    stream.read_exact(&mut texels)?;
+    Ok(Frame { region: Region { x, y, w, h }, texels })
+}
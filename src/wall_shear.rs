@@ -0,0 +1,87 @@
+//! Wall shear along obstacle boundaries. **S** outlines the obstacles in
+//! `render.wgsl`, thicker and hotter where the flow shears past them
+//! faster; **Shift + S** writes the distribution along every wall to a CSV
+//! file, for plotting against the position around an obstacle.
+//!
+//! The wall is no-slip, so the shear rate at a wall cell is the flow's
+//! speed along the wall, relative to the wall's own, over the half cell
+//! between the cell center and the wall face. The stress is that times the
+//! viscosity (the fluid's density is 1), and so zero in an inviscid run,
+//! where the rate still shows where the flow scrapes past hardest.
+
+/// Shear rate in 1/s outlined at full thickness and color when
+/// `--wall-shear` doesn't set it.
+pub const DEFAULT_SCALE: f32 = 100.0;
+
+/// One fluid cell next to a solid one.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct WallSample {
+    pub cell: [u32; 2],
+    /// Unit normal out of the wall, into the fluid.
+    pub normal: [f32; 2],
+    /// Speed along the wall over the distance to it, in 1/s.
+    pub rate: f32,
+}
+
+/// The wall cells of `grid`² `velocity` and `obstacles` fields (as
+/// `read_field` returns them), in row order.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn wall_samples(velocity: &[[f32; 4]], obstacles: &[[f32; 4]], grid: u32, cell_size: [f32; 2]) -> Vec<WallSample> {
+    let n = grid as i32;
+    let solid = |x: i32, y: i32| (0..n).contains(&x) && (0..n).contains(&y) && obstacles[(y * n + x) as usize][0] > 0.5;
+    let mut samples = Vec::new();
+    for y in 0..n {
+        for x in 0..n {
+            if solid(x, y) {
+                continue;
+            }
+            // Sum the directions away from each solid neighbor, and average
+            // their velocities (grid cells/s, scaled to the fluid's units)
+            let (mut normal, mut wall_velocity, mut walls) = ([0.0f32; 2], [0.0f32; 2], 0.0);
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                if solid(x + dx, y + dy) {
+                    let o = obstacles[((y + dy) * n + x + dx) as usize];
+                    normal = [normal[0] - dx as f32, normal[1] - dy as f32];
+                    wall_velocity = [wall_velocity[0] + o[2] * cell_size[0], wall_velocity[1] + o[3] * cell_size[1]];
+                    walls += 1.0;
+                }
+            }
+            let len = normal[0].hypot(normal[1]);
+            if walls == 0.0 || len == 0.0 {
+                // No solid neighbor, or one on either side
+                continue;
+            }
+            let normal = [normal[0] / len, normal[1] / len];
+            let v = velocity[(y * n + x) as usize];
+            let flow = [v[0] - wall_velocity[0] / walls, v[1] - wall_velocity[1] / walls];
+            let along = flow[0] * normal[1] - flow[1] * normal[0];
+            let distance = 0.5 * (normal[0].abs() * cell_size[0] + normal[1].abs() * cell_size[1]);
+            samples.push(WallSample { cell: [x as u32, y as u32], normal, rate: along.abs() / distance });
+        }
+    }
+    samples
+}
+
+/// Write `samples` to `wall-shear-<time>.csv`: one row per wall cell with
+/// its position (0 to 1, y down), wall normal, shear rate and stress.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_csv(samples: &[WallSample], grid: u32, viscosity: f32, time: f32) {
+    let mut csv = format!("# wall shear at t = {:.3} s, {}² grid, viscosity {} cells²/s\n", time, grid, viscosity);
+    csv.push_str("x,y,normal_x,normal_y,shear_rate,shear_stress\n");
+    for s in samples {
+        let (x, y) = ((s.cell[0] as f32 + 0.5) / grid as f32, (s.cell[1] as f32 + 0.5) / grid as f32);
+        csv.push_str(&format!(
+            "{:.5},{:.5},{:.4},{:.4},{:.4},{:.4}\n",
+            x, y, s.normal[0], s.normal[1], s.rate, s.rate * viscosity,
+        ));
+    }
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let path = format!("wall-shear-{}.csv", secs);
+    match std::fs::write(&path, csv) {
+        Ok(()) => {
+            let peak = samples.iter().map(|s| s.rate).fold(0.0, f32::max);
+            eprintln!("Saved {} ({} wall cells, peak shear rate {:.1}/s)", path, samples.len(), peak);
+        }
+        Err(e) => eprintln!("Failed to save {}: {}", path, e),
+    }
+}
@@ -0,0 +1,85 @@
+//! Per-driver shader workarounds. Some drivers miscompile patterns that are
+//! valid WGSL, such as a kernel that loads and stores the same texel of a
+//! read-write storage texture in one invocation. Each workaround is a
+//! `const WA_*: bool = false;` switch in the WGSL that is flipped to `true`
+//! in the source before it's compiled. One set of shaders carries both the
+//! fast and the safe path, and the constant branch folds the other away.
+
+use std::fmt;
+
+/// Name on the command line and the WGSL constant it flips.
+const FLAGS: [(&str, &str); 1] = [("split-read-write", "WA_SPLIT_READ_WRITE")];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Workarounds {
+    /// Kernels that update a field in place read the texel from a copy in
+    /// the field's scratch texture instead. `Simulation::step` stashes the
+    /// field there first, so no invocation loads and stores the same
+    /// storage texel.
+    pub split_read_write: bool,
+}
+
+impl Workarounds {
+    /// Workarounds for drivers where the fast paths have misbehaved: GL
+    /// (image load/store coherence varies by implementation and ANGLE
+    /// layer) and the Adreno and Mali Vulkan drivers.
+    pub fn detect(info: &wgpu::AdapterInfo) -> Self {
+        const QUALCOMM: u32 = 0x5143;
+        const ARM: u32 = 0x13B5;
+        let split_read_write = match info.backend {
+            wgpu::Backend::Gl => true,
+            wgpu::Backend::Vulkan => matches!(info.vendor, QUALCOMM | ARM),
+            _ => false,
+        };
+        Self { split_read_write }
+    }
+
+    fn enabled(&self) -> [bool; FLAGS.len()] {
+        [self.split_read_write]
+    }
+
+    /// `source` with the enabled workarounds' constants switched on. Shaders
+    /// without a workaround's constant are left as they are.
+    pub fn apply(&self, source: &str) -> String {
+        let mut out = source.to_string();
+        for ((_, constant), on) in FLAGS.iter().zip(self.enabled()) {
+            if on {
+                let off = format!("const {}: bool = false;", constant);
+                out = out.replace(&off, &format!("const {}: bool = true;", constant));
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Workarounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = FLAGS.iter().zip(self.enabled()).filter(|(_, on)| *on).map(|((name, _), _)| *name).collect();
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(","))
+        }
+    }
+}
+
+impl std::str::FromStr for Workarounds {
+    type Err = String;
+
+    /// `none`, `all`, or a comma-separated list of workaround names.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut w = Workarounds::default();
+        for name in s.split(',').map(str::trim) {
+            match name {
+                "none" => {}
+                "all" => w.split_read_write = true,
+                "split-read-write" => w.split_read_write = true,
+                other => {
+                    let known: Vec<_> = FLAGS.iter().map(|(n, _)| *n).collect();
+                    return Err(format!("unknown workaround `{}` (expected auto, none, all or {})", other, known.join(", ")));
+                }
+            }
+        }
+        Ok(w)
+    }
+}
@@ -0,0 +1,145 @@
+// ============================================================
+// Streamline overlay (Z, --streamlines): curves everywhere tangent to the
+// velocity field, traced from a jittered grid of seeds and drawn as lines
+// over the fluid. They show the flow's instantaneous shape the way a CFD
+// post-processor would, where the dye shows its history.
+// ============================================================
+struct StreamParams {
+    grid_size: u32,
+    // Seeds per side of the grid
+    seeds: u32,
+    // Segments traced each way from a seed
+    steps: u32,
+    _pad0: u32,
+    // Physical cell size, see SimParams in fluid.wgsl
+    cell_size: vec2<f32>,
+    // Length of a segment, in x-cells
+    step_len: f32,
+    _pad1: f32,
+    // Part of the domain drawn, (x, y, w, h) in texture coordinates
+    viewport: vec4<f32>,
+}
+
+// Both ends of one segment in grid cells, then the speed there (x-cells/s)
+// and how far along its half of the line it is, 0 at the seed to 1
+struct Segment {
+    ends: vec4<f32>,
+    speed: f32,
+    along: f32,
+    _pad: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: StreamParams;
+@group(0) @binding(1) var velocity: texture_2d<f32>;
+@group(0) @binding(2) var obstacles: texture_2d<f32>;
+@group(0) @binding(3) var<storage, read_write> segments: array<Segment>;
+
+// Below this speed (x-cells/s) the direction is noise, so lines stop
+const STILL: f32 = 0.05;
+
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn rand01(seed: u32) -> f32 {
+    return f32(pcg(seed)) / 4294967295.0;
+}
+
+fn clamp_cell(c: vec2<i32>) -> vec2<i32> {
+    return clamp(c, vec2<i32>(0), vec2<i32>(i32(params.grid_size) - 1));
+}
+
+fn sample_vel(pos: vec2<f32>) -> vec2<f32> {
+    let q = pos - 0.5;
+    let c = vec2<i32>(floor(q));
+    let f = q - floor(q);
+    let a = textureLoad(velocity, clamp_cell(c), 0).xy;
+    let b = textureLoad(velocity, clamp_cell(c + vec2<i32>(1, 0)), 0).xy;
+    let d = textureLoad(velocity, clamp_cell(c + vec2<i32>(0, 1)), 0).xy;
+    let e = textureLoad(velocity, clamp_cell(c + vec2<i32>(1, 1)), 0).xy;
+    return mix(mix(a, b, f.x), mix(d, e, f.x), f.y);
+}
+
+fn inside(pos: vec2<f32>) -> bool {
+    let size = f32(params.grid_size);
+    if (any(pos < vec2<f32>(0.0)) || any(pos >= vec2<f32>(size))) { return false; }
+    return textureLoad(obstacles, vec2<i32>(floor(pos)), 0).x <= 0.5;
+}
+
+// Grid cells moved along the flow's direction for one segment, `sign` 1
+// downstream and -1 upstream; zero where the flow is still
+fn direction(pos: vec2<f32>, sign: f32) -> vec2<f32> {
+    let v = sample_vel(pos);
+    let speed = length(v);
+    if (speed < STILL) { return vec2<f32>(0.0); }
+    return sign * params.step_len * (v / speed) / params.cell_size;
+}
+
+// ============================================================
+// Per seed and direction: trace one half of a line with midpoint steps.
+// Segments past where it stops are parked off the grid.
+// ============================================================
+@compute @workgroup_size(64)
+fn trace(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let total = params.seeds * params.seeds;
+    if (gid.x >= 2u * total) { return; }
+    let seed = gid.x % total;
+    let sign = select(1.0, -1.0, gid.x >= total);
+    let spacing = f32(params.grid_size) / f32(params.seeds);
+    let jitter = vec2<f32>(rand01(seed * 2u), rand01(seed * 2u + 1u));
+    var pos = (vec2<f32>(f32(seed % params.seeds), f32(seed / params.seeds)) + 0.25 + 0.5 * jitter) * spacing;
+    var alive = inside(pos);
+    let first = gid.x * params.steps;
+    for (var k = 0u; k < params.steps; k++) {
+        var seg = Segment(vec4<f32>(-1.0), 0.0, 1.0, vec2<f32>(0.0));
+        if (alive) {
+            let half = direction(pos, sign);
+            let step = direction(pos + 0.5 * half, sign);
+            let next = pos + step;
+            if (all(step == vec2<f32>(0.0)) || !inside(next)) {
+                alive = false;
+            } else {
+                seg = Segment(vec4<f32>(pos, next), length(sample_vel(pos)), f32(k) / f32(params.steps), vec2<f32>(0.0));
+                pos = next;
+            }
+        }
+        segments[first + k] = seg;
+    }
+}
+
+// ============================================================
+// Draw: one line per segment instance, faded toward the line's ends
+// ============================================================
+struct LineOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_line(
+    @builtin(vertex_index) vid: u32,
+    @location(0) ends: vec4<f32>,
+    @location(1) speed: f32,
+    @location(2) along: f32,
+) -> LineOut {
+    let cell = select(ends.xy, ends.zw, vid == 1u);
+    let uv = (cell / f32(params.grid_size) - params.viewport.xy) / params.viewport.zw;
+    var out: LineOut;
+    out.pos = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    // Pale blue in still fluid to white in fast flow
+    let t = speed / (speed + 40.0);
+    let rgb = mix(vec3<f32>(0.35, 0.6, 1.0), vec3<f32>(1.0), t);
+    out.color = vec4<f32>(rgb, 0.85 * (1.0 - along * along));
+    // Parked segments fall outside the clip volume
+    if (ends.x < 0.0) {
+        out.pos = vec4<f32>(2.0, 2.0, 0.0, 1.0);
+    }
+    return out;
+}
+
+@fragment
+fn fs_line(in: LineOut) -> @location(0) vec4<f32> {
+    return in.color;
+}
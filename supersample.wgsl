@@ -0,0 +1,72 @@
+// ============================================================
+// Supersampling (--supersample): the scene is drawn into a target N times
+// the window's size on each side, then averaged down here, N×N texels to
+// a pixel, so dye edges and obstacle outlines stop stair-stepping where a
+// small grid is stretched over a large window
+// ============================================================
+struct DownsampleParams {
+    // Target texels per window pixel, on each side
+    factor: u32,
+    // Dithering mode of the next target, see `dither`: 0 when that's the
+    // tonemapper's HDR one
+    dither: u32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var scene_tex: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> down: DownsampleParams;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    return vec4<f32>(positions[vid], 0.0, 1.0);
+}
+
+// ============================================================
+// Dithering before the window quantizes, as in render.wgsl: `mode` 0 off,
+// 1 an 8-bit target, 2 an sRGB one
+// ============================================================
+// Jimenez's interleaved gradient noise: uniform in 0..1 and spread evenly
+// over neighboring pixels, with little of the clumping white noise has
+fn gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.982919 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+fn srgb_encode(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn dither(color: vec3<f32>, pixel: vec2<f32>, mode: u32) -> vec3<f32> {
+    if (mode == 0u) { return color; }
+    let c = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    // Two samples make the noise triangular, ±1 step: its error no longer
+    // depends on the color, which a single uniform sample's does
+    let p = floor(pixel);
+    let n = (gradient_noise(p) + gradient_noise(p + vec2<f32>(113.0, 71.0)) - 1.0) / 255.0;
+    if (mode == 2u) {
+        return srgb_decode(clamp(srgb_encode(c) + n, vec3<f32>(0.0), vec3<f32>(1.0)));
+    }
+    return clamp(c + n, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_downsample(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    // A box filter over the pixel's own block: exact for a whole factor,
+    // and no texel counts twice
+    let n = i32(down.factor);
+    let origin = vec2<i32>(pos.xy) * n;
+    var sum = vec3<f32>(0.0);
+    for (var y = 0; y < n; y++) {
+        for (var x = 0; x < n; x++) {
+            sum += textureLoad(scene_tex, origin + vec2<i32>(x, y), 0).rgb;
+        }
+    }
+    return vec4<f32>(dither(sum / f32(n * n), pos.xy, down.dither), 1.0);
+}
@@ -0,0 +1,82 @@
+// ============================================================
+// Tonemapping (--tonemap): the fluid is drawn into an Rgba16Float target
+// without clamping, then mapped into the window here, so the dense core
+// rolls off smoothly instead of clipping to flat white
+// ============================================================
+struct ToneParams {
+    // 0 clamp, 1 Reinhard, 2 ACES
+    curve: u32,
+    // Linear scale applied first, 2^stops
+    exposure: f32,
+    // Dithering mode of the window, see `dither`
+    dither: u32,
+    _pad0: f32,
+}
+
+@group(0) @binding(0) var hdr_tex: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> tone: ToneParams;
+
+// Extended Reinhard: white at this value, so it still reaches full
+// brightness where plain c / (1 + c) never would
+const REINHARD_WHITE: f32 = 4.0;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> @builtin(position) vec4<f32> {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    return vec4<f32>(positions[vid], 0.0, 1.0);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(c: vec3<f32>) -> vec3<f32> {
+    return (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14);
+}
+
+fn reinhard(c: vec3<f32>) -> vec3<f32> {
+    return c * (1.0 + c / (REINHARD_WHITE * REINHARD_WHITE)) / (1.0 + c);
+}
+
+// ============================================================
+// Dithering before the window quantizes, as in render.wgsl: `mode` 0 off,
+// 1 an 8-bit target, 2 an sRGB one
+// ============================================================
+// Jimenez's interleaved gradient noise: uniform in 0..1 and spread evenly
+// over neighboring pixels, with little of the clumping white noise has
+fn gradient_noise(p: vec2<f32>) -> f32 {
+    return fract(52.982919 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+fn srgb_encode(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_decode(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+fn dither(color: vec3<f32>, pixel: vec2<f32>, mode: u32) -> vec3<f32> {
+    if (mode == 0u) { return color; }
+    let c = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    // Two samples make the noise triangular, ±1 step: its error no longer
+    // depends on the color, which a single uniform sample's does
+    let p = floor(pixel);
+    let n = (gradient_noise(p) + gradient_noise(p + vec2<f32>(113.0, 71.0)) - 1.0) / 255.0;
+    if (mode == 2u) {
+        return srgb_decode(clamp(srgb_encode(c) + n, vec3<f32>(0.0), vec3<f32>(1.0)));
+    }
+    return clamp(c + n, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_tonemap(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    // Same size as the window, so one texel per pixel
+    let c = max(textureLoad(hdr_tex, vec2<i32>(pos.xy), 0).rgb, vec3<f32>(0.0)) * tone.exposure;
+    var mapped: vec3<f32>;
+    switch tone.curve {
+        case 1u: { mapped = reinhard(c); }
+        case 2u: { mapped = aces(c); }
+        default: { mapped = c; }
+    }
+    return vec4<f32>(dither(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), pos.xy, tone.dither), 1.0);
+}
@@ -0,0 +1,297 @@
+// ============================================================
+// Two-phase mode (--two-phase): two immiscible fluids of different
+// density and viscosity, told apart by the volume fraction of the first
+// one. The fraction is advected with MacCormack and a limiter so the
+// interface stays sharp, and the projection solves the variable-density
+// Poisson equation ∇·(∇p/ρ) = ∇·u, so gravity pulls the heavy fluid
+// under the light one.
+// ============================================================
+struct PhaseParams {
+    size: u32,
+    dt: f32,
+    // Density and kinematic viscosity (cells²/s) of the first fluid
+    // (fraction 1) and the second (fraction 0)
+    density: vec2<f32>,
+    viscosity: vec2<f32>,
+    // Cells/s², +y down
+    gravity: vec2<f32>,
+    // Brush center, and how far it moves per step, in cells
+    mouse_pos: vec2<f32>,
+    mouse_delta: vec2<f32>,
+    radius: f32,
+    mouse_down: u32,
+    // Surface tension coefficient in cells³/s², 0 for none
+    surface_tension: f32,
+    _pad: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: PhaseParams;
+// xy = velocity in cells/s, z = fraction of the first fluid. Holds the
+// state between steps.
+@group(0) @binding(1) var state: texture_storage_2d<rgba16float, read_write>;
+// The state advected, before the projection
+@group(0) @binding(2) var advected: texture_storage_2d<rgba16float, read_write>;
+// x = the advected fraction carried back again, for MacCormack's error
+@group(0) @binding(3) var scratch: texture_storage_2d<rgba16float, read_write>;
+// x = divergence of the advected velocity, y = the step's corrected fraction
+@group(0) @binding(4) var divergence: texture_storage_2d<rgba16float, read_write>;
+// Pressure times dt, kept between steps as the next solve's first guess.
+// 32-bit: the hydrostatic pressure of a deep heavy column is far past what
+// half floats hold with any precision.
+@group(0) @binding(5) var pressure_src: texture_storage_2d<r32float, read_write>;
+@group(0) @binding(6) var pressure_dst: texture_storage_2d<r32float, read_write>;
+
+// ν·dt stays below this, where explicit diffusion is stable
+const MAX_DIFFUSION: f32 = 0.2;
+// σ·dt²/ρ stays below this (in cells³), under the capillary wave limit of
+// an explicit surface tension step
+const MAX_CAPILLARY: f32 = 0.1;
+
+// ============================================================
+// Helpers
+// ============================================================
+fn inside(c: vec2<i32>) -> bool {
+    let n = i32(params.size);
+    return all(c >= vec2<i32>(0)) && all(c < vec2<i32>(n));
+}
+
+fn clamp_cell(c: vec2<i32>) -> vec2<i32> {
+    return clamp(c, vec2<i32>(0), vec2<i32>(i32(params.size) - 1));
+}
+
+fn density_of(fraction: f32) -> f32 {
+    return mix(params.density.y, params.density.x, clamp(fraction, 0.0, 1.0));
+}
+
+// The lowest of the four cells around `p` (in cells, centers at +0.5) and
+// how far `p` is past its center
+struct Bilinear {
+    c00: vec2<i32>,
+    f: vec2<f32>,
+}
+
+fn bilinear(p: vec2<f32>) -> Bilinear {
+    let n = f32(params.size);
+    let q = clamp(p, vec2<f32>(0.5), vec2<f32>(n - 0.5)) - 0.5;
+    let c = vec2<i32>(floor(q));
+    return Bilinear(c, q - floor(q));
+}
+
+fn sample_state(p: vec2<f32>) -> vec4<f32> {
+    let b = bilinear(p);
+    let a = textureLoad(state, clamp_cell(b.c00));
+    let r = textureLoad(state, clamp_cell(b.c00 + vec2<i32>(1, 0)));
+    let d = textureLoad(state, clamp_cell(b.c00 + vec2<i32>(0, 1)));
+    let e = textureLoad(state, clamp_cell(b.c00 + vec2<i32>(1, 1)));
+    return mix(mix(a, r, b.f.x), mix(d, e, b.f.x), b.f.y);
+}
+
+fn sample_advected_fraction(p: vec2<f32>) -> f32 {
+    let b = bilinear(p);
+    let a = textureLoad(advected, clamp_cell(b.c00)).z;
+    let r = textureLoad(advected, clamp_cell(b.c00 + vec2<i32>(1, 0))).z;
+    let d = textureLoad(advected, clamp_cell(b.c00 + vec2<i32>(0, 1))).z;
+    let e = textureLoad(advected, clamp_cell(b.c00 + vec2<i32>(1, 1))).z;
+    return mix(mix(a, r, b.f.x), mix(d, e, b.f.x), b.f.y);
+}
+
+// Smallest and largest fraction of the cells `sample_state` blends at `p`
+fn fraction_bounds(p: vec2<f32>) -> vec2<f32> {
+    let b = bilinear(p);
+    let a = textureLoad(state, clamp_cell(b.c00)).z;
+    let r = textureLoad(state, clamp_cell(b.c00 + vec2<i32>(1, 0))).z;
+    let d = textureLoad(state, clamp_cell(b.c00 + vec2<i32>(0, 1))).z;
+    let e = textureLoad(state, clamp_cell(b.c00 + vec2<i32>(1, 1))).z;
+    return vec2<f32>(min(min(a, r), min(d, e)), max(max(a, r), max(d, e)));
+}
+
+// Velocity past a wall is zero
+fn load_velocity(t: vec2<i32>) -> vec2<f32> {
+    if (!inside(t)) { return vec2<f32>(0.0); }
+    return textureLoad(advected, t).xy;
+}
+
+// 1/ρ on the face between a cell of density `rho` and its neighbor `t`;
+// zero through a wall, so no pressure gradient pushes across it
+fn face_beta(t: vec2<i32>, rho: f32) -> f32 {
+    if (!inside(t)) { return 0.0; }
+    return 2.0 / (rho + density_of(textureLoad(divergence, t).y));
+}
+
+// Central difference gradient of the fraction
+fn fraction_gradient(c: vec2<i32>) -> vec2<f32> {
+    let l = textureLoad(state, clamp_cell(c - vec2<i32>(1, 0))).z;
+    let r = textureLoad(state, clamp_cell(c + vec2<i32>(1, 0))).z;
+    let t = textureLoad(state, clamp_cell(c - vec2<i32>(0, 1))).z;
+    let b = textureLoad(state, clamp_cell(c + vec2<i32>(0, 1))).z;
+    return 0.5 * vec2<f32>(r - l, b - t);
+}
+
+// Unit normal pointing into the first fluid, zero away from the interface
+fn fraction_normal(c: vec2<i32>) -> vec2<f32> {
+    let g = fraction_gradient(c);
+    let len = length(g);
+    return select(vec2<f32>(0.0), g / len, len > 1e-3);
+}
+
+// Surface tension as a continuum force (Brackbill et al.): σκ∇f, with the
+// curvature κ the divergence of the normal pointing out of the first fluid.
+// ∇f is only nonzero across the interface and sums to one over it, so the
+// force is the pressure jump σκ spread over the interface's few cells.
+fn surface_tension_force(c: vec2<i32>) -> vec2<f32> {
+    let g = fraction_gradient(c);
+    if (params.surface_tension <= 0.0 || dot(g, g) < 1e-6) { return vec2<f32>(0.0); }
+    let kappa = -0.5 * (fraction_normal(c + vec2<i32>(1, 0)).x - fraction_normal(c - vec2<i32>(1, 0)).x
+        + fraction_normal(c + vec2<i32>(0, 1)).y - fraction_normal(c - vec2<i32>(0, 1)).y);
+    // Tighter than a cell's radius is grid noise, not curvature
+    let sigma = min(params.surface_tension, MAX_CAPILLARY * min(params.density.x, params.density.y) / (params.dt * params.dt));
+    return sigma * clamp(kappa, -1.0, 1.0) * g;
+}
+
+// ============================================================
+// Advection, forces and viscosity: state -> advected
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn advect(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let here = textureLoad(state, c);
+    let back = vec2<f32>(c) + 0.5 - params.dt * here.xy;
+    let s = sample_state(back);
+
+    // Each fluid keeps its own viscosity; the mixture's is mass weighted
+    let rho = density_of(here.z);
+    let mu = mix(params.density.y * params.viscosity.y, params.density.x * params.viscosity.x, clamp(here.z, 0.0, 1.0));
+    let nu = min(mu / rho, MAX_DIFFUSION / params.dt);
+    let lap = textureLoad(state, clamp_cell(c - vec2<i32>(1, 0))).xy + textureLoad(state, clamp_cell(c + vec2<i32>(1, 0))).xy
+        + textureLoad(state, clamp_cell(c - vec2<i32>(0, 1))).xy + textureLoad(state, clamp_cell(c + vec2<i32>(0, 1))).xy
+        - 4.0 * here.xy;
+
+    // Gravity acts on both fluids alike; the projection's 1/ρ turns the
+    // density difference into buoyancy
+    var v = s.xy + params.dt * (nu * lap + params.gravity);
+    v += params.dt * surface_tension_force(c) / rho;
+    if (params.mouse_down != 0u) {
+        let d = vec2<f32>(c) + 0.5 - params.mouse_pos;
+        let w = exp(-dot(d, d) / (params.radius * params.radius));
+        v = mix(v, params.mouse_delta / params.dt, w);
+    }
+    textureStore(advected, c, vec4<f32>(v, s.z, 0.0));
+}
+
+// The advected fraction carried forward again, along the same velocity
+@compute @workgroup_size(8, 8)
+fn reverse(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let ahead = vec2<f32>(c) + 0.5 + params.dt * textureLoad(state, c).xy;
+    textureStore(scratch, c, vec4<f32>(sample_advected_fraction(ahead), 0.0, 0.0, 0.0));
+}
+
+// Divergence of the advected velocity, and MacCormack's correction of the
+// fraction: half the round trip's error added back, clamped to the cells
+// the backtrace blended so it can't overshoot
+@compute @workgroup_size(8, 8)
+fn compute_divergence(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let l = load_velocity(c - vec2<i32>(1, 0)).x;
+    let r = load_velocity(c + vec2<i32>(1, 0)).x;
+    let t = load_velocity(c - vec2<i32>(0, 1)).y;
+    let b = load_velocity(c + vec2<i32>(0, 1)).y;
+    let div = 0.5 * (r - l + b - t);
+
+    let here = textureLoad(state, c);
+    let advected_fraction = textureLoad(advected, c).z;
+    let corrected = advected_fraction + 0.5 * (here.z - textureLoad(scratch, c).x);
+    let bounds = fraction_bounds(vec2<f32>(c) + 0.5 - params.dt * here.xy);
+    textureStore(divergence, c, vec4<f32>(div, clamp(corrected, bounds.x, bounds.y), 0.0, 0.0));
+}
+
+// ============================================================
+// Projection
+// ============================================================
+// One Jacobi sweep of ∇·(β∇p) = ∇·u with β = 1/ρ on the faces
+@compute @workgroup_size(8, 8)
+fn jacobi(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let rho = density_of(textureLoad(divergence, c).y);
+    let w = c - vec2<i32>(1, 0);
+    let e = c + vec2<i32>(1, 0);
+    let n = c - vec2<i32>(0, 1);
+    let s = c + vec2<i32>(0, 1);
+    let bw = face_beta(w, rho);
+    let be = face_beta(e, rho);
+    let bn = face_beta(n, rho);
+    let bs = face_beta(s, rho);
+    let sum = bw * textureLoad(pressure_src, clamp_cell(w)).x + be * textureLoad(pressure_src, clamp_cell(e)).x
+        + bn * textureLoad(pressure_src, clamp_cell(n)).x + bs * textureLoad(pressure_src, clamp_cell(s)).x;
+    let weight = bw + be + bn + bs;
+    let p = (sum - textureLoad(divergence, c).x) / weight;
+    textureStore(pressure_dst, c, vec4<f32>(p, 0.0, 0.0, 0.0));
+}
+
+// Subtract β∇p from the advected velocity and store the new state
+@compute @workgroup_size(8, 8)
+fn project(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let fraction = textureLoad(divergence, c).y;
+    let rho = density_of(fraction);
+    let p = textureLoad(pressure_src, c).x;
+    // Gradient on each face, times that face's 1/ρ, averaged to the center
+    let w = c - vec2<i32>(1, 0);
+    let e = c + vec2<i32>(1, 0);
+    let n = c - vec2<i32>(0, 1);
+    let s = c + vec2<i32>(0, 1);
+    let gx = 0.5 * (face_beta(e, rho) * (textureLoad(pressure_src, clamp_cell(e)).x - p)
+        + face_beta(w, rho) * (p - textureLoad(pressure_src, clamp_cell(w)).x));
+    let gy = 0.5 * (face_beta(s, rho) * (textureLoad(pressure_src, clamp_cell(s)).x - p)
+        + face_beta(n, rho) * (p - textureLoad(pressure_src, clamp_cell(n)).x));
+    var v = textureLoad(advected, c).xy - vec2<f32>(gx, gy);
+    // Free slip: nothing flows through the walls, but along them it may
+    let last = i32(params.size) - 1;
+    if (c.x == 0 || c.x == last) { v.x = 0.0; }
+    if (c.y == 0 || c.y == last) { v.y = 0.0; }
+    textureStore(state, c, vec4<f32>(v, fraction, 0.0));
+}
+
+// ============================================================
+// Render: the first fluid blue, the second amber, the interface dark
+// ============================================================
+// Numbered after the compute bindings, so both fit one module
+@group(0) @binding(7) var state_tex: texture_2d<f32>;
+@group(0) @binding(8) var state_sampler: sampler;
+
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var uvs = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 1.0), vec2<f32>(2.0, 1.0), vec2<f32>(0.0, -1.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.uv = uvs[vid];
+    return out;
+}
+
+@fragment
+fn fs_two_phase(in: VSOut) -> @location(0) vec4<f32> {
+    let s = textureSampleLevel(state_tex, state_sampler, in.uv, 0.0);
+    let f = clamp(s.z, 0.0, 1.0);
+    let first = vec3<f32>(0.08, 0.32, 0.62);
+    let second = vec3<f32>(0.92, 0.62, 0.16);
+    // Faster fluid a little brighter, so the stirring shows inside a phase
+    let glow = 1.0 + 0.15 * clamp(length(s.xy) / 100.0, 0.0, 1.0);
+    let edge = 4.0 * f * (1.0 - f);
+    let color = mix(second, first, smoothstep(0.3, 0.7, f)) * glow * (1.0 - 0.6 * edge);
+    return vec4<f32>(color, 1.0);
+}
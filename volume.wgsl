@@ -0,0 +1,294 @@
+// ============================================================
+// 3D mode (--3d): a cube of cells stepped with the same stable-fluids
+// scheme as fluid.wgsl, and ray-marched to the screen.
+// ============================================================
+struct VolumeParams {
+    size: u32,
+    dt: f32,
+    // Brush radius in cells and dye added per second at its center
+    radius: f32,
+    strength: f32,
+    // Brush center and its movement this frame, in cells (y up)
+    mouse_pos: vec3<f32>,
+    mouse_down: u32,
+    mouse_delta: vec3<f32>,
+    // Per-step multipliers, from `Decay::factor(dt)`
+    velocity_decay: f32,
+    dye_color: vec3<f32>,
+    dye_decay: f32,
+    // Camera, in texture coordinates (the cube is [0, 1]³)
+    eye: vec3<f32>,
+    aspect: f32,
+    right: vec3<f32>,
+    // Upward acceleration in cells/s² per unit of dye
+    lift: f32,
+    up: vec3<f32>,
+    // Nonzero while the plume at the bottom is on
+    plume: u32,
+    forward: vec3<f32>,
+    time: f32,
+    // 0 = one view, 1 = red-cyan anaglyph, 2 = side by side; and the
+    // distance between the eyes, in cube widths
+    stereo: u32,
+    eye_separation: f32,
+    // Ray march samples per cube width, and nonzero while the dye is lit
+    steps: u32,
+    light: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: VolumeParams;
+// The field a pass updates, and what it reads besides
+@group(0) @binding(1) var src: texture_3d<f32>;
+@group(0) @binding(2) var vel: texture_3d<f32>;
+@group(0) @binding(3) var aux: texture_3d<f32>;
+@group(0) @binding(4) var dst: texture_storage_3d<rgba16float, write>;
+@group(0) @binding(5) var trilinear: sampler;
+
+// ============================================================
+// Helpers
+// ============================================================
+fn outside(c: vec3<i32>) -> bool {
+    let n = i32(params.size);
+    return any(c < vec3<i32>(0)) || any(c >= vec3<i32>(n));
+}
+
+// Edge cells repeat outward, for pressure (zero normal gradient at walls)
+fn load_clamped(t: texture_3d<f32>, c: vec3<i32>) -> vec4<f32> {
+    return textureLoad(t, clamp(c, vec3<i32>(0), vec3<i32>(i32(params.size) - 1)), 0);
+}
+
+// Nothing flows through the walls, so velocity outside them is zero
+fn load_velocity(c: vec3<i32>) -> vec4<f32> {
+    if (outside(c)) { return vec4<f32>(0.0); }
+    return textureLoad(src, c, 0);
+}
+
+fn brush(c: vec3<f32>) -> f32 {
+    if (params.mouse_down == 0u) { return 0.0; }
+    let d = c - params.mouse_pos;
+    return exp(-dot(d, d) / (params.radius * params.radius));
+}
+
+// A round jet of warm dye at the middle of the floor
+fn plume(c: vec3<f32>) -> f32 {
+    if (params.plume == 0u) { return 0.0; }
+    let n = f32(params.size);
+    let r = n * 0.08;
+    let d = vec2<f32>(c.x - n * 0.5, c.z - n * 0.5);
+    if (c.y > 3.0) { return 0.0; }
+    return exp(-dot(d, d) / (r * r));
+}
+
+const PLUME_SPEED: f32 = 30.0;
+
+// ============================================================
+// Forces and sources
+// ============================================================
+@compute @workgroup_size(4, 4, 4)
+fn splat_velocity(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= vec3<u32>(params.size))) { return; }
+    let c = vec3<i32>(id);
+    let p = vec3<f32>(id) + 0.5;
+    var v = textureLoad(src, c, 0).xyz;
+    // Dragged along at the stroke's speed, like the stirring rod
+    v = mix(v, params.mouse_delta / params.dt, brush(p));
+    let jet = plume(p);
+    v.y = mix(v.y, max(v.y, PLUME_SPEED), jet);
+    // Dye is warm: it rises
+    v.y += params.lift * textureLoad(aux, c, 0).w * params.dt;
+    textureStore(dst, c, vec4<f32>(v * params.velocity_decay, 0.0));
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn splat_dye(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= vec3<u32>(params.size))) { return; }
+    let c = vec3<i32>(id);
+    let p = vec3<f32>(id) + 0.5;
+    // rgb is color times amount, w the amount, so overlapping dyes mix
+    var d = textureLoad(src, c, 0);
+    d += vec4<f32>(params.dye_color, 1.0) * brush(p) * params.strength * params.dt;
+    let hue = vec3<f32>(1.0, 0.55, 0.25);
+    d += vec4<f32>(hue, 1.0) * plume(p) * params.strength * params.dt;
+    textureStore(dst, c, d * params.dye_decay);
+}
+
+// ============================================================
+// Advection (semi-Lagrangian, trilinear)
+// ============================================================
+@compute @workgroup_size(4, 4, 4)
+fn advect(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= vec3<u32>(params.size))) { return; }
+    let c = vec3<i32>(id);
+    let back = vec3<f32>(id) + 0.5 - params.dt * textureLoad(vel, c, 0).xyz;
+    let n = f32(params.size);
+    let p = clamp(back, vec3<f32>(0.5), vec3<f32>(n - 0.5));
+    textureStore(dst, c, textureSampleLevel(src, trilinear, p / n, 0.0));
+}
+
+// ============================================================
+// Projection
+// ============================================================
+@compute @workgroup_size(4, 4, 4)
+fn divergence(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= vec3<u32>(params.size))) { return; }
+    let c = vec3<i32>(id);
+    let l = load_velocity(c - vec3<i32>(1, 0, 0)).x;
+    let r = load_velocity(c + vec3<i32>(1, 0, 0)).x;
+    let b = load_velocity(c - vec3<i32>(0, 1, 0)).y;
+    let t = load_velocity(c + vec3<i32>(0, 1, 0)).y;
+    let k = load_velocity(c - vec3<i32>(0, 0, 1)).z;
+    let f = load_velocity(c + vec3<i32>(0, 0, 1)).z;
+    textureStore(dst, c, vec4<f32>(0.5 * (r - l + t - b + f - k), 0.0, 0.0, 0.0));
+}
+
+// One Jacobi iteration on the 7-point Laplacian; src is the pressure
+// guess, aux the divergence
+@compute @workgroup_size(4, 4, 4)
+fn jacobi(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= vec3<u32>(params.size))) { return; }
+    let c = vec3<i32>(id);
+    let sum = load_clamped(src, c - vec3<i32>(1, 0, 0)).x + load_clamped(src, c + vec3<i32>(1, 0, 0)).x
+        + load_clamped(src, c - vec3<i32>(0, 1, 0)).x + load_clamped(src, c + vec3<i32>(0, 1, 0)).x
+        + load_clamped(src, c - vec3<i32>(0, 0, 1)).x + load_clamped(src, c + vec3<i32>(0, 0, 1)).x;
+    let p = (sum - textureLoad(aux, c, 0).x) / 6.0;
+    textureStore(dst, c, vec4<f32>(p, 0.0, 0.0, 0.0));
+}
+
+// src is the velocity, aux the pressure
+@compute @workgroup_size(4, 4, 4)
+fn subtract_gradient(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= vec3<u32>(params.size))) { return; }
+    let c = vec3<i32>(id);
+    let grad = 0.5 * vec3<f32>(
+        load_clamped(aux, c + vec3<i32>(1, 0, 0)).x - load_clamped(aux, c - vec3<i32>(1, 0, 0)).x,
+        load_clamped(aux, c + vec3<i32>(0, 1, 0)).x - load_clamped(aux, c - vec3<i32>(0, 1, 0)).x,
+        load_clamped(aux, c + vec3<i32>(0, 0, 1)).x - load_clamped(aux, c - vec3<i32>(0, 0, 1)).x,
+    );
+    var v = textureLoad(src, c, 0).xyz - grad;
+    // No flow through the walls
+    let last = vec3<u32>(params.size - 1u);
+    v = select(v, vec3<f32>(0.0), id == vec3<u32>(0u) | id == last);
+    textureStore(dst, c, vec4<f32>(v, 0.0));
+}
+
+// ============================================================
+// Volume renderer: march each pixel's ray through the cube,
+// compositing dye front to back
+// ============================================================
+struct VSOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vid: u32) -> VSOut {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(3.0, -1.0), vec2<f32>(-1.0, 3.0)
+    );
+    var out: VSOut;
+    out.pos = vec4<f32>(positions[vid], 0.0, 1.0);
+    out.ndc = positions[vid];
+    return out;
+}
+
+// Tangent of half the vertical field of view; must match `TAN_HALF_FOV`
+// in src/volume.rs
+const TAN_HALF_FOV: f32 = 0.5;
+// Opacity per cell crossed, per unit of dye
+const ABSORPTION: f32 = 0.6;
+// Toward the light, from above and to one side (y up)
+const LIGHT_DIR: vec3<f32> = vec3<f32>(0.35, 0.85, 0.4);
+// Samples of the shadow ray toward the light, and cells between them
+const SHADOW_STEPS: i32 = 8;
+const SHADOW_STEP_CELLS: f32 = 2.0;
+// Light every sample gets, shadowed or not
+const AMBIENT: f32 = 0.3;
+
+fn inside_cube(p: vec3<f32>) -> bool {
+    return all(p >= vec3<f32>(0.0)) && all(p <= vec3<f32>(1.0));
+}
+
+// Fraction of the light that reaches `p` through the dye between it and
+// the light; the walls don't shadow
+fn light_reaching(p: vec3<f32>) -> f32 {
+    let n = f32(params.size);
+    let step_len = SHADOW_STEP_CELLS / n;
+    let dir = normalize(LIGHT_DIR);
+    var depth = 0.0;
+    for (var i = 1; i <= SHADOW_STEPS; i++) {
+        let q = p + dir * step_len * f32(i);
+        if (!inside_cube(q)) { break; }
+        depth += max(textureSampleLevel(src, trilinear, q, 0.0).w, 0.0);
+    }
+    return exp(-depth * ABSORPTION * SHADOW_STEP_CELLS);
+}
+
+// Composite the dye along the ray from `eye` in direction `dir` over
+// `background`, `params.steps` samples per cube width. Opacity follows the
+// cells each sample stands for, so the step count changes only how fine
+// the march is, not how dense the dye looks.
+fn march(eye: vec3<f32>, dir: vec3<f32>, background: vec3<f32>) -> vec3<f32> {
+    // Slab test against the unit cube
+    let inv = 1.0 / dir;
+    let t0 = (vec3<f32>(0.0) - eye) * inv;
+    let t1 = (vec3<f32>(1.0) - eye) * inv;
+    let near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), max(min(t0.z, t1.z), 0.0));
+    let far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), max(t0.z, t1.z));
+    if (near >= far) { return background; }
+
+    let step_len = 1.0 / f32(max(params.steps, 1u));
+    let cells = step_len * f32(params.size);
+    var color = vec3<f32>(0.0);
+    var alpha = 0.0;
+    var t = near + step_len * 0.5;
+    loop {
+        if (t >= far || alpha > 0.99) { break; }
+        let p = eye + dir * t;
+        let d = textureSampleLevel(src, trilinear, p, 0.0);
+        let a = 1.0 - exp(-max(d.w, 0.0) * ABSORPTION * cells);
+        if (a > 1e-3) {
+            var hue = d.rgb / max(d.w, 1e-3);
+            if (params.light != 0u) {
+                hue *= AMBIENT + (1.0 - AMBIENT) * light_reaching(p);
+            }
+            color += (1.0 - alpha) * a * hue;
+            alpha += (1.0 - alpha) * a;
+        }
+        t += step_len;
+    }
+    return color + (1.0 - alpha) * background;
+}
+
+// One eye's view of the pixel at `ndc`: `side` is -1 for the left eye,
+// 1 for the right and 0 for the mono camera. The eyes sit apart along
+// `right` with parallel axes and aim their rays at the same point on the
+// plane through the cube's center, so that plane shows no parallax and
+// nearer dye pops out of the screen.
+fn eye_view(ndc: vec2<f32>, side: f32) -> vec3<f32> {
+    let background = mix(vec3<f32>(0.02, 0.02, 0.03), vec3<f32>(0.08, 0.09, 0.12), ndc.y * 0.5 + 0.5);
+    let focus = dot(vec3<f32>(0.5) - params.eye, params.forward);
+    let aim = params.eye + focus * (params.forward
+        + params.right * ndc.x * params.aspect * TAN_HALF_FOV
+        + params.up * ndc.y * TAN_HALF_FOV);
+    let eye = params.eye + params.right * side * params.eye_separation * 0.5;
+    return march(eye, normalize(aim - eye), background);
+}
+
+@fragment
+fn fs_volume(in: VSOut) -> @location(0) vec4<f32> {
+    if (params.stereo == 1u) {
+        let left = eye_view(in.ndc, -1.0);
+        let right = eye_view(in.ndc, 1.0);
+        // Half-color anaglyph: gray on the red side keeps the two eyes'
+        // brightness close, so colored dye doesn't shimmer
+        let gray = dot(left, vec3<f32>(0.299, 0.587, 0.114));
+        return vec4<f32>(gray, right.g, right.b, 1.0);
+    }
+    if (params.stereo == 2u) {
+        // Each half spans the whole view, squeezed horizontally
+        let side = select(-1.0, 1.0, in.ndc.x >= 0.0);
+        let ndc = vec2<f32>(2.0 * in.ndc.x - side, in.ndc.y);
+        return vec4<f32>(eye_view(ndc, side), 1.0);
+    }
+    return vec4<f32>(eye_view(in.ndc, 0.0), 1.0);
+}
@@ -0,0 +1,167 @@
+// ============================================================
+// Vortex particles (--vortex-particles): Lagrangian blobs of vorticity
+// seeded where the grid's flow is swirling. They ride the flow and keep
+// the vorticity they were born with, which the grid's advection and
+// diffusion smear away. Each step a particle compares its own vorticity
+// with the grid's under it and splats the velocity of a small Gaussian
+// vortex making up part of the difference. The splat is purely azimuthal,
+// so it adds no divergence for the projection to remove.
+// ============================================================
+struct VortexParams {
+    grid_size: u32,
+    count: u32,
+    dt: f32,
+    // Longest a particle lives, in seconds; each gets between half and all
+    // of it, so they don't all die on the same frame
+    life: f32,
+    // Physical cell size, see SimParams in fluid.wgsl
+    cell_size: vec2<f32>,
+    frame: u32,
+    _pad: u32,
+}
+
+struct VortexParticle {
+    // Position in grid cells
+    pos: vec2<f32>,
+    // Vorticity the particle carries, in 1/s
+    vorticity: f32,
+    // Seconds left; 0 or less is a free slot
+    life: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: VortexParams;
+@group(0) @binding(1) var velocity: texture_storage_2d<rgba16float, read_write>;
+// x = curl, from the fluid's last compute_curl
+@group(0) @binding(2) var curl: texture_2d<f32>;
+@group(0) @binding(3) var obstacles: texture_2d<f32>;
+@group(0) @binding(4) var<storage, read_write> particles: array<VortexParticle>;
+// Per cell: x and y of the splatted velocity, in FIXED units
+@group(0) @binding(5) var<storage, read_write> splat: array<atomic<i32>>;
+
+// Core radius of a particle's vortex, in x-cells, and how far out its
+// velocity reaches before fading to zero
+const CORE: f32 = 3.0;
+const REACH: f32 = 9.0;
+// Weakest |curl| (1/s) a particle is seeded at
+const SEED_CURL: f32 = 4.0;
+// Share of the vorticity deficit made up per second. Several particles
+// share a vortex, so this stays well below 1/dt to keep them from
+// overshooting together.
+const REINJECT_RATE: f32 = 2.0;
+// Fixed-point scale for the atomic sums
+const FIXED: f32 = 65536.0;
+
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn rand01(seed: u32) -> f32 {
+    return f32(pcg(seed)) / 4294967295.0;
+}
+
+fn clamp_cell(c: vec2<i32>) -> vec2<i32> {
+    return clamp(c, vec2<i32>(0), vec2<i32>(i32(params.grid_size) - 1));
+}
+
+fn is_solid(pos: vec2<f32>) -> bool {
+    return textureLoad(obstacles, clamp_cell(vec2<i32>(floor(pos))), 0).x > 0.5;
+}
+
+// Bilinear lookups at a position in grid cells
+fn sample_vel(pos: vec2<f32>) -> vec2<f32> {
+    let q = pos - 0.5;
+    let c = vec2<i32>(floor(q));
+    let f = q - floor(q);
+    let a = textureLoad(velocity, clamp_cell(c)).xy;
+    let b = textureLoad(velocity, clamp_cell(c + vec2<i32>(1, 0))).xy;
+    let d = textureLoad(velocity, clamp_cell(c + vec2<i32>(0, 1))).xy;
+    let e = textureLoad(velocity, clamp_cell(c + vec2<i32>(1, 1))).xy;
+    return mix(mix(a, b, f.x), mix(d, e, f.x), f.y);
+}
+
+fn sample_curl(pos: vec2<f32>) -> f32 {
+    let q = pos - 0.5;
+    let c = vec2<i32>(floor(q));
+    let f = q - floor(q);
+    let a = textureLoad(curl, clamp_cell(c), 0).x;
+    let b = textureLoad(curl, clamp_cell(c + vec2<i32>(1, 0)), 0).x;
+    let d = textureLoad(curl, clamp_cell(c + vec2<i32>(0, 1)), 0).x;
+    let e = textureLoad(curl, clamp_cell(c + vec2<i32>(1, 1)), 0).x;
+    return mix(mix(a, b, f.x), mix(d, e, f.x), f.y);
+}
+
+// ============================================================
+// Per particle: move or seed, then splat the vortex making up the deficit
+// ============================================================
+@compute @workgroup_size(64)
+fn update(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid.x;
+    if (idx >= params.count) { return; }
+    let size = f32(params.grid_size);
+    var p = particles[idx];
+
+    if (p.life > 0.0) {
+        // Midpoint step, so particles circle a vortex instead of spiraling out
+        let mid = p.pos + 0.5 * params.dt * sample_vel(p.pos) / params.cell_size;
+        p.pos += params.dt * sample_vel(mid) / params.cell_size;
+        p.life -= params.dt;
+        if (any(p.pos < vec2<f32>(0.0)) || any(p.pos >= vec2<f32>(size)) || is_solid(p.pos)) {
+            p.life = 0.0;
+        }
+    } else {
+        // A free slot tries one random cell per frame, so particles gather
+        // where the flow swirls and leave once it calms
+        let seed = pcg(idx * 9781u + params.frame * 6271u);
+        let pos = vec2<f32>(rand01(seed), rand01(seed + 1u)) * size;
+        let w = sample_curl(pos);
+        if (abs(w) >= SEED_CURL && !is_solid(pos)) {
+            p = VortexParticle(pos, w, params.life * (0.5 + 0.5 * rand01(seed + 2u)));
+        }
+    }
+    particles[idx] = p;
+    if (p.life <= 0.0) { return; }
+
+    // Lamb–Oseen vortex of core vorticity `deficit`: u_θ = ω σ² / 2r ·
+    // (1 − exp(−r²/σ²)), faded out toward REACH. Any u_θ(r) is divergence
+    // free.
+    let deficit = (p.vorticity - sample_curl(p.pos)) * min(REINJECT_RATE * params.dt, 1.0);
+    let h = params.cell_size;
+    let extent = vec2<i32>(ceil(REACH / h));
+    let center = vec2<i32>(floor(p.pos));
+    for (var dy = -extent.y; dy <= extent.y; dy++) {
+        for (var dx = -extent.x; dx <= extent.x; dx++) {
+            let c = center + vec2<i32>(dx, dy);
+            if (any(c < vec2<i32>(0)) || any(c >= vec2<i32>(i32(params.grid_size)))) { continue; }
+            let r = (vec2<f32>(c) + 0.5 - p.pos) * h;
+            let r2 = dot(r, r);
+            if (r2 >= REACH * REACH) { continue; }
+            var k = 0.5 * deficit;
+            if (r2 > 1e-6) {
+                k = deficit * CORE * CORE / (2.0 * r2) * (1.0 - exp(-r2 / (CORE * CORE)));
+            }
+            k *= 1.0 - smoothstep(0.6 * REACH, REACH, sqrt(r2));
+            let u = k * vec2<f32>(-r.y, r.x);
+            let i = 2u * (u32(c.y) * params.grid_size + u32(c.x));
+            atomicAdd(&splat[i], i32(round(u.x * FIXED)));
+            atomicAdd(&splat[i + 1u], i32(round(u.y * FIXED)));
+        }
+    }
+}
+
+// ============================================================
+// Per cell: add the splatted velocity and clear the sums
+// ============================================================
+@compute @workgroup_size(8, 8)
+fn apply(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (any(gid.xy >= vec2<u32>(params.grid_size))) { return; }
+    let c = vec2<i32>(gid.xy);
+    let i = 2u * (gid.y * params.grid_size + gid.x);
+    let du = vec2<f32>(f32(atomicLoad(&splat[i])), f32(atomicLoad(&splat[i + 1u]))) / FIXED;
+    atomicStore(&splat[i], 0);
+    atomicStore(&splat[i + 1u], 0);
+    if (textureLoad(obstacles, c, 0).x > 0.5) { return; }
+    let v = textureLoad(velocity, c).xy;
+    textureStore(velocity, c, vec4<f32>(v + du, 0.0, 0.0));
+}